@@ -1,6 +1,6 @@
 use pyo3::prelude::*;
 
-/// Setup python modules for `libafl_qemu` and `libafl_sugar`.
+/// Setup python modules for `libafl`, `libafl_qemu` and `libafl_sugar`.
 ///
 /// # Errors
 /// Returns error if python libafl setup failed.
@@ -29,5 +29,13 @@ pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_submodule(&bolts_module)?;
     modules.set_item("pylibafl.libafl_bolts", bolts_module)?;
 
+    #[cfg(unix)]
+    {
+        let libafl_module = PyModule::new(m.py(), "libafl")?;
+        libafl::pybind::python_module(&libafl_module)?;
+        m.add_submodule(&libafl_module)?;
+        modules.set_item("pylibafl.libafl", libafl_module)?;
+    }
+
     Ok(())
 }
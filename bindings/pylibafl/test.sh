@@ -1,5 +1,7 @@
 #!/usr/bin/env bash
 
+python3 ./test_libafl.py || exit $?
+
 mkdir in || true
 echo "a" > ./in/a
 
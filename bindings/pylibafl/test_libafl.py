@@ -0,0 +1,101 @@
+import os
+import sys
+import tempfile
+import threading
+import unittest
+
+from pylibafl import libafl
+
+# A stand-in for an AFL++-instrumented target: it attaches to the coverage map in `__AFL_SHM_ID`
+# and marks an entry for the first byte of the input file.
+TARGET = """
+import ctypes, os, sys
+
+libc = ctypes.CDLL(None)
+libc.shmat.restype = ctypes.c_void_p
+libc.shmat.argtypes = [ctypes.c_int, ctypes.c_void_p, ctypes.c_int]
+addr = libc.shmat(int(os.environ["__AFL_SHM_ID"]), None, 0)
+coverage = (ctypes.c_ubyte * 64).from_address(addr)
+with open(sys.argv[1], "rb") as f:
+    data = f.read()
+coverage[0] = 1
+if data:
+    coverage[1 + data[0] % 63] = 1
+"""
+
+
+class TestComponents(unittest.TestCase):
+    def test_weighted_scheduler_schedules(self):
+        for schedule in ["explore", "exploit", "fast", "coe", "lin", "quad"]:
+            libafl.WeightedScheduler(schedule)
+        with self.assertRaises(ValueError):
+            libafl.WeightedScheduler("nonexistent")
+
+    def test_llmp_event_manager_remote_broker_addr(self):
+        libafl.LlmpEventManager(cores=[0], remote_broker_addr="127.0.0.1:1337")
+        with self.assertRaises(ValueError):
+            libafl.LlmpEventManager(cores=[0], remote_broker_addr="not an address")
+
+    def test_fuzzer_rejects_unknown_components(self):
+        with self.assertRaises(TypeError):
+            libafl.Fuzzer(
+                executor=libafl.MaxMapFeedback(),
+                feedback=libafl.MaxMapFeedback(),
+                objectives=[],
+                scheduler=libafl.QueueScheduler(),
+                event_manager=libafl.SimpleEventManager(),
+                output_dir="./out",
+            )
+
+
+class TestFuzzer(unittest.TestCase):
+    def test_command_fuzzer(self):
+        with tempfile.TemporaryDirectory() as workdir:
+            target = os.path.join(workdir, "target.py")
+            with open(target, "w") as f:
+                f.write(TARGET)
+            output_dir = os.path.join(workdir, "out")
+
+            monitor_lines = []
+            fuzzer = libafl.Fuzzer(
+                executor=libafl.CommandExecutor(
+                    sys.executable, [target, "@@"], timeout_ms=5000
+                ),
+                feedback=libafl.MaxMapFeedback(),
+                objectives=[libafl.CrashFeedback(), libafl.TimeoutFeedback()],
+                scheduler=libafl.MinimizerScheduler(libafl.QueueScheduler()),
+                event_manager=libafl.SimpleEventManager(),
+                output_dir=output_dir,
+                seed=1337,
+                monitor=monitor_lines.append,
+            )
+
+            # Python threads keep running while the fuzzer runs
+            ticks = [0]
+            stop = threading.Event()
+
+            def tick():
+                while not stop.is_set():
+                    ticks[0] += 1
+                    stop.wait(0.001)
+
+            ticker = threading.Thread(target=tick)
+            ticker.start()
+            cwd = os.getcwd()
+            os.chdir(workdir)
+            try:
+                ticks_before = ticks[0]
+                fuzzer.run(iterations=2)
+                ticks_during = ticks[0] - ticks_before
+            finally:
+                os.chdir(cwd)
+                stop.set()
+                ticker.join()
+
+            self.assertGreater(ticks_during, 0)
+            self.assertTrue(monitor_lines)
+            self.assertTrue(os.listdir(os.path.join(output_dir, "queue")))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -96,20 +96,11 @@ impl Client<'_> {
         let injection_module = None;
 
         #[cfg(feature = "injections")]
-        let injection_module = self
-            .options
-            .injections
-            .as_ref()
-            .and_then(|injections_file| {
-                let lower = injections_file.to_lowercase();
-                if lower.ends_with("yaml") || lower.ends_with("yml") {
-                    Some(InjectionModule::from_yaml(injections_file).unwrap())
-                } else if lower.ends_with("toml") {
-                    Some(InjectionModule::from_toml(injections_file).unwrap())
-                } else {
-                    None
-                }
-            });
+        let injection_module = if self.options.injections.is_empty() {
+            None
+        } else {
+            Some(InjectionModule::from_files(&self.options.injections)?)
+        };
 
         let harness = Harness::init(qemu).expect("Error setting up harness.");
 
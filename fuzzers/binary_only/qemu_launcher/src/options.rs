@@ -32,9 +32,9 @@ pub struct FuzzerOptions {
     #[arg(
         short = 'j',
         long,
-        help = "Injections TOML or YAML file definition. Filename must end in .toml or .yaml/.yml."
+        help = "Injections TOML or YAML file definition. Filename must end in .toml or .yaml/.yml. Can be given several times to add custom definitions."
     )]
-    pub injections: Option<String>,
+    pub injections: Vec<String>,
 
     #[arg(long, help = "Log file")]
     pub log: Option<String>,
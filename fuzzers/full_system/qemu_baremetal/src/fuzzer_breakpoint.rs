@@ -28,13 +28,8 @@ use libafl_bolts::{
     tuples::tuple_list,
 };
 use libafl_qemu::{
-    breakpoint::Breakpoint,
-    command::{EndCommand, StartCommand},
-    elf::EasyElf,
-    emu::Emulator,
-    executor::QemuExecutor,
-    modules::edges::StdEdgeCoverageModule,
-    GuestPhysAddr, GuestReg, QemuMemoryChunk,
+    breakpoint::BreakpointHarness, elf::EasyElf, emu::Emulator, executor::QemuExecutor,
+    modules::edges::StdEdgeCoverageModule, GuestPhysAddr, GuestReg, QemuMemoryChunk,
 };
 use libafl_targets::{edges_map_mut_ptr, EDGES_MAP_DEFAULT_SIZE, MAX_EDGES_FOUND};
 
@@ -70,18 +65,7 @@ pub fn fuzz() {
         .expect("Symbol or env FUZZ_INPUT not found") as GuestPhysAddr;
     println!("FUZZ_INPUT @ {input_addr:#x}");
 
-    let main_addr = elf
-        .resolve_symbol("main", 0)
-        .expect("Symbol main not found");
-    println!("main address = {main_addr:#x}");
-
-    let breakpoint = elf
-        .resolve_symbol(
-            &env::var("BREAKPOINT").unwrap_or_else(|_| "BREAKPOINT".to_owned()),
-            0,
-        )
-        .expect("Symbol or env BREAKPOINT not found");
-    println!("Breakpoint address = {breakpoint:#x}");
+    let breakpoint = env::var("BREAKPOINT").unwrap_or_else(|_| "BREAKPOINT".to_owned());
 
     let mut run_client = |state: Option<_>, mut mgr, _client_description| {
         let args: Vec<String> = env::args().collect();
@@ -117,27 +101,14 @@ pub fn fuzz() {
             .unwrap();
 
         // Set breakpoints of interest with corresponding commands.
-        emu.add_breakpoint(
-            Breakpoint::with_command(
-                main_addr,
-                StartCommand::new(QemuMemoryChunk::phys(
-                    input_addr,
-                    unsafe { MAX_INPUT_SIZE } as GuestReg,
-                    None,
-                ))
-                .into(),
-                true,
-            ),
-            true,
-        );
-        emu.add_breakpoint(
-            Breakpoint::with_command(
-                breakpoint,
-                EndCommand::new(Some(ExitKind::Ok)).into(),
-                false,
-            ),
-            true,
-        );
+        BreakpointHarness::new()
+            .start(
+                "start",
+                "main",
+                QemuMemoryChunk::phys(input_addr, unsafe { MAX_INPUT_SIZE } as GuestReg, None),
+            )
+            .end("end", breakpoint.as_str(), ExitKind::Ok)
+            .install_with_elf(&emu, &elf, 0)?;
 
         let devices = emu.list_devices();
         println!("Devices = {:?}", devices);
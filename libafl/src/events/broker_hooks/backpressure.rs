@@ -0,0 +1,127 @@
+//! A broker hook publishing how far the broker is behind the clients.
+//!
+//! If the clients fire events faster than the broker handles them, their LLMP pages pile up until
+//! they run out of memory. The [`BackpressureLlmpHook`] regularly publishes the amount of messages
+//! the broker did not handle yet. An [`crate::events::LlmpEventManager`] with a
+//! [`crate::events::LlmpEventManagerBuilder::backpressure_threshold`] then holds back its low-priority
+//! events while the broker is behind.
+
+use alloc::vec::Vec;
+
+use libafl_bolts::{
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag, LLMP_FLAG_NO_B2B},
+    shmem::ShMemProvider,
+    ClientId, Error,
+};
+
+use crate::events::llmp::LLMP_TAG_BROKER_QUEUE_DEPTH;
+
+/// The default amount of messages between two publications of the queue depth
+pub const DEFAULT_BACKPRESSURE_PUBLISH_INTERVAL: u64 = 64;
+
+/// A broker hook publishing the queue depth of the broker to all clients.
+///
+/// The depth is published every few messages, and as soon as the broker caught up again.
+/// It is only published to the clients of this broker, never to connected brokers.
+/// The hook never drops any message.
+#[derive(Debug)]
+pub struct BackpressureLlmpHook {
+    publish_interval: u64,
+    msgs_since_publish: u64,
+    last_published: u64,
+}
+
+impl Default for BackpressureLlmpHook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackpressureLlmpHook {
+    /// Creates a new [`BackpressureLlmpHook`], publishing the queue depth every
+    /// [`DEFAULT_BACKPRESSURE_PUBLISH_INTERVAL`] messages.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_publish_interval(DEFAULT_BACKPRESSURE_PUBLISH_INTERVAL)
+    }
+
+    /// Creates a new [`BackpressureLlmpHook`], publishing the queue depth every `publish_interval` messages.
+    #[must_use]
+    pub fn with_publish_interval(publish_interval: u64) -> Self {
+        Self {
+            publish_interval: publish_interval.max(1),
+            msgs_since_publish: 0,
+            last_published: 0,
+        }
+    }
+
+    /// The queue depth that was last published to the clients
+    #[must_use]
+    pub fn last_published(&self) -> u64 {
+        self.last_published
+    }
+
+    /// Counts a message, returns if the current `depth` should be published now
+    fn should_publish(&mut self, depth: u64) -> bool {
+        self.msgs_since_publish += 1;
+        let caught_up = depth == 0 && self.last_published != 0;
+        if caught_up || self.msgs_since_publish >= self.publish_interval {
+            self.msgs_since_publish = 0;
+            self.last_published = depth;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<SP> LlmpHook<SP> for BackpressureLlmpHook
+where
+    SP: ShMemProvider,
+{
+    fn on_new_message(
+        &mut self,
+        broker_inner: &mut LlmpBrokerInner<SP>,
+        _client_id: ClientId,
+        msg_tag: &mut Tag,
+        _msg_flags: &mut Flags,
+        _msg: &mut [u8],
+        new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        if *msg_tag == LLMP_TAG_BROKER_QUEUE_DEPTH {
+            // Coming from another broker, our clients only care about our own depth
+            return Ok(LlmpMsgHookResult::Handled);
+        }
+
+        let depth = broker_inner.pending_msgs();
+        if self.should_publish(depth) {
+            new_msgs.push((
+                LLMP_TAG_BROKER_QUEUE_DEPTH,
+                LLMP_FLAG_NO_B2B,
+                depth.to_le_bytes().to_vec(),
+            ));
+        }
+        Ok(LlmpMsgHookResult::ForwardToClients)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BackpressureLlmpHook;
+
+    #[test]
+    fn test_publish_interval() {
+        let mut hook = BackpressureLlmpHook::with_publish_interval(3);
+        assert!(!hook.should_publish(5));
+        assert!(!hook.should_publish(6));
+        assert!(hook.should_publish(7));
+        assert_eq!(hook.last_published(), 7);
+
+        // Publish as soon as the broker caught up, and only once
+        assert!(hook.should_publish(0));
+        assert_eq!(hook.last_published(), 0);
+        assert!(!hook.should_publish(0));
+        assert!(!hook.should_publish(0));
+        assert!(hook.should_publish(0));
+    }
+}
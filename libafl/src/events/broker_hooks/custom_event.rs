@@ -0,0 +1,99 @@
+//! A broker hook dispatching [`Event::Custom`] events to a handler in the broker.
+//!
+//! The handler decides, per [`CustomEvent`], if the event is forwarded to the clients,
+//! or if it was handled in the broker. All other events are passed on to the next hook.
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData};
+
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
+use libafl_bolts::{
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
+    shmem::ShMemProvider,
+    ClientId, Error,
+};
+
+#[cfg(feature = "llmp_compression")]
+use crate::events::llmp::COMPRESS_THRESHOLD;
+use crate::{
+    events::{llmp::LLMP_TAG_EVENT_TO_BOTH, BrokerEventResult, CustomEvent, Event},
+    inputs::Input,
+};
+
+/// A broker hook calling a handler for each [`Event::Custom`] event.
+///
+/// Add it to the broker hooks before the [`crate::events::StdLlmpEventHook`].
+pub struct CustomEventLlmpHook<I, F> {
+    handler: F,
+    #[cfg(feature = "llmp_compression")]
+    compressor: GzipCompressor,
+    phantom: PhantomData<I>,
+}
+
+impl<I, F> Debug for CustomEventLlmpHook<I, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CustomEventLlmpHook").finish_non_exhaustive()
+    }
+}
+
+impl<I, F> CustomEventLlmpHook<I, F>
+where
+    I: Input,
+    F: FnMut(ClientId, &CustomEvent) -> Result<BrokerEventResult, Error>,
+{
+    /// Creates a new [`CustomEventLlmpHook`].
+    /// The `handler` gets the sender and the event, and returns [`BrokerEventResult::Forward`]
+    /// to pass the event on to the clients.
+    #[must_use]
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            #[cfg(feature = "llmp_compression")]
+            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, F, SP> LlmpHook<SP> for CustomEventLlmpHook<I, F>
+where
+    I: Input,
+    F: FnMut(ClientId, &CustomEvent) -> Result<BrokerEventResult, Error>,
+    SP: ShMemProvider,
+{
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SP>,
+        client_id: ClientId,
+        msg_tag: &mut Tag,
+        #[cfg(feature = "llmp_compression")] msg_flags: &mut Flags,
+        #[cfg(not(feature = "llmp_compression"))] _msg_flags: &mut Flags,
+        msg: &mut [u8],
+        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        if *msg_tag != LLMP_TAG_EVENT_TO_BOTH {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        }
+
+        #[cfg(not(feature = "llmp_compression"))]
+        let event_bytes = msg;
+        #[cfg(feature = "llmp_compression")]
+        let compressed;
+        #[cfg(feature = "llmp_compression")]
+        let event_bytes = if *msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+            compressed = self.compressor.decompress(msg)?;
+            &compressed
+        } else {
+            &*msg
+        };
+        let Event::<I>::Custom { event, .. } = postcard::from_bytes(event_bytes)? else {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        };
+
+        match (self.handler)(client_id, &event)? {
+            BrokerEventResult::Forward => Ok(LlmpMsgHookResult::ForwardToClients),
+            BrokerEventResult::Handled => Ok(LlmpMsgHookResult::Handled),
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! A broker hook dropping testcases that were already forwarded to the clients.
+//!
+//! In large campaigns, several clients often find the same input, or a testcase is received again
+//! through another broker, and every client re-evaluates each copy of it.
+//! The [`DedupLlmpHook`] keeps the hashes of the inputs it recently forwarded and drops [`Event::NewTestcase`]s
+//! with an input it has already seen, before they reach the clients.
+
+use alloc::vec::Vec;
+use core::{fmt::Debug, marker::PhantomData};
+
+use hashbrown::HashSet;
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
+use libafl_bolts::{
+    hash_std,
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag},
+    shmem::ShMemProvider,
+    ClientId, Error,
+};
+
+#[cfg(feature = "llmp_compression")]
+use crate::events::llmp::COMPRESS_THRESHOLD;
+use crate::{
+    events::{llmp::LLMP_TAG_EVENT_TO_BOTH, Event},
+    inputs::Input,
+};
+
+/// The default amount of input hashes a [`DedupLlmpHook`] remembers
+pub const DEFAULT_DEDUP_CAPACITY: usize = 1 << 20;
+
+/// A broker hook dropping [`Event::NewTestcase`]s whose input was already forwarded.
+///
+/// Inputs are compared by the hash of their serialized form, so each seen input costs 8 bytes.
+/// At most `capacity` hashes are kept: the inputs that were not seen for the longest time are
+/// forgotten first, and are forwarded again if a client finds them again.
+/// Add it to the broker hooks after the [`crate::events::StdLlmpEventHook`], so that the monitor still
+/// sees the corpus sizes of all clients.
+pub struct DedupLlmpHook<I> {
+    /// The hashes seen since the last rotation
+    recent: HashSet<u64>,
+    /// The hashes seen in the generation before, forgotten at the next rotation
+    older: HashSet<u64>,
+    capacity: usize,
+    forwarded: u64,
+    suppressed: u64,
+    #[cfg(feature = "llmp_compression")]
+    compressor: GzipCompressor,
+    phantom: PhantomData<I>,
+}
+
+impl<I> Debug for DedupLlmpHook<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DedupLlmpHook")
+            .field("seen", &(self.recent.len() + self.older.len()))
+            .field("capacity", &self.capacity)
+            .field("forwarded", &self.forwarded)
+            .field("suppressed", &self.suppressed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Default for DedupLlmpHook<I>
+where
+    I: Input,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> DedupLlmpHook<I>
+where
+    I: Input,
+{
+    /// Creates a new [`DedupLlmpHook`], remembering [`DEFAULT_DEDUP_CAPACITY`] inputs
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_DEDUP_CAPACITY)
+    }
+
+    /// Creates a new [`DedupLlmpHook`], remembering at most `capacity` inputs
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            recent: HashSet::new(),
+            older: HashSet::new(),
+            capacity: capacity.max(2),
+            forwarded: 0,
+            suppressed: 0,
+            #[cfg(feature = "llmp_compression")]
+            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The amount of testcases forwarded to the clients
+    #[must_use]
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded
+    }
+
+    /// The amount of testcases dropped, as their input was already forwarded
+    #[must_use]
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// The amount of input hashes currently remembered
+    #[must_use]
+    pub fn seen(&self) -> usize {
+        self.recent.len() + self.older.len()
+    }
+
+    /// Records the input as recently seen, returns if it was not seen before.
+    ///
+    /// The hashes are kept in two generations of half the capacity each. Once the recent generation
+    /// is full, it becomes the older one, and the hashes of the older one are forgotten.
+    /// A hash found in the older generation moves to the recent one.
+    fn insert(&mut self, input: &I) -> Result<bool, Error> {
+        let hash = hash_std(&postcard::to_allocvec(input)?);
+        if self.recent.contains(&hash) {
+            return Ok(false);
+        }
+        let known = self.older.remove(&hash);
+        if self.recent.len() >= self.capacity / 2 {
+            self.older = core::mem::take(&mut self.recent);
+        }
+        self.recent.insert(hash);
+        Ok(!known)
+    }
+
+    /// Decides about a message sent to the broker, see [`LlmpHook::on_new_message`]
+    fn filter(
+        &mut self,
+        msg_tag: Tag,
+        #[cfg(feature = "llmp_compression")] msg_flags: Flags,
+        #[cfg(not(feature = "llmp_compression"))] _msg_flags: Flags,
+        msg: &[u8],
+    ) -> Result<LlmpMsgHookResult, Error> {
+        if msg_tag != LLMP_TAG_EVENT_TO_BOTH {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        }
+
+        #[cfg(not(feature = "llmp_compression"))]
+        let event_bytes = msg;
+        #[cfg(feature = "llmp_compression")]
+        let compressed;
+        #[cfg(feature = "llmp_compression")]
+        let event_bytes = if msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+            compressed = self.compressor.decompress(msg)?;
+            &compressed
+        } else {
+            msg
+        };
+        let Event::<I>::NewTestcase { input, .. } = postcard::from_bytes(event_bytes)? else {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        };
+
+        if self.insert(&input)? {
+            self.forwarded += 1;
+            Ok(LlmpMsgHookResult::ForwardToClients)
+        } else {
+            self.suppressed += 1;
+            Ok(LlmpMsgHookResult::Handled)
+        }
+    }
+}
+
+impl<I, SP> LlmpHook<SP> for DedupLlmpHook<I>
+where
+    I: Input,
+    SP: ShMemProvider,
+{
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SP>,
+        _client_id: ClientId,
+        msg_tag: &mut Tag,
+        msg_flags: &mut Flags,
+        msg: &mut [u8],
+        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        self.filter(*msg_tag, *msg_flags, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
+    use libafl_bolts::{
+        current_time,
+        llmp::{Flags, LlmpMsgHookResult, Tag},
+    };
+
+    use super::DedupLlmpHook;
+    use crate::{
+        events::{
+            llmp::{LLMP_TAG_EVENT_TO_BOTH, _LLMP_TAG_EVENT_TO_BROKER},
+            Event, EventConfig, LogSeverity,
+        },
+        executors::ExitKind,
+        fuzzer::ExecuteInputResult,
+        inputs::BytesInput,
+    };
+
+    fn new_testcase(bytes: &[u8]) -> Vec<u8> {
+        postcard::to_allocvec(&Event::NewTestcase {
+            input: BytesInput::new(bytes.to_vec()),
+            observers_buf: None,
+            exit_kind: ExitKind::Ok,
+            exec_res: ExecuteInputResult::Corpus,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: current_time(),
+            forward_id: None,
+            #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+            node_id: None,
+        })
+        .unwrap()
+    }
+
+    /// If the hook forwards the message to the clients
+    fn forwards(hook: &mut DedupLlmpHook<BytesInput>, tag: Tag, msg: &[u8]) -> bool {
+        match hook.filter(tag, Flags(0), msg).unwrap() {
+            LlmpMsgHookResult::ForwardToClients => true,
+            LlmpMsgHookResult::Handled => false,
+        }
+    }
+
+    #[test]
+    fn test_dedup_testcases() {
+        let mut hook = DedupLlmpHook::<BytesInput>::new();
+        let a = new_testcase(b"a");
+        assert!(forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &a));
+        assert!(!forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &a));
+        assert!(forwards(
+            &mut hook,
+            LLMP_TAG_EVENT_TO_BOTH,
+            &new_testcase(b"b")
+        ));
+        assert_eq!(hook.forwarded(), 2);
+        assert_eq!(hook.suppressed(), 1);
+
+        // Other events and tags always pass
+        let log = postcard::to_allocvec(&Event::<BytesInput>::Log {
+            severity_level: LogSeverity::Info,
+            message: "a".into(),
+            phantom: PhantomData,
+        })
+        .unwrap();
+        for _ in 0..2 {
+            assert!(forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &log));
+            assert!(forwards(&mut hook, _LLMP_TAG_EVENT_TO_BROKER, &a));
+        }
+    }
+
+    #[test]
+    fn test_dedup_capacity() {
+        let mut hook = DedupLlmpHook::<BytesInput>::with_capacity(4);
+        let inputs: Vec<_> = (0_u8..5).map(|i| new_testcase(&[i])).collect();
+        for input in &inputs[..3] {
+            assert!(forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, input));
+        }
+        // Keep the first input recent
+        assert!(!forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &inputs[0]));
+        for input in &inputs[3..] {
+            assert!(forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, input));
+        }
+        assert!(hook.seen() <= 4);
+
+        // The least recently seen input was forgotten, the recently seen one was not
+        assert!(!forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &inputs[0]));
+        assert!(forwards(&mut hook, LLMP_TAG_EVENT_TO_BOTH, &inputs[1]));
+    }
+}
@@ -0,0 +1,272 @@
+//! A broker hook keeping a merged, global coverage map of all clients.
+//!
+//! With many clients, a large fraction of the forwarded testcases is redundant: another client
+//! already found the same coverage. The [`GlobalNoveltyLlmpHook`] merges the map observer of each
+//! [`Event::NewTestcase`] into a global map. Every few merges, the global map is broadcast to all
+//! clients as an [`Event::CustomBuf`] with the [`GLOBAL_NOVELTY_MAP_TAG`], so that, using a
+//! [`global_novelty_handler`], they merge it into their own [`MapFeedbackMetadata`] and stop reporting
+//! coverage that is already globally known. Testcases that other feedbacks found interesting are still
+//! reported, and forwarded.
+//!
+//! With [`GlobalNoveltyLlmpHook::veto`], the broker also drops testcases that add no new global coverage.
+//! The broker does not know why a testcase was interesting, so this is an AND of the feedbacks of the
+//! clients with the global map novelty: testcases only interesting to other feedbacks are dropped, too.
+//!
+//! Testcases are only inspected if they carry serialized observers, i.e., if the clients do not use
+//! [`crate::events::EventConfig::AlwaysUnique`].
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{fmt::Debug, marker::PhantomData};
+
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
+use libafl_bolts::{
+    llmp::{Flags, LlmpBrokerInner, LlmpHook, LlmpMsgHookResult, Tag, LLMP_FLAG_INITIALIZED},
+    shmem::ShMemProvider,
+    tuples::{Handle, MatchName, MatchNameRef},
+    ClientId, Error,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(feature = "llmp_compression")]
+use crate::events::llmp::COMPRESS_THRESHOLD;
+use crate::{
+    events::{llmp::LLMP_TAG_EVENT_TO_BOTH, CustomBufEventResult, CustomBufHandlerFn, Event},
+    feedbacks::MapFeedbackMetadata,
+    inputs::Input,
+    observers::MapObserver,
+    HasNamedMetadata,
+};
+
+/// The tag of the [`Event::CustomBuf`] carrying the global coverage map
+pub const GLOBAL_NOVELTY_MAP_TAG: &str = "global_novelty_map";
+
+/// The default amount of merged testcases between two broadcasts of the global map
+pub const DEFAULT_GLOBAL_NOVELTY_BROADCAST_INTERVAL: u64 = 64;
+
+/// A broker hook merging the coverage of all clients into a global map, broadcast to the clients,
+/// and optionally dropping testcases whose coverage is already globally known.
+///
+/// Add it to the broker hooks after the [`crate::events::StdLlmpEventHook`], so that the monitor still
+/// sees the corpus sizes of all clients.
+pub struct GlobalNoveltyLlmpHook<I, OT, C, O>
+where
+    O: MapObserver,
+{
+    map_handle: Handle<C>,
+    global_map: Vec<O::Entry>,
+    broadcast_interval: u64,
+    merged_since_broadcast: u64,
+    veto: bool,
+    forwarded: u64,
+    suppressed: u64,
+    #[cfg(feature = "llmp_compression")]
+    compressor: GzipCompressor,
+    phantom: PhantomData<(I, OT)>,
+}
+
+impl<I, OT, C, O> Debug for GlobalNoveltyLlmpHook<I, OT, C, O>
+where
+    O: MapObserver,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("GlobalNoveltyLlmpHook")
+            .field("map_handle", &self.map_handle)
+            .field("global_map_len", &self.global_map.len())
+            .field("broadcast_interval", &self.broadcast_interval)
+            .field("veto", &self.veto)
+            .field("forwarded", &self.forwarded)
+            .field("suppressed", &self.suppressed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I, OT, C, O> GlobalNoveltyLlmpHook<I, OT, C, O>
+where
+    I: Input,
+    OT: MatchName + DeserializeOwned,
+    C: AsRef<O>,
+    O: MapObserver,
+    O::Entry: PartialOrd + Default + Serialize,
+{
+    /// Creates a new [`GlobalNoveltyLlmpHook`] for the map observer with the given handle,
+    /// broadcasting the global map every [`DEFAULT_GLOBAL_NOVELTY_BROADCAST_INTERVAL`] merges.
+    #[must_use]
+    pub fn new(map_handle: Handle<C>) -> Self {
+        Self::with_broadcast_interval(map_handle, DEFAULT_GLOBAL_NOVELTY_BROADCAST_INTERVAL)
+    }
+
+    /// Creates a new [`GlobalNoveltyLlmpHook`], broadcasting the global map to the clients every
+    /// `broadcast_interval` merges. An interval of `0` never broadcasts.
+    #[must_use]
+    pub fn with_broadcast_interval(map_handle: Handle<C>, broadcast_interval: u64) -> Self {
+        Self {
+            map_handle,
+            global_map: Vec::new(),
+            broadcast_interval,
+            merged_since_broadcast: 0,
+            veto: false,
+            forwarded: 0,
+            suppressed: 0,
+            #[cfg(feature = "llmp_compression")]
+            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Drop testcases that add no new coverage to the global map, instead of only broadcasting it.
+    ///
+    /// This ANDs the feedbacks of the clients with the global map novelty, see the [module-level docs](self):
+    /// testcases that were interesting to other feedbacks only, such as a time or hash feedback, are dropped, too.
+    #[must_use]
+    pub fn veto(mut self, veto: bool) -> Self {
+        self.veto = veto;
+        self
+    }
+
+    /// The merged coverage map of all clients
+    #[must_use]
+    pub fn global_map(&self) -> &[O::Entry] {
+        &self.global_map
+    }
+
+    /// The amount of testcases forwarded to the clients
+    #[must_use]
+    pub fn forwarded(&self) -> u64 {
+        self.forwarded
+    }
+
+    /// The amount of testcases dropped, as their coverage was already globally known, with the [`Self::veto`]
+    #[must_use]
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+
+    /// Merges the map observer into the global map, returns if it contained anything new
+    fn merge(&mut self, observers_buf: &[u8]) -> Result<bool, Error> {
+        let observers: OT = postcard::from_bytes(observers_buf)?;
+        let Some(observer) = observers.get(&self.map_handle) else {
+            return Ok(true);
+        };
+        let observer = observer.as_ref();
+        let len = observer.usable_count();
+        if self.global_map.len() < len {
+            self.global_map.resize(len, O::Entry::default());
+        }
+
+        let mut novel = false;
+        for (i, global) in self.global_map[..len].iter_mut().enumerate() {
+            let item = observer.get(i);
+            if item > *global {
+                *global = item;
+                novel = true;
+            }
+        }
+        Ok(novel)
+    }
+
+    /// Serializes the global map as an [`Event::CustomBuf`], to be sent to all clients
+    fn broadcast_msg(&self) -> Result<Vec<u8>, Error> {
+        let event: Event<I> = Event::CustomBuf {
+            buf: postcard::to_allocvec(&self.global_map)?,
+            tag: String::from(GLOBAL_NOVELTY_MAP_TAG),
+        };
+        Ok(postcard::to_allocvec(&event)?)
+    }
+}
+
+impl<I, OT, C, O, SP> LlmpHook<SP> for GlobalNoveltyLlmpHook<I, OT, C, O>
+where
+    I: Input,
+    OT: MatchName + DeserializeOwned,
+    C: AsRef<O>,
+    O: MapObserver,
+    O::Entry: PartialOrd + Default + Serialize,
+    SP: ShMemProvider,
+{
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SP>,
+        _client_id: ClientId,
+        msg_tag: &mut Tag,
+        #[cfg(feature = "llmp_compression")] msg_flags: &mut Flags,
+        #[cfg(not(feature = "llmp_compression"))] _msg_flags: &mut Flags,
+        msg: &mut [u8],
+        new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        if *msg_tag != LLMP_TAG_EVENT_TO_BOTH {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        }
+
+        #[cfg(not(feature = "llmp_compression"))]
+        let event_bytes = msg;
+        #[cfg(feature = "llmp_compression")]
+        let compressed;
+        #[cfg(feature = "llmp_compression")]
+        let event_bytes = if *msg_flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+            compressed = self.compressor.decompress(msg)?;
+            &compressed
+        } else {
+            &*msg
+        };
+        let event: Event<I> = postcard::from_bytes(event_bytes)?;
+        let Event::NewTestcase {
+            observers_buf: Some(observers_buf),
+            ..
+        } = &event
+        else {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        };
+
+        let novel = self.merge(observers_buf)?;
+        if !novel && self.veto {
+            self.suppressed += 1;
+            return Ok(LlmpMsgHookResult::Handled);
+        }
+        self.forwarded += 1;
+
+        if !novel {
+            return Ok(LlmpMsgHookResult::ForwardToClients);
+        }
+        self.merged_since_broadcast += 1;
+        if self.broadcast_interval > 0 && self.merged_since_broadcast >= self.broadcast_interval {
+            self.merged_since_broadcast = 0;
+            new_msgs.push((
+                LLMP_TAG_EVENT_TO_BOTH,
+                LLMP_FLAG_INITIALIZED,
+                self.broadcast_msg()?,
+            ));
+        }
+        Ok(LlmpMsgHookResult::ForwardToClients)
+    }
+}
+
+/// Creates a custom buf handler for the clients, merging the global map broadcast by a
+/// [`GlobalNoveltyLlmpHook`] into the history map of the [`crate::feedbacks::MapFeedback`] with the
+/// given name. Register it with [`crate::events::HasCustomBufHandlers::add_custom_buf_handler`].
+#[must_use]
+pub fn global_novelty_handler<S, T>(feedback_name: String) -> Box<CustomBufHandlerFn<S>>
+where
+    S: HasNamedMetadata,
+    T: 'static + Debug + Serialize + DeserializeOwned + PartialOrd + Default + Copy,
+{
+    Box::new(move |state: &mut S, tag: &str, buf: &[u8]| {
+        if tag != GLOBAL_NOVELTY_MAP_TAG {
+            return Ok(CustomBufEventResult::Next);
+        }
+        let global_map: Vec<T> = postcard::from_bytes(buf)?;
+        let map_state = state.named_metadata_mut::<MapFeedbackMetadata<T>>(&feedback_name)?;
+        if map_state.history_map.len() < global_map.len() {
+            map_state.history_map.resize(global_map.len(), T::default());
+        }
+        for (history, global) in map_state.history_map.iter_mut().zip(global_map) {
+            if global > *history {
+                if *history == T::default() {
+                    map_state.num_covered_map_indexes += 1;
+                }
+                *history = global;
+            }
+        }
+        Ok(CustomBufEventResult::Handled)
+    })
+}
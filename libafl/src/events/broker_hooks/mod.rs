@@ -19,12 +19,28 @@ use crate::{
     Error,
 };
 
+/// Backpressure hook
+pub mod backpressure;
+pub use backpressure::*;
+
 /// centralized hook
 #[cfg(all(unix, feature = "std"))]
 pub mod centralized;
 #[cfg(all(unix, feature = "std"))]
 pub use centralized::*;
 
+/// Custom event hook
+pub mod custom_event;
+pub use custom_event::*;
+
+/// Deduplication hook
+pub mod dedup;
+pub use dedup::*;
+
+/// Global novelty hook
+pub mod global_novelty;
+pub use global_novelty::*;
+
 /// Multi-machine hook
 #[cfg(all(unix, feature = "multi_machine"))]
 pub mod centralized_multi_machine;
@@ -195,6 +211,7 @@ where
                 Ok(BrokerEventResult::Handled)
             }
             Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
+            Event::Custom { .. } => Ok(BrokerEventResult::Forward),
             Event::Stop => Ok(BrokerEventResult::Forward),
             //_ => Ok(BrokerEventResult::Forward),
         }
@@ -31,12 +31,13 @@ use crate::events::llmp::COMPRESS_THRESHOLD;
 use crate::state::HasScalabilityMonitor;
 use crate::{
     events::{
-        AdaptiveSerializer, CustomBufEventResult, Event, EventConfig, EventFirer, EventManager,
-        EventManagerHooksTuple, EventManagerId, EventProcessor, EventRestarter,
-        HasCustomBufHandlers, HasEventManagerId, LogSeverity, ProgressReporter,
+        AdaptiveSerializer, CustomBufEventResult, CustomEventHandlerFn, Event, EventConfig,
+        EventFirer, EventManager, EventManagerHooksTuple, EventManagerId, EventProcessor,
+        EventRestarter, HasCustomBufHandlers, HasCustomEventHandlers, HasEventManagerId,
+        LogSeverity, ProgressReporter,
     },
     executors::{Executor, HasObservers},
-    fuzzer::{EvaluatorObservers, ExecutionProcessor},
+    fuzzer::{EvaluatorObservers, ExecuteInputResult, ExecutionProcessor},
     inputs::{Input, NopInput, UsesInput},
     observers::{ObserversTuple, TimeObserver},
     state::{HasExecutions, HasLastReportTime, NopState, State, Stoppable, UsesState},
@@ -45,6 +46,47 @@ use crate::{
 
 pub(crate) const _LLMP_TAG_TO_MAIN: Tag = Tag(0x3453453);
 
+/// Decides which [`Event::NewTestcase`]s of a secondary node are forwarded to the main node,
+/// to be re-evaluated there before they get broadcast.
+///
+/// Testcases that are not forwarded skip the re-evaluation, and are shared directly through the inner event manager.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum CentralizedForwardPolicy {
+    /// Forward all testcases to the main node
+    #[default]
+    ForwardAll,
+    /// Only forward the testcases the feedback of the secondary node found interesting.
+    /// Testcases added to the corpus regardless, for example with [`crate::fuzzer::Evaluator::add_input`]
+    /// while loading the initial inputs, are not forwarded.
+    ForwardInterestingOnly,
+    /// Forward an evenly spaced fraction of the testcases.
+    /// A `rate` of `0.25` forwards every fourth testcase, a `rate` of `1.0` (or higher) forwards all of them.
+    Sampling {
+        /// The fraction of testcases to forward, between `0.0` and `1.0`
+        rate: f64,
+    },
+}
+
+impl CentralizedForwardPolicy {
+    /// Decide if a new testcase, evaluated with the given [`ExecuteInputResult`], is forwarded.
+    /// `sampling_credit` carries the testcases owed to the main node between calls under [`Self::Sampling`].
+    fn should_forward(self, exec_res: ExecuteInputResult, sampling_credit: &mut f64) -> bool {
+        match self {
+            Self::ForwardAll => true,
+            Self::ForwardInterestingOnly => exec_res == ExecuteInputResult::Corpus,
+            Self::Sampling { rate } => {
+                *sampling_credit += rate.clamp(0.0, 1.0);
+                if *sampling_credit >= 1.0 {
+                    *sampling_credit -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
 /// A wrapper manager to implement a main-secondary architecture with another broker
 #[derive(Debug)]
 pub struct CentralizedEventManager<EM, EMH, S, SP>
@@ -62,6 +104,9 @@ where
     time_ref: Option<Handle<TimeObserver>>,
     hooks: EMH,
     is_main: bool,
+    forward_policy: CentralizedForwardPolicy,
+    /// The testcases owed to the main node under [`CentralizedForwardPolicy::Sampling`]
+    sampling_credit: f64,
     phantom: PhantomData<S>,
 }
 
@@ -84,6 +129,7 @@ impl
 #[derive(Debug)]
 pub struct CentralizedEventManagerBuilder {
     is_main: bool,
+    forward_policy: CentralizedForwardPolicy,
 }
 
 impl Default for CentralizedEventManagerBuilder {
@@ -96,13 +142,26 @@ impl CentralizedEventManagerBuilder {
     /// The constructor
     #[must_use]
     pub fn new() -> Self {
-        Self { is_main: false }
+        Self {
+            is_main: false,
+            forward_policy: CentralizedForwardPolicy::ForwardAll,
+        }
     }
 
     /// Make this a main evaluator node
     #[must_use]
     pub fn is_main(self, is_main: bool) -> Self {
-        Self { is_main }
+        Self { is_main, ..self }
+    }
+
+    /// Set which testcases a secondary node forwards to the main node, see [`CentralizedForwardPolicy`].
+    /// Main nodes ignore the policy.
+    #[must_use]
+    pub fn forward_policy(self, forward_policy: CentralizedForwardPolicy) -> Self {
+        Self {
+            forward_policy,
+            ..self
+        }
     }
 
     /// Creates a new [`CentralizedEventManager`].
@@ -127,6 +186,8 @@ impl CentralizedEventManagerBuilder {
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs,
             is_main: self.is_main,
+            forward_policy: self.forward_policy,
+            sampling_credit: 0.0,
             phantom: PhantomData,
         })
     }
@@ -159,6 +220,8 @@ impl CentralizedEventManagerBuilder {
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs,
             is_main: self.is_main,
+            forward_policy: self.forward_policy,
+            sampling_credit: 0.0,
             phantom: PhantomData,
         })
     }
@@ -188,6 +251,8 @@ impl CentralizedEventManagerBuilder {
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs,
             is_main: self.is_main,
+            forward_policy: self.forward_policy,
+            sampling_credit: 0.0,
             phantom: PhantomData,
         })
     }
@@ -216,6 +281,8 @@ impl CentralizedEventManagerBuilder {
             compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
             time_ref: time_obs,
             is_main: self.is_main,
+            forward_policy: self.forward_policy,
+            sampling_credit: 0.0,
             phantom: PhantomData,
         })
     }
@@ -290,10 +357,16 @@ where
             let mut is_tc = false;
             // Forward to main only if new tc or heartbeat
             let should_be_forwarded = match &mut event {
-                Event::NewTestcase { forward_id, .. } => {
-                    *forward_id = Some(ClientId(self.inner.mgr_id().0 as u32));
-                    is_tc = true;
-                    true
+                Event::NewTestcase {
+                    exec_res,
+                    forward_id,
+                    ..
+                } => {
+                    is_tc = self.should_forward_testcase(*exec_res);
+                    if is_tc {
+                        *forward_id = Some(ClientId(self.inner.mgr_id().0 as u32));
+                    }
+                    is_tc
                 }
                 Event::UpdateExecStats { .. } => true, // send it but this guy won't be handled. the only purpose is to keep this client alive else the broker thinks it is dead and will dc it
                 Event::Stop => true,
@@ -436,6 +509,18 @@ where
     }
 }
 
+impl<EM, EMH, S, SP> HasCustomEventHandlers for CentralizedEventManager<EM, EMH, S, SP>
+where
+    EM: HasCustomEventHandlers,
+    EMH: EventManagerHooksTuple<EM::State>,
+    S: State,
+    SP: ShMemProvider,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<Self::State>>) {
+        self.inner.add_custom_event_handler(handler);
+    }
+}
+
 impl<EM, EMH, S, SP> ProgressReporter for CentralizedEventManager<EM, EMH, S, SP>
 where
     EM: AdaptiveSerializer + ProgressReporter + HasEventManagerId,
@@ -481,6 +566,17 @@ where
     pub fn is_main(&self) -> bool {
         self.is_main
     }
+
+    /// The policy deciding which testcases of this secondary node are forwarded to the main node
+    pub fn forward_policy(&self) -> CentralizedForwardPolicy {
+        self.forward_policy
+    }
+
+    /// Decide if a new testcase of this secondary node goes to the main node, according to the [`CentralizedForwardPolicy`]
+    fn should_forward_testcase(&mut self, exec_res: ExecuteInputResult) -> bool {
+        self.forward_policy
+            .should_forward(exec_res, &mut self.sampling_credit)
+    }
 }
 
 impl<EM, EMH, S, SP> CentralizedEventManager<EM, EMH, S, SP>
@@ -597,6 +693,7 @@ where
                 input,
                 client_config,
                 exit_kind,
+                exec_res: _,
                 corpus_size,
                 observers_buf,
                 time,
@@ -650,10 +747,12 @@ where
                     };
 
                 if let Some(item) = res.1 {
+                    // The result of the re-evaluation on this main node
                     let event = Event::NewTestcase {
                         input,
                         client_config,
                         exit_kind,
+                        exec_res: res.0,
                         corpus_size,
                         observers_buf,
                         time,
@@ -700,3 +799,33 @@ where
         self.await_restart_safe();
     }
 }*/
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::CentralizedForwardPolicy;
+    use crate::fuzzer::ExecuteInputResult;
+
+    #[test]
+    fn test_forward_policy() {
+        let mut credit = 0.0;
+        let all = CentralizedForwardPolicy::ForwardAll;
+        assert!(all.should_forward(ExecuteInputResult::Corpus, &mut credit));
+        assert!(all.should_forward(ExecuteInputResult::None, &mut credit));
+
+        let interesting = CentralizedForwardPolicy::ForwardInterestingOnly;
+        assert!(interesting.should_forward(ExecuteInputResult::Corpus, &mut credit));
+        // Added to the corpus without the feedback finding it interesting
+        assert!(!interesting.should_forward(ExecuteInputResult::None, &mut credit));
+
+        let sampling = CentralizedForwardPolicy::Sampling { rate: 0.25 };
+        let forwarded = (0..8)
+            .map(|_| sampling.should_forward(ExecuteInputResult::Corpus, &mut credit))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forwarded,
+            [false, false, false, true, false, false, false, true]
+        );
+    }
+}
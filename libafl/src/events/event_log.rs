@@ -0,0 +1,538 @@
+//! Logging all [`Event`]s fired by a fuzzer to disk, and replaying them offline.
+//!
+//! The [`EventLogWriter`] wraps any event manager and appends every [`Event`] it fires
+//! to an append-only log file, as `postcard`-encoded events written back to back.
+//! Such a log can be inspected after a campaign with [`read_event_log`], or fed back into
+//! a fuzzer with the [`EventLogReplayer`], to deterministically reproduce how the corpus evolved.
+
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    num::NonZeroUsize,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use serde::Serialize;
+
+use crate::{
+    events::{
+        CustomBufHandlerFn, CustomEventHandlerFn, Event, EventConfig, EventFirer, EventManager,
+        EventManagerId, EventProcessor, EventRestarter, HasCustomBufHandlers,
+        HasCustomEventHandlers, HasEventManagerId, ProgressReporter,
+    },
+    executors::{Executor, HasObservers},
+    fuzzer::EvaluatorObservers,
+    inputs::{Input, UsesInput},
+    observers::ObserversTuple,
+    state::{HasExecutions, HasLastReportTime, State, Stoppable, UsesState},
+    Error, HasMetadata,
+};
+
+/// Reads all [`Event`]s from a log written by an [`EventLogWriter`], in the order they were fired.
+///
+/// A broken event at the end of the log, for example written by a client that crashed while logging it,
+/// is skipped with a warning.
+pub fn read_event_log<I, P>(path: P) -> Result<Vec<Event<I>>, Error>
+where
+    I: Input,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+
+    let mut events = Vec::new();
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        match postcard::take_from_bytes::<Event<I>>(rest) {
+            Ok((event, next)) => {
+                events.push(event);
+                rest = next;
+            }
+            Err(err) => {
+                log::warn!(
+                    "Ignoring {} broken bytes at the end of the event log {}: {err}",
+                    rest.len(),
+                    path.display()
+                );
+                break;
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// An [`EventManager`] that wraps another manager, appending every fired [`Event`] to a log on disk.
+///
+/// The events are still passed on to the inner manager.
+#[derive(Debug)]
+pub struct EventLogWriter<EM> {
+    inner: EM,
+    file: File,
+}
+
+impl<EM> EventLogWriter<EM> {
+    /// Creates a new [`EventLogWriter`], logging the events fired through `inner` to `path`.
+    /// If the log already exists, new events are appended, so that a restarted client continues its log.
+    pub fn new<P>(inner: EM, path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { inner, file })
+    }
+
+    /// The wrapped event manager
+    #[must_use]
+    pub fn inner(&self) -> &EM {
+        &self.inner
+    }
+
+    /// The wrapped event manager (mutable)
+    pub fn inner_mut(&mut self) -> &mut EM {
+        &mut self.inner
+    }
+}
+
+impl<EM> UsesState for EventLogWriter<EM>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<EM> EventFirer for EventLogWriter<EM>
+where
+    EM: EventFirer,
+{
+    fn should_send(&self) -> bool {
+        self.inner.should_send()
+    }
+
+    fn fire(
+        &mut self,
+        state: &mut Self::State,
+        event: Event<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.file.write_all(&postcard::to_allocvec(&event)?)?;
+        self.inner.fire(state, event)
+    }
+
+    #[inline]
+    fn serialize_observers<OT>(&mut self, observers: &OT) -> Result<Option<Vec<u8>>, Error>
+    where
+        OT: ObserversTuple<<Self as UsesInput>::Input, Self::State> + Serialize,
+    {
+        self.inner.serialize_observers(observers)
+    }
+
+    #[inline]
+    fn configuration(&self) -> EventConfig {
+        self.inner.configuration()
+    }
+}
+
+impl<EM> EventRestarter for EventLogWriter<EM>
+where
+    EM: EventRestarter,
+{
+    #[inline]
+    fn on_restart(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        self.file.flush()?;
+        self.inner.on_restart(state)
+    }
+
+    #[inline]
+    fn send_exiting(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+        self.inner.send_exiting()
+    }
+
+    #[inline]
+    fn await_restart_safe(&mut self) {
+        self.inner.await_restart_safe();
+    }
+}
+
+impl<E, EM, Z> EventProcessor<E, Z> for EventLogWriter<EM>
+where
+    EM: EventProcessor<E, Z>,
+{
+    #[inline]
+    fn process(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        executor: &mut E,
+    ) -> Result<usize, Error> {
+        self.inner.process(fuzzer, state, executor)
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Error> {
+        self.file.flush()?;
+        self.inner.on_shutdown()
+    }
+}
+
+impl<E, EM, Z> EventManager<E, Z> for EventLogWriter<EM>
+where
+    EM: EventManager<E, Z>,
+    Self::State: HasLastReportTime + HasExecutions + HasMetadata,
+{
+}
+
+/// The progress is reported through [`EventFirer::fire`], so the stats end up in the log as well.
+impl<EM> ProgressReporter for EventLogWriter<EM>
+where
+    EM: EventFirer,
+    Self::State: HasLastReportTime + HasExecutions + HasMetadata,
+{
+}
+
+impl<EM> HasEventManagerId for EventLogWriter<EM>
+where
+    EM: HasEventManagerId,
+{
+    #[inline]
+    fn mgr_id(&self) -> EventManagerId {
+        self.inner.mgr_id()
+    }
+}
+
+impl<EM> HasCustomBufHandlers for EventLogWriter<EM>
+where
+    EM: HasCustomBufHandlers,
+{
+    #[inline]
+    fn add_custom_buf_handler(&mut self, handler: Box<CustomBufHandlerFn<Self::State>>) {
+        self.inner.add_custom_buf_handler(handler);
+    }
+}
+
+impl<EM> HasCustomEventHandlers for EventLogWriter<EM>
+where
+    EM: HasCustomEventHandlers,
+{
+    #[inline]
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<Self::State>>) {
+        self.inner.add_custom_event_handler(handler);
+    }
+}
+
+/// An [`EventManager`] that wraps another manager, and feeds the events of a log
+/// written by an [`EventLogWriter`] back into the fuzzer.
+///
+/// Each call to [`EventProcessor::process`] first replays the logged events, then processes the events
+/// of the inner manager. Testcases are re-evaluated, without sending events for them, stop requests
+/// are applied to the state and log messages are printed. All other events are skipped.
+pub struct EventLogReplayer<EM>
+where
+    EM: UsesState,
+{
+    inner: EM,
+    events: VecDeque<Event<<EM::State as UsesInput>::Input>>,
+    batch_size: Option<NonZeroUsize>,
+}
+
+impl<EM> Debug for EventLogReplayer<EM>
+where
+    EM: UsesState + Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventLogReplayer")
+            .field("inner", &self.inner)
+            .field("remaining", &self.events.len())
+            .field("batch_size", &self.batch_size)
+            .finish()
+    }
+}
+
+impl<EM> EventLogReplayer<EM>
+where
+    EM: UsesState,
+{
+    /// Creates a new [`EventLogReplayer`], replaying the log at `path` into the fuzzer.
+    pub fn new<P>(inner: EM, path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            inner,
+            events: read_event_log(path)?.into(),
+            batch_size: None,
+        })
+    }
+
+    /// Replays at most `batch_size` logged events per call to [`EventProcessor::process`],
+    /// instead of all remaining events at once.
+    #[must_use]
+    pub fn with_batch_size(mut self, batch_size: NonZeroUsize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// The number of logged events that were not replayed yet
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+
+    /// The wrapped event manager
+    #[must_use]
+    pub fn inner(&self) -> &EM {
+        &self.inner
+    }
+
+    /// The wrapped event manager (mutable)
+    pub fn inner_mut(&mut self) -> &mut EM {
+        &mut self.inner
+    }
+}
+
+impl<EM> EventLogReplayer<EM>
+where
+    EM: EventFirer,
+    EM::State: State + Stoppable,
+{
+    fn replay<E, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut EM::State,
+        event: Event<<EM::State as UsesInput>::Input>,
+    ) -> Result<(), Error>
+    where
+        E: HasObservers + Executor<Self, Z, State = EM::State>,
+        Z: EvaluatorObservers<Self, E::Observers, State = EM::State>,
+    {
+        match event {
+            Event::NewTestcase { input, .. } => {
+                let (res, id) = fuzzer
+                    .evaluate_input_with_observers::<E>(state, executor, self, input, false)?;
+                if let Some(item) = id {
+                    log::debug!("Replayed testcase {res:?} and added it as item #{item}");
+                }
+            }
+            Event::Stop => state.request_stop(),
+            Event::Log {
+                severity_level,
+                message,
+                ..
+            } => log::log!(severity_level.into(), "[replayed] {message}"),
+            _ => log::debug!("Skipping replayed {} event", event.name()),
+        }
+        Ok(())
+    }
+}
+
+impl<EM> UsesState for EventLogReplayer<EM>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<EM> EventFirer for EventLogReplayer<EM>
+where
+    EM: EventFirer,
+{
+    fn should_send(&self) -> bool {
+        self.inner.should_send()
+    }
+
+    #[inline]
+    fn fire(
+        &mut self,
+        state: &mut Self::State,
+        event: Event<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.inner.fire(state, event)
+    }
+
+    #[inline]
+    fn serialize_observers<OT>(&mut self, observers: &OT) -> Result<Option<Vec<u8>>, Error>
+    where
+        OT: ObserversTuple<<Self as UsesInput>::Input, Self::State> + Serialize,
+    {
+        self.inner.serialize_observers(observers)
+    }
+
+    #[inline]
+    fn configuration(&self) -> EventConfig {
+        self.inner.configuration()
+    }
+}
+
+impl<EM> EventRestarter for EventLogReplayer<EM>
+where
+    EM: EventRestarter,
+{
+    #[inline]
+    fn on_restart(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        self.inner.on_restart(state)
+    }
+
+    #[inline]
+    fn send_exiting(&mut self) -> Result<(), Error> {
+        self.inner.send_exiting()
+    }
+
+    #[inline]
+    fn await_restart_safe(&mut self) {
+        self.inner.await_restart_safe();
+    }
+}
+
+impl<E, EM, Z> EventProcessor<E, Z> for EventLogReplayer<EM>
+where
+    EM: EventProcessor<E, Z> + EventFirer,
+    EM::State: State + Stoppable,
+    E: HasObservers + Executor<Self, Z, State = EM::State>,
+    Z: EvaluatorObservers<Self, E::Observers, State = EM::State>,
+{
+    fn process(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        executor: &mut E,
+    ) -> Result<usize, Error> {
+        let limit = self.batch_size.map_or(usize::MAX, NonZeroUsize::get);
+        let mut count = 0;
+        while count < limit {
+            let Some(event) = self.events.pop_front() else {
+                break;
+            };
+            self.replay(fuzzer, executor, state, event)?;
+            count += 1;
+        }
+        Ok(count + self.inner.process(fuzzer, state, executor)?)
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Error> {
+        self.inner.on_shutdown()
+    }
+}
+
+impl<E, EM, Z> EventManager<E, Z> for EventLogReplayer<EM>
+where
+    EM: EventManager<E, Z>,
+    EM::State: State + Stoppable + HasLastReportTime + HasExecutions + HasMetadata,
+    E: HasObservers + Executor<Self, Z, State = EM::State>,
+    Z: EvaluatorObservers<Self, E::Observers, State = EM::State>,
+{
+}
+
+impl<EM> ProgressReporter for EventLogReplayer<EM>
+where
+    EM: EventFirer,
+    Self::State: HasLastReportTime + HasExecutions + HasMetadata,
+{
+}
+
+impl<EM> HasEventManagerId for EventLogReplayer<EM>
+where
+    EM: HasEventManagerId + UsesState,
+{
+    #[inline]
+    fn mgr_id(&self) -> EventManagerId {
+        self.inner.mgr_id()
+    }
+}
+
+impl<EM> HasCustomBufHandlers for EventLogReplayer<EM>
+where
+    EM: HasCustomBufHandlers,
+{
+    #[inline]
+    fn add_custom_buf_handler(&mut self, handler: Box<CustomBufHandlerFn<Self::State>>) {
+        self.inner.add_custom_buf_handler(handler);
+    }
+}
+
+impl<EM> HasCustomEventHandlers for EventLogReplayer<EM>
+where
+    EM: HasCustomEventHandlers,
+{
+    #[inline]
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<Self::State>>) {
+        self.inner.add_custom_event_handler(handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::{current_time, rands::StdRand, tuples::tuple_list};
+
+    use super::{read_event_log, EventLogReplayer, EventLogWriter};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus},
+        events::{Event, EventConfig, EventFirer, EventProcessor, NopEventManager},
+        executors::{test::NopExecutor, ExitKind, WithObservers},
+        feedbacks::ConstFeedback,
+        fuzzer::{ExecuteInputResult, StdFuzzer},
+        inputs::BytesInput,
+        schedulers::QueueScheduler,
+        state::{HasCorpus, StdState, Stoppable},
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    fn new_testcase(bytes: &[u8]) -> Event<BytesInput> {
+        Event::NewTestcase {
+            input: BytesInput::new(bytes.to_vec()),
+            observers_buf: None,
+            exit_kind: ExitKind::Ok,
+            exec_res: ExecuteInputResult::Corpus,
+            corpus_size: 1,
+            client_config: EventConfig::AlwaysUnique,
+            time: current_time(),
+            forward_id: None,
+            #[cfg(all(unix, feature = "std", feature = "multi_machine"))]
+            node_id: None,
+        }
+    }
+
+    #[test]
+    fn test_event_log_replay() {
+        let path = env::temp_dir().join(format!("libafl_event_log_test_{}", process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut state: TestState = StdState::nop().unwrap();
+        let mut writer = EventLogWriter::new(NopEventManager::new(), &path).unwrap();
+        writer.fire(&mut state, new_testcase(b"a")).unwrap();
+        writer.fire(&mut state, new_testcase(b"b")).unwrap();
+        drop(writer);
+
+        // Reopening the log appends to it
+        let mut writer = EventLogWriter::new(NopEventManager::new(), &path).unwrap();
+        writer.fire(&mut state, Event::Stop).unwrap();
+        drop(writer);
+
+        let events = read_event_log::<BytesInput, _>(&path).unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[2], Event::Stop));
+
+        let mut fuzzer = StdFuzzer::new(
+            QueueScheduler::new(),
+            ConstFeedback::new(true),
+            ConstFeedback::new(false),
+        );
+        let mut executor = WithObservers::new(NopExecutor::new(), tuple_list!());
+        let mut replayer = EventLogReplayer::new(NopEventManager::new(), &path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let processed = replayer
+            .process(&mut fuzzer, &mut state, &mut executor)
+            .unwrap();
+        assert_eq!(processed, 3);
+        assert_eq!(replayer.remaining(), 0);
+        assert_eq!(state.corpus().count(), 2);
+        assert!(state.stop_requested());
+    }
+}
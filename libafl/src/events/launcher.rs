@@ -17,10 +17,11 @@ use core::{
     num::NonZeroUsize,
     time::Duration,
 };
-use std::{net::SocketAddr, string::String};
+use std::{net::SocketAddr, path::PathBuf, string::String};
 
 use libafl_bolts::{
-    core_affinity::{CoreId, Cores},
+    core_affinity::{get_core_ids, CoreId, CorePlacement, CorePlan, Cores, CpuTopology},
+    llmp::LlmpB2bKey,
     shmem::ShMemProvider,
     tuples::{tuple_list, Handle},
 };
@@ -29,13 +30,15 @@ use typed_builder::TypedBuilder;
 #[cfg(all(unix, feature = "fork"))]
 use {
     crate::{
-        events::{centralized::CentralizedEventManager, CentralizedLlmpHook, StdLlmpEventHook},
+        events::{
+            centralized::{CentralizedEventManager, CentralizedForwardPolicy},
+            CentralizedLlmpHook, StdLlmpEventHook,
+        },
         inputs::UsesInput,
         state::UsesState,
     },
     alloc::string::ToString,
     libafl_bolts::{
-        core_affinity::get_core_ids,
         llmp::{Broker, Brokers, LlmpBroker},
         os::{fork, ForkResult},
     },
@@ -58,6 +61,7 @@ use crate::{
     },
     monitors::Monitor,
     observers::TimeObserver,
+    stages::client_autosave_path,
     state::{HasExecutions, State},
     Error,
 };
@@ -149,6 +153,11 @@ pub struct Launcher<'a, CF, MT, SP> {
     /// The number of clients to spawn on each core
     #[builder(default = 1)]
     overcommit: usize,
+    /// Place the clients and the broker according to the NUMA nodes and SMT siblings of the
+    /// [`Self::cores`], see [`CpuTopology::plan`].
+    /// If `None`, the clients run on all [`Self::cores`], in order, and the broker is not bound.
+    #[builder(default = None)]
+    core_placement: Option<CorePlacement>,
     /// A file name to write all client output to
     #[cfg(unix)]
     #[builder(default = None)]
@@ -173,6 +182,15 @@ pub struct Launcher<'a, CF, MT, SP> {
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key encrypting the connections to other brokers.
+    /// Both ends of a broker-to-broker connection need the same key.
+    #[builder(default = None)]
+    b2b_key: Option<LlmpB2bKey>,
+    /// The directory with the autosaves of the clients, to resume the campaign from.
+    /// Each client resumes from its own autosave, see [`crate::stages::client_autosave_path`],
+    /// if it exists, and if the client has no state to restore.
+    #[builder(default = None)]
+    resume_from: Option<PathBuf>,
     /// The time observer for addaptive serialization
     #[builder(default = None)]
     time_ref: Option<Handle<TimeObserver>>,
@@ -194,8 +212,11 @@ impl<CF, MT, SP> Debug for Launcher<'_, CF, MT, SP> {
             .field("configuration", &self.configuration)
             .field("broker_port", &self.broker_port)
             .field("core", &self.cores)
+            .field("core_placement", &self.core_placement)
             .field("spawn_broker", &self.spawn_broker)
-            .field("remote_broker_addr", &self.remote_broker_addr);
+            .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("b2b_key", &self.b2b_key)
+            .field("resume_from", &self.resume_from);
         #[cfg(unix)]
         {
             dbg_struct
@@ -212,6 +233,57 @@ where
     MT: Monitor + Clone,
     SP: ShMemProvider,
 {
+    /// The autosave the client resumes from, if the launcher resumes a campaign.
+    fn client_resume_path(&self, client_description: &ClientDescription) -> Option<PathBuf> {
+        self.resume_from
+            .as_ref()
+            .map(|dir| client_autosave_path(dir, client_description.id()))
+    }
+
+    /// The cores to spawn the clients on, in order, and the core of the broker, if any.
+    fn core_plan(&self) -> Result<CorePlan, Error> {
+        if let Some(placement) = &self.core_placement {
+            let plan = CpuTopology::detect()?.plan(self.cores, placement)?;
+            log::info!("Core placement: {plan:?}");
+            Ok(plan)
+        } else {
+            Ok(CorePlan {
+                clients: get_core_ids()?
+                    .into_iter()
+                    .filter(|core_id| self.cores.contains(*core_id))
+                    .collect(),
+                broker: None,
+            })
+        }
+    }
+
+    /// The number of clients spawned for the plan, including the overcommitted ones.
+    fn client_count(&self, plan: &CorePlan) -> Result<NonZeroUsize, Error> {
+        NonZeroUsize::new(plan.clients.len() * self.overcommit)
+            .ok_or_else(|| Error::illegal_argument("no client to spawn"))
+    }
+
+    /// Prefers the NUMA node of `core_id` for the memory of this process, if requested.
+    fn bind_memory(&self, core_id: CoreId) -> Result<(), Error> {
+        if self
+            .core_placement
+            .is_some_and(|placement| placement.bind_memory)
+        {
+            core_id.set_memory_affinity()?;
+        }
+        Ok(())
+    }
+
+    /// Binds the broker, running in this process, to its own core, if it has one.
+    fn bind_broker(&self, plan: &CorePlan) -> Result<(), Error> {
+        if let Some(core_id) = plan.broker {
+            log::info!("Binding the broker to core {core_id:?}");
+            core_id.set_affinity()?;
+            self.bind_memory(core_id)?;
+        }
+        Ok(())
+    }
+
     /// Launch the broker and the clients and fuzz
     #[cfg(any(windows, not(feature = "fork"), all(unix, feature = "fork")))]
     pub fn launch<S>(&mut self) -> Result<(), Error>
@@ -256,7 +328,7 @@ where
             ));
         }
 
-        let core_ids = get_core_ids()?;
+        let plan = self.core_plan()?;
         let mut handles = vec![];
 
         log::info!("spawning on cores: {:?}", self.cores);
@@ -272,71 +344,64 @@ where
 
         // Spawn clients
         let mut index = 0_usize;
-        for bind_to in core_ids {
-            if self.cores.ids.iter().any(|&x| x == bind_to) {
-                for overcommit_id in 0..self.overcommit {
-                    index += 1;
-                    self.shmem_provider.pre_fork()?;
-                    // # Safety
-                    // Fork is safe in general, apart from potential side effects to the OS and other threads
-                    match unsafe { fork() }? {
-                        ForkResult::Parent(child) => {
-                            self.shmem_provider.post_fork(false)?;
-                            handles.push(child.pid);
-                            log::info!(
-                                "child spawned with id {index} and bound to core {bind_to:?}"
-                            );
-                        }
-                        ForkResult::Child => {
-                            // # Safety
-                            // A call to `getpid` is safe.
-                            log::info!("{:?} PostFork", unsafe { libc::getpid() });
-                            self.shmem_provider.post_fork(true)?;
-
-                            std::thread::sleep(Duration::from_millis(
-                                index as u64 * self.launch_delay,
-                            ));
-
-                            if !debug_output {
-                                if let Some(file) = &self.opened_stdout_file {
-                                    dup2(file.as_raw_fd(), libc::STDOUT_FILENO)?;
-                                    if let Some(stderr) = &self.opened_stderr_file {
-                                        dup2(stderr.as_raw_fd(), libc::STDERR_FILENO)?;
-                                    } else {
-                                        dup2(file.as_raw_fd(), libc::STDERR_FILENO)?;
-                                    }
+        for &bind_to in &plan.clients {
+            for overcommit_id in 0..self.overcommit {
+                index += 1;
+                self.shmem_provider.pre_fork()?;
+                // # Safety
+                // Fork is safe in general, apart from potential side effects to the OS and other threads
+                match unsafe { fork() }? {
+                    ForkResult::Parent(child) => {
+                        self.shmem_provider.post_fork(false)?;
+                        handles.push(child.pid);
+                        log::info!("child spawned with id {index} and bound to core {bind_to:?}");
+                    }
+                    ForkResult::Child => {
+                        // # Safety
+                        // A call to `getpid` is safe.
+                        log::info!("{:?} PostFork", unsafe { libc::getpid() });
+                        self.shmem_provider.post_fork(true)?;
+
+                        std::thread::sleep(Duration::from_millis(index as u64 * self.launch_delay));
+
+                        if !debug_output {
+                            if let Some(file) = &self.opened_stdout_file {
+                                dup2(file.as_raw_fd(), libc::STDOUT_FILENO)?;
+                                if let Some(stderr) = &self.opened_stderr_file {
+                                    dup2(stderr.as_raw_fd(), libc::STDERR_FILENO)?;
+                                } else {
+                                    dup2(file.as_raw_fd(), libc::STDERR_FILENO)?;
                                 }
                             }
+                        }
 
-                            let client_description =
-                                ClientDescription::new(index, overcommit_id, bind_to);
+                        let client_description =
+                            ClientDescription::new(index, overcommit_id, bind_to);
+                        self.bind_memory(bind_to)?;
 
-                            // Fuzzer client. keeps retrying the connection to broker till the broker starts
-                            let builder = RestartingMgr::<EMH, MT, S, SP>::builder()
-                                .shmem_provider(self.shmem_provider.clone())
-                                .broker_port(self.broker_port)
-                                .kind(ManagerKind::Client {
-                                    client_description: client_description.clone(),
-                                })
-                                .configuration(self.configuration)
-                                .serialize_state(self.serialize_state)
-                                .hooks(hooks);
-                            let builder = builder.time_ref(self.time_ref.clone());
-                            let (state, mgr) = builder.build().launch()?;
-
-                            return (self.run_client.take().unwrap())(
-                                state,
-                                mgr,
-                                client_description,
-                            );
-                        }
-                    };
-                }
+                        // Fuzzer client. keeps retrying the connection to broker till the broker starts
+                        let builder = RestartingMgr::<EMH, MT, S, SP>::builder()
+                            .shmem_provider(self.shmem_provider.clone())
+                            .broker_port(self.broker_port)
+                            .kind(ManagerKind::Client {
+                                client_description: client_description.clone(),
+                            })
+                            .configuration(self.configuration)
+                            .serialize_state(self.serialize_state)
+                            .resume_from(self.client_resume_path(&client_description))
+                            .hooks(hooks);
+                        let builder = builder.time_ref(self.time_ref.clone());
+                        let (state, mgr) = builder.build().launch()?;
+
+                        return (self.run_client.take().unwrap())(state, mgr, client_description);
+                    }
+                };
             }
         }
 
         if self.spawn_broker {
             log::info!("I am broker!!.");
+            self.bind_broker(&plan)?;
 
             // TODO we don't want always a broker here, think about using different laucher process to spawn different configurations
             let builder = RestartingMgr::<EMH, MT, S, SP>::builder()
@@ -345,7 +410,8 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
-                .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
+                .b2b_key(self.b2b_key.clone())
+                .exit_cleanly_after(Some(self.client_count(&plan)?))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
                 .hooks(hooks);
@@ -391,13 +457,12 @@ where
             ClientDescription,
         ) -> Result<(), Error>,
     {
-        use libafl_bolts::core_affinity;
-
         let is_client = std::env::var(_AFL_LAUNCHER_CLIENT);
 
-        let mut handles = match is_client {
+        let (mut handles, plan) = match is_client {
             Ok(core_conf) => {
                 let client_description = ClientDescription::from_safe_string(&core_conf);
+                self.bind_memory(client_description.core_id())?;
                 // the actual client. do the fuzzing
 
                 let builder = RestartingMgr::<EMH, MT, S, SP>::builder()
@@ -408,6 +473,7 @@ where
                     })
                     .configuration(self.configuration)
                     .serialize_state(self.serialize_state)
+                    .resume_from(self.client_resume_path(&client_description))
                     .hooks(hooks);
 
                 let builder = builder.time_ref(self.time_ref.clone());
@@ -420,7 +486,7 @@ where
                 // I am a broker
                 // before going to the broker loop, spawn n clients
 
-                let core_ids = core_affinity::get_core_ids().unwrap();
+                let plan = self.core_plan()?;
                 let mut handles = vec![];
 
                 log::info!("spawning on cores: {:?}", self.cores);
@@ -448,44 +514,42 @@ where
                 }
                 //spawn clients
                 let mut index = 0;
-                for core_id in core_ids {
-                    if self.cores.ids.iter().any(|&x| x == core_id) {
-                        for overcommit_i in 0..self.overcommit {
-                            index += 1;
-                            // Forward own stdio to child processes, if requested by user
-                            #[allow(unused_mut)]
-                            let (mut stdout, mut stderr) = (Stdio::null(), Stdio::null());
-                            #[cfg(unix)]
-                            {
-                                if self.stdout_file.is_some() || self.stderr_file.is_some() {
-                                    stdout = Stdio::inherit();
-                                    stderr = Stdio::inherit();
-                                };
-                            }
-
-                            std::thread::sleep(Duration::from_millis(
-                                core_id.0 as u64 * self.launch_delay,
-                            ));
-
-                            let client_description =
-                                ClientDescription::new(index, overcommit_i, core_id);
-                            std::env::set_var(
-                                _AFL_LAUNCHER_CLIENT,
-                                client_description.to_safe_string(),
-                            );
-                            let mut child = startable_self()?;
-                            let child = (if debug_output {
-                                &mut child
-                            } else {
-                                child.stdout(stdout);
-                                child.stderr(stderr)
-                            })
-                            .spawn()?;
-                            handles.push(child);
+                for &core_id in &plan.clients {
+                    for overcommit_i in 0..self.overcommit {
+                        index += 1;
+                        // Forward own stdio to child processes, if requested by user
+                        #[allow(unused_mut)]
+                        let (mut stdout, mut stderr) = (Stdio::null(), Stdio::null());
+                        #[cfg(unix)]
+                        {
+                            if self.stdout_file.is_some() || self.stderr_file.is_some() {
+                                stdout = Stdio::inherit();
+                                stderr = Stdio::inherit();
+                            };
                         }
+
+                        std::thread::sleep(Duration::from_millis(
+                            core_id.0 as u64 * self.launch_delay,
+                        ));
+
+                        let client_description =
+                            ClientDescription::new(index, overcommit_i, core_id);
+                        std::env::set_var(
+                            _AFL_LAUNCHER_CLIENT,
+                            client_description.to_safe_string(),
+                        );
+                        let mut child = startable_self()?;
+                        let child = (if debug_output {
+                            &mut child
+                        } else {
+                            child.stdout(stdout);
+                            child.stderr(stderr)
+                        })
+                        .spawn()?;
+                        handles.push(child);
                     }
                 }
-                handles
+                (handles, plan)
             }
             Err(_) => panic!("Env variables are broken, received non-unicode!"),
         };
@@ -500,6 +564,7 @@ where
 
         if self.spawn_broker {
             log::info!("I am broker!!.");
+            self.bind_broker(&plan)?;
 
             let builder = RestartingMgr::<EMH, MT, S, SP>::builder()
                 .shmem_provider(self.shmem_provider.clone())
@@ -507,7 +572,8 @@ where
                 .broker_port(self.broker_port)
                 .kind(ManagerKind::Broker)
                 .remote_broker_addr(self.remote_broker_addr)
-                .exit_cleanly_after(Some(NonZeroUsize::try_from(self.cores.ids.len()).unwrap()))
+                .b2b_key(self.b2b_key.clone())
+                .exit_cleanly_after(Some(self.client_count(&plan)?))
                 .configuration(self.configuration)
                 .serialize_state(self.serialize_state)
                 .hooks(hooks);
@@ -565,6 +631,9 @@ pub struct CentralizedLauncher<'a, CF, MF, MT, SP> {
     /// The time observer by which to adaptively serialize
     #[builder(default = None)]
     time_obs: Option<Handle<TimeObserver>>,
+    /// Which testcases the secondary clients forward to the main node to be re-evaluated
+    #[builder(default = CentralizedForwardPolicy::ForwardAll)]
+    forward_policy: CentralizedForwardPolicy,
     /// The list of cores to run on
     cores: &'a Cores,
     /// The number of clients to spawn on each core
@@ -592,6 +661,10 @@ pub struct CentralizedLauncher<'a, CF, MF, MT, SP> {
     /// clusters.
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key encrypting the connections to other brokers.
+    /// Both ends of a broker-to-broker connection need the same key.
+    #[builder(default = None)]
+    b2b_key: Option<LlmpB2bKey>,
     #[cfg(feature = "multi_machine")]
     multi_machine_node_descriptor: NodeDescriptor<SocketAddr>,
     /// If this launcher should spawn a new `broker` on `[Self::broker_port]` (default).
@@ -615,6 +688,7 @@ impl<CF, MF, MT, SP> Debug for CentralizedLauncher<'_, CF, MF, MT, SP> {
             .field("overcommit", &self.overcommit)
             .field("spawn_broker", &self.spawn_broker)
             .field("remote_broker_addr", &self.remote_broker_addr)
+            .field("b2b_key", &self.b2b_key)
             .field("stdout_file", &self.stdout_file)
             .field("stderr_file", &self.stderr_file)
             .finish_non_exhaustive()
@@ -803,7 +877,8 @@ where
                                     client_description.clone(),
                                 )?;
 
-                                let centralized_builder = CentralizedEventManager::builder();
+                                let centralized_builder = CentralizedEventManager::builder()
+                                    .forward_policy(self.forward_policy);
 
                                 let c_mgr = centralized_builder.build_on_port(
                                     mgr,
@@ -891,6 +966,7 @@ where
                 llmp_hook,
                 self.broker_port,
             )?;
+            broker.inner_mut().set_b2b_key(self.b2b_key.clone());
 
             if let Some(remote_broker_addr) = self.remote_broker_addr {
                 log::info!("B2b: Connecting to {:?}", &remote_broker_addr);
@@ -4,7 +4,7 @@
 #[cfg(feature = "std")]
 use alloc::string::ToString;
 use alloc::{boxed::Box, vec::Vec};
-use core::{marker::PhantomData, time::Duration};
+use core::{marker::PhantomData, mem, time::Duration};
 #[cfg(feature = "std")]
 use std::net::TcpStream;
 
@@ -15,7 +15,7 @@ use libafl_bolts::{
 };
 use libafl_bolts::{
     current_time,
-    llmp::{LlmpClient, LlmpClientDescription, LLMP_FLAG_FROM_MM},
+    llmp::{LlmpClient, LlmpClientDescription, LLMP_FLAG_FROM_B2B, LLMP_FLAG_FROM_MM},
     shmem::{NopShMemProvider, ShMemProvider},
     tuples::Handle,
     ClientId,
@@ -31,14 +31,15 @@ use serde::{Deserialize, Serialize};
 use crate::events::llmp::COMPRESS_THRESHOLD;
 use crate::{
     events::{
-        llmp::{LLMP_TAG_EVENT_TO_BOTH, _LLMP_TAG_EVENT_TO_BROKER},
-        AdaptiveSerializer, CustomBufEventResult, CustomBufHandlerFn, Event, EventConfig,
-        EventFirer, EventManager, EventManagerHooksTuple, EventManagerId, EventProcessor,
-        EventRestarter, HasCustomBufHandlers, HasEventManagerId, ProgressReporter,
+        llmp::{LLMP_TAG_BROKER_QUEUE_DEPTH, LLMP_TAG_EVENT_TO_BOTH, _LLMP_TAG_EVENT_TO_BROKER},
+        AdaptiveSerializer, CustomBufEventResult, CustomBufHandlerFn, CustomEventHandlerFn, Event,
+        EventConfig, EventFirer, EventManager, EventManagerHooksTuple, EventManagerId,
+        EventProcessor, EventRestarter, HasCustomBufHandlers, HasCustomEventHandlers,
+        HasEventManagerId, ProgressReporter,
     },
     executors::{Executor, HasObservers},
     fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
-    inputs::{NopInput, UsesInput},
+    inputs::{Input, NopInput, UsesInput},
     observers::{ObserversTuple, TimeObserver},
     state::{HasExecutions, HasImported, HasLastReportTime, NopState, State, UsesState},
     Error, HasMetadata,
@@ -57,11 +58,15 @@ where
     always_interesting: bool,
     /// We sent last message at `last_sent`
     last_sent: Duration,
+    /// How far the broker is behind, and the events held back meanwhile
+    backpressure: Backpressure<S::Input>,
     hooks: EMH,
     /// The LLMP client for inter process communication
     llmp: LlmpClient<SP>,
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
+    /// The custom event handler
+    custom_event_handlers: Vec<Box<CustomEventHandlerFn<S>>>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
     /// The configuration defines this specific fuzzer.
@@ -76,6 +81,90 @@ where
     phantom: PhantomData<S>,
 }
 
+/// The maximum number of [`Event::Log`]s held back while the broker is behind; older ones are dropped.
+const MAX_HELD_BACK_LOGS: usize = 64;
+
+/// The client side of the backpressure: how far the broker is behind, and the events held back meanwhile,
+/// see [`LlmpEventManagerBuilder::backpressure_threshold`].
+#[derive(Debug)]
+struct Backpressure<I>
+where
+    I: Input,
+{
+    /// Hold back low-priority events while the broker is at least this many messages behind
+    threshold: Option<u64>,
+    /// The queue depth last published by the broker
+    depth: u64,
+    /// If the broker is behind: set at the threshold, cleared once it is less than half of it behind
+    behind: bool,
+    /// The low-priority events held back while the broker is behind
+    held_back: Vec<Event<I>>,
+}
+
+impl<I> Backpressure<I>
+where
+    I: Input,
+{
+    fn new(threshold: Option<u64>) -> Self {
+        Self {
+            threshold,
+            depth: 0,
+            behind: false,
+            held_back: vec![],
+        }
+    }
+
+    fn set_threshold(&mut self, threshold: Option<u64>) {
+        self.threshold = threshold;
+        self.update_depth(self.depth);
+    }
+
+    /// Records the queue depth published by the broker
+    fn update_depth(&mut self, depth: u64) {
+        self.depth = depth;
+        self.behind = match self.threshold {
+            None => false,
+            Some(threshold) if depth >= threshold => true,
+            Some(threshold) if depth < threshold / 2 => false,
+            Some(_) => self.behind,
+        };
+    }
+
+    /// Holds back a low-priority event, replacing the held back stats of the same kind.
+    /// Returns the event if it can not be held back.
+    fn hold_back(&mut self, event: Event<I>) -> Option<Event<I>> {
+        match &event {
+            Event::UpdateExecStats { .. } => {
+                self.held_back
+                    .retain(|held| !matches!(held, Event::UpdateExecStats { .. }));
+            }
+            Event::UpdateUserStats { name, .. } => {
+                self.held_back.retain(|held| {
+                    !matches!(held, Event::UpdateUserStats { name: other, .. } if other == name)
+                });
+            }
+            Event::Log { .. } => {
+                let logs = self
+                    .held_back
+                    .iter()
+                    .filter(|held| matches!(held, Event::Log { .. }))
+                    .count();
+                if logs >= MAX_HELD_BACK_LOGS {
+                    let oldest = self
+                        .held_back
+                        .iter()
+                        .position(|held| matches!(held, Event::Log { .. }))
+                        .unwrap();
+                    self.held_back.remove(oldest);
+                }
+            }
+            _ => return Some(event),
+        }
+        self.held_back.push(event);
+        None
+    }
+}
+
 impl LlmpEventManager<(), NopState<NopInput>, NopShMemProvider> {
     /// Creates a builder for [`LlmpEventManager`]
     #[must_use]
@@ -88,8 +177,11 @@ impl LlmpEventManager<(), NopState<NopInput>, NopShMemProvider> {
 #[derive(Debug, Copy, Clone)]
 pub struct LlmpEventManagerBuilder<EMH> {
     throttle: Option<Duration>,
+    backpressure_threshold: Option<u64>,
     hooks: EMH,
     always_interesting: bool,
+    #[cfg(feature = "llmp_compression")]
+    compression_threshold: usize,
 }
 
 impl Default for LlmpEventManagerBuilder<()> {
@@ -104,8 +196,11 @@ impl LlmpEventManagerBuilder<()> {
     pub fn new() -> Self {
         Self {
             throttle: None,
+            backpressure_threshold: None,
             hooks: (),
             always_interesting: false,
+            #[cfg(feature = "llmp_compression")]
+            compression_threshold: COMPRESS_THRESHOLD,
         }
     }
 
@@ -113,8 +208,11 @@ impl LlmpEventManagerBuilder<()> {
     pub fn hooks<EMH>(self, hooks: EMH) -> LlmpEventManagerBuilder<EMH> {
         LlmpEventManagerBuilder {
             throttle: self.throttle,
+            backpressure_threshold: self.backpressure_threshold,
             hooks,
             always_interesting: self.always_interesting,
+            #[cfg(feature = "llmp_compression")]
+            compression_threshold: self.compression_threshold,
         }
     }
 
@@ -123,8 +221,11 @@ impl LlmpEventManagerBuilder<()> {
     pub fn always_interesting(self, always_interesting: bool) -> LlmpEventManagerBuilder<()> {
         LlmpEventManagerBuilder {
             throttle: self.throttle,
+            backpressure_threshold: self.backpressure_threshold,
             hooks: self.hooks,
             always_interesting,
+            #[cfg(feature = "llmp_compression")]
+            compression_threshold: self.compression_threshold,
         }
     }
 }
@@ -137,6 +238,28 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         self
     }
 
+    /// Hold back low-priority events, [`Event::UpdateExecStats`], [`Event::UpdateUserStats`] and [`Event::Log`],
+    /// once the broker is at least `backpressure_threshold` messages behind, until it is less than half of it behind.
+    /// Only the latest held back stats of each kind are sent once the broker caught up, and the latest logs.
+    /// All other events, such as new testcases, are always sent right away.
+    /// The broker needs a [`crate::events::BackpressureLlmpHook`] to publish how far it is behind.
+    #[must_use]
+    pub fn backpressure_threshold(mut self, backpressure_threshold: u64) -> Self {
+        self.backpressure_threshold = Some(backpressure_threshold);
+        self
+    }
+
+    /// Compress all events, such as [`Event::NewTestcase`]s with large inputs and observers,
+    /// that are at least `compression_threshold` bytes long once serialized.
+    /// They are decompressed transparently on the receiving side.
+    /// Defaults to [`COMPRESS_THRESHOLD`], use `usize::MAX` to never compress.
+    #[cfg(feature = "llmp_compression")]
+    #[must_use]
+    pub fn compression_threshold(mut self, compression_threshold: usize) -> Self {
+        self.compression_threshold = compression_threshold;
+        self
+    }
+
     /// Create a manager from a raw LLMP client
     pub fn build_from_client<S, SP>(
         self,
@@ -151,11 +274,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         Ok(LlmpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
+            backpressure: Backpressure::new(self.backpressure_threshold),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: GzipCompressor::with_threshold(self.compression_threshold),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -164,6 +288,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             time_ref,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
         })
     }
 
@@ -185,11 +310,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         Ok(LlmpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
+            backpressure: Backpressure::new(self.backpressure_threshold),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: GzipCompressor::with_threshold(self.compression_threshold),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -198,6 +324,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             time_ref,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
         })
     }
 
@@ -219,11 +346,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         Ok(LlmpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
+            backpressure: Backpressure::new(self.backpressure_threshold),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: GzipCompressor::with_threshold(self.compression_threshold),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -232,6 +360,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             time_ref,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
         })
     }
 
@@ -251,11 +380,12 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
         Ok(LlmpEventManager {
             throttle: self.throttle,
             last_sent: Duration::from_secs(0),
+            backpressure: Backpressure::new(self.backpressure_threshold),
             hooks: self.hooks,
             always_interesting: self.always_interesting,
             llmp,
             #[cfg(feature = "llmp_compression")]
-            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            compressor: GzipCompressor::with_threshold(self.compression_threshold),
             configuration,
             serialization_time: Duration::ZERO,
             deserialization_time: Duration::ZERO,
@@ -264,6 +394,7 @@ impl<EMH> LlmpEventManagerBuilder<EMH> {
             time_ref,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
         })
     }
 }
@@ -376,6 +507,60 @@ where
         self.llmp.describe()
     }
 
+    /// Compress the events that are at least `compression_threshold` bytes long once serialized,
+    /// see [`LlmpEventManagerBuilder::compression_threshold`]
+    #[cfg(feature = "llmp_compression")]
+    pub fn set_compression_threshold(&mut self, compression_threshold: usize) {
+        self.compressor = GzipCompressor::with_threshold(compression_threshold);
+    }
+
+    /// Hold back low-priority events while the broker is at least `backpressure_threshold` messages behind,
+    /// see [`LlmpEventManagerBuilder::backpressure_threshold`]. `None` disables the backpressure.
+    pub fn set_backpressure_threshold(&mut self, backpressure_threshold: Option<u64>) {
+        self.backpressure.set_threshold(backpressure_threshold);
+    }
+
+    /// The amount of messages the broker did not handle yet, as last published by the broker
+    #[must_use]
+    pub fn broker_queue_depth(&self) -> u64 {
+        self.backpressure.depth
+    }
+
+    /// If the broker is too far behind, according to the backpressure threshold
+    #[must_use]
+    pub fn is_broker_behind(&self) -> bool {
+        self.backpressure.behind
+    }
+
+    #[cfg(feature = "llmp_compression")]
+    fn send_event(&mut self, event: &Event<S::Input>) -> Result<(), Error> {
+        let serialized = postcard::to_allocvec(event)?;
+        let flags = LLMP_FLAG_INITIALIZED;
+
+        match self.compressor.maybe_compress(&serialized) {
+            Some(comp_buf) => {
+                self.llmp.send_buf_with_flags(
+                    LLMP_TAG_EVENT_TO_BOTH,
+                    flags | LLMP_FLAG_COMPRESSED,
+                    &comp_buf,
+                )?;
+            }
+            None => {
+                self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+            }
+        }
+        self.last_sent = current_time();
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "llmp_compression"))]
+    fn send_event(&mut self, event: &Event<S::Input>) -> Result<(), Error> {
+        let serialized = postcard::to_allocvec(event)?;
+        self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
+        Ok(())
+    }
+
     /// Write the config for a client [`EventManager`] to env vars, a new
     /// client can reattach using [`LlmpEventManagerBuilder::build_existing_client_from_env()`].
     #[cfg(feature = "std")]
@@ -468,6 +653,13 @@ where
                     }
                 }
             }
+            Event::Custom { event, .. } => {
+                for handler in &mut self.custom_event_handlers {
+                    if handler(state, &event)? == CustomBufEventResult::Handled {
+                        break;
+                    }
+                }
+            }
             Event::Stop => {
                 state.request_stop();
             }
@@ -514,41 +706,23 @@ where
         }
     }
 
-    #[cfg(feature = "llmp_compression")]
     fn fire(
         &mut self,
         _state: &mut Self::State,
         event: Event<<Self::State as UsesInput>::Input>,
     ) -> Result<(), Error> {
-        let serialized = postcard::to_allocvec(&event)?;
-        let flags = LLMP_FLAG_INITIALIZED;
-
-        match self.compressor.maybe_compress(&serialized) {
-            Some(comp_buf) => {
-                self.llmp.send_buf_with_flags(
-                    LLMP_TAG_EVENT_TO_BOTH,
-                    flags | LLMP_FLAG_COMPRESSED,
-                    &comp_buf,
-                )?;
-            }
-            None => {
-                self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
-            }
+        if self.backpressure.behind {
+            return match self.backpressure.hold_back(event) {
+                Some(event) => self.send_event(&event),
+                None => Ok(()),
+            };
         }
-        self.last_sent = current_time();
-
-        Ok(())
-    }
 
-    #[cfg(not(feature = "llmp_compression"))]
-    fn fire(
-        &mut self,
-        _state: &mut Self::State,
-        event: Event<<Self::State as UsesInput>::Input>,
-    ) -> Result<(), Error> {
-        let serialized = postcard::to_allocvec(&event)?;
-        self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &serialized)?;
-        Ok(())
+        // The broker caught up, send what we held back first
+        for held_back in mem::take(&mut self.backpressure.held_back) {
+            self.send_event(&held_back)?;
+        }
+        self.send_event(&event)
     }
 
     fn serialize_observers<OT>(&mut self, observers: &OT) -> Result<Option<Vec<u8>>, Error>
@@ -609,6 +783,16 @@ where
                 "EVENT_TO_BROKER parcel should not have arrived in the client!"
             );
 
+            if tag == LLMP_TAG_BROKER_QUEUE_DEPTH {
+                // Only our own broker's depth matters, not the one of a connected broker
+                if flags & LLMP_FLAG_FROM_B2B != LLMP_FLAG_FROM_B2B {
+                    let depth = msg.try_into().map_err(|_| {
+                        Error::illegal_state("Received a malformed broker queue depth")
+                    })?;
+                    self.backpressure.update_depth(u64::from_le_bytes(depth));
+                }
+                continue;
+            }
             if client_id == self_id {
                 continue;
             }
@@ -670,6 +854,16 @@ where
     }
 }
 
+impl<EMH, S, SP> HasCustomEventHandlers for LlmpEventManager<EMH, S, SP>
+where
+    S: State,
+    SP: ShMemProvider,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<S>>) {
+        self.custom_event_handlers.push(handler);
+    }
+}
+
 impl<EMH, S, SP> ProgressReporter for LlmpEventManager<EMH, S, SP>
 where
     S: State + HasExecutions + HasMetadata + HasLastReportTime,
@@ -687,3 +881,99 @@ where
         EventManagerId(self.llmp.sender().id().0 as usize)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, vec::Vec};
+    use core::{marker::PhantomData, time::Duration};
+
+    use super::{Backpressure, MAX_HELD_BACK_LOGS};
+    use crate::{
+        events::{Event, LogSeverity},
+        inputs::BytesInput,
+        monitors::{AggregatorOps, UserStats, UserStatsValue},
+    };
+
+    fn exec_stats(executions: u64) -> Event<BytesInput> {
+        Event::UpdateExecStats {
+            time: Duration::ZERO,
+            executions,
+            phantom: PhantomData,
+        }
+    }
+
+    fn user_stats(name: &'static str, value: u64) -> Event<BytesInput> {
+        Event::UpdateUserStats {
+            name: Cow::Borrowed(name),
+            value: UserStats::new(UserStatsValue::Number(value), AggregatorOps::None),
+            phantom: PhantomData,
+        }
+    }
+
+    fn log(message: &str) -> Event<BytesInput> {
+        Event::Log {
+            severity_level: LogSeverity::Info,
+            message: message.into(),
+            phantom: PhantomData,
+        }
+    }
+
+    #[test]
+    fn test_backpressure_threshold() {
+        let mut backpressure = Backpressure::<BytesInput>::new(Some(100));
+        backpressure.update_depth(99);
+        assert!(!backpressure.behind);
+        backpressure.update_depth(100);
+        assert!(backpressure.behind);
+        // Hysteresis: stay behind until the broker is less than half of the threshold behind
+        backpressure.update_depth(50);
+        assert!(backpressure.behind);
+        backpressure.update_depth(49);
+        assert!(!backpressure.behind);
+        backpressure.update_depth(99);
+        assert!(!backpressure.behind);
+
+        backpressure.update_depth(1000);
+        backpressure.set_threshold(None);
+        assert!(!backpressure.behind);
+    }
+
+    #[test]
+    fn test_backpressure_hold_back() {
+        let mut backpressure = Backpressure::<BytesInput>::new(Some(1));
+        assert!(backpressure.hold_back(exec_stats(1)).is_none());
+        assert!(backpressure.hold_back(user_stats("a", 1)).is_none());
+        assert!(backpressure.hold_back(user_stats("b", 1)).is_none());
+        assert!(backpressure.hold_back(exec_stats(2)).is_none());
+        assert!(backpressure.hold_back(user_stats("a", 2)).is_none());
+        assert!(matches!(
+            backpressure.hold_back(Event::Stop),
+            Some(Event::Stop)
+        ));
+
+        assert_eq!(backpressure.held_back.len(), 3);
+        assert!(backpressure
+            .held_back
+            .iter()
+            .any(|event| matches!(event, Event::UpdateExecStats { executions: 2, .. })));
+        assert!(backpressure.held_back.iter().any(|event| matches!(
+            event,
+            Event::UpdateUserStats { name, value, .. }
+                if name == "a" && matches!(value.value(), UserStatsValue::Number(2))
+        )));
+
+        for i in 0..=MAX_HELD_BACK_LOGS {
+            assert!(backpressure.hold_back(log(&format!("{i}"))).is_none());
+        }
+        let logs: Vec<_> = backpressure
+            .held_back
+            .iter()
+            .filter_map(|event| match event {
+                Event::Log { message, .. } => Some(message.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(logs.len(), MAX_HELD_BACK_LOGS);
+        assert_eq!(logs[0], "1");
+    }
+}
@@ -43,6 +43,8 @@ pub(crate) const _LLMP_TAG_EVENT_TO_BROKER: Tag = Tag(0x2B80438);
 pub(crate) const LLMP_TAG_EVENT_TO_BOTH: Tag = Tag(0x2B0741);
 pub(crate) const _LLMP_TAG_RESTART: Tag = Tag(0x8357A87);
 pub(crate) const _LLMP_TAG_NO_RESTART: Tag = Tag(0x57A7EE71);
+/// The amount of messages the broker did not handle yet, published by the [`crate::events::BackpressureLlmpHook`]
+pub(crate) const LLMP_TAG_BROKER_QUEUE_DEPTH: Tag = Tag(0xB4C6DE97);
 
 /// The minimum buffer size at which to compress LLMP IPC messages.
 #[cfg(feature = "llmp_compression")]
@@ -329,7 +331,7 @@ where
                 }
                 Ok(())
             }
-            Event::Stop => Ok(()),
+            Event::Custom { .. } | Event::Stop => Ok(()),
             _ => Err(Error::unknown(format!(
                 "Received illegal message that message should not have arrived: {:?}.",
                 event.name()
@@ -361,7 +363,7 @@ where
                 "EVENT_TO_BROKER parcel should not have arrived in the client!"
             );
 
-            if client_id == self_id {
+            if client_id == self_id || tag == LLMP_TAG_BROKER_QUEUE_DEPTH {
                 continue;
             }
             #[cfg(not(feature = "llmp_compression"))]
@@ -428,6 +430,7 @@ where
                 input,
                 client_config,
                 exit_kind,
+                exec_res,
                 corpus_size,
                 observers_buf,
                 time,
@@ -438,6 +441,7 @@ where
                 input: self.converter.as_mut().unwrap().convert(input)?,
                 client_config,
                 exit_kind,
+                exec_res,
                 corpus_size,
                 observers_buf,
                 time,
@@ -446,6 +450,10 @@ where
                 node_id,
             },
             Event::CustomBuf { buf, tag } => Event::CustomBuf { buf, tag },
+            Event::Custom { event, .. } => Event::Custom {
+                event,
+                phantom: PhantomData,
+            },
             _ => {
                 return Ok(());
             }
@@ -485,6 +493,7 @@ where
                 input,
                 client_config,
                 exit_kind,
+                exec_res,
                 corpus_size,
                 observers_buf,
                 time,
@@ -495,6 +504,7 @@ where
                 input: self.converter.as_mut().unwrap().convert(input)?,
                 client_config,
                 exit_kind,
+                exec_res,
                 corpus_size,
                 observers_buf,
                 time,
@@ -503,6 +513,10 @@ where
                 node_id,
             },
             Event::CustomBuf { buf, tag } => Event::CustomBuf { buf, tag },
+            Event::Custom { event, .. } => Event::Custom {
+                event,
+                phantom: PhantomData,
+            },
             _ => {
                 return Ok(());
             }
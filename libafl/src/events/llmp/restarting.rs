@@ -10,7 +10,7 @@ use core::sync::atomic::{compiler_fence, Ordering};
 use core::time::Duration;
 use core::{marker::PhantomData, num::NonZeroUsize};
 #[cfg(feature = "std")]
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 
 #[cfg(feature = "std")]
 use libafl_bolts::core_affinity::CoreId;
@@ -25,7 +25,7 @@ use libafl_bolts::{
     llmp::LlmpConnection, os::CTRL_C_EXIT, shmem::StdShMemProvider, staterestore::StateRestorer,
 };
 use libafl_bolts::{
-    llmp::{Broker, LlmpBroker},
+    llmp::{Broker, LlmpB2bKey, LlmpBroker},
     shmem::ShMemProvider,
     tuples::{tuple_list, Handle},
 };
@@ -33,10 +33,17 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
+#[cfg(all(feature = "std", feature = "llmp_compression"))]
+use crate::events::llmp::COMPRESS_THRESHOLD;
 #[cfg(all(unix, feature = "std", not(miri)))]
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 #[cfg(feature = "std")]
-use crate::events::{AdaptiveSerializer, CustomBufEventResult, HasCustomBufHandlers};
+use crate::events::{
+    AdaptiveSerializer, CustomBufEventResult, CustomEventHandlerFn, HasCustomBufHandlers,
+    HasCustomEventHandlers,
+};
+#[cfg(feature = "std")]
+use crate::stages::load_autosave;
 use crate::{
     events::{
         launcher::ClientDescription, Event, EventConfig, EventFirer, EventManager,
@@ -263,6 +270,17 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<EMH, S, SP> HasCustomEventHandlers for LlmpRestartingEventManager<EMH, S, SP>
+where
+    S: State,
+    SP: ShMemProvider,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<S>>) {
+        self.llmp_mgr.add_custom_event_handler(handler);
+    }
+}
+
 /// The llmp connection from the actual fuzzer to the process supervising it
 const _ENV_FUZZER_SENDER: &str = "_AFL_ENV_FUZZER_SENDER";
 const _ENV_FUZZER_RECEIVER: &str = "_AFL_ENV_FUZZER_RECEIVER";
@@ -308,6 +326,21 @@ where
         &mut self.staterestorer
     }
 
+    /// Compress the events that are at least `compression_threshold` bytes long once serialized,
+    /// see [`LlmpEventManager::set_compression_threshold`].
+    /// The manager of a restarted client compresses with the threshold it was built with again,
+    /// see [`RestartingMgrBuilder::compression_threshold`] to keep it across restarts.
+    #[cfg(feature = "llmp_compression")]
+    pub fn set_compression_threshold(&mut self, compression_threshold: usize) {
+        self.llmp_mgr.set_compression_threshold(compression_threshold);
+    }
+
+    /// Hold back low-priority events while the broker is at least `backpressure_threshold` messages behind,
+    /// see [`LlmpEventManager::set_backpressure_threshold`].
+    pub fn set_backpressure_threshold(&mut self, backpressure_threshold: Option<u64>) {
+        self.llmp_mgr.set_backpressure_threshold(backpressure_threshold);
+    }
+
     /// Save LLMP state and empty state in staterestorer
     pub fn intermediate_save(&mut self) -> Result<(), Error> {
         // First, reset the page to 0 so the next iteration can read read from the beginning of this page
@@ -426,6 +459,14 @@ pub struct RestartingMgr<EMH, MT, S, SP> {
     /// The address to connect to
     #[builder(default = None)]
     remote_broker_addr: Option<SocketAddr>,
+    /// The pre-shared key encrypting the connections to other brokers.
+    /// Both ends of a broker-to-broker connection need the same key.
+    #[builder(default = None)]
+    b2b_key: Option<LlmpB2bKey>,
+    /// Resume from the autosave at this path, written by an [`crate::stages::AutosaveStage`],
+    /// if the client starts without a state to restore. Ignored if there is no autosave yet.
+    #[builder(default = None)]
+    resume_from: Option<PathBuf>,
     /// The type of manager to build
     #[builder(default = ManagerKind::Any)]
     kind: ManagerKind,
@@ -440,6 +481,12 @@ pub struct RestartingMgr<EMH, MT, S, SP> {
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = LlmpShouldSaveState::OnRestart)]
     serialize_state: LlmpShouldSaveState,
+    /// Compress the events of the clients that are at least this many bytes long once serialized,
+    /// see [`crate::events::LlmpEventManagerBuilder::compression_threshold`].
+    /// Applies to the managers of all restarted clients, too.
+    #[cfg(feature = "llmp_compression")]
+    #[builder(default = COMPRESS_THRESHOLD)]
+    compression_threshold: usize,
     /// The hooks passed to event manager:
     hooks: EMH,
     #[builder(default = None)]
@@ -457,13 +504,23 @@ where
     S: State,
     MT: Monitor + Clone,
 {
+    /// Loads the autosave to resume from, if any.
+    fn load_autosave(&self) -> Result<Option<S>, Error> {
+        match &self.resume_from {
+            Some(path) => load_autosave(path),
+            None => Ok(None),
+        }
+    }
+
     /// Launch the broker and the clients and fuzz
     pub fn launch(&mut self) -> Result<(Option<S>, LlmpRestartingEventManager<EMH, S, SP>), Error> {
         // We start ourselves as child process to actually fuzz
         let (staterestorer, new_shmem_provider, core_id) = if std::env::var(_ENV_FUZZER_SENDER)
             .is_err()
         {
+            let b2b_key = self.b2b_key.clone();
             let broker_things = |mut broker: LlmpBroker<_, SP>, remote_broker_addr| {
+                broker.inner_mut().set_b2b_key(b2b_key.clone());
                 if let Some(remote_broker_addr) = remote_broker_addr {
                     log::info!("B2b: Connecting to {:?}", &remote_broker_addr);
                     broker.inner_mut().connect_b2b(remote_broker_addr)?;
@@ -664,14 +721,19 @@ where
         // If we're restarting, deserialize the old state.
         let (state, mut mgr) =
             if let Some((state_opt, mgr_description)) = staterestorer.restore()? {
-                let llmp_mgr = LlmpEventManager::builder()
-                    .hooks(self.hooks)
-                    .build_existing_client_from_description(
-                        new_shmem_provider,
-                        &mgr_description,
-                        self.configuration,
-                        self.time_ref.clone(),
-                    )?;
+                let builder = LlmpEventManager::builder().hooks(self.hooks);
+                #[cfg(feature = "llmp_compression")]
+                let builder = builder.compression_threshold(self.compression_threshold);
+                let llmp_mgr = builder.build_existing_client_from_description(
+                    new_shmem_provider,
+                    &mgr_description,
+                    self.configuration,
+                    self.time_ref.clone(),
+                )?;
+                let state_opt = match state_opt {
+                    Some(state) => Some(state),
+                    None => self.load_autosave()?,
+                };
                 (
                     state_opt,
                     LlmpRestartingEventManager::with_save_state(
@@ -683,17 +745,18 @@ where
             } else {
                 log::info!("First run. Let's set it all up");
                 // Mgr to send and receive msgs from/to all other fuzzer instances
-                let mgr = LlmpEventManager::builder()
-                    .hooks(self.hooks)
-                    .build_existing_client_from_env(
-                        new_shmem_provider,
-                        _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                        self.configuration,
-                        self.time_ref.clone(),
-                    )?;
+                let builder = LlmpEventManager::builder().hooks(self.hooks);
+                #[cfg(feature = "llmp_compression")]
+                let builder = builder.compression_threshold(self.compression_threshold);
+                let mgr = builder.build_existing_client_from_env(
+                    new_shmem_provider,
+                    _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                    self.configuration,
+                    self.time_ref.clone(),
+                )?;
 
                 (
-                    None,
+                    self.load_autosave()?,
                     LlmpRestartingEventManager::with_save_state(
                         mgr,
                         staterestorer,
@@ -6,11 +6,19 @@ pub use events_hooks::*;
 
 pub mod simple;
 pub use simple::*;
+pub mod serial;
+pub use serial::{SerialChannel, SerialEventManager};
+#[cfg(feature = "std")]
+pub use serial::{IoSerialChannel, SerialBridge};
 #[cfg(all(unix, feature = "std"))]
 pub mod centralized;
 #[cfg(all(unix, feature = "std"))]
 pub use centralized::*;
 #[cfg(feature = "std")]
+pub mod event_log;
+#[cfg(feature = "std")]
+pub use event_log::*;
+#[cfg(feature = "std")]
 #[allow(clippy::ignored_unit_patterns)]
 pub mod launcher;
 #[allow(clippy::ignored_unit_patterns)]
@@ -19,9 +27,11 @@ pub use llmp::*;
 #[cfg(feature = "tcp_manager")]
 #[allow(clippy::ignored_unit_patterns)]
 pub mod tcp;
+#[cfg(feature = "tcp_manager")]
+pub use tcp::*;
 
 pub mod broker_hooks;
-use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+use alloc::{borrow::Cow, boxed::Box, string::String, sync::Arc, vec::Vec};
 use core::{
     fmt,
     hash::{BuildHasher, Hasher},
@@ -39,9 +49,10 @@ use libafl_bolts::os::unix_signals::{siginfo_t, ucontext_t, Signal, SignalHandle
 use libafl_bolts::os::CTRL_C_EXIT;
 use libafl_bolts::{
     current_time,
+    serdeany::SerdeAny,
     tuples::{Handle, MatchNameRef},
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 #[cfg(feature = "std")]
 use uuid::Uuid;
 
@@ -49,6 +60,7 @@ use uuid::Uuid;
 use crate::state::HasClientPerfMonitor;
 use crate::{
     executors::ExitKind,
+    fuzzer::ExecuteInputResult,
     inputs::Input,
     monitors::UserStats,
     observers::ObserversTuple,
@@ -156,7 +168,8 @@ impl fmt::Display for LogSeverity {
     }
 }
 
-/// The result of a custom buf handler added using [`HasCustomBufHandlers::add_custom_buf_handler`]
+/// The result of a custom buf handler added using [`HasCustomBufHandlers::add_custom_buf_handler`],
+/// or of a custom event handler added using [`HasCustomEventHandlers::add_custom_event_handler`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CustomBufEventResult {
     /// Exit early from event handling
@@ -247,20 +260,68 @@ impl From<String> for EventConfig {
     }
 }
 
-/*
-/// A custom event, for own messages, with own handler.
-pub trait CustomEvent<I>: SerdeAny
-where
-    I: Input,
-{
-    /// Returns the name of this event
-    fn name(&self) -> &str;
-    /// This method will be called in the broker
-    fn handle_in_broker(&self) -> Result<BrokerEventResult, Error>;
-    /// This method will be called in the clients after handle_in_broker (unless BrokerEventResult::Handled) was returned in handle_in_broker
-    fn handle_in_client(&self) -> Result<(), Error>;
+/// A custom event, for own messages, such as dictionary syncs or target reconfigurations.
+/// It carries any [`SerdeAny`] payload, sent around as [`Event::Custom`].
+///
+/// The clients handle it in the handlers added with [`HasCustomEventHandlers::add_custom_event_handler`],
+/// the broker can intercept it with a [`CustomEventLlmpHook`]; otherwise, it is forwarded to all clients.
+/// Like all [`SerdeAny`] types, the payload type needs to be registered to be deserialized.
+#[derive(Debug, Clone)]
+pub struct CustomEvent(Arc<dyn SerdeAny>);
+
+impl CustomEvent {
+    /// Creates a new [`CustomEvent`] with the given payload
+    #[must_use]
+    pub fn new<T>(payload: T) -> Self
+    where
+        T: SerdeAny,
+    {
+        Self(Arc::new(payload))
+    }
+
+    /// Returns the name of this event, the type name of its payload
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.0.type_name()
+    }
+
+    /// Returns the payload, if it is of type `T`
+    #[must_use]
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: SerdeAny,
+    {
+        self.0.as_any().downcast_ref::<T>()
+    }
+
+    /// Returns if the payload is of type `T`
+    #[must_use]
+    pub fn is<T>(&self) -> bool
+    where
+        T: SerdeAny,
+    {
+        self.0.as_any().is::<T>()
+    }
+}
+
+impl Serialize for CustomEvent {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Serialize::serialize(&*self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CustomEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let payload = Box::<dyn SerdeAny>::deserialize(deserializer)?;
+        Ok(Self(Arc::from(payload)))
+    }
 }
-*/
 
 // TODO remove forward_id as not anymore needed for centralized
 /// Events sent around in the library
@@ -280,6 +341,10 @@ where
         observers_buf: Option<Vec<u8>>,
         /// The exit kind
         exit_kind: ExitKind,
+        /// How the sender evaluated the input: [`ExecuteInputResult::Corpus`] if its feedback found the
+        /// input interesting, [`ExecuteInputResult::None`] if it was added to the corpus regardless,
+        /// for example by [`crate::fuzzer::Evaluator::add_input`]
+        exec_res: ExecuteInputResult,
         /// The new corpus size of this client
         corpus_size: usize,
         /// The client config for this observers/testcase combination
@@ -348,11 +413,13 @@ where
     },
     /// Exit gracefully
     Stop,
-    /*/// A custom type
+    /// A custom event, with an own [`SerdeAny`] payload
     Custom {
-        // TODO: Allow custom events
-        // custom_event: Box<dyn CustomEvent<I, OT>>,
-    },*/
+        /// The custom event
+        event: CustomEvent,
+        /// `PhantomData`
+        phantom: PhantomData<I>,
+    },
 }
 
 impl<I> Event<I>
@@ -370,9 +437,7 @@ where
             Event::Objective { .. } => "Objective",
             Event::Log { .. } => "Log",
             Event::CustomBuf { .. } => "CustomBuf",
-            /*Event::Custom {
-                sender_id: _, /*custom_event} => custom_event.name()*/
-            } => "todo",*/
+            Event::Custom { event, .. } => event.name(),
             Event::Stop => "Stop",
         }
     }
@@ -391,9 +456,7 @@ where
             Event::Log { .. } => Cow::Borrowed("Log"),
             Event::CustomBuf { .. } => Cow::Borrowed("CustomBuf"),
             Event::Stop => Cow::Borrowed("Stop"),
-            /*Event::Custom {
-                sender_id: _, /*custom_event} => custom_event.name()*/
-            } => "todo",*/
+            Event::Custom { event, .. } => Cow::Owned(format!("Custom {}", event.name())),
         }
     }
 
@@ -609,6 +672,30 @@ pub trait HasCustomBufHandlers: UsesState {
     fn add_custom_buf_handler(&mut self, handler: Box<CustomBufHandlerFn<Self::State>>);
 }
 
+/// The handler function for [`CustomEvent`]s exchanged via [`EventManager`]
+type CustomEventHandlerFn<S> =
+    dyn FnMut(&mut S, &CustomEvent) -> Result<CustomBufEventResult, Error>;
+
+/// Supports custom event handlers to handle [`Event::Custom`] events.
+pub trait HasCustomEventHandlers: UsesState {
+    /// Adds a custom event handler that will run for each incoming [`Event::Custom`] event.
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<Self::State>>);
+
+    /// Adds a custom event handler that will only run for [`Event::Custom`] events with a payload of type `T`
+    fn add_custom_event_handler_for<T, F>(&mut self, mut handler: F)
+    where
+        T: SerdeAny,
+        F: FnMut(&mut Self::State, &T) -> Result<CustomBufEventResult, Error> + 'static,
+    {
+        self.add_custom_event_handler(Box::new(move |state, event| {
+            match event.downcast_ref::<T>() {
+                Some(payload) => handler(state, payload),
+                None => Ok(CustomBufEventResult::Next),
+            }
+        }));
+    }
+}
+
 /// An eventmgr for tests, and as placeholder if you really don't need an event manager.
 #[derive(Copy, Clone, Debug)]
 pub struct NopEventManager<S> {
@@ -693,6 +780,13 @@ where
     }
 }
 
+impl<S> HasCustomEventHandlers for NopEventManager<S>
+where
+    S: State,
+{
+    fn add_custom_event_handler(&mut self, _handler: Box<CustomEventHandlerFn<Self::State>>) {}
+}
+
 impl<S> ProgressReporter for NopEventManager<S> where
     S: State + HasExecutions + HasLastReportTime + HasMetadata
 {
@@ -833,6 +927,17 @@ where
     }
 }
 
+impl<EM, M> HasCustomEventHandlers for MonitorTypedEventManager<EM, M>
+where
+    Self: UsesState,
+    EM: HasCustomEventHandlers<State = Self::State>,
+{
+    #[inline]
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<Self::State>>) {
+        self.inner.add_custom_event_handler(handler);
+    }
+}
+
 impl<EM, M> ProgressReporter for MonitorTypedEventManager<EM, M>
 where
     Self: UsesState,
@@ -941,12 +1046,17 @@ pub trait AdaptiveSerializer {
 #[cfg(test)]
 mod tests {
 
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
     use libafl_bolts::{current_time, tuples::tuple_list, Named};
+    use serde::{Deserialize, Serialize};
     use tuple_list::tuple_list_type;
 
     use crate::{
-        events::{Event, EventConfig},
+        events::{CustomEvent, Event, EventConfig},
         executors::ExitKind,
+        fuzzer::ExecuteInputResult,
         inputs::bytes::BytesInput,
         observers::StdMapObserver,
     };
@@ -968,6 +1078,7 @@ mod tests {
             input: i,
             observers_buf: Some(observers_buf),
             exit_kind: ExitKind::Ok,
+            exec_res: ExecuteInputResult::Corpus,
             corpus_size: 123,
             client_config: EventConfig::AlwaysUnique,
             time: current_time(),
@@ -988,4 +1099,33 @@ mod tests {
             _ => panic!("mistmatch"),
         };
     }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct DictSync(Vec<Vec<u8>>);
+    libafl_bolts::impl_serdeany!(DictSync);
+
+    #[test]
+    fn test_custom_event_serde() {
+        // # Safety
+        // No concurrency per testcase
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        unsafe {
+            DictSync::register();
+        }
+
+        let e: Event<BytesInput> = Event::Custom {
+            event: CustomEvent::new(DictSync(vec![b"token".to_vec()])),
+            phantom: PhantomData,
+        };
+        let serialized = postcard::to_allocvec(&e).unwrap();
+
+        match postcard::from_bytes::<Event<BytesInput>>(&serialized).unwrap() {
+            Event::Custom { event, .. } => {
+                assert!(event.is::<DictSync>());
+                assert_eq!(event.downcast_ref::<DictSync>().unwrap().0[0], b"token");
+                assert_eq!(event.name(), core::any::type_name::<DictSync>());
+            }
+            _ => panic!("mismatch"),
+        };
+    }
 }
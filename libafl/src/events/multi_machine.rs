@@ -3,6 +3,7 @@ use std::{
     boxed::Box,
     collections::HashMap,
     io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     process,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -15,11 +16,11 @@ use std::{
 use enumflags2::{bitflags, BitFlags};
 #[cfg(feature = "llmp_compression")]
 use libafl_bolts::compress::GzipCompressor;
-use libafl_bolts::{current_time, ownedref::OwnedRef, Error};
+use libafl_bolts::{current_nanos, current_time, ownedref::OwnedRef, Error};
 use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream, ToSocketAddrs},
+    net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
     runtime::Runtime,
     sync::RwLock,
     task::JoinHandle,
@@ -108,7 +109,8 @@ where
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
-/// A `NodeId` (unused for now)
+/// The id of a node in the multi-machine tree, assigned by the root when the tree is discovered.
+/// It also identifies the children connected to a node.
 pub struct NodeId(pub u64);
 
 impl NodeId {
@@ -151,6 +153,116 @@ pub struct NodeDescriptor<A> {
     /// Node flags
     #[builder(default_code = "BitFlags::default()")]
     pub flags: BitFlags<NodePolicy>, // The policy for shared messages between nodes.
+
+    /// Discover the tree on the local network instead of using `parent_addr`. Disabled by default.
+    #[builder(default = None)]
+    pub discovery: Option<NodeDiscovery>,
+
+    /// The UDP port used for the discovery. Defaults to 50001
+    #[builder(default = 50001)]
+    pub discovery_port: u16,
+
+    /// The [`NodeId`] of this node in the tree. Set by the discovery, if enabled.
+    #[builder(default = None)]
+    pub node_id: Option<NodeId>,
+}
+
+/// The role of a node in the automatic discovery of the multi-machine tree on the local network.
+///
+/// Nodes broadcast announcements on the LAN, and the root answers each of them with a [`NodeId`]
+/// and the address of the parent it should connect to, filling the tree level by level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeDiscovery {
+    /// This node is the root of the tree. It answers announcements and assigns the [`NodeId`]s.
+    Root {
+        /// The maximum number of children of each node in the tree
+        max_children: usize,
+    },
+    /// This node announces itself until the root assigns it a parent.
+    Node,
+}
+
+/// The messages exchanged on the discovery port.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum DiscoveryMsg {
+    /// A node looking for its place in the tree
+    Announce {
+        /// Identifies the announcing node, for retransmissions
+        nonce: u64,
+        /// The port on which the node accepts children, if any
+        listening_port: Option<u16>,
+    },
+    /// The answer of the root to an announcement
+    Assign {
+        /// The nonce of the announcement
+        nonce: u64,
+        /// The id assigned to the node
+        node_id: NodeId,
+        /// The ip of the parent, `None` for the root itself
+        parent_ip: Option<IpAddr>,
+        /// The listening port of the parent
+        parent_port: u16,
+    },
+}
+
+#[derive(Debug)]
+struct DiscoveredNode {
+    /// `None` for the root, reached at the address its answers come from
+    ip: Option<IpAddr>,
+    listening_port: Option<u16>,
+    nb_children: usize,
+}
+
+/// The tree built by the root from the announcements. The index of a node is its [`NodeId`].
+#[derive(Debug)]
+struct DiscoveryRegistry {
+    nodes: Vec<DiscoveredNode>,
+    assigned: HashMap<u64, DiscoveryMsg>,
+    max_children: usize,
+}
+
+impl DiscoveryRegistry {
+    fn new(root_listening_port: u16, max_children: usize) -> Self {
+        Self {
+            nodes: vec![DiscoveredNode {
+                ip: None,
+                listening_port: Some(root_listening_port),
+                nb_children: 0,
+            }],
+            assigned: HashMap::default(),
+            max_children: max_children.max(1),
+        }
+    }
+
+    /// Answer an announcement, placing the node under the first node that can take one more child.
+    /// If no node can, the root takes it. A repeated announcement gets the same answer.
+    fn assign(&mut self, nonce: u64, ip: IpAddr, listening_port: Option<u16>) -> DiscoveryMsg {
+        if let Some(msg) = self.assigned.get(&nonce) {
+            return msg.clone();
+        }
+
+        let parent_id = self
+            .nodes
+            .iter()
+            .position(|node| node.listening_port.is_some() && node.nb_children < self.max_children)
+            .unwrap_or(0);
+        let parent = &mut self.nodes[parent_id];
+        parent.nb_children += 1;
+
+        let msg = DiscoveryMsg::Assign {
+            nonce,
+            node_id: NodeId(self.nodes.len() as u64),
+            parent_ip: parent.ip,
+            parent_port: parent.listening_port.unwrap(),
+        };
+        self.nodes.push(DiscoveredNode {
+            ip: Some(ip),
+            listening_port,
+            nb_children: 0,
+        });
+        self.assigned.insert(nonce, msg.clone());
+        msg
+    }
 }
 
 /// A set of multi-machine `broker_hooks`.
@@ -174,6 +286,8 @@ where
     pub sender: TcpMultiMachineLlmpSenderHook<A, I>,
     /// The hooks
     pub receiver: TcpMultiMachineLlmpReceiverHook<A, I>,
+    /// The [`NodeId`] of this node in the tree, discovered or set in the [`NodeDescriptor`]
+    pub node_id: Option<NodeId>,
 }
 
 impl TcpMultiMachineHooks<(), NopInput> {
@@ -246,13 +360,12 @@ where
         let rt =
             Arc::new(Runtime::new().map_err(|_| Error::unknown("Tokio runtime spawning failed"))?);
 
-        unsafe {
-            TcpMultiMachineState::init::<I>(&state.clone(), &rt.clone())?;
-        }
+        let node_id = unsafe { TcpMultiMachineState::init::<I>(&state.clone(), &rt.clone())? };
 
         Ok(TcpMultiMachineHooks {
             sender: TcpMultiMachineLlmpSenderHook::new(state.clone(), rt.clone()),
             receiver: TcpMultiMachineLlmpReceiverHook::new(state, rt),
+            node_id,
         })
     }
 }
@@ -262,6 +375,7 @@ where
     A: Clone + Display + ToSocketAddrs + Send + Sync + 'static,
 {
     /// Initializes the Multi-Machine state.
+    /// Returns the [`NodeId`] of this node, either discovered or from the [`NodeDescriptor`].
     ///
     /// # Safety
     ///
@@ -270,38 +384,59 @@ where
     unsafe fn init<I: Input>(
         self_mutex: &Arc<RwLock<Self>>,
         rt: &Arc<Runtime>,
-    ) -> Result<(), Error> {
+    ) -> Result<Option<NodeId>, Error> {
         let node_descriptor =
             rt.block_on(async { self_mutex.read().await.node_descriptor.clone() });
 
+        // Find our place in the tree, if it should be discovered
+        let mut node_id = node_descriptor.node_id;
+        let mut discovered_parent = None;
+        match node_descriptor.discovery {
+            Some(NodeDiscovery::Root { max_children }) => {
+                let listening_port = node_descriptor.node_listening_port.ok_or_else(|| {
+                    Error::illegal_argument("The discovery root needs a node listening port")
+                })?;
+                let registry = DiscoveryRegistry::new(listening_port, max_children);
+                // Bind here, the nodes would wait for answers forever if the root can not listen
+                let discovery_port = node_descriptor.discovery_port;
+                let socket = rt
+                    .block_on(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, discovery_port)))
+                    .map_err(|e| {
+                        Error::os_error(e, format!("Error while binding to port {discovery_port}"))
+                    })?;
+                let _handle: JoinHandle<()> =
+                    rt.spawn(Self::answer_announcements(socket, registry));
+                node_id = Some(NodeId(0));
+            }
+            Some(NodeDiscovery::Node) if node_descriptor.parent_addr.is_none() => {
+                let (id, parent_addr) = rt.block_on(Self::discover_parent(
+                    node_descriptor.discovery_port,
+                    node_descriptor.node_listening_port,
+                    node_descriptor.timeout,
+                ))?;
+                node_id = Some(id);
+                discovered_parent = Some(parent_addr);
+            }
+            Some(NodeDiscovery::Node) => {
+                log::info!("A parent address is set, skipping the discovery.");
+            }
+            None => {}
+        }
+
         // Try to connect to the parent if we should
         rt.block_on(async {
             let parent_mutex = self_mutex.clone();
             let mut parent_lock = parent_mutex.write().await;
+            parent_lock.node_descriptor.node_id = node_id;
+            let timeout = parent_lock.node_descriptor.timeout;
 
             if let Some(parent_addr) = &parent_lock.node_descriptor.parent_addr {
-                let timeout = current_time() + parent_lock.node_descriptor.timeout;
-
-                parent_lock.parent = loop {
-                    log::debug!("Trying to connect to parent @ {}..", parent_addr);
-                    match TcpStream::connect(parent_addr).await {
-                        Ok(stream) => {
-                            log::debug!("Connected to parent @ {}", parent_addr);
-
-                            break Some(stream);
-                        }
-                        Err(e) => {
-                            if current_time() > timeout {
-                                return Err(Error::os_error(e, "Unable to connect to parent"));
-                            }
-                        }
-                    }
-
-                    time::sleep(Duration::from_secs(1)).await;
-                };
+                parent_lock.parent = Some(Self::connect_to_parent(parent_addr, timeout).await?);
+            } else if let Some(parent_addr) = &discovered_parent {
+                parent_lock.parent = Some(Self::connect_to_parent(parent_addr, timeout).await?);
             }
 
-            Ok(())
+            Ok::<(), Error>(())
         })?;
 
         // Now, setup the background tasks for the children to connect to
@@ -334,8 +469,9 @@ where
 
                             state_guard.children.insert(NodeId::new(), stream);
                             log::debug!(
-                                "[pid {}]{addr} added the child. nb children: {}",
+                                "[pid {}, {:?}]{addr} added the child. nb children: {}",
                                 process::id(),
+                                state_guard.node_descriptor.node_id,
                                 state_guard.children.len()
                             );
                         }
@@ -347,7 +483,127 @@ where
             });
         }
 
-        Ok(())
+        Ok(node_id)
+    }
+
+    /// Connect to the parent, retrying until `timeout` passed.
+    async fn connect_to_parent<T>(parent_addr: &T, timeout: Duration) -> Result<TcpStream, Error>
+    where
+        T: Display + ToSocketAddrs,
+    {
+        let timeout = current_time() + timeout;
+
+        loop {
+            log::debug!("Trying to connect to parent @ {}..", parent_addr);
+            match TcpStream::connect(parent_addr).await {
+                Ok(stream) => {
+                    log::debug!("Connected to parent @ {}", parent_addr);
+
+                    return Ok(stream);
+                }
+                Err(e) => {
+                    if current_time() > timeout {
+                        return Err(Error::os_error(e, "Unable to connect to parent"));
+                    }
+                }
+            }
+
+            time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Broadcast announcements on the discovery port until the root assigns us a [`NodeId`] and a parent.
+    async fn discover_parent(
+        discovery_port: u16,
+        listening_port: Option<u16>,
+        timeout: Duration,
+    ) -> Result<(NodeId, SocketAddr), Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        socket.set_broadcast(true)?;
+
+        let nonce = current_nanos() ^ (u64::from(process::id()) << 32);
+        let announce = postcard::to_allocvec(&DiscoveryMsg::Announce {
+            nonce,
+            listening_port,
+        })?;
+        let timeout = current_time() + timeout;
+        let mut buf = [0u8; 512];
+
+        loop {
+            log::debug!("Announcing ourselves on the discovery port {discovery_port}..");
+            socket
+                .send_to(&announce, (Ipv4Addr::BROADCAST, discovery_port))
+                .await?;
+
+            if let Ok(res) = time::timeout(Duration::from_secs(1), socket.recv_from(&mut buf)).await
+            {
+                let (len, root_addr) = res?;
+                if let Ok(DiscoveryMsg::Assign {
+                    nonce: assigned_nonce,
+                    node_id,
+                    parent_ip,
+                    parent_port,
+                }) = postcard::from_bytes(&buf[..len])
+                {
+                    if assigned_nonce == nonce {
+                        let parent_addr =
+                            SocketAddr::new(parent_ip.unwrap_or(root_addr.ip()), parent_port);
+                        log::info!(
+                            "Discovered the tree: we are {node_id:?}, parent @ {parent_addr}"
+                        );
+                        return Ok((node_id, parent_addr));
+                    }
+                }
+            }
+
+            if current_time() > timeout {
+                return Err(Error::unknown(
+                    "No answer from the discovery root, is it running on the local network?",
+                ));
+            }
+        }
+    }
+
+    /// The background task of the root, answering the announcements of the other nodes on `socket`.
+    async fn answer_announcements(socket: UdpSocket, mut registry: DiscoveryRegistry) {
+        let mut buf = [0u8; 512];
+
+        // The discovery loop. Should never fail.
+        loop {
+            let (len, addr) = match socket.recv_from(&mut buf).await {
+                Ok(res) => res,
+                Err(e) => {
+                    log::error!("Error while receiving an announcement {e:?}.");
+                    continue;
+                }
+            };
+            let Ok(DiscoveryMsg::Announce {
+                nonce,
+                listening_port,
+            }) = postcard::from_bytes(&buf[..len])
+            else {
+                continue;
+            };
+
+            let answer = registry.assign(nonce, addr.ip(), listening_port);
+            log::debug!("{addr} announced itself, answering {answer:?}");
+            let answer = match postcard::to_allocvec(&answer) {
+                Ok(answer) => answer,
+                Err(e) => {
+                    log::error!("Error while serializing the answer to {addr}: {e:?}.");
+                    continue;
+                }
+            };
+            if let Err(e) = socket.send_to(&answer, addr).await {
+                log::error!("Error while answering {addr}: {e:?}.");
+            }
+        }
+    }
+
+    /// The [`NodeId`] of this node in the tree, if known.
+    #[must_use]
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.node_descriptor.node_id
     }
 
     /// Add an event as past event.
@@ -618,3 +874,82 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+
+    use super::{
+        DiscoveryMsg, DiscoveryRegistry, NodeDescriptor, NodeDiscovery, NodeId,
+        TcpMultiMachineHooks,
+    };
+    use crate::inputs::NopInput;
+
+    fn assign(
+        registry: &mut DiscoveryRegistry,
+        nonce: u64,
+        last_byte: u8,
+    ) -> (NodeId, Option<IpAddr>) {
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_byte));
+        match registry.assign(nonce, ip, Some(50000)) {
+            DiscoveryMsg::Assign {
+                node_id, parent_ip, ..
+            } => (node_id, parent_ip),
+            DiscoveryMsg::Announce { .. } => panic!("mismatch"),
+        }
+    }
+
+    #[test]
+    fn test_discovery_tree() {
+        let mut registry = DiscoveryRegistry::new(50000, 2);
+
+        // The first two nodes are children of the root
+        assert_eq!(assign(&mut registry, 11, 1), (NodeId(1), None));
+        assert_eq!(assign(&mut registry, 12, 2), (NodeId(2), None));
+        // A retransmitted announcement gets the same answer
+        assert_eq!(assign(&mut registry, 11, 1), (NodeId(1), None));
+        // The next ones fill the next level
+        let node_1 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let node_2 = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)));
+        assert_eq!(assign(&mut registry, 13, 3), (NodeId(3), node_1));
+        assert_eq!(assign(&mut registry, 14, 4), (NodeId(4), node_1));
+        assert_eq!(assign(&mut registry, 15, 5), (NodeId(5), node_2));
+    }
+
+    /// A root of the discovery on `discovery_port`, with its children listening on a free port
+    fn discovery_root(discovery_port: u16) -> NodeDescriptor<SocketAddr> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let listening_port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        NodeDescriptor::builder()
+            .parent_addr(None)
+            .node_listening_port(Some(listening_port))
+            .discovery(Some(NodeDiscovery::Root { max_children: 2 }))
+            .discovery_port(discovery_port)
+            .build()
+    }
+
+    #[test]
+    fn test_discovery_root() {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).unwrap();
+        let discovery_port = socket.local_addr().unwrap().port();
+
+        // The discovery port is taken, the root can not answer
+        let res = unsafe {
+            TcpMultiMachineHooks::builder()
+                .node_descriptor(discovery_root(discovery_port))
+                .build::<NopInput>()
+        };
+        assert!(res.is_err());
+
+        drop(socket);
+        let hooks = unsafe {
+            TcpMultiMachineHooks::builder()
+                .node_descriptor(discovery_root(discovery_port))
+                .build::<NopInput>()
+        }
+        .unwrap();
+        assert_eq!(hooks.node_id, Some(NodeId(0)));
+    }
+}
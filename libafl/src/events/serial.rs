@@ -0,0 +1,661 @@
+//! An event manager for bare-metal fuzzers, sending its events over a byte stream,
+//! such as a UART, an RTT channel, or semihosting.
+//!
+//! The [`SerialEventManager`] runs in the target, and only needs `alloc`.
+//! On the host, a [`SerialBridge`] reads the events from the other end of the stream,
+//! and feeds them into a running llmp broker, so the target shows up in the broker's monitor
+//! like any other client, and shares its testcases with the other clients.
+//!
+//! Each event is serialized with `postcard`, followed by a 32-bit FNV-1a checksum,
+//! and framed with [COBS](https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing),
+//! so every frame ends at the next `0` byte.
+//! Frames that are too long, or fail the checksum, are dropped, and the stream resyncs on the
+//! next frame. Both ends must be built with the same `Event`-related features,
+//! such as `introspection`, for the serialized events to match.
+
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "std")]
+use core::time::Duration;
+use core::{fmt::Debug, marker::PhantomData};
+#[cfg(feature = "std")]
+use std::io::{ErrorKind, Read, Write};
+
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::{compress::GzipCompressor, llmp::LLMP_FLAG_COMPRESSED};
+#[cfg(feature = "std")]
+use libafl_bolts::{llmp::LlmpClient, shmem::ShMemProvider};
+
+#[cfg(feature = "llmp_compression")]
+use crate::events::llmp::COMPRESS_THRESHOLD;
+#[cfg(feature = "std")]
+use crate::{events::llmp::LLMP_TAG_EVENT_TO_BOTH, inputs::Input};
+use crate::{
+    events::{
+        CustomBufEventResult, CustomBufHandlerFn, Event, EventFirer, EventManager, EventManagerId,
+        EventProcessor, EventRestarter, HasCustomBufHandlers, HasEventManagerId, ProgressReporter,
+    },
+    inputs::UsesInput,
+    state::{HasExecutions, HasLastReportTime, State, Stoppable, UsesState},
+    Error, HasMetadata,
+};
+
+/// The default maximum length of a single frame, larger frames are dropped
+pub const DEFAULT_MAX_FRAME_LEN: usize = 64 * 1024;
+
+/// A byte stream to the other end, such as a UART, an RTT channel, or semihosting.
+pub trait SerialChannel {
+    /// Writes all of `buf` to the stream, blocking if needed
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error>;
+
+    /// Reads the bytes available right now into `buf`, without blocking.
+    /// Returns the number of bytes read, `0` if nothing is pending.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+}
+
+/// A [`SerialChannel`] over a [`Read`] + [`Write`] stream, such as a serial port device,
+/// or the TCP socket of a debug probe forwarding RTT.
+///
+/// Reads should not block for long: configure a short read timeout, or non-blocking mode,
+/// on the stream. Timeouts count as no bytes pending.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoSerialChannel<T> {
+    inner: T,
+}
+
+#[cfg(feature = "std")]
+impl<T> IoSerialChannel<T> {
+    /// Create a new [`IoSerialChannel`] over the given stream
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// The underlying stream
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// The underlying stream (mutable)
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> SerialChannel for IoSerialChannel<T>
+where
+    T: Read + Write,
+{
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(buf)?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self.inner.read(buf) {
+            Ok(len) => Ok(len),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The 32-bit FNV-1a hash, cheap enough for any target
+fn fnv1a32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Frames `payload` for a [`SerialChannel`]: appends the checksum, COBS-encodes it,
+/// and terminates the frame with a `0` byte.
+#[must_use]
+pub fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let checksum = fnv1a32(payload).to_le_bytes();
+    let len = payload.len() + checksum.len();
+    let mut frame = Vec::with_capacity(len + len / 254 + 2);
+
+    let mut code_idx = 0;
+    let mut code = 1_u8;
+    frame.push(0);
+    for byte in payload.iter().chain(checksum.iter()) {
+        if *byte == 0 {
+            frame[code_idx] = code;
+            code_idx = frame.len();
+            frame.push(0);
+            code = 1;
+        } else {
+            frame.push(*byte);
+            code += 1;
+            if code == 0xff {
+                frame[code_idx] = code;
+                code_idx = frame.len();
+                frame.push(0);
+                code = 1;
+            }
+        }
+    }
+    frame[code_idx] = code;
+    frame.push(0);
+    frame
+}
+
+/// Decodes a COBS-encoded frame, without its terminating `0`,
+/// returning the payload if the frame is well-formed and the checksum matches.
+fn decode_frame(frame: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(frame.len());
+    let mut idx = 0;
+    while idx < frame.len() {
+        let code = usize::from(frame[idx]);
+        if code == 0 {
+            return None;
+        }
+        idx += 1;
+        let end = idx + code - 1;
+        if end > frame.len() {
+            return None;
+        }
+        decoded.extend_from_slice(&frame[idx..end]);
+        idx = end;
+        if code < 0xff && idx < frame.len() {
+            decoded.push(0);
+        }
+    }
+
+    let payload_len = decoded.len().checked_sub(4)?;
+    let (payload, checksum) = decoded.split_at(payload_len);
+    if fnv1a32(payload).to_le_bytes() != checksum {
+        return None;
+    }
+    decoded.truncate(payload_len);
+    Some(decoded)
+}
+
+/// Splits a byte stream into the payloads of the frames written by [`encode_frame`]
+#[derive(Debug, Clone)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    max_frame_len: usize,
+    /// The current frame is too long, skip to its end
+    overflow: bool,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    /// Create a new [`FrameDecoder`], accepting frames of up to [`DEFAULT_MAX_FRAME_LEN`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_frame_len(DEFAULT_MAX_FRAME_LEN)
+    }
+
+    /// Create a new [`FrameDecoder`], accepting frames of up to `max_frame_len` encoded bytes
+    #[must_use]
+    pub fn with_max_frame_len(max_frame_len: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            max_frame_len,
+            overflow: false,
+        }
+    }
+
+    /// Feeds `bytes` from the stream, calling `on_payload` for each complete and valid frame
+    pub fn feed<F>(&mut self, bytes: &[u8], mut on_payload: F) -> Result<(), Error>
+    where
+        F: FnMut(Vec<u8>) -> Result<(), Error>,
+    {
+        for byte in bytes {
+            if *byte != 0 {
+                if self.buf.len() < self.max_frame_len {
+                    self.buf.push(*byte);
+                } else {
+                    self.overflow = true;
+                }
+                continue;
+            }
+
+            if self.overflow {
+                log::warn!(
+                    "Dropping a serial frame longer than {} bytes",
+                    self.max_frame_len
+                );
+            } else if !self.buf.is_empty() {
+                match decode_frame(&self.buf) {
+                    Some(payload) => on_payload(payload)?,
+                    None => log::warn!("Dropping a corrupted serial frame"),
+                }
+            }
+            self.buf.clear();
+            self.overflow = false;
+        }
+        Ok(())
+    }
+}
+
+/// An event manager for a fuzzer running in a bare-metal target, without an OS.
+/// It sends all events over a [`SerialChannel`], to a [`SerialBridge`] on the host,
+/// see the [module docs](self).
+///
+/// From the host, it only receives [`Event::Stop`] and [`Event::CustomBuf`].
+pub struct SerialEventManager<CH, S>
+where
+    S: UsesInput,
+{
+    channel: CH,
+    decoder: FrameDecoder,
+    custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
+    phantom: PhantomData<S>,
+}
+
+impl<CH, S> Debug for SerialEventManager<CH, S>
+where
+    CH: Debug,
+    S: UsesInput,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SerialEventManager")
+            .field("channel", &self.channel)
+            .field("decoder", &self.decoder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<CH, S> SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: UsesInput,
+{
+    /// Create a new [`SerialEventManager`], sending its events over `channel`
+    pub fn new(channel: CH) -> Self {
+        Self::with_decoder(channel, FrameDecoder::new())
+    }
+
+    /// Create a new [`SerialEventManager`], decoding incoming frames with `decoder`,
+    /// for example to accept only short frames on targets with little memory
+    pub fn with_decoder(channel: CH, decoder: FrameDecoder) -> Self {
+        Self {
+            channel,
+            decoder,
+            custom_buf_handlers: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// The channel of this manager
+    pub fn channel(&self) -> &CH {
+        &self.channel
+    }
+
+    /// The channel of this manager (mutable)
+    pub fn channel_mut(&mut self) -> &mut CH {
+        &mut self.channel
+    }
+}
+
+impl<CH, S> UsesState for SerialEventManager<CH, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<CH, S> EventFirer for SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: State,
+{
+    fn should_send(&self) -> bool {
+        true
+    }
+
+    fn fire(
+        &mut self,
+        _state: &mut Self::State,
+        event: Event<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        let serialized = postcard::to_allocvec(&event)?;
+        self.channel.write_all(&encode_frame(&serialized))
+    }
+}
+
+impl<CH, S> EventRestarter for SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: State,
+{
+}
+
+impl<CH, E, S, Z> EventProcessor<E, Z> for SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: State,
+{
+    fn process(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _executor: &mut E,
+    ) -> Result<usize, Error> {
+        let mut events = Vec::new();
+        let mut buf = [0_u8; 64];
+        loop {
+            let len = self.channel.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            self.decoder.feed(&buf[..len], |payload| {
+                events.push(postcard::from_bytes::<Event<S::Input>>(&payload)?);
+                Ok(())
+            })?;
+        }
+
+        let count = events.len();
+        for event in events {
+            match event {
+                Event::Stop => state.request_stop(),
+                Event::CustomBuf { buf, tag } => {
+                    for handler in &mut self.custom_buf_handlers {
+                        if handler(state, &tag, &buf)? == CustomBufEventResult::Handled {
+                            break;
+                        }
+                    }
+                }
+                _ => log::debug!("Ignoring a {} event from the host", event.name()),
+            }
+        }
+        Ok(count)
+    }
+
+    fn on_shutdown(&mut self) -> Result<(), Error> {
+        self.send_exiting()
+    }
+}
+
+impl<CH, E, S, Z> EventManager<E, Z> for SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: State + HasExecutions + HasLastReportTime + HasMetadata,
+{
+}
+
+impl<CH, S> HasCustomBufHandlers for SerialEventManager<CH, S>
+where
+    S: State,
+{
+    fn add_custom_buf_handler(
+        &mut self,
+        handler: Box<
+            dyn FnMut(&mut Self::State, &str, &[u8]) -> Result<CustomBufEventResult, Error>,
+        >,
+    ) {
+        self.custom_buf_handlers.push(handler);
+    }
+}
+
+impl<CH, S> ProgressReporter for SerialEventManager<CH, S>
+where
+    CH: SerialChannel,
+    S: State + HasExecutions + HasMetadata + HasLastReportTime,
+{
+}
+
+impl<CH, S> HasEventManagerId for SerialEventManager<CH, S>
+where
+    S: UsesInput + Stoppable,
+{
+    fn mgr_id(&self) -> EventManagerId {
+        EventManagerId(0)
+    }
+}
+
+/// The host side of a [`SerialEventManager`]: an llmp client forwarding the events of the
+/// target to the broker, and [`Event::Stop`] and [`Event::CustomBuf`] from the broker to the target.
+///
+/// Start the broker first, for example with [`crate::events::Launcher`] or a standalone
+/// [`libafl_bolts::llmp::LlmpBroker`], then run the bridge in its own process or thread.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SerialBridge<CH, I, SP>
+where
+    SP: ShMemProvider,
+{
+    channel: CH,
+    decoder: FrameDecoder,
+    llmp: LlmpClient<SP>,
+    #[cfg(feature = "llmp_compression")]
+    compressor: GzipCompressor,
+    phantom: PhantomData<I>,
+}
+
+#[cfg(feature = "std")]
+impl<CH, I, SP> SerialBridge<CH, I, SP>
+where
+    CH: SerialChannel,
+    I: Input,
+    SP: ShMemProvider,
+{
+    /// Create a new [`SerialBridge`] between `channel` and the broker listening on `broker_port`
+    pub fn connect(channel: CH, shmem_provider: SP, broker_port: u16) -> Result<Self, Error> {
+        Ok(Self {
+            channel,
+            decoder: FrameDecoder::new(),
+            llmp: LlmpClient::create_attach_to_tcp(shmem_provider, broker_port)?,
+            #[cfg(feature = "llmp_compression")]
+            compressor: GzipCompressor::with_threshold(COMPRESS_THRESHOLD),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Forwards the pending events in both directions, without blocking.
+    /// Returns the number of forwarded events, and if the broker asked to stop.
+    pub fn forward(&mut self) -> Result<(usize, bool), Error> {
+        let mut forwarded = 0;
+
+        let mut payloads = Vec::new();
+        let mut buf = [0_u8; 4096];
+        loop {
+            let len = self.channel.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            self.decoder.feed(&buf[..len], |payload| {
+                payloads.push(payload);
+                Ok(())
+            })?;
+        }
+        for payload in payloads {
+            // The broker fails on events it cannot deserialize, so only forward valid ones
+            match postcard::from_bytes::<Event<I>>(&payload) {
+                Ok(_) => {
+                    self.llmp.send_buf(LLMP_TAG_EVENT_TO_BOTH, &payload)?;
+                    forwarded += 1;
+                }
+                Err(e) => log::warn!("Dropping an event the target sent: {e}"),
+            }
+        }
+
+        let mut stop = false;
+        while let Some((_client_id, tag, flags, msg)) = self.llmp.recv_buf_with_flags()? {
+            if tag != LLMP_TAG_EVENT_TO_BOTH {
+                continue;
+            }
+            #[cfg(not(feature = "llmp_compression"))]
+            let event_bytes = msg;
+            #[cfg(feature = "llmp_compression")]
+            let compressed;
+            #[cfg(feature = "llmp_compression")]
+            let event_bytes = if flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+                compressed = self.compressor.decompress(msg)?;
+                &compressed
+            } else {
+                msg
+            };
+            match postcard::from_bytes::<Event<I>>(event_bytes)? {
+                Event::Stop => stop = true,
+                Event::CustomBuf { .. } => {
+                    self.channel.write_all(&encode_frame(event_bytes))?;
+                    forwarded += 1;
+                }
+                _ => {}
+            }
+        }
+        if stop {
+            let serialized = postcard::to_allocvec(&Event::<I>::Stop)?;
+            self.channel.write_all(&encode_frame(&serialized))?;
+            forwarded += 1;
+        }
+        Ok((forwarded, stop))
+    }
+
+    /// Forwards events until the broker asks to stop,
+    /// sleeping for `idle_sleep` whenever there is nothing to forward
+    pub fn run(&mut self, idle_sleep: Duration) -> Result<(), Error> {
+        loop {
+            let (forwarded, stop) = self.forward()?;
+            if stop {
+                return Ok(());
+            }
+            if forwarded == 0 {
+                std::thread::sleep(idle_sleep);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<CH, I, SP> SerialBridge<CH, I, SP>
+where
+    SP: ShMemProvider,
+{
+    /// Only accept frames of up to `max_frame_len` encoded bytes from the target
+    #[must_use]
+    pub fn with_max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.decoder = FrameDecoder::with_max_frame_len(max_frame_len);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::VecDeque, vec::Vec};
+    use core::marker::PhantomData;
+
+    use libafl_bolts::rands::StdRand;
+
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::{
+            serial::{encode_frame, FrameDecoder, SerialChannel, SerialEventManager},
+            Event, EventFirer, EventProcessor, LogSeverity,
+        },
+        inputs::BytesInput,
+        state::{StdState, Stoppable},
+        Error,
+    };
+
+    /// A loopback channel, reading what the test pushes to `rx`
+    #[derive(Debug, Default)]
+    struct TestChannel {
+        tx: Vec<u8>,
+        rx: VecDeque<u8>,
+    }
+
+    impl SerialChannel for TestChannel {
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+            self.tx.extend_from_slice(buf);
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            let len = buf.len().min(self.rx.len());
+            for (dst, src) in buf.iter_mut().zip(self.rx.drain(..len)) {
+                *dst = src;
+            }
+            Ok(len)
+        }
+    }
+
+    fn decode_all(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut payloads = Vec::new();
+        FrameDecoder::new()
+            .feed(bytes, |payload| {
+                payloads.push(payload);
+                Ok(())
+            })
+            .unwrap();
+        payloads
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let payloads = [
+            Vec::new(),
+            vec![0],
+            vec![0, 0, 1, 0],
+            (0..=255).collect::<Vec<u8>>(),
+            vec![0xaa; 600],
+        ];
+        let mut stream = Vec::new();
+        for payload in &payloads {
+            let frame = encode_frame(payload);
+            assert_eq!(
+                frame.iter().position(|byte| *byte == 0),
+                Some(frame.len() - 1)
+            );
+            stream.extend(frame);
+        }
+        assert_eq!(decode_all(&stream), payloads);
+
+        // A corrupted frame is dropped, the next one still decodes
+        let mut corrupted = encode_frame(&[1, 2, 3]);
+        corrupted[2] ^= 0x40;
+        corrupted.extend(encode_frame(&[4, 5, 6]));
+        assert_eq!(decode_all(&corrupted), vec![vec![4, 5, 6]]);
+
+        // As is a frame that is too long
+        let mut decoder = FrameDecoder::with_max_frame_len(8);
+        let mut stream = encode_frame(&[1; 16]);
+        stream.extend(encode_frame(&[2]));
+        let mut payloads = Vec::new();
+        decoder
+            .feed(&stream, |payload| {
+                payloads.push(payload);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(payloads, vec![vec![2]]);
+    }
+
+    #[test]
+    fn test_serial_event_manager() {
+        let mut state: StdState<_, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<_>> =
+            StdState::nop().unwrap();
+        let mut mgr = SerialEventManager::new(TestChannel::default());
+
+        mgr.fire(
+            &mut state,
+            Event::Log {
+                severity_level: LogSeverity::Info,
+                message: "hello from the target".into(),
+                phantom: PhantomData,
+            },
+        )
+        .unwrap();
+        let payloads = decode_all(&mgr.channel().tx);
+        assert_eq!(payloads.len(), 1);
+        let event: Event<BytesInput> = postcard::from_bytes(&payloads[0]).unwrap();
+        assert!(matches!(event, Event::Log { message, .. } if message == "hello from the target"));
+
+        let stop = postcard::to_allocvec(&Event::<BytesInput>::Stop).unwrap();
+        mgr.channel_mut().rx.extend(encode_frame(&stop));
+        let processed =
+            EventProcessor::<(), ()>::process(&mut mgr, &mut (), &mut state, &mut ()).unwrap();
+        assert_eq!(processed, 1);
+        assert!(state.stop_requested());
+    }
+}
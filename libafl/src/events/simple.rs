@@ -20,7 +20,10 @@ use libafl_bolts::{os::CTRL_C_EXIT, shmem::ShMemProvider, staterestore::StateRes
 #[cfg(feature = "std")]
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{CustomBufEventResult, CustomBufHandlerFn, HasCustomBufHandlers, ProgressReporter};
+use super::{
+    CustomBufEventResult, CustomBufHandlerFn, CustomEventHandlerFn, HasCustomBufHandlers,
+    HasCustomEventHandlers, ProgressReporter,
+};
 #[cfg(all(unix, feature = "std", not(miri)))]
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 use crate::{
@@ -56,6 +59,8 @@ where
     events: Vec<Event<S::Input>>,
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
+    /// The custom event handler
+    custom_event_handlers: Vec<Box<CustomEventHandlerFn<S>>>,
     phantom: PhantomData<S>,
 }
 
@@ -155,6 +160,16 @@ where
     }
 }
 
+impl<MT, S> HasCustomEventHandlers for SimpleEventManager<MT, S>
+where
+    MT: Monitor,
+    S: State,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<S>>) {
+        self.custom_event_handlers.push(handler);
+    }
+}
+
 impl<MT, S> ProgressReporter for SimpleEventManager<MT, S>
 where
     MT: Monitor,
@@ -195,6 +210,7 @@ where
             monitor,
             events: vec![],
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
             phantom: PhantomData,
         }
     }
@@ -268,6 +284,7 @@ where
                 Ok(BrokerEventResult::Handled)
             }
             Event::CustomBuf { .. } => Ok(BrokerEventResult::Forward),
+            Event::Custom { .. } => Ok(BrokerEventResult::Forward),
             Event::Stop => Ok(BrokerEventResult::Forward),
         }
     }
@@ -282,6 +299,14 @@ where
                 }
                 Ok(())
             }
+            Event::Custom { event, .. } => {
+                for handler in &mut self.custom_event_handlers {
+                    if handler(state, &event)? == CustomBufEventResult::Handled {
+                        break;
+                    }
+                }
+                Ok(())
+            }
             Event::Stop => {
                 state.request_stop();
                 Ok(())
@@ -411,6 +436,18 @@ where
     }
 }
 
+#[cfg(feature = "std")]
+impl<MT, S, SP> HasCustomEventHandlers for SimpleRestartingEventManager<MT, S, SP>
+where
+    MT: Monitor,
+    S: State,
+    SP: ShMemProvider,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<S>>) {
+        self.simple_event_mgr.add_custom_event_handler(handler);
+    }
+}
+
 #[cfg(feature = "std")]
 impl<MT, S, SP> ProgressReporter for SimpleRestartingEventManager<MT, S, SP>
 where
@@ -1,4 +1,9 @@
 //! TCP-backed event manager for scalable multi-processed fuzzing
+//!
+//! Unlike llmp, it does not need shared memory between the clients and the broker,
+//! so the clients can run on other hosts.
+//! Clients can reconnect to a restarted broker, see [`TcpEventManagerBuilder::reconnect_timeout`],
+//! and keep idle connections alive, see [`TcpEventManagerBuilder::keepalive`].
 
 use alloc::{boxed::Box, vec::Vec};
 use core::{
@@ -8,10 +13,12 @@ use core::{
     time::Duration,
 };
 use std::{
+    collections::HashMap,
     env,
     io::{ErrorKind, Read, Write},
     net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
     sync::Arc,
+    thread,
 };
 
 #[cfg(feature = "tcp_compression")]
@@ -26,7 +33,7 @@ use libafl_bolts::os::unix_signals::setup_signal_handler;
 use libafl_bolts::os::CTRL_C_EXIT;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 use libafl_bolts::os::{fork, ForkResult};
-use libafl_bolts::{shmem::ShMemProvider, tuples::tuple_list, ClientId};
+use libafl_bolts::{current_time, shmem::ShMemProvider, tuples::tuple_list, ClientId};
 #[cfg(feature = "std")]
 use libafl_bolts::{shmem::StdShMemProvider, staterestore::StateRestorer};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -38,7 +45,9 @@ use tokio::{
 #[cfg(feature = "std")]
 use typed_builder::TypedBuilder;
 
-use super::{CustomBufEventResult, CustomBufHandlerFn};
+use super::{
+    CustomBufEventResult, CustomBufHandlerFn, CustomEventHandlerFn, HasCustomEventHandlers,
+};
 #[cfg(all(unix, feature = "std", not(miri)))]
 use crate::events::EVENTMGR_SIGHANDLER_STATE;
 use crate::{
@@ -82,6 +91,9 @@ where
 
 const UNDEFINED_CLIENT_ID: ClientId = ClientId(0xffffffff);
 
+/// The time between two attempts to reconnect to the broker
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(500);
+
 impl<I, MT> TcpEventBroker<I, MT>
 where
     I: Input,
@@ -127,8 +139,11 @@ where
         let listener = tokio::net::TcpListener::from_std(listener)?;
 
         let tokio_broker = spawn(async move {
-            let mut recv_handles: Vec<JoinHandle<_>> = vec![];
-            let mut receivers: Vec<Arc<tokio::sync::Mutex<broadcast::Receiver<_>>>> = vec![];
+            let mut recv_handles: HashMap<ClientId, JoinHandle<_>> = HashMap::new();
+            let mut receivers: HashMap<ClientId, Arc<tokio::sync::Mutex<broadcast::Receiver<_>>>> =
+                HashMap::new();
+            // ClientIds for this broker start at 0.
+            let mut next_client_id = 0_u32;
 
             loop {
                 let mut reached_max = false;
@@ -154,11 +169,13 @@ where
                     if reached_max {
                         (UNDEFINED_CLIENT_ID, false) // Dumb id
                     } else {
-                        // ClientIds for this broker start at 0.
-                        (ClientId(recv_handles.len().try_into().unwrap()), false)
+                        next_client_id += 1;
+                        (ClientId(next_client_id - 1), false)
                     }
                 } else {
-                    (this_client_id, true)
+                    // A client of a previous run of this broker keeps its id
+                    next_client_id = next_client_id.max(this_client_id.0 + 1);
+                    (this_client_id, recv_handles.contains_key(&this_client_id))
                 };
 
                 let this_client_id_bytes = this_client_id.0.to_le_bytes();
@@ -184,6 +201,7 @@ where
                         }
 
                         let mut len = u32::from_le_bytes(len_buf);
+                        let keepalive = len == 0;
                         // we forward the sender id as well, so we add 4 bytes to the message length
                         len += 4;
 
@@ -202,25 +220,27 @@ where
                             return;
                         }
 
+                        if keepalive {
+                            log::debug!("TCP Manager - keepalive from {this_client_id:?}");
+                            continue;
+                        }
+
                         log::debug!("TCP Manager - len: {len:?} - {buf:?}");
                         tx_inner.send(buf).await.expect("Could not send");
                     }
                 };
 
-                let client_idx = this_client_id.0 as usize;
-
                 // Keep all handles around.
-                if is_old {
-                    recv_handles[client_idx].abort();
-                    recv_handles[client_idx] = spawn(handle);
-                } else {
-                    recv_handles.push(spawn(handle));
+                if let Some(old_handle) = recv_handles.insert(this_client_id, spawn(handle)) {
+                    old_handle.abort();
+                }
+                if !is_old {
                     // Get old messages only if new
                     let rx_inner = Arc::new(tokio::sync::Mutex::new(rx.resubscribe()));
-                    receivers.push(rx_inner.clone());
+                    receivers.insert(this_client_id, rx_inner);
                 }
 
-                let rx_inner = receivers[client_idx].clone();
+                let rx_inner = receivers[&this_client_id].clone();
 
                 // The forwarding end. No need to keep a handle to this (TODO: unless they don't quit/get stuck?)
                 spawn(async move {
@@ -399,7 +419,9 @@ where
                 log::log!((*severity_level).into(), "{message}");
                 Ok(BrokerEventResult::Handled)
             }
-            Event::CustomBuf { .. } | Event::Stop => Ok(BrokerEventResult::Forward),
+            Event::CustomBuf { .. } | Event::Custom { .. } | Event::Stop => {
+                Ok(BrokerEventResult::Forward)
+            }
             //_ => Ok(BrokerEventResult::Forward),
         }
     }
@@ -418,10 +440,18 @@ where
     hooks: EMH,
     /// The TCP stream for inter process communication
     tcp: TcpStream,
+    /// The addresses of the broker, to reconnect to
+    broker_addrs: Vec<SocketAddr>,
+    /// Send a keepalive after this time without other messages
+    keepalive: Option<Duration>,
+    /// Try to reconnect to the broker for this long, once the connection is lost
+    reconnect_timeout: Option<Duration>,
     /// Our `CientId`
     client_id: ClientId,
     /// The custom buf handler
     custom_buf_handlers: Vec<Box<CustomBufHandlerFn<S>>>,
+    /// The custom event handler
+    custom_event_handlers: Vec<Box<CustomEventHandlerFn<S>>>,
     #[cfg(feature = "tcp_compression")]
     compressor: GzipCompressor,
     /// The configuration defines this specific fuzzer.
@@ -446,6 +476,8 @@ where
 #[derive(Debug, Copy, Clone)]
 pub struct TcpEventManagerBuilder<EMH, S> {
     throttle: Option<Duration>,
+    keepalive: Option<Duration>,
+    reconnect_timeout: Option<Duration>,
     hooks: EMH,
     phantom: PhantomData<S>,
 }
@@ -462,6 +494,8 @@ impl<S> TcpEventManagerBuilder<(), S> {
     pub fn new() -> Self {
         Self {
             throttle: None,
+            keepalive: None,
+            reconnect_timeout: None,
             hooks: (),
             phantom: PhantomData,
         }
@@ -472,6 +506,8 @@ impl<S> TcpEventManagerBuilder<(), S> {
     pub fn hooks<EMH>(self, hooks: EMH) -> TcpEventManagerBuilder<EMH, S> {
         TcpEventManagerBuilder {
             throttle: self.throttle,
+            keepalive: self.keepalive,
+            reconnect_timeout: self.reconnect_timeout,
             hooks,
             phantom: PhantomData,
         }
@@ -490,6 +526,26 @@ where
         self
     }
 
+    /// Send a keepalive to the broker after `keepalive` without other messages,
+    /// so that a lost connection is noticed, and, with a [`Self::reconnect_timeout`],
+    /// reestablished, even while the client has nothing to report.
+    /// The keepalive is sent while processing events.
+    #[must_use]
+    pub fn keepalive(mut self, keepalive: Duration) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
+    /// Once the connection to the broker is lost, for example because the broker restarted,
+    /// try to reconnect for up to `timeout`, keeping our [`ClientId`].
+    /// Messages sent by other clients in the meantime are lost.
+    /// Without a timeout, a lost connection is an error.
+    #[must_use]
+    pub fn reconnect_timeout(mut self, timeout: Duration) -> Self {
+        self.reconnect_timeout = Some(timeout);
+        self
+    }
+
     /// Create a manager from a raw TCP client with hooks
     pub fn build_from_client<A: ToSocketAddrs>(
         self,
@@ -497,15 +553,8 @@ where
         client_id: ClientId,
         configuration: EventConfig,
     ) -> Result<TcpEventManager<EMH, S>, Error> {
-        let mut tcp = TcpStream::connect(addr)?;
-
-        let mut our_client_id_buf = client_id.0.to_le_bytes();
-        tcp.write_all(&our_client_id_buf)
-            .expect("Cannot write to the broker");
-
-        tcp.read_exact(&mut our_client_id_buf)
-            .expect("Cannot read from the broker");
-        let client_id = ClientId(u32::from_le_bytes(our_client_id_buf));
+        let broker_addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+        let (tcp, client_id) = connect_to_broker(&broker_addrs, client_id)?;
 
         log::info!("Our client id: {client_id:?}");
 
@@ -514,12 +563,16 @@ where
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             tcp,
+            broker_addrs,
+            keepalive: self.keepalive,
+            reconnect_timeout: self.reconnect_timeout,
             client_id,
             #[cfg(feature = "tcp_compression")]
             compressor: GzipCompressor::new(),
             configuration,
             phantom: PhantomData,
             custom_buf_handlers: vec![],
+            custom_event_handlers: vec![],
         })
     }
 
@@ -551,6 +604,33 @@ where
     }
 }
 
+/// Connects to the broker at `addrs`, announcing our `client_id`, or [`UNDEFINED_CLIENT_ID`] for
+/// a new client, and returns the connection and the [`ClientId`] assigned by the broker.
+fn connect_to_broker(
+    addrs: &[SocketAddr],
+    client_id: ClientId,
+) -> Result<(TcpStream, ClientId), Error> {
+    let mut tcp = TcpStream::connect(addrs)?;
+
+    let mut our_client_id_buf = client_id.0.to_le_bytes();
+    tcp.write_all(&our_client_id_buf)?;
+    tcp.read_exact(&mut our_client_id_buf)?;
+
+    Ok((tcp, ClientId(u32::from_le_bytes(our_client_id_buf))))
+}
+
+/// If this error means that the broker closed the connection
+fn is_connection_lost(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::UnexpectedEof
+    )
+}
+
 impl<EMH, S> core::fmt::Debug for TcpEventManager<EMH, S>
 where
     EMH: EventManagerHooksTuple<S>,
@@ -558,7 +638,10 @@ where
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut debug_struct = f.debug_struct("TcpEventManager");
-        let debug = debug_struct.field("tcp", &self.tcp);
+        let debug = debug_struct
+            .field("tcp", &self.tcp)
+            .field("keepalive", &self.keepalive)
+            .field("reconnect_timeout", &self.reconnect_timeout);
         //.field("custom_buf_handlers", &self.custom_buf_handlers)
         #[cfg(feature = "tcp_compression")]
         let debug = debug.field("compressor", &self.compressor);
@@ -651,6 +734,13 @@ where
                     }
                 }
             }
+            Event::Custom { event, .. } => {
+                for handler in &mut self.custom_event_handlers {
+                    if handler(state, &event)? == CustomBufEventResult::Handled {
+                        break;
+                    }
+                }
+            }
             Event::Stop => {
                 state.request_stop();
             }
@@ -671,6 +761,99 @@ where
     EMH: EventManagerHooksTuple<S>,
     S: State,
 {
+    /// Reconnects to the broker, keeping our [`ClientId`], see [`TcpEventManagerBuilder::reconnect_timeout`]
+    fn reconnect(&mut self, cause: &std::io::Error) -> Result<(), Error> {
+        let Some(timeout) = self.reconnect_timeout else {
+            return Err(Error::illegal_state(format!(
+                "Lost the connection to the broker: {cause}"
+            )));
+        };
+        log::warn!("Lost the connection to the broker ({cause}), reconnecting");
+
+        let start = current_time();
+        loop {
+            match connect_to_broker(&self.broker_addrs, self.client_id) {
+                Ok((tcp, client_id)) => {
+                    if client_id != self.client_id {
+                        return Err(Error::illegal_state(format!(
+                            "The broker assigned {client_id:?} to us on reconnect, instead of {:?}",
+                            self.client_id
+                        )));
+                    }
+                    log::info!("Reconnected to the broker as {client_id:?}");
+                    self.tcp = tcp;
+                    return Ok(());
+                }
+                Err(e) if current_time().saturating_sub(start) < timeout => {
+                    log::debug!("Reconnecting to the broker failed: {e}");
+                    thread::sleep(RECONNECT_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends a message to the broker, reconnecting once if the connection was lost.
+    /// An empty message is a keepalive.
+    fn send_msg(&mut self, msg: &[u8]) -> Result<(), Error> {
+        let size = u32::try_from(msg.len())?;
+        let mut buf = Vec::with_capacity(8 + msg.len());
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&self.client_id.0.to_le_bytes());
+        buf.extend_from_slice(msg);
+
+        match self.tcp.write_all(&buf) {
+            Err(e) if is_connection_lost(&e) => {
+                self.reconnect(&e)?;
+                self.tcp.write_all(&buf)?;
+            }
+            res => res?,
+        }
+        self.last_sent = current_time();
+        Ok(())
+    }
+
+    /// Reads all pending messages from the broker, with the [`ClientId`] of their sender.
+    /// Reconnects if the connection was lost.
+    fn recv_msgs(&mut self) -> Result<Vec<(ClientId, Vec<u8>)>, Error> {
+        let mut len_buf = [0_u8; 4];
+        let mut msgs = vec![];
+
+        self.tcp.set_nonblocking(true)?;
+        // read all pending messages
+        let res = loop {
+            match self.tcp.read_exact(&mut len_buf) {
+                Ok(()) => {
+                    self.tcp.set_nonblocking(false)?;
+                    let len = u32::from_le_bytes(len_buf);
+                    let mut buf = vec![0_u8; 4_usize + len as usize];
+                    if let Err(e) = self.tcp.read_exact(&mut buf) {
+                        break Err(e);
+                    }
+
+                    let mut client_id_buf = [0_u8; 4];
+                    client_id_buf.copy_from_slice(&buf[..4]);
+                    buf.drain(..4);
+                    msgs.push((ClientId(u32::from_le_bytes(client_id_buf)), buf));
+
+                    self.tcp.set_nonblocking(true)?;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    // no new data on the socket
+                    break Ok(());
+                }
+                Err(e) => break Err(e),
+            }
+        };
+
+        match res {
+            Ok(()) => self.tcp.set_nonblocking(false)?,
+            Err(e) if is_connection_lost(&e) => self.reconnect(&e)?,
+            Err(e) => return Err(e.into()),
+        }
+        Ok(msgs)
+    }
+
     /// Send information that this client is exiting.
     /// The other side may free up all allocated memory.
     /// We are no longer allowed to send anything afterwards.
@@ -712,13 +895,7 @@ where
         #[cfg(feature = "tcp_compression")]
         let serialized = self.compressor.compress(&serialized);
 
-        let size = u32::try_from(serialized.len())?;
-        self.tcp.write_all(&size.to_le_bytes())?;
-        self.tcp.write_all(&self.client_id.0.to_le_bytes())?;
-        self.tcp.write_all(&serialized)?;
-
-        self.last_sent = libafl_bolts::current_time();
-        Ok(())
+        self.send_msg(&serialized)
     }
 
     fn configuration(&self) -> EventConfig {
@@ -755,56 +932,30 @@ where
         state: &mut Self::State,
         executor: &mut E,
     ) -> Result<usize, Error> {
+        if let Some(keepalive) = self.keepalive {
+            if current_time().saturating_sub(self.last_sent) >= keepalive {
+                self.send_msg(&[])?;
+            }
+        }
+
         // TODO: Get around local event copy by moving handle_in_client
         let self_id = self.client_id;
-        let mut len_buf = [0_u8; 4];
         let mut count = 0;
+        for (other_client_id, buf) in self.recv_msgs()? {
+            assert!(
+                self_id != other_client_id,
+                "Own ID should never have been sent by the broker"
+            );
+            log::info!("{self_id:?} (from {other_client_id:?}) Received: {buf:?}");
 
-        self.tcp.set_nonblocking(true).expect("set to non-blocking");
-
-        // read all pending messages
-        loop {
-            match self.tcp.read_exact(&mut len_buf) {
-                Ok(()) => {
-                    self.tcp.set_nonblocking(false).expect("set to blocking");
-                    let len = u32::from_le_bytes(len_buf);
-                    let mut buf = vec![0_u8; 4_usize + len as usize];
-                    self.tcp.read_exact(&mut buf)?;
-
-                    let mut client_id_buf = [0_u8; 4];
-                    client_id_buf.copy_from_slice(&buf[..4]);
-
-                    let other_client_id = ClientId(u32::from_le_bytes(client_id_buf));
-
-                    self.tcp.set_nonblocking(true).expect("set to non-blocking");
-                    if self_id == other_client_id {
-                        panic!("Own ID should never have been sent by the broker");
-                    } else {
-                        log::info!("{self_id:?} (from {other_client_id:?}) Received: {buf:?}");
-
-                        let buf = &buf[4..];
-                        #[cfg(feature = "tcp_compression")]
-                        let buf = self.compressor.decompress(buf)?;
+            #[cfg(feature = "tcp_compression")]
+            let buf = self.compressor.decompress(&buf)?;
 
-                        // make decompressed vec and slice compatible
-                        #[allow(clippy::needless_borrow)]
-                        let event = postcard::from_bytes(&buf)?;
+            let event = postcard::from_bytes(&buf)?;
 
-                        self.handle_in_client(fuzzer, executor, state, other_client_id, event)?;
-                        count += 1;
-                    }
-                }
-
-                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                    // no new data on the socket
-                    break;
-                }
-                Err(e) => {
-                    panic!("Unexpected error {e:?}");
-                }
-            }
+            self.handle_in_client(fuzzer, executor, state, other_client_id, event)?;
+            count += 1;
         }
-        self.tcp.set_nonblocking(false).expect("set to blocking");
 
         Ok(count)
     }
@@ -839,6 +990,16 @@ where
     }
 }
 
+impl<EMH, S> HasCustomEventHandlers for TcpEventManager<EMH, S>
+where
+    EMH: EventManagerHooksTuple<S>,
+    S: State,
+{
+    fn add_custom_event_handler(&mut self, handler: Box<CustomEventHandlerFn<S>>) {
+        self.custom_event_handlers.push(handler);
+    }
+}
+
 impl<EMH, S> ProgressReporter for TcpEventManager<EMH, S>
 where
     EMH: EventManagerHooksTuple<S>,
@@ -1142,6 +1303,14 @@ where
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = true)]
     serialize_state: bool,
+    /// Clients send a keepalive to the broker after this time without other messages,
+    /// see [`TcpEventManagerBuilder::keepalive`]
+    #[builder(default = None)]
+    keepalive: Option<Duration>,
+    /// Clients try to reconnect to a restarted broker for this long,
+    /// see [`TcpEventManagerBuilder::reconnect_timeout`]
+    #[builder(default = None)]
+    reconnect_timeout: Option<Duration>,
     /// The hooks for `handle_in_client`
     hooks: EMH,
     #[builder(setter(skip), default = PhantomData)]
@@ -1157,6 +1326,18 @@ where
     S: State + HasExecutions + HasMetadata + HasImported,
     MT: Monitor + Clone,
 {
+    /// The builder for the managers of the clients
+    fn manager_builder(&self) -> TcpEventManagerBuilder<EMH, S> {
+        let mut builder = TcpEventManagerBuilder::new().hooks(self.hooks);
+        if let Some(keepalive) = self.keepalive {
+            builder = builder.keepalive(keepalive);
+        }
+        if let Some(timeout) = self.reconnect_timeout {
+            builder = builder.reconnect_timeout(timeout);
+        }
+        builder
+    }
+
     /// Launch the restarting manager
     pub fn launch(&mut self) -> Result<(Option<S>, TcpRestartingEventManager<EMH, S, SP>), Error> {
         // We start ourself as child process to actually fuzz
@@ -1192,13 +1373,11 @@ where
                         }
                         Err(Error::OsError(..)) => {
                             // port was likely already bound
-                            let mgr = TcpEventManagerBuilder::new()
-                                .hooks(self.hooks)
-                                .build_from_client(
-                                    &("127.0.0.1", self.broker_port),
-                                    UNDEFINED_CLIENT_ID,
-                                    self.configuration,
-                                )?;
+                            let mgr = self.manager_builder().build_from_client(
+                                &("127.0.0.1", self.broker_port),
+                                UNDEFINED_CLIENT_ID,
+                                self.configuration,
+                            )?;
                             (mgr, None)
                         }
                         Err(e) => {
@@ -1217,9 +1396,11 @@ where
                 }
                 TcpManagerKind::Client { cpu_core } => {
                     // We are a client
-                    let mgr = TcpEventManagerBuilder::new()
-                        .hooks(self.hooks)
-                        .build_on_port(self.broker_port, UNDEFINED_CLIENT_ID, self.configuration)?;
+                    let mgr = self.manager_builder().build_on_port(
+                        self.broker_port,
+                        UNDEFINED_CLIENT_ID,
+                        self.configuration,
+                    )?;
 
                     (mgr, cpu_core)
                 }
@@ -1339,9 +1520,11 @@ where
             (
                 state_opt,
                 TcpRestartingEventManager::with_save_state(
-                    TcpEventManagerBuilder::new()
-                        .hooks(self.hooks)
-                        .build_on_port(self.broker_port, this_id, self.configuration)?,
+                    self.manager_builder().build_on_port(
+                        self.broker_port,
+                        this_id,
+                        self.configuration,
+                    )?,
                     staterestorer,
                     self.serialize_state,
                 ),
@@ -1349,13 +1532,11 @@ where
         } else {
             log::info!("First run. Let's set it all up");
             // Mgr to send and receive msgs from/to all other fuzzer instances
-            let mgr = TcpEventManagerBuilder::new()
-                .hooks(self.hooks)
-                .build_existing_from_env(
-                    &("127.0.0.1", self.broker_port),
-                    _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                    self.configuration,
-                )?;
+            let mgr = self.manager_builder().build_existing_from_env(
+                &("127.0.0.1", self.broker_port),
+                _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                self.configuration,
+            )?;
 
             (
                 None,
@@ -1378,3 +1559,92 @@ where
         Ok((state, mgr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use libafl_bolts::{rands::StdRand, ClientId};
+
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::{
+            tcp::{TcpEventManagerBuilder, UNDEFINED_CLIENT_ID},
+            Event, EventConfig, EventFirer,
+        },
+        inputs::BytesInput,
+        state::StdState,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_tcp_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // A broker that restarts right after the first handshake
+        let broker = thread::spawn(move || {
+            let mut id_buf = [0_u8; 4];
+
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut id_buf).unwrap();
+            assert_eq!(ClientId(u32::from_le_bytes(id_buf)), UNDEFINED_CLIENT_ID);
+            stream.write_all(&7_u32.to_le_bytes()).unwrap();
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_exact(&mut id_buf).unwrap();
+            assert_eq!(u32::from_le_bytes(id_buf), 7);
+            stream.write_all(&id_buf).unwrap();
+
+            let mut len_buf = [0_u8; 4];
+            stream.read_exact(&mut len_buf).unwrap();
+            let mut msg = vec![0_u8; 4 + u32::from_le_bytes(len_buf) as usize];
+            stream.read_exact(&mut msg).unwrap();
+            msg
+        });
+
+        let mut mgr = TcpEventManagerBuilder::<(), TestState>::new()
+            .reconnect_timeout(Duration::from_secs(10))
+            .build_from_client(&addr, UNDEFINED_CLIENT_ID, EventConfig::AlwaysUnique)
+            .unwrap();
+        assert_eq!(mgr.client_id, ClientId(7));
+
+        // Notices the closed connection, and reconnects
+        let first_port = mgr.tcp.local_addr().unwrap().port();
+        while mgr.tcp.local_addr().unwrap().port() == first_port {
+            assert!(mgr.recv_msgs().unwrap().is_empty());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut state = TestState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        mgr.fire(&mut state, Event::Stop).unwrap();
+
+        let msg = broker.join().unwrap();
+        assert_eq!(&msg[..4], &7_u32.to_le_bytes());
+        let payload = &msg[4..];
+        #[cfg(feature = "tcp_compression")]
+        let payload = &libafl_bolts::compress::GzipCompressor::new()
+            .decompress(payload)
+            .unwrap();
+        assert!(matches!(
+            postcard::from_bytes::<Event<BytesInput>>(payload).unwrap(),
+            Event::Stop
+        ));
+    }
+}
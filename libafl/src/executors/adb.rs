@@ -0,0 +1,534 @@
+//! The [`AdbExecutor`] runs targets on an Android device or emulator, orchestrated over `adb`.
+//!
+//! For each run, the input is pushed to the device, the [`AdbTarget`] is started with it, and the
+//! coverage map file the target wrote on the device is pulled back into the coverage map of the fuzzer.
+//! The target has to write its coverage map to [`AdbExecutor::device_map_path`], for example by
+//! flushing a shared memory map to it at exit.
+//!
+//! Devices reboot, or drop off `adb`, during long campaigns. If a run fails because the device is gone,
+//! the executor waits for the device to boot again, sets it up again, and retries the run.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    io::Read,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    ownedref::OwnedMutSlice,
+    tuples::RefIndexable,
+    AsSlice,
+};
+use wait_timeout::ChildExt;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The marker printed after a [`AdbTarget::Binary`] exited, followed by its exit code
+const ADB_EXIT_MARKER: &str = "__LIBAFL_EXIT__=";
+
+/// The interval in which the [`AdbExecutor`] polls a rebooting device
+const ADB_BOOT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The target the [`AdbExecutor`] runs on the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdbTarget {
+    /// A native binary on the device, started with the given arguments.
+    /// An argument `@@` is replaced by the path of the input on the device.
+    ///
+    /// Exits by a signal are crashes.
+    Binary {
+        /// The path of the binary on the device
+        path: String,
+        /// The arguments of the binary
+        args: Vec<String>,
+    },
+    /// An instrumented app, started with `am instrument -w -e input <input path> <component>`.
+    ///
+    /// Runs where the process of the app crashed are crashes.
+    Instrumentation {
+        /// The instrumentation component, `package/runner`
+        component: String,
+    },
+}
+
+/// Quotes an argument for the device shell
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// An [`Executor`] running an [`AdbTarget`] on an Android device, see the [module-level docs](self).
+pub struct AdbExecutor<OT, S> {
+    target: AdbTarget,
+    adb: PathBuf,
+    serial: Option<String>,
+    device_dir: String,
+    push_files: Vec<(PathBuf, String)>,
+    input_file: InputFile,
+    map: OwnedMutSlice<'static, u8>,
+    timeout: Duration,
+    boot_timeout: Duration,
+    max_retries: usize,
+    is_set_up: bool,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for AdbExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdbExecutor")
+            .field("target", &self.target)
+            .field("adb", &self.adb)
+            .field("serial", &self.serial)
+            .field("device_dir", &self.device_dir)
+            .field("push_files", &self.push_files)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> AdbExecutor<OT, S> {
+    /// Creates a new [`AdbExecutor`] for the given [`AdbTarget`], pulling the coverage into `map`.
+    ///
+    /// By default, the device is the only device connected to `adb`, files are stored in
+    /// `/data/local/tmp/libafl` on the device, and a run times out after 5 seconds.
+    pub fn new(
+        target: AdbTarget,
+        map: OwnedMutSlice<'static, u8>,
+        observers: OT,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            target,
+            adb: PathBuf::from("adb"),
+            serial: None,
+            device_dir: "/data/local/tmp/libafl".to_string(),
+            push_files: Vec::new(),
+            input_file: InputFile::create(get_unique_std_input_file())?,
+            map,
+            timeout: Duration::from_secs(5),
+            boot_timeout: Duration::from_secs(300),
+            max_retries: 3,
+            is_set_up: false,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets the path to the `adb` binary; default is `adb`
+    #[must_use]
+    pub fn adb_path<P>(mut self, adb: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.adb = adb.into();
+        self
+    }
+
+    /// Selects the device with the given serial, as listed by `adb devices`
+    #[must_use]
+    pub fn serial<T>(mut self, serial: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.serial = Some(serial.into());
+        self
+    }
+
+    /// Sets the directory on the device holding the input and the coverage map
+    #[must_use]
+    pub fn device_dir<T>(mut self, device_dir: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.device_dir = device_dir.into();
+        self
+    }
+
+    /// Pushes the local file to `remote` on the device, before the first run and after each reboot.
+    ///
+    /// Use it for the target binary and its libraries, which may be gone after a reboot.
+    #[must_use]
+    pub fn push_file<P, T>(mut self, local: P, remote: T) -> Self
+    where
+        P: Into<PathBuf>,
+        T: Into<String>,
+    {
+        self.push_files.push((local.into(), remote.into()));
+        self
+    }
+
+    /// Sets how long to wait for a rebooting device; default is 5 minutes
+    #[must_use]
+    pub fn boot_timeout(mut self, boot_timeout: Duration) -> Self {
+        self.boot_timeout = boot_timeout;
+        self
+    }
+
+    /// Sets how often a run is retried after the device was lost; default is 3
+    #[must_use]
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// The path of the input on the device
+    #[must_use]
+    pub fn device_input_path(&self) -> String {
+        format!("{}/input", self.device_dir)
+    }
+
+    /// The path of the coverage map the target writes on the device
+    #[must_use]
+    pub fn device_map_path(&self) -> String {
+        format!("{}/map", self.device_dir)
+    }
+
+    /// A [`Command`] running `adb` for the selected device
+    fn adb_command(&self) -> Command {
+        let mut command = Command::new(&self.adb);
+        if let Some(serial) = &self.serial {
+            command.arg("-s").arg(serial);
+        }
+        command.stdin(Stdio::null());
+        command
+    }
+
+    /// Runs `adb` with the given arguments, returning its stdout
+    fn adb<I, T>(&self, args: I) -> Result<Vec<u8>, Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<std::ffi::OsStr>,
+    {
+        let mut command = self.adb_command();
+        command.args(args);
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(Error::unknown(format!(
+                "{command:?} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Prepares the device: creates the device directory and pushes the files
+    fn set_up(&mut self) -> Result<(), Error> {
+        self.adb([
+            "shell",
+            &format!("mkdir -p {}", shell_quote(&self.device_dir)),
+        ])?;
+        for (local, remote) in &self.push_files {
+            self.adb(["push".as_ref(), local.as_os_str(), remote.as_ref()])?;
+        }
+        self.is_set_up = true;
+        Ok(())
+    }
+
+    /// Waits until the device is back and booted
+    fn wait_for_device(&self) -> Result<(), Error> {
+        let start = Instant::now();
+        while start.elapsed() < self.boot_timeout {
+            let booted = self
+                .adb(["shell", "getprop sys.boot_completed"])
+                .is_ok_and(|out| String::from_utf8_lossy(&out).trim() == "1");
+            if booted {
+                return Ok(());
+            }
+            thread::sleep(ADB_BOOT_POLL_INTERVAL);
+        }
+        Err(Error::unknown(format!(
+            "The device did not boot within {:?}",
+            self.boot_timeout
+        )))
+    }
+
+    /// The device shell command running the target
+    fn target_command(&self) -> String {
+        let input_path = self.device_input_path();
+        match &self.target {
+            AdbTarget::Binary { path, args } => {
+                let mut command = shell_quote(path);
+                for arg in args {
+                    command.push(' ');
+                    if arg == "@@" {
+                        command.push_str(&shell_quote(&input_path));
+                    } else {
+                        command.push_str(&shell_quote(arg));
+                    }
+                }
+                // `adb shell` of old devices does not forward the exit code
+                format!("{command} </dev/null >/dev/null 2>&1; echo {ADB_EXIT_MARKER}$?")
+            }
+            AdbTarget::Instrumentation { component } => format!(
+                "am instrument -w -e input {} {}",
+                shell_quote(&input_path),
+                shell_quote(component)
+            ),
+        }
+    }
+
+    /// Kills the target on the device, after a timeout
+    fn kill_target(&self) {
+        let command = match &self.target {
+            AdbTarget::Binary { path, .. } => format!("pkill -9 -f {}", shell_quote(path)),
+            AdbTarget::Instrumentation { component } => {
+                let package = component.split('/').next().unwrap_or(component);
+                format!("am force-stop {}", shell_quote(package))
+            }
+        };
+        drop(self.adb(["shell", &command]));
+    }
+
+    /// Runs the target once on the device.
+    ///
+    /// Fails if the device is gone, or the output of the target is garbled.
+    fn run_once(&mut self) -> Result<ExitKind, Error> {
+        let input_path = self.device_input_path();
+        let map_path = self.device_map_path();
+        self.adb([
+            "push".as_ref(),
+            self.input_file.path.as_os_str(),
+            input_path.as_ref(),
+        ])?;
+        self.adb(["shell", &format!("rm -f {}", shell_quote(&map_path))])?;
+
+        let mut child = self
+            .adb_command()
+            .arg("shell")
+            .arg(self.target_command())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let Some(status) = child.wait_timeout(self.timeout)? else {
+            drop(child.kill());
+            drop(child.wait());
+            self.kill_target();
+            return Ok(ExitKind::Timeout);
+        };
+        let mut output = String::new();
+        if let Some(stdout) = child.stdout.as_mut() {
+            stdout.read_to_string(&mut output)?;
+        }
+
+        let exit_kind = match &self.target {
+            AdbTarget::Binary { .. } => {
+                let code = output
+                    .lines()
+                    .find_map(|line| line.strip_prefix(ADB_EXIT_MARKER))
+                    .and_then(|code| code.trim().parse::<i32>().ok())
+                    .ok_or_else(|| {
+                        Error::unknown(format!("The target did not report its exit ({status})"))
+                    })?;
+                // The shell reports deaths by signals as 128 + the signal
+                if code > 128 {
+                    ExitKind::Crash
+                } else {
+                    ExitKind::Ok
+                }
+            }
+            AdbTarget::Instrumentation { .. } => {
+                if output.contains("Process crashed") {
+                    ExitKind::Crash
+                } else if output.contains("INSTRUMENTATION_CODE") {
+                    ExitKind::Ok
+                } else {
+                    return Err(Error::unknown(format!(
+                        "The instrumentation did not finish ({status})"
+                    )));
+                }
+            }
+        };
+
+        let coverage = self.adb([
+            "exec-out",
+            &format!("cat {} 2>/dev/null", shell_quote(&map_path)),
+        ])?;
+        let len = coverage.len().min(self.map.len());
+        self.map[..len].copy_from_slice(&coverage[..len]);
+        self.map[len..].fill(0);
+
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> UsesState for AdbExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for AdbExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+        self.observers.pre_exec_child_all(state, input)?;
+        let mut retries = 0;
+        let exit_kind = loop {
+            let result = if self.is_set_up {
+                self.run_once()
+            } else {
+                self.set_up().and_then(|()| self.run_once())
+            };
+            match result {
+                Ok(exit_kind) => break exit_kind,
+                Err(err) if retries < self.max_retries => {
+                    log::warn!("Lost the device ({err}), waiting for it to come back");
+                    retries += 1;
+                    self.is_set_up = false;
+                    self.wait_for_device()?;
+                }
+                Err(err) => return Err(err),
+            }
+        };
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for AdbExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S> HasObservers for AdbExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use alloc::string::ToString;
+    use std::{env, fs, os::unix::fs::PermissionsExt, process};
+
+    use libafl_bolts::{ownedref::OwnedMutSlice, tuples::tuple_list};
+
+    use super::{AdbExecutor, AdbTarget};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    /// A fake `adb`, running everything on the host
+    const FAKE_ADB: &str = r#"#!/bin/sh
+[ "$1" = "-s" ] && shift 2
+cmd="$1"; shift
+case "$cmd" in
+    push) cp "$1" "$2" ;;
+    shell|exec-out) sh -c "$*" ;;
+esac
+"#;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_adb_executor() {
+        let dir = env::temp_dir().join(format!("libafl_adb_test_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let adb = dir.join("adb");
+        fs::write(&adb, FAKE_ADB).unwrap();
+        fs::set_permissions(&adb, fs::Permissions::from_mode(0o755)).unwrap();
+        let device_dir = dir.join("device").to_str().unwrap().to_string();
+
+        // Writes the first byte of the input as coverage, and crashes on `!`
+        let target = AdbTarget::Binary {
+            path: "sh".into(),
+            args: vec![
+                "-c".into(),
+                format!(
+                    "head -c 1 \"$0\" > {device_dir}/map; grep -q '!' \"$0\" && kill -SEGV $$; exit 0"
+                ),
+                "@@".into(),
+            ],
+        };
+        let mut map = vec![0_u8; 4];
+        let map_ptr = map.as_mut_ptr();
+        let mut executor = AdbExecutor::new(
+            target,
+            unsafe { OwnedMutSlice::from_raw_parts_mut(map_ptr, 4) },
+            tuple_list!(),
+        )
+        .unwrap()
+        .adb_path(&adb)
+        .serial("emulator-5554")
+        .device_dir(&device_dir);
+
+        let mut state = NopState::new();
+        let mut run = |executor: &mut AdbExecutor<_, _>, input: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::default(),
+                    &BytesInput::new(input.to_vec()),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(run(&mut executor, b"ab"), ExitKind::Ok);
+        assert_eq!(executor.map[..2], [b'a', 0]);
+        assert_eq!(run(&mut executor, b"x!"), ExitKind::Crash);
+        assert_eq!(executor.map[0], b'x');
+        drop(executor);
+        assert_eq!(map[0], b'x');
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+//! The [`BatchedExecutor`] passes a whole batch of inputs to a single invocation of the target.
+//!
+//! For very fast targets, the overhead of each invocation, such as syscalls or process management,
+//! dominates the runtime. A batched harness iterates over all inputs of the batch instead, and reports
+//! the end of each input. The executor then snapshots and resets the coverage map, so the coverage is
+//! still attributed to each single input.
+//! Like the [`crate::executors::InProcessExecutor`], it installs the in-process crash and timeout
+//! handlers. Between two inputs, it points them to the next input and restarts the timeout, so a crash
+//! or a timeout is attributed to the input of the batch that caused it.
+//! Use it with the [`crate::stages::BatchMutationalStage`], which evaluates every input of a batch.
+
+use alloc::vec::Vec;
+use core::{
+    ffi::c_void,
+    fmt::{self, Debug, Formatter},
+    ptr,
+    time::Duration,
+};
+
+use libafl_bolts::tuples::{tuple_list, Handle, MatchNameRef, RefIndexable};
+
+use crate::{
+    corpus::Corpus,
+    events::{EventFirer, EventRestarter},
+    executors::{
+        hooks::{inprocess::InProcessHooks, ExecutorHook},
+        inprocess::{inner::GenericInProcessExecutorInner, HasInProcessHooks},
+        Executor, ExitKind, HasObservers,
+    },
+    feedbacks::Feedback,
+    fuzzer::HasObjective,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+    state::{HasCorpus, HasExecutions, HasSolutions, State, UsesState},
+    Error,
+};
+
+/// An executor that can run a whole batch of inputs at once
+pub trait HasBatchExecution<EM, Z>: Executor<EM, Z> + HasObservers {
+    /// Runs the batch of inputs, returning the [`ExitKind`] of each input that ran.
+    ///
+    /// If the target stopped early, e.g., because it timed out, fewer [`ExitKind`]s than inputs are
+    /// returned, and the last one belongs to the input that stopped the target.
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        inputs: &[<Self::State as UsesInput>::Input],
+    ) -> Result<Vec<ExitKind>, Error>;
+
+    /// Loads the observations of the `idx`th input of the last batch into the observers
+    fn load_batch_entry(&mut self, idx: usize) -> Result<(), Error>;
+}
+
+/// An [`Executor`] running a batched harness in-process, see the [module-level docs](self).
+///
+/// The harness gets all inputs of the batch, and calls the given function with the [`ExitKind`]
+/// after each input. Its return value is the [`ExitKind`] of the input that stopped the target
+/// early, if any. Only the map observer is split per input, all other observers see the whole batch.
+pub struct BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    S: State,
+{
+    harness_fn: H,
+    inner: GenericInProcessExecutorInner<(), OT, S>,
+    map_handle: Handle<C>,
+    exit_kinds: Vec<ExitKind>,
+    /// The map snapshots, kept between batches to reuse their allocations
+    snapshots: Vec<Vec<C::Entry>>,
+}
+
+impl<C, H, OT, S> Debug for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    OT: Debug,
+    S: State,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchedExecutor")
+            .field("inner", &self.inner)
+            .field("map_handle", &self.map_handle)
+            .field("batch_len", &self.exit_kinds.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, H, OT, S> BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    H: FnMut(&[S::Input], &mut dyn FnMut(ExitKind)) -> ExitKind,
+    OT: ObserversTuple<S::Input, S>,
+    S: State + HasExecutions + HasSolutions + HasCorpus,
+    <S as HasSolutions>::Solutions: Corpus<Input = S::Input>, //delete me
+    <<S as HasCorpus>::Corpus as Corpus>::Input: Clone,       //delete me
+{
+    /// Creates a new [`BatchedExecutor`] with the default timeout (5 sec) per input,
+    /// splitting the coverage of the map observer with the given handle
+    pub fn new<EM, OF, Z>(
+        harness_fn: H,
+        observers: OT,
+        map_handle: Handle<C>,
+        fuzzer: &mut Z,
+        state: &mut S,
+        event_mgr: &mut EM,
+    ) -> Result<Self, Error>
+    where
+        Self: Executor<EM, Z, State = S>,
+        EM: EventFirer<State = S> + EventRestarter,
+        OF: Feedback<EM, S::Input, OT, S>,
+        Z: HasObjective<Objective = OF, State = S>,
+    {
+        Self::with_timeout(
+            harness_fn,
+            observers,
+            map_handle,
+            fuzzer,
+            state,
+            event_mgr,
+            Duration::from_millis(5000),
+        )
+    }
+
+    /// Creates a new [`BatchedExecutor`], with the given timeout for each input of a batch.
+    ///
+    /// This may return an error on unix, if signal handler setup fails
+    pub fn with_timeout<EM, OF, Z>(
+        harness_fn: H,
+        observers: OT,
+        map_handle: Handle<C>,
+        fuzzer: &mut Z,
+        state: &mut S,
+        event_mgr: &mut EM,
+        timeout: Duration,
+    ) -> Result<Self, Error>
+    where
+        Self: Executor<EM, Z, State = S>,
+        EM: EventFirer<State = S> + EventRestarter,
+        OF: Feedback<EM, S::Input, OT, S>,
+        Z: HasObjective<Objective = OF, State = S>,
+    {
+        let inner = GenericInProcessExecutorInner::with_timeout_generic::<Self, EM, OF, Z>(
+            tuple_list!(),
+            observers,
+            fuzzer,
+            state,
+            event_mgr,
+            timeout,
+        )?;
+
+        Ok(Self {
+            harness_fn,
+            inner,
+            map_handle,
+            exit_kinds: Vec::new(),
+            snapshots: Vec::new(),
+        })
+    }
+}
+
+/// Takes a snapshot of the map observer at the end of the `idx`th input, and resets it for the next one
+fn snapshot_map<C, OT>(
+    observers: &mut OT,
+    map_handle: &Handle<C>,
+    snapshots: &mut Vec<Vec<C::Entry>>,
+    idx: usize,
+) where
+    C: MapObserver,
+    OT: MatchNameRef,
+{
+    let map = observers
+        .get_mut(map_handle)
+        .expect("A BatchedExecutor needs its map observer");
+    if snapshots.len() <= idx {
+        snapshots.push(Vec::with_capacity(map.usable_count()));
+    }
+    let snapshot = &mut snapshots[idx];
+    snapshot.clear();
+    snapshot.extend((0..map.usable_count()).map(|i| map.get(i)));
+    map.reset_map().unwrap();
+}
+
+impl<C, EM, H, OT, S, Z> HasBatchExecution<EM, Z> for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    EM: UsesState<State = S>,
+    H: FnMut(&[S::Input], &mut dyn FnMut(ExitKind)) -> ExitKind,
+    OT: ObserversTuple<S::Input, S>,
+    S: State + HasExecutions,
+    Z: UsesState<State = S>,
+{
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        inputs: &[S::Input],
+    ) -> Result<Vec<ExitKind>, Error> {
+        self.exit_kinds.clear();
+        let Some(first) = inputs.first() else {
+            return Ok(Vec::new());
+        };
+
+        let executor_ptr = ptr::from_ref(self) as *const c_void;
+        *state.executions_mut() += 1;
+        unsafe {
+            self.inner
+                .enter_target(fuzzer, state, mgr, first, executor_ptr);
+        }
+        self.inner.inprocess_hooks_mut().pre_exec(state, first);
+
+        let inner = &mut self.inner;
+        let map_handle = &self.map_handle;
+        let snapshots = &mut self.snapshots;
+        let exit_kinds = &mut self.exit_kinds;
+        let last_exit_kind = (self.harness_fn)(inputs, &mut |exit_kind| {
+            let idx = exit_kinds.len();
+            inner.inprocess_hooks_mut().post_exec(state, &inputs[idx]);
+            snapshot_map(&mut *inner.observers_mut(), map_handle, snapshots, idx);
+            exit_kinds.push(exit_kind);
+
+            // Point the crash and timeout handlers to the next input
+            if let Some(next) = inputs.get(idx + 1) {
+                *state.executions_mut() += 1;
+                unsafe {
+                    inner.enter_target(fuzzer, state, mgr, next, executor_ptr);
+                }
+                inner.inprocess_hooks_mut().pre_exec(state, next);
+            }
+        });
+
+        let idx = self.exit_kinds.len();
+        if idx < inputs.len() {
+            self.inner
+                .inprocess_hooks_mut()
+                .post_exec(state, &inputs[idx]);
+            snapshot_map(
+                &mut *self.inner.observers_mut(),
+                &self.map_handle,
+                &mut self.snapshots,
+                idx,
+            );
+            self.exit_kinds.push(last_exit_kind);
+        }
+        self.inner.leave_target(fuzzer, state, mgr, first);
+
+        Ok(self.exit_kinds.clone())
+    }
+
+    fn load_batch_entry(&mut self, idx: usize) -> Result<(), Error> {
+        let snapshot = self
+            .snapshots
+            .get(idx)
+            .filter(|_| idx < self.exit_kinds.len())
+            .ok_or_else(|| Error::key_not_found(format!("No input {idx} in the last batch")))?;
+        let mut observers = self.inner.observers_mut();
+        let map = observers
+            .get_mut(&self.map_handle)
+            .ok_or_else(|| Error::key_not_found("A BatchedExecutor needs its map observer"))?;
+        for (i, entry) in snapshot.iter().enumerate() {
+            map.set(i, *entry);
+        }
+        Ok(())
+    }
+}
+
+impl<C, H, OT, S> UsesState for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    S: State,
+{
+    type State = S;
+}
+
+impl<C, EM, H, OT, S, Z> Executor<EM, Z> for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    EM: UsesState<State = S>,
+    H: FnMut(&[S::Input], &mut dyn FnMut(ExitKind)) -> ExitKind,
+    OT: ObserversTuple<S::Input, S>,
+    S: State + HasExecutions,
+    Z: UsesState<State = S>,
+{
+    /// Runs a batch of a single input
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        let exit_kinds = self.run_batch(fuzzer, state, mgr, core::slice::from_ref(input))?;
+        self.load_batch_entry(0)?;
+        Ok(exit_kinds[0])
+    }
+}
+
+impl<C, H, OT, S> HasObservers for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    OT: ObserversTuple<S::Input, S>,
+    S: State,
+{
+    type Observers = OT;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.inner.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.inner.observers_mut()
+    }
+}
+
+impl<C, H, OT, S> HasInProcessHooks<S> for BatchedExecutor<C, H, OT, S>
+where
+    C: MapObserver,
+    S: State,
+{
+    /// the timeout handler
+    #[inline]
+    fn inprocess_hooks(&self) -> &InProcessHooks<S> {
+        self.inner.inprocess_hooks()
+    }
+
+    /// the timeout handler
+    #[inline]
+    fn inprocess_hooks_mut(&mut self) -> &mut InProcessHooks<S> {
+        self.inner.inprocess_hooks_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr::addr_of_mut;
+
+    use libafl_bolts::{
+        rands::StdRand,
+        tuples::{tuple_list, Handled},
+    };
+
+    use super::{BatchedExecutor, HasBatchExecution};
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::{ExitKind, HasObservers},
+        feedbacks::CrashFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{MapObserver, StdMapObserver},
+        schedulers::QueueScheduler,
+        state::{HasExecutions, StdState},
+        StdFuzzer,
+    };
+
+    static mut BATCH_MAP: [u8; 4] = [0; 4];
+
+    #[test]
+    fn test_batched_executor() {
+        let observer = unsafe { StdMapObserver::new("map", &mut *addr_of_mut!(BATCH_MAP)) };
+        let handle = observer.handle();
+        let mut feedback = tuple_list!();
+        let mut objective = CrashFeedback::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut mgr = NopEventManager::new();
+
+        let mut executor = BatchedExecutor::new(
+            |inputs: &[BytesInput], finish_input: &mut dyn FnMut(ExitKind)| {
+                for input in inputs {
+                    let idx = input.bytes()[0] as usize;
+                    unsafe { (*addr_of_mut!(BATCH_MAP))[idx] = 1 };
+                    if idx == 3 {
+                        return ExitKind::Timeout;
+                    }
+                    finish_input(ExitKind::Ok);
+                }
+                ExitKind::Ok
+            },
+            tuple_list!(observer),
+            handle.clone(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        let inputs = [1, 2, 3, 0].map(|idx| BytesInput::new(vec![idx]));
+        let exit_kinds = executor
+            .run_batch(&mut fuzzer, &mut state, &mut mgr, &inputs)
+            .unwrap();
+        assert_eq!(exit_kinds, [ExitKind::Ok, ExitKind::Ok, ExitKind::Timeout]);
+        assert_eq!(*state.executions(), 3);
+
+        for (idx, covered) in [1, 2, 3].into_iter().enumerate() {
+            executor.load_batch_entry(idx).unwrap();
+            let observers = executor.observers();
+            let map = &observers[&handle];
+            assert_eq!(map.count_bytes(), 1);
+            assert_eq!(map.get(covered), 1);
+        }
+        executor.load_batch_entry(3).unwrap_err();
+
+        // A shorter batch reuses the snapshots, but only exposes its own inputs
+        let exit_kinds = executor
+            .run_batch(&mut fuzzer, &mut state, &mut mgr, &inputs[3..])
+            .unwrap();
+        assert_eq!(exit_kinds, [ExitKind::Ok]);
+        executor.load_batch_entry(0).unwrap();
+        assert_eq!(executor.observers()[&handle].get(0), 1);
+        executor.load_batch_entry(1).unwrap_err();
+    }
+}
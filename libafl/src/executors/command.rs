@@ -12,7 +12,7 @@ use std::{
 };
 use std::{
     ffi::{OsStr, OsString},
-    io::{Read, Write},
+    io::Write,
     os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
@@ -48,7 +48,7 @@ use typed_builder::TypedBuilder;
 use super::HasTimeout;
 use crate::{
     corpus::Corpus,
-    executors::{hooks::ExecutorHooksTuple, Executor, ExitKind, HasObservers},
+    executors::{hooks::ExecutorHooksTuple, ChildSandbox, Executor, ExitKind, HasObservers},
     inputs::{HasTargetBytes, Input, UsesInput},
     observers::{ObserversTuple, StdErrObserver, StdOutObserver},
     state::{HasCorpus, HasExecutions, State, UsesState},
@@ -334,17 +334,22 @@ where
 }
 
 // this only works on unix because of the reliance on checking the process signal for detecting OOM
-impl<I, OT, S, T> CommandExecutor<OT, S, T>
+impl<I, OT, S, T, HT> CommandExecutor<OT, S, T, HT>
 where
     S: State + HasExecutions + UsesInput<Input = I>,
     T: CommandConfigurator<I> + Debug,
     OT: Debug + ObserversTuple<I, S>,
+    HT: ExecutorHooksTuple<S>,
 {
     fn execute_input_with_command(&mut self, state: &mut S, input: &I) -> Result<ExitKind, Error> {
         use wait_timeout::ChildExt;
 
         *state.executions_mut() += 1;
         self.observers.pre_exec_child_all(state, input)?;
+        if *state.executions() == 1 {
+            self.hooks.init_all::<Self>(state);
+        }
+        self.hooks.pre_exec_all(state, input);
 
         let mut child = self.configurer.spawn_child(input)?;
 
@@ -361,42 +366,42 @@ where
                 ExitKind::Timeout
             });
 
+        self.hooks.post_exec_all(state, input);
         self.observers
             .post_exec_child_all(state, input, &exit_kind)?;
 
         if let Some(h) = &mut self.configurer.stdout_observer() {
-            let mut stdout = Vec::new();
-            child.stdout.as_mut().ok_or_else(|| {
-                 Error::illegal_state(
-                     "Observer tries to read stderr, but stderr was not `Stdio::pipe` in CommandExecutor",
-                 )
-             })?.read_to_end(&mut stdout)?;
+            let stdout = child.stdout.as_mut().ok_or_else(|| {
+                Error::illegal_state(
+                    "Observer tries to read stdout, but stdout was not `Stdio::pipe` in CommandExecutor",
+                )
+            })?;
             let mut observers = self.observers_mut();
             let obs = observers.index_mut(h);
-            obs.observe_stdout(&stdout);
+            obs.observe_stdout_from(stdout)?;
         }
         if let Some(h) = &mut self.configurer.stderr_observer() {
-            let mut stderr = Vec::new();
-            child.stderr.as_mut().ok_or_else(|| {
-                 Error::illegal_state(
-                     "Observer tries to read stderr, but stderr was not `Stdio::pipe` in CommandExecutor",
-                 )
-             })?.read_to_end(&mut stderr)?;
+            let stderr = child.stderr.as_mut().ok_or_else(|| {
+                Error::illegal_state(
+                    "Observer tries to read stderr, but stderr was not `Stdio::pipe` in CommandExecutor",
+                )
+            })?;
             let mut observers = self.observers_mut();
             let obs = observers.index_mut(h);
-            obs.observe_stderr(&stderr);
+            obs.observe_stderr_from(stderr)?;
         }
         Ok(exit_kind)
     }
 }
 
-impl<EM, OT, S, T, Z> Executor<EM, Z> for CommandExecutor<OT, S, T>
+impl<EM, OT, S, T, Z, HT> Executor<EM, Z> for CommandExecutor<OT, S, T, HT>
 where
     EM: UsesState<State = S>,
     S: State + HasExecutions + UsesInput,
     T: CommandConfigurator<S::Input> + Debug,
     OT: Debug + MatchName + ObserversTuple<S::Input, S>,
     Z: UsesState<State = S>,
+    HT: ExecutorHooksTuple<S>,
 {
     fn run_target(
         &mut self,
@@ -410,7 +415,7 @@ where
 }
 
 // this only works on unix because of the reliance on checking the process signal for detecting OOM
-impl<OT, S, T> HasTimeout for CommandExecutor<OT, S, T>
+impl<OT, S, T, HT> HasTimeout for CommandExecutor<OT, S, T, HT>
 where
     S: HasCorpus,
     T: CommandConfigurator<<S::Corpus as Corpus>::Input>,
@@ -540,6 +545,7 @@ pub struct CommandExecutorBuilder {
     cwd: Option<PathBuf>,
     envs: Vec<(OsString, OsString)>,
     timeout: Duration,
+    sandbox: ChildSandbox,
 }
 
 impl Default for CommandExecutorBuilder {
@@ -562,6 +568,7 @@ impl CommandExecutorBuilder {
             envs: vec![],
             timeout: Duration::from_secs(5),
             debug_child: false,
+            sandbox: ChildSandbox::new(),
         }
     }
 
@@ -692,6 +699,12 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Sets the resource limits and isolation of the child process, see [`ChildSandbox`].
+    pub fn sandbox(&mut self, sandbox: ChildSandbox) -> &mut CommandExecutorBuilder {
+        self.sandbox = sandbox;
+        self
+    }
+
     /// Builds the `CommandExecutor`
     pub fn build<OT, S>(
         &self,
@@ -701,6 +714,22 @@ impl CommandExecutorBuilder {
         OT: MatchName + ObserversTuple<S::Input, S>,
         S: UsesInput,
         S::Input: Input + HasTargetBytes,
+    {
+        self.build_with_hooks(observers, ())
+    }
+
+    /// Builds the `CommandExecutor`, running the given hooks right before spawning the target,
+    /// and right after it exited.
+    pub fn build_with_hooks<OT, S, HT>(
+        &self,
+        observers: OT,
+        hooks: HT,
+    ) -> Result<CommandExecutor<OT, S, StdCommandConfigurator, HT>, Error>
+    where
+        OT: MatchName + ObserversTuple<S::Input, S>,
+        S: UsesInput,
+        S::Input: Input + HasTargetBytes,
+        HT: ExecutorHooksTuple<S>,
     {
         let Some(program) = &self.program else {
             return Err(Error::illegal_argument(
@@ -738,6 +767,7 @@ impl CommandExecutorBuilder {
         if self.stderr.is_some() {
             command.stderr(Stdio::piped());
         }
+        self.sandbox.apply(&mut command)?;
 
         let configurator = StdCommandConfigurator {
             debug_child: self.debug_child,
@@ -748,10 +778,11 @@ impl CommandExecutorBuilder {
             command,
         };
         Ok(
-            <StdCommandConfigurator as CommandConfigurator<S::Input>>::into_executor::<OT, S>(
-                configurator,
-                observers,
-            ),
+            <StdCommandConfigurator as CommandConfigurator<S::Input>>::into_executor_with_hooks::<
+                OT,
+                S,
+                HT,
+            >(configurator, observers, hooks),
         )
     }
 }
@@ -882,10 +913,16 @@ fn waitpid_filtered(pid: Pid, options: Option<WaitPidFlag>) -> Result<WaitStatus
 
 #[cfg(test)]
 mod tests {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    use libafl_bolts::tuples::tuple_list;
+
     use crate::{
         events::SimpleEventManager,
         executors::{
             command::{CommandExecutor, InputLocation},
+            hooks::ClosureExecutorHook,
             Executor,
         },
         fuzzer::NopFuzzer,
@@ -917,4 +954,44 @@ mod tests {
             )
             .unwrap();
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_builder_with_hooks() {
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::new(|status| {
+            log::info!("{status}");
+        }));
+        let pre_count = Rc::new(Cell::new(0));
+        let post_count = Rc::new(Cell::new(0));
+        let hook = {
+            let (pre, pre_count, post_count) =
+                (pre_count.clone(), pre_count.clone(), post_count.clone());
+            ClosureExecutorHook::new(
+                move |_: &mut NopState<BytesInput>, _: &BytesInput| {
+                    pre.set(pre.get() + 1);
+                },
+                move |_: &mut NopState<BytesInput>, _: &BytesInput| {
+                    assert_eq!(pre_count.get(), post_count.get() + 1);
+                    post_count.set(post_count.get() + 1);
+                },
+            )
+        };
+
+        let mut executor = CommandExecutor::builder()
+            .program("true")
+            .build_with_hooks((), tuple_list!(hook))
+            .unwrap();
+        let mut state = NopState::new();
+        for _ in 0..2 {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut mgr,
+                    &BytesInput::new(b"test".to_vec()),
+                )
+                .unwrap();
+        }
+        assert_eq!(post_count.get(), 2);
+    }
 }
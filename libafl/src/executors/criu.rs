@@ -0,0 +1,407 @@
+//! The [`CriuExecutor`] resets the target by restoring a [CRIU](https://criu.org) snapshot,
+//! taken right after the target initialized.
+//!
+//! Forking does not cheaply reset targets with heavy global state, such as open sockets,
+//! threads, or large heaps that are dirtied on each run. CRIU checkpoints the whole process tree
+//! to disk and restores it from there, so each restore starts from a pristine, initialized target.
+//!
+//! The target has to cooperate:
+//! 1. Initialize, then stop itself with `raise(SIGSTOP)`. The executor snapshots it at this point.
+//! 2. Once continued, read the input from the file at the path in the [`CRIU_INPUT_ENV`] environment
+//!    variable, and run it.
+//! 3. Either exit, or stop itself with `raise(SIGSTOP)` again, then continue at step 2 for the next input.
+//!
+//! The snapshot is restored after each exit or crash of the target, and every
+//! [`CriuExecutor::restore_interval`] executions.
+//!
+//! Coverage maps must be file-backed shared memory, e.g., from a
+//! [`libafl_bolts::shmem::MmapShMemProvider`], so restored targets still write into the map of the fuzzer.
+//! Restoring requires the `criu` binary and the capabilities it needs, usually root.
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::OsString,
+    fs,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    tuples::RefIndexable,
+    AsSlice,
+};
+use nix::{
+    sys::{
+        signal::{kill, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::Pid,
+};
+
+use super::HasTimeout;
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The environment variable telling the target where to read the input from
+pub const CRIU_INPUT_ENV: &str = "LIBAFL_CRIU_INPUT";
+
+/// The interval in which the [`CriuExecutor`] polls the target for the end of a run
+const CRIU_POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+/// An [`Executor`] restoring a CRIU snapshot of the target, see the [module-level docs](self).
+pub struct CriuExecutor<OT, S> {
+    command: Command,
+    criu: OsString,
+    images_dir: PathBuf,
+    input_file: InputFile,
+    timeout: Duration,
+    restore_interval: u64,
+    runs_since_restore: u64,
+    /// The pid of the (stopped) target, waiting for the next input
+    pid: Option<Pid>,
+    has_snapshot: bool,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for CriuExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CriuExecutor")
+            .field("command", &self.command)
+            .field("images_dir", &self.images_dir)
+            .field("input_file", &self.input_file)
+            .field("timeout", &self.timeout)
+            .field("restore_interval", &self.restore_interval)
+            .field("pid", &self.pid)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> CriuExecutor<OT, S> {
+    /// Creates a new [`CriuExecutor`] for the target started by the given [`Command`].
+    ///
+    /// The snapshot is stored in `images_dir`, and taken on the first execution.
+    /// By default, the snapshot is only restored after the target exits or crashes,
+    /// and a run times out after 5 seconds, see [`HasTimeout::set_timeout`].
+    pub fn new<P>(mut command: Command, images_dir: P, observers: OT) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let input_file = InputFile::create(get_unique_std_input_file())?;
+        command
+            .env(CRIU_INPUT_ENV, &input_file.path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        // CRIU only dumps session leaders without a controlling terminal
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(Self {
+            command,
+            criu: OsString::from("criu"),
+            images_dir: images_dir.into(),
+            input_file,
+            timeout: Duration::from_secs(5),
+            restore_interval: u64::MAX,
+            runs_since_restore: 0,
+            pid: None,
+            has_snapshot: false,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets the path to the `criu` binary; default is `criu`
+    #[must_use]
+    pub fn criu_path<P>(mut self, criu: P) -> Self
+    where
+        P: Into<OsString>,
+    {
+        self.criu = criu.into();
+        self
+    }
+
+    /// Restores the snapshot every `restore_interval` executions, even if the target did not exit;
+    /// default is to only restore after the target exits.
+    /// An interval of `1` restores the snapshot for every execution.
+    #[must_use]
+    pub fn restore_interval(mut self, restore_interval: u64) -> Self {
+        self.restore_interval = restore_interval.max(1);
+        self
+    }
+
+    /// The directory holding the CRIU images of the snapshot
+    pub fn images_dir(&self) -> &PathBuf {
+        &self.images_dir
+    }
+
+    /// The [`InputFile`] the target reads the input from
+    pub fn input_file(&self) -> &InputFile {
+        &self.input_file
+    }
+
+    /// Runs `criu` with the given arguments
+    fn criu(&self, args: &[&str]) -> Result<(), Error> {
+        let status = Command::new(&self.criu)
+            .args(args)
+            .arg("-D")
+            .arg(&self.images_dir)
+            .stdin(Stdio::null())
+            .status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::unknown(format!(
+                "Running criu {args:?} failed with {status}"
+            )))
+        }
+    }
+
+    /// Waits for the target to stop itself, returns if it did
+    fn wait_for_stop(pid: Pid) -> Result<bool, Error> {
+        Ok(matches!(
+            waitpid(pid, Some(WaitPidFlag::WUNTRACED))?,
+            WaitStatus::Stopped(_, _)
+        ))
+    }
+
+    /// Starts the target, and snapshots it once it stopped after initializing
+    fn snapshot(&mut self) -> Result<(), Error> {
+        fs::create_dir_all(&self.images_dir)?;
+        let child = self.command.spawn()?;
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = Pid::from_raw(child.id() as i32);
+        if !Self::wait_for_stop(pid)? {
+            return Err(Error::illegal_state(
+                "The target exited before stopping itself for the CRIU snapshot",
+            ));
+        }
+        self.criu(&["dump", "-t", &format!("{pid}"), "--leave-stopped"])?;
+        self.pid = Some(pid);
+        self.runs_since_restore = 0;
+        self.has_snapshot = true;
+        Ok(())
+    }
+
+    /// Kills the current target, if any, and restores the snapshot.
+    ///
+    /// The restored target stays stopped until [`Self::run_once`] continues it, after the input was written.
+    fn restore(&mut self) -> Result<(), Error> {
+        self.kill_target();
+        let pidfile = self.images_dir.join("restored.pid");
+        // The restored target is a sibling of criu, i.e., our child, so we can wait on it.
+        self.criu(&[
+            "restore",
+            "--restore-detached",
+            "--restore-sibling",
+            "--leave-stopped",
+            "--pidfile",
+            &pidfile.to_string_lossy(),
+        ])?;
+        let pid = fs::read_to_string(&pidfile)?
+            .trim()
+            .parse()
+            .map_err(|err| Error::illegal_state(format!("Invalid CRIU pidfile: {err}")))?;
+        self.pid = Some(Pid::from_raw(pid));
+        self.runs_since_restore = 0;
+        Ok(())
+    }
+
+    /// Kills and reaps the current target, if any
+    fn kill_target(&mut self) {
+        if let Some(pid) = self.pid.take() {
+            let _ = kill(pid, Signal::SIGKILL);
+            let _ = waitpid(pid, None);
+        }
+    }
+
+    /// Continues the stopped target, and waits for the run to finish
+    fn run_once(&mut self, pid: Pid) -> Result<ExitKind, Error> {
+        kill(pid, Signal::SIGCONT)?;
+        let start = Instant::now();
+        loop {
+            match waitpid(pid, Some(WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED))? {
+                WaitStatus::StillAlive => {
+                    if start.elapsed() > self.timeout {
+                        self.kill_target();
+                        return Ok(ExitKind::Timeout);
+                    }
+                    thread::sleep(CRIU_POLL_INTERVAL);
+                }
+                // Ready for the next input
+                WaitStatus::Stopped(_, _) => return Ok(ExitKind::Ok),
+                WaitStatus::Exited(_, _) => {
+                    self.pid = None;
+                    return Ok(ExitKind::Ok);
+                }
+                WaitStatus::Signaled(_, _, _) => {
+                    self.pid = None;
+                    return Ok(ExitKind::Crash);
+                }
+                // Continued, or other events we do not care about
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<OT, S> Drop for CriuExecutor<OT, S> {
+    fn drop(&mut self) {
+        self.kill_target();
+    }
+}
+
+impl<OT, S> UsesState for CriuExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for CriuExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        if !self.has_snapshot {
+            self.snapshot()?;
+        } else if self.pid.is_none() || self.runs_since_restore >= self.restore_interval {
+            self.restore()?;
+        }
+        let pid = self.pid.unwrap();
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+        self.observers.pre_exec_child_all(state, input)?;
+        let exit_kind = self.run_once(pid)?;
+        self.runs_since_restore += 1;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for CriuExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S> HasObservers for CriuExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs,
+        process::{self, Command, Stdio},
+    };
+
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::CriuExecutor;
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    /// Stops for the snapshot, then crashes if the input contains `crash`, and exits otherwise
+    const TARGET: &str =
+        r#"kill -STOP $$; if grep -q crash "$LIBAFL_CRIU_INPUT"; then kill -SEGV $$; fi"#;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_criu_restore() {
+        let criu_works = Command::new("criu")
+            .arg("check")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !criu_works {
+            log::warn!("criu is missing, or lacks the permissions to run, skipping the test");
+            return;
+        }
+
+        let images_dir = env::temp_dir().join(format!("libafl_criu_test_{}", process::id()));
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(TARGET);
+        let mut executor = CriuExecutor::new(command, &images_dir, tuple_list!()).unwrap();
+        let mut state = NopState::new();
+        let mut run = |executor: &mut CriuExecutor<_, _>, input: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::default(),
+                    &BytesInput::new(input.to_vec()),
+                )
+                .unwrap()
+        };
+
+        // Snapshots, then runs the snapshot, which exits
+        assert_eq!(run(&mut executor, b"fine"), ExitKind::Ok);
+        // Each further run restores the snapshot, which reads the new input
+        assert_eq!(run(&mut executor, b"crash"), ExitKind::Crash);
+        assert_eq!(run(&mut executor, b"fine"), ExitKind::Ok);
+
+        drop(executor);
+        fs::remove_dir_all(images_dir).unwrap();
+    }
+}
@@ -0,0 +1,753 @@
+//! The [`DebuggerExecutor`] runs the target under `gdb` or `lldb`, for crash triage.
+//!
+//! The debugger is driven over its machine interface (GDB/MI, also spoken by `lldb-mi`).
+//! When the target crashes, the executor extracts the stop reason, the backtrace, the registers,
+//! and the locals at the crash site into a [`CrashContext`], and hands it to a [`CrashContextObserver`].
+//! Add a [`crate::feedbacks::CrashContextFeedback`] to the objective to attach it to the testcase.
+//!
+//! Running under a debugger is slow, so use this executor to replay crashes, not to fuzz.
+
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    os::unix_signals::Signal,
+    tuples::{Handle, MatchNameRef, RefIndexable},
+    AsSlice,
+};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::HasTargetBytes,
+    observers::{CrashContext, CrashContextObserver, CrashFrame, CrashLocal, ObserversTuple},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The debugger driving the target of a [`DebuggerExecutor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebuggerKind {
+    /// `gdb`, with `--interpreter=mi2`
+    Gdb,
+    /// `lldb-mi`, the GDB/MI frontend of `lldb`
+    Lldb,
+}
+
+impl DebuggerKind {
+    /// The default binary of the debugger
+    #[must_use]
+    pub fn default_path(self) -> &'static str {
+        match self {
+            Self::Gdb => "gdb",
+            Self::Lldb => "lldb-mi",
+        }
+    }
+
+    /// The arguments to start the debugger in MI mode
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            Self::Gdb => &["--interpreter=mi2", "--nx", "--quiet"],
+            Self::Lldb => &["--interpreter"],
+        }
+    }
+}
+
+/// A value of a GDB/MI record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiValue {
+    /// A c-string constant
+    Const(String),
+    /// A tuple `{name=value,...}`
+    Tuple(Vec<(String, MiValue)>),
+    /// A list `[value,...]`, or `[name=value,...]` with the names dropped
+    List(Vec<MiValue>),
+}
+
+impl MiValue {
+    /// Gets the value of the given field, if this is a tuple
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&MiValue> {
+        match self {
+            Self::Tuple(results) => results.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Gets the string of the given field, if this is a tuple
+    #[must_use]
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.get(name) {
+            Some(Self::Const(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The elements, if this is a list
+    #[must_use]
+    pub fn as_list(&self) -> &[MiValue] {
+        match self {
+            Self::List(values) => values,
+            _ => &[],
+        }
+    }
+}
+
+/// A result or async record of GDB/MI, such as `^done,...` or `*stopped,...`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiRecord {
+    /// The prefix of the record: `^` for results, `*`, `+`, `=` for async records
+    pub kind: char,
+    /// The class, such as `done`, `error`, `running`, or `stopped`
+    pub class: String,
+    /// The results of the record, as a [`MiValue::Tuple`]
+    pub results: MiValue,
+}
+
+impl MiRecord {
+    /// Parses a result or async record. Stream records and prompts are [`None`].
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        // Skip the optional token
+        let line = line
+            .trim_end()
+            .trim_start_matches(|c: char| c.is_ascii_digit());
+        let kind = line.chars().next()?;
+        if !matches!(kind, '^' | '*' | '+' | '=') {
+            return None;
+        }
+        let rest = &line[1..];
+        let (class, mut results) = rest.split_once(',').unwrap_or((rest, ""));
+        let mut parsed = Vec::new();
+        while !results.is_empty() {
+            let (name, value, rest) = parse_result(results)?;
+            parsed.push((name, value));
+            results = rest.strip_prefix(',').unwrap_or(rest);
+        }
+        Some(Self {
+            kind,
+            class: class.to_owned(),
+            results: MiValue::Tuple(parsed),
+        })
+    }
+}
+
+/// Parses `name=value`, returning the rest
+fn parse_result(input: &str) -> Option<(String, MiValue, &str)> {
+    let (name, rest) = input.split_once('=')?;
+    let (value, rest) = parse_value(rest)?;
+    Some((name.to_owned(), value, rest))
+}
+
+/// Parses a value, returning the rest
+fn parse_value(input: &str) -> Option<(MiValue, &str)> {
+    match input.chars().next()? {
+        '"' => {
+            let mut value = String::new();
+            let mut chars = input.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Some((MiValue::Const(value), &input[i + 1..])),
+                    '\\' => match chars.next()?.1 {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        c => value.push(c),
+                    },
+                    c => value.push(c),
+                }
+            }
+            None
+        }
+        '{' => {
+            let mut rest = &input[1..];
+            let mut results = Vec::new();
+            while !rest.starts_with('}') {
+                let (name, value, next) = parse_result(rest)?;
+                results.push((name, value));
+                rest = next.strip_prefix(',').unwrap_or(next);
+            }
+            Some((MiValue::Tuple(results), &rest[1..]))
+        }
+        '[' => {
+            let mut rest = &input[1..];
+            let mut values = Vec::new();
+            while !rest.starts_with(']') {
+                let (value, next) = if matches!(rest.chars().next()?, '"' | '{' | '[') {
+                    parse_value(rest)?
+                } else {
+                    let (_, value, next) = parse_result(rest)?;
+                    (value, next)
+                };
+                values.push(value);
+                rest = next.strip_prefix(',').unwrap_or(next);
+            }
+            Some((MiValue::List(values), &rest[1..]))
+        }
+        _ => None,
+    }
+}
+
+/// Quotes a string as a GDB/MI c-string
+fn mi_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// Quotes an argument for the shell the debugger starts the target with
+fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Parses an address, such as `0x0000555555555131` or `(void *) 0x0`
+fn parse_address(value: &str) -> Option<usize> {
+    let hex = &value[value.find("0x")? + 2..];
+    let end = hex
+        .find(|c: char| !c.is_ascii_hexdigit())
+        .unwrap_or(hex.len());
+    usize::from_str_radix(&hex[..end], 16).ok()
+}
+
+/// A running debugger, talking GDB/MI
+struct MiSession {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    deadline: Instant,
+    /// The pid of the running target, from the `=thread-group-started` record
+    inferior_pid: Option<i32>,
+}
+
+impl MiSession {
+    /// Reads the next result or async record, collecting the console output
+    fn next_record(&mut self, console: &mut String) -> Result<Option<MiRecord>, Error> {
+        loop {
+            let timeout = self.deadline.saturating_duration_since(Instant::now());
+            let line = match self.lines.recv_timeout(timeout) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::unknown("The debugger exited unexpectedly"))
+                }
+            };
+            if let Some(output) = line.strip_prefix('~') {
+                if let Some((MiValue::Const(output), _)) = parse_value(output) {
+                    console.push_str(&output);
+                }
+            } else if let Some(record) = MiRecord::parse(&line) {
+                match (record.kind, record.class.as_str()) {
+                    ('=', "thread-group-started") => {
+                        self.inferior_pid = record
+                            .results
+                            .get_str("pid")
+                            .and_then(|pid| pid.parse().ok());
+                    }
+                    ('=', "thread-group-exited") => self.inferior_pid = None,
+                    _ => {}
+                }
+                return Ok(Some(record));
+            }
+        }
+    }
+
+    /// Runs a command, returning its result record and console output
+    fn command(&mut self, command: &str) -> Result<(MiRecord, String), Error> {
+        writeln!(self.stdin, "{command}")?;
+        self.stdin.flush()?;
+        let mut console = String::new();
+        loop {
+            let record = self.next_record(&mut console)?.ok_or_else(|| {
+                Error::unknown(format!("The debugger did not answer `{command}` in time"))
+            })?;
+            if record.kind == '^' {
+                if record.class == "error" {
+                    return Err(Error::unknown(format!(
+                        "The debugger failed to run `{command}`: {}",
+                        record.results.get_str("msg").unwrap_or_default()
+                    )));
+                }
+                return Ok((record, console));
+            }
+        }
+    }
+
+    /// Waits until the target stopped, or the deadline passed
+    fn wait_stopped(&mut self) -> Result<Option<MiRecord>, Error> {
+        let mut console = String::new();
+        while let Some(record) = self.next_record(&mut console)? {
+            if record.kind == '*' && record.class == "stopped" {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Kills the target, if it is running.
+    /// A debugger busy with a running target may not answer `-exec-abort`, so kill it directly.
+    fn kill_inferior(&mut self) {
+        if let Some(pid) = self.inferior_pid.take() {
+            // # Safety
+            // Normal libc call, no dereferences whatsoever
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+impl Drop for MiSession {
+    fn drop(&mut self) {
+        // Killing the debugger alone may leave the target running, detached
+        self.kill_inferior();
+        drop(writeln!(self.stdin, "-gdb-exit"));
+        drop(self.child.kill());
+        drop(self.child.wait());
+    }
+}
+
+/// An [`Executor`] running the target under a debugger, see the [module-level docs](self).
+pub struct DebuggerExecutor<OT, S> {
+    kind: DebuggerKind,
+    debugger: PathBuf,
+    program: PathBuf,
+    args: Vec<String>,
+    input_file: InputFile,
+    timeout: Duration,
+    max_frames: usize,
+    crash_context: Handle<CrashContextObserver>,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for DebuggerExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebuggerExecutor")
+            .field("kind", &self.kind)
+            .field("debugger", &self.debugger)
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("input_file", &self.input_file)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> DebuggerExecutor<OT, S> {
+    /// Creates a new [`DebuggerExecutor`] running `program` with `args` under `gdb`.
+    ///
+    /// An argument `@@` is replaced by the path of the input file; without it, the input is
+    /// passed on stdin. The [`CrashContext`] of crashes is handed to the given observer.
+    /// A run times out after 30 seconds, since debuggers are slow to start.
+    pub fn new<P>(
+        program: P,
+        args: Vec<String>,
+        crash_context: Handle<CrashContextObserver>,
+        observers: OT,
+    ) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        Ok(Self {
+            kind: DebuggerKind::Gdb,
+            debugger: PathBuf::from(DebuggerKind::Gdb.default_path()),
+            program: program.into(),
+            args,
+            input_file: InputFile::create(get_unique_std_input_file())?,
+            timeout: Duration::from_secs(30),
+            max_frames: 32,
+            crash_context,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Sets the debugger to use, at its default path
+    #[must_use]
+    pub fn debugger(mut self, kind: DebuggerKind) -> Self {
+        self.kind = kind;
+        self.debugger = PathBuf::from(kind.default_path());
+        self
+    }
+
+    /// Sets the path to the debugger binary
+    #[must_use]
+    pub fn debugger_path<P>(mut self, debugger: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.debugger = debugger.into();
+        self
+    }
+
+    /// Sets how many frames of the backtrace are extracted; default is 32
+    #[must_use]
+    pub fn max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames.max(1);
+        self
+    }
+
+    /// Starts the debugger, with the target loaded
+    fn start(&self) -> Result<MiSession, Error> {
+        let mut child = Command::new(&self.debugger)
+            .args(self.kind.args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut session = MiSession {
+            child,
+            stdin,
+            lines,
+            deadline: Instant::now() + self.timeout,
+            inferior_pid: None,
+        };
+
+        let program = self
+            .program
+            .to_str()
+            .ok_or_else(|| Error::illegal_argument("The program path is not valid UTF-8"))?;
+        session.command(&format!("-file-exec-and-symbols {}", mi_quote(program)))?;
+        // Keep the output of the target out of the MI stream
+        session.command("-inferior-tty-set /dev/null")?;
+
+        let input_path = self.input_file.path.to_string_lossy();
+        let mut arguments: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| {
+                if arg == "@@" {
+                    shell_quote(&input_path)
+                } else {
+                    shell_quote(arg)
+                }
+            })
+            .collect();
+        if !self.args.iter().any(|arg| arg == "@@") {
+            arguments.push(format!("< {}", shell_quote(&input_path)));
+        }
+        session.command(&format!("-exec-arguments {}", arguments.join(" ")))?;
+        Ok(session)
+    }
+
+    /// Extracts the [`CrashContext`] of the target, stopped at a crash
+    fn crash_context(&self, session: &mut MiSession, signal: &str) -> CrashContext {
+        let mut backtrace = Vec::new();
+        if let Ok((record, _)) =
+            session.command(&format!("-stack-list-frames 0 {}", self.max_frames - 1))
+        {
+            if let Some(stack) = record.results.get("stack") {
+                backtrace = stack
+                    .as_list()
+                    .iter()
+                    .map(|frame| CrashFrame {
+                        ip: frame.get_str("addr").and_then(parse_address).unwrap_or(0),
+                        symbol: frame.get_str("func").map(ToOwned::to_owned),
+                        file: frame
+                            .get_str("fullname")
+                            .or_else(|| frame.get_str("file"))
+                            .map(ToOwned::to_owned),
+                        line: frame.get_str("line").and_then(|line| line.parse().ok()),
+                        module: None,
+                    })
+                    .collect();
+            }
+        }
+
+        // The locals of the innermost frame with debug info, usually not the libc frames
+        let mut locals = Vec::new();
+        if let Some(level) = backtrace.iter().position(|frame| frame.file.is_some()) {
+            let selected = session.command(&format!("-stack-select-frame {level}"));
+            if let Ok((record, _)) =
+                selected.and_then(|_| session.command("-stack-list-locals --simple-values"))
+            {
+                if let Some(list) = record.results.get("locals") {
+                    locals = list
+                        .as_list()
+                        .iter()
+                        .filter_map(|local| {
+                            Some(CrashLocal {
+                                name: local.get_str("name")?.to_owned(),
+                                ty: local.get_str("type").map(ToOwned::to_owned),
+                                value: local.get_str("value").map(ToOwned::to_owned),
+                            })
+                        })
+                        .collect();
+                }
+            }
+        }
+
+        let registers = session
+            .command("-interpreter-exec console \"info registers\"")
+            .map(|(_, console)| console)
+            .unwrap_or_default();
+        // Only gdb knows about the siginfo
+        let evaluate = |session: &mut MiSession, expression: &str| {
+            session
+                .command(&format!("-data-evaluate-expression {expression}"))
+                .ok()
+                .and_then(|(record, _)| record.results.get_str("value").map(ToOwned::to_owned))
+        };
+        let si_code = evaluate(session, "$_siginfo.si_code")
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0);
+        let fault_addr = evaluate(session, "$_siginfo._sifields._sigfault.si_addr")
+            .and_then(|addr| parse_address(&addr))
+            .unwrap_or(0);
+
+        CrashContext {
+            signal: signal.to_string(),
+            signal_number: Signal::try_from(signal).map_or(0, |signal| signal as i32),
+            si_code,
+            fault_addr,
+            pc: backtrace.first().map(|frame| frame.ip),
+            registers,
+            backtrace,
+            locals,
+        }
+    }
+
+    /// Runs the target once under the debugger
+    fn run_once(&self) -> Result<(ExitKind, Option<CrashContext>), Error> {
+        let mut session = self.start()?;
+        session.command("-exec-run")?;
+        let Some(stopped) = session.wait_stopped()? else {
+            session.kill_inferior();
+            return Ok((ExitKind::Timeout, None));
+        };
+
+        let reason = stopped.results.get_str("reason").unwrap_or_default();
+        match reason {
+            "exited-normally" | "exited" => Ok((ExitKind::Ok, None)),
+            "exited-signalled" | "signal-received" => {
+                let signal = stopped
+                    .results
+                    .get_str("signal-name")
+                    .unwrap_or("unknown")
+                    .to_owned();
+                if signal == "SIGALRM" {
+                    return Ok((ExitKind::Timeout, None));
+                }
+                let context = (reason == "signal-received")
+                    .then(|| self.crash_context(&mut session, &signal));
+                Ok((ExitKind::Crash, context))
+            }
+            _ => Err(Error::unknown(format!(
+                "The target stopped for an unexpected reason: {reason}"
+            ))),
+        }
+    }
+}
+
+impl<OT, S> UsesState for DebuggerExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for DebuggerExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+        self.observers.pre_exec_child_all(state, input)?;
+        let (exit_kind, context) = self.run_once()?;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+
+        let observer = self.observers.get_mut(&self.crash_context).ok_or_else(|| {
+            Error::key_not_found("The CrashContextObserver of the executor is missing")
+        })?;
+        observer.set_context(context);
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for DebuggerExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S> HasObservers for DebuggerExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::time::Duration;
+    use std::{fs, process::Command};
+
+    use libafl_bolts::tuples::{tuple_list, Handled};
+
+    use super::{parse_address, DebuggerExecutor, MiRecord, MiValue};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasTimeout},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::CrashContextObserver,
+        state::NopState,
+    };
+
+    /// If `gdb` can be run, to skip the end-to-end tests without it
+    fn has_gdb() -> bool {
+        Command::new("gdb")
+            .arg("--version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// If any process runs with the given command line argument
+    fn is_running(arg: &str) -> bool {
+        fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+            fs::read(entry.path().join("cmdline"))
+                .is_ok_and(|cmdline| cmdline.split(|b| *b == 0).any(|a| a == arg.as_bytes()))
+        })
+    }
+
+    fn run(program: &str, args: &[&str], timeout: Duration) -> (ExitKind, CrashContextObserver) {
+        let observer = CrashContextObserver::new("crash_context");
+        let handle = observer.handle();
+        let args = args
+            .iter()
+            .map(|arg| String::from(*arg))
+            .collect::<Vec<_>>();
+        let mut executor =
+            DebuggerExecutor::new(program, args, handle, tuple_list!(observer)).unwrap();
+        executor.set_timeout(timeout);
+
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::<BytesInput>::new(),
+                &mut NopEventManager::new(),
+                &BytesInput::new(vec![]),
+            )
+            .unwrap();
+        (exit_kind, executor.observers.0)
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_debugger_executor_gdb() {
+        if !has_gdb() {
+            log::warn!("gdb not found, skipping the test");
+            return;
+        }
+
+        let (exit_kind, observer) =
+            run("/bin/sh", &["-c", "kill -SEGV $$"], Duration::from_secs(30));
+        assert_eq!(exit_kind, ExitKind::Crash);
+        let context = observer.context().unwrap();
+        assert_eq!(context.signal, "SIGSEGV");
+        assert!(!context.backtrace.is_empty());
+
+        let (exit_kind, observer) = run("/bin/sh", &["-c", "exit 0"], Duration::from_secs(30));
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert!(observer.context().is_none());
+
+        // The target must not outlive a timeout
+        let (exit_kind, _) = run("/bin/sleep", &["917.25"], Duration::from_secs(5));
+        assert_eq!(exit_kind, ExitKind::Timeout);
+        assert!(!is_running("917.25"));
+    }
+
+    #[test]
+    fn test_mi_records() {
+        let stopped = MiRecord::parse(
+            r#"*stopped,reason="signal-received",signal-name="SIGSEGV",signal-meaning="Segmentation fault",frame={addr="0x0000555555555131",func="crash",args=[],file="t.c",line="3"},thread-id="1""#,
+        )
+        .unwrap();
+        assert_eq!(stopped.kind, '*');
+        assert_eq!(stopped.class, "stopped");
+        assert_eq!(stopped.results.get_str("signal-name"), Some("SIGSEGV"));
+        let frame = stopped.results.get("frame").unwrap();
+        assert_eq!(frame.get_str("func"), Some("crash"));
+        assert_eq!(frame.get("args"), Some(&MiValue::List(vec![])));
+
+        let stack = MiRecord::parse(
+            r#"12^done,stack=[frame={level="0",addr="0x1131",func="crash"},frame={level="1",addr="0x1150",func="main"}]"#,
+        )
+        .unwrap();
+        let frames = stack.results.get("stack").unwrap().as_list();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].get_str("func"), Some("main"));
+
+        let locals = MiRecord::parse(
+            r#"^done,locals=[{name="p",type="int *",value="0x0"},{name="s",type="char *",value="0x2004 \"a\\\"b\""}]"#,
+        )
+        .unwrap();
+        let locals = locals.results.get("locals").unwrap().as_list();
+        assert_eq!(locals[1].get_str("value"), Some(r#"0x2004 "a\"b""#));
+
+        assert_eq!(MiRecord::parse("^running").unwrap().class, "running");
+        assert!(MiRecord::parse("(gdb) ").is_none());
+        assert!(MiRecord::parse(r#"~"Starting program""#).is_none());
+
+        assert_eq!(parse_address("(void *) 0x10"), Some(0x10));
+        assert_eq!(parse_address("0x0000555555555131"), Some(0x5555_5555_5131));
+    }
+}
@@ -3,11 +3,20 @@
 //! It wraps two executors that will be run after each other with the same input.
 //! In comparison to the [`crate::executors::CombinedExecutor`] it also runs the secondary executor in `run_target`.
 //!
+//! Both executors keep their own observers, which the [`DiffExecutor`] exposes together with its
+//! differential observers as a [`ProxyObserversTuple`]. To compare an observer of the primary executor
+//! with one of the secondary executor, even if both have the same name, use a
+//! [`crate::feedbacks::DiffExecutorFeedback`]. Differing [`ExitKind`]s are reported as [`ExitKind::Diff`],
+//! see the [`crate::feedbacks::DiffExitKindFeedback`].
+//!
+//! If one target is much slower than the other, give each executor its own timeout with
+//! [`DiffExecutor::set_timeouts`].
 use core::{
     cell::UnsafeCell,
     fmt::Debug,
     ops::{Deref, DerefMut},
     ptr,
+    time::Duration,
 };
 
 use libafl_bolts::{
@@ -62,6 +71,29 @@ impl<A, B, DOT, OTA, OTB> DiffExecutor<A, B, DOT, OTA, OTB> {
     pub fn secondary(&mut self) -> &mut B {
         &mut self.secondary
     }
+
+    /// Sets the timeouts of the primary and the secondary executor individually.
+    ///
+    /// Use this if one target is much slower than the other, instead of [`HasTimeout::set_timeout`].
+    #[inline]
+    pub fn set_timeouts(&mut self, primary: Duration, secondary: Duration)
+    where
+        A: HasTimeout,
+        B: HasTimeout,
+    {
+        self.primary.set_timeout(primary);
+        self.secondary.set_timeout(secondary);
+    }
+
+    /// The timeouts of the primary and the secondary executor
+    #[inline]
+    pub fn timeouts(&self) -> (Duration, Duration)
+    where
+        A: HasTimeout,
+        B: HasTimeout,
+    {
+        (self.primary.timeout(), self.secondary.timeout())
+    }
 }
 
 impl<A, B, DOT, EM, Z> Executor<EM, Z> for DiffExecutor<A, B, DOT, A::Observers, B::Observers>
@@ -126,19 +158,17 @@ where
     A: HasTimeout,
     B: HasTimeout,
 {
+    /// Sets the same timeout for both executors, see [`DiffExecutor::set_timeouts`] for individual ones
     #[inline]
-    fn set_timeout(&mut self, timeout: core::time::Duration) {
+    fn set_timeout(&mut self, timeout: Duration) {
         self.primary.set_timeout(timeout);
         self.secondary.set_timeout(timeout);
     }
 
+    /// The longer timeout of the two executors
     #[inline]
-    fn timeout(&self) -> core::time::Duration {
-        assert!(
-            self.primary.timeout() == self.secondary.timeout(),
-            "Primary and Secondary Executors have different timeouts!"
-        );
-        self.primary.timeout()
+    fn timeout(&self) -> Duration {
+        self.primary.timeout().max(self.secondary.timeout())
     }
 }
 
@@ -233,6 +263,21 @@ where
 }
 
 impl<A, B, DOT> ProxyObserversTuple<A, B, DOT> {
+    /// The observers of the primary executor
+    pub fn primary(&self) -> &A {
+        self.primary.as_ref()
+    }
+
+    /// The observers of the secondary executor
+    pub fn secondary(&self) -> &B {
+        self.secondary.as_ref()
+    }
+
+    /// The differential observers of the [`DiffExecutor`]
+    pub fn differential(&self) -> &DOT {
+        &self.differential
+    }
+
     fn set(&mut self, primary: &A, secondary: &B) {
         self.primary = OwnedMutPtr::Ptr(ptr::from_ref(primary).cast_mut());
         self.secondary = OwnedMutPtr::Ptr(ptr::from_ref(secondary).cast_mut());
@@ -279,3 +324,68 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use libafl_bolts::{tuples::tuple_list, Named};
+
+    use crate::{
+        events::NopEventManager,
+        executors::{test::NopExecutor, DiffExecutor, ExitKind, HasObservers, WithObservers},
+        feedbacks::{DiffExecutorFeedback, Feedback},
+        inputs::BytesInput,
+        observers::Observer,
+        state::NopState,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct ValueObserver {
+        name: Cow<'static, str>,
+        value: u8,
+    }
+
+    impl<I, S> Observer<I, S> for ValueObserver {}
+
+    impl Named for ValueObserver {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.name
+        }
+    }
+
+    #[test]
+    fn test_diff_executor_feedback() {
+        let mut state = NopState::<BytesInput>::new();
+
+        for secondary_value in [0, 1] {
+            // both executors use an observer of the same name
+            let o1 = ValueObserver {
+                name: Cow::Borrowed("value"),
+                value: 0,
+            };
+            let o2 = ValueObserver {
+                name: Cow::Borrowed("value"),
+                value: secondary_value,
+            };
+            let mut feedback = DiffExecutorFeedback::with_partial_eq("diff", &o1, &o2);
+            let executor = DiffExecutor::new(
+                WithObservers::new(NopExecutor::<NopState<BytesInput>>::new(), tuple_list!(o1)),
+                WithObservers::new(NopExecutor::new(), tuple_list!(o2)),
+                (),
+            );
+
+            let observers = executor.observers();
+            let interesting = feedback
+                .is_interesting(
+                    &mut state,
+                    &mut NopEventManager::<NopState<BytesInput>>::default(),
+                    &BytesInput::new(vec![0]),
+                    &*observers,
+                    &ExitKind::Ok,
+                )
+                .unwrap();
+            assert_eq!(interesting, secondary_value != 0);
+        }
+    }
+}
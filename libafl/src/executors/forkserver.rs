@@ -9,6 +9,7 @@ use core::{
 use std::{
     env,
     ffi::{OsStr, OsString},
+    fs,
     io::{self, ErrorKind, Read, Write},
     os::{
         fd::{AsRawFd, BorrowedFd},
@@ -43,12 +44,14 @@ use crate::observers::{
     get_asan_runtime_flags, get_asan_runtime_flags_with_log_path, AsanBacktraceObserver,
 };
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{ChildSandbox, Executor, ExitKind, HasObservers},
     inputs::{
         BytesInput, HasTargetBytes, Input, NopTargetBytesConverter, TargetBytesConverter, UsesInput,
     },
     mutators::Tokens,
-    observers::{MapObserver, Observer, ObserversTuple},
+    observers::{
+        MapObserver, Observer, ObserversTuple, ResizableMapObserver, StdErrObserver, StdOutObserver,
+    },
     state::{HasExecutions, State, UsesState},
     Error,
 };
@@ -121,6 +124,11 @@ fn report_error_and_exit(status: i32) -> Result<(), Error> {
     }
 }
 
+/// The signature AFL++ embeds in targets using persistent mode (`__AFL_LOOP`)
+const PERSISTENT_SIG: &[u8] = b"##SIG_AFL_PERSISTENT##";
+/// The signature AFL++ embeds in targets using a deferred forkserver (`__AFL_INIT`)
+const DEFERRED_SIG: &[u8] = b"##SIG_AFL_DEFER_FORKSRV##";
+
 /// The length of header bytes which tells shmem size
 const SHMEM_FUZZ_HDR_SIZE: usize = 4;
 const MAX_INPUT_SIZE_DEFAULT: usize = 1024 * 1024;
@@ -255,6 +263,21 @@ impl ConfigTarget for Command {
     }
 }
 
+/// The name of the file capturing the given stream of the target, next to the input file
+fn capture_filename(input_filename: &OsStr, stream: &str) -> OsString {
+    let mut filename = input_filename.to_owned();
+    filename.push(".");
+    filename.push(stream);
+    filename
+}
+
+/// Empties a file capturing the output of the target, before the next run.
+/// The target shares the file offset with us, so it writes from the start again.
+fn clear_capture_file(file: &mut InputFile) -> Result<(), Error> {
+    file.file.set_len(0)?;
+    file.rewind()
+}
+
 /// The [`Forkserver`] is communication channel with a child process that forks on request of the fuzzer.
 /// The communication happens via pipe.
 #[derive(Debug)]
@@ -311,6 +334,12 @@ impl Drop for Forkserver {
     }
 }
 
+/// The error of a forkserver that stopped responding, as a broken pipe, so that it counts as a
+/// [transient error](crate::executors::is_transient_error), which [`ForkserverExecutor::restart_forkserver`] may fix.
+fn forkserver_gone(msg: String) -> Error {
+    Error::os_error(io::Error::from(ErrorKind::BrokenPipe), msg)
+}
+
 const fn fs_opt_get_mapsize(x: i32) -> i32 {
     ((x & 0x00fffffe) >> 1) + 1
 }
@@ -365,6 +394,50 @@ impl Forkserver {
         coverage_map_size: Option<usize>,
         debug_output: bool,
         kill_signal: Signal,
+    ) -> Result<Self, Error> {
+        let stdio = || {
+            if debug_output {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            }
+        };
+        Self::with_stdio(
+            target,
+            args,
+            envs,
+            input_filefd,
+            use_stdin,
+            memlimit,
+            is_persistent,
+            is_deferred_frksrv,
+            dump_asan_logs,
+            coverage_map_size,
+            stdio(),
+            stdio(),
+            &ChildSandbox::new(),
+            kill_signal,
+        )
+    }
+
+    /// Create a new [`Forkserver`] with the given `stdout` and `stderr` of the forkserver process,
+    /// which are inherited by the forked children, as is the `sandbox`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_stdio(
+        target: OsString,
+        args: Vec<OsString>,
+        envs: Vec<(OsString, OsString)>,
+        input_filefd: RawFd,
+        use_stdin: bool,
+        memlimit: u64,
+        is_persistent: bool,
+        is_deferred_frksrv: bool,
+        dump_asan_logs: bool,
+        coverage_map_size: Option<usize>,
+        stdout: Stdio,
+        stderr: Stdio,
+        sandbox: &ChildSandbox,
+        kill_signal: Signal,
     ) -> Result<Self, Error> {
         let Some(coverage_map_size) = coverage_map_size else {
             return Err(Error::unknown("Coverage map size unknown. Use coverage_map_size() to tell the forkserver about the map size."));
@@ -390,12 +463,6 @@ impl Forkserver {
         let mut st_pipe = Pipe::new().unwrap();
         let mut ctl_pipe = Pipe::new().unwrap();
 
-        let (stdout, stderr) = if debug_output {
-            (Stdio::inherit(), Stdio::inherit())
-        } else {
-            (Stdio::null(), Stdio::null())
-        };
-
         let mut command = Command::new(target);
         // Setup args, stdio
         command
@@ -425,11 +492,11 @@ impl Forkserver {
             command.env("ASAN_OPTIONS", asan_options);
         }
 
-        let fsrv_handle = match command
+        command
             .env("LD_BIND_NOW", "1")
             .envs(envs)
             .setlimit(memlimit)
-            .set_coredump(afl_debug)
+            .set_coredump(afl_debug || sandbox.has_core_dumps())
             .setsid()
             .setstdin(input_filefd, use_stdin)
             .setpipe(
@@ -437,9 +504,11 @@ impl Forkserver {
                 st_pipe.write_end().unwrap(),
                 ctl_pipe.read_end().unwrap(),
                 ctl_pipe.write_end().unwrap(),
-            )
-            .spawn()
-        {
+            );
+        // After the pipes are set up, as limiting the open files would prevent dup'ing them
+        sandbox.apply(&mut command)?;
+
+        let fsrv_handle = match command.spawn() {
             Ok(fsrv_handle) => fsrv_handle,
             Err(err) => {
                 return Err(Error::illegal_state(format!(
@@ -594,7 +663,7 @@ impl Forkserver {
                 let val: i32 = i32::from_ne_bytes(buf);
                 Ok(Some(val))
             } else {
-                Err(Error::unknown(
+                Err(forkserver_gone(
                     "Unable to communicate with fork server (OOM?)".to_string(),
                 ))
             }
@@ -604,6 +673,24 @@ impl Forkserver {
     }
 }
 
+/// The capabilities a forkserver target reported during the handshake, and the modes it runs in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ForkserverCapabilities {
+    /// The version of the forkserver protocol, `None` for the old protocol (< AFL++ v4.20c)
+    pub version: Option<u32>,
+    /// The coverage map size reported by the target, if any
+    pub map_size: Option<usize>,
+    /// If the target is able to read testcases from shared memory
+    pub shmem_testcase: bool,
+    /// If the target offered an autodictionary
+    pub autodict: bool,
+    /// If the target runs in persistent mode (`__AFL_LOOP`)
+    pub persistent: bool,
+    /// If the target uses a deferred forkserver (`__AFL_INIT`)
+    pub deferred: bool,
+}
+
 /// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
 ///
 /// Shared memory feature is also available, but you have to set things up in your code.
@@ -628,6 +715,15 @@ where
     asan_obs: Handle<AsanBacktraceObserver>,
     timeout: TimeSpec,
     crash_exitcode: Option<i8>,
+    capabilities: ForkserverCapabilities,
+    /// The coverage map, if it was allocated by the executor after negotiating its size
+    coverage_map: Option<SP::ShMem>,
+    /// The observer and file capturing the `stdout` of the target
+    stdout_capture: Option<(Handle<StdOutObserver>, InputFile)>,
+    /// The observer and file capturing the `stderr` of the target
+    stderr_capture: Option<(Handle<StdErrObserver>, InputFile)>,
+    /// The configuration to restart the forkserver with
+    respawner: ForkserverExecutorBuilder<'static, (), SP>,
 }
 
 impl<TC, OT, S, SP> Debug for ForkserverExecutor<TC, OT, S, SP>
@@ -697,6 +793,49 @@ where
         self.map_size
     }
 
+    /// If the testcases are passed to the target over shared memory, instead of the [`InputFile`].
+    ///
+    /// This is negotiated with the target during the forkserver handshake, and requires
+    /// a [`ForkserverExecutorBuilder::shmem_provider`].
+    pub fn uses_shmem_testcase(&self) -> bool {
+        self.uses_shmem_testcase
+    }
+
+    /// The [`ForkserverCapabilities`] reported by the target during the handshake
+    pub fn capabilities(&self) -> &ForkserverCapabilities {
+        &self.capabilities
+    }
+
+    /// The coverage map shared with the target, if it was allocated by
+    /// [`ForkserverExecutorBuilder::build_negotiated_map`]
+    pub fn coverage_map(&self) -> Option<&SP::ShMem> {
+        self.coverage_map.as_ref()
+    }
+
+    /// Restarts the forkserver, e.g., after it stopped responding, repeating the handshake of the first start.
+    ///
+    /// The new forkserver keeps using the coverage map and the shared memory testcases of the first start.
+    /// The [`crate::executors::RetryExecutor`] can call this before every retry, see
+    /// [`crate::executors::RetryExecutor::with_forkserver`].
+    pub fn restart_forkserver(&mut self) -> Result<(), Error> {
+        let debug_child = self.respawner.debug_child;
+        let stdio = |file: Option<&InputFile>| -> Result<Stdio, Error> {
+            Ok(match file {
+                Some(file) => Stdio::from(file.file.try_clone()?),
+                None if debug_child => Stdio::inherit(),
+                None => Stdio::null(),
+            })
+        };
+        let stdout = stdio(self.stdout_capture.as_ref().map(|(_, file)| file))?;
+        let stderr = stdio(self.stderr_capture.as_ref().map(|(_, file)| file))?;
+
+        log::info!("Restarting the forkserver of {:?}", self.target);
+        self.forkserver =
+            self.respawner
+                .spawn_forkserver(&self.input_file, self.map.as_ref(), stdout, stderr)?;
+        Ok(())
+    }
+
     /// Execute input and increase the execution counter.
     #[inline]
     fn execute_input(&mut self, state: &mut S, input: &TC::Input) -> Result<ExitKind, Error>
@@ -723,13 +862,10 @@ where
         } else if input_size < self.min_input_size {
             // Extend like AFL++ does
             input_size = self.min_input_size;
-            let mut input_bytes_copy = Vec::with_capacity(input_size);
-            input_bytes_copy
-                .as_slice_mut()
-                .copy_from_slice(input_bytes.as_slice());
+            let mut input_bytes_copy = input_bytes.as_slice().to_vec();
+            input_bytes_copy.resize(input_size, 0);
             input_bytes = OwnedSlice::from(input_bytes_copy);
         }
-        let input_size_in_bytes = input_size.to_ne_bytes();
         if self.uses_shmem_testcase {
             debug_assert!(
                 self.map.is_some(),
@@ -738,9 +874,10 @@ where
             // # Safety
             // Struct can never be created when uses_shmem_testcase is true and map is none.
             let map = unsafe { self.map.as_mut().unwrap_unchecked() };
-            // The first four bytes declares the size of the shmem.
-            map.as_slice_mut()[..SHMEM_FUZZ_HDR_SIZE]
-                .copy_from_slice(&input_size_in_bytes[..SHMEM_FUZZ_HDR_SIZE]);
+            // The first four bytes declare the length of the testcase, as `u32`, like AFL++ expects.
+            #[allow(clippy::cast_possible_truncation)] // max_input_size is checked in the builder
+            let input_size_in_bytes = (input_size as u32).to_ne_bytes();
+            map.as_slice_mut()[..SHMEM_FUZZ_HDR_SIZE].copy_from_slice(&input_size_in_bytes);
             map.as_slice_mut()[SHMEM_FUZZ_HDR_SIZE..(SHMEM_FUZZ_HDR_SIZE + input_size)]
                 .copy_from_slice(&input_bytes.as_slice()[..input_size]);
         } else {
@@ -748,21 +885,28 @@ where
                 .write_buf(&input_bytes.as_slice()[..input_size])?;
         }
 
+        if let Some((_, file)) = &mut self.stdout_capture {
+            clear_capture_file(file)?;
+        }
+        if let Some((_, file)) = &mut self.stderr_capture {
+            clear_capture_file(file)?;
+        }
+
         self.forkserver.set_last_run_timed_out(false);
         if let Err(err) = self.forkserver.write_ctl(last_run_timed_out) {
-            return Err(Error::unknown(format!(
+            return Err(forkserver_gone(format!(
                 "Unable to request new process from fork server (OOM?): {err:?}"
             )));
         }
 
         let pid = self.forkserver.read_st().map_err(|err| {
-            Error::unknown(format!(
+            forkserver_gone(format!(
                 "Unable to request new process from fork server (OOM?): {err:?}"
             ))
         })?;
 
         if pid <= 0 {
-            return Err(Error::unknown(
+            return Err(forkserver_gone(
                 "Fork server is misbehaving (OOM?)".to_string(),
             ));
         }
@@ -789,7 +933,7 @@ where
             // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
             let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
             if let Err(err) = self.forkserver.read_st() {
-                return Err(Error::unknown(format!(
+                return Err(forkserver_gone(format!(
                     "Could not kill timed-out child: {err:?}"
                 )));
             }
@@ -800,6 +944,19 @@ where
             self.forkserver.reset_child_pid();
         }
 
+        if let Some((handle, file)) = &mut self.stdout_capture {
+            file.rewind()?;
+            if let Some(observer) = self.observers.get_mut(handle) {
+                observer.observe_stdout_from(&mut file.file)?;
+            }
+        }
+        if let Some((handle, file)) = &mut self.stderr_capture {
+            file.rewind()?;
+            if let Some(observer) = self.observers.get_mut(handle) {
+                observer.observe_stderr_from(&mut file.file)?;
+            }
+        }
+
         Ok(exit_kind)
     }
 }
@@ -816,6 +973,9 @@ pub struct ForkserverExecutorBuilder<'a, TC, SP> {
     uses_shmem_testcase: bool,
     is_persistent: bool,
     is_deferred_frksrv: bool,
+    autodetect_modes: bool,
+    capabilities: ForkserverCapabilities,
+    required_map_size: Option<usize>,
     autotokens: Option<&'a mut Tokens>,
     input_filename: Option<OsString>,
     shmem_provider: Option<&'a mut SP>,
@@ -827,6 +987,11 @@ pub struct ForkserverExecutorBuilder<'a, TC, SP> {
     #[cfg(feature = "regex")]
     asan_obs: Option<Handle<AsanBacktraceObserver>>,
     crash_exitcode: Option<i8>,
+    stdout_observer: Option<Handle<StdOutObserver>>,
+    stderr_observer: Option<Handle<StdErrObserver>>,
+    stdout_file: Option<InputFile>,
+    stderr_file: Option<InputFile>,
+    sandbox: ChildSandbox,
     target_bytes_converter: TC,
 }
 
@@ -849,75 +1014,128 @@ where
         SP: ShMemProvider,
     {
         let (forkserver, input_file, map) = self.build_helper()?;
+        self.into_executor(forkserver, input_file, map, observers, None)
+    }
 
-        let target = self.program.take().unwrap();
-        log::info!(
-            "ForkserverExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}",
-            target,
-            self.arguments.clone(),
-            self.use_stdin
-        );
-
-        if self.uses_shmem_testcase && map.is_none() {
-            return Err(Error::illegal_state(
-                "Map must always be set for `uses_shmem_testcase`",
-            ));
-        }
+    /// Builds `ForkserverExecutor` downsizing the coverage map to fit exaclty the AFL++ map size.
+    #[allow(clippy::pedantic)]
+    pub fn build_dynamic_map<A, MO, OT, S>(
+        mut self,
+        mut map_observer: A,
+        other_observers: OT,
+    ) -> Result<ForkserverExecutor<TC, (A, OT), S, SP>, Error>
+    where
+        MO: MapObserver + Truncate, // TODO maybe enforce Entry = u8 for the cov map
+        A: Observer<S::Input, S> + AsMut<MO>,
+        OT: ObserversTuple<S::Input, S> + Prepend<MO>,
+        S: UsesInput,
+        S::Input: Input + HasTargetBytes,
+        SP: ShMemProvider,
+    {
+        let (forkserver, input_file, map) = self.build_helper()?;
 
-        let timeout: TimeSpec = match self.timeout {
-            Some(t) => t.into(),
-            None => Duration::from_millis(5000).into(),
-        };
-        if self.min_input_size > self.max_input_size {
-            return Err(Error::illegal_argument(
-                format!(
-                    "Minimum input size ({}) must not exceed maximum input size ({})",
-                    self.min_input_size, self.max_input_size
-                )
-                .as_str(),
-            ));
+        if let Some(dynamic_map_size) = self.map_size {
+            map_observer.as_mut().truncate(dynamic_map_size);
         }
 
-        Ok(ForkserverExecutor {
-            target,
-            args: self.arguments.clone(),
-            input_file,
-            uses_shmem_testcase: self.uses_shmem_testcase,
+        self.into_executor(
             forkserver,
-            observers,
+            input_file,
             map,
-            phantom: PhantomData,
-            map_size: self.map_size,
-            min_input_size: self.min_input_size,
-            max_input_size: self.max_input_size,
-            timeout,
-            #[cfg(feature = "regex")]
-            asan_obs: self
-                .asan_obs
-                .clone()
-                .unwrap_or(AsanBacktraceObserver::default().handle()),
-            crash_exitcode: self.crash_exitcode,
-            target_bytes_converter: self.target_bytes_converter,
-        })
+            (map_observer, other_observers),
+            None,
+        )
     }
 
-    /// Builds `ForkserverExecutor` downsizing the coverage map to fit exaclty the AFL++ map size.
+    /// Builds `ForkserverExecutor`, allocating the coverage map in shared memory with the map size
+    /// the target reports during the handshake.
+    ///
+    /// The coverage map is allocated with the [`Self::shmem_provider`], starting with the
+    /// [`Self::coverage_map_size`], or the current size of the `map_observer`. If the target
+    /// reports a larger map, the forkserver is restarted once with a map of the reported size.
+    /// The `map_observer` is remapped to the allocated map, so its initial memory is not used.
+    /// [`crate::feedbacks::MapFeedback`]s grow their history maps to the new size on demand.
     #[allow(clippy::pedantic)]
-    pub fn build_dynamic_map<A, MO, OT, S>(
+    pub fn build_negotiated_map<A, MO, OT, S>(
         mut self,
         mut map_observer: A,
         other_observers: OT,
     ) -> Result<ForkserverExecutor<TC, (A, OT), S, SP>, Error>
     where
-        MO: MapObserver + Truncate, // TODO maybe enforce Entry = u8 for the cov map
+        MO: ResizableMapObserver<Entry = u8>,
         A: Observer<S::Input, S> + AsMut<MO>,
-        OT: ObserversTuple<S::Input, S> + Prepend<MO>,
+        OT: ObserversTuple<S::Input, S>,
         S: UsesInput,
         S::Input: Input + HasTargetBytes,
         SP: ShMemProvider,
     {
-        let (forkserver, input_file, map) = self.build_helper()?;
+        let mut map_len = self
+            .map_size
+            .unwrap_or_else(|| map_observer.as_mut().usable_count());
+
+        let (forkserver, input_file, map, mut coverage_map) = loop {
+            let coverage_map = self
+                .shmem_provider
+                .as_mut()
+                .ok_or_else(|| {
+                    Error::illegal_argument(
+                        "A shmem_provider is needed to negotiate the coverage map size",
+                    )
+                })?
+                .new_shmem(map_len)?;
+            coverage_map.write_to_env("__AFL_SHM_ID")?;
+            self.map_size = Some(map_len);
+            self.required_map_size = None;
+
+            match self.build_helper() {
+                Ok((forkserver, input_file, map)) => {
+                    break (forkserver, input_file, map, coverage_map)
+                }
+                Err(err) => match self.required_map_size {
+                    Some(required) if required > map_len => {
+                        log::info!(
+                            "Target requires a coverage map of {required} bytes, restarting the forkserver"
+                        );
+                        map_len = required;
+                    }
+                    _ => return Err(err),
+                },
+            }
+        };
+
+        // The target may report a smaller map than we allocated
+        let map_len = self.map_size.unwrap_or(map_len).min(map_len);
+        // # Safety
+        // The coverage map is kept alive by the executor, and does not move in memory.
+        unsafe {
+            map_observer
+                .as_mut()
+                .remap(coverage_map.as_slice_mut().as_mut_ptr(), map_len);
+        }
+
+        self.map_size = Some(map_len);
 
+        self.into_executor(
+            forkserver,
+            input_file,
+            map,
+            (map_observer, other_observers),
+            Some(coverage_map),
+        )
+    }
+
+    /// Assembles the [`ForkserverExecutor`] around the started `forkserver`, shared by all builds.
+    ///
+    /// `coverage_map` is the coverage map allocated by the executor, if any, kept alive as long as it runs.
+    fn into_executor<OT, S>(
+        mut self,
+        forkserver: Forkserver,
+        input_file: InputFile,
+        map: Option<SP::ShMem>,
+        observers: OT,
+        coverage_map: Option<SP::ShMem>,
+    ) -> Result<ForkserverExecutor<TC, OT, S, SP>, Error> {
+        let respawner = self.respawner();
         let target = self.program.take().unwrap();
         log::info!(
             "ForkserverExecutor: program: {:?}, arguments: {:?}, use_stdin: {:?}, map_size: {:?}",
@@ -927,12 +1145,6 @@ where
             self.map_size
         );
 
-        if let Some(dynamic_map_size) = self.map_size {
-            map_observer.as_mut().truncate(dynamic_map_size);
-        }
-
-        let observers = (map_observer, other_observers);
-
         if self.uses_shmem_testcase && map.is_none() {
             return Err(Error::illegal_state(
                 "Map must always be set for `uses_shmem_testcase`",
@@ -943,6 +1155,15 @@ where
             Some(t) => t.into(),
             None => Duration::from_millis(5000).into(),
         };
+        if self.min_input_size > self.max_input_size {
+            return Err(Error::illegal_argument(
+                format!(
+                    "Minimum input size ({}) must not exceed maximum input size ({})",
+                    self.min_input_size, self.max_input_size
+                )
+                .as_str(),
+            ));
+        }
 
         Ok(ForkserverExecutor {
             target,
@@ -963,10 +1184,49 @@ where
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            capabilities: self.capabilities,
+            coverage_map,
+            stdout_capture: self.stdout_observer.clone().zip(self.stdout_file.take()),
+            stderr_capture: self.stderr_observer.clone().zip(self.stderr_file.take()),
             target_bytes_converter: self.target_bytes_converter,
+            respawner,
         })
     }
 
+    /// The configuration to restart the forkserver with, see [`ForkserverExecutor::restart_forkserver`]
+    fn respawner(&self) -> ForkserverExecutorBuilder<'static, (), SP> {
+        ForkserverExecutorBuilder {
+            program: self.program.clone(),
+            arguments: self.arguments.clone(),
+            envs: self.envs.clone(),
+            debug_child: self.debug_child,
+            use_stdin: self.use_stdin,
+            uses_shmem_testcase: self.uses_shmem_testcase,
+            is_persistent: self.is_persistent,
+            is_deferred_frksrv: self.is_deferred_frksrv,
+            autodetect_modes: false,
+            capabilities: self.capabilities,
+            required_map_size: None,
+            autotokens: None,
+            input_filename: self.input_filename.clone(),
+            shmem_provider: None,
+            max_input_size: self.max_input_size,
+            min_input_size: self.min_input_size,
+            map_size: self.map_size,
+            kill_signal: self.kill_signal,
+            timeout: self.timeout,
+            #[cfg(feature = "regex")]
+            asan_obs: self.asan_obs.clone(),
+            crash_exitcode: self.crash_exitcode,
+            stdout_observer: None,
+            stderr_observer: None,
+            stdout_file: None,
+            stderr_file: None,
+            sandbox: self.sandbox.clone(),
+            target_bytes_converter: (),
+        }
+    }
+
     #[allow(clippy::pedantic)]
     fn build_helper(&mut self) -> Result<(Forkserver, InputFile, Option<SP::ShMem>), Error>
     where
@@ -980,7 +1240,30 @@ where
             }
         };
 
-        let input_file = InputFile::create(input_filename)?;
+        let input_file = InputFile::create(&input_filename)?;
+
+        let debug_child = self.debug_child;
+        let stdio = |file: &Option<InputFile>| -> Result<Stdio, Error> {
+            Ok(match file {
+                Some(file) => Stdio::from(file.file.try_clone()?),
+                None if debug_child => Stdio::inherit(),
+                None => Stdio::null(),
+            })
+        };
+        if self.stdout_observer.is_some() {
+            self.stdout_file = Some(InputFile::create(capture_filename(
+                &input_filename,
+                "stdout",
+            ))?);
+        }
+        if self.stderr_observer.is_some() {
+            self.stderr_file = Some(InputFile::create(capture_filename(
+                &input_filename,
+                "stderr",
+            ))?);
+        }
+        let stdout = stdio(&self.stdout_file)?;
+        let stderr = stdio(&self.stderr_file)?;
 
         let map = match &mut self.shmem_provider {
             None => None,
@@ -989,14 +1272,38 @@ where
                 let mut shmem = provider.new_shmem(self.max_input_size + SHMEM_FUZZ_HDR_SIZE)?;
                 shmem.write_to_env("__AFL_SHM_FUZZ_ID")?;
 
-                let size_in_bytes = (self.max_input_size + SHMEM_FUZZ_HDR_SIZE).to_ne_bytes();
-                shmem.as_slice_mut()[..4].clone_from_slice(&size_in_bytes[..4]);
+                let size =
+                    u32::try_from(self.max_input_size + SHMEM_FUZZ_HDR_SIZE).map_err(|_| {
+                        Error::illegal_argument(format!(
+                            "max_input_size {} is too large for shared memory testcases",
+                            self.max_input_size
+                        ))
+                    })?;
+                shmem.as_slice_mut()[..SHMEM_FUZZ_HDR_SIZE].clone_from_slice(&size.to_ne_bytes());
                 Some(shmem)
             }
         };
 
+        if self.autodetect_modes {
+            self.detect_modes();
+        }
+        self.capabilities.persistent = self.is_persistent;
+        self.capabilities.deferred = self.is_deferred_frksrv;
+
+        let forkserver = self.spawn_forkserver(&input_file, map.as_ref(), stdout, stderr)?;
+        Ok((forkserver, input_file, map))
+    }
+
+    /// Spawns the forkserver, and runs the handshake with the target
+    fn spawn_forkserver(
+        &mut self,
+        input_file: &InputFile,
+        map: Option<&SP::ShMem>,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> Result<Forkserver, Error> {
         let mut forkserver = match &self.program {
-            Some(t) => Forkserver::with_kill_signal(
+            Some(t) => Forkserver::with_stdio(
                 t.clone(),
                 self.arguments.clone(),
                 self.envs.clone(),
@@ -1007,7 +1314,9 @@ where
                 self.is_deferred_frksrv,
                 self.has_asan_obs(),
                 self.map_size,
-                self.debug_child,
+                stdout,
+                stderr,
+                &self.sandbox,
                 self.kill_signal.unwrap_or(KILL_SIGNAL_DEFAULT),
             )?,
             None => {
@@ -1028,11 +1337,34 @@ where
 
         if Self::is_old_forkserver(version_status) {
             log::info!("Old fork server model is used by the target, this still works though.");
-            self.initialize_old_forkserver(version_status, map.as_ref(), &mut forkserver)?;
+            self.initialize_old_forkserver(version_status, map, &mut forkserver)?;
         } else {
-            self.initialize_forkserver(version_status, map.as_ref(), &mut forkserver)?;
+            self.initialize_forkserver(version_status, map, &mut forkserver)?;
+        }
+        Ok(forkserver)
+    }
+
+    /// Enables persistent and deferred forkserver mode, if the target binary contains their signatures
+    fn detect_modes(&mut self) {
+        let Some(program) = &self.program else {
+            return;
+        };
+        let binary = match fs::read(program) {
+            Ok(binary) => binary,
+            Err(err) => {
+                log::warn!("Could not read {program:?} to detect the forkserver modes: {err}");
+                return;
+            }
+        };
+        let contains = |sig: &[u8]| binary.windows(sig.len()).any(|window| window == sig);
+        if contains(PERSISTENT_SIG) {
+            log::info!("Persistent mode binary detected.");
+            self.is_persistent = true;
+        }
+        if contains(DEFERRED_SIG) {
+            log::info!("Deferred forkserver binary detected.");
+            self.is_deferred_frksrv = true;
         }
-        Ok((forkserver, input_file, map))
     }
 
     fn is_old_forkserver(version_status: i32) -> bool {
@@ -1055,7 +1387,7 @@ where
                 return Err(Error::illegal_state("Fork server version is not assigned, this should not happen. Recompile target."));
             }
             FS_NEW_VERSION_MIN..=FS_NEW_VERSION_MAX => {
-                // good, do nothing
+                self.capabilities.version = Some(version);
             }
             _ => {
                 return Err(Error::illegal_state(
@@ -1085,9 +1417,11 @@ where
             let fsrv_map_size = forkserver.read_st().map_err(|err| {
                 Error::illegal_state(format!("Failed to read map size from forkserver: {err:?}"))
             })?;
-            self.set_map_size(fsrv_map_size)?;
+            self.capabilities.map_size = Some(self.set_map_size(fsrv_map_size)?);
         }
 
+        self.capabilities.shmem_testcase = status & FS_NEW_OPT_SHDMEM_FUZZ != 0;
+        self.capabilities.autodict = status & FS_NEW_OPT_AUTODTCT != 0;
         if status & FS_NEW_OPT_SHDMEM_FUZZ != 0 {
             if map.is_some() {
                 log::info!("Using SHARED MEMORY FUZZING feature.");
@@ -1149,7 +1483,11 @@ where
     ) -> Result<(), Error> {
         if status & FS_OPT_ENABLED == FS_OPT_ENABLED && status & FS_OPT_MAPSIZE == FS_OPT_MAPSIZE {
             let fsrv_map_size = fs_opt_get_mapsize(status);
-            self.set_map_size(fsrv_map_size)?;
+            self.capabilities.map_size = Some(self.set_map_size(fsrv_map_size)?);
+        }
+        if status & FS_OPT_ENABLED == FS_OPT_ENABLED {
+            self.capabilities.shmem_testcase = status & FS_OPT_SHDMEM_FUZZ == FS_OPT_SHDMEM_FUZZ;
+            self.capabilities.autodict = status & FS_OPT_AUTODTCT == FS_OPT_AUTODTCT;
         }
 
         // Only with SHMEM or AUTODTCT we can send send_status back or it breaks!
@@ -1231,6 +1569,7 @@ where
         // TODO set AFL_MAP_SIZE
         if let Some(max_size) = self.map_size {
             if actual_map_size as usize > max_size {
+                self.required_map_size = Some(actual_map_size as usize);
                 return Err(Error::illegal_state(format!(
                     "The target map size is {actual_map_size} but the allocated map size is {max_size}. \
                     Increase the initial size of the forkserver map to at least that size using the forkserver builder's `coverage_map_size`."
@@ -1424,6 +1763,30 @@ where
         self
     }
 
+    /// Captures the `stdout` of the target into the given [`StdOutObserver`], which must be
+    /// part of the observers of the executor. This takes precedence over [`Self::debug_child`].
+    #[must_use]
+    pub fn stdout_observer(mut self, stdout: Handle<StdOutObserver>) -> Self {
+        self.stdout_observer = Some(stdout);
+        self
+    }
+
+    /// Captures the `stderr` of the target into the given [`StdErrObserver`], which must be
+    /// part of the observers of the executor. This takes precedence over [`Self::debug_child`].
+    #[must_use]
+    pub fn stderr_observer(mut self, stderr: Handle<StdErrObserver>) -> Self {
+        self.stderr_observer = Some(stderr);
+        self
+    }
+
+    /// Sets the resource limits and isolation of the forkserver, inherited by each forked child,
+    /// see [`ChildSandbox`].
+    #[must_use]
+    pub fn sandbox(mut self, sandbox: ChildSandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
     /// Call this if you want to run it under persistent mode; default is false
     #[must_use]
     pub fn is_persistent(mut self, is_persistent: bool) -> Self {
@@ -1445,6 +1808,15 @@ where
         self
     }
 
+    /// Detect persistent mode and deferred forkserver mode from the signatures AFL++ embeds in the
+    /// target binary, like `afl-fuzz` does; default is false.
+    /// Modes enabled with [`Self::is_persistent`] or [`Self::is_deferred_frksrv`] stay enabled.
+    #[must_use]
+    pub fn autodetect_modes(mut self, autodetect_modes: bool) -> Self {
+        self.autodetect_modes = autodetect_modes;
+        self
+    }
+
     /// Call this to set a defauult const coverage map size
     #[must_use]
     pub fn coverage_map_size(mut self, size: usize) -> Self {
@@ -1491,6 +1863,9 @@ impl<'a> ForkserverExecutorBuilder<'a, NopTargetBytesConverter<BytesInput>, Unix
             uses_shmem_testcase: false,
             is_persistent: false,
             is_deferred_frksrv: false,
+            autodetect_modes: false,
+            capabilities: ForkserverCapabilities::default(),
+            required_map_size: None,
             autotokens: None,
             input_filename: None,
             shmem_provider: None,
@@ -1502,6 +1877,11 @@ impl<'a> ForkserverExecutorBuilder<'a, NopTargetBytesConverter<BytesInput>, Unix
             #[cfg(feature = "regex")]
             asan_obs: None,
             crash_exitcode: None,
+            stdout_observer: None,
+            stderr_observer: None,
+            stdout_file: None,
+            stderr_file: None,
+            sandbox: ChildSandbox::new(),
             target_bytes_converter: NopTargetBytesConverter::new(),
         }
     }
@@ -1525,6 +1905,9 @@ impl<'a, TC> ForkserverExecutorBuilder<'a, TC, UnixShMemProvider> {
             uses_shmem_testcase: self.uses_shmem_testcase,
             is_persistent: self.is_persistent,
             is_deferred_frksrv: self.is_deferred_frksrv,
+            autodetect_modes: self.autodetect_modes,
+            capabilities: self.capabilities,
+            required_map_size: self.required_map_size,
             autotokens: self.autotokens,
             input_filename: self.input_filename,
             map_size: self.map_size,
@@ -1535,6 +1918,11 @@ impl<'a, TC> ForkserverExecutorBuilder<'a, TC, UnixShMemProvider> {
             #[cfg(feature = "regex")]
             asan_obs: self.asan_obs,
             crash_exitcode: self.crash_exitcode,
+            stdout_observer: self.stdout_observer,
+            stderr_observer: self.stderr_observer,
+            stdout_file: self.stdout_file,
+            stderr_file: self.stderr_file,
+            sandbox: self.sandbox,
             target_bytes_converter: self.target_bytes_converter,
         }
     }
@@ -1558,6 +1946,9 @@ impl<'a, TC, SP> ForkserverExecutorBuilder<'a, TC, SP> {
             uses_shmem_testcase: self.uses_shmem_testcase,
             is_persistent: self.is_persistent,
             is_deferred_frksrv: self.is_deferred_frksrv,
+            autodetect_modes: self.autodetect_modes,
+            capabilities: self.capabilities,
+            required_map_size: self.required_map_size,
             autotokens: self.autotokens,
             input_filename: self.input_filename,
             map_size: self.map_size,
@@ -1568,6 +1959,11 @@ impl<'a, TC, SP> ForkserverExecutorBuilder<'a, TC, SP> {
             #[cfg(feature = "regex")]
             asan_obs: self.asan_obs,
             crash_exitcode: self.crash_exitcode,
+            stdout_observer: self.stdout_observer,
+            stderr_observer: self.stderr_observer,
+            stdout_file: self.stdout_file,
+            stderr_file: self.stderr_file,
+            sandbox: self.sandbox,
             target_bytes_converter,
         }
     }
@@ -1656,11 +2052,36 @@ mod tests {
     use serial_test::serial;
 
     use crate::{
-        executors::forkserver::{ForkserverExecutor, FAILED_TO_START_FORKSERVER_MSG},
+        executors::forkserver::{
+            fs_opt_get_mapsize, ForkserverExecutor, FAILED_TO_START_FORKSERVER_MSG, FS_OPT_ENABLED,
+            FS_OPT_MAPSIZE,
+        },
         observers::{ConstMapObserver, HitcountsMapObserver},
         Error,
     };
 
+    #[test]
+    fn test_negotiate_map_size() {
+        // AFL++ reports the map size as `(map_size - 1) << 1` in the handshake
+        let status = FS_OPT_ENABLED | FS_OPT_MAPSIZE | ((100_000 - 1) << 1);
+        assert_eq!(fs_opt_get_mapsize(status), 100_000);
+
+        // The reported size is rounded up to 64 bytes, and remembered if it does not fit
+        let mut builder = ForkserverExecutor::builder().coverage_map_size(65536);
+        assert!(builder.set_map_size(fs_opt_get_mapsize(status)).is_err());
+        assert_eq!(builder.required_map_size, Some(100_032));
+
+        let mut builder = builder.coverage_map_size(131_072);
+        assert_eq!(
+            builder.set_map_size(fs_opt_get_mapsize(status)).unwrap(),
+            100_032
+        );
+        assert_eq!(builder.map_size, Some(100_032));
+
+        let mut builder = ForkserverExecutor::builder();
+        assert!(builder.set_map_size(fs_opt_get_mapsize(status)).is_err());
+    }
+
     #[test]
     #[serial]
     #[cfg_attr(miri, ignore)]
@@ -2,11 +2,12 @@
 #[cfg(all(target_os = "linux", feature = "std"))]
 use core::mem::zeroed;
 #[cfg(any(unix, all(windows, feature = "std")))]
-use core::sync::atomic::{compiler_fence, Ordering};
+use core::sync::atomic::compiler_fence;
 use core::{
     ffi::c_void,
     marker::PhantomData,
     ptr::{self, null_mut},
+    sync::atomic::{AtomicBool, Ordering},
     time::Duration,
 };
 
@@ -28,7 +29,9 @@ use crate::state::State;
 use crate::{
     corpus::Corpus,
     events::{EventFirer, EventRestarter},
-    executors::{hooks::ExecutorHook, inprocess::HasInProcessHooks, Executor, HasObservers},
+    executors::{
+        hooks::ExecutorHook, inprocess::HasInProcessHooks, Executor, ExitKind, HasObservers,
+    },
     feedbacks::Feedback,
     inputs::UsesInput,
     observers::ObserversTuple,
@@ -525,3 +528,23 @@ pub fn inprocess_in_handler() -> bool {
     // Safe because the state is set up and the handler is a single bool. Worst case we read an old value.
     unsafe { GLOBAL_STATE.in_handler }
 }
+
+/// Set by [`inprocess_report_oom`], until the next crash
+static OOM_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Reports the next crash of the target as [`ExitKind::Oom`] instead of [`ExitKind::Crash`].
+///
+/// For allocator hooks enforcing a memory limit from within the target: they report the OOM, then abort.
+pub fn inprocess_report_oom() {
+    OOM_REPORTED.store(true, Ordering::Relaxed);
+}
+
+/// The [`ExitKind`] of a crash of the target, see [`inprocess_report_oom`]
+#[cfg(any(unix, feature = "std"))]
+pub(crate) fn inprocess_crash_exit_kind() -> ExitKind {
+    if OOM_REPORTED.swap(false, Ordering::Relaxed) {
+        ExitKind::Oom
+    } else {
+        ExitKind::Crash
+    }
+}
@@ -1,5 +1,13 @@
 //! Hooks for the executors.
 //! These will be executed right before and after the executor's harness run.
+//!
+//! Unlike observers, hooks do not observe the execution, but prepare the target for it,
+//! e.g., by resetting global state of the target, flushing caches, or toggling instrumentation.
+//! Register your own hooks with [`crate::executors::inprocess::GenericInProcessExecutor::generic`]
+//! or [`crate::executors::command::CommandExecutorBuilder::build_with_hooks`], for example
+//! as a [`ClosureExecutorHook`].
+
+use core::fmt::{self, Debug, Formatter};
 
 use crate::{executors::HasObservers, inputs::UsesInput};
 
@@ -82,3 +90,40 @@ where
         self.1.post_exec_all(state, input);
     }
 }
+
+/// An [`ExecutorHook`] running closures right before and after the target
+pub struct ClosureExecutorHook<PRE, POST> {
+    pre: PRE,
+    post: POST,
+}
+
+impl<PRE, POST> ClosureExecutorHook<PRE, POST> {
+    /// Creates a new [`ClosureExecutorHook`], running `pre` right before and `post` right after the target
+    pub fn new(pre: PRE, post: POST) -> Self {
+        Self { pre, post }
+    }
+}
+
+impl<PRE, POST> Debug for ClosureExecutorHook<PRE, POST> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClosureExecutorHook")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<PRE, POST, S> ExecutorHook<S> for ClosureExecutorHook<PRE, POST>
+where
+    S: UsesInput,
+    PRE: FnMut(&mut S, &S::Input),
+    POST: FnMut(&mut S, &S::Input),
+{
+    fn init<E: HasObservers>(&mut self, _state: &mut S) {}
+
+    fn pre_exec(&mut self, state: &mut S, input: &S::Input) {
+        (self.pre)(state, input);
+    }
+
+    fn post_exec(&mut self, state: &mut S, input: &S::Input) {
+        (self.post)(state, input);
+    }
+}
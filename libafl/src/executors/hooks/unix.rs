@@ -13,14 +13,19 @@ pub mod unix_signal_handler {
         events::{EventFirer, EventRestarter},
         executors::{
             common_signals,
-            hooks::inprocess::{HasTimeout, InProcessExecutorHandlerData, GLOBAL_STATE},
+            hooks::inprocess::{
+                inprocess_crash_exit_kind, HasTimeout, InProcessExecutorHandlerData, GLOBAL_STATE,
+            },
             inprocess::{run_observers_and_save_state, HasInProcessHooks},
             Executor, ExitKind, HasObservers,
         },
         feedbacks::Feedback,
         fuzzer::HasObjective,
         inputs::{Input, UsesInput},
-        observers::ObserversTuple,
+        observers::{
+            crash_context::{is_crash_context_enabled, record_crash_context},
+            CrashContext, ObserversTuple,
+        },
         state::{HasCorpus, HasExecutions, HasSolutions, UsesState},
     };
 
@@ -101,6 +106,12 @@ pub mod unix_signal_handler {
                 let fuzzer = (*data).fuzzer_mut::<Z>();
                 let event_mgr = (*data).event_mgr_mut::<EM>();
 
+                if is_crash_context_enabled() {
+                    // The panic hook is no signal handler, so the backtrace can be symbolized right away
+                    let mut context = CrashContext::capture(Signal::SigAbort, None, None);
+                    context.resolve();
+                    record_crash_context(context);
+                }
                 run_observers_and_save_state::<E, EM, OF, Z>(
                     executor,
                     state,
@@ -234,13 +245,20 @@ pub mod unix_signal_handler {
                 }
             }
 
+            if is_crash_context_enabled() {
+                record_crash_context(CrashContext::capture(
+                    signal,
+                    Some(_info),
+                    _context.as_deref(),
+                ));
+            }
             run_observers_and_save_state::<E, EM, OF, Z>(
                 executor,
                 state,
                 input,
                 fuzzer,
                 event_mgr,
-                ExitKind::Crash,
+                inprocess_crash_exit_kind(),
             );
         } else {
             {
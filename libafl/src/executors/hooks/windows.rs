@@ -130,7 +130,9 @@ pub mod windows_exception_handler {
         corpus::Corpus,
         events::{EventFirer, EventRestarter},
         executors::{
-            hooks::inprocess::{HasTimeout, InProcessExecutorHandlerData, GLOBAL_STATE},
+            hooks::inprocess::{
+                inprocess_crash_exit_kind, HasTimeout, InProcessExecutorHandlerData, GLOBAL_STATE,
+            },
             inprocess::{run_observers_and_save_state, HasInProcessHooks},
             Executor, ExitKind, HasObservers,
         },
@@ -432,7 +434,7 @@ pub mod windows_exception_handler {
                     input,
                     fuzzer,
                     event_mgr,
-                    ExitKind::Crash,
+                    inprocess_crash_exit_kind(),
                 );
             } else {
                 // This is not worth saving
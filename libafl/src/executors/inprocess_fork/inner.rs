@@ -6,6 +6,7 @@ use core::{
     sync::atomic::{compiler_fence, Ordering},
     time::Duration,
 };
+use std::io;
 
 use libafl_bolts::{
     os::unix_signals::Signal,
@@ -123,15 +124,18 @@ where
     EM: EventFirer<State = S> + EventRestarter<State = S>,
     Z: UsesState<State = S>,
 {
+    /// Runs in the child, right after the fork, before any executor code touches the shared memory.
+    pub(super) fn post_fork_child(&mut self) -> Result<(), Error> {
+        self.shmem_provider.post_fork(true)
+    }
+
     pub(super) unsafe fn pre_run_target_child(
         &mut self,
         fuzzer: &mut Z,
         state: &mut <GenericInProcessForkExecutorInner<HT, OT, S, SP, EM, Z> as UsesState>::State,
         mgr: &mut EM,
         input: &<GenericInProcessForkExecutorInner<HT, OT, S, SP, EM, Z> as UsesInput>::Input,
-    ) -> Result<(), Error> {
-        self.shmem_provider.post_fork(true)?;
-
+    ) {
         self.enter_target(fuzzer, state, mgr, input);
         self.hooks.pre_exec_all(state, input);
 
@@ -154,8 +158,6 @@ where
             setitimer(ITIMER_REAL, &mut self.itimerval, null_mut());
         }
         // log::trace!("{v:#?} {}", nix::errno::errno());
-
-        Ok(())
     }
 
     pub(super) unsafe fn post_run_target_child(
@@ -179,7 +181,8 @@ where
         // log::trace!("from parent {} child is {}", std::process::id(), child);
         self.shmem_provider.post_fork(false)?;
 
-        let res = waitpid(child, None)?;
+        let res = waitpid(child, None)
+            .map_err(|err| Error::os_error(io::Error::from(err), "waitpid failed"))?;
         log::trace!("{res:#?}");
         match res {
             WaitStatus::Signaled(_, signal, _) => match signal {
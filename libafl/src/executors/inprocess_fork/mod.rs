@@ -1,8 +1,18 @@
 //! The `GenericInProcessForkExecutor` to do forking before executing the harness in-processly
+//!
+//! Initialize the harness once in the fuzzer process, e.g., load models or configs, then every execution runs in a
+//! fresh fork of it, so changes to global state never leak between runs. Observers must live in shared memory, such as
+//! a [`crate::observers::StdMapObserver`] on a [`libafl_bolts::shmem::ShMem`], to hand their data back to the parent.
+//! Whatever needs to be redone in every child, such as reseeding RNGs or reopening file descriptors, goes into a
+//! [`GenericInProcessForkExecutor::with_post_fork_hook`].
+//!
+//! Windows has no `fork`, so this executor is Unix-only.
+use alloc::{boxed::Box, vec::Vec};
 use core::{
     fmt::{self, Debug, Formatter},
     time::Duration,
 };
+use std::io;
 
 use libafl_bolts::{
     os::unix_signals::{ucontext_t, Signal},
@@ -96,6 +106,7 @@ where
     Z: UsesState<State = S>,
 {
     harness_fn: &'a mut H,
+    post_fork_hooks: Vec<Box<dyn FnMut() + 'a>>,
     inner: GenericInProcessForkExecutorInner<HT, OT, S, SP, EM, Z>,
 }
 
@@ -113,7 +124,8 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("GenericInProcessForkExecutor")
             .field("GenericInProcessForkExecutorInner", &self.inner)
-            .finish()
+            .field("post_fork_hooks", &self.post_fork_hooks.len())
+            .finish_non_exhaustive()
     }
 
     #[cfg(not(target_os = "linux"))]
@@ -122,7 +134,8 @@ where
         return f
             .debug_struct("GenericInProcessForkExecutor")
             .field("GenericInProcessForkExecutorInner", &self.inner)
-            .finish();
+            .field("post_fork_hooks", &self.post_fork_hooks.len())
+            .finish_non_exhaustive();
     }
 }
 
@@ -167,7 +180,11 @@ where
             match fork() {
                 Ok(ForkResult::Child) => {
                     // Child
-                    self.inner.pre_run_target_child(fuzzer, state, mgr, input)?;
+                    self.inner.post_fork_child()?;
+                    for hook in &mut self.post_fork_hooks {
+                        hook();
+                    }
+                    self.inner.pre_run_target_child(fuzzer, state, mgr, input);
                     (self.harness_fn)(input);
                     self.inner.post_run_target_child(fuzzer, state, mgr, input);
                     Ok(ExitKind::Ok)
@@ -176,7 +193,8 @@ where
                     // Parent
                     self.inner.parent(child)
                 }
-                Err(e) => Err(Error::from(e)),
+                // Keep the errno, so that e.g. an `EAGAIN` can be retried
+                Err(e) => Err(Error::os_error(io::Error::from(e), "fork failed")),
             }
         }
     }
@@ -208,6 +226,7 @@ where
 where {
         Ok(Self {
             harness_fn,
+            post_fork_hooks: Vec::new(),
             inner: GenericInProcessForkExecutorInner::with_hooks(
                 userhooks,
                 observers,
@@ -233,6 +252,30 @@ where {
     }
 }
 
+impl<'a, H, HT, OT, S, SP, EM, Z> GenericInProcessForkExecutor<'a, H, HT, OT, S, SP, EM, Z>
+where
+    H: FnMut(&S::Input) -> ExitKind + ?Sized,
+    OT: ObserversTuple<S::Input, S>,
+    S: UsesInput,
+    SP: ShMemProvider,
+    HT: ExecutorHooksTuple<S>,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    /// Adds a hook run in every child, right after the fork and before the timeout starts,
+    /// to re-initialize the parts of the target that must not be shared with the parent.
+    ///
+    /// Hooks run in the order they were added.
+    #[must_use]
+    pub fn with_post_fork_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut() + 'a,
+    {
+        self.post_fork_hooks.push(Box::new(hook));
+        self
+    }
+}
+
 impl<H, HT, OT, S, SP, EM, Z> HasObservers
     for GenericInProcessForkExecutor<'_, H, HT, OT, S, SP, EM, Z>
 where
@@ -356,6 +399,8 @@ pub mod child_signal_handlers {
 #[cfg(test)]
 #[cfg(all(feature = "std", feature = "fork", unix))]
 mod tests {
+    use alloc::vec::Vec;
+
     use libafl_bolts::tuples::tuple_list;
     use serial_test::serial;
 
@@ -415,6 +460,7 @@ mod tests {
         #[cfg(target_os = "linux")]
         let mut in_process_fork_executor = GenericInProcessForkExecutor {
             harness_fn: &mut harness,
+            post_fork_hooks: Vec::new(),
             inner: GenericInProcessForkExecutorInner {
                 hooks: tuple_list!(default),
                 shmem_provider: provider,
@@ -426,6 +472,7 @@ mod tests {
         #[cfg(not(target_os = "linux"))]
         let mut in_process_fork_executor = GenericInProcessForkExecutor {
             harness_fn: &mut harness,
+            post_fork_hooks: Vec::new(),
             inner: GenericInProcessForkExecutorInner {
                 hooks: tuple_list!(default),
                 shmem_provider: provider,
@@ -442,4 +489,71 @@ mod tests {
             .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
             .unwrap();
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_inprocessfork_post_fork_hook() {
+        use core::{
+            marker::PhantomData,
+            sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        };
+
+        use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+        use libc::{itimerspec, timespec};
+
+        use crate::{
+            events::SimpleEventManager,
+            executors::{
+                hooks::inprocess_fork::InChildProcessHooks,
+                inprocess_fork::GenericInProcessForkExecutor,
+            },
+            fuzzer::NopFuzzer,
+            state::NopState,
+        };
+
+        static INITIALIZED: AtomicBool = AtomicBool::new(false);
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let timespec = timespec {
+            tv_sec: 5,
+            tv_nsec: 0,
+        };
+        let mut harness = |_buf: &NopInput| {
+            // Aborts, i.e., crashes, if the hook did not run, or a previous run leaked
+            if !INITIALIZED.load(Ordering::SeqCst) || RUNS.fetch_add(1, Ordering::SeqCst) != 0 {
+                std::process::abort();
+            }
+            ExitKind::Ok
+        };
+        let mut in_process_fork_executor = GenericInProcessForkExecutor {
+            harness_fn: &mut harness,
+            post_fork_hooks: Vec::new(),
+            inner: GenericInProcessForkExecutorInner {
+                hooks: tuple_list!(InChildProcessHooks::nop()),
+                shmem_provider: StdShMemProvider::new().unwrap(),
+                observers: tuple_list!(),
+                itimerspec: itimerspec {
+                    it_interval: timespec,
+                    it_value: timespec,
+                },
+                phantom: PhantomData,
+            },
+        }
+        .with_post_fork_hook(|| INITIALIZED.store(true, Ordering::SeqCst));
+
+        let input = NopInput {};
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::new();
+        let mut mgr = SimpleEventManager::printing();
+        for _ in 0..2 {
+            let exit_kind = in_process_fork_executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+        }
+        assert!(!INITIALIZED.load(Ordering::SeqCst));
+        assert_eq!(RUNS.load(Ordering::SeqCst), 0);
+    }
 }
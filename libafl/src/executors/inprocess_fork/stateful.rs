@@ -2,11 +2,13 @@
 //!
 //! The `StatefulGenericInProcessForkExecutor` to do forking before executing the harness in-process.
 //! The harness can access internal state.
+use alloc::{boxed::Box, vec::Vec};
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
     time::Duration,
 };
+use std::io;
 
 use libafl_bolts::{
     shmem::ShMemProvider,
@@ -72,6 +74,8 @@ where
 {
     /// The harness function, being executed for each fuzzing loop execution
     harness_fn: &'a mut H,
+    /// Hooks run in every child, right after the fork
+    post_fork_hooks: Vec<Box<dyn FnMut() + 'a>>,
     /// The state used as argument of the harness
     pub exposed_executor_state: ES,
     /// Inner state of the executor
@@ -92,7 +96,8 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("GenericInProcessForkExecutor")
             .field("GenericInProcessForkExecutionInner", &self.inner)
-            .finish()
+            .field("post_fork_hooks", &self.post_fork_hooks.len())
+            .finish_non_exhaustive()
     }
 
     #[cfg(not(target_os = "linux"))]
@@ -101,7 +106,8 @@ where
         return f
             .debug_struct("GenericInProcessForkExecutor")
             .field("GenericInProcessForkExecutionInner", &self.inner)
-            .finish();
+            .field("post_fork_hooks", &self.post_fork_hooks.len())
+            .finish_non_exhaustive();
     }
 }
 
@@ -142,7 +148,11 @@ where
             match fork() {
                 Ok(ForkResult::Child) => {
                     // Child
-                    self.inner.pre_run_target_child(fuzzer, state, mgr, input)?;
+                    self.inner.post_fork_child()?;
+                    for hook in &mut self.post_fork_hooks {
+                        hook();
+                    }
+                    self.inner.pre_run_target_child(fuzzer, state, mgr, input);
                     (self.harness_fn)(&mut self.exposed_executor_state, input);
                     self.inner.post_run_target_child(fuzzer, state, mgr, input);
                     Ok(ExitKind::Ok)
@@ -151,7 +161,8 @@ where
                     // Parent
                     self.inner.parent(child)
                 }
-                Err(e) => Err(Error::from(e)),
+                // Keep the errno, so that e.g. an `EAGAIN` can be retried
+                Err(e) => Err(Error::os_error(io::Error::from(e), "fork failed")),
             }
         }
     }
@@ -180,6 +191,7 @@ where
     ) -> Result<Self, Error> {
         Ok(Self {
             harness_fn,
+            post_fork_hooks: Vec::new(),
             exposed_executor_state,
             inner: GenericInProcessForkExecutorInner::with_hooks(
                 userhooks,
@@ -205,6 +217,19 @@ where
     pub fn harness_mut(&mut self) -> &mut H {
         self.harness_fn
     }
+
+    /// Adds a hook run in every child, right after the fork and before the timeout starts,
+    /// to re-initialize the parts of the target that must not be shared with the parent.
+    ///
+    /// Hooks run in the order they were added.
+    #[must_use]
+    pub fn with_post_fork_hook<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut() + 'a,
+    {
+        self.post_fork_hooks.push(Box::new(hook));
+        self
+    }
 }
 
 impl<H, HT, OT, S, SP, ES, EM, Z> HasObservers
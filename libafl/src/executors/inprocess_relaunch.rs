@@ -0,0 +1,542 @@
+//! The [`InProcessRelaunchExecutor`] isolates each run of an in-process harness in a fresh child process.
+//!
+//! It is the counterpart of the `InProcessForkExecutor` for platforms without `fork`,
+//! most notably Windows. Instead of forking, each run relaunches the current executable with the input
+//! in shared memory. In the child, [`InProcessRelaunchExecutor::run_if_child`] runs the harness on that
+//! input, sends the [`ExitKind`] and the observers back over shared memory, and exits. Crashes and hangs
+//! of the harness only take down the child, and are reported as [`ExitKind::Crash`] and
+//! [`ExitKind::Timeout`]. A child failing to run the input at all is reported as an error.
+//!
+//! Everything the fuzzer does before [`InProcessRelaunchExecutor::run_if_child`] is repeated in each
+//! child, so call it first thing in `main`, before setting up the event manager or the state:
+//!
+//! ```rust,ignore
+//! fn main() {
+//!     let mut shmem_provider = StdShMemProvider::new().unwrap();
+//!     let mut observers = tuple_list!(edges_observer);
+//!     // In a relaunched child, this runs the input and exits
+//!     InProcessRelaunchExecutor::run_if_child(&mut harness, &mut observers, &mut shmem_provider);
+//!
+//!     let (state, mut mgr) = setup_restarting_mgr_std(monitor, 1337, EventConfig::AlwaysUnique)?;
+//!     // ...
+//!     let mut executor =
+//!         InProcessRelaunchExecutor::new(observers, timeout, max_input_size, shmem_provider)?;
+//! }
+//! ```
+//!
+//! The children do not inherit the environment of the restarting event managers, so they never
+//! attach to the event manager of the fuzzer.
+//! Like with the `RemoteExecutor`, the child only calls `pre_exec` on its observers, with a
+//! [`NopState`], and their `post_exec` runs in the fuzzer. The observers of the child are merged into
+//! the observers of the executor, so they must implement [`RemoteObserversTuple`].
+//! Observers of crashing or hanging runs are not sent back, and stay as they were before the run.
+
+use alloc::string::ToString;
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{env, process, string::String};
+
+use libafl_bolts::{
+    os::startable_self,
+    shmem::{ShMem, ShMemProvider},
+    tuples::RefIndexable,
+    AsSlice, AsSliceMut,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use wait_timeout::ChildExt;
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::Input,
+    observers::{ObserversTuple, RemoteObserversTuple},
+    state::{HasExecutions, NopState, State, UsesState},
+    Error,
+};
+
+/// The env var holding the shared memory with the input, set for the relaunched child
+const INPUT_SHMEM_ENV: &str = "_LIBAFL_RELAUNCH_INPUT";
+/// The env var holding the shared memory for the result, set for the relaunched child
+const RESULT_SHMEM_ENV: &str = "_LIBAFL_RELAUNCH_RESULT";
+/// The size of the length header in front of the serialized input and result
+const LEN_HDR_SIZE: usize = 4;
+/// The prefix of the env vars of the restarting event managers, which the children must not see
+const EVENT_MGR_ENV_PREFIX: &str = "_AFL_ENV_";
+/// The exit code of a child that failed to run the input, as opposed to a crashing harness
+const CHILD_FAILED_EXIT_CODE: i32 = 77;
+
+/// The default size of the shared memory holding the serialized [`ExitKind`] and observers
+pub const DEFAULT_RELAUNCH_RESULT_SIZE: usize = 1 << 22;
+
+/// Serializes the message into the shared memory, prefixed with its length
+fn write_shmem<M, SHM>(shmem: &mut SHM, msg: &M) -> Result<(), Error>
+where
+    M: Serialize,
+    SHM: ShMem,
+{
+    let buf = postcard::to_allocvec(msg)?;
+    if buf.len() + LEN_HDR_SIZE > shmem.len() {
+        return Err(Error::illegal_argument(format!(
+            "Serialized message of {} bytes does not fit the shared memory of {} bytes",
+            buf.len(),
+            shmem.len()
+        )));
+    }
+    #[allow(clippy::cast_possible_truncation)] // the shared memory is smaller than 4 GiB
+    let len = (buf.len() as u32).to_le_bytes();
+    let slice = shmem.as_slice_mut();
+    slice[..LEN_HDR_SIZE].copy_from_slice(&len);
+    slice[LEN_HDR_SIZE..LEN_HDR_SIZE + buf.len()].copy_from_slice(&buf);
+    Ok(())
+}
+
+/// What the relaunched child sends back
+#[derive(Debug, Serialize, Deserialize)]
+enum ChildResult<OT> {
+    /// The harness ran
+    Ran {
+        /// How the run finished
+        exit_kind: ExitKind,
+        /// The observers of the child after the run
+        observers: OT,
+    },
+    /// The child could not run the input
+    Failed(String),
+}
+
+/// Deserializes the message from the shared memory, if one was written
+fn read_shmem<M, SHM>(shmem: &SHM) -> Result<Option<M>, Error>
+where
+    M: DeserializeOwned,
+    SHM: ShMem,
+{
+    let slice = shmem.as_slice();
+    let len = u32::from_le_bytes(slice[..LEN_HDR_SIZE].try_into().unwrap()) as usize;
+    if len == 0 {
+        return Ok(None);
+    }
+    Ok(Some(postcard::from_bytes(
+        &slice[LEN_HDR_SIZE..LEN_HDR_SIZE + len],
+    )?))
+}
+
+/// An [`Executor`] running each input in a relaunched child process, see the [module-level docs](self).
+pub struct InProcessRelaunchExecutor<OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    observers: OT,
+    timeout: Duration,
+    input_shmem: SP::ShMem,
+    result_shmem: SP::ShMem,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S, SP> Debug for InProcessRelaunchExecutor<OT, S, SP>
+where
+    OT: Debug,
+    SP: ShMemProvider,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InProcessRelaunchExecutor")
+            .field("observers", &self.observers)
+            .field("timeout", &self.timeout)
+            .field("input_shmem", &self.input_shmem)
+            .field("result_shmem", &self.result_shmem)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S, SP> InProcessRelaunchExecutor<OT, S, SP>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+    SP: ShMemProvider,
+{
+    /// Creates a new [`InProcessRelaunchExecutor`], passing inputs of up to `max_input_size`
+    /// serialized bytes to the children.
+    ///
+    /// The children run the harness passed to [`Self::run_if_child`].
+    pub fn new(
+        observers: OT,
+        timeout: Duration,
+        max_input_size: usize,
+        shmem_provider: SP,
+    ) -> Result<Self, Error> {
+        Self::with_result_size(
+            observers,
+            timeout,
+            max_input_size,
+            DEFAULT_RELAUNCH_RESULT_SIZE,
+            shmem_provider,
+        )
+    }
+
+    /// Creates a new [`InProcessRelaunchExecutor`], with `result_size` bytes of shared memory for the
+    /// serialized observers, instead of [`DEFAULT_RELAUNCH_RESULT_SIZE`].
+    pub fn with_result_size(
+        observers: OT,
+        timeout: Duration,
+        max_input_size: usize,
+        result_size: usize,
+        mut shmem_provider: SP,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            observers,
+            timeout,
+            input_shmem: shmem_provider.new_shmem(max_input_size + LEN_HDR_SIZE)?,
+            result_shmem: shmem_provider.new_shmem(result_size + LEN_HDR_SIZE)?,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<OT, I, SP> InProcessRelaunchExecutor<OT, NopState<I>, SP>
+where
+    I: Input,
+    OT: ObserversTuple<I, NopState<I>> + Serialize,
+    SP: ShMemProvider,
+{
+    /// In a child relaunched by an [`InProcessRelaunchExecutor`], runs `harness_fn` on the input of the
+    /// fuzzer, sends the [`ExitKind`] and the `observers` back, and exits.
+    /// Does nothing in any other process.
+    ///
+    /// Call this first thing in `main`, see the [module-level docs](self).
+    /// The `observers` get `pre_exec` with a [`NopState`], as the child has no state of its own.
+    pub fn run_if_child<H>(harness_fn: H, observers: &mut OT, shmem_provider: &mut SP)
+    where
+        H: FnMut(&I) -> ExitKind,
+    {
+        if env::var(INPUT_SHMEM_ENV).is_err() {
+            return;
+        }
+
+        let mut result_shmem = match shmem_provider.existing_from_env(RESULT_SHMEM_ENV) {
+            Ok(result_shmem) => result_shmem,
+            Err(err) => {
+                log::error!("Relaunched child failed to map the result: {err}");
+                process::exit(CHILD_FAILED_EXIT_CODE);
+            }
+        };
+        if let Err(err) = Self::run_child(harness_fn, observers, shmem_provider, &mut result_shmem)
+        {
+            log::error!("Relaunched child failed to run the input: {err}");
+            drop(write_shmem(
+                &mut result_shmem,
+                &ChildResult::<()>::Failed(format!("{err}")),
+            ));
+            process::exit(CHILD_FAILED_EXIT_CODE);
+        }
+        process::exit(0);
+    }
+
+    /// Runs the harness on the input of the fuzzer, and writes the result back
+    fn run_child<H>(
+        mut harness_fn: H,
+        observers: &mut OT,
+        shmem_provider: &mut SP,
+        result_shmem: &mut SP::ShMem,
+    ) -> Result<(), Error>
+    where
+        H: FnMut(&I) -> ExitKind,
+    {
+        let input_shmem = shmem_provider.existing_from_env(INPUT_SHMEM_ENV)?;
+        let input: I = read_shmem(&input_shmem)?
+            .ok_or_else(|| Error::illegal_state("The relaunched child did not get an input"))?;
+        observers.pre_exec_all(&mut NopState::new(), &input)?;
+        let exit_kind = harness_fn(&input);
+        write_shmem(
+            result_shmem,
+            &ChildResult::Ran {
+                exit_kind,
+                observers: &*observers,
+            },
+        )
+    }
+}
+
+impl<OT, S, SP> UsesState for InProcessRelaunchExecutor<OT, S, SP>
+where
+    S: State,
+    SP: ShMemProvider,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, SP, Z> Executor<EM, Z> for InProcessRelaunchExecutor<OT, S, SP>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: Serialize,
+    OT: ObserversTuple<S::Input, S> + RemoteObserversTuple + DeserializeOwned,
+    SP: ShMemProvider,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        write_shmem(&mut self.input_shmem, input)?;
+        self.result_shmem.as_slice_mut()[..LEN_HDR_SIZE].fill(0);
+
+        let mut command = startable_self()?;
+        // The child must not attach to the event manager of the fuzzer, see `run_if_child`
+        for (key, _) in env::vars_os() {
+            if key.to_string_lossy().starts_with(EVENT_MGR_ENV_PREFIX) {
+                command.env_remove(key);
+            }
+        }
+        let mut child = command
+            .env(INPUT_SHMEM_ENV, self.input_shmem.id().to_string())
+            .env(
+                format!("{INPUT_SHMEM_ENV}_SIZE"),
+                self.input_shmem.len().to_string(),
+            )
+            .env(RESULT_SHMEM_ENV, self.result_shmem.id().to_string())
+            .env(
+                format!("{RESULT_SHMEM_ENV}_SIZE"),
+                self.result_shmem.len().to_string(),
+            )
+            .spawn()?;
+
+        let Some(status) = child.wait_timeout(self.timeout)? else {
+            drop(child.kill());
+            drop(child.wait());
+            return Ok(ExitKind::Timeout);
+        };
+        if status.code() == Some(CHILD_FAILED_EXIT_CODE) {
+            let reason = match read_shmem::<ChildResult<OT>, _>(&self.result_shmem) {
+                Ok(Some(ChildResult::Failed(reason))) => reason,
+                _ => String::from("see the log of the child"),
+            };
+            return Err(Error::illegal_state(format!(
+                "The relaunched child failed to run the input: {reason}"
+            )));
+        }
+        if !status.success() {
+            // On Windows, the exit code is the code of the unhandled exception
+            log::debug!("Relaunched child failed with {status}");
+            return Ok(ExitKind::Crash);
+        }
+
+        match read_shmem(&self.result_shmem)? {
+            Some(ChildResult::Ran {
+                exit_kind,
+                observers,
+            }) => {
+                self.observers.merge_remote_all(&observers)?;
+                Ok(exit_kind)
+            }
+            Some(ChildResult::Failed(reason)) => Err(Error::illegal_state(format!(
+                "The relaunched child failed to run the input: {reason}"
+            ))),
+            None => Err(Error::illegal_state(
+                "The relaunched child did not report a result",
+            )),
+        }
+    }
+}
+
+impl<OT, S, SP> HasTimeout for InProcessRelaunchExecutor<OT, S, SP>
+where
+    SP: ShMemProvider,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S, SP> HasObservers for InProcessRelaunchExecutor<OT, S, SP>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+    SP: ShMemProvider,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{
+        env,
+        panic::{self, AssertUnwindSafe},
+        process::{self, Command},
+    };
+
+    use libafl_bolts::{
+        rands::StdRand,
+        shmem::{ShMemProvider, StdShMemProvider},
+        tuples::tuple_list,
+    };
+
+    use super::{read_shmem, write_shmem, InProcessRelaunchExecutor};
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::{EventRestarter, SimpleRestartingEventManager},
+        executors::{Executor, ExitKind},
+        feedbacks::ConstFeedback,
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, HasMutatorBytes},
+        monitors::NopMonitor,
+        observers::StdMapObserver,
+        state::StdState,
+        Error,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    /// Set for the process running the restarting manager of [`test_relaunch_restarting_mgr`]
+    const RESTARTING_TEST_ENV: &str = "_LIBAFL_RELAUNCH_TEST";
+
+    static mut RELAUNCH_MAP: [u8; 4] = [0; 4];
+
+    fn relaunch_observers() -> (StdMapObserver<'static, u8, false>, ()) {
+        tuple_list!(unsafe {
+            StdMapObserver::from_mut_ptr("map", (&raw mut RELAUNCH_MAP).cast::<u8>(), 4)
+        })
+    }
+
+    fn relaunch_harness(input: &BytesInput) -> ExitKind {
+        // The child must not attach to the event manager of the fuzzer
+        if env::var_os("_AFL_ENV_FUZZER_SENDER").is_some() {
+            return ExitKind::Crash;
+        }
+        match input.bytes() {
+            b"crash" => process::abort(),
+            bytes => {
+                unsafe { (*(&raw mut RELAUNCH_MAP))[usize::from(bytes[0]) % 4] = 1 };
+                ExitKind::Ok
+            }
+        }
+    }
+
+    #[test]
+    fn test_relaunch_shmem_roundtrip() {
+        let mut provider = StdShMemProvider::new().unwrap();
+        let mut shmem = provider.new_shmem(64).unwrap();
+        assert!(read_shmem::<BytesInput, _>(&shmem).unwrap().is_none());
+
+        let input = BytesInput::new(vec![1, 2, 3]);
+        write_shmem(&mut shmem, &(ExitKind::Crash, &input)).unwrap();
+        let (exit_kind, read): (ExitKind, BytesInput) = read_shmem(&shmem).unwrap().unwrap();
+        assert_eq!(exit_kind, ExitKind::Crash);
+        assert_eq!(read, input);
+
+        write_shmem(&mut shmem, &BytesInput::new(vec![0; 64])).unwrap_err();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_relaunch_restarting_mgr() {
+        let mut shmem_provider = StdShMemProvider::new().unwrap();
+        let mut observers = relaunch_observers();
+        InProcessRelaunchExecutor::run_if_child(
+            relaunch_harness,
+            &mut observers,
+            &mut shmem_provider,
+        );
+
+        if env::var(RESTARTING_TEST_ENV).is_err() {
+            // The restarting manager takes over the process, so give it a process of its own
+            let status = Command::new(env::current_exe().unwrap())
+                .env(RESTARTING_TEST_ENV, "1")
+                .args([
+                    "executors::inprocess_relaunch::tests::test_relaunch_restarting_mgr",
+                    "--exact",
+                    "--test-threads=1",
+                ])
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let (_, mut mgr) = match SimpleRestartingEventManager::<_, TestState, _>::launch(
+            NopMonitor::new(),
+            &mut shmem_provider,
+        ) {
+            Ok(launched) => launched,
+            // The respawner, after the fuzzer exited cleanly
+            Err(Error::ShuttingDown) => return,
+            Err(err) => panic!("{err}"),
+        };
+
+        // The fuzzer: report failures through the exit code, so the respawner notices them
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut state = TestState::new(
+                StdRand::with_seed(0),
+                InMemoryCorpus::new(),
+                InMemoryCorpus::new(),
+                &mut ConstFeedback::new(false),
+                &mut ConstFeedback::new(false),
+            )
+            .unwrap();
+            let mut fuzzer = NopFuzzer::new();
+            let timeout = Duration::from_secs(10);
+
+            let mut executor = InProcessRelaunchExecutor::new(
+                observers,
+                timeout,
+                64,
+                StdShMemProvider::new().unwrap(),
+            )
+            .unwrap();
+            let input = BytesInput::new(vec![2]);
+            let exit_kind = executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+            // The coverage of the child got merged into the map of the fuzzer
+            assert_eq!(unsafe { *(&raw const RELAUNCH_MAP) }, [0, 0, 1, 0]);
+
+            let input = BytesInput::new(b"crash".to_vec());
+            let exit_kind = executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Crash);
+
+            // A child that can not send its observers back is an error, not a crash
+            let mut executor = InProcessRelaunchExecutor::with_result_size(
+                relaunch_observers(),
+                timeout,
+                64,
+                1,
+                StdShMemProvider::new().unwrap(),
+            )
+            .unwrap();
+            let input = BytesInput::new(vec![1]);
+            assert!(executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .is_err());
+        }));
+
+        if res.is_ok() {
+            mgr.send_exiting().unwrap();
+            process::exit(0);
+        }
+        process::exit(1);
+    }
+}
@@ -4,27 +4,57 @@
 use alloc::vec::Vec;
 use core::{fmt::Debug, time::Duration};
 
+#[cfg(feature = "std")]
+pub use adb::{AdbExecutor, AdbTarget};
+pub use batched::{BatchedExecutor, HasBatchExecution};
 pub use combined::CombinedExecutor;
 #[cfg(all(feature = "std", unix))]
 pub use command::CommandExecutor;
-pub use differential::DiffExecutor;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use criu::CriuExecutor;
+#[cfg(all(feature = "std", unix))]
+pub use debugger::{DebuggerExecutor, DebuggerKind};
+pub use differential::{DiffExecutor, ProxyObserversTuple};
 #[cfg(all(feature = "std", feature = "fork", unix))]
-pub use forkserver::{Forkserver, ForkserverExecutor};
+pub use forkserver::{Forkserver, ForkserverCapabilities, ForkserverExecutor};
 pub use inprocess::InProcessExecutor;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use inprocess_fork::InProcessForkExecutor;
+#[cfg(feature = "std")]
+pub use inprocess_relaunch::InProcessRelaunchExecutor;
 #[cfg(unix)]
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
+#[cfg(all(feature = "std", unix))]
+pub use network::{Handshake, HandshakeStep, HasNetworkMessages, NetworkEndpoint, NetworkExecutor};
+#[cfg(feature = "std")]
+pub use periodic_restart::PeriodicRestartExecutor;
+#[cfg(all(feature = "std", target_os = "linux", target_arch = "x86_64"))]
+pub use ptrace_syscalls::{PtraceSyscallExecutor, SyscallAction, SyscallPolicy};
+#[cfg(all(feature = "std", unix))]
+pub use remote::{serve_remote, RemoteExecutor};
+#[cfg(feature = "std")]
+pub use retry::{is_transient_error, RetryExecutor, RetryPolicy};
+#[cfg(all(feature = "std", unix))]
+pub use sandbox::ChildSandbox;
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
+#[cfg(feature = "wasmtime")]
+pub use wasm::WasmExecutor;
 pub use with_observers::WithObservers;
 
 use crate::{state::UsesState, Error};
 
+#[cfg(feature = "std")]
+pub mod adb;
+pub mod batched;
 pub mod combined;
 #[cfg(all(feature = "std", unix))]
 pub mod command;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod criu;
+#[cfg(all(feature = "std", unix))]
+pub mod debugger;
 pub mod differential;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub mod forkserver;
@@ -34,8 +64,26 @@ pub mod inprocess;
 #[cfg(all(feature = "std", unix))]
 pub mod inprocess_fork;
 
+#[cfg(feature = "std")]
+pub mod inprocess_relaunch;
+
+#[cfg(all(feature = "std", unix))]
+pub mod network;
+#[cfg(feature = "std")]
+pub mod periodic_restart;
+#[cfg(all(feature = "std", target_os = "linux", target_arch = "x86_64"))]
+pub mod ptrace_syscalls;
+#[cfg(all(feature = "std", unix))]
+pub mod remote;
+#[cfg(feature = "std")]
+pub mod retry;
+#[cfg(all(feature = "std", unix))]
+pub mod sandbox;
+
 pub mod shadow;
 
+#[cfg(feature = "wasmtime")]
+pub mod wasm;
 pub mod with_observers;
 
 /// The module for all the hooks
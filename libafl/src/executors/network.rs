@@ -0,0 +1,656 @@
+//! The [`NetworkExecutor`] fuzzes servers over TCP, UDP, or Unix sockets.
+//!
+//! The executor launches the server and keeps it running across executions, restarting it whenever it exits.
+//! For every input, it connects, performs the scripted [`Handshake`], and sends the messages of the input,
+//! see [`HasNetworkMessages`]. Each message is a write on stream sockets, and a datagram on UDP.
+//!
+//! A server killed by a signal, or exiting with a non-zero code, is a crash. A server that accepts no
+//! connection or does not finish the handshake within the timeout, is a timeout.
+//! Coverage comes from observers in shared memory, such as a [`crate::observers::StdMapObserver`] on a
+//! [`libafl_bolts::shmem::ShMem`], whose id is passed to the server with [`NetworkExecutor::env`].
+//!
+//! Handshakes are declared as a list of [`HandshakeStep`]s, in code or as JSON:
+//! ```json
+//! [{ "send": "HELO fuzz\r\n" }, { "expect": "250" }, { "sleep_ms": 10 }]
+//! ```
+
+use alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    time::Duration,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, TcpStream, UdpSocket},
+    os::unix::{net::UnixStream, process::ExitStatusExt},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{ownedref::OwnedSlice, tuples::RefIndexable, AsSlice};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "multipart_inputs")]
+use crate::inputs::MultipartInput;
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// Where the server of a [`NetworkExecutor`] listens
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkEndpoint {
+    /// A TCP address, such as `127.0.0.1:8080`
+    Tcp(String),
+    /// A UDP address, such as `127.0.0.1:5353`
+    Udp(String),
+    /// The path of a Unix stream socket
+    Unix(PathBuf),
+}
+
+/// A step of a [`Handshake`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandshakeStep {
+    /// Sends the text
+    Send(String),
+    /// Sends the bytes
+    SendBytes(Vec<u8>),
+    /// Waits until the data received since the last `expect` contains the text
+    Expect(String),
+    /// Waits until the data received since the last `expect` contains the bytes
+    ExpectBytes(Vec<u8>),
+    /// Sleeps for the given milliseconds
+    SleepMs(u64),
+}
+
+/// The scripted exchange with the server, before the input is sent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Handshake {
+    steps: Vec<HandshakeStep>,
+}
+
+impl Handshake {
+    /// Creates a new [`Handshake`] from its steps
+    #[must_use]
+    pub fn new(steps: Vec<HandshakeStep>) -> Self {
+        Self { steps }
+    }
+
+    /// Parses a [`Handshake`] from a JSON list of steps, see the [module-level docs](self)
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json)
+            .map_err(|err| Error::illegal_argument(format!("Invalid handshake: {err}")))
+    }
+
+    /// The steps of this [`Handshake`]
+    #[must_use]
+    pub fn steps(&self) -> &[HandshakeStep] {
+        &self.steps
+    }
+}
+
+/// Inputs that can be sent to a server as a sequence of messages
+pub trait HasNetworkMessages {
+    /// The messages to send, in order
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>>;
+}
+
+impl<I> HasNetworkMessages for I
+where
+    I: HasTargetBytes,
+{
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        vec![self.target_bytes()]
+    }
+}
+
+#[cfg(feature = "multipart_inputs")]
+impl<I> HasNetworkMessages for MultipartInput<I>
+where
+    I: HasTargetBytes,
+{
+    /// Every part is a message
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        self.parts()
+            .iter()
+            .map(HasTargetBytes::target_bytes)
+            .collect()
+    }
+}
+
+/// A connection to the server
+#[derive(Debug)]
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn connect(endpoint: &NetworkEndpoint) -> std::io::Result<Self> {
+        Ok(match endpoint {
+            NetworkEndpoint::Tcp(addr) => {
+                let stream = TcpStream::connect(addr)?;
+                stream.set_nodelay(true)?;
+                Self::Tcp(stream)
+            }
+            NetworkEndpoint::Udp(addr) => {
+                let socket = UdpSocket::bind(if addr.starts_with('[') {
+                    "[::]:0"
+                } else {
+                    "0.0.0.0:0"
+                })?;
+                socket.connect(addr)?;
+                Self::Udp(socket)
+            }
+            NetworkEndpoint::Unix(path) => Self::Unix(UnixStream::connect(path)?),
+        })
+    }
+
+    fn send(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.write_all(buf),
+            Self::Udp(socket) => socket.send(buf).map(drop),
+            Self::Unix(stream) => stream.write_all(buf),
+        }
+    }
+
+    /// Receives data until the timeout. Returns `Ok(0)` when the server closed the connection.
+    fn recv(&mut self, buf: &mut [u8], timeout: Duration) -> std::io::Result<usize> {
+        // A zero timeout means blocking forever
+        let timeout = Some(timeout.max(Duration::from_millis(1)));
+        match self {
+            Self::Tcp(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.read(buf)
+            }
+            Self::Udp(socket) => {
+                socket.set_read_timeout(timeout)?;
+                socket.recv(buf)
+            }
+            Self::Unix(stream) => {
+                stream.set_read_timeout(timeout)?;
+                stream.read(buf)
+            }
+        }
+    }
+
+    /// Tells the server that no more data follows
+    fn shutdown_write(&self) {
+        // The server may have closed the connection already
+        drop(match self {
+            Self::Tcp(stream) => stream.shutdown(Shutdown::Write),
+            Self::Udp(_) => Ok(()),
+            Self::Unix(stream) => stream.shutdown(Shutdown::Write),
+        });
+    }
+}
+
+/// Whether the error means the read timed out
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// An [`Executor`] sending inputs to a server over the network, see the [module-level docs](self).
+pub struct NetworkExecutor<OT, S> {
+    program: PathBuf,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    endpoint: NetworkEndpoint,
+    handshake: Handshake,
+    server: Option<Child>,
+    timeout: Duration,
+    startup_timeout: Duration,
+    response_timeout: Duration,
+    restart_each_run: bool,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for NetworkExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkExecutor")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("endpoint", &self.endpoint)
+            .field("handshake", &self.handshake)
+            .field("server", &self.server)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> NetworkExecutor<OT, S> {
+    /// Creates a new [`NetworkExecutor`] for the server started with `program` and `args`,
+    /// listening on `endpoint`.
+    ///
+    /// By default, there is no handshake, a run times out after 5 seconds, and the server gets 10 seconds to start.
+    pub fn new<P, A>(program: P, args: A, endpoint: NetworkEndpoint, observers: OT) -> Self
+    where
+        P: Into<PathBuf>,
+        A: IntoIterator,
+        A::Item: AsRef<OsStr>,
+    {
+        Self {
+            program: program.into(),
+            args: args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_owned())
+                .collect(),
+            envs: Vec::new(),
+            endpoint,
+            handshake: Handshake::default(),
+            server: None,
+            timeout: Duration::from_secs(5),
+            startup_timeout: Duration::from_secs(10),
+            response_timeout: Duration::from_millis(20),
+            restart_each_run: false,
+            observers,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets an environment variable of the server, e.g., the id of the coverage map
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// Sets the [`Handshake`] performed on every connection, before the input is sent
+    #[must_use]
+    pub fn handshake(mut self, handshake: Handshake) -> Self {
+        self.handshake = handshake;
+        self
+    }
+
+    /// Sets how long the server may take to accept connections after a (re)start.
+    ///
+    /// UDP servers can not be probed, so the executor waits a tenth of this time before sending to them.
+    #[must_use]
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// Sets how long the executor reads the responses to every message; default is 20 milliseconds.
+    ///
+    /// This paces the messages, and gives the server time to crash before the next execution.
+    #[must_use]
+    pub fn response_timeout(mut self, response_timeout: Duration) -> Self {
+        self.response_timeout = response_timeout;
+        self
+    }
+
+    /// Restarts the server for every execution, so no state leaks between runs. Slow.
+    #[must_use]
+    pub fn restart_each_run(mut self, restart_each_run: bool) -> Self {
+        self.restart_each_run = restart_each_run;
+        self
+    }
+
+    /// The [`NetworkEndpoint`] of the server
+    #[must_use]
+    pub fn endpoint(&self) -> &NetworkEndpoint {
+        &self.endpoint
+    }
+
+    /// Starts the server, if it is not running, and waits until it accepts connections
+    fn ensure_server(&mut self) -> Result<(), Error> {
+        if let Some(server) = &mut self.server {
+            if server.try_wait()?.is_none() {
+                return Ok(());
+            }
+        }
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        self.server = Some(command.spawn()?);
+
+        if matches!(self.endpoint, NetworkEndpoint::Udp(_)) {
+            thread::sleep(self.startup_timeout / 10);
+            return Ok(());
+        }
+        let deadline = Instant::now() + self.startup_timeout;
+        loop {
+            if Connection::connect(&self.endpoint).is_ok() {
+                return Ok(());
+            }
+            if let Some(status) = self.server.as_mut().unwrap().try_wait()? {
+                return Err(Error::illegal_state(format!(
+                    "The server exited during startup: {status}"
+                )));
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::illegal_state(format!(
+                    "The server did not accept connections on {:?} within {:?}",
+                    self.endpoint, self.startup_timeout
+                )));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Stops the server
+    fn kill_server(&mut self) {
+        if let Some(mut server) = self.server.take() {
+            drop(server.kill());
+            drop(server.wait());
+        }
+    }
+
+    /// The exit status of the server, if it exited
+    fn server_status(&mut self) -> Result<Option<ExitStatus>, Error> {
+        match &mut self.server {
+            Some(server) => Ok(server.try_wait()?),
+            None => Ok(None),
+        }
+    }
+
+    /// Performs the handshake. Returns `false` if it did not finish in time.
+    fn perform_handshake(
+        &self,
+        connection: &mut Connection,
+        deadline: Instant,
+    ) -> Result<bool, Error> {
+        let mut received = Vec::new();
+        let mut buf = vec![0; 4096];
+        for step in &self.handshake.steps {
+            let expected = match step {
+                HandshakeStep::Send(text) => {
+                    connection.send(text.as_bytes())?;
+                    continue;
+                }
+                HandshakeStep::SendBytes(bytes) => {
+                    connection.send(bytes)?;
+                    continue;
+                }
+                HandshakeStep::SleepMs(millis) => {
+                    thread::sleep(Duration::from_millis(*millis));
+                    continue;
+                }
+                HandshakeStep::Expect(text) => text.as_bytes(),
+                HandshakeStep::ExpectBytes(bytes) => bytes.as_slice(),
+            };
+            while !expected.is_empty() && !received.windows(expected.len()).any(|w| w == expected) {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(false);
+                }
+                match connection.recv(&mut buf, deadline - now) {
+                    Ok(0) => {
+                        return Err(Error::illegal_state(format!(
+                            "The server closed the connection during the handshake, expecting {expected:?}"
+                        )))
+                    }
+                    Ok(len) => received.extend_from_slice(&buf[..len]),
+                    Err(err) if is_timeout(&err) => return Ok(false),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            received.clear();
+        }
+        Ok(true)
+    }
+
+    /// Sends the messages, reading the responses in between
+    fn send_messages(&self, connection: &mut Connection, messages: &[OwnedSlice<'_, u8>]) {
+        let mut buf = vec![0; 4096];
+        for message in messages {
+            if connection.send(message.as_slice()).is_err() {
+                // The server closed the connection, maybe it crashed
+                return;
+            }
+            loop {
+                match connection.recv(&mut buf, self.response_timeout) {
+                    Ok(0) => return,
+                    Ok(_) => {}
+                    Err(err) if is_timeout(&err) => break,
+                    Err(_) => return,
+                }
+            }
+        }
+        connection.shutdown_write();
+    }
+
+    /// Runs one connection with the given messages
+    fn run_once(&mut self, messages: &[OwnedSlice<'_, u8>]) -> Result<ExitKind, Error> {
+        self.ensure_server()?;
+        let deadline = Instant::now() + self.timeout;
+
+        let mut connection = loop {
+            match Connection::connect(&self.endpoint) {
+                Ok(connection) => break Some(connection),
+                Err(_) if Instant::now() < deadline && self.server_status()?.is_none() => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(_) => break None,
+            }
+        };
+        let connected = match &mut connection {
+            Some(connection) => {
+                let done = self.perform_handshake(connection, deadline);
+                if done.is_err() && self.server_status()?.is_some() {
+                    // The server died during the handshake, likely because of the previous input
+                    false
+                } else if done? {
+                    self.send_messages(connection, messages);
+                    true
+                } else {
+                    false
+                }
+            }
+            None => false,
+        };
+        drop(connection);
+
+        // Give the server the time to crash on the input
+        let mut status = self.server_status()?;
+        let wait_until = Instant::now() + self.response_timeout;
+        while status.is_none() && Instant::now() < wait_until {
+            thread::sleep(Duration::from_millis(1));
+            status = self.server_status()?;
+        }
+
+        let exit_kind = match status {
+            Some(status) if status.signal().is_some() || status.code() != Some(0) => {
+                ExitKind::Crash
+            }
+            None if !connected => {
+                // The server hangs, restart it
+                self.kill_server();
+                ExitKind::Timeout
+            }
+            // A server exiting cleanly is restarted on the next run
+            _ => ExitKind::Ok,
+        };
+        if self.restart_each_run {
+            self.kill_server();
+        }
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> Drop for NetworkExecutor<OT, S> {
+    fn drop(&mut self) {
+        self.kill_server();
+    }
+}
+
+impl<OT, S> UsesState for NetworkExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for NetworkExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasNetworkMessages,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        self.observers.pre_exec_child_all(state, input)?;
+        let exit_kind = self.run_once(&input.network_messages())?;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for NetworkExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S> HasObservers for NetworkExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{Handshake, HandshakeStep, NetworkEndpoint, NetworkExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    #[test]
+    fn test_handshake_json() {
+        let handshake =
+            Handshake::from_json(r#"[{"send": "HELO\r\n"}, {"expect": "250"}, {"sleep_ms": 1}]"#)
+                .unwrap();
+        assert_eq!(
+            handshake.steps(),
+            &[
+                HandshakeStep::Send("HELO\r\n".to_string()),
+                HandshakeStep::Expect("250".to_string()),
+                HandshakeStep::SleepMs(1)
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor() {
+        // The listener stands in for the server, the process only decides how the server exits
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (sender, received) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut data = Vec::new();
+                let mut buf = [0; 5];
+                if stream.read_exact(&mut buf).is_err() {
+                    // The probe of the executor
+                    continue;
+                }
+                assert_eq!(&buf, b"HELLO");
+                stream.write_all(b"WELCOME\n").unwrap();
+                stream.read_to_end(&mut data).unwrap();
+                sender.send(data).unwrap();
+            }
+        });
+
+        let handshake = Handshake::new(vec![
+            HandshakeStep::Send("HELLO".to_string()),
+            HandshakeStep::Expect("WELCOME".to_string()),
+        ]);
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(b"fuzz".to_vec());
+
+        let mut executor = NetworkExecutor::new(
+            "sleep",
+            ["10"],
+            NetworkEndpoint::Tcp(addr.clone()),
+            tuple_list!(),
+        )
+        .handshake(handshake.clone());
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(received.recv().unwrap(), b"fuzz");
+
+        let mut executor = NetworkExecutor::new(
+            "sh",
+            ["-c", "kill -SEGV $$"],
+            NetworkEndpoint::Tcp(addr),
+            tuple_list!(),
+        )
+        .handshake(handshake);
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Crash);
+    }
+}
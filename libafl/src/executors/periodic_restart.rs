@@ -0,0 +1,121 @@
+//! The [`PeriodicRestartExecutor`] restarts the fuzzer process every few executions.
+//!
+//! In-process and persistent harnesses accumulate state and leak memory over millions of executions, until
+//! the target behaves differently, or runs out of memory. Wrapping their executor in a
+//! [`PeriodicRestartExecutor`] bounds this: every `N` executions, it saves the fuzzer state with the
+//! restarting event manager, and exits, so the manager respawns the fuzzer in a fresh process.
+//!
+//! The restart is marked with the [`PlannedRestartMetadata`], so the resumed stages do not count it as a crash
+//! of the current testcase. Only use this executor with a restarting event manager, such as the
+//! [`crate::events::LlmpRestartingEventManager`]. Other event managers do not respawn the fuzzer.
+
+use core::time::Duration;
+use std::process;
+
+use libafl_bolts::tuples::RefIndexable;
+
+use crate::{
+    events::EventRestarter,
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    stages::PlannedRestartMetadata,
+    state::{HasExecutions, UsesState},
+    Error, HasNamedMetadata,
+};
+
+/// An [`Executor`] wrapper restarting the fuzzer process every few executions, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct PeriodicRestartExecutor<E> {
+    executor: E,
+    restart_interval: u64,
+    executions: u64,
+}
+
+impl<E> PeriodicRestartExecutor<E> {
+    /// Wraps the `executor`, restarting the fuzzer every `restart_interval` executions
+    pub fn new(executor: E, restart_interval: u64) -> Self {
+        Self {
+            executor,
+            restart_interval: restart_interval.max(1),
+            executions: 0,
+        }
+    }
+
+    /// The wrapped executor
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    /// The executions since the fuzzer (re)started
+    #[must_use]
+    pub fn executions_since_restart(&self) -> u64 {
+        self.executions
+    }
+}
+
+impl<E, EM, Z> Executor<EM, Z> for PeriodicRestartExecutor<E>
+where
+    E: Executor<EM, Z>,
+    E::State: HasExecutions + HasNamedMetadata,
+    EM: EventRestarter<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        if self.executions == 0 {
+            // The stages resumed after the restart, now crashes count again
+            state.remove_named_metadata::<PlannedRestartMetadata>(PlannedRestartMetadata::NAME);
+        } else if self.executions >= self.restart_interval {
+            log::info!("Restarting the fuzzer after {} executions", self.executions);
+            state.add_named_metadata(PlannedRestartMetadata::NAME, PlannedRestartMetadata);
+            mgr.on_restart(state)?;
+            process::exit(0);
+        }
+        self.executions += 1;
+
+        self.executor.run_target(fuzzer, state, mgr, input)
+    }
+}
+
+impl<E> UsesState for PeriodicRestartExecutor<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E> HasObservers for PeriodicRestartExecutor<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+impl<E> HasTimeout for PeriodicRestartExecutor<E>
+where
+    E: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
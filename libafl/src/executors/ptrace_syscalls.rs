@@ -0,0 +1,504 @@
+//! The [`PtraceSyscallExecutor`] runs the target under `ptrace`, intercepting its system calls.
+//!
+//! Each system call is recorded into a [`SyscallObserver`], and handled according to a [`SyscallPolicy`]:
+//! it is either allowed, skipped with a faked result, e.g., to make time and randomness deterministic
+//! or to cut the target off the network, or it is a policy violation. A violation kills the target,
+//! is recorded in the [`SyscallObserver`], and turned into an objective by the
+//! [`crate::feedbacks::SyscallViolationFeedback`].
+//!
+//! Only the main thread of the target is traced, children and other threads run untraced.
+//! Calls served by the vDSO, such as `clock_gettime` in most libcs, do not enter the kernel and cannot
+//! be intercepted.
+
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+use std::{
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{
+    fs::{get_unique_std_input_file, InputFile},
+    tuples::{Handle, MatchNameRef, RefIndexable},
+    AsSlice,
+};
+use nix::{
+    libc::{c_long, user_regs_struct},
+    sys::{
+        ptrace,
+        signal::{kill, Signal},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::{command::InputLocation, Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::HasTargetBytes,
+    observers::{ObserversTuple, SyscallObserver, SyscallRecord},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The maximum amount of bytes faked per system call, e.g., for `getrandom`
+const MAX_FAKE_LEN: u64 = 1 << 20;
+
+/// How the [`PtraceSyscallExecutor`] handles a system call
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyscallAction {
+    /// Run the system call
+    #[default]
+    Allow,
+    /// Skip the system call, and return the given value, e.g., `-libc::EACCES` to fail it
+    Return(i64),
+    /// Skip the system call, and return the given time in seconds since the epoch.
+    /// Only for `time`, `gettimeofday`, and `clock_gettime`.
+    FakeTime(u64),
+    /// Skip the system call, and fill the buffer with the given byte. Only for `getrandom`.
+    FakeRandom(u8),
+    /// The system call violates the policy: kill the target, and record the violation
+    Violation,
+}
+
+/// Maps system call numbers to the [`SyscallAction`] of the [`PtraceSyscallExecutor`]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyscallPolicy {
+    actions: HashMap<u64, SyscallAction>,
+    default_action: SyscallAction,
+}
+
+/// The system call numbers of `syscalls`, for the [`SyscallPolicy`]
+macro_rules! syscall_nrs {
+    ($($syscall:ident),*) => {
+        [$(libc::$syscall as u64),*]
+    };
+}
+
+impl SyscallPolicy {
+    /// Creates a new [`SyscallPolicy`], allowing all system calls
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the action for all system calls without an action of their own, e.g.,
+    /// [`SyscallAction::Violation`] for an allowlist policy
+    #[must_use]
+    pub fn default_action(mut self, action: SyscallAction) -> Self {
+        self.default_action = action;
+        self
+    }
+
+    /// Sets the action for the system call with the number `nr`, such as [`libc::SYS_open`]
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn on(mut self, nr: c_long, action: SyscallAction) -> Self {
+        self.actions.insert(nr as u64, action);
+        self
+    }
+
+    /// Fakes `time`, `gettimeofday`, and `clock_gettime` to always return `secs` since the epoch
+    #[must_use]
+    pub fn fake_time(mut self, secs: u64) -> Self {
+        for nr in syscall_nrs!(SYS_time, SYS_gettimeofday, SYS_clock_gettime) {
+            self.actions.insert(nr, SyscallAction::FakeTime(secs));
+        }
+        self
+    }
+
+    /// Fakes `getrandom` to always fill the buffer with `byte`
+    #[must_use]
+    pub fn fake_random(mut self, byte: u8) -> Self {
+        self.actions
+            .insert(libc::SYS_getrandom as u64, SyscallAction::FakeRandom(byte));
+        self
+    }
+
+    /// Cuts the target off the network, failing the creation of sockets with `EACCES`,
+    /// and connections on inherited sockets with `ENETUNREACH`
+    #[must_use]
+    pub fn deny_network(mut self) -> Self {
+        self.actions.insert(
+            libc::SYS_socket as u64,
+            SyscallAction::Return(-i64::from(libc::EACCES)),
+        );
+        for nr in syscall_nrs!(SYS_connect, SYS_sendto, SYS_sendmsg) {
+            self.actions
+                .insert(nr, SyscallAction::Return(-i64::from(libc::ENETUNREACH)));
+        }
+        self
+    }
+
+    /// The action for the system call with the number `nr`
+    #[must_use]
+    pub fn action(&self, nr: u64) -> SyscallAction {
+        self.actions
+            .get(&nr)
+            .copied()
+            .unwrap_or(self.default_action)
+    }
+}
+
+/// Writes `bytes` into the memory of the stopped tracee at `addr`
+#[allow(clippy::cast_possible_wrap)]
+fn poke_bytes(pid: Pid, addr: u64, bytes: &[u8]) -> Result<(), Error> {
+    for (idx, chunk) in bytes.chunks(8).enumerate() {
+        let word_addr = (addr + idx as u64 * 8) as ptrace::AddressType;
+        let mut word = if chunk.len() == 8 {
+            [0; 8]
+        } else {
+            // Keep the bytes behind the buffer
+            ptrace::read(pid, word_addr)?.to_ne_bytes()
+        };
+        word[..chunk.len()].copy_from_slice(chunk);
+        ptrace::write(pid, word_addr, c_long::from_ne_bytes(word))?;
+    }
+    Ok(())
+}
+
+/// Performs the faked system call for the stopped tracee, and returns its result
+#[allow(clippy::cast_possible_wrap)]
+fn fake_syscall(pid: Pid, regs: &user_regs_struct, action: SyscallAction) -> Result<i64, Error> {
+    let nr = regs.orig_rax;
+    let enosys = -i64::from(libc::ENOSYS);
+    match action {
+        SyscallAction::Return(ret) => Ok(ret),
+        SyscallAction::FakeTime(secs) => {
+            let time = [secs.to_ne_bytes(), 0u64.to_ne_bytes()].concat();
+            if nr == libc::SYS_time as u64 {
+                if regs.rdi != 0 {
+                    poke_bytes(pid, regs.rdi, &time[..8])?;
+                }
+                Ok(secs as i64)
+            } else if nr == libc::SYS_gettimeofday as u64 {
+                if regs.rdi != 0 {
+                    poke_bytes(pid, regs.rdi, &time)?;
+                }
+                Ok(0)
+            } else if nr == libc::SYS_clock_gettime as u64 {
+                poke_bytes(pid, regs.rsi, &time)?;
+                Ok(0)
+            } else {
+                Ok(enosys)
+            }
+        }
+        SyscallAction::FakeRandom(byte) => {
+            if nr == libc::SYS_getrandom as u64 {
+                let len = regs.rsi.min(MAX_FAKE_LEN);
+                #[allow(clippy::cast_possible_truncation)] // at most MAX_FAKE_LEN
+                poke_bytes(pid, regs.rdi, &vec![byte; len as usize])?;
+                Ok(len as i64)
+            } else {
+                Ok(enosys)
+            }
+        }
+        SyscallAction::Allow | SyscallAction::Violation => {
+            unreachable!("Only faked system calls are skipped")
+        }
+    }
+}
+
+/// An [`Executor`] tracing the system calls of the target, see the [module-level docs](self).
+pub struct PtraceSyscallExecutor<OT, S> {
+    command: Command,
+    input_file: InputFile,
+    use_stdin: bool,
+    policy: SyscallPolicy,
+    syscall_observer: Handle<SyscallObserver>,
+    timeout: Duration,
+    /// The timeout in whole seconds, shared with the `pre_exec` of the command
+    timeout_secs: Arc<AtomicU32>,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for PtraceSyscallExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PtraceSyscallExecutor")
+            .field("command", &self.command)
+            .field("input_file", &self.input_file)
+            .field("policy", &self.policy)
+            .field("timeout", &self.timeout)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> PtraceSyscallExecutor<OT, S> {
+    /// Creates a new [`PtraceSyscallExecutor`] for the target started by the given [`Command`].
+    ///
+    /// The input is passed via stdin, or an [`InputLocation::File`], whose path the command has
+    /// to pass to the target. The system calls are recorded into the [`SyscallObserver`] with
+    /// the given handle, which must be part of the `observers`.
+    /// A run times out after 5 seconds by default, see [`HasTimeout::set_timeout`].
+    pub fn new(
+        mut command: Command,
+        input_location: InputLocation,
+        policy: SyscallPolicy,
+        syscall_observer: Handle<SyscallObserver>,
+        observers: OT,
+    ) -> Result<Self, Error> {
+        let (input_file, use_stdin) = match input_location {
+            InputLocation::StdIn => (InputFile::create(get_unique_std_input_file())?, true),
+            InputLocation::File { out_file } => (out_file, false),
+            InputLocation::Arg { .. } => {
+                return Err(Error::illegal_argument(
+                    "The PtraceSyscallExecutor cannot pass the input as argument",
+                ))
+            }
+        };
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+        if !use_stdin {
+            command.stdin(Stdio::null());
+        }
+        // Like the `PTraceCommandConfigurator`, time out with `SIGALRM`, in whole seconds
+        let timeout_secs = Arc::new(AtomicU32::new(5));
+        let alarm_secs = timeout_secs.clone();
+        unsafe {
+            command.pre_exec(move || {
+                ptrace::traceme()?;
+                libc::alarm(alarm_secs.load(Ordering::Relaxed));
+                Ok(())
+            });
+        }
+        Ok(Self {
+            command,
+            input_file,
+            use_stdin,
+            policy,
+            syscall_observer,
+            timeout: Duration::from_secs(5),
+            timeout_secs,
+            observers,
+            phantom: PhantomData,
+        })
+    }
+
+    /// The [`SyscallPolicy`] of this executor
+    pub fn policy(&self) -> &SyscallPolicy {
+        &self.policy
+    }
+
+    /// The [`SyscallPolicy`] of this executor, mutable
+    pub fn policy_mut(&mut self) -> &mut SyscallPolicy {
+        &mut self.policy
+    }
+
+    /// Traces the target until it ends, handling its system calls according to the policy
+    #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+    fn trace(
+        &self,
+        pid: Pid,
+        syscalls: &mut Vec<SyscallRecord>,
+        violations: &mut Vec<SyscallRecord>,
+    ) -> Result<ExitKind, Error> {
+        // The number of the current system call, and its faked result, between entry and exit
+        let mut current: Option<(u64, Option<i64>)> = None;
+        let mut signal = None;
+        loop {
+            ptrace::syscall(pid, signal.take())?;
+            match waitpid(pid, None)? {
+                WaitStatus::PtraceSyscall(_) => {
+                    let mut regs = ptrace::getregs(pid)?;
+                    if let Some((nr, faked)) = current.take() {
+                        if let Some(ret) = faked {
+                            regs.rax = ret as u64;
+                            ptrace::setregs(pid, regs)?;
+                        }
+                        syscalls.push(SyscallRecord::from_linux_return(nr, regs.rax as i64));
+                        continue;
+                    }
+
+                    let nr = regs.orig_rax;
+                    match self.policy.action(nr) {
+                        SyscallAction::Allow => current = Some((nr, None)),
+                        SyscallAction::Violation => {
+                            violations.push(SyscallRecord::new(nr, 0));
+                            kill(pid, Signal::SIGKILL)?;
+                            waitpid(pid, None)?;
+                            return Ok(ExitKind::Crash);
+                        }
+                        action => {
+                            let ret = fake_syscall(pid, &regs, action)?;
+                            // An invalid system call number skips the system call
+                            regs.orig_rax = u64::MAX;
+                            ptrace::setregs(pid, regs)?;
+                            current = Some((nr, Some(ret)));
+                        }
+                    }
+                }
+                WaitStatus::Stopped(_, sig) => signal = Some(sig),
+                WaitStatus::Exited(_, 0) => return Ok(ExitKind::Ok),
+                WaitStatus::Signaled(_, Signal::SIGALRM, _) => return Ok(ExitKind::Timeout),
+                WaitStatus::Exited(_, _) | WaitStatus::Signaled(_, _, _) => {
+                    return Ok(ExitKind::Crash)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<OT, S> UsesState for PtraceSyscallExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for PtraceSyscallExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        self.input_file.write_buf(input.target_bytes().as_slice())?;
+        if self.use_stdin {
+            self.command.stdin(self.input_file.file.try_clone()?);
+        }
+        let child = self.command.spawn()?;
+        #[allow(clippy::cast_possible_wrap)]
+        let pid = Pid::from_raw(child.id() as i32);
+
+        // The target stops after its `exec`
+        let wait_status = waitpid(pid, None)?;
+        if !matches!(wait_status, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+            return Err(Error::unknown(format!(
+                "Unexpected state of the traced target {wait_status:?} (while waiting for its exec)"
+            )));
+        }
+        ptrace::setoptions(
+            pid,
+            ptrace::Options::PTRACE_O_TRACESYSGOOD | ptrace::Options::PTRACE_O_EXITKILL,
+        )?;
+
+        self.observers.pre_exec_child_all(state, input)?;
+        let mut syscalls = Vec::new();
+        let mut violations = Vec::new();
+        let exit_kind = self.trace(pid, &mut syscalls, &mut violations)?;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+
+        let observer = self
+            .observers
+            .get_mut(&self.syscall_observer)
+            .ok_or_else(|| {
+                Error::key_not_found("The SyscallObserver of the executor is missing")
+            })?;
+        observer.fill_external(&syscalls);
+        for violation in violations {
+            observer.record_violation(violation);
+        }
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for PtraceSyscallExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+        let secs = u32::try_from(timeout.as_secs()).unwrap_or(u32::MAX).max(1);
+        self.timeout_secs.store(secs, Ordering::Relaxed);
+    }
+}
+
+impl<OT, S> HasObservers for PtraceSyscallExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use libafl_bolts::tuples::{tuple_list, Handled};
+
+    use super::{PtraceSyscallExecutor, SyscallAction, SyscallPolicy};
+    use crate::{
+        events::NopEventManager,
+        executors::{command::InputLocation, Executor, ExitKind, HasObservers},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::{SyscallObserver, SyscallRecord},
+        state::NopState,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_ptrace_syscall_executor() {
+        let observer = SyscallObserver::new("syscalls");
+        let handle = observer.handle();
+        let policy = SyscallPolicy::new().on(libc::SYS_uname, SyscallAction::Return(-1));
+        let mut executor = PtraceSyscallExecutor::new(
+            Command::new("uname"),
+            InputLocation::StdIn,
+            policy,
+            handle.clone(),
+            tuple_list!(observer),
+        )
+        .unwrap();
+        let mut state = NopState::new();
+        let mut run = |executor: &mut PtraceSyscallExecutor<_, _>| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::default(),
+                    &BytesInput::new(vec![]),
+                )
+                .unwrap()
+        };
+
+        // uname fails with the faked EPERM
+        assert_eq!(run(&mut executor), ExitKind::Crash);
+        let syscalls = executor.observers()[&handle].syscalls().to_vec();
+        assert!(syscalls.contains(&SyscallRecord::new(libc::SYS_uname as u64, libc::EPERM)));
+
+        *executor.policy_mut() = SyscallPolicy::new().on(libc::SYS_uname, SyscallAction::Violation);
+        assert_eq!(run(&mut executor), ExitKind::Crash);
+        assert_eq!(
+            executor.observers()[&handle].violations(),
+            [SyscallRecord::new(libc::SYS_uname as u64, 0)]
+        );
+
+        *executor.policy_mut() = SyscallPolicy::new();
+        assert_eq!(run(&mut executor), ExitKind::Ok);
+    }
+}
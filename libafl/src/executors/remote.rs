@@ -0,0 +1,420 @@
+//! The [`RemoteExecutor`] runs the target on a remote worker, while the fuzzer state stays local.
+//!
+//! The remote worker is a small program on the target machine that wraps any local [`Executor`],
+//! for example an [`crate::executors::InProcessExecutor`], and calls [`serve_remote`].
+//! For each input, the [`RemoteExecutor`] sends the serialized input to the worker, which runs it and
+//! answers with the [`ExitKind`] and its serialized observers. These are merged into the observers of the
+//! [`RemoteExecutor`], so feedbacks evaluate them as if the target ran locally.
+//!
+//! This way, targets that need special hardware, or that must run on isolated machines, can be fuzzed.
+//! The worker can be reached over any bidirectional stream, for example a [`TcpStream`],
+//! or `ssh` with the [`ChildTransport`] on the fuzzer side and the [`StdioTransport`] on the worker side.
+//!
+//! Messages are `postcard`-serialized, each prefixed with its length as little-endian `u32`.
+//! The observers of the worker executor and of the [`RemoteExecutor`] must have the same types and names.
+//! The worker only calls `pre_exec` on its observers, their `post_exec` runs on the fuzzer side.
+//! Only observers implementing [`RemoteObserver`] are supported: the map observers, which copy the map
+//! of the worker into their local map, and the [`TimeObserver`], [`StdOutObserver`] and [`StdErrObserver`].
+//!
+//! [`RemoteObserver`]: crate::observers::RemoteObserver
+//! [`TimeObserver`]: crate::observers::TimeObserver
+//! [`StdOutObserver`]: crate::observers::StdOutObserver
+//! [`StdErrObserver`]: crate::observers::StdErrObserver
+
+use alloc::string::String;
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+use std::{
+    fs::File,
+    io::{self, ErrorKind, Read, Stdin, Write},
+    net::{TcpStream, ToSocketAddrs},
+    os::fd::{FromRawFd, RawFd},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use libafl_bolts::{
+    os::{dup, dup2},
+    tuples::RefIndexable,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers},
+    observers::{ObserversTuple, RemoteObserversTuple},
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// A request from the [`RemoteExecutor`] to the remote worker
+#[derive(Serialize, Deserialize, Debug)]
+pub enum RemoteRequest<I> {
+    /// Run the target with the given input
+    Run(I),
+    /// Stop serving
+    Shutdown,
+}
+
+/// The answer of the remote worker to a [`RemoteRequest::Run`]
+#[derive(Serialize, Deserialize, Debug)]
+pub enum RemoteResponse<OT> {
+    /// The target ran
+    Ran {
+        /// How the run finished
+        exit_kind: ExitKind,
+        /// The observers of the worker after the run
+        observers: OT,
+    },
+    /// The worker executor returned an error
+    Failed(String),
+}
+
+/// Sends a length-prefixed, serialized message over the transport
+fn send_msg<T, M>(transport: &mut T, msg: &M) -> Result<(), Error>
+where
+    T: Write,
+    M: Serialize,
+{
+    let buf = postcard::to_allocvec(msg)?;
+    let len = u32::try_from(buf.len())
+        .map_err(|_| Error::illegal_argument("Remote message is too large"))?;
+    transport.write_all(&len.to_le_bytes())?;
+    transport.write_all(&buf)?;
+    transport.flush()?;
+    Ok(())
+}
+
+/// Receives a length-prefixed, serialized message from the transport
+fn recv_msg<T, M>(transport: &mut T) -> Result<M, Error>
+where
+    T: Read,
+    M: DeserializeOwned,
+{
+    let mut len_buf = [0_u8; 4];
+    transport.read_exact(&mut len_buf)?;
+    let mut buf = vec![0_u8; u32::from_le_bytes(len_buf) as usize];
+    transport.read_exact(&mut buf)?;
+    Ok(postcard::from_bytes(&buf)?)
+}
+
+/// An [`Executor`] shipping inputs to a remote worker, see the [module-level docs](self).
+pub struct RemoteExecutor<OT, S, T> {
+    transport: T,
+    observers: OT,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S, T> Debug for RemoteExecutor<OT, S, T>
+where
+    OT: Debug,
+    T: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteExecutor")
+            .field("transport", &self.transport)
+            .field("observers", &self.observers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S, T> RemoteExecutor<OT, S, T>
+where
+    T: Read + Write,
+{
+    /// Creates a new [`RemoteExecutor`], talking to a remote worker over the given transport.
+    /// The `observers` must match the observers of the worker executor.
+    pub fn new(transport: T, observers: OT) -> Self {
+        Self {
+            transport,
+            observers,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The transport to the remote worker
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Asks the remote worker to stop serving
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        send_msg(&mut self.transport, &RemoteRequest::<()>::Shutdown)
+    }
+}
+
+impl<OT, S> RemoteExecutor<OT, S, TcpStream> {
+    /// Connects to a remote worker listening on the given address
+    pub fn connect<A>(addr: A, observers: OT) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream, observers))
+    }
+}
+
+impl<OT, S> RemoteExecutor<OT, S, ChildTransport> {
+    /// Spawns the remote worker with the given [`Command`], talking to it over its stdin and stdout,
+    /// for example `ssh worker-host /path/to/worker`.
+    pub fn spawn(command: Command, observers: OT) -> Result<Self, Error> {
+        Ok(Self::new(ChildTransport::spawn(command)?, observers))
+    }
+}
+
+impl<OT, S, T> UsesState for RemoteExecutor<OT, S, T>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, T, Z> Executor<EM, Z> for RemoteExecutor<OT, S, T>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: Serialize,
+    OT: ObserversTuple<S::Input, S> + RemoteObserversTuple + DeserializeOwned,
+    T: Read + Write,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        send_msg(&mut self.transport, &RemoteRequest::Run(input))?;
+        match recv_msg(&mut self.transport)? {
+            RemoteResponse::Ran {
+                exit_kind,
+                observers,
+            } => {
+                self.observers.merge_remote_all(&observers)?;
+                Ok(exit_kind)
+            }
+            RemoteResponse::Failed(err) => Err(Error::unknown(format!(
+                "The remote worker failed to run the input: {err}"
+            ))),
+        }
+    }
+}
+
+impl<OT, S, T> HasObservers for RemoteExecutor<OT, S, T>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// Serves a [`RemoteExecutor`] on the worker side, running each received input with the given executor.
+///
+/// Returns once the [`RemoteExecutor`] shuts the worker down, or closes the transport.
+pub fn serve_remote<E, EM, T, Z>(
+    executor: &mut E,
+    fuzzer: &mut Z,
+    state: &mut E::State,
+    mgr: &mut EM,
+    transport: &mut T,
+) -> Result<(), Error>
+where
+    E: Executor<EM, Z> + HasObservers,
+    E::Observers: ObserversTuple<E::Input, E::State> + Serialize,
+    E::Input: DeserializeOwned,
+    EM: UsesState<State = E::State>,
+    T: Read + Write,
+    Z: UsesState<State = E::State>,
+{
+    loop {
+        let input = match recv_msg(transport) {
+            Ok(RemoteRequest::Run(input)) => input,
+            Ok(RemoteRequest::Shutdown) => return Ok(()),
+            Err(Error::OsError(err, _, _)) if err.kind() == ErrorKind::UnexpectedEof => {
+                return Ok(())
+            }
+            Err(err) => return Err(err),
+        };
+
+        executor.observers_mut().pre_exec_all(state, &input)?;
+        match executor.run_target(fuzzer, state, mgr, &input) {
+            Ok(exit_kind) => send_msg(
+                transport,
+                &RemoteResponse::Ran {
+                    exit_kind,
+                    observers: &*executor.observers(),
+                },
+            )?,
+            Err(err) => {
+                log::error!("Remote run failed: {err}");
+                send_msg(transport, &RemoteResponse::<()>::Failed(format!("{err}")))?;
+            }
+        }
+    }
+}
+
+/// A transport over the stdin and stdout of a spawned child process, e.g., `ssh`.
+/// The child is killed on drop.
+#[derive(Debug)]
+pub struct ChildTransport {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl ChildTransport {
+    /// Spawns the given [`Command`], piping its stdin and stdout
+    pub fn spawn(mut command: Command) -> Result<Self, Error> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    /// The spawned child process
+    #[must_use]
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+}
+
+impl Read for ChildTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl Write for ChildTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+impl Drop for ChildTransport {
+    fn drop(&mut self) {
+        drop(self.child.kill());
+        drop(self.child.wait());
+    }
+}
+
+/// The worker side of a [`ChildTransport`], talking over the stdin and stdout of the worker process.
+#[derive(Debug)]
+pub struct StdioTransport {
+    stdin: Stdin,
+    stdout: File,
+}
+
+impl StdioTransport {
+    /// Takes over the stdin and stdout of this process.
+    ///
+    /// Anything else written to stdout afterwards, e.g., by the target, is redirected to stderr,
+    /// so that it does not corrupt the messages.
+    pub fn new() -> Result<Self, Error> {
+        let stdout_fd: RawFd = 1;
+        let stderr_fd: RawFd = 2;
+        let stdout = dup(stdout_fd)?;
+        dup2(stderr_fd, stdout_fd)?;
+        Ok(Self {
+            stdin: io::stdin(),
+            // # Safety
+            // The fd was just duplicated, and is owned by nobody else.
+            stdout: unsafe { File::from_raw_fd(stdout) },
+        })
+    }
+}
+
+impl Read for StdioTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buf)
+    }
+}
+
+impl Write for StdioTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::net::UnixStream, thread};
+
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{serve_remote, RemoteExecutor};
+    use crate::{
+        events::NopEventManager,
+        executors::{test::NopExecutor, Executor, ExitKind, WithObservers},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::StdOutObserver,
+        state::{HasExecutions, NopState},
+    };
+
+    #[test]
+    fn test_remote_executor() {
+        let (local, mut remote) = UnixStream::pair().unwrap();
+
+        let worker = thread::spawn(move || {
+            let mut executor = WithObservers::new(
+                NopExecutor::new(),
+                tuple_list!(StdOutObserver::new("stdout")),
+            );
+            let mut state = NopState::<BytesInput>::new();
+            serve_remote(
+                &mut executor,
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &mut remote,
+            )
+            .unwrap();
+            *state.executions()
+        });
+
+        let mut executor = RemoteExecutor::new(local, tuple_list!(StdOutObserver::new("stdout")));
+        let mut state = NopState::<BytesInput>::new();
+        let mut fuzzer = NopFuzzer::new();
+        let mut mgr = NopEventManager::new();
+
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &BytesInput::new(vec![1]))
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        // The worker executor fails on empty inputs
+        executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &BytesInput::new(vec![]))
+            .unwrap_err();
+        executor.shutdown().unwrap();
+
+        assert_eq!(worker.join().unwrap(), 2);
+        assert_eq!(*state.executions(), 2);
+    }
+}
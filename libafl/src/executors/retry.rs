@@ -0,0 +1,437 @@
+//! The [`RetryExecutor`] retries executions that failed for environmental reasons.
+//!
+//! Long campaigns occasionally hit transient failures that have nothing to do with the input, such as a failing
+//! `fork` under memory pressure, an interrupted syscall, or a reset connection. Instead of surfacing the error
+//! and killing the fuzzer, the [`RetryExecutor`] runs the input again, following its [`RetryPolicy`].
+//! The observers are reset with their `pre_exec` before every retry, so they only see the last run, and the
+//! retries are not counted as further executions. Executors that need to recover first, such as a
+//! `ForkserverExecutor` whose forkserver died, get a [`RetryExecutor::on_retry`] hook, see
+//! `RetryExecutor::with_forkserver`.
+//! The numbers of retried and recovered executions are reported as user stats.
+
+use alloc::borrow::Cow;
+use core::{fmt::Debug, marker::PhantomData, time::Duration};
+use std::{io::ErrorKind, thread};
+
+use libafl_bolts::tuples::RefIndexable;
+
+#[cfg(all(feature = "fork", unix))]
+use libafl_bolts::shmem::ShMemProvider;
+
+#[cfg(all(feature = "fork", unix))]
+use crate::{executors::ForkserverExecutor, inputs::TargetBytesConverter};
+use crate::{
+    events::{Event, EventFirer},
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::UsesInput,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// Decides if a failed execution is retried, see [`is_transient_error`]
+pub type RetryPredicate = fn(&Error) -> bool;
+
+/// Whether the error is likely transient, so the execution may succeed when retried.
+///
+/// These are [`Error::OsError`]s of interrupted or refused syscalls, resource exhaustion, and broken connections.
+/// This includes a failing `fork` of the `InProcessForkExecutor`, and a forkserver that
+/// stopped responding. Errors of other variants are never retried by default; in particular, `nix` errors
+/// converted with `?` are [`Error::Unknown`] and lose their errno. Pass a custom [`RetryPolicy::retry_if`]
+/// predicate for those.
+#[must_use]
+pub fn is_transient_error(err: &Error) -> bool {
+    let Error::OsError(err, _, _) = err else {
+        return false;
+    };
+    #[cfg(unix)]
+    if let Some(libc::EAGAIN | libc::EINTR | libc::ENOMEM | libc::EMFILE | libc::ENFILE) =
+        err.raw_os_error()
+    {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+            | ErrorKind::OutOfMemory
+    )
+}
+
+/// How the [`RetryExecutor`] retries failed executions
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_retries: usize,
+    backoff: Duration,
+    retry_if: RetryPredicate,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a new [`RetryPolicy`], retrying [transient errors](is_transient_error) up to `max_retries` times,
+    /// waiting 10 milliseconds before the first retry, and twice as long before every further one.
+    #[must_use]
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            backoff: Duration::from_millis(10),
+            retry_if: is_transient_error,
+        }
+    }
+
+    /// Sets the time to wait before the first retry; it doubles with every further retry
+    #[must_use]
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets which errors are retried
+    #[must_use]
+    pub fn retry_if(mut self, retry_if: RetryPredicate) -> Self {
+        self.retry_if = retry_if;
+        self
+    }
+
+    /// The maximum number of retries of an execution
+    #[must_use]
+    pub fn max_retries(&self) -> usize {
+        self.max_retries
+    }
+}
+
+/// Recovers the wrapped executor before a retry, see [`RetryExecutor::on_retry`]
+pub type RetryHook<E> = fn(&mut E) -> Result<(), Error>;
+
+/// An [`Executor`] wrapper retrying failed executions, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct RetryExecutor<E> {
+    executor: E,
+    policy: RetryPolicy,
+    on_retry: RetryHook<E>,
+    retried: u64,
+    recovered: u64,
+}
+
+impl<E> RetryExecutor<E> {
+    /// Wraps the `executor`, retrying its failed executions following the `policy`
+    pub fn new(executor: E, policy: RetryPolicy) -> Self {
+        Self {
+            executor,
+            policy,
+            on_retry: |_| Ok(()),
+            retried: 0,
+            recovered: 0,
+        }
+    }
+
+    /// Sets a hook recovering the wrapped executor before every retry.
+    ///
+    /// If the hook fails, its error is returned instead of retrying.
+    #[must_use]
+    pub fn on_retry(mut self, on_retry: RetryHook<E>) -> Self {
+        self.on_retry = on_retry;
+        self
+    }
+
+    /// The wrapped executor
+    pub fn inner(&mut self) -> &mut E {
+        &mut self.executor
+    }
+
+    /// How many executions were retried
+    #[must_use]
+    pub fn retried(&self) -> u64 {
+        self.retried
+    }
+
+    /// How many retried executions eventually succeeded
+    #[must_use]
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    /// Reports the counters to the event manager
+    fn report<EM>(&self, state: &mut E::State, mgr: &mut EM) -> Result<(), Error>
+    where
+        E: UsesState,
+        EM: EventFirer<State = E::State>,
+    {
+        for (name, value) in [
+            ("retried executions", self.retried),
+            ("recovered executions", self.recovered),
+        ] {
+            mgr.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(name),
+                    value: UserStats::new(UserStatsValue::Number(value), AggregatorOps::Sum),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "fork", unix))]
+impl<TC, OT, S, SP> RetryExecutor<ForkserverExecutor<TC, OT, S, SP>>
+where
+    OT: ObserversTuple<S::Input, S>,
+    S: UsesInput,
+    SP: ShMemProvider,
+    TC: TargetBytesConverter,
+{
+    /// Wraps the forkserver `executor`, restarting its forkserver before every retry
+    pub fn with_forkserver(
+        executor: ForkserverExecutor<TC, OT, S, SP>,
+        policy: RetryPolicy,
+    ) -> Self {
+        Self::new(executor, policy).on_retry(ForkserverExecutor::restart_forkserver)
+    }
+}
+
+impl<E, EM, Z> Executor<EM, Z> for RetryExecutor<E>
+where
+    E: Executor<EM, Z> + HasObservers,
+    E::Observers: ObserversTuple<<E::State as UsesInput>::Input, E::State>,
+    E::State: State + HasExecutions,
+    EM: EventFirer<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut Self::State,
+        mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        let mut backoff = self.policy.backoff;
+        let mut retries = 0;
+        let executions = *state.executions();
+        loop {
+            match self.executor.run_target(fuzzer, state, mgr, input) {
+                Ok(exit_kind) => {
+                    if retries > 0 {
+                        self.recovered += 1;
+                        self.report(state, mgr)?;
+                    }
+                    return Ok(exit_kind);
+                }
+                Err(err) if retries < self.policy.max_retries && (self.policy.retry_if)(&err) => {
+                    log::warn!(
+                        "Execution failed, retrying ({}/{}): {err}",
+                        retries + 1,
+                        self.policy.max_retries
+                    );
+                    if retries == 0 {
+                        self.retried += 1;
+                    }
+                    retries += 1;
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    (self.on_retry)(&mut self.executor)?;
+                    // The retry runs the same input again, so it's not another execution
+                    *state.executions_mut() = executions;
+                    self.executor.observers_mut().pre_exec_all(state, input)?;
+                }
+                Err(err) => {
+                    if retries > 0 {
+                        self.report(state, mgr)?;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl<E> UsesState for RetryExecutor<E>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E> HasObservers for RetryExecutor<E>
+where
+    E: HasObservers,
+{
+    type Observers = E::Observers;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.executor.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.executor.observers_mut()
+    }
+}
+
+impl<E> HasTimeout for RetryExecutor<E>
+where
+    E: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executor.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executor.set_timeout(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+    use core::{marker::PhantomData, time::Duration};
+    use std::io;
+
+    use libafl_bolts::{
+        tuples::{tuple_list, tuple_list_type, RefIndexable},
+        Named,
+    };
+
+    use super::{is_transient_error, RetryExecutor, RetryPolicy};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers},
+        fuzzer::NopFuzzer,
+        inputs::{BytesInput, UsesInput},
+        observers::Observer,
+        state::{HasExecutions, NopState, State, UsesState},
+        Error,
+    };
+
+    /// Counts the calls to its `pre_exec`
+    #[derive(Debug)]
+    struct PreExecCounter(usize);
+
+    impl Named for PreExecCounter {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("PreExecCounter");
+            &NAME
+        }
+    }
+
+    impl<I, S> Observer<I, S> for PreExecCounter {
+        fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+
+    /// Fails with an interrupted syscall the given number of times, then succeeds
+    struct FlakyExecutor<S> {
+        failures: usize,
+        recoveries: usize,
+        observers: tuple_list_type!(PreExecCounter),
+        phantom: PhantomData<S>,
+    }
+
+    impl<S> HasObservers for FlakyExecutor<S> {
+        type Observers = tuple_list_type!(PreExecCounter);
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    impl<S> UsesState for FlakyExecutor<S>
+    where
+        S: State,
+    {
+        type State = S;
+    }
+
+    impl<EM, S, Z> Executor<EM, Z> for FlakyExecutor<S>
+    where
+        EM: UsesState<State = S>,
+        S: State + HasExecutions,
+        Z: UsesState<State = S>,
+    {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            state: &mut S,
+            _mgr: &mut EM,
+            _input: &<S as UsesInput>::Input,
+        ) -> Result<ExitKind, Error> {
+            *state.executions_mut() += 1;
+            if self.failures == 0 {
+                return Ok(ExitKind::Ok);
+            }
+            self.failures -= 1;
+            Err(Error::os_error(
+                io::Error::from(io::ErrorKind::Interrupted),
+                "flaky",
+            ))
+        }
+    }
+
+    #[test]
+    fn test_retry_executor() {
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::<BytesInput>::new();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![]);
+        let policy = RetryPolicy::new(2).backoff(Duration::ZERO);
+
+        let mut executor = RetryExecutor::new(
+            FlakyExecutor {
+                failures: 2,
+                recoveries: 0,
+                observers: tuple_list!(PreExecCounter(0)),
+                phantom: PhantomData,
+            },
+            policy,
+        )
+        .on_retry(|executor| {
+            executor.recoveries += 1;
+            Ok(())
+        });
+        assert_eq!(
+            executor
+                .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+                .unwrap(),
+            ExitKind::Ok
+        );
+        assert_eq!((executor.retried(), executor.recovered()), (1, 1));
+        assert_eq!(executor.inner().observers.0 .0, 2);
+        assert_eq!(executor.inner().recoveries, 2);
+        assert_eq!(*state.executions(), 1);
+
+        executor.inner().failures = 3;
+        assert!(executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
+            .is_err());
+        assert_eq!((executor.retried(), executor.recovered()), (2, 1));
+
+        #[cfg(unix)]
+        assert!(is_transient_error(&Error::os_error(
+            io::Error::from_raw_os_error(libc::EMFILE),
+            "open"
+        )));
+        assert!(!is_transient_error(&Error::illegal_state("broken")));
+    }
+}
@@ -0,0 +1,229 @@
+//! Resource limits and isolation for targets spawned by executors, such as the
+//! [`crate::executors::CommandExecutor`] and the [`crate::executors::ForkserverExecutor`].
+//!
+//! A [`ChildSandbox`] is applied in the spawned child, right before it `exec`s the target.
+//! Without limits, a leaking or forking target can take down the whole fuzzing host.
+
+use core::time::Duration;
+#[cfg(target_os = "linux")]
+use std::{ffi::CString, os::unix::ffi::OsStrExt, path::PathBuf};
+use std::{io, os::unix::process::CommandExt, process::Command};
+
+use crate::Error;
+
+/// Turns the return value of a libc call into an [`io::Result`]
+fn check_libc(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Sets the soft and hard limit of the given resource
+macro_rules! set_rlimit {
+    ($resource:expr, $cur:expr, $max:expr) => {{
+        #[allow(trivial_numeric_casts, clippy::cast_possible_truncation)]
+        let limit = libc::rlimit {
+            rlim_cur: $cur as libc::rlim_t,
+            rlim_max: $max as libc::rlim_t,
+        };
+        check_libc(unsafe { libc::setrlimit($resource, &limit) })
+    }};
+}
+
+/// Resource limits and isolation for a spawned target, see the [module-level docs](self).
+///
+/// By default, nothing is limited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChildSandbox {
+    address_space_limit: Option<u64>,
+    cpu_time_limit: Option<Duration>,
+    open_files_limit: Option<u64>,
+    core_dumps: bool,
+    #[cfg(target_os = "linux")]
+    chroot: Option<PathBuf>,
+    #[cfg(target_os = "linux")]
+    namespaces: libc::c_int,
+    #[cfg(target_os = "linux")]
+    isolate_network: bool,
+}
+
+impl ChildSandbox {
+    /// Creates a new [`ChildSandbox`] without any limits
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the address space of the target to `bytes`, so allocations beyond fail.
+    #[must_use]
+    pub fn address_space_limit(mut self, bytes: u64) -> Self {
+        self.address_space_limit = Some(bytes);
+        self
+    }
+
+    /// Limits the CPU time of the target, rounded to whole seconds.
+    ///
+    /// The target is killed with `SIGXCPU` once it used up the limit, which executors report as a crash.
+    /// For forkservers, the limit holds for each forked child, but persistent targets accumulate
+    /// the CPU time of all runs in the same child.
+    #[must_use]
+    pub fn cpu_time_limit(mut self, limit: Duration) -> Self {
+        self.cpu_time_limit = Some(limit);
+        self
+    }
+
+    /// Limits the amount of file descriptors the target can open
+    #[must_use]
+    pub fn open_files_limit(mut self, limit: u64) -> Self {
+        self.open_files_limit = Some(limit);
+        self
+    }
+
+    /// Allows the target to dump its core on a crash, by lifting the core dump size limit.
+    ///
+    /// Where the core dump ends up depends on the `core_pattern` of the system,
+    /// see [`crate::observers::CoreDumpObserver`].
+    #[must_use]
+    pub fn core_dumps(mut self, core_dumps: bool) -> Self {
+        self.core_dumps = core_dumps;
+        self
+    }
+
+    /// If the target may dump its core, see [`Self::core_dumps`]
+    #[must_use]
+    pub fn has_core_dumps(&self) -> bool {
+        self.core_dumps
+    }
+
+    /// Changes the root directory of the target to `path`.
+    ///
+    /// The target and all its dependencies are then resolved within `path`.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn chroot<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.chroot = Some(path.into());
+        self
+    }
+
+    /// Moves the target into new namespaces, given as `CLONE_NEW*` flags of [`libc::unshare`].
+    ///
+    /// Without root, a new user namespace is created as well, so this also works unprivileged.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn namespaces(mut self, flags: libc::c_int) -> Self {
+        self.namespaces |= flags;
+        self
+    }
+
+    /// Moves the target into a new network namespace without any configured interfaces,
+    /// so it cannot reach the network, not even `localhost` of the host.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn isolate_network(mut self, isolate_network: bool) -> Self {
+        self.isolate_network = isolate_network;
+        self
+    }
+
+    /// Applies this sandbox to the given [`Command`], in the spawned child right before it `exec`s.
+    pub fn apply(&self, command: &mut Command) -> Result<(), Error> {
+        if *self == Self::default() {
+            return Ok(());
+        }
+
+        let address_space_limit = self.address_space_limit;
+        let cpu_time_limit = self.cpu_time_limit.map(|limit| limit.as_secs().max(1));
+        let open_files_limit = self.open_files_limit;
+        let core_dumps = self.core_dumps;
+
+        #[cfg(target_os = "linux")]
+        let chroot = self
+            .chroot
+            .as_ref()
+            .map(|path| CString::new(path.as_os_str().as_bytes()))
+            .transpose()
+            .map_err(|_| Error::illegal_argument("The chroot path must not contain nul bytes"))?;
+        #[cfg(target_os = "linux")]
+        let namespaces = {
+            let mut flags = self.namespaces;
+            if self.isolate_network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            // Creating namespaces and chroot need root, or a user namespace
+            if (flags != 0 || chroot.is_some()) && unsafe { libc::geteuid() } != 0 {
+                flags |= libc::CLONE_NEWUSER;
+            }
+            flags
+        };
+
+        let func = move || {
+            #[cfg(target_os = "linux")]
+            {
+                if namespaces != 0 {
+                    check_libc(unsafe { libc::unshare(namespaces) })?;
+                }
+                if let Some(chroot) = &chroot {
+                    check_libc(unsafe { libc::chroot(chroot.as_ptr()) })?;
+                    check_libc(unsafe { libc::chdir(c"/".as_ptr()) })?;
+                }
+            }
+            if let Some(bytes) = address_space_limit {
+                #[cfg(target_os = "openbsd")]
+                set_rlimit!(libc::RLIMIT_RSS, bytes, bytes)?;
+                #[cfg(not(target_os = "openbsd"))]
+                set_rlimit!(libc::RLIMIT_AS, bytes, bytes)?;
+            }
+            if let Some(secs) = cpu_time_limit {
+                // SIGXCPU at the soft limit, SIGKILL one second later
+                set_rlimit!(libc::RLIMIT_CPU, secs, secs + 1)?;
+            }
+            if let Some(limit) = open_files_limit {
+                set_rlimit!(libc::RLIMIT_NOFILE, limit, limit)?;
+            }
+            if core_dumps {
+                set_rlimit!(libc::RLIMIT_CORE, libc::RLIM_INFINITY, libc::RLIM_INFINITY)?;
+            }
+            Ok(())
+        };
+        // # Safety
+        // The closure only calls async-signal-safe libc functions, and does not allocate.
+        unsafe {
+            command.pre_exec(func);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::process::Command;
+
+    use super::ChildSandbox;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_sandbox_rlimits() {
+        let sandbox = ChildSandbox::new()
+            .address_space_limit(1 << 32)
+            .cpu_time_limit(Duration::from_secs(10))
+            .open_files_limit(17);
+
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            "test \"$(ulimit -n)\" = 17 && test \"$(ulimit -t)\" = 10",
+        ]);
+        sandbox.apply(&mut command).unwrap();
+        assert!(command.status().unwrap().success());
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "test \"$(ulimit -n)\" = 17"]);
+        ChildSandbox::new().apply(&mut command).unwrap();
+        assert!(!command.status().unwrap().success());
+    }
+}
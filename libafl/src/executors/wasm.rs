@@ -0,0 +1,462 @@
+//! The [`WasmExecutor`] runs a harness compiled to WebAssembly in-process, using [`wasmtime`].
+//!
+//! The module is instantiated once per campaign, and its exported harness is called with each input,
+//! like `LLVMFuzzerTestOneInput`. Traps, such as `unreachable` or out-of-bounds memory accesses,
+//! are reported as crashes. After a crash or a timeout, the module is instantiated again in a fresh
+//! [`Store`], so following runs start from a sane state.
+//!
+//! Coverage is collected by a host-side shim, provided to the module as imports:
+//! * `env.__sanitizer_cov_trace_pc_guard_init(start, stop)` and `env.__sanitizer_cov_trace_pc_guard(guard)`,
+//!   as emitted by `clang --target=wasm32-wasi -fsanitize-coverage=trace-pc-guard`.
+//! * `libafl.cov_edge(id)`, for modules instrumented by other tools.
+//!
+//! Both write to the coverage map given to the executor, which is usually observed by a
+//! [`crate::observers::StdMapObserver`] on the same memory.
+//! All other imports of the module trap when called.
+
+use alloc::{borrow::ToOwned, string::String, sync::Arc, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::thread;
+
+use libafl_bolts::{ownedref::OwnedMutSlice, tuples::RefIndexable};
+use wasmtime::{Caller, Config, Engine, Instance, Linker, Memory, Module, Store, Trap, TypedFunc};
+
+use crate::{
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::HasTargetBytes,
+    observers::ObserversTuple,
+    state::{HasExecutions, State, UsesState},
+    Error,
+};
+
+/// The interval in which the epoch of the engine is incremented, the granularity of timeouts
+const EPOCH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The default name of the harness export
+pub const DEFAULT_WASM_HARNESS: &str = "LLVMFuzzerTestOneInput";
+
+/// Turns a [`wasmtime::Error`] into an [`Error`]
+fn wasm_error(err: &wasmtime::Error) -> Error {
+    Error::unknown(format!("WebAssembly error: {err:?}"))
+}
+
+/// The host side of a module run by the [`WasmExecutor`]
+struct WasmHost {
+    /// The coverage map, written to by the instrumentation shim
+    map: OwnedMutSlice<'static, u8>,
+    /// The next id to hand out to a `trace-pc-guard` guard
+    next_guard: u32,
+}
+
+impl WasmHost {
+    /// Counts a hit of the edge with the given id
+    fn hit(&mut self, id: u32) {
+        let len = self.map.len();
+        if len != 0 {
+            let entry = &mut self.map[id as usize % len];
+            *entry = entry.wrapping_add(1);
+        }
+    }
+}
+
+/// Gets the exported memory of the module from a host function
+fn caller_memory(caller: &mut Caller<'_, WasmHost>) -> Result<Memory, wasmtime::Error> {
+    caller
+        .get_export("memory")
+        .and_then(wasmtime::Extern::into_memory)
+        .ok_or_else(|| wasmtime::Error::msg("The module does not export its memory"))
+}
+
+/// Adds the coverage instrumentation shim to the linker, see the [module-level docs](self)
+fn link_coverage_shim(linker: &mut Linker<WasmHost>) -> Result<(), wasmtime::Error> {
+    linker.func_wrap(
+        "env",
+        "__sanitizer_cov_trace_pc_guard_init",
+        |mut caller: Caller<'_, WasmHost>, start: u32, stop: u32| {
+            let memory = caller_memory(&mut caller)?;
+            let (data, host) = memory.data_and_store_mut(&mut caller);
+            for guard in (start..stop).step_by(4) {
+                let guard = guard as usize;
+                let bytes = data
+                    .get_mut(guard..guard + 4)
+                    .ok_or_else(|| wasmtime::Error::msg("Guard out of bounds"))?;
+                if bytes == [0; 4] {
+                    bytes.copy_from_slice(&host.next_guard.to_le_bytes());
+                    host.next_guard = host.next_guard.wrapping_add(1);
+                }
+            }
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "env",
+        "__sanitizer_cov_trace_pc_guard",
+        |mut caller: Caller<'_, WasmHost>, guard: u32| {
+            let memory = caller_memory(&mut caller)?;
+            let (data, host) = memory.data_and_store_mut(&mut caller);
+            let guard = guard as usize;
+            let bytes = data
+                .get(guard..guard + 4)
+                .ok_or_else(|| wasmtime::Error::msg("Guard out of bounds"))?;
+            let id = u32::from_le_bytes(bytes.try_into().unwrap());
+            if id != 0 {
+                host.hit(id);
+            }
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "libafl",
+        "cov_edge",
+        |mut caller: Caller<'_, WasmHost>, id: u32| {
+            caller.data_mut().hit(id);
+        },
+    )?;
+    Ok(())
+}
+
+/// The exports of an instantiated module used by the [`WasmExecutor`]
+struct WasmInstance {
+    memory: Memory,
+    harness: TypedFunc<(u32, u32), i32>,
+    malloc: TypedFunc<u32, u32>,
+    free: TypedFunc<u32, ()>,
+}
+
+/// Executor running a WebAssembly harness in-process, see the [module-level docs](self).
+///
+/// The module has to export its `memory`, the harness taking a pointer to the input and its length,
+/// and `malloc` and `free`, to place the input in the memory of the module.
+pub struct WasmExecutor<OT, S> {
+    engine: Engine,
+    module: Module,
+    linker: Linker<WasmHost>,
+    store: Store<WasmHost>,
+    instance: WasmInstance,
+    harness: String,
+    observers: OT,
+    timeout: Duration,
+    ticker_stop: Arc<AtomicBool>,
+    phantom: PhantomData<S>,
+}
+
+impl<OT, S> Debug for WasmExecutor<OT, S>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmExecutor")
+            .field("harness", &self.harness)
+            .field("observers", &self.observers)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<OT, S> WasmExecutor<OT, S> {
+    /// Creates a new [`WasmExecutor`] for the module in `wasm`, in binary or (with the `wat` feature
+    /// of `wasmtime`) text format, calling the [`DEFAULT_WASM_HARNESS`].
+    ///
+    /// The instrumentation shim writes the coverage to `map`.
+    pub fn new(
+        wasm: &[u8],
+        map: OwnedMutSlice<'static, u8>,
+        observers: OT,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        Self::with_harness(wasm, DEFAULT_WASM_HARNESS, map, observers, timeout)
+    }
+
+    /// Creates a new [`WasmExecutor`] for the module in `wasm`, calling the `harness` export
+    pub fn with_harness(
+        wasm: &[u8],
+        harness: &str,
+        map: OwnedMutSlice<'static, u8>,
+        observers: OT,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).map_err(|err| wasm_error(&err))?;
+        let module = Module::new(&engine, wasm).map_err(|err| wasm_error(&err))?;
+
+        let mut linker = Linker::new(&engine);
+        link_coverage_shim(&mut linker).map_err(|err| wasm_error(&err))?;
+        linker
+            .define_unknown_imports_as_traps(&module)
+            .map_err(|err| wasm_error(&err))?;
+
+        let mut store = Self::new_store(&engine, WasmHost { map, next_guard: 1 });
+        let instance = Self::instantiate(&linker, &module, &mut store, harness)?;
+
+        // Drives the epoch of the engine, for the timeouts
+        let ticker_stop = Arc::new(AtomicBool::new(false));
+        let ticker_engine = engine.clone();
+        let stop = ticker_stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                thread::sleep(EPOCH_INTERVAL);
+                ticker_engine.increment_epoch();
+            }
+        });
+
+        Ok(Self {
+            engine,
+            module,
+            linker,
+            store,
+            instance,
+            harness: harness.to_owned(),
+            observers,
+            timeout,
+            ticker_stop,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Creates a new [`Store`] for the module, trapping once the epoch deadline is reached
+    fn new_store(engine: &Engine, host: WasmHost) -> Store<WasmHost> {
+        let mut store = Store::new(engine, host);
+        store.epoch_deadline_trap();
+        store
+    }
+
+    /// Drops the current instance and its [`Store`], and instantiates the module again in a fresh one.
+    ///
+    /// Instances can not be removed from a [`Store`], so instantiating into the same one would leak
+    /// the memory of all previous instances.
+    fn reinstantiate(&mut self) -> Result<(), Error> {
+        let placeholder = WasmHost {
+            map: OwnedMutSlice::from(Vec::new()),
+            next_guard: 1,
+        };
+        let old_store = mem::replace(&mut self.store, Self::new_store(&self.engine, placeholder));
+        self.store = Self::new_store(&self.engine, old_store.into_data());
+        self.instance =
+            Self::instantiate(&self.linker, &self.module, &mut self.store, &self.harness)?;
+        Ok(())
+    }
+
+    /// Instantiates the module, and looks up its exports
+    fn instantiate(
+        linker: &Linker<WasmHost>,
+        module: &Module,
+        store: &mut Store<WasmHost>,
+        harness: &str,
+    ) -> Result<WasmInstance, Error> {
+        // The constructors of the module assign the guards again, to the same ids
+        store.data_mut().next_guard = 1;
+        // The constructors are not subject to the timeout
+        store.set_epoch_deadline(u64::from(u32::MAX));
+
+        let instance = linker
+            .instantiate(&mut *store, module)
+            .map_err(|err| wasm_error(&err))?;
+        // Reactor modules run their constructors in `_initialize`
+        if let Ok(initialize) = instance.get_typed_func::<(), ()>(&mut *store, "_initialize") {
+            initialize
+                .call(&mut *store, ())
+                .map_err(|err| wasm_error(&err))?;
+        }
+
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| Error::key_not_found("The module does not export its memory"))?;
+        let harness = Self::typed_func(&instance, store, harness)?;
+        let malloc = Self::typed_func(&instance, store, "malloc")?;
+        let free = Self::typed_func(&instance, store, "free")?;
+        Ok(WasmInstance {
+            memory,
+            harness,
+            malloc,
+            free,
+        })
+    }
+
+    /// Looks up an exported function of the module
+    fn typed_func<P, R>(
+        instance: &Instance,
+        store: &mut Store<WasmHost>,
+        name: &str,
+    ) -> Result<TypedFunc<P, R>, Error>
+    where
+        P: wasmtime::WasmParams,
+        R: wasmtime::WasmResults,
+    {
+        instance
+            .get_typed_func(&mut *store, name)
+            .map_err(|err| Error::key_not_found(format!("Missing export {name} ({err})")))
+    }
+
+    /// The [`Engine`] running the module
+    #[must_use]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Calls the harness with the input, placed in the memory of the module
+    fn call_harness(&mut self, input: &[u8]) -> Result<(), wasmtime::Error> {
+        let store = &mut self.store;
+        let instance = &self.instance;
+        let len = u32::try_from(input.len())?;
+
+        let ptr = instance.malloc.call(&mut *store, len)?;
+        if ptr == 0 && len != 0 {
+            return Err(wasmtime::Error::msg("malloc in the module failed"));
+        }
+        instance.memory.write(&mut *store, ptr as usize, input)?;
+        instance.harness.call(&mut *store, (ptr, len))?;
+        instance.free.call(&mut *store, ptr)?;
+        Ok(())
+    }
+}
+
+impl<OT, S> Drop for WasmExecutor<OT, S> {
+    fn drop(&mut self) {
+        self.ticker_stop.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<OT, S> UsesState for WasmExecutor<OT, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<EM, OT, S, Z> Executor<EM, Z> for WasmExecutor<OT, S>
+where
+    EM: UsesState<State = S>,
+    S: State + HasExecutions,
+    S::Input: HasTargetBytes,
+    OT: ObserversTuple<S::Input, S>,
+    Z: UsesState<State = S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut Self::State,
+        _mgr: &mut EM,
+        input: &Self::Input,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        let ticks = self.timeout.as_millis() / EPOCH_INTERVAL.as_millis();
+        self.store
+            .set_epoch_deadline(u64::try_from(ticks).unwrap_or(u64::MAX).max(1));
+
+        let exit_kind = match self.call_harness(input.target_bytes().as_slice()) {
+            Ok(()) => ExitKind::Ok,
+            Err(err) => {
+                let exit_kind = if matches!(err.downcast_ref::<Trap>(), Some(Trap::Interrupt)) {
+                    ExitKind::Timeout
+                } else {
+                    log::debug!("The WebAssembly harness trapped: {err:?}");
+                    ExitKind::Crash
+                };
+                // The module may be in any state after a trap, start over
+                self.reinstantiate()?;
+                exit_kind
+            }
+        };
+        Ok(exit_kind)
+    }
+}
+
+impl<OT, S> HasTimeout for WasmExecutor<OT, S> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT, S> HasObservers for WasmExecutor<OT, S>
+where
+    S: State,
+    OT: ObserversTuple<S::Input, S>,
+{
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::{ownedref::OwnedMutSlice, tuples::tuple_list};
+
+    use super::WasmExecutor;
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+
+    const HARNESS: &str = r#"
+        (module
+            (import "libafl" "cov_edge" (func $cov (param i32)))
+            (memory (export "memory") 1)
+            (func (export "malloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "free") (param i32))
+            (func (export "LLVMFuzzerTestOneInput") (param $ptr i32) (param $len i32) (result i32)
+                (call $cov (i32.const 1))
+                (if (i32.eqz (local.get $len)) (then (return (i32.const 0))))
+                (call $cov (i32.const 2))
+                (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 0x41)) (then (unreachable)))
+                (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 0x42)) (then (loop $l (br $l))))
+                (i32.const 0)))
+    "#;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_wasm_executor() {
+        let mut map = vec![0_u8; 16];
+        let map_ptr = map.as_mut_ptr();
+        let mut executor = WasmExecutor::new(
+            HARNESS.as_bytes(),
+            unsafe { OwnedMutSlice::from_raw_parts_mut(map_ptr, 16) },
+            tuple_list!(),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+        let mut state = NopState::new();
+        let mut run = |executor: &mut WasmExecutor<_, _>, input: &[u8]| {
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::default(),
+                    &BytesInput::new(input.to_vec()),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(run(&mut executor, b""), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"x"), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"A"), ExitKind::Crash);
+        assert_eq!(run(&mut executor, b"B"), ExitKind::Timeout);
+        assert_eq!(run(&mut executor, b"x"), ExitKind::Ok);
+        drop(executor);
+        assert_eq!(&map[..3], [0, 5, 4]);
+    }
+}
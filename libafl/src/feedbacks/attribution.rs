@@ -0,0 +1,101 @@
+//! Attribution of saved testcases and objectives to the feedbacks that deemed them interesting.
+//!
+//! With the `track_hit_feedbacks` feature, the fuzzer attaches a [`SaveReasonsMetadata`] to each
+//! testcase added to the corpus or to the solutions. It lists every feedback that fired, and the
+//! specific novelty it observed, if the feedback reports one (e.g., the new indices of a
+//! [`crate::feedbacks::MapFeedback`] tracking novelties).
+//! Like any other testcase metadata, it is stored next to on-disk testcases for triage.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{corpus::Testcase, HasMetadata};
+
+/// Why a single feedback deemed a testcase interesting
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SaveReason {
+    /// The name of the feedback
+    pub feedback: Cow<'static, str>,
+    /// If the feedback is part of the objective, rather than of the corpus feedback
+    pub objective: bool,
+    /// The novel map indices reported by the feedback, if any
+    pub novelties: Vec<usize>,
+    /// A human-readable description of the novelty reported by the feedback, if any
+    pub details: Option<String>,
+}
+
+/// The feedbacks that deemed a testcase interesting, with the novelty each of them observed
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct SaveReasonsMetadata {
+    /// One entry per feedback that fired
+    pub reasons: Vec<SaveReason>,
+}
+
+libafl_bolts::impl_serdeany!(SaveReasonsMetadata);
+
+impl SaveReasonsMetadata {
+    /// The [`SaveReason`] for the given feedback, if it fired
+    #[must_use]
+    pub fn reason(&self, feedback: &str) -> Option<&SaveReason> {
+        self.reasons.iter().find(|r| r.feedback == feedback)
+    }
+
+    fn reason_mut(&mut self, feedback: &str) -> &mut SaveReason {
+        let idx = if let Some(idx) = self.reasons.iter().position(|r| r.feedback == feedback) {
+            idx
+        } else {
+            self.reasons.push(SaveReason {
+                feedback: Cow::Owned(feedback.into()),
+                ..SaveReason::default()
+            });
+            self.reasons.len() - 1
+        };
+        &mut self.reasons[idx]
+    }
+
+    /// Records the novel map indices a feedback observed for this testcase.
+    /// Call this from [`crate::feedbacks::Feedback::append_metadata`].
+    pub fn add_novelties<I>(testcase: &mut Testcase<I>, feedback: &str, novelties: &[usize]) {
+        testcase
+            .metadata_or_insert_with(Self::default)
+            .reason_mut(feedback)
+            .novelties
+            .extend_from_slice(novelties);
+    }
+
+    /// Records a description of the novelty a feedback observed for this testcase.
+    /// Call this from [`crate::feedbacks::Feedback::append_metadata`].
+    pub fn add_details<I>(testcase: &mut Testcase<I>, feedback: &str, details: String) {
+        testcase
+            .metadata_or_insert_with(Self::default)
+            .reason_mut(feedback)
+            .details = Some(details);
+    }
+
+    /// Records the hit feedbacks and hit objectives of the testcase.
+    ///
+    /// Reasons added by feedbacks that did not fire in the end (e.g., the second
+    /// feedback of a fast `OR`) are dropped.
+    pub fn record_hits<I>(testcase: &mut Testcase<I>) {
+        let hits: Vec<(Cow<'static, str>, bool)> = testcase
+            .hit_feedbacks()
+            .iter()
+            .map(|name| (name.clone(), false))
+            .chain(
+                testcase
+                    .hit_objectives()
+                    .iter()
+                    .map(|name| (name.clone(), true)),
+            )
+            .collect();
+
+        let meta = testcase.metadata_or_insert_with(Self::default);
+        meta.reasons
+            .retain(|r| hits.iter().any(|(name, _)| *name == r.feedback));
+        for (name, objective) in hits {
+            meta.reason_mut(&name).objective = objective;
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Feedback and metadata keeping the core dumps of crashing testcases for offline triage.
+
+use alloc::borrow::Cow;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    observers::{CoreDumpObserver, CoreDumpSummary},
+    Error, HasMetadata,
+};
+
+/// Metadata for [`CoreDumpFeedback`], pointing to the core dump of the testcase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreDumpMetadata {
+    /// The path of the core dump
+    pub path: PathBuf,
+    /// The summary of the core dump, if it could be parsed
+    pub summary: Option<CoreDumpSummary>,
+}
+
+impl_serdeany!(CoreDumpMetadata);
+
+/// Moves the file, copying it if it is on another file system
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Nop feedback that moves the core dump of a crashing testcase next to it, and annotates the
+/// testcase with a [`CoreDumpMetadata`]. The testcase is never interesting (use with an OR).
+///
+/// Use it in the objective, with the directory of the on-disk objective corpus.
+/// The core dump of a testcase with the filename `name` is stored as `.name.core` in that directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CoreDumpFeedback {
+    o_ref: Handle<CoreDumpObserver>,
+    dir: PathBuf,
+}
+
+impl CoreDumpFeedback {
+    /// Creates a new [`CoreDumpFeedback`], storing the core dumps in `dir`
+    #[must_use]
+    pub fn new<P>(observer: &CoreDumpObserver, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            o_ref: observer.handle(),
+            dir: dir.into(),
+        }
+    }
+}
+
+impl Named for CoreDumpFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<S> StateInitializer<S> for CoreDumpFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CoreDumpFeedback
+where
+    I: Input,
+    OT: MatchName,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("CoreDumpObserver is missing"))?;
+        let Some(core) = observer.core() else {
+            return Ok(());
+        };
+
+        // Fix the filename of the testcase, to name the core dump after it
+        let filename = if let Some(filename) = testcase.filename() {
+            filename.clone()
+        } else {
+            let filename = testcase
+                .input()
+                .as_ref()
+                .ok_or(Error::illegal_state("Testcase without input"))?
+                .generate_name(None);
+            *testcase.filename_mut() = Some(filename.clone());
+            filename
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(".{filename}.core"));
+        move_file(core, &path)?;
+        let summary = CoreDumpSummary::from_file(&path)
+            .inspect_err(|err| {
+                log::warn!("Could not summarize core dump {}: {err}", path.display());
+            })
+            .ok();
+
+        testcase
+            .metadata_map_mut()
+            .insert(CoreDumpMetadata { path, summary });
+        Ok(())
+    }
+}
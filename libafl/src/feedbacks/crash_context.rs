@@ -0,0 +1,83 @@
+//! Feedback storing the [`crate::observers::CrashContext`] of in-process crashes with the objective testcase.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::CrashContextObserver,
+    Error, HasMetadata,
+};
+
+/// Nop feedback that annotates crashing testcases with the [`crate::observers::CrashContext`] of a
+/// [`CrashContextObserver`]. The testcase is never interesting (use with an OR).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashContextFeedback {
+    o_ref: Handle<CrashContextObserver>,
+}
+
+impl CrashContextFeedback {
+    /// Creates a new [`CrashContextFeedback`] for the given [`CrashContextObserver`]
+    #[must_use]
+    pub fn new(observer: &CrashContextObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
+
+impl Named for CrashContextFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<S> StateInitializer<S> for CrashContextFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashContextFeedback
+where
+    OT: MatchName,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("CrashContextObserver is missing"))?;
+        if let Some(context) = observer.context() {
+            testcase.metadata_map_mut().insert(context.clone());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Feedback and metadata storing the [`crate::observers::CrashState`] of crashing emulated testcases for offline triage.
+
+use alloc::borrow::Cow;
+use std::{fs, path::PathBuf};
+
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    inputs::Input,
+    observers::CrashStateObserver,
+    Error, HasMetadata,
+};
+
+/// Metadata for [`CrashStateFeedback`], pointing to the serialized [`crate::observers::CrashState`] of the testcase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashStateMetadata {
+    /// The path of the serialized crash state, see [`crate::observers::CrashState::from_file`]
+    pub path: PathBuf,
+}
+
+impl_serdeany!(CrashStateMetadata);
+
+/// Nop feedback that stores the [`crate::observers::CrashState`] of a crashing testcase next to it, and annotates the
+/// testcase with a [`CrashStateMetadata`]. The testcase is never interesting (use with an OR).
+///
+/// Use it in the objective, with the directory of the on-disk objective corpus.
+/// The crash state of a testcase with the filename `name` is stored as `.name.crashstate` in that directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashStateFeedback {
+    o_ref: Handle<CrashStateObserver>,
+    dir: PathBuf,
+}
+
+impl CrashStateFeedback {
+    /// Creates a new [`CrashStateFeedback`], storing the crash states in `dir`
+    #[must_use]
+    pub fn new<P>(observer: &CrashStateObserver, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            o_ref: observer.handle(),
+            dir: dir.into(),
+        }
+    }
+}
+
+impl Named for CrashStateFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<S> StateInitializer<S> for CrashStateFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashStateFeedback
+where
+    I: Input,
+    OT: MatchName,
+{
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    #[inline]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("CrashStateObserver is missing"))?;
+        let Some(state) = observer.state() else {
+            return Ok(());
+        };
+
+        // Fix the filename of the testcase, to name the crash state after it
+        let filename = if let Some(filename) = testcase.filename() {
+            filename.clone()
+        } else {
+            let filename = testcase
+                .input()
+                .as_ref()
+                .ok_or(Error::illegal_state("Testcase without input"))?
+                .generate_name(None);
+            *testcase.filename_mut() = Some(filename.clone());
+            filename
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(".{filename}.crashstate"));
+        fs::write(&path, state.to_bytes()?)?;
+
+        testcase
+            .metadata_map_mut()
+            .insert(CrashStateMetadata { path });
+        Ok(())
+    }
+}
@@ -13,7 +13,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "track_hit_feedbacks")]
 use crate::feedbacks::premature_last_result_err;
 use crate::{
-    executors::ExitKind,
+    executors::{ExitKind, ProxyObserversTuple},
     feedbacks::{Feedback, FeedbackFactory, StateInitializer},
     Error,
 };
@@ -177,6 +177,144 @@ where
     }
 }
 
+/// A [`DiffExecutorFeedback`] compares an observer of the primary executor of a
+/// [`crate::executors::DiffExecutor`] with an observer of its secondary executor.
+///
+/// Unlike the [`DiffFeedback`], the two observers may have the same name, as each one is only
+/// looked up in the observers of its own executor.
+#[derive(Serialize, Deserialize)]
+pub struct DiffExecutorFeedback<C, O1, O2> {
+    /// This feedback's name
+    name: Cow<'static, str>,
+    /// The observer of the primary executor
+    primary_ref: Handle<O1>,
+    /// The observer of the secondary executor
+    secondary_ref: Handle<O2>,
+    // The previous run's result of `Self::is_interesting`
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+    /// The comparator used to compare the two observers
+    comparator: C,
+}
+
+impl<C, O1, O2> DiffExecutorFeedback<C, O1, O2>
+where
+    O1: Named,
+    O2: Named,
+{
+    /// Create a new [`DiffExecutorFeedback`] comparing the observer `primary` of the primary executor
+    /// with the observer `secondary` of the secondary executor.
+    #[must_use]
+    pub fn new(name: &'static str, primary: &O1, secondary: &O2, comparator: C) -> Self {
+        Self {
+            name: Cow::from(name),
+            primary_ref: primary.handle(),
+            secondary_ref: secondary.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            comparator,
+        }
+    }
+}
+
+impl<O1, O2> DiffExecutorFeedback<fn(&O1, &O2) -> DiffResult, O1, O2>
+where
+    O1: Named + PartialEq<O2>,
+    O2: Named,
+{
+    /// Create a new [`DiffExecutorFeedback`] reporting a diff if the two observers are not equal.
+    #[must_use]
+    pub fn with_partial_eq(name: &'static str, primary: &O1, secondary: &O2) -> Self {
+        Self::new(name, primary, secondary, |o1, o2| {
+            if o1 == o2 {
+                DiffResult::Equal
+            } else {
+                DiffResult::Diff
+            }
+        })
+    }
+}
+
+impl<C, O1, O2, T> FeedbackFactory<DiffExecutorFeedback<C, O1, O2>, T>
+    for DiffExecutorFeedback<C, O1, O2>
+where
+    C: Clone,
+{
+    fn create_feedback(&self, _ctx: &T) -> DiffExecutorFeedback<C, O1, O2> {
+        Self {
+            name: self.name.clone(),
+            primary_ref: self.primary_ref.clone(),
+            secondary_ref: self.secondary_ref.clone(),
+            comparator: self.comparator.clone(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<C, O1, O2> Named for DiffExecutorFeedback<C, O1, O2> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, O1, O2> Debug for DiffExecutorFeedback<C, O1, O2> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiffExecutorFeedback")
+            .field("name", self.name())
+            .field("primary", &self.primary_ref)
+            .field("secondary", &self.secondary_ref)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, O1, O2, S> StateInitializer<S> for DiffExecutorFeedback<C, O1, O2> {}
+
+impl<C, DOT, EM, I, O1, O2, OTA, OTB, S> Feedback<EM, I, ProxyObserversTuple<OTA, OTB, DOT>, S>
+    for DiffExecutorFeedback<C, O1, O2>
+where
+    OTA: MatchName,
+    OTB: MatchName,
+    C: DiffComparator<O1, O2>,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &ProxyObserversTuple<OTA, OTB, DOT>,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let o1: &O1 = observers.primary().get(&self.primary_ref).ok_or_else(|| {
+            Error::illegal_argument(format!(
+                "DiffExecutorFeedback: observer {} not found in the primary executor",
+                self.primary_ref.name()
+            ))
+        })?;
+        let o2: &O2 = observers
+            .secondary()
+            .get(&self.secondary_ref)
+            .ok_or_else(|| {
+                Error::illegal_argument(format!(
+                    "DiffExecutorFeedback: observer {} not found in the secondary executor",
+                    self.secondary_ref.name()
+                ))
+            })?;
+        let res = self.comparator.compare(o1, o2) == DiffResult::Diff;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::borrow::Cow;
@@ -0,0 +1,370 @@
+//! The [`FastestPathFeedback`] keeps inputs that reach an already known coverage fingerprint
+//! significantly faster than any input before.
+//!
+//! The fingerprint of a run is the [`MapObserver::hash_simple`] of the coverage map, the best
+//! execution time for each fingerprint of an input added to the corpus is kept in the
+//! [`FastestPathMetadata`]. At most `max_fingerprints` are kept: when a new one is added, the
+//! fingerprints that were not added or matched for the longest time are forgotten.
+//! This is useful to build fast reproducers, or speed-focused corpora.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+
+use hashbrown::HashMap;
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::{MapObserver, TimeObserver},
+    Error, HasNamedMetadata,
+};
+
+/// The prefix of the metadata names
+pub const FASTEST_PATH_FEEDBACK_PREFIX: &str = "fastestpathfeedback_metadata_";
+
+/// The default speedup ratio an input needs to reach to be considered interesting.
+///
+/// With a ratio of `0.8`, a run needs to take at most 80% of the best known time for its fingerprint.
+pub const DEFAULT_SPEEDUP_RATIO: f64 = 0.8;
+
+/// The default maximum amount of coverage fingerprints kept by a [`FastestPathFeedback`]
+pub const DEFAULT_MAX_FASTEST_PATH_FINGERPRINTS: usize = 1 << 16;
+
+/// The state of [`FastestPathFeedback`]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct FastestPathMetadata {
+    /// The best known execution time for each coverage fingerprint,
+    /// with the amount of added fingerprints when it was last added or matched
+    best_times: HashMap<u64, (Duration, u64)>,
+    /// The amount of fingerprints added so far
+    added: u64,
+}
+
+libafl_bolts::impl_serdeany!(FastestPathMetadata);
+
+impl FastestPathMetadata {
+    /// Create a new [`FastestPathMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The best known execution time for the given coverage fingerprint, if any
+    #[must_use]
+    pub fn best_time(&self, fingerprint: u64) -> Option<Duration> {
+        self.best_times.get(&fingerprint).map(|(time, _)| *time)
+    }
+
+    /// The amount of coverage fingerprints with a known execution time
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.best_times.len()
+    }
+
+    /// If no coverage fingerprint has a known execution time yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.best_times.is_empty()
+    }
+
+    /// Returns the best known time of the fingerprint, and marks it as recently used
+    fn match_fingerprint(&mut self, fingerprint: u64) -> Option<Duration> {
+        let (time, last_used) = self.best_times.get_mut(&fingerprint)?;
+        *last_used = self.added;
+        Some(*time)
+    }
+
+    /// Records the time of a fingerprint of an input added to the corpus, forgetting the least
+    /// recently used fingerprints if more than `max` are known
+    fn add_fingerprint(&mut self, fingerprint: u64, time: Duration, max: usize) {
+        self.added += 1;
+        self.best_times.insert(fingerprint, (time, self.added));
+
+        let excess = self.best_times.len().saturating_sub(max);
+        if excess == 0 {
+            return;
+        }
+        let mut by_age: Vec<(u64, u64)> = self
+            .best_times
+            .iter()
+            .map(|(fingerprint, (_, last_used))| (*last_used, *fingerprint))
+            .collect();
+        by_age.select_nth_unstable(excess - 1);
+        for (_, fingerprint) in &by_age[..excess] {
+            self.best_times.remove(fingerprint);
+        }
+    }
+}
+
+/// A [`FastestPathFeedback`] considers a run interesting, if it reaches a coverage fingerprint that
+/// is already known, but (significantly) faster than the best run for this fingerprint so far.
+///
+/// Runs reaching a fingerprint that was never seen before are not interesting by themselves. Combine
+/// this feedback with a coverage feedback using an `OR`: the execution time of their fingerprint is
+/// recorded once they are added to the corpus.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FastestPathFeedback<C, O> {
+    name: Cow<'static, str>,
+    map_ref: Handle<C>,
+    time_ref: Handle<TimeObserver>,
+    speedup_ratio: f64,
+    max_fingerprints: usize,
+    /// The fingerprint and time of the last new or faster run, committed once the testcase is added
+    pending: Option<(u64, Duration)>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> FastestPathFeedback<C, O>
+where
+    C: Named,
+{
+    /// Returns a new [`FastestPathFeedback`], using the [`DEFAULT_SPEEDUP_RATIO`].
+    #[must_use]
+    pub fn new(map_observer: &C, time_observer: &TimeObserver) -> Self {
+        Self::with_speedup_ratio(map_observer, time_observer, DEFAULT_SPEEDUP_RATIO)
+    }
+
+    /// Returns a new [`FastestPathFeedback`]. A run is interesting if its execution time is at most
+    /// `speedup_ratio` times the best known time for its coverage fingerprint.
+    #[must_use]
+    pub fn with_speedup_ratio(
+        map_observer: &C,
+        time_observer: &TimeObserver,
+        speedup_ratio: f64,
+    ) -> Self {
+        Self {
+            name: Cow::from(format!(
+                "{FASTEST_PATH_FEEDBACK_PREFIX}{}",
+                map_observer.name()
+            )),
+            map_ref: map_observer.handle(),
+            time_ref: time_observer.handle(),
+            speedup_ratio,
+            max_fingerprints: DEFAULT_MAX_FASTEST_PATH_FINGERPRINTS,
+            pending: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum amount of coverage fingerprints kept,
+    /// [`DEFAULT_MAX_FASTEST_PATH_FINGERPRINTS`] by default
+    #[must_use]
+    pub fn with_max_fingerprints(mut self, max_fingerprints: usize) -> Self {
+        self.max_fingerprints = max_fingerprints;
+        self
+    }
+}
+
+impl<C, O> FastestPathFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_faster_path<OT, S>(&mut self, state: &mut S, observers: &OT) -> Result<bool, Error>
+    where
+        OT: MatchName,
+        S: HasNamedMetadata,
+    {
+        self.pending = None;
+
+        let Some(runtime) = *observers
+            .get(&self.time_ref)
+            .expect("A FastestPathFeedback needs a TimeObserver")
+            .last_runtime()
+        else {
+            return Ok(false);
+        };
+        let fingerprint = observers
+            .get(&self.map_ref)
+            .expect("A FastestPathFeedback needs a MapObserver")
+            .as_ref()
+            .hash_simple();
+
+        let meta = state.named_metadata_mut::<FastestPathMetadata>(&self.name)?;
+        let Some(best) = meta.match_fingerprint(fingerprint) else {
+            // A new fingerprint, remember how long it took to reach it if the input is added
+            self.pending = Some((fingerprint, runtime));
+            return Ok(false);
+        };
+
+        if runtime.as_secs_f64() <= best.as_secs_f64() * self.speedup_ratio {
+            self.pending = Some((fingerprint, runtime));
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for FastestPathFeedback<C, O>
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(&self.name, FastestPathMetadata::new());
+        Ok(())
+    }
+}
+
+impl<C, O, EM, I, OT, S> Feedback<EM, I, OT, S> for FastestPathFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver,
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let res = self.is_faster_path(state, observers)?;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if let Some((fingerprint, runtime)) = self.pending.take() {
+            state
+                .named_metadata_mut::<FastestPathMetadata>(&self.name)?
+                .add_fingerprint(fingerprint, runtime, self.max_fingerprints);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.pending = None;
+        Ok(())
+    }
+}
+
+impl<C, O> Named for FastestPathFeedback<C, O> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, O> HasObserverHandle for FastestPathFeedback<C, O> {
+    type Observer = C;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<C> {
+        &self.map_ref
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::thread;
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list, Named};
+
+    use super::{FastestPathFeedback, FastestPathMetadata};
+    use crate::{
+        corpus::{InMemoryCorpus, Testcase},
+        executors::ExitKind,
+        feedbacks::{ConstFeedback, Feedback, StateInitializer},
+        inputs::BytesInput,
+        observers::{MapObserver, Observer, StdMapObserver, TimeObserver},
+        state::StdState,
+        HasNamedMetadata,
+    };
+
+    #[test]
+    fn test_fastest_path() {
+        let map = StdMapObserver::owned("map", vec![0_u8; 4]);
+        let time = TimeObserver::new("time");
+        let mut feedback =
+            FastestPathFeedback::<_, StdMapObserver<'_, u8, false>>::new(&map, &time)
+                .with_max_fingerprints(1);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        feedback.init_state(&mut state).unwrap();
+        let input = BytesInput::new(vec![]);
+        let mut observers = tuple_list!(map, time);
+
+        // Runs the input with the given coverage, then adds it to the corpus or discards it
+        let mut evaluate = |edge: usize, delay: Duration, add: bool| {
+            observers.0.reset_map().unwrap();
+            observers.0.set(edge, 1);
+            observers.1.pre_exec(&mut state, &input).unwrap();
+            thread::sleep(delay);
+            observers
+                .1
+                .post_exec(&mut state, &input, &ExitKind::Ok)
+                .unwrap();
+
+            let interesting = feedback
+                .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+                .unwrap();
+            if add {
+                let mut testcase = Testcase::new(input.clone());
+                feedback
+                    .append_metadata(&mut state, &mut (), &observers, &mut testcase)
+                    .unwrap();
+            } else {
+                feedback.discard_metadata(&mut state, &input).unwrap();
+            }
+            let fingerprint = observers.0.hash_simple();
+            let meta = state
+                .named_metadata::<FastestPathMetadata>(feedback.name())
+                .unwrap();
+            (interesting, meta.best_time(fingerprint), meta.len())
+        };
+
+        let slow = Duration::from_millis(50);
+        // A discarded input does not record its fingerprint
+        assert_eq!(evaluate(0, slow, false), (false, None, 0));
+        let (interesting, slow_time, len) = evaluate(0, slow, true);
+        assert!(!interesting && slow_time.is_some() && len == 1);
+
+        let (interesting, fast_time, _) = evaluate(0, Duration::ZERO, true);
+        assert!(interesting);
+        assert!(fast_time < slow_time);
+
+        // Only the most recent fingerprint is kept
+        let (_, time, len) = evaluate(1, slow, true);
+        assert!(time.is_some() && len == 1);
+        assert_eq!(evaluate(0, Duration::ZERO, false), (false, None, 1));
+    }
+}
@@ -0,0 +1,427 @@
+//! Combined feedbacks which keep statistics about their leaf feedbacks.
+//!
+//! A [`StatsCombinedFeedback`] behaves exactly like a [`CombinedFeedback`](super::CombinedFeedback),
+//! but additionally counts how often each of its two feedbacks fired, how often one of them was
+//! short-circuited by the [`FeedbackLogic`], and how often both were evaluated and disagreed.
+//! The statistics can be queried at runtime via [`StatsCombinedFeedback::stats`] and are
+//! periodically sent to the monitor as [`UserStats`].
+//!
+//! This is mostly useful to debug complex feedback setups, e.g., objectives that never fire.
+
+use alloc::borrow::Cow;
+#[cfg(feature = "track_hit_feedbacks")]
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "introspection")]
+use crate::state::HasClientPerfMonitor;
+use crate::{
+    corpus::Testcase,
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::{
+        Feedback, FeedbackFactory, FeedbackLogic, LogicEagerAnd, LogicEagerOr, LogicFastAnd,
+        LogicFastOr, StateInitializer,
+    },
+    inputs::UsesInput,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    Error,
+};
+
+/// The default amount of evaluations after which a [`StatsCombinedFeedback`] reports its stats
+pub const DEFAULT_LOGIC_STATS_REPORT_INTERVAL: u64 = 1024;
+
+/// Statistics about a single leaf of a [`StatsCombinedFeedback`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LeafFeedbackStats {
+    /// How often this feedback was evaluated and reported the run as interesting
+    pub fired: u64,
+    /// How often this feedback was evaluated and reported the run as uninteresting
+    pub not_fired: u64,
+    /// How often this feedback was not evaluated at all, because the logic short-circuited
+    pub short_circuited: u64,
+}
+
+impl LeafFeedbackStats {
+    /// Record the outcome of a single evaluation, `None` if the feedback was not evaluated
+    fn record(&mut self, result: Option<bool>) {
+        match result {
+            Some(true) => self.fired += 1,
+            Some(false) => self.not_fired += 1,
+            None => self.short_circuited += 1,
+        }
+    }
+
+    /// How often this feedback was actually evaluated
+    #[must_use]
+    pub fn evaluated(&self) -> u64 {
+        self.fired + self.not_fired
+    }
+}
+
+/// Statistics collected by a [`StatsCombinedFeedback`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeedbackLogicStats {
+    /// How often the combined feedback was evaluated
+    pub evaluations: u64,
+    /// How often the combined feedback reported the run as interesting
+    pub fired: u64,
+    /// How often both feedbacks were evaluated but returned different results
+    pub disagreed: u64,
+    /// Stats of the first feedback
+    pub first: LeafFeedbackStats,
+    /// Stats of the second feedback
+    pub second: LeafFeedbackStats,
+}
+
+impl FeedbackLogicStats {
+    /// Record the outcome of a single evaluation of the combined feedback
+    fn record(&mut self, first: Option<bool>, second: Option<bool>, result: bool) {
+        self.evaluations += 1;
+        if result {
+            self.fired += 1;
+        }
+        if let (Some(first), Some(second)) = (first, second) {
+            if first != second {
+                self.disagreed += 1;
+            }
+        }
+        self.first.record(first);
+        self.second.record(second);
+    }
+}
+
+/// A combined feedback consisting of two [`Feedback`]s, keeping [`FeedbackLogicStats`] about them
+#[derive(Debug)]
+pub struct StatsCombinedFeedback<A, B, FL> {
+    /// First [`Feedback`]
+    pub first: A,
+    /// Second [`Feedback`]
+    pub second: B,
+    stats: FeedbackLogicStats,
+    report_interval: u64,
+    name: Cow<'static, str>,
+    stats_names: [Cow<'static, str>; 6],
+    phantom: PhantomData<FL>,
+}
+
+impl<A, B, FL> Named for StatsCombinedFeedback<A, B, FL> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<A, B, FL> StatsCombinedFeedback<A, B, FL>
+where
+    A: Named,
+    B: Named,
+    FL: FeedbackLogic,
+{
+    /// Create a new combined feedback, keeping statistics about `first` and `second`
+    pub fn new(first: A, second: B) -> Self {
+        let name = Cow::from(format!(
+            "{} ({},{})",
+            FL::name(),
+            first.name(),
+            second.name()
+        ));
+        let stats_names = [
+            Cow::from(format!("{name} fired")),
+            Cow::from(format!("{name} disagreed")),
+            Cow::from(format!("{} fired", first.name())),
+            Cow::from(format!("{} short-circuited", first.name())),
+            Cow::from(format!("{} fired", second.name())),
+            Cow::from(format!("{} short-circuited", second.name())),
+        ];
+        Self {
+            first,
+            second,
+            stats: FeedbackLogicStats::default(),
+            report_interval: DEFAULT_LOGIC_STATS_REPORT_INTERVAL,
+            name,
+            stats_names,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<A, B, FL> StatsCombinedFeedback<A, B, FL> {
+    /// Set after how many evaluations the stats are sent to the monitor.
+    /// An interval of `0` disables reporting.
+    #[must_use]
+    pub fn with_report_interval(mut self, report_interval: u64) -> Self {
+        self.report_interval = report_interval;
+        self
+    }
+
+    /// The statistics collected so far
+    #[must_use]
+    pub fn stats(&self) -> &FeedbackLogicStats {
+        &self.stats
+    }
+
+    /// Reset the statistics collected so far
+    pub fn reset_stats(&mut self) {
+        self.stats = FeedbackLogicStats::default();
+    }
+
+    /// Records one evaluation and, if due, sends the current stats to the monitor
+    fn record_and_report<EM, S>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        first: Option<bool>,
+        second: Option<bool>,
+        result: bool,
+    ) -> Result<(), Error>
+    where
+        EM: EventFirer<State = S>,
+        S: UsesInput,
+    {
+        self.stats.record(first, second, result);
+        if self.report_interval == 0 || self.stats.evaluations % self.report_interval != 0 {
+            return Ok(());
+        }
+
+        let values = [
+            self.stats.fired,
+            self.stats.disagreed,
+            self.stats.first.fired,
+            self.stats.first.short_circuited,
+            self.stats.second.fired,
+            self.stats.second.short_circuited,
+        ];
+        for (name, value) in self.stats_names.iter().zip(values) {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: name.clone(),
+                    value: UserStats::new(UserStatsValue::Number(value), AggregatorOps::Sum),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<A, B, FL, S> StateInitializer<S> for StatsCombinedFeedback<A, B, FL>
+where
+    A: StateInitializer<S>,
+    B: StateInitializer<S>,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        self.first.init_state(state)?;
+        self.second.init_state(state)?;
+        Ok(())
+    }
+}
+
+impl<A, B, FL, EM, I, OT, S> Feedback<EM, I, OT, S> for StatsCombinedFeedback<A, B, FL>
+where
+    A: Feedback<EM, I, OT, S>,
+    B: Feedback<EM, I, OT, S>,
+    EM: EventFirer<State = S>,
+    FL: FeedbackLogic,
+    S: UsesInput,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let mut first_result = None;
+        let mut second_result = None;
+        let res = FL::is_pair_interesting(
+            |state, manager, input, observers, exit_kind| {
+                let res = self
+                    .first
+                    .is_interesting(state, manager, input, observers, exit_kind)?;
+                first_result = Some(res);
+                Ok(res)
+            },
+            |state, manager, input, observers, exit_kind| {
+                let res = self
+                    .second
+                    .is_interesting(state, manager, input, observers, exit_kind)?;
+                second_result = Some(res);
+                Ok(res)
+            },
+            state,
+            manager,
+            input,
+            observers,
+            exit_kind,
+        )?;
+        self.record_and_report(state, manager, first_result, second_result, res)?;
+        Ok(res)
+    }
+
+    #[cfg(feature = "introspection")]
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting_introspection(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        S: HasClientPerfMonitor,
+    {
+        let mut first_result = None;
+        let mut second_result = None;
+        let res = FL::is_pair_interesting(
+            |state, manager, input, observers, exit_kind| {
+                let res = self
+                    .first
+                    .is_interesting_introspection(state, manager, input, observers, exit_kind)?;
+                first_result = Some(res);
+                Ok(res)
+            },
+            |state, manager, input, observers, exit_kind| {
+                let res = self
+                    .second
+                    .is_interesting_introspection(state, manager, input, observers, exit_kind)?;
+                second_result = Some(res);
+                Ok(res)
+            },
+            state,
+            manager,
+            input,
+            observers,
+            exit_kind,
+        )?;
+        self.record_and_report(state, manager, first_result, second_result, res)?;
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        FL::last_result(self.first.last_result(), self.second.last_result())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn append_hit_feedbacks(&self, list: &mut Vec<Cow<'static, str>>) -> Result<(), Error> {
+        FL::append_hit_feedbacks(
+            self.first.last_result(),
+            |list| self.first.append_hit_feedbacks(list),
+            self.second.last_result(),
+            |list| self.second.append_hit_feedbacks(list),
+            list,
+        )
+    }
+
+    #[inline]
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        self.first
+            .append_metadata(state, manager, observers, testcase)?;
+        self.second
+            .append_metadata(state, manager, observers, testcase)
+    }
+
+    #[inline]
+    fn discard_metadata(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.first.discard_metadata(state, input)?;
+        self.second.discard_metadata(state, input)
+    }
+}
+
+impl<A, B, FL, T> FeedbackFactory<StatsCombinedFeedback<A, B, FL>, T>
+    for StatsCombinedFeedback<A, B, FL>
+where
+    A: FeedbackFactory<A, T> + Named,
+    B: FeedbackFactory<B, T> + Named,
+    FL: FeedbackLogic,
+{
+    fn create_feedback(&self, ctx: &T) -> StatsCombinedFeedback<A, B, FL> {
+        StatsCombinedFeedback::new(
+            self.first.create_feedback(ctx),
+            self.second.create_feedback(ctx),
+        )
+        .with_report_interval(self.report_interval)
+    }
+}
+
+/// Combine two feedbacks with an eager AND operation, keeping statistics
+pub type StatsEagerAndFeedback<A, B> = StatsCombinedFeedback<A, B, LogicEagerAnd>;
+
+/// Combine two feedbacks with a fast AND operation, keeping statistics
+pub type StatsFastAndFeedback<A, B> = StatsCombinedFeedback<A, B, LogicFastAnd>;
+
+/// Combine two feedbacks with an eager OR operation, keeping statistics
+pub type StatsEagerOrFeedback<A, B> = StatsCombinedFeedback<A, B, LogicEagerOr>;
+
+/// Combine two feedbacks with a fast OR operation, keeping statistics
+pub type StatsFastOrFeedback<A, B> = StatsCombinedFeedback<A, B, LogicFastOr>;
+
+/// Variadic macro to create a chain of [`StatsCombinedFeedback`]s with the given [`FeedbackLogic`]
+///
+/// ```rust,ignore
+/// let objective = feedback_with_stats!(LogicFastOr; CrashFeedback::new(), TimeoutFeedback::new());
+/// ```
+#[macro_export]
+macro_rules! feedback_with_stats {
+    ( $logic:ty; $last:expr ) => { $last };
+
+    ( $logic:ty; $last:expr, ) => { $last };
+
+    ( $logic:ty; $head:expr, $($tail:expr),+ $(,)?) => {
+        // recursive call
+        $crate::feedbacks::logic_stats::StatsCombinedFeedback::<_, _, $logic>::new(
+            $head,
+            feedback_with_stats!($logic; $($tail),+),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedbackLogicStats, LeafFeedbackStats};
+
+    #[test]
+    fn test_logic_stats_record() {
+        let mut stats = FeedbackLogicStats::default();
+        // fast or, first fired, second short-circuited
+        stats.record(Some(true), None, true);
+        // both evaluated, disagreeing
+        stats.record(Some(false), Some(true), true);
+        // both evaluated, agreeing
+        stats.record(Some(false), Some(false), false);
+
+        assert_eq!(stats.evaluations, 3);
+        assert_eq!(stats.fired, 2);
+        assert_eq!(stats.disagreed, 1);
+        assert_eq!(
+            stats.first,
+            LeafFeedbackStats {
+                fired: 1,
+                not_fired: 2,
+                short_circuited: 0,
+            }
+        );
+        assert_eq!(
+            stats.second,
+            LeafFeedbackStats {
+                fired: 1,
+                not_fired: 1,
+                short_circuited: 1,
+            }
+        );
+        assert_eq!(stats.second.evaluated(), 2);
+    }
+}
@@ -9,6 +9,7 @@ use core::{
     ops::{BitAnd, BitOr, Deref, DerefMut},
 };
 
+use hashbrown::HashMap;
 #[rustversion::nightly]
 use libafl_bolts::AsSlice;
 use libafl_bolts::{
@@ -19,7 +20,7 @@ use num_traits::PrimInt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 #[cfg(feature = "track_hit_feedbacks")]
-use crate::feedbacks::premature_last_result_err;
+use crate::feedbacks::{premature_last_result_err, SaveReasonsMetadata};
 use crate::{
     corpus::Testcase,
     events::{Event, EventFirer},
@@ -294,6 +295,224 @@ impl MapNoveltiesMetadata {
     }
 }
 
+/// Static weights for the entries of a map, used by a [`MapFeedback`].
+///
+/// The weights can come from a CFG analysis, security annotations, or a user-supplied file.
+/// Novelties in entries with a weight of `0` are ignored, while a single novelty in an entry
+/// with a weight of at least [`MapEdgeWeights::threshold`] is always interesting.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapEdgeWeights {
+    weights: Vec<u32>,
+    default_weight: u32,
+    threshold: u64,
+}
+
+impl MapEdgeWeights {
+    /// Creates new [`MapEdgeWeights`], where each entry has the `default_weight`.
+    /// A run is interesting if the weights of its novel entries add up to `threshold` (at least `1`).
+    #[must_use]
+    pub fn new(default_weight: u32, threshold: u64) -> Self {
+        Self {
+            weights: Vec::new(),
+            default_weight,
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// Creates new [`MapEdgeWeights`] from `(key, weight)` pairs.
+    ///
+    /// The `key_to_index` function translates each key, for example the PC of an edge,
+    /// to its index in the map. Keys it returns `None` for are skipped.
+    pub fn from_keyed<K, F>(
+        entries: impl IntoIterator<Item = (K, u32)>,
+        mut key_to_index: F,
+        default_weight: u32,
+        threshold: u64,
+    ) -> Self
+    where
+        F: FnMut(K) -> Option<usize>,
+    {
+        let mut weights = Self::new(default_weight, threshold);
+        for (key, weight) in entries {
+            if let Some(idx) = key_to_index(key) {
+                weights.set_weight(idx, weight);
+            }
+        }
+        weights
+    }
+
+    /// Loads [`MapEdgeWeights`] from a file.
+    ///
+    /// Each line contains a key and a weight, separated by whitespace. Keys may be decimal or
+    /// `0x`-prefixed hexadecimal, lines starting with `#` are ignored.
+    /// The `key_to_index` function translates each key to its index in the map, see [`Self::from_keyed`].
+    #[cfg(feature = "std")]
+    pub fn from_file<P, F>(
+        path: P,
+        key_to_index: F,
+        default_weight: u32,
+        threshold: u64,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<std::path::Path>,
+        F: FnMut(u64) -> Option<usize>,
+    {
+        let content = std::fs::read_to_string(path)?;
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (Some(key), Some(weight), None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(Error::illegal_argument(format!(
+                    "Invalid edge weight line: {line}"
+                )));
+            };
+            let key = match key.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => key.parse(),
+            }
+            .map_err(|_| Error::illegal_argument(format!("Invalid edge weight key: {key}")))?;
+            let weight = weight
+                .parse()
+                .map_err(|_| Error::illegal_argument(format!("Invalid edge weight: {weight}")))?;
+            entries.push((key, weight));
+        }
+        Ok(Self::from_keyed(
+            entries,
+            key_to_index,
+            default_weight,
+            threshold,
+        ))
+    }
+
+    /// Sets the weight of the entry at `idx`
+    pub fn set_weight(&mut self, idx: usize, weight: u32) {
+        if self.weights.len() <= idx {
+            self.weights.resize(idx + 1, self.default_weight);
+        }
+        self.weights[idx] = weight;
+    }
+
+    /// The weight of the entry at `idx`
+    #[must_use]
+    #[inline]
+    pub fn weight(&self, idx: usize) -> u32 {
+        self.weights
+            .get(idx)
+            .copied()
+            .unwrap_or(self.default_weight)
+    }
+
+    /// The sum of weights of novel entries needed for a run to be interesting
+    #[must_use]
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+}
+
+/// Periodic decay ("forgetting") of the history map of a [`MapFeedback`].
+///
+/// Every `interval` evaluations, one of `slices` equally-sized parts of the history map is reset to
+/// the initial value of the map, round-robin. This re-opens saturated coverage for exploration in
+/// very long campaigns. With a single slice, the whole history map is reset at once.
+///
+/// To not re-add inputs with the exact same coverage as existing corpus entries, the feedback keeps
+/// the coverage fingerprints of the inputs it added to the corpus in the [`MapDecayMetadata`].
+/// At most `max_fingerprints` are kept: each decay step forgets the fingerprints that were not added
+/// or matched for the longest time.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NoveltyDecay {
+    /// The amount of evaluations between two decay steps
+    pub interval: u64,
+    /// The amount of slices the history map is split into, one slice decays per step
+    pub slices: usize,
+    /// The maximum amount of coverage fingerprints kept after a decay step
+    #[serde(default = "default_max_fingerprints")]
+    pub max_fingerprints: usize,
+}
+
+/// The default maximum amount of coverage fingerprints kept by a [`NoveltyDecay`]
+pub const DEFAULT_MAX_DECAY_FINGERPRINTS: usize = 1 << 16;
+
+fn default_max_fingerprints() -> usize {
+    DEFAULT_MAX_DECAY_FINGERPRINTS
+}
+
+impl NoveltyDecay {
+    /// Creates a new [`NoveltyDecay`], resetting one of `slices` parts of the history map every
+    /// `interval` evaluations
+    #[must_use]
+    pub fn new(interval: u64, slices: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            slices: slices.max(1),
+            max_fingerprints: DEFAULT_MAX_DECAY_FINGERPRINTS,
+        }
+    }
+
+    /// Sets the maximum amount of coverage fingerprints kept after a decay step,
+    /// [`DEFAULT_MAX_DECAY_FINGERPRINTS`] by default
+    #[must_use]
+    pub fn with_max_fingerprints(mut self, max_fingerprints: usize) -> Self {
+        self.max_fingerprints = max_fingerprints;
+        self
+    }
+}
+
+/// The decay state of a [`MapFeedback`] with [`NoveltyDecay`]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)] // for SerdeAny
+pub struct MapDecayMetadata {
+    /// Evaluations since the last decay step
+    pub evaluations: u64,
+    /// The slice of the history map that decays next
+    pub next_slice: usize,
+    /// The amount of decay steps so far
+    pub decay_steps: u64,
+    /// The coverage fingerprints of inputs added to the corpus by this feedback,
+    /// with the decay step they were last added or matched in
+    pub fingerprints: HashMap<u64, u64>,
+}
+
+libafl_bolts::impl_serdeany!(MapDecayMetadata);
+
+impl MapDecayMetadata {
+    /// Records the fingerprint of an input added to the corpus
+    fn add_fingerprint(&mut self, fingerprint: u64) {
+        self.fingerprints.insert(fingerprint, self.decay_steps);
+    }
+
+    /// Returns if the fingerprint is known, and marks it as recently used
+    fn match_fingerprint(&mut self, fingerprint: u64) -> bool {
+        match self.fingerprints.get_mut(&fingerprint) {
+            Some(step) => {
+                *step = self.decay_steps;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets the least recently used fingerprints, until at most `max` are left
+    fn purge_fingerprints(&mut self, max: usize) {
+        let excess = self.fingerprints.len().saturating_sub(max);
+        if excess == 0 {
+            return;
+        }
+        let mut by_age: Vec<(u64, u64)> = self
+            .fingerprints
+            .iter()
+            .map(|(fingerprint, step)| (*step, *fingerprint))
+            .collect();
+        by_age.select_nth_unstable(excess - 1);
+        for (_, fingerprint) in &by_age[..excess] {
+            self.fingerprints.remove(fingerprint);
+        }
+    }
+}
+
 /// The state of [`MapFeedback`]
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
 #[allow(clippy::unsafe_derive_deserialize)] // for SerdeAny
@@ -359,6 +578,21 @@ where
     }
 }
 
+impl<T> MapFeedbackMetadata<T>
+where
+    T: Clone,
+{
+    /// Grow the history map to hold at least `len` entries, for targets whose map grows at runtime.
+    ///
+    /// New entries are set to `initial`. The history map never shrinks, so no coverage is lost if
+    /// the observed map temporarily reports fewer entries.
+    pub fn grow_history_map(&mut self, len: usize, initial: T) {
+        if self.history_map.len() < len {
+            self.history_map.resize(len, initial);
+        }
+    }
+}
+
 /// The most common AFL-like feedback type
 #[derive(Clone, Debug)]
 pub struct MapFeedback<C, N, O, R> {
@@ -370,6 +604,10 @@ pub struct MapFeedback<C, N, O, R> {
     map_ref: Handle<C>,
     /// Name of the feedback as shown in the `UserStats`
     stats_name: Cow<'static, str>,
+    /// Optional static weights of the map entries
+    edge_weights: Option<MapEdgeWeights>,
+    /// Optional periodic decay of the history map
+    novelty_decay: Option<NoveltyDecay>,
     // The previous run's result of [`Self::is_interesting`]
     #[cfg(feature = "track_hit_feedbacks")]
     last_result: Option<bool>,
@@ -388,6 +626,9 @@ where
         // Initialize `MapFeedbackMetadata` with an empty vector and add it to the state.
         // The `MapFeedbackMetadata` would be resized on-demand in `is_interesting`
         state.add_named_metadata(&self.name, MapFeedbackMetadata::<O::Entry>::default());
+        if self.novelty_decay.is_some() {
+            state.add_named_metadata(&self.name, MapDecayMetadata::default());
+        }
         Ok(())
     }
 }
@@ -451,19 +692,24 @@ where
         testcase: &mut Testcase<I>,
     ) -> Result<(), Error> {
         if let Some(novelties) = self.novelties.as_mut().map(core::mem::take) {
+            #[cfg(feature = "track_hit_feedbacks")]
+            SaveReasonsMetadata::add_novelties(testcase, &self.name, &novelties);
             let meta = MapNoveltiesMetadata::new(novelties);
             testcase.add_metadata(meta);
         }
         let observer = observers.get(&self.map_ref).unwrap().as_ref();
+        if self.novelty_decay.is_some() {
+            let fingerprint = observer.hash_simple();
+            state
+                .named_metadata_mut::<MapDecayMetadata>(&self.name)?
+                .add_fingerprint(fingerprint);
+        }
         let initial = observer.initial();
         let map_state = state
             .named_metadata_map_mut()
             .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.name)
             .unwrap();
-        let len = observer.len();
-        if map_state.history_map.len() < len {
-            map_state.history_map.resize(len, observer.initial());
-        }
+        map_state.grow_history_map(observer.len(), observer.initial());
 
         let history_map = &mut map_state.history_map;
         if C::INDICES {
@@ -553,7 +799,15 @@ where
         observers: &OT,
         _exit_kind: &ExitKind,
     ) -> Result<bool, Error> {
-        Ok(self.is_interesting_u8_simd_optimized(state, observers))
+        if self.edge_weights.is_none() && self.novelty_decay.is_none() {
+            return Ok(self.is_interesting_u8_simd_optimized(state, observers));
+        }
+        let res = self.is_interesting_default(state, observers);
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
     }
 }
 
@@ -585,6 +839,8 @@ where
             name: map_observer.name().clone(),
             map_ref: map_observer.handle(),
             stats_name: create_stats_name(map_observer.name()),
+            edge_weights: None,
+            novelty_decay: None,
             #[cfg(feature = "track_hit_feedbacks")]
             last_result: None,
             phantom: PhantomData,
@@ -602,6 +858,8 @@ where
             map_ref: map_observer.handle(),
             stats_name: create_stats_name(&name),
             name,
+            edge_weights: None,
+            novelty_decay: None,
             #[cfg(feature = "track_hit_feedbacks")]
             last_result: None,
             phantom: PhantomData,
@@ -609,6 +867,37 @@ where
     }
 }
 
+impl<C, N, O, R> MapFeedback<C, N, O, R> {
+    /// Attach static [`MapEdgeWeights`] to this feedback.
+    ///
+    /// Novelties in entries with a weight of `0` are ignored, and a run is only interesting if the
+    /// weights of all its novel entries add up to at least [`MapEdgeWeights::threshold`].
+    #[must_use]
+    pub fn with_edge_weights(mut self, edge_weights: MapEdgeWeights) -> Self {
+        self.edge_weights = Some(edge_weights);
+        self
+    }
+
+    /// The static [`MapEdgeWeights`] of this feedback, if any
+    #[must_use]
+    pub fn edge_weights(&self) -> Option<&MapEdgeWeights> {
+        self.edge_weights.as_ref()
+    }
+
+    /// Periodically forget parts of the history map, see [`NoveltyDecay`]
+    #[must_use]
+    pub fn with_novelty_decay(mut self, novelty_decay: NoveltyDecay) -> Self {
+        self.novelty_decay = Some(novelty_decay);
+        self
+    }
+
+    /// The [`NoveltyDecay`] of this feedback, if any
+    #[must_use]
+    pub fn novelty_decay(&self) -> Option<&NoveltyDecay> {
+        self.novelty_decay.as_ref()
+    }
+}
+
 /// Specialize for the common coverage map size, maximization of u8s
 #[rustversion::nightly]
 impl<C, O> MapFeedback<C, DifferentIsNovel, O, MaxReducer>
@@ -635,10 +924,7 @@ where
             .get_mut::<MapFeedbackMetadata<u8>>(&self.name)
             .unwrap();
         let size = observer.usable_count();
-        let len = observer.len();
-        if map_state.history_map.len() < len {
-            map_state.history_map.resize(len, u8::default());
-        }
+        map_state.grow_history_map(observer.len(), u8::default());
 
         let map = observer.as_slice();
         debug_assert!(map.len() >= size);
@@ -751,18 +1037,21 @@ where
         // TODO Replace with match_name_type when stable
         let observer = observers.get(&self.map_ref).unwrap().as_ref();
 
+        if let Some(novelty_decay) = self.novelty_decay {
+            Self::decay_history(&self.name, novelty_decay, state, observer.initial());
+        }
+
         let map_state = state
             .named_metadata_map_mut()
             .get_mut::<MapFeedbackMetadata<O::Entry>>(&self.name)
             .unwrap();
-        let len = observer.len();
-        if map_state.history_map.len() < len {
-            map_state.history_map.resize(len, observer.initial());
-        }
+        map_state.grow_history_map(observer.len(), observer.initial());
 
         let history_map = map_state.history_map.as_slice();
 
         let initial = observer.initial();
+        let edge_weights = self.edge_weights.as_ref();
+        let mut novel_weight = 0;
 
         if let Some(novelties) = self.novelties.as_mut() {
             novelties.clear();
@@ -775,10 +1064,20 @@ where
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
                 if N::is_novel(existing, reduced) {
+                    if let Some(edge_weights) = edge_weights {
+                        let weight = edge_weights.weight(i);
+                        if weight == 0 {
+                            continue;
+                        }
+                        novel_weight += u64::from(weight);
+                    }
                     interesting = true;
                     novelties.push(i);
                 }
             }
+            if let Some(edge_weights) = edge_weights {
+                interesting = novel_weight >= edge_weights.threshold();
+            }
         } else {
             for (i, item) in observer
                 .as_iter()
@@ -789,19 +1088,83 @@ where
                 let existing = unsafe { *history_map.get_unchecked(i) };
                 let reduced = R::reduce(existing, item);
                 if N::is_novel(existing, reduced) {
+                    if let Some(edge_weights) = edge_weights {
+                        novel_weight += u64::from(edge_weights.weight(i));
+                        if novel_weight < edge_weights.threshold() {
+                            continue;
+                        }
+                    }
                     interesting = true;
                     break;
                 }
             }
         }
 
+        if interesting && self.novelty_decay.is_some() {
+            // Do not re-add inputs with the same coverage as an existing corpus entry, after parts of
+            // the history map were forgotten
+            let decay_state = state
+                .named_metadata_map_mut()
+                .get_mut::<MapDecayMetadata>(&self.name)
+                .unwrap();
+            interesting = !decay_state.match_fingerprint(observer.hash_simple());
+        }
+
         interesting
     }
+
+    /// Counts one evaluation and, if the interval has passed, resets the next slice of the history map
+    fn decay_history<S>(name: &str, novelty_decay: NoveltyDecay, state: &mut S, initial: O::Entry)
+    where
+        S: HasNamedMetadata,
+    {
+        let decay_state = state
+            .named_metadata_map_mut()
+            .get_mut::<MapDecayMetadata>(name)
+            .unwrap();
+        decay_state.evaluations += 1;
+        if decay_state.evaluations < novelty_decay.interval {
+            return;
+        }
+        let slices = novelty_decay.slices.max(1);
+        let slice = decay_state.next_slice % slices;
+        decay_state.evaluations = 0;
+        decay_state.decay_steps += 1;
+        decay_state.next_slice = (slice + 1) % slices;
+        decay_state.purge_fingerprints(novelty_decay.max_fingerprints);
+
+        let map_state = state
+            .named_metadata_map_mut()
+            .get_mut::<MapFeedbackMetadata<O::Entry>>(name)
+            .unwrap();
+        let len = map_state.history_map.len();
+        let slice_len = len.div_ceil(slices);
+        let start = (slice * slice_len).min(len);
+        let end = (start + slice_len).min(len);
+        for entry in &mut map_state.history_map[start..end] {
+            if *entry != initial {
+                *entry = initial;
+                map_state.num_covered_map_indexes -= 1;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::feedbacks::{AllIsNovel, IsNovel, NextPow2IsNovel};
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use crate::{
+        corpus::{InMemoryCorpus, Testcase},
+        executors::ExitKind,
+        feedbacks::{
+            AllIsNovel, ConstFeedback, Feedback, IsNovel, MapEdgeWeights, MaxMapFeedback,
+            NextPow2IsNovel, NoveltyDecay, StateInitializer,
+        },
+        inputs::BytesInput,
+        observers::{MapObserver, StdMapObserver},
+        state::StdState,
+    };
 
     #[test]
     fn test_map_is_novel() {
@@ -822,4 +1185,64 @@ mod tests {
         assert!(NextPow2IsNovel::is_novel(254_u8, 255));
         assert!(!NextPow2IsNovel::is_novel(255_u8, 255));
     }
+
+    #[test]
+    fn test_map_edge_weights() {
+        let weights = MapEdgeWeights::from_keyed(
+            [(0x1000_u64, 0), (0x1004, 10), (0xdead, 5)],
+            |pc| (pc < 0x2000).then(|| ((pc - 0x1000) / 4) as usize),
+            1,
+            0,
+        );
+        assert_eq!(weights.threshold(), 1);
+        assert_eq!(weights.weight(0), 0);
+        assert_eq!(weights.weight(1), 10);
+        assert_eq!(weights.weight(2), 1);
+        assert_eq!(weights.weight(1000), 1);
+    }
+
+    #[test]
+    fn test_novelty_decay() {
+        let observer = StdMapObserver::owned("map", vec![0_u8; 4]);
+        let mut feedback = MaxMapFeedback::new(&observer)
+            .with_novelty_decay(NoveltyDecay::new(1, 1).with_max_fingerprints(1));
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        feedback.init_state(&mut state).unwrap();
+        let input = BytesInput::new(vec![]);
+
+        let mut observers = tuple_list!(observer);
+        let mut evaluate = |observers: &(StdMapObserver<'_, u8, false>, ())| {
+            let interesting = feedback
+                .is_interesting(&mut state, &mut (), &input, observers, &ExitKind::Ok)
+                .unwrap();
+            if interesting {
+                let mut testcase = Testcase::new(input.clone());
+                feedback
+                    .append_metadata(&mut state, &mut (), observers, &mut testcase)
+                    .unwrap();
+            }
+            interesting
+        };
+
+        observers.0.set(0, 1);
+        assert!(evaluate(&observers));
+        // The history map was forgotten, but the coverage is already in the corpus
+        assert!(!evaluate(&observers));
+
+        observers.0.reset_map().unwrap();
+        observers.0.set(1, 1);
+        assert!(evaluate(&observers));
+
+        // Only the most recent fingerprint is kept, the first coverage is new again
+        observers.0.reset_map().unwrap();
+        observers.0.set(0, 1);
+        assert!(evaluate(&observers));
+    }
 }
@@ -9,14 +9,24 @@ use alloc::borrow::Cow;
 use alloc::vec::Vec;
 use core::{fmt::Debug, marker::PhantomData};
 
+#[cfg(feature = "track_hit_feedbacks")]
+pub use attribution::{SaveReason, SaveReasonsMetadata};
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
-pub use differential::DiffFeedback;
+#[cfg(all(feature = "std", unix))]
+pub use coredump::{CoreDumpFeedback, CoreDumpMetadata};
+#[cfg(all(feature = "std", unix))]
+pub use crash_context::CrashContextFeedback;
+#[cfg(feature = "std")]
+pub use crash_state::{CrashStateFeedback, CrashStateMetadata};
+pub use differential::{DiffExecutorFeedback, DiffFeedback};
+pub use fastest_path::{FastestPathFeedback, FastestPathMetadata};
 use libafl_bolts::{
     tuples::{Handle, Handled, MatchName, MatchNameRef},
     Named,
 };
 pub use list::*;
+pub use logic_stats::*;
 pub use map::*;
 #[cfg(feature = "nautilus")]
 pub use nautilus::*;
@@ -25,20 +35,34 @@ pub use new_hash_feedback::NewHashFeedback;
 #[cfg(feature = "std")]
 pub use new_hash_feedback::NewHashFeedbackMetadata;
 use serde::{Deserialize, Serialize};
+pub use syscalls::{
+    SyscallNovelty, SyscallSetFeedback, SyscallSetMetadata, SyscallViolationFeedback,
+    SyscallViolationsMetadata,
+};
 
 use crate::{corpus::Testcase, executors::ExitKind, observers::TimeObserver, Error};
 
+#[cfg(feature = "track_hit_feedbacks")]
+pub mod attribution;
 #[cfg(feature = "std")]
 pub mod capture_feedback;
 
 #[cfg(feature = "std")]
 pub mod concolic;
+#[cfg(all(feature = "std", unix))]
+pub mod coredump;
+#[cfg(all(feature = "std", unix))]
+pub mod crash_context;
+#[cfg(feature = "std")]
+pub mod crash_state;
 #[cfg(feature = "std")]
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
+pub mod fastest_path;
 /// The module for list feedback
 pub mod list;
+pub mod logic_stats;
 pub mod map;
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
@@ -46,6 +70,7 @@ pub mod nautilus;
 pub mod new_hash_feedback;
 #[cfg(feature = "std")]
 pub mod stdio;
+pub mod syscalls;
 pub mod transferred;
 
 #[cfg(feature = "std")]
@@ -811,6 +836,21 @@ impl ExitKindLogic for TimeoutLogic {
     }
 }
 
+/// Name used by `OomFeedback`
+pub const OOM_FEEDBACK_NAME: &str = "OomFeedback";
+
+/// Logic which finds all [`ExitKind::Oom`] exits interesting
+#[derive(Debug, Copy, Clone)]
+pub struct OomLogic;
+
+impl ExitKindLogic for OomLogic {
+    const NAME: Cow<'static, str> = Cow::Borrowed(OOM_FEEDBACK_NAME);
+
+    fn check_exit_kind(kind: &ExitKind) -> Result<bool, Error> {
+        Ok(matches!(kind, ExitKind::Oom))
+    }
+}
+
 /// Logic which finds all [`ExitKind::Diff`] exits interesting
 #[derive(Debug, Copy, Clone)]
 pub struct GenericDiffLogic;
@@ -908,6 +948,8 @@ where
 pub type CrashFeedback = ExitKindFeedback<CrashLogic>;
 /// A [`TimeoutFeedback`] reduces the timeout value of a run.
 pub type TimeoutFeedback = ExitKindFeedback<TimeoutLogic>;
+/// An [`OomFeedback`] reports as interesting if the target ran out of memory.
+pub type OomFeedback = ExitKindFeedback<OomLogic>;
 /// A [`DiffExitKindFeedback`] checks if there is a difference in the [`ExitKind`]s in a [`crate::executors::DiffExecutor`].
 pub type DiffExitKindFeedback = ExitKindFeedback<GenericDiffLogic>;
 
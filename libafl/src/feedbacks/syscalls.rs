@@ -0,0 +1,323 @@
+//! The [`SyscallSetFeedback`] considers runs interesting that issue system calls never seen before.
+//!
+//! This catches behavioral novelty of I/O-heavy targets that edge coverage misses,
+//! for example an existing code path now failing with a different error number.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::SaveReasonsMetadata;
+use crate::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::{SyscallObserver, SyscallRecord},
+    Error, HasMetadata, HasNamedMetadata,
+};
+
+/// What makes a system call novel for a [`SyscallSetFeedback`]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyscallNovelty {
+    /// Only new system call numbers are novel
+    Number,
+    /// New pairs of system call number and error number are novel
+    #[default]
+    NumberAndErrno,
+}
+
+/// The system calls seen so far by a [`SyscallSetFeedback`]
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct SyscallSetMetadata {
+    /// The set of seen system calls. With [`SyscallNovelty::Number`], all error numbers are `0`.
+    pub seen: HashSet<SyscallRecord>,
+}
+
+libafl_bolts::impl_serdeany!(SyscallSetMetadata);
+
+/// The novel system calls that made a testcase interesting
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct SyscallNoveltiesMetadata {
+    /// The system calls not seen before this testcase
+    pub novelties: Vec<SyscallRecord>,
+}
+
+libafl_bolts::impl_serdeany!(SyscallNoveltiesMetadata);
+
+/// A [`SyscallSetFeedback`] considers a run interesting if it issues a system call,
+/// or a (system call, error number) pair, that was never seen before.
+#[derive(Clone, Debug)]
+pub struct SyscallSetFeedback {
+    observer_handle: Handle<SyscallObserver>,
+    novelty: SyscallNovelty,
+    novelties: Vec<SyscallRecord>,
+}
+
+impl SyscallSetFeedback {
+    /// Creates a new [`SyscallSetFeedback`], treating new (system call, error number) pairs as novel
+    #[must_use]
+    pub fn new(observer: &SyscallObserver) -> Self {
+        Self::with_novelty(observer, SyscallNovelty::default())
+    }
+
+    /// Creates a new [`SyscallSetFeedback`] with the given [`SyscallNovelty`]
+    #[must_use]
+    pub fn with_novelty(observer: &SyscallObserver, novelty: SyscallNovelty) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            novelty,
+            novelties: Vec::new(),
+        }
+    }
+
+    fn key(&self, record: &SyscallRecord) -> SyscallRecord {
+        match self.novelty {
+            SyscallNovelty::Number => SyscallRecord::new(record.nr, 0),
+            SyscallNovelty::NumberAndErrno => *record,
+        }
+    }
+}
+
+impl<S> StateInitializer<S> for SyscallSetFeedback
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(self.name(), SyscallSetMetadata::default());
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SyscallSetFeedback
+where
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .expect("A SyscallSetFeedback needs a SyscallObserver");
+        self.novelties.clear();
+        let seen = &state
+            .named_metadata::<SyscallSetMetadata>(self.name())?
+            .seen;
+        for record in observer.syscalls() {
+            let key = self.key(record);
+            if !seen.contains(&key) && !self.novelties.contains(&key) {
+                self.novelties.push(key);
+            }
+        }
+        Ok(!self.novelties.is_empty())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(!self.novelties.is_empty())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if self.novelties.is_empty() {
+            return Ok(());
+        }
+        let novelties = core::mem::take(&mut self.novelties);
+        state
+            .named_metadata_mut::<SyscallSetMetadata>(self.name())?
+            .seen
+            .extend(novelties.iter().copied());
+        #[cfg(feature = "track_hit_feedbacks")]
+        SaveReasonsMetadata::add_details(testcase, self.name(), format!("{novelties:?}"));
+        testcase.add_metadata(SyscallNoveltiesMetadata { novelties });
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.novelties.clear();
+        Ok(())
+    }
+}
+
+impl Named for SyscallSetFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl HasObserverHandle for SyscallSetFeedback {
+    type Observer = SyscallObserver;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<SyscallObserver> {
+        &self.observer_handle
+    }
+}
+
+/// The system calls of a testcase that violated the policy of the tracer
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct SyscallViolationsMetadata {
+    /// The violating system calls, in order
+    pub violations: Vec<SyscallRecord>,
+}
+
+libafl_bolts::impl_serdeany!(SyscallViolationsMetadata);
+
+/// A [`SyscallViolationFeedback`] considers a run interesting if it issued a system call that
+/// violated the policy of the tracer, e.g., of a [`crate::executors::PtraceSyscallExecutor`].
+/// Use it as objective.
+#[derive(Clone, Debug)]
+pub struct SyscallViolationFeedback {
+    observer_handle: Handle<SyscallObserver>,
+    violated: bool,
+}
+
+impl SyscallViolationFeedback {
+    /// Creates a new [`SyscallViolationFeedback`]
+    #[must_use]
+    pub fn new(observer: &SyscallObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            violated: false,
+        }
+    }
+}
+
+impl<S> StateInitializer<S> for SyscallViolationFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for SyscallViolationFeedback
+where
+    OT: MatchName,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .expect("A SyscallViolationFeedback needs a SyscallObserver");
+        self.violated = !observer.violations().is_empty();
+        Ok(self.violated)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(self.violated)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .expect("A SyscallViolationFeedback needs a SyscallObserver");
+        if !observer.violations().is_empty() {
+            testcase.add_metadata(SyscallViolationsMetadata {
+                violations: observer.violations().to_vec(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Named for SyscallViolationFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SyscallViolationFeedback");
+        &NAME
+    }
+}
+
+impl HasObserverHandle for SyscallViolationFeedback {
+    type Observer = SyscallObserver;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<SyscallObserver> {
+        &self.observer_handle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::SyscallSetFeedback;
+    use crate::{
+        corpus::{InMemoryCorpus, Testcase},
+        executors::ExitKind,
+        feedbacks::{ConstFeedback, Feedback, StateInitializer},
+        inputs::BytesInput,
+        observers::{SyscallObserver, SyscallRecord},
+        state::StdState,
+    };
+
+    #[test]
+    fn test_syscall_set_feedback() {
+        let mut observer = SyscallObserver::new("syscalls");
+        let mut feedback = SyscallSetFeedback::new(&observer);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut ConstFeedback::new(false),
+            &mut ConstFeedback::new(false),
+        )
+        .unwrap();
+        feedback.init_state(&mut state).unwrap();
+        let input = BytesInput::new(vec![]);
+
+        observer.fill_external(&[
+            SyscallRecord::new(0, 0),
+            SyscallRecord::from_linux_return(2, -2),
+        ]);
+        assert_eq!(observer.syscalls()[1].errno, 2);
+        let observers = tuple_list!(observer);
+        assert!(feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+        let mut testcase = Testcase::new(input.clone());
+        feedback
+            .append_metadata(&mut state, &mut (), &observers, &mut testcase)
+            .unwrap();
+        assert!(!feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+
+        let (mut observer, ()) = observers;
+        observer.fill_external(&[SyscallRecord::from_linux_return(2, 3)]);
+        let observers = tuple_list!(observer);
+        assert!(feedback
+            .is_interesting(&mut state, &mut (), &input, &observers, &ExitKind::Ok)
+            .unwrap());
+    }
+}
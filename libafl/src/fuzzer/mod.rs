@@ -4,8 +4,10 @@ use alloc::{string::ToString, vec::Vec};
 use core::{fmt::Debug, marker::PhantomData, time::Duration};
 
 use libafl_bolts::current_time;
-use serde::{de::DeserializeOwned, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::SaveReasonsMetadata;
 #[cfg(feature = "introspection")]
 use crate::monitors::PerfFeature;
 use crate::{
@@ -291,7 +293,7 @@ where
 }
 
 /// The corpus this input should be added to
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ExecuteInputResult {
     /// No special input
     None,
@@ -493,6 +495,7 @@ where
                             input,
                             observers_buf,
                             exit_kind: *exit_kind,
+                            exec_res: *exec_res,
                             corpus_size: state.corpus().count(),
                             client_config: manager.configuration(),
                             time: current_time(),
@@ -549,6 +552,8 @@ where
                     .append_hit_feedbacks(testcase.hit_feedbacks_mut())?;
                 self.feedback_mut()
                     .append_metadata(state, manager, observers, &mut testcase)?;
+                #[cfg(feature = "track_hit_feedbacks")]
+                SaveReasonsMetadata::record_hits(&mut testcase);
                 let id = state.corpus_mut().add(testcase)?;
                 self.scheduler_mut().on_add(state, id)?;
 
@@ -569,6 +574,8 @@ where
                     .append_hit_feedbacks(testcase.hit_objectives_mut())?;
                 self.objective_mut()
                     .append_metadata(state, manager, observers, &mut testcase)?;
+                #[cfg(feature = "track_hit_feedbacks")]
+                SaveReasonsMetadata::record_hits(&mut testcase);
                 state.solutions_mut().add(testcase)?;
 
                 Ok(None)
@@ -681,6 +688,8 @@ where
                 .append_hit_feedbacks(testcase.hit_objectives_mut())?;
             self.objective_mut()
                 .append_metadata(state, manager, &*observers, &mut testcase)?;
+            #[cfg(feature = "track_hit_feedbacks")]
+            SaveReasonsMetadata::record_hits(&mut testcase);
             let id = state.solutions_mut().add(testcase)?;
 
             manager.fire(
@@ -699,12 +708,12 @@ where
         // several is_interesting implementations collect some data about the run, later used in
         // append_metadata; we *must* invoke is_interesting here to collect it
         #[cfg(not(feature = "introspection"))]
-        let _corpus_worthy =
+        let corpus_worthy =
             self.feedback_mut()
                 .is_interesting(state, manager, &input, &*observers, &exit_kind)?;
 
         #[cfg(feature = "introspection")]
-        let _corpus_worthy = self.feedback_mut().is_interesting_introspection(
+        let corpus_worthy = self.feedback_mut().is_interesting_introspection(
             state,
             manager,
             &input,
@@ -718,6 +727,8 @@ where
         // Add the input to the main corpus
         self.feedback_mut()
             .append_metadata(state, manager, &*observers, &mut testcase)?;
+        #[cfg(feature = "track_hit_feedbacks")]
+        SaveReasonsMetadata::record_hits(&mut testcase);
         let id = state.corpus_mut().add(testcase)?;
         self.scheduler_mut().on_add(state, id)?;
 
@@ -732,6 +743,11 @@ where
                 input,
                 observers_buf,
                 exit_kind,
+                exec_res: if corpus_worthy {
+                    ExecuteInputResult::Corpus
+                } else {
+                    ExecuteInputResult::None
+                },
                 corpus_size: state.corpus().count(),
                 client_config: manager.configuration(),
                 time: current_time(),
@@ -82,6 +82,9 @@ pub mod schedulers;
 pub mod stages;
 pub mod state;
 
+#[cfg(all(feature = "python", unix))]
+pub mod pybind;
+
 pub use fuzzer::*;
 pub use libafl_bolts::{nonzero, Error};
 
@@ -0,0 +1,396 @@
+//! A monitor that wraps a base monitor and pushes the stats to a `StatsD` or `InfluxDB` server over UDP.
+//!
+//! Useful for fleets of fuzzers where scraping each node, as with the `PrometheusMonitor`, is not feasible.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write, time::Duration};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use libafl_bolts::{current_time, ClientId, Error};
+
+use crate::monitors::{ClientStats, Monitor, NopMonitor, UserStatsValue};
+
+/// The maximum size of a datagram, to stay below the usual MTU
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// The format in which the [`StatsdMonitor`] pushes the stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsdProtocol {
+    /// `StatsD` gauges, one per stat, such as `libafl.global.corpus:42|g`
+    Statsd,
+    /// `InfluxDB` line protocol, one line per client, such as `libafl,client=global corpus=42i 1700000000000000000`
+    InfluxLine,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MetricValue {
+    Int(u64),
+    Float(f64),
+}
+
+impl MetricValue {
+    /// Neither protocol accepts `NaN` or infinite values
+    fn is_finite(self) -> bool {
+        match self {
+            MetricValue::Int(_) => true,
+            MetricValue::Float(f) => f.is_finite(),
+        }
+    }
+}
+
+/// Wraps a base monitor and periodically pushes the global and per-client stats over UDP,
+/// in the [`StatsdProtocol`] of choice.
+#[derive(Debug)]
+pub struct StatsdMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    socket: UdpSocket,
+    protocol: StatsdProtocol,
+    prefix: String,
+    last_update: Duration,
+    update_interval: Duration,
+}
+
+impl<M> Monitor for StatsdMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    /// Set creation time
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        let cur_time = current_time();
+
+        if cur_time - self.last_update >= self.update_interval {
+            self.last_update = cur_time;
+
+            for datagram in self.datagrams(cur_time) {
+                if let Err(e) = self.socket.send(datagram.as_bytes()) {
+                    log::warn!("Failed to push the stats: {e}");
+                    break;
+                }
+            }
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> StatsdMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`StatsdMonitor`], pushing the stats to `addr` every 10 seconds
+    pub fn new<A>(addr: A, protocol: StatsdProtocol, base: M) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::with_update_interval(addr, protocol, base, Duration::from_secs(10))
+    }
+
+    /// Create a new [`StatsdMonitor`] with a custom update interval
+    pub fn with_update_interval<A>(
+        addr: A,
+        protocol: StatsdProtocol,
+        base: M,
+        update_interval: Duration,
+    ) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::illegal_argument("The stats server address did not resolve"))?;
+        let socket = match addr {
+            SocketAddr::V4(_) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            SocketAddr::V6(_) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        };
+        socket.connect(addr)?;
+
+        Ok(Self {
+            base,
+            socket,
+            protocol,
+            prefix: String::from("libafl"),
+            last_update: current_time() - update_interval,
+            update_interval,
+        })
+    }
+
+    /// Set the prefix of all metric names, `libafl` by default
+    #[must_use]
+    pub fn prefix<P>(mut self, prefix: P) -> Self
+    where
+        P: Into<String>,
+    {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The stats of all clients, then of each client, by scope.
+    /// Non-finite values, and user stats without a valid name, are left out.
+    fn metrics(&mut self, cur_time: Duration) -> Vec<(String, Vec<(String, MetricValue)>)> {
+        let mut metrics = vec![(
+            String::from("global"),
+            vec![
+                (
+                    String::from("run_time"),
+                    MetricValue::Int((cur_time - self.start_time()).as_secs()),
+                ),
+                (
+                    String::from("clients"),
+                    MetricValue::Int(self.client_stats_count() as u64),
+                ),
+                (String::from("corpus"), MetricValue::Int(self.corpus_size())),
+                (
+                    String::from("objectives"),
+                    MetricValue::Int(self.objective_size()),
+                ),
+                (
+                    String::from("executions"),
+                    MetricValue::Int(self.total_execs()),
+                ),
+                (
+                    String::from("exec_sec"),
+                    MetricValue::Float(self.execs_per_sec()),
+                ),
+            ],
+        )];
+
+        for (i, client) in self.client_stats_mut().iter_mut().enumerate() {
+            if !client.enabled {
+                continue;
+            }
+            let mut client_metrics = vec![
+                (String::from("corpus"), MetricValue::Int(client.corpus_size)),
+                (
+                    String::from("objectives"),
+                    MetricValue::Int(client.objective_size),
+                ),
+                (
+                    String::from("executions"),
+                    MetricValue::Int(client.executions),
+                ),
+                (
+                    String::from("exec_sec"),
+                    MetricValue::Float(client.execs_per_sec(cur_time)),
+                ),
+            ];
+            for (key, val) in &client.user_monitor {
+                if let Some(value) = metric_value(val.value()) {
+                    let k: String = key
+                        .chars()
+                        .map(|c| if c.is_whitespace() { '_' } else { c })
+                        .filter(|c| c.is_alphanumeric() || *c == '_')
+                        .collect();
+                    if !k.is_empty() {
+                        client_metrics.push((k, value));
+                    }
+                }
+            }
+            metrics.push((format!("client_{i}"), client_metrics));
+        }
+
+        for (_, scope_metrics) in &mut metrics {
+            scope_metrics.retain(|(_, value)| value.is_finite());
+        }
+        metrics
+    }
+
+    /// Formats the stats in our protocol, packed into datagrams
+    fn datagrams(&mut self, cur_time: Duration) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (scope, metrics) in self.metrics(cur_time) {
+            match self.protocol {
+                StatsdProtocol::Statsd => {
+                    for (name, value) in metrics {
+                        let value = match value {
+                            MetricValue::Int(n) => n.to_string(),
+                            MetricValue::Float(f) => f.to_string(),
+                        };
+                        lines.push(format!("{}.{scope}.{name}:{value}|g", self.prefix));
+                    }
+                }
+                StatsdProtocol::InfluxLine if metrics.is_empty() => {}
+                StatsdProtocol::InfluxLine => {
+                    let mut line = format!("{},client={scope} ", self.prefix);
+                    for (i, (name, value)) in metrics.iter().enumerate() {
+                        if i > 0 {
+                            line.push(',');
+                        }
+                        match value {
+                            MetricValue::Int(n) => write!(line, "{name}={n}i"),
+                            MetricValue::Float(f) => write!(line, "{name}={f}"),
+                        }
+                        .unwrap();
+                    }
+                    write!(line, " {}", cur_time.as_nanos()).unwrap();
+                    lines.push(line);
+                }
+            }
+        }
+
+        let mut datagrams: Vec<String> = Vec::new();
+        for line in lines {
+            match datagrams.last_mut() {
+                Some(datagram) if datagram.len() + 1 + line.len() <= MAX_DATAGRAM_SIZE => {
+                    datagram.push('\n');
+                    datagram.push_str(&line);
+                }
+                _ => datagrams.push(line),
+            }
+        }
+        datagrams
+    }
+}
+
+impl StatsdMonitor<NopMonitor> {
+    /// Create a new [`StatsdMonitor`] without a base
+    pub fn nop<A>(addr: A, protocol: StatsdProtocol) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(addr, protocol, NopMonitor::new())
+    }
+}
+
+/// The value of a numeric user stat, as pushed to the server
+#[allow(clippy::cast_precision_loss)]
+fn metric_value(value: &UserStatsValue) -> Option<MetricValue> {
+    match value {
+        UserStatsValue::Number(n) => Some(MetricValue::Int(*n)),
+        UserStatsValue::Float(f) | UserStatsValue::Percent(f) => Some(MetricValue::Float(*f)),
+        UserStatsValue::Ratio(a, b) => {
+            if *b == 0 {
+                None
+            } else {
+                Some(MetricValue::Float(*a as f64 / *b as f64))
+            }
+        }
+        UserStatsValue::String(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, string::String, vec::Vec};
+    use core::time::Duration;
+
+    use libafl_bolts::ClientId;
+
+    use super::{StatsdMonitor, StatsdProtocol, MAX_DATAGRAM_SIZE};
+    use crate::monitors::{AggregatorOps, Monitor, NopMonitor, UserStats, UserStatsValue};
+
+    fn monitor(protocol: StatsdProtocol) -> StatsdMonitor<NopMonitor> {
+        let mut monitor = StatsdMonitor::nop("127.0.0.1:8125", protocol).unwrap();
+        monitor.client_stats_insert(ClientId(0));
+        let client = monitor.client_stats_mut_for(ClientId(0));
+        client.update_corpus_size(2);
+        client.update_objective_size(1);
+        for (name, value) in [
+            ("my stat", UserStatsValue::Number(3)),
+            ("ratio", UserStatsValue::Ratio(1, 4)),
+            ("nan", UserStatsValue::Float(f64::NAN)),
+            ("inf", UserStatsValue::Percent(f64::INFINITY)),
+            ("!?", UserStatsValue::Number(5)),
+        ] {
+            client.update_user_stats(Cow::from(name), UserStats::new(value, AggregatorOps::None));
+        }
+        monitor
+    }
+
+    fn lines(datagrams: &[String]) -> Vec<&str> {
+        datagrams.iter().flat_map(|d| d.lines()).collect()
+    }
+
+    #[test]
+    fn test_statsd_format() {
+        let mut monitor = monitor(StatsdProtocol::Statsd);
+        let cur_time = monitor.start_time() + Duration::from_secs(10);
+        let datagrams = monitor.datagrams(cur_time);
+        let lines = lines(&datagrams);
+
+        assert!(lines.contains(&"libafl.global.run_time:10|g"));
+        assert!(lines.contains(&"libafl.global.corpus:2|g"));
+        assert!(lines.contains(&"libafl.client_0.objectives:1|g"));
+        assert!(lines.contains(&"libafl.client_0.my_stat:3|g"));
+        assert!(lines.contains(&"libafl.client_0.ratio:0.25|g"));
+        // 6 global stats, 4 client stats, and the 2 valid user stats
+        assert_eq!(lines.len(), 12);
+        for line in lines {
+            assert!(!line.contains("..") && !line.contains(".:"), "{line}");
+            assert!(!line.contains("NaN") && !line.contains("inf"), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_influx_format() {
+        let mut monitor = monitor(StatsdProtocol::InfluxLine).prefix("fuzz");
+        let cur_time = monitor.start_time() + Duration::from_secs(10);
+        let datagrams = monitor.datagrams(cur_time);
+        let lines = lines(&datagrams);
+        assert_eq!(lines.len(), 2);
+
+        let timestamp = format!(" {}", cur_time.as_nanos());
+        assert!(lines[0].starts_with("fuzz,client=global run_time=10i,clients=1i,corpus=2i,"));
+        assert!(lines[1].starts_with("fuzz,client=client_0 corpus=2i,objectives=1i,"));
+        assert!(lines[1].contains(",my_stat=3i"));
+        assert!(lines[1].contains(",ratio=0.25"));
+        for line in lines {
+            assert!(line.ends_with(&timestamp), "{line}");
+            assert!(!line.contains(",=") && !line.contains(" ="), "{line}");
+            assert!(!line.contains("NaN") && !line.contains("inf"), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_datagram_packing() {
+        let mut monitor = monitor(StatsdProtocol::Statsd);
+        let client = monitor.client_stats_mut_for(ClientId(0));
+        for i in 0..200 {
+            client.update_user_stats(
+                Cow::from(format!("stat_{i}")),
+                UserStats::new(UserStatsValue::Number(i), AggregatorOps::None),
+            );
+        }
+        let cur_time = monitor.start_time() + Duration::from_secs(10);
+        let datagrams = monitor.datagrams(cur_time);
+
+        assert!(datagrams.len() > 1);
+        for datagram in &datagrams {
+            assert!(datagram.len() <= MAX_DATAGRAM_SIZE);
+        }
+        // All lines made it into a datagram, whole
+        let lines = lines(&datagrams);
+        assert_eq!(lines.len(), 12 + 200);
+        assert!(lines.iter().all(|line| line.ends_with("|g")));
+        assert!(lines.contains(&"libafl.client_0.stat_199:199|g"));
+    }
+}
@@ -206,6 +206,17 @@ impl Tokens {
         Ok(self)
     }
 
+    /// Merges these tokens into the [`Tokens`] metadata of the `state`, adding the metadata if it is missing.
+    ///
+    /// Use it at fuzzer startup to combine tokens from several sources, such as the dictionary written by the
+    /// `dict2file` pass of `libafl_cc`, loaded with [`Tokens::from_file`], and the [autotokens section](Self::from_mut_ptrs).
+    pub fn add_to_state<S>(self, state: &mut S)
+    where
+        S: HasMetadata,
+    {
+        *state.metadata_or_insert_with(Tokens::new) += self;
+    }
+
     /// Returns the amount of tokens in this Tokens instance
     #[inline]
     #[must_use]
@@ -2094,6 +2105,7 @@ mod tests {
 
     #[cfg(feature = "std")]
     use super::{AFLppRedQueen, Tokens};
+    use crate::{inputs::BytesInput, state::NopState, HasMetadata};
 
     #[cfg(feature = "std")]
     #[test]
@@ -2113,6 +2125,18 @@ token2="B"
         let _res = fs::remove_file("test.tkns");
     }
 
+    #[test]
+    fn test_add_to_state() {
+        let mut state = NopState::<BytesInput>::new();
+        Tokens::from([b"AAA".to_vec(), b"BBB".to_vec()]).add_to_state(&mut state);
+        Tokens::from([b"BBB".to_vec(), b"CCC".to_vec()]).add_to_state(&mut state);
+        let tokens = state.metadata::<Tokens>().unwrap();
+        assert_eq!(
+            tokens.tokens(),
+            &[b"AAA".to_vec(), b"BBB".to_vec(), b"CCC".to_vec()]
+        );
+    }
+
     #[cfg(feature = "std")]
     #[test]
     fn test_token_mutations() {
@@ -0,0 +1,366 @@
+//! The [`CoreDumpObserver`] picks up the core dump a crashing target left behind.
+//!
+//! The target has to dump its core into a dedicated directory: enable core dumps with
+//! [`crate::executors::ChildSandbox::core_dumps`], and either run the target in that directory, if the
+//! `core_pattern` of the system is relative (the default `core`), or point the observer to the
+//! directory of an absolute `core_pattern`. Cores piped to a handler, such as `systemd-coredump` or
+//! `apport`, cannot be picked up.
+//! Use the [`crate::feedbacks::CoreDumpFeedback`] to keep the core dumps of objectives for triage.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+/// The file linux reads the `core_pattern` from
+const CORE_PATTERN_FILE: &str = "/proc/sys/kernel/core_pattern";
+
+/// The maximum size of a note segment read for the [`CoreDumpSummary`]
+const MAX_NOTE_SEGMENT_SIZE: u64 = 1 << 24;
+
+/// `NT_PRSTATUS`, the note holding the signal and registers of a thread
+const NT_PRSTATUS: u32 = 1;
+/// `NT_FILE`, the note holding the memory-mapped files
+const NT_FILE: u32 = 0x4649_4c45;
+/// The offset of the registers in the `elf_prstatus` struct on 64-bit linux
+const PRSTATUS_REGS_OFFSET: usize = 112;
+/// The offset of the current signal in the `elf_prstatus` struct
+const PRSTATUS_CURSIG_OFFSET: usize = 12;
+
+/// Returns the `core_pattern` of the system, if it can be read
+#[must_use]
+pub fn core_pattern() -> Option<String> {
+    fs::read_to_string(CORE_PATTERN_FILE)
+        .ok()
+        .map(|pattern| pattern.trim().into())
+}
+
+/// A minidump-style summary of a core dump, parsed from its notes.
+///
+/// Only 64-bit little-endian ELF core dumps, as written by linux, are supported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreDumpSummary {
+    /// The signal that killed the target
+    pub signal: u16,
+    /// The program counter of the crashing thread, for `x86_64` and `aarch64` targets
+    pub pc: Option<u64>,
+    /// The stack pointer of the crashing thread, for `x86_64` and `aarch64` targets
+    pub sp: Option<u64>,
+    /// The amount of threads of the target
+    pub threads: usize,
+    /// The files mapped into the target, with their start and end address
+    pub mappings: Vec<(u64, u64, String)>,
+}
+
+/// Reads a little-endian `u16` at `offset`
+fn read_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a little-endian `u32` at `offset`
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a little-endian `u64` at `offset`
+fn read_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        buf.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+impl CoreDumpSummary {
+    /// Parses the summary from the core dump at `path`
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let malformed = || Error::illegal_argument("Malformed ELF core dump");
+
+        let mut file = File::open(path)?;
+        let mut header = [0; 64];
+        file.read_exact(&mut header)?;
+        if header[..4] != *b"\x7fELF" || header[4] != 2 || header[5] != 1 {
+            return Err(Error::illegal_argument(
+                "Not a 64-bit little-endian ELF file",
+            ));
+        }
+        if read_u16(&header, 16) != Some(4) {
+            return Err(Error::illegal_argument("Not an ELF core dump"));
+        }
+        let machine = read_u16(&header, 18).ok_or_else(malformed)?;
+        let phoff = read_u64(&header, 32).ok_or_else(malformed)?;
+        let phentsize = read_u16(&header, 54).ok_or_else(malformed)?;
+        let phnum = read_u16(&header, 56).ok_or_else(malformed)?;
+
+        let mut phdrs = vec![0; usize::from(phentsize) * usize::from(phnum)];
+        file.seek(SeekFrom::Start(phoff))?;
+        file.read_exact(&mut phdrs)?;
+
+        let mut summary = Self {
+            signal: 0,
+            pc: None,
+            sp: None,
+            threads: 0,
+            mappings: Vec::new(),
+        };
+        for phdr in phdrs.chunks_exact(usize::from(phentsize)) {
+            // PT_NOTE
+            if read_u32(phdr, 0) != Some(4) {
+                continue;
+            }
+            let offset = read_u64(phdr, 8).ok_or_else(malformed)?;
+            let size = read_u64(phdr, 32).ok_or_else(malformed)?;
+            if size > MAX_NOTE_SEGMENT_SIZE {
+                continue;
+            }
+            #[allow(clippy::cast_possible_truncation)] // at most MAX_NOTE_SEGMENT_SIZE
+            let mut notes = vec![0; size as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut notes)?;
+            summary.parse_notes(&notes, machine).ok_or_else(malformed)?;
+        }
+        Ok(summary)
+    }
+
+    /// Parses the notes of a `PT_NOTE` segment
+    fn parse_notes(&mut self, notes: &[u8], machine: u16) -> Option<()> {
+        let align = |len: usize| (len + 3) & !3;
+        let mut pos = 0;
+        while pos + 12 <= notes.len() {
+            let namesz = read_u32(notes, pos)? as usize;
+            let descsz = read_u32(notes, pos + 4)? as usize;
+            let kind = read_u32(notes, pos + 8)?;
+            let desc_start = pos + 12 + align(namesz);
+            let desc = notes.get(desc_start..desc_start + descsz)?;
+            pos = desc_start + align(descsz);
+
+            match kind {
+                NT_PRSTATUS => {
+                    // The first thread is the one that received the signal
+                    if self.threads == 0 {
+                        self.signal = read_u16(desc, PRSTATUS_CURSIG_OFFSET)?;
+                        let reg = |idx: usize| read_u64(desc, PRSTATUS_REGS_OFFSET + idx * 8);
+                        (self.pc, self.sp) = match machine {
+                            // EM_X86_64, `rip` and `rsp` of `user_regs_struct`
+                            62 => (reg(16), reg(19)),
+                            // EM_AARCH64, `pc` and `sp` of `user_pt_regs`
+                            183 => (reg(32), reg(31)),
+                            _ => (None, None),
+                        };
+                    }
+                    self.threads += 1;
+                }
+                NT_FILE => {
+                    let count = usize::try_from(read_u64(desc, 0)?).ok()?;
+                    let names_start = count.checked_mul(24)?.checked_add(16)?;
+                    let mut names = desc.get(names_start..)?.split(|b| *b == 0);
+                    for idx in 0..count {
+                        let start = read_u64(desc, 16 + idx * 24)?;
+                        let end = read_u64(desc, 16 + idx * 24 + 8)?;
+                        let name = String::from_utf8_lossy(names.next()?).into_owned();
+                        self.mappings.push((start, end, name));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(())
+    }
+
+    /// The file mapped at the program counter of the crashing thread, if any
+    #[must_use]
+    pub fn pc_mapping(&self) -> Option<&str> {
+        let pc = self.pc?;
+        self.mappings
+            .iter()
+            .find(|(start, end, _)| (*start..*end).contains(&pc))
+            .map(|(_, _, name)| name.as_str())
+    }
+}
+
+/// An observer picking up the core dump of a crashing target, see the [module-level docs](self).
+///
+/// To not fill up the disk, the core dump of a run is removed before the next run, unless it was
+/// moved away in the meantime, e.g., by the [`crate::feedbacks::CoreDumpFeedback`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreDumpObserver {
+    name: Cow<'static, str>,
+    core_dir: PathBuf,
+    core: Option<PathBuf>,
+}
+
+impl CoreDumpObserver {
+    /// Creates a new [`CoreDumpObserver`] picking up core dumps from `core_dir`.
+    ///
+    /// Each file in `core_dir` whose name starts with `core` is taken as core dump,
+    /// so use a directory dedicated to the target.
+    pub fn new<P>(name: &'static str, core_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        if let Some(pattern) = core_pattern() {
+            if pattern.starts_with('|') {
+                log::warn!(
+                    "Core dumps are piped to {pattern}, the CoreDumpObserver will not find them"
+                );
+            }
+        }
+        Self {
+            name: Cow::from(name),
+            core_dir: core_dir.into(),
+            core: None,
+        }
+    }
+
+    /// The directory core dumps are picked up from
+    #[must_use]
+    pub fn core_dir(&self) -> &Path {
+        &self.core_dir
+    }
+
+    /// The core dump of the last run, if the target crashed and dumped its core
+    #[must_use]
+    pub fn core(&self) -> Option<&Path> {
+        self.core.as_deref()
+    }
+
+    /// Takes the core dump of the last run, which is then not removed before the next run
+    pub fn take_core(&mut self) -> Option<PathBuf> {
+        self.core.take()
+    }
+
+    /// Finds a core dump in the core directory
+    fn find_core(&self) -> Result<Option<PathBuf>, Error> {
+        for entry in fs::read_dir(&self.core_dir)? {
+            let entry = entry?;
+            if entry.file_name().as_encoded_bytes().starts_with(b"core")
+                && entry.file_type()?.is_file()
+            {
+                return Ok(Some(entry.path()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Named for CoreDumpObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for CoreDumpObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        if let Some(core) = self.core.take() {
+            // It may have been moved away already
+            let _ = fs::remove_file(core);
+        }
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        if *exit_kind == ExitKind::Crash {
+            self.core = self.find_core()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+    use std::{env, fs};
+
+    use super::{CoreDumpObserver, CoreDumpSummary, NT_FILE, NT_PRSTATUS};
+    use crate::{executors::ExitKind, inputs::NopInput, observers::Observer};
+
+    /// Appends a note with the name `CORE`
+    fn push_note(notes: &mut Vec<u8>, kind: u32, desc: &[u8]) {
+        notes.extend(5u32.to_le_bytes());
+        notes.extend((desc.len() as u32).to_le_bytes());
+        notes.extend(kind.to_le_bytes());
+        notes.extend(b"CORE\0\0\0\0");
+        notes.extend(desc);
+        notes.resize((notes.len() + 3) & !3, 0);
+    }
+
+    #[test]
+    fn test_core_dump_summary() {
+        let mut prstatus = vec![0; 112 + 27 * 8];
+        prstatus[12..14].copy_from_slice(&11u16.to_le_bytes());
+        prstatus[112 + 16 * 8..112 + 17 * 8].copy_from_slice(&0x1234u64.to_le_bytes());
+        prstatus[112 + 19 * 8..112 + 20 * 8].copy_from_slice(&0x7ff0u64.to_le_bytes());
+        let mut file = Vec::new();
+        for value in [1u64, 0x1000, 0x1000, 0x2000, 0] {
+            file.extend(value.to_le_bytes());
+        }
+        file.extend(b"/bin/target\0");
+
+        let mut notes = Vec::new();
+        push_note(&mut notes, NT_PRSTATUS, &prstatus);
+        push_note(&mut notes, NT_PRSTATUS, &prstatus);
+        push_note(&mut notes, NT_FILE, &file);
+
+        let mut core = vec![0; 64 + 56];
+        core[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        core[16..18].copy_from_slice(&4u16.to_le_bytes());
+        core[18..20].copy_from_slice(&62u16.to_le_bytes());
+        core[32..40].copy_from_slice(&64u64.to_le_bytes());
+        core[54..56].copy_from_slice(&56u16.to_le_bytes());
+        core[56..58].copy_from_slice(&1u16.to_le_bytes());
+        core[64..68].copy_from_slice(&4u32.to_le_bytes());
+        core[72..80].copy_from_slice(&120u64.to_le_bytes());
+        core[96..104].copy_from_slice(&(notes.len() as u64).to_le_bytes());
+        core.extend(notes);
+
+        let path = env::temp_dir().join("libafl_test_core_dump_summary");
+        fs::write(&path, core).unwrap();
+        let summary = CoreDumpSummary::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary.signal, 11);
+        assert_eq!(summary.pc, Some(0x1234));
+        assert_eq!(summary.sp, Some(0x7ff0));
+        assert_eq!(summary.threads, 2);
+        assert_eq!(
+            summary.mappings,
+            [(0x1000, 0x2000, "/bin/target".to_string())]
+        );
+        assert_eq!(summary.pc_mapping(), Some("/bin/target"));
+    }
+
+    #[test]
+    fn test_core_dump_observer() {
+        let core_dir = env::temp_dir().join("libafl_test_core_dump_observer");
+        fs::create_dir_all(&core_dir).unwrap();
+        let mut observer = CoreDumpObserver::new("core", &core_dir);
+        let mut state = ();
+
+        Observer::<NopInput, ()>::pre_exec(&mut observer, &mut state, &NopInput {}).unwrap();
+        fs::write(core_dir.join("core.1234"), b"core").unwrap();
+        observer
+            .post_exec(&mut state, &NopInput {}, &ExitKind::Crash)
+            .unwrap();
+        assert_eq!(observer.core(), Some(core_dir.join("core.1234").as_path()));
+
+        // The core of the last run is removed before the next run
+        observer.pre_exec(&mut state, &NopInput {}).unwrap();
+        observer
+            .post_exec(&mut state, &NopInput {}, &ExitKind::Crash)
+            .unwrap();
+        assert_eq!(observer.core(), None);
+        fs::remove_dir(&core_dir).unwrap();
+    }
+}
@@ -0,0 +1,583 @@
+//! The [`CrashContextObserver`] keeps the [`CrashContext`] of in-process crashes:
+//! the signal, the faulting address, the registers, and a symbolized backtrace.
+//!
+//! The context is captured in the crash handler of the [`crate::executors::InProcessExecutor`],
+//! where the registers of the crashing thread are still available, and picked up by the observer.
+//! The handler only captures it while a [`CrashContextObserver`] observes the execution.
+//! Symbolizing is not safe in a signal handler, so the backtraces of signals keep the module and offset
+//! of each frame instead; call [`CrashContext::resolve`] to symbolize them when triaging, also in a later
+//! run of the same binaries. The backtraces of panics are symbolized right away, in the panic hook.
+//! Add a [`crate::feedbacks::CrashContextFeedback`] to the objective to store it with the testcase,
+//! or dedup crashes on the [`CrashContext::hash`], e.g., with a [`crate::feedbacks::NewHashFeedback`].
+
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    ffi::c_void,
+    fmt::Write as _,
+    ops::Range,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering},
+};
+use std::sync::OnceLock;
+
+use backtrace::Backtrace;
+use libafl_bolts::{
+    hash_std, impl_serdeany,
+    os::unix_signals::{siginfo_t, ucontext_t, Signal},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    executors::ExitKind,
+    observers::{Observer, ObserverWithHashField},
+    Error,
+};
+
+/// The default number of innermost frames of the backtrace that make up the [`CrashContext::hash`]
+pub const DEFAULT_CRASH_CONTEXT_HASH_FRAMES: usize = 5;
+
+/// Whether a [`CrashContextObserver`] observes the current execution, set in its `pre_exec`
+static CRASH_CONTEXT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The [`CrashContext`] captured by the last crash handler, not yet picked up by a [`CrashContextObserver`]
+static LAST_CRASH_CONTEXT: AtomicPtr<CrashContext> = AtomicPtr::new(ptr::null_mut());
+
+/// A module loaded in the current process
+#[derive(Debug)]
+struct LoadedModule {
+    /// The path of the module
+    path: String,
+    /// The difference between the addresses in the process and in the module file
+    bias: usize,
+    /// The address ranges of the loaded segments of the module
+    ranges: Vec<Range<usize>>,
+}
+
+/// The modules loaded in the current process, listed outside of the crash handlers
+static LOADED_MODULES: OnceLock<Vec<LoadedModule>> = OnceLock::new();
+
+/// Lists the modules loaded in the current process once, so that the crash handlers can find the module
+/// of each frame without calling into the loader. Modules loaded later are not listed.
+fn loaded_modules() -> &'static [LoadedModule] {
+    LOADED_MODULES.get_or_init(list_modules)
+}
+
+/// Lists the modules loaded in the current process
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn list_modules() -> Vec<LoadedModule> {
+    #[allow(clippy::cast_possible_truncation)]
+    unsafe extern "C" fn add_module(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        modules: *mut c_void,
+    ) -> libc::c_int {
+        let modules = &mut *modules.cast::<Vec<LoadedModule>>();
+        let info = &*info;
+        let path = if info.dlpi_name.is_null() || *info.dlpi_name == 0 {
+            // The main executable
+            std::env::current_exe()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            core::ffi::CStr::from_ptr(info.dlpi_name)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let bias = info.dlpi_addr as usize;
+        let ranges = (0..usize::from(info.dlpi_phnum))
+            .map(|i| &*info.dlpi_phdr.add(i))
+            .filter(|header| header.p_type == libc::PT_LOAD)
+            .map(|header| {
+                let start = bias + header.p_vaddr as usize;
+                start..start + header.p_memsz as usize
+            })
+            .collect();
+        modules.push(LoadedModule { path, bias, ranges });
+        0
+    }
+
+    let mut modules = Vec::new();
+    // # Safety
+    // The callback only adds to the `modules` it is given.
+    unsafe {
+        libc::dl_iterate_phdr(Some(add_module), ptr::from_mut(&mut modules).cast());
+    }
+    modules
+}
+
+/// Lists the modules loaded in the current process, not supported on this platform
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn list_modules() -> Vec<LoadedModule> {
+    Vec::new()
+}
+
+/// Whether the crash handlers should capture a [`CrashContext`], i.e., if a [`CrashContextObserver`]
+/// observes the current execution
+#[must_use]
+pub fn is_crash_context_enabled() -> bool {
+    CRASH_CONTEXT_ENABLED.load(Ordering::Acquire)
+}
+
+/// Stores the [`CrashContext`] of a crash, for the [`CrashContextObserver`].
+pub fn record_crash_context(context: CrashContext) {
+    let old = LAST_CRASH_CONTEXT.swap(Box::into_raw(Box::new(context)), Ordering::AcqRel);
+    if !old.is_null() {
+        // # Safety
+        // The pointer was created by `Box::into_raw` above, and nobody else owns it after the swap.
+        drop(unsafe { Box::from_raw(old) });
+    }
+}
+
+/// Takes the [`CrashContext`] stored by the last crash handler
+fn take_crash_context() -> Option<CrashContext> {
+    let context = LAST_CRASH_CONTEXT.swap(ptr::null_mut(), Ordering::AcqRel);
+    // # Safety
+    // The pointer was created by `Box::into_raw` in `record_crash_context`, and nobody else owns it after the swap.
+    (!context.is_null()).then(|| *unsafe { Box::from_raw(context) })
+}
+
+/// Whether the frame belongs to the panic machinery of the standard library
+fn is_panic_frame(frame: &CrashFrame) -> bool {
+    frame.symbol.as_ref().is_some_and(|symbol| {
+        symbol.contains("std::panicking")
+            || symbol.contains("core::panicking")
+            || symbol.contains("rust_panic")
+            || symbol.contains("begin_panic_handler")
+            || symbol.contains("rust_begin_unwind")
+            || symbol.contains("__rust_end_short_backtrace")
+    })
+}
+
+/// A frame of the backtrace of a [`CrashContext`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CrashFrame {
+    /// The instruction pointer of the frame
+    pub ip: usize,
+    /// The demangled name of the function, if it could be symbolized
+    pub symbol: Option<String>,
+    /// The source file, if known
+    pub file: Option<String>,
+    /// The source line, if known
+    pub line: Option<u32>,
+    /// The path of the module of the frame, and the offset of the `ip` in it, if known
+    pub module: Option<(String, usize)>,
+}
+
+/// A local variable at the crash site, as reported by a debugger
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CrashLocal {
+    /// The name of the variable
+    pub name: String,
+    /// The type of the variable, if known
+    pub ty: Option<String>,
+    /// The value of the variable, if it is simple enough to print
+    pub value: Option<String>,
+}
+
+/// The context of a crash, captured in the in-process crash handler, or by a debugger, see
+/// [`crate::executors::DebuggerExecutor`].
+///
+/// Stored as metadata of objective testcases by the [`crate::feedbacks::CrashContextFeedback`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashContext {
+    /// The name of the signal, e.g., `SIGSEGV`
+    pub signal: String,
+    /// The number of the signal
+    pub signal_number: i32,
+    /// The `si_code` of the signal, telling why it was raised
+    pub si_code: i32,
+    /// The faulting address, for memory faults
+    pub fault_addr: usize,
+    /// The program counter at the crash, where supported
+    pub pc: Option<usize>,
+    /// The registers at the crash, where supported, as printed by [`libafl_bolts::minibsod`]
+    pub registers: String,
+    /// The backtrace, innermost frame first, starting at the crash if the `pc` is known.
+    /// For signals, it is not symbolized until [`CrashContext::resolve`] is called.
+    pub backtrace: Vec<CrashFrame>,
+    /// The locals of the innermost frame with debug info; only captured by debuggers
+    pub locals: Vec<CrashLocal>,
+}
+
+impl_serdeany!(CrashContext);
+
+impl CrashContext {
+    /// Captures the context of a crash from within the signal handler.
+    ///
+    /// `info` and `context` are missing for panics.
+    /// The backtrace is not symbolized yet, call [`CrashContext::resolve`] once out of the handler.
+    /// The modules of the frames are only known if a [`CrashContextObserver`] listed them before.
+    #[must_use]
+    pub fn capture(signal: Signal, info: Option<&siginfo_t>, context: Option<&ucontext_t>) -> Self {
+        let (si_code, fault_addr) = info.map_or((0, 0), |info| {
+            #[cfg(target_os = "android")]
+            let fault_addr = (info._pad[0] as usize) | ((info._pad[1] as usize) << 32);
+            #[cfg(not(target_os = "android"))]
+            let fault_addr = unsafe { info.si_addr() as usize };
+            (info.si_code, fault_addr)
+        });
+        let pc = context.and_then(program_counter);
+        let registers = context.map(dump_registers).unwrap_or_default();
+
+        let modules = LOADED_MODULES.get().map_or(&[][..], Vec::as_slice);
+        let mut backtrace: Vec<CrashFrame> = Backtrace::new_unresolved()
+            .frames()
+            .iter()
+            .map(|frame| {
+                let ip = frame.ip() as usize;
+                CrashFrame {
+                    ip,
+                    symbol: None,
+                    file: None,
+                    line: None,
+                    module: modules
+                        .iter()
+                        .find(|module| module.ranges.iter().any(|range| range.contains(&ip)))
+                        .map(|module| (module.path.clone(), ip - module.bias)),
+                }
+            })
+            .collect();
+        // Skip the frames of the crash handler
+        if let Some(crash_frame) = pc.and_then(|pc| backtrace.iter().position(|f| f.ip == pc)) {
+            backtrace.drain(..crash_frame);
+        }
+
+        Self {
+            signal: signal.to_string(),
+            signal_number: signal as i32,
+            si_code,
+            fault_addr,
+            pc,
+            registers,
+            backtrace,
+            locals: Vec::new(),
+        }
+    }
+
+    /// Symbolizes the frames of the backtrace that are not symbolized yet, in the current process.
+    ///
+    /// Frames with a known module are looked up at their offset in the module as loaded now,
+    /// so this also works in a later run of the same binaries.
+    pub fn resolve(&mut self) {
+        let modules = loaded_modules();
+        for frame in self.backtrace.iter_mut().filter(|f| f.symbol.is_none()) {
+            let ip = frame
+                .module
+                .as_ref()
+                .and_then(|(path, offset)| {
+                    let module = modules.iter().find(|module| module.path == *path)?;
+                    Some(module.bias + offset)
+                })
+                .unwrap_or(frame.ip);
+            backtrace::resolve(ip as *mut c_void, |symbol| {
+                if frame.symbol.is_some() {
+                    // Only keep the innermost of inlined functions
+                    return;
+                }
+                frame.symbol = symbol.name().map(|name| format!("{name:#}"));
+                frame.file = symbol
+                    .filename()
+                    .map(|file| file.to_string_lossy().into_owned());
+                frame.line = symbol.lineno();
+            });
+        }
+    }
+
+    /// A hash of the signal and the innermost `frames` frames of the backtrace, to dedup crashes.
+    ///
+    /// Frames are hashed by their module and offset if known, else by their function name, so the hash
+    /// is stable across runs with ASLR, and does not change when the backtrace is symbolized later.
+    /// For panics, where the `pc` is unknown, the frames of the panic handler are skipped, so the hash
+    /// starts at the panic site.
+    #[must_use]
+    pub fn hash(&self, frames: usize) -> u64 {
+        let mut start = 0;
+        if self.pc.is_none() {
+            if let Some(first) = self.backtrace.iter().position(is_panic_frame) {
+                start = first
+                    + self.backtrace[first..]
+                        .iter()
+                        .take_while(|frame| is_panic_frame(frame))
+                        .count();
+            }
+        }
+
+        let mut key = format!("{}", self.signal_number);
+        for frame in self.backtrace.iter().skip(start).take(frames) {
+            match (&frame.module, &frame.symbol) {
+                (Some((path, offset)), _) => write!(key, "|{path}+{offset:#x}"),
+                (None, Some(symbol)) => write!(key, "|{symbol}"),
+                (None, None) => write!(key, "|{:#x}", frame.ip),
+            }
+            .unwrap();
+        }
+        hash_std(key.as_bytes())
+    }
+}
+
+/// The program counter in the given context
+#[allow(unused_variables)]
+fn program_counter(context: &ucontext_t) -> Option<usize> {
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "x86_64"
+    ))]
+    {
+        #[allow(clippy::cast_sign_loss)]
+        return Some(context.uc_mcontext.gregs[libc::REG_RIP as usize] as usize);
+    }
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "aarch64"
+    ))]
+    {
+        #[allow(clippy::cast_possible_truncation)]
+        return Some(context.uc_mcontext.pc as usize);
+    }
+    #[allow(unreachable_code)]
+    None
+}
+
+/// The registers in the given context, as printed by [`libafl_bolts::minibsod`]
+#[allow(unused_variables)]
+fn dump_registers(context: &ucontext_t) -> String {
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    {
+        let mut out = Vec::new();
+        {
+            let mut writer = std::io::BufWriter::new(&mut out);
+            if libafl_bolts::minibsod::dump_registers(&mut writer, context).is_err() {
+                return String::new();
+            }
+        }
+        return String::from_utf8_lossy(&out).into_owned();
+    }
+    #[allow(unreachable_code)]
+    String::new()
+}
+
+/// An observer keeping the [`CrashContext`] of in-process crashes, see the [module-level docs](self).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashContextObserver {
+    name: Cow<'static, str>,
+    context: Option<CrashContext>,
+    hash_frames: usize,
+}
+
+impl CrashContextObserver {
+    /// Creates a new [`CrashContextObserver`] with the given name
+    #[must_use]
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            context: None,
+            hash_frames: DEFAULT_CRASH_CONTEXT_HASH_FRAMES,
+        }
+    }
+
+    /// Sets how many innermost frames make up the hash of this observer,
+    /// see [`CrashContext::hash`]
+    #[must_use]
+    pub fn with_hash_frames(mut self, hash_frames: usize) -> Self {
+        self.hash_frames = hash_frames;
+        self
+    }
+
+    /// The [`CrashContext`] of the last execution, if it crashed
+    #[must_use]
+    pub fn context(&self) -> Option<&CrashContext> {
+        self.context.as_ref()
+    }
+
+    /// Sets the [`CrashContext`] of the current execution, for executors capturing it themselves
+    pub fn set_context(&mut self, context: Option<CrashContext>) {
+        self.context = context;
+    }
+}
+
+impl ObserverWithHashField for CrashContextObserver {
+    fn hash(&self) -> Option<u64> {
+        self.context
+            .as_ref()
+            .map(|context| context.hash(self.hash_frames))
+    }
+}
+
+impl<I, S> Observer<I, S> for CrashContextObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.context = None;
+        loaded_modules();
+        CRASH_CONTEXT_ENABLED.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Picks up the [`CrashContext`] of a crash.
+    ///
+    /// For in-process crashes, this runs in the crash handler, so the backtrace is not symbolized here.
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        CRASH_CONTEXT_ENABLED.store(false, Ordering::Release);
+        let context = take_crash_context();
+        if *exit_kind != ExitKind::Crash {
+            self.context = None;
+        } else if context.is_some() {
+            self.context = context;
+        }
+        Ok(())
+    }
+}
+
+impl Named for CrashContextObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::ptr;
+
+    use libafl_bolts::os::unix_signals::{siginfo_t, ucontext_t, Signal};
+
+    use super::{record_crash_context, CrashContext, CrashContextObserver, CrashFrame};
+    use crate::{
+        executors::ExitKind,
+        inputs::BytesInput,
+        observers::{Observer, ObserverWithHashField},
+        state::NopState,
+    };
+
+    #[inline(never)]
+    fn crashing_function() {
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+        }
+    }
+
+    extern "C" fn handler(signal: libc::c_int, info: *mut siginfo_t, context: *mut libc::c_void) {
+        let signal = Signal::try_from(signal).unwrap();
+        let context = unsafe {
+            CrashContext::capture(signal, info.as_ref(), context.cast::<ucontext_t>().as_ref())
+        };
+        record_crash_context(context);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_crash_context() {
+        unsafe {
+            let mut action: libc::sigaction = core::mem::zeroed();
+            action.sa_sigaction =
+                handler as extern "C" fn(libc::c_int, *mut siginfo_t, *mut libc::c_void) as usize;
+            action.sa_flags = libc::SA_SIGINFO;
+            assert_eq!(
+                libc::sigaction(libc::SIGUSR2, &raw const action, ptr::null_mut()),
+                0
+            );
+        }
+
+        let mut observer = CrashContextObserver::new("crash_context");
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(vec![]);
+
+        observer.pre_exec(&mut state, &input).unwrap();
+        crashing_function();
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Crash)
+            .unwrap();
+
+        let context = observer.context().unwrap();
+        assert_eq!(context.signal_number, libc::SIGUSR2);
+        if let Some(pc) = context.pc {
+            assert_eq!(context.backtrace[0].ip, pc);
+        }
+        // The handler does not symbolize the backtrace
+        assert!(context.backtrace.iter().all(|frame| frame.symbol.is_none()));
+        #[cfg(target_os = "linux")]
+        assert!(context.backtrace[0].module.is_some());
+        assert_eq!(observer.hash(), Some(context.hash(5)));
+
+        let mut resolved = context.clone();
+        resolved.resolve();
+        assert!(resolved.backtrace.iter().take(4).any(|frame| frame
+            .symbol
+            .as_ref()
+            .is_some_and(|symbol| symbol.ends_with("crashing_function"))));
+        assert_eq!(resolved.hash(5), context.hash(5));
+
+        observer.pre_exec(&mut state, &input).unwrap();
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert!(observer.context().is_none());
+    }
+
+    fn frame(symbol: &str) -> CrashFrame {
+        CrashFrame {
+            ip: 0,
+            symbol: Some(symbol.into()),
+            file: None,
+            line: None,
+            module: None,
+        }
+    }
+
+    fn panic_context(site: &str) -> CrashContext {
+        CrashContext {
+            signal: "SIGABRT".into(),
+            signal_number: libc::SIGABRT,
+            si_code: 0,
+            fault_addr: 0,
+            pc: None,
+            registers: String::new(),
+            backtrace: vec![
+                frame("libafl::observers::crash_context::CrashContext::capture"),
+                frame("libafl::executors::hooks::unix::unix_signal_handler::setup_panic_hook::{{closure}}"),
+                frame("std::panicking::rust_panic_with_hook"),
+                frame("std::panicking::begin_panic_handler::{{closure}}"),
+                frame("std::sys::backtrace::__rust_end_short_backtrace"),
+                frame("rust_begin_unwind"),
+                frame("core::panicking::panic_fmt"),
+                frame(site),
+                frame("harness::run"),
+                frame("std::panicking::try"),
+                frame("main"),
+            ],
+            locals: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_panic_hash() {
+        let first = panic_context("harness::first_site");
+        let second = panic_context("harness::second_site");
+        assert_ne!(first.hash(1), second.hash(1));
+        assert_ne!(first.hash(5), second.hash(5));
+        assert_eq!(first.hash(5), panic_context("harness::first_site").hash(5));
+    }
+}
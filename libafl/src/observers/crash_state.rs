@@ -0,0 +1,270 @@
+//! The [`CrashStateObserver`] holds the register file and selected memory of a crashed emulated target.
+//!
+//! Emulator-based executors, such as `libafl_qemu`, capture the [`CrashState`] of the guest when it crashes.
+//! Use the [`crate::feedbacks::CrashStateFeedback`] to store it next to the objective, as a compact and versioned
+//! blob, see [`CrashState::to_bytes`]. Stored states can be loaded and printed offline, without re-running the
+//! emulator, with [`CrashState::from_file`] and the [`Display`](core::fmt::Display) implementation.
+
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::{fmt, ptr};
+use std::{fs, path::Path};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, observers::Observer, Error};
+
+/// The magic bytes starting a serialized [`CrashState`]
+pub const CRASH_STATE_MAGIC: [u8; 4] = *b"LCST";
+
+/// The version of the serialized [`CrashState`], increased on incompatible changes
+pub const CRASH_STATE_VERSION: u32 = 1;
+
+/// The number of bytes per line when printing memory
+const HEXDUMP_WIDTH: usize = 16;
+
+/// The [`CrashState`] stored by the executor, until the [`CrashStateObserver`] takes it
+static mut LAST_CRASH_STATE: Option<CrashState> = None;
+
+/// Stores the [`CrashState`] of a crash, for the [`CrashStateObserver`].
+///
+/// # Safety
+/// Only call this from the executor or its crash handler, while no observer runs.
+pub unsafe fn record_crash_state(state: CrashState) {
+    unsafe {
+        *ptr::addr_of_mut!(LAST_CRASH_STATE) = Some(state);
+    }
+}
+
+/// Takes the [`CrashState`] stored by the executor
+fn take_crash_state() -> Option<CrashState> {
+    // # Safety
+    // The executor stored the state before it ran the observers, on the same thread.
+    unsafe { (*ptr::addr_of_mut!(LAST_CRASH_STATE)).take() }
+}
+
+/// A memory region of a [`CrashState`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashMemoryRegion {
+    /// The guest address of the region
+    pub addr: u64,
+    /// The content of the region, empty if it could not be read
+    pub bytes: Vec<u8>,
+}
+
+/// The registers and selected memory of a crashed emulated target
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashState {
+    /// The guest architecture, e.g., `x86_64`
+    pub arch: String,
+    /// The registers, by name
+    pub registers: Vec<(String, u64)>,
+    /// The captured memory regions
+    pub regions: Vec<CrashMemoryRegion>,
+}
+
+impl CrashState {
+    /// Creates a new, empty [`CrashState`] for the guest architecture `arch`
+    #[must_use]
+    pub fn new<A>(arch: A) -> Self
+    where
+        A: Into<String>,
+    {
+        Self {
+            arch: arch.into(),
+            registers: Vec::new(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// The value of the register `name`, if it was captured
+    #[must_use]
+    pub fn register(&self, name: &str) -> Option<u64> {
+        self.registers
+            .iter()
+            .find(|(reg, _)| reg.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Serializes the state into a blob: the magic bytes, the version, and the postcard-encoded state
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = CRASH_STATE_MAGIC.to_vec();
+        bytes.extend_from_slice(&CRASH_STATE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&postcard::to_allocvec(self)?);
+        Ok(bytes)
+    }
+
+    /// Deserializes a blob written by [`Self::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 8 || bytes[..4] != CRASH_STATE_MAGIC {
+            return Err(Error::illegal_argument("Not a serialized crash state"));
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        if version != CRASH_STATE_VERSION {
+            return Err(Error::illegal_argument(format!(
+                "Unsupported crash state version {version}, expected {CRASH_STATE_VERSION}"
+            )));
+        }
+        Ok(postcard::from_bytes(&bytes[8..])?)
+    }
+
+    /// Loads a blob written by [`Self::to_bytes`] from the file at `path`
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+impl fmt::Display for CrashState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Crash state ({})", self.arch)?;
+        writeln!(f, "Registers:")?;
+        for (name, value) in &self.registers {
+            writeln!(f, "  {name:>8} = {value:#018x}")?;
+        }
+        for region in &self.regions {
+            writeln!(
+                f,
+                "Memory at {:#x} ({} bytes):",
+                region.addr,
+                region.bytes.len()
+            )?;
+            for (i, line) in region.bytes.chunks(HEXDUMP_WIDTH).enumerate() {
+                write!(f, "  {:016x}: ", region.addr + (i * HEXDUMP_WIDTH) as u64)?;
+                for byte in line {
+                    write!(f, "{byte:02x} ")?;
+                }
+                for _ in line.len()..HEXDUMP_WIDTH {
+                    write!(f, "   ")?;
+                }
+                let ascii = line
+                    .iter()
+                    .map(|b| {
+                        if b.is_ascii_graphic() {
+                            char::from(*b)
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>();
+                writeln!(f, " {ascii}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An observer holding the [`CrashState`] of the last execution, recorded by the executor when the target crashes,
+/// see [`record_crash_state`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashStateObserver {
+    name: Cow<'static, str>,
+    state: Option<CrashState>,
+}
+
+impl CrashStateObserver {
+    /// Creates a new [`CrashStateObserver`] with the given name
+    #[must_use]
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            state: None,
+        }
+    }
+
+    /// The [`CrashState`] of the last execution, if it crashed
+    #[must_use]
+    pub fn state(&self) -> Option<&CrashState> {
+        self.state.as_ref()
+    }
+
+    /// Sets the [`CrashState`] of the current execution
+    pub fn set_state(&mut self, state: Option<CrashState>) {
+        self.state = state;
+    }
+}
+
+impl<I, S> Observer<I, S> for CrashStateObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.state = None;
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        let state = take_crash_state();
+        if *exit_kind != ExitKind::Crash {
+            self.state = None;
+        } else if state.is_some() {
+            self.state = state;
+        }
+        Ok(())
+    }
+}
+
+impl Named for CrashStateObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::{
+        record_crash_state, CrashMemoryRegion, CrashState, CrashStateObserver, CRASH_STATE_MAGIC,
+    };
+    use crate::{executors::ExitKind, observers::Observer};
+
+    fn state() -> CrashState {
+        let mut state = CrashState::new("x86_64");
+        state.registers.push(("rip".into(), 0x0040_1000));
+        state.registers.push(("rsp".into(), 0x7fff_0000));
+        state.regions.push(CrashMemoryRegion {
+            addr: 0x7fff_0000,
+            bytes: b"AAAA\x00\x01".to_vec(),
+        });
+        state
+    }
+
+    #[test]
+    fn test_crash_state_roundtrip() {
+        let state = state();
+        let bytes = state.to_bytes().unwrap();
+        assert_eq!(bytes[..4], CRASH_STATE_MAGIC);
+        assert_eq!(CrashState::from_bytes(&bytes).unwrap(), state);
+        assert_eq!(state.register("RIP"), Some(0x0040_1000));
+
+        let mut bytes = bytes;
+        bytes[4] = 0xff;
+        assert!(CrashState::from_bytes(&bytes).is_err());
+        assert!(CrashState::from_bytes(b"LC").is_err());
+    }
+
+    #[test]
+    fn test_crash_state_display() {
+        let printed = state().to_string();
+        assert!(printed.contains("Crash state (x86_64)"));
+        assert!(printed.contains("rip = 0x0000000000401000"));
+        assert!(printed.contains("000000007fff0000: 41 41 41 41 00 01"));
+        assert!(printed.contains(" AAAA.."));
+    }
+
+    #[test]
+    fn test_crash_state_observer() {
+        let mut observer = CrashStateObserver::new("crash_state");
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        unsafe { record_crash_state(state()) };
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Crash).unwrap();
+        assert_eq!(observer.state(), Some(&state()));
+
+        unsafe { record_crash_state(state()) };
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert!(observer.state().is_none());
+    }
+}
@@ -0,0 +1,387 @@
+//! Compaction of sparse coverage maps
+//!
+//! Most entries of a large coverage map are never written by a small target.
+//! A [`MapCompactionBuilder`] records which indices of a map are written during a calibration pass
+//! (for example, by running the initial corpus), and builds a [`MapCompaction`] from them.
+//! The [`CompactedMapObserver`] then wraps the original map observer and only exposes the live
+//! entries, which shrinks the feedback history maps and the serialized observers sent to other nodes.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+};
+
+use ahash::RandomState;
+use libafl_bolts::{HasLen, Named};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    executors::ExitKind,
+    observers::{map::MapObserver, Observer},
+    Error,
+};
+
+/// Records which indices of a map are written, to build a [`MapCompaction`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MapCompactionBuilder {
+    written: Vec<bool>,
+}
+
+impl MapCompactionBuilder {
+    /// Creates a new [`MapCompactionBuilder`] for a map of `map_len` entries
+    #[must_use]
+    pub fn new(map_len: usize) -> Self {
+        Self {
+            written: vec![false; map_len],
+        }
+    }
+
+    /// Records all entries of the observer that differ from their initial value.
+    /// Call this after each execution of the calibration pass.
+    pub fn observe<O>(&mut self, observer: &O)
+    where
+        O: MapObserver,
+    {
+        let initial = observer.initial();
+        let len = observer.usable_count();
+        if self.written.len() < len {
+            self.written.resize(len, false);
+        }
+        for (i, written) in self.written[..len].iter_mut().enumerate() {
+            if !*written && observer.get(i) != initial {
+                *written = true;
+            }
+        }
+    }
+
+    /// Marks an index as live, even if it was never written during calibration
+    pub fn mark(&mut self, idx: usize) {
+        if self.written.len() <= idx {
+            self.written.resize(idx + 1, false);
+        }
+        self.written[idx] = true;
+    }
+
+    /// The amount of indices written so far
+    #[must_use]
+    pub fn written_count(&self) -> usize {
+        self.written.iter().filter(|x| **x).count()
+    }
+
+    /// Builds the [`MapCompaction`] from all indices written so far
+    #[must_use]
+    pub fn build(&self) -> MapCompaction {
+        MapCompaction {
+            indices: self
+                .written
+                .iter()
+                .enumerate()
+                .filter_map(|(i, written)| written.then_some(i))
+                .collect(),
+            original_len: self.written.len(),
+        }
+    }
+}
+
+/// A translation between the indices of an original map and the indices of its compacted version
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MapCompaction {
+    /// The live indices in the original map, sorted
+    indices: Vec<usize>,
+    original_len: usize,
+}
+
+impl MapCompaction {
+    /// Creates a new [`MapCompaction`] from the given live indices of a map of `original_len` entries
+    #[must_use]
+    pub fn new(mut indices: Vec<usize>, original_len: usize) -> Self {
+        indices.sort_unstable();
+        indices.dedup();
+        Self {
+            indices,
+            original_len,
+        }
+    }
+
+    /// The amount of entries in the compacted map
+    #[must_use]
+    pub fn compacted_len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// The amount of entries in the original map
+    #[must_use]
+    pub fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    /// The live indices in the original map, sorted
+    #[must_use]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// The index in the compacted map for the given index of the original map, if it is live
+    #[must_use]
+    pub fn compacted_index(&self, original_idx: usize) -> Option<usize> {
+        self.indices.binary_search(&original_idx).ok()
+    }
+
+    /// The index in the original map for the given index of the compacted map
+    #[must_use]
+    pub fn original_index(&self, compacted_idx: usize) -> usize {
+        self.indices[compacted_idx]
+    }
+
+    /// Compacts a slice of the original map size, e.g., an existing feedback history map
+    #[must_use]
+    pub fn compact_slice<T>(&self, original: &[T]) -> Vec<T>
+    where
+        T: Copy,
+    {
+        self.indices.iter().map(|i| original[*i]).collect()
+    }
+}
+
+/// A [`MapObserver`] exposing only the live entries of the wrapped map, according to a [`MapCompaction`].
+///
+/// Hits in entries that were not live during calibration are dropped, so calibrate with a
+/// representative set of inputs, or [`MapCompactionBuilder::mark`] the indices you care about.
+///
+/// Only the compacted entries are serialized, the wrapped observer is not sent to other nodes.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
+pub struct CompactedMapObserver<M, T> {
+    #[serde(skip)]
+    base: Option<M>,
+    compaction: MapCompaction,
+    map: Vec<T>,
+    initial: T,
+    name: Cow<'static, str>,
+}
+
+impl<M, T> CompactedMapObserver<M, T>
+where
+    M: MapObserver<Entry = T>,
+    T: Copy,
+{
+    /// Creates a new [`CompactedMapObserver`], wrapping the `base` observer
+    #[must_use]
+    pub fn new(base: M, compaction: MapCompaction) -> Self {
+        let initial = base.initial();
+        Self {
+            map: vec![initial; compaction.compacted_len()],
+            name: base.name().clone(),
+            base: Some(base),
+            compaction,
+            initial,
+        }
+    }
+
+    /// The [`MapCompaction`] used by this observer
+    #[must_use]
+    pub fn compaction(&self) -> &MapCompaction {
+        &self.compaction
+    }
+
+    /// The wrapped observer, if this observer was not deserialized
+    #[must_use]
+    pub fn base(&self) -> Option<&M> {
+        self.base.as_ref()
+    }
+
+    /// The wrapped observer, mutably, if this observer was not deserialized
+    pub fn base_mut(&mut self) -> Option<&mut M> {
+        self.base.as_mut()
+    }
+}
+
+impl<I, S, M, T> Observer<I, S> for CompactedMapObserver<M, T>
+where
+    M: MapObserver<Entry = T> + Observer<I, S>,
+    T: Copy,
+{
+    #[inline]
+    fn pre_exec(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        let initial = self.initial;
+        self.map.fill(initial);
+        match self.base.as_mut() {
+            Some(base) => base.pre_exec(state, input),
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        let Some(base) = self.base.as_mut() else {
+            return Ok(());
+        };
+        base.post_exec(state, input, exit_kind)?;
+        // The map of the base may have shrunk since the calibration, e.g., for a variable-length map.
+        // Live indices past its end count as not hit.
+        let len = base.usable_count();
+        for (entry, idx) in self.map.iter_mut().zip(self.compaction.indices()) {
+            *entry = if *idx < len {
+                base.get(*idx)
+            } else {
+                self.initial
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<M, T> Named for CompactedMapObserver<M, T> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<M, T> HasLen for CompactedMapObserver<M, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+impl<M, T> Hash for CompactedMapObserver<M, T>
+where
+    T: Hash,
+{
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.map.hash(hasher);
+    }
+}
+
+impl<M, T> AsRef<Self> for CompactedMapObserver<M, T> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl<M, T> AsMut<Self> for CompactedMapObserver<M, T> {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+impl<M, T> Deref for CompactedMapObserver<M, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.map
+    }
+}
+
+impl<M, T> DerefMut for CompactedMapObserver<M, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.map
+    }
+}
+
+impl<M, T> MapObserver for CompactedMapObserver<M, T>
+where
+    T: PartialEq + Copy + Hash + Serialize + DeserializeOwned + Debug,
+{
+    type Entry = T;
+
+    #[inline]
+    fn get(&self, idx: usize) -> T {
+        self.map[idx]
+    }
+
+    #[inline]
+    fn set(&mut self, idx: usize, val: T) {
+        self.map[idx] = val;
+    }
+
+    fn count_bytes(&self) -> u64 {
+        let initial = self.initial;
+        self.map.iter().filter(|x| **x != initial).count() as u64
+    }
+
+    #[inline]
+    fn usable_count(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    fn hash_simple(&self) -> u64 {
+        RandomState::with_seeds(0, 0, 0, 0).hash_one(self)
+    }
+
+    #[inline]
+    fn initial(&self) -> T {
+        self.initial
+    }
+
+    fn reset_map(&mut self) -> Result<(), Error> {
+        let initial = self.initial;
+        self.map.fill(initial);
+        Ok(())
+    }
+
+    fn to_vec(&self) -> Vec<T> {
+        self.map.clone()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        let initial = self.initial;
+        indexes
+            .iter()
+            .filter(|i| self.map.get(**i).is_some_and(|x| *x != initial))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::HasLen;
+
+    use super::{CompactedMapObserver, MapCompaction, MapCompactionBuilder};
+    use crate::{
+        executors::ExitKind,
+        observers::{MapObserver, Observer, StdMapObserver},
+    };
+
+    #[test]
+    fn test_compacted_map_observer() {
+        let mut builder = MapCompactionBuilder::new(8);
+        let mut calibration = StdMapObserver::owned("edges", vec![0_u8; 8]);
+        calibration.set(1, 1);
+        calibration.set(6, 3);
+        builder.observe(&calibration);
+        builder.mark(3);
+        let compaction = builder.build();
+        assert_eq!(compaction, MapCompaction::new(vec![6, 1, 3], 8));
+        assert_eq!(compaction.compacted_index(6), Some(2));
+        assert_eq!(compaction.compacted_index(0), None);
+
+        let base = StdMapObserver::owned("edges", vec![0_u8; 8]);
+        let mut observer = CompactedMapObserver::new(base, compaction);
+        assert_eq!(observer.len(), 3);
+
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        observer.base_mut().unwrap().set(6, 2);
+        observer.base_mut().unwrap().set(7, 1);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(observer.to_vec(), vec![0, 0, 2]);
+        assert_eq!(observer.count_bytes(), 1);
+    }
+
+    #[test]
+    fn test_compacted_map_observer_shrunk_base() {
+        let base = StdMapObserver::owned("edges", vec![0_u8; 4]);
+        let mut observer = CompactedMapObserver::new(base, MapCompaction::new(vec![1, 6], 8));
+
+        Observer::<(), ()>::pre_exec(&mut observer, &mut (), &()).unwrap();
+        observer.base_mut().unwrap().set(1, 1);
+        Observer::<(), ()>::post_exec(&mut observer, &mut (), &(), &ExitKind::Ok).unwrap();
+        assert_eq!(observer.to_vec(), vec![1, 0]);
+    }
+}
@@ -14,7 +14,8 @@ use serde::{Deserialize, Serialize};
 use crate::{
     executors::ExitKind,
     observers::{
-        map::MapObserver, ConstLenMapObserver, DifferentialObserver, Observer, VarLenMapObserver,
+        map::MapObserver, ConstLenMapObserver, DifferentialObserver, Observer,
+        ResizableMapObserver, VarLenMapObserver,
     },
     Error,
 };
@@ -278,6 +279,19 @@ where
     }
 }
 
+impl<M> ResizableMapObserver for HitcountsMapObserver<M>
+where
+    M: ResizableMapObserver + MapObserver<Entry = u8>,
+{
+    fn resize(&mut self, new_len: usize) -> Result<(), Error> {
+        self.base.resize(new_len)
+    }
+
+    unsafe fn remap(&mut self, map_ptr: *mut u8, len: usize) {
+        self.base.remap(map_ptr, len);
+    }
+}
+
 impl<'a, M> AsSlice<'a> for HitcountsMapObserver<M>
 where
     M: AsSlice<'a>,
@@ -466,6 +480,19 @@ where
     }
 }
 
+impl<M> ResizableMapObserver for HitcountsIterableMapObserver<M>
+where
+    M: ResizableMapObserver + MapObserver<Entry = u8>,
+{
+    fn resize(&mut self, new_len: usize) -> Result<(), Error> {
+        self.base.resize(new_len)
+    }
+
+    unsafe fn remap(&mut self, map_ptr: *mut u8, len: usize) {
+        self.base.remap(map_ptr, len);
+    }
+}
+
 impl<M, OTA, OTB, I, S> DifferentialObserver<OTA, OTB, I, S> for HitcountsIterableMapObserver<M>
 where
     M: DifferentialObserver<OTA, OTB, I, S>
@@ -32,6 +32,9 @@ pub use multi_map::*;
 pub mod owned_map;
 pub use owned_map::*;
 
+pub mod compacted_map;
+pub use compacted_map::*;
+
 /// A trait indicating tracking of observed map values after testcase execution
 ///
 /// Trait marker which indicates that this [`MapObserver`] is tracked for indices or novelties.
@@ -415,6 +418,28 @@ pub trait ConstLenMapObserver<const N: usize>: MapObserver {
     fn map_slice_mut(&mut self) -> &mut [Self::Entry; N];
 }
 
+/// A [`MapObserver`] whose size may be changed at runtime, for example after a map size handshake
+/// with a forkserver, or after the target loaded additional modules.
+///
+/// Feedbacks such as [`crate::feedbacks::MapFeedback`] grow their history map on demand,
+/// so resizing the observer between runs is always safe for them.
+pub trait ResizableMapObserver: MapObserver {
+    /// Resize the map to `new_len` entries.
+    ///
+    /// New entries are set to [`MapObserver::initial`].
+    /// Maps backed by memory we do not own (e.g., a shared memory region of the target)
+    /// can only shrink, use [`ResizableMapObserver::remap`] to grow them.
+    fn resize(&mut self, new_len: usize) -> Result<(), Error>;
+
+    /// Point this map to a new memory region of `len` entries, e.g. after the target re-allocated its
+    /// coverage map.
+    ///
+    /// # Safety
+    /// Will dereference the `map_ptr` with up to len elements.
+    /// The map must not move in memory!
+    unsafe fn remap(&mut self, map_ptr: *mut Self::Entry, len: usize);
+}
+
 impl<M> CanTrack for M
 where
     M: MapObserver,
@@ -573,6 +598,28 @@ impl<T, const DIFFERENTIAL: bool> Truncate for StdMapObserver<'_, T, DIFFERENTIA
     }
 }
 
+impl<T, const DIFFERENTIAL: bool> ResizableMapObserver for StdMapObserver<'_, T, DIFFERENTIAL>
+where
+    T: PartialEq + Copy + Hash + Serialize + DeserializeOwned + Debug,
+{
+    fn resize(&mut self, new_len: usize) -> Result<(), Error> {
+        let initial = self.initial;
+        if self.map.resize(new_len, initial).is_some() {
+            Ok(())
+        } else {
+            Err(Error::illegal_argument(format!(
+                "Cannot grow the borrowed map of {} from {} to {new_len} entries, use remap instead",
+                self.name,
+                self.map.len()
+            )))
+        }
+    }
+
+    unsafe fn remap(&mut self, map_ptr: *mut T, len: usize) {
+        self.map = OwnedMutSlice::from_raw_parts_mut(map_ptr, len);
+    }
+}
+
 impl<T, const DIFFERENTIAL: bool> Deref for StdMapObserver<'_, T, DIFFERENTIAL> {
     type Target = [T];
     fn deref(&self) -> &[T] {
@@ -7,7 +7,26 @@ pub use cmp::*;
 #[cfg(feature = "std")]
 pub mod stdio;
 #[cfg(feature = "std")]
-pub use stdio::{StdErrObserver, StdOutObserver};
+pub use stdio::{
+    StdErrObserver, StdOutObserver, DEFAULT_STDIO_CAPTURE_LIMIT, STDIO_TRUNCATION_MARKER,
+};
+
+#[cfg(all(feature = "std", unix))]
+pub mod coredump;
+#[cfg(all(feature = "std", unix))]
+pub use coredump::{CoreDumpObserver, CoreDumpSummary};
+#[cfg(all(feature = "std", unix))]
+pub mod crash_context;
+#[cfg(all(feature = "std", unix))]
+pub use crash_context::{CrashContext, CrashContextObserver, CrashFrame, CrashLocal};
+#[cfg(feature = "std")]
+pub mod remote;
+#[cfg(feature = "std")]
+pub use remote::{RemoteObserver, RemoteObserversTuple};
+#[cfg(feature = "std")]
+pub mod crash_state;
+#[cfg(feature = "std")]
+pub use crash_state::{record_crash_state, CrashMemoryRegion, CrashState, CrashStateObserver};
 
 #[cfg(feature = "regex")]
 pub mod stacktrace;
@@ -26,6 +45,9 @@ pub use map::*;
 
 pub mod value;
 
+pub mod syscalls;
+pub use syscalls::{SyscallEvent, SyscallObserver, SyscallRecord};
+
 /// List observer
 pub mod list;
 use core::{fmt::Debug, time::Duration};
@@ -408,7 +430,7 @@ mod tests {
         Named,
     };
 
-    use crate::observers::{StdMapObserver, TimeObserver};
+    use crate::observers::{MapObserver, ResizableMapObserver, StdMapObserver, TimeObserver};
 
     static mut MAP: [u32; 4] = [0; 4];
 
@@ -428,4 +450,19 @@ mod tests {
             postcard::from_bytes(&vec).unwrap();
         assert_eq!(obv.0.name(), obv2.0.name());
     }
+
+    #[test]
+    fn test_map_observer_resize() {
+        let mut obv = StdMapObserver::owned("map", vec![1_u8; 4]);
+        obv.resize(8).unwrap();
+        assert_eq!(obv.to_vec(), vec![1, 1, 1, 1, 0, 0, 0, 0]);
+        obv.resize(2).unwrap();
+        assert_eq!(obv.usable_count(), 2);
+
+        let mut map = [0_u8; 4];
+        let mut obv = unsafe { StdMapObserver::new("map", &mut map) };
+        assert!(obv.resize(8).is_err());
+        obv.resize(3).unwrap();
+        assert_eq!(obv.usable_count(), 3);
+    }
 }
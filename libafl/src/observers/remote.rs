@@ -0,0 +1,173 @@
+//! Observers that take over the state of the same observers after a run in another process.
+//!
+//! Executors running the target elsewhere, such as the [`crate::executors::InProcessRelaunchExecutor`],
+//! or the `RemoteExecutor` on a remote worker, get the observers of the run back serialized.
+//! Replacing the local observers with the deserialized ones would lose what is not serialized,
+//! most importantly the memory the map observers point to, so the remote state is merged instead.
+
+use libafl_bolts::Named;
+
+use crate::{
+    observers::{
+        ConstMapObserver, HitcountsMapObserver, MapObserver, OwnedMapObserver, StdErrObserver,
+        StdMapObserver, StdOutObserver, TimeObserver, VarLenMapObserver, VariableMapObserver,
+    },
+    Error,
+};
+
+/// An observer that can take over the state of the same observer after a run in another process,
+/// see the [module-level docs](self).
+pub trait RemoteObserver {
+    /// Takes over the state of `remote`, as deserialized from the worker, keeping the local state
+    /// that is not serialized, such as the memory the observer points to.
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error>;
+}
+
+/// A tuple of [`RemoteObserver`]s
+pub trait RemoteObserversTuple {
+    /// Takes over the state of all `remote` observers, see [`RemoteObserver::merge_remote`]
+    fn merge_remote_all(&mut self, remote: &Self) -> Result<(), Error>;
+}
+
+impl RemoteObserversTuple for () {
+    fn merge_remote_all(&mut self, _remote: &Self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail> RemoteObserversTuple for (Head, Tail)
+where
+    Head: RemoteObserver,
+    Tail: RemoteObserversTuple,
+{
+    fn merge_remote_all(&mut self, remote: &Self) -> Result<(), Error> {
+        self.0.merge_remote(&remote.0)?;
+        self.1.merge_remote_all(&remote.1)
+    }
+}
+
+/// Copies the entries of the `remote` map into the `local` map, which must have the same size
+fn merge_remote_map<M>(local: &mut M, remote: &M) -> Result<(), Error>
+where
+    M: MapObserver,
+{
+    let len = remote.usable_count();
+    if local.usable_count() != len {
+        return Err(Error::illegal_state(format!(
+            "The map {} has {len} entries on the worker, but {} locally",
+            remote.name(),
+            local.usable_count()
+        )));
+    }
+    for i in 0..len {
+        local.set(i, remote.get(i));
+    }
+    Ok(())
+}
+
+impl<T, const DIFFERENTIAL: bool> RemoteObserver for StdMapObserver<'_, T, DIFFERENTIAL>
+where
+    Self: MapObserver,
+{
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        merge_remote_map(self, remote)
+    }
+}
+
+impl<T, const N: usize> RemoteObserver for ConstMapObserver<'_, T, N>
+where
+    Self: MapObserver,
+{
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        merge_remote_map(self, remote)
+    }
+}
+
+impl<T> RemoteObserver for OwnedMapObserver<T>
+where
+    Self: MapObserver,
+{
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        merge_remote_map(self, remote)
+    }
+}
+
+impl<T> RemoteObserver for VariableMapObserver<'_, T>
+where
+    Self: VarLenMapObserver,
+{
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        let size = *remote.size();
+        let map = self.map_slice_mut();
+        if size > map.len() {
+            return Err(Error::illegal_state(format!(
+                "The map {} has {size} entries on the worker, but only room for {} locally",
+                remote.name(),
+                map.len()
+            )));
+        }
+        map[..size].copy_from_slice(&remote.map_slice()[..size]);
+        *self.size_mut() = size;
+        Ok(())
+    }
+}
+
+impl<M> RemoteObserver for HitcountsMapObserver<M>
+where
+    M: RemoteObserver,
+{
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        (**self).merge_remote(&**remote)
+    }
+}
+
+impl RemoteObserver for TimeObserver {
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        self.clone_from(remote);
+        Ok(())
+    }
+}
+
+impl RemoteObserver for StdOutObserver {
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        self.clone_from(remote);
+        Ok(())
+    }
+}
+
+impl RemoteObserver for StdErrObserver {
+    fn merge_remote(&mut self, remote: &Self) -> Result<(), Error> {
+        self.clone_from(remote);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::RemoteObserversTuple;
+    use crate::observers::{HitcountsMapObserver, StdMapObserver};
+
+    #[test]
+    fn test_merge_remote_map() {
+        let mut map = vec![0_u8; 4];
+        let mut local = tuple_list!(HitcountsMapObserver::new(unsafe {
+            StdMapObserver::new("map", &mut map)
+        }));
+        let remote = tuple_list!(HitcountsMapObserver::new(StdMapObserver::owned(
+            "map",
+            vec![1_u8, 0, 2, 0]
+        )));
+        local.merge_remote_all(&remote).unwrap();
+        drop(local);
+        // The local map, e.g., in shared memory, got the coverage of the worker
+        assert_eq!(map, [1, 0, 2, 0]);
+
+        let mut short_map = vec![0_u8; 2];
+        let mut local = tuple_list!(HitcountsMapObserver::new(unsafe {
+            StdMapObserver::new("map", &mut short_map)
+        }));
+        assert!(local.merge_remote_all(&remote).is_err());
+    }
+}
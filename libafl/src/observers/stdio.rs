@@ -6,15 +6,54 @@
     all(feature = "std", unix),
     doc = r"For example, they are supported on the [`crate::executors::CommandExecutor`]."
 )]
+#![cfg_attr(
+    all(feature = "std", feature = "fork", unix),
+    doc = r"The [`crate::executors::ForkserverExecutor`] supports them as well."
+)]
+//! The captured output is bounded by a per-observer limit, see [`StdOutObserver::with_limit`].
 
 use alloc::borrow::Cow;
-use std::vec::Vec;
+use std::{
+    io::{self, Read},
+    vec::Vec,
+};
 
 use libafl_bolts::Named;
 use serde::{Deserialize, Serialize};
 
 use crate::{observers::Observer, Error};
 
+/// The default maximum amount of bytes a [`StdOutObserver`] or [`StdErrObserver`] keeps per run
+pub const DEFAULT_STDIO_CAPTURE_LIMIT: usize = 1 << 20;
+
+/// Appended to the captured output if the target wrote more than the capture limit
+pub const STDIO_TRUNCATION_MARKER: &[u8] = b"\n[... output truncated by LibAFL ...]\n";
+
+/// Keeps at most `limit` bytes of `output`, marking the truncation
+fn truncate_output(output: &[u8], limit: usize) -> Vec<u8> {
+    if output.len() <= limit {
+        return output.into();
+    }
+    let mut truncated = Vec::with_capacity(limit + STDIO_TRUNCATION_MARKER.len());
+    truncated.extend_from_slice(&output[..limit]);
+    truncated.extend_from_slice(STDIO_TRUNCATION_MARKER);
+    truncated
+}
+
+/// Reads the `reader` to its end, keeping at most `limit` bytes and marking the truncation
+fn read_output<R: Read>(reader: &mut R, limit: usize) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    reader
+        .by_ref()
+        .take(limit as u64)
+        .read_to_end(&mut output)?;
+    // Drain the rest, so the target never blocks on a full pipe
+    if io::copy(reader, &mut io::sink())? > 0 {
+        output.extend_from_slice(STDIO_TRUNCATION_MARKER);
+    }
+    Ok(output)
+}
+
 /// An observer that captures stdout of a target.
 /// Only works for supported executors.
 ///
@@ -177,6 +216,8 @@ pub struct StdOutObserver {
     pub name: Cow<'static, str>,
     /// The stdout of the target during its last execution.
     pub stdout: Option<Vec<u8>>,
+    /// The maximum amount of bytes kept per execution, see [`STDIO_TRUNCATION_MARKER`]
+    pub limit: usize,
 }
 
 /// An observer that captures stdout of a target.
@@ -187,12 +228,27 @@ impl StdOutObserver {
         Self {
             name: Cow::from(name),
             stdout: None,
+            limit: DEFAULT_STDIO_CAPTURE_LIMIT,
         }
     }
 
+    /// Sets the maximum amount of bytes kept per execution.
+    /// Longer output is truncated and ends with the [`STDIO_TRUNCATION_MARKER`].
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
     /// React to new `stdout`
     pub fn observe_stdout(&mut self, stdout: &[u8]) {
-        self.stdout = Some(stdout.into());
+        self.stdout = Some(truncate_output(stdout, self.limit));
+    }
+
+    /// React to new `stdout`, read from the given reader until its end
+    pub fn observe_stdout_from<R: Read>(&mut self, reader: &mut R) -> Result<(), Error> {
+        self.stdout = Some(read_output(reader, self.limit)?);
+        Ok(())
     }
 }
 
@@ -224,6 +280,8 @@ pub struct StdErrObserver {
     pub name: Cow<'static, str>,
     /// The stderr of the target during its last execution.
     pub stderr: Option<Vec<u8>>,
+    /// The maximum amount of bytes kept per execution, see [`STDIO_TRUNCATION_MARKER`]
+    pub limit: usize,
 }
 
 /// An observer that captures stderr of a target.
@@ -234,12 +292,27 @@ impl StdErrObserver {
         Self {
             name: Cow::from(name),
             stderr: None,
+            limit: DEFAULT_STDIO_CAPTURE_LIMIT,
         }
     }
 
+    /// Sets the maximum amount of bytes kept per execution.
+    /// Longer output is truncated and ends with the [`STDIO_TRUNCATION_MARKER`].
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
     /// React to new `stderr`
     pub fn observe_stderr(&mut self, stderr: &[u8]) {
-        self.stderr = Some(stderr.into());
+        self.stderr = Some(truncate_output(stderr, self.limit));
+    }
+
+    /// React to new `stderr`, read from the given reader until its end
+    pub fn observe_stderr_from<R: Read>(&mut self, reader: &mut R) -> Result<(), Error> {
+        self.stderr = Some(read_output(reader, self.limit)?);
+        Ok(())
     }
 }
 
@@ -260,3 +333,29 @@ impl<I, S> Observer<I, S> for StdErrObserver {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StdErrObserver, StdOutObserver, STDIO_TRUNCATION_MARKER};
+
+    #[test]
+    fn test_stdio_truncation() {
+        let mut stdout = StdOutObserver::new("stdout").with_limit(4);
+        stdout.observe_stdout(b"abcd");
+        assert_eq!(stdout.stdout.as_deref(), Some(&b"abcd"[..]));
+        stdout.observe_stdout(b"abcdef");
+        assert_eq!(
+            stdout.stdout.unwrap(),
+            [&b"abcd"[..], STDIO_TRUNCATION_MARKER].concat()
+        );
+
+        let mut stderr = StdErrObserver::new("stderr").with_limit(2);
+        stderr.observe_stderr_from(&mut &b"ab"[..]).unwrap();
+        assert_eq!(stderr.stderr.as_deref(), Some(&b"ab"[..]));
+        stderr.observe_stderr_from(&mut &b"abc"[..]).unwrap();
+        assert_eq!(
+            stderr.stderr.unwrap(),
+            [&b"ab"[..], STDIO_TRUNCATION_MARKER].concat()
+        );
+    }
+}
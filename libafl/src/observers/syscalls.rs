@@ -0,0 +1,141 @@
+//! The [`SyscallObserver`] collects the system calls issued by the target during a run.
+//!
+//! The observer does not trace anything by itself, it is filled by an external tracer,
+//! for example a `ptrace`-based executor or a QEMU module.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::{Error, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::observers::Observer;
+
+/// A single system call issued by the target
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SyscallRecord {
+    /// The system call number
+    pub nr: u64,
+    /// The error number returned by the system call, `0` if it succeeded
+    pub errno: i32,
+}
+
+impl SyscallRecord {
+    /// Creates a new [`SyscallRecord`]
+    #[must_use]
+    pub fn new(nr: u64, errno: i32) -> Self {
+        Self { nr, errno }
+    }
+
+    /// Creates a new [`SyscallRecord`] from the raw return value of a Linux system call,
+    /// where values in `-4095..0` encode an error number.
+    #[must_use]
+    pub fn from_linux_return(nr: u64, ret: i64) -> Self {
+        let errno = if (-4095..0).contains(&ret) {
+            -ret as i32
+        } else {
+            0
+        };
+        Self { nr, errno }
+    }
+}
+
+/// A system call issued by the target, with its arguments and raw return value, for tracers that see them
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SyscallEvent {
+    /// The system call number
+    pub nr: u64,
+    /// The first six arguments
+    pub args: [u64; 6],
+    /// The raw return value
+    pub ret: i64,
+}
+
+impl SyscallEvent {
+    /// The [`SyscallRecord`] of this Linux system call
+    #[must_use]
+    pub fn record(&self) -> SyscallRecord {
+        SyscallRecord::from_linux_return(self.nr, self.ret)
+    }
+}
+
+/// An observer collecting the system calls of a run, filled by an external tracer
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyscallObserver {
+    name: Cow<'static, str>,
+    syscalls: Vec<SyscallRecord>,
+    events: Vec<SyscallEvent>,
+    violations: Vec<SyscallRecord>,
+}
+
+impl SyscallObserver {
+    /// Creates a new [`SyscallObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            syscalls: Vec::new(),
+            events: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Records a system call of the current run
+    pub fn record(&mut self, record: SyscallRecord) {
+        self.syscalls.push(record);
+    }
+
+    /// Replaces the system calls of the current run, e.g., with the trace of an external tracer
+    pub fn fill_external(&mut self, syscalls: &[SyscallRecord]) {
+        self.syscalls.clear();
+        self.syscalls.extend_from_slice(syscalls);
+    }
+
+    /// Replaces the system calls of the current run with the events of an external tracer,
+    /// keeping their arguments and return values
+    pub fn fill_external_events(&mut self, events: &[SyscallEvent]) {
+        self.events.clear();
+        self.events.extend_from_slice(events);
+        self.syscalls.clear();
+        self.syscalls
+            .extend(events.iter().map(SyscallEvent::record));
+    }
+
+    /// The system calls of the last run, in order
+    #[must_use]
+    pub fn syscalls(&self) -> &[SyscallRecord] {
+        &self.syscalls
+    }
+
+    /// The system calls of the last run with their arguments and return values, in order,
+    /// if the tracer records them
+    #[must_use]
+    pub fn events(&self) -> &[SyscallEvent] {
+        &self.events
+    }
+
+    /// Records a system call of the current run that violated the policy of the tracer
+    pub fn record_violation(&mut self, record: SyscallRecord) {
+        self.violations.push(record);
+    }
+
+    /// The system calls of the last run that violated the policy of the tracer, in order
+    #[must_use]
+    pub fn violations(&self) -> &[SyscallRecord] {
+        &self.violations
+    }
+}
+
+impl<I, S> Observer<I, S> for SyscallObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.syscalls.clear();
+        self.events.clear();
+        self.violations.clear();
+        Ok(())
+    }
+}
+
+impl Named for SyscallObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
@@ -0,0 +1,392 @@
+//! Assembles a forkserver-based fuzzer from the components picked in Python
+
+use alloc::{string::String, vec::Vec};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use libafl_bolts::{
+    core_affinity::Cores,
+    current_nanos, nonzero,
+    rands::StdRand,
+    shmem::{ShMem, ShMemProvider, StdShMemProvider, UnixShMemProvider},
+    tuples::{tuple_list, Merge},
+    AsSliceMut,
+};
+
+use crate::{
+    corpus::{CachedOnDiskCorpus, Corpus, OnDiskCorpus},
+    events::{
+        launcher::Launcher, EventConfig, EventFirer, EventRestarter, LlmpRestartingEventManager,
+        ProgressReporter, SimpleEventManager,
+    },
+    executors::{command::CommandExecutor, forkserver::ForkserverExecutor},
+    feedback_and_fast, feedback_or, feedback_or_fast,
+    feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, TimeFeedback, TimeoutFeedback},
+    fuzzer::{Evaluator, Fuzzer, HasFeedback, StdFuzzer},
+    generators::RandBytesGenerator,
+    inputs::BytesInput,
+    monitors::{MultiMonitor, SimpleMonitor},
+    mutators::{havoc_mutations, tokens_mutations, StdScheduledMutator, Tokens},
+    observers::{CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver},
+    schedulers::{
+        powersched::PowerSchedule, IndexesLenTimeMinimizerScheduler, QueueScheduler,
+        StdWeightedScheduler,
+    },
+    stages::{CalibrationStage, StagesTuple, StdMutationalStage},
+    state::{HasCorpus, StdState, UsesState},
+    Error, HasMetadata,
+};
+
+/// The number of testcases kept in memory, the others only live on disk
+const CORPUS_CACHE_SIZE: usize = 4096;
+
+/// The state of fuzzers assembled from Python
+pub(crate) type PythonState =
+    StdState<BytesInput, CachedOnDiskCorpus<BytesInput>, StdRand, OnDiskCorpus<BytesInput>>;
+
+/// How the clients of the fuzzer communicate
+#[derive(Debug, Clone)]
+pub(crate) enum EventManagerConfig {
+    /// A single client in this process, see [`SimpleEventManager`]
+    Simple,
+    /// A client for each core, connected by llmp, see [`Launcher`]
+    Llmp {
+        cores: Cores,
+        broker_port: u16,
+        remote_broker_addr: Option<SocketAddr>,
+        configuration: Option<String>,
+    },
+}
+
+/// How the target runs.
+/// Either way, the target writes its coverage to the shared map in `__AFL_SHM_ID`, like AFL++ targets do.
+#[derive(Debug, Clone)]
+pub(crate) enum ExecutorConfig {
+    /// See [`ForkserverExecutor`]
+    Forkserver {
+        program: String,
+        arguments: Vec<String>,
+        timeout: Duration,
+        map_size: usize,
+        persistent: bool,
+        shmem_testcase: bool,
+        debug_child: bool,
+    },
+    /// See [`CommandExecutor`], `@@` in the arguments is replaced by the input file,
+    /// the target reads from stdin otherwise
+    Command {
+        program: String,
+        arguments: Vec<String>,
+        timeout: Duration,
+        map_size: usize,
+        debug_child: bool,
+    },
+}
+
+impl ExecutorConfig {
+    fn map_size(&self) -> usize {
+        match self {
+            ExecutorConfig::Forkserver { map_size, .. }
+            | ExecutorConfig::Command { map_size, .. } => *map_size,
+        }
+    }
+}
+
+/// How the next testcase to fuzz is picked
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SchedulerConfig {
+    /// See [`QueueScheduler`]
+    Queue,
+    /// See [`StdWeightedScheduler`]
+    Weighted(PowerSchedule),
+}
+
+/// A whole fuzzer, as assembled in Python
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct FuzzerConfig {
+    pub(crate) event_manager: EventManagerConfig,
+    pub(crate) executor: ExecutorConfig,
+    pub(crate) scheduler: SchedulerConfig,
+    /// Wrap the scheduler in an [`IndexesLenTimeMinimizerScheduler`]
+    pub(crate) minimize: bool,
+    /// Let the calibration find unstable map entries, which the map feedback then ignores
+    pub(crate) track_stability: bool,
+    pub(crate) crash_objective: bool,
+    pub(crate) timeout_objective: bool,
+    pub(crate) input_dirs: Vec<PathBuf>,
+    pub(crate) output_dir: PathBuf,
+    pub(crate) tokens_file: Option<PathBuf>,
+    pub(crate) seed: Option<u64>,
+}
+
+/// The stages of a client, calibrating, and mutating with havoc and token mutations.
+/// The stages depend on the types of the fuzzer and the executor, so each combination builds its own.
+macro_rules! client_stages {
+    ($config:expr, $fuzzer:expr) => {
+        tuple_list!(
+            if $config.track_stability {
+                CalibrationStage::new(&$fuzzer.feedback().first)
+            } else {
+                CalibrationStage::ignore_stability(&$fuzzer.feedback().first)
+            },
+            StdMutationalStage::new(StdScheduledMutator::new(
+                havoc_mutations().merge(tokens_mutations())
+            ))
+        )
+    };
+}
+
+/// Builds the components of a client and fuzzes, with the event manager `$mgr`,
+/// starting from the state `$state` if the client restarted.
+/// Set `$exit` in clients that should exit the process once `$iterations` are done.
+macro_rules! fuzz_client {
+    ($config:expr, $state:expr, $mgr:expr, $iterations:expr, $exit:expr) => {{
+        let config: &FuzzerConfig = $config;
+        let mut mgr = $mgr;
+        let map_size = config.executor.map_size();
+
+        // The coverage map shared with the target, which AFL++ targets find in `__AFL_SHM_ID`
+        let mut shmem_provider = UnixShMemProvider::new()?;
+        let mut shmem = shmem_provider.new_shmem(map_size)?;
+        shmem.write_to_env("__AFL_SHM_ID")?;
+        let shmem_map = shmem.as_slice_mut();
+        // To let know the AFL++ binary that we have a big map
+        std::env::set_var("AFL_MAP_SIZE", format!("{map_size}"));
+
+        let edges_observer = unsafe {
+            HitcountsMapObserver::new(StdMapObserver::new("shared_mem", shmem_map)).track_indices()
+        };
+        let time_observer = TimeObserver::new("time");
+
+        let mut feedback = feedback_or!(
+            MaxMapFeedback::new(&edges_observer),
+            TimeFeedback::new(&time_observer)
+        );
+        let mut objective = feedback_or_fast!(
+            feedback_and_fast!(
+                ConstFeedback::new(config.crash_objective),
+                CrashFeedback::new()
+            ),
+            feedback_and_fast!(
+                ConstFeedback::new(config.timeout_objective),
+                TimeoutFeedback::new()
+            )
+        );
+
+        let mut state: PythonState = match $state {
+            Some(state) => state,
+            None => StdState::new(
+                StdRand::with_seed(config.seed.unwrap_or_else(current_nanos)),
+                CachedOnDiskCorpus::new(config.output_dir.join("queue"), CORPUS_CACHE_SIZE)?,
+                OnDiskCorpus::new(config.output_dir.join("crashes"))?,
+                &mut feedback,
+                &mut objective,
+            )?,
+        };
+
+        let mut tokens = Tokens::new();
+
+        // Each combination of scheduler and executor is a fuzzer of a different type
+        macro_rules! fuzz_with {
+            ($scheduler:expr) => {{
+                let mut fuzzer = StdFuzzer::new($scheduler, feedback, objective);
+                match &config.executor {
+                    ExecutorConfig::Forkserver {
+                        program,
+                        arguments,
+                        timeout,
+                        persistent,
+                        shmem_testcase,
+                        debug_child,
+                        ..
+                    } => {
+                        let builder = ForkserverExecutor::builder()
+                            .program(program.clone())
+                            .parse_afl_cmdline(arguments)
+                            .is_persistent(*persistent)
+                            .autotokens(&mut tokens)
+                            .coverage_map_size(map_size)
+                            .timeout(*timeout)
+                            .debug_child(*debug_child);
+                        let mut executor = if *shmem_testcase {
+                            builder
+                                .shmem_provider(&mut shmem_provider)
+                                .build_dynamic_map(edges_observer, tuple_list!(time_observer))?
+                        } else {
+                            builder.build_dynamic_map(edges_observer, tuple_list!(time_observer))?
+                        };
+                        let mut stages = client_stages!(config, fuzzer);
+                        fuzz(
+                            config,
+                            &mut fuzzer,
+                            &mut executor,
+                            &mut state,
+                            &mut mgr,
+                            &mut stages,
+                            tokens,
+                            $iterations,
+                            $exit,
+                        )
+                    }
+                    ExecutorConfig::Command {
+                        program,
+                        arguments,
+                        timeout,
+                        debug_child,
+                        ..
+                    } => {
+                        let mut builder = CommandExecutor::builder();
+                        builder
+                            .program(program)
+                            .timeout(*timeout)
+                            .debug_child(*debug_child);
+                        for argument in arguments {
+                            if argument == "@@" {
+                                builder.arg_input_file_std();
+                            } else {
+                                builder.arg(argument);
+                            }
+                        }
+                        let mut executor =
+                            builder.build(tuple_list!(edges_observer, time_observer))?;
+                        let mut stages = client_stages!(config, fuzzer);
+                        fuzz(
+                            config,
+                            &mut fuzzer,
+                            &mut executor,
+                            &mut state,
+                            &mut mgr,
+                            &mut stages,
+                            tokens,
+                            $iterations,
+                            $exit,
+                        )
+                    }
+                }
+            }};
+        }
+
+        match (config.scheduler, config.minimize) {
+            (SchedulerConfig::Queue, false) => fuzz_with!(QueueScheduler::new()),
+            (SchedulerConfig::Queue, true) => fuzz_with!(IndexesLenTimeMinimizerScheduler::new(
+                &edges_observer,
+                QueueScheduler::new()
+            )),
+            (SchedulerConfig::Weighted(schedule), false) => fuzz_with!(
+                StdWeightedScheduler::with_schedule(&mut state, &edges_observer, Some(schedule))
+            ),
+            (SchedulerConfig::Weighted(schedule), true) => {
+                fuzz_with!(IndexesLenTimeMinimizerScheduler::new(
+                    &edges_observer,
+                    StdWeightedScheduler::with_schedule(
+                        &mut state,
+                        &edges_observer,
+                        Some(schedule)
+                    )
+                ))
+            }
+        }
+    }};
+}
+
+/// Runs the fuzzer, indefinitely, or for `iterations` in each client.
+/// The monitor output goes to `print_fn`.
+///
+/// With an LLMP event manager, the clients exit once their `iterations` are done,
+/// but the broker in this process keeps running, so this does not return.
+pub(crate) fn run<F>(
+    config: &FuzzerConfig,
+    print_fn: F,
+    iterations: Option<u64>,
+) -> Result<(), Error>
+where
+    F: FnMut(&str) + Clone,
+{
+    match &config.event_manager {
+        EventManagerConfig::Simple => {
+            let mgr = SimpleEventManager::new(SimpleMonitor::new(print_fn));
+            fuzz_client!(config, None, mgr, iterations, false)
+        }
+        EventManagerConfig::Llmp {
+            cores,
+            broker_port,
+            remote_broker_addr,
+            configuration,
+        } => {
+            let conf = match configuration {
+                Some(name) => EventConfig::from_name(name),
+                None => EventConfig::AlwaysUnique,
+            };
+
+            let mut run_client = |state: Option<_>,
+                                  mgr: LlmpRestartingEventManager<_, _, _>,
+                                  _client_description| {
+                fuzz_client!(config, state, mgr, iterations, true)
+            };
+
+            let launcher = Launcher::builder()
+                .shmem_provider(StdShMemProvider::new()?)
+                .configuration(conf)
+                .monitor(MultiMonitor::new(print_fn))
+                .run_client(&mut run_client)
+                .cores(cores)
+                .broker_port(*broker_port)
+                .remote_broker_addr(*remote_broker_addr);
+            launcher.build().launch()
+        }
+    }
+}
+
+/// Loads the initial inputs, if needed, and runs the fuzzing loop
+#[allow(clippy::too_many_arguments)]
+fn fuzz<E, EM, ST, Z>(
+    config: &FuzzerConfig,
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut PythonState,
+    mgr: &mut EM,
+    stages: &mut ST,
+    mut tokens: Tokens,
+    iterations: Option<u64>,
+    exit: bool,
+) -> Result<(), Error>
+where
+    E: UsesState<State = PythonState>,
+    EM: EventFirer<State = PythonState> + EventRestarter + ProgressReporter,
+    ST: StagesTuple<E, EM, PythonState, Z>,
+    Z: Fuzzer<E, EM, ST, State = PythonState> + Evaluator<E, EM, State = PythonState>,
+{
+    if let Some(tokens_file) = &config.tokens_file {
+        tokens.add_from_file(tokens_file)?;
+    }
+    if !tokens.is_empty() {
+        state.add_metadata(tokens);
+    }
+
+    if state.must_load_initial_inputs() {
+        if config.input_dirs.is_empty() {
+            // Generate 8 random initial inputs of up to 32 bytes
+            let mut generator = RandBytesGenerator::new(nonzero!(32));
+            state.generate_initial_inputs(fuzzer, executor, &mut generator, mgr, 8)?;
+            log::info!(
+                "We imported {} inputs from the generator.",
+                state.corpus().count()
+            );
+        } else {
+            state.load_initial_inputs(fuzzer, executor, mgr, &config.input_dirs)?;
+            log::info!("We imported {} inputs from disk.", state.corpus().count());
+        }
+    }
+
+    if let Some(iterations) = iterations {
+        fuzzer.fuzz_loop_for(stages, executor, state, mgr, iterations)?;
+        if exit {
+            // Tell the restarter not to respawn this client
+            mgr.send_exiting()?;
+            std::process::exit(0);
+        }
+    } else {
+        fuzzer.fuzz_loop(stages, executor, state, mgr)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,404 @@
+//! Python bindings for `LibAFL`, to assemble a whole forkserver-based fuzzer from Python,
+//! for quick prototyping.
+//!
+//! ```python
+//! from pylibafl import libafl
+//!
+//! fuzzer = libafl.Fuzzer(
+//!     executor=libafl.ForkserverExecutor("./target", ["@@"], timeout_ms=1000),
+//!     feedback=libafl.MaxMapFeedback(),
+//!     objectives=[libafl.CrashFeedback(), libafl.TimeoutFeedback()],
+//!     scheduler=libafl.MinimizerScheduler(libafl.WeightedScheduler("explore")),
+//!     event_manager=libafl.LlmpEventManager(cores=[0, 1]),
+//!     input_dirs=["./in"],
+//!     output_dir="./out",
+//! )
+//! fuzzer.run()
+//! ```
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use core::time::Duration;
+use std::{net::SocketAddr, path::PathBuf};
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+};
+
+use crate::{schedulers::powersched::PowerSchedule, Error};
+
+mod fuzzer;
+use fuzzer::{EventManagerConfig, ExecutorConfig, FuzzerConfig, SchedulerConfig};
+
+/// A single client, running in this process
+#[pyclass(unsendable, name = "SimpleEventManager")]
+#[derive(Debug, Clone)]
+pub struct PythonSimpleEventManager {}
+
+#[pymethods]
+impl PythonSimpleEventManager {
+    #[new]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+/// A client for each of the `cores`, and a broker connecting them over llmp
+#[pyclass(unsendable, name = "LlmpEventManager")]
+#[derive(Debug, Clone)]
+pub struct PythonLlmpEventManager {
+    inner: EventManagerConfig,
+}
+
+#[pymethods]
+impl PythonLlmpEventManager {
+    #[new]
+    #[pyo3(signature = (cores, broker_port=1337, remote_broker_addr=None, configuration=None))]
+    fn new(
+        cores: Vec<usize>,
+        broker_port: u16,
+        remote_broker_addr: Option<String>,
+        configuration: Option<String>,
+    ) -> PyResult<Self> {
+        let remote_broker_addr = remote_broker_addr
+            .map(|addr| addr.parse::<SocketAddr>())
+            .transpose()
+            .map_err(|e| PyValueError::new_err(format!("Invalid remote broker address: {e}")))?;
+        Ok(Self {
+            inner: EventManagerConfig::Llmp {
+                cores: cores.into(),
+                broker_port,
+                remote_broker_addr,
+                configuration,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, FromPyObject)]
+enum PythonEventManager {
+    Simple(PythonSimpleEventManager),
+    Llmp(PythonLlmpEventManager),
+}
+
+/// Runs an AFL++-instrumented target in a forkserver
+#[pyclass(unsendable, name = "ForkserverExecutor")]
+#[derive(Debug, Clone)]
+pub struct PythonForkserverExecutor {
+    inner: ExecutorConfig,
+}
+
+#[pymethods]
+impl PythonForkserverExecutor {
+    #[new]
+    #[pyo3(signature = (
+        program,
+        arguments,
+        timeout_ms=1000,
+        map_size=65536,
+        persistent=false,
+        shmem_testcase=false,
+        debug_child=false
+    ))]
+    fn new(
+        program: String,
+        arguments: Vec<String>,
+        timeout_ms: u64,
+        map_size: usize,
+        persistent: bool,
+        shmem_testcase: bool,
+        debug_child: bool,
+    ) -> Self {
+        Self {
+            inner: ExecutorConfig::Forkserver {
+                program,
+                arguments,
+                timeout: Duration::from_millis(timeout_ms),
+                map_size,
+                persistent,
+                shmem_testcase,
+                debug_child,
+            },
+        }
+    }
+}
+
+/// Runs an AFL++-instrumented target as a new process for each input.
+/// `@@` in the arguments is replaced by the input file, the target reads from stdin otherwise.
+#[pyclass(unsendable, name = "CommandExecutor")]
+#[derive(Debug, Clone)]
+pub struct PythonCommandExecutor {
+    inner: ExecutorConfig,
+}
+
+#[pymethods]
+impl PythonCommandExecutor {
+    #[new]
+    #[pyo3(signature = (program, arguments, timeout_ms=1000, map_size=65536, debug_child=false))]
+    fn new(
+        program: String,
+        arguments: Vec<String>,
+        timeout_ms: u64,
+        map_size: usize,
+        debug_child: bool,
+    ) -> Self {
+        Self {
+            inner: ExecutorConfig::Command {
+                program,
+                arguments,
+                timeout: Duration::from_millis(timeout_ms),
+                map_size,
+                debug_child,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromPyObject)]
+enum PythonExecutor {
+    Forkserver(PythonForkserverExecutor),
+    Command(PythonCommandExecutor),
+}
+
+/// Keeps inputs reaching new entries, or higher hitcounts, of the coverage map
+#[pyclass(unsendable, name = "MaxMapFeedback")]
+#[derive(Debug, Clone)]
+pub struct PythonMaxMapFeedback {
+    track_stability: bool,
+}
+
+#[pymethods]
+impl PythonMaxMapFeedback {
+    #[new]
+    #[pyo3(signature = (track_stability=true))]
+    fn new(track_stability: bool) -> Self {
+        Self { track_stability }
+    }
+}
+
+/// Inputs crashing the target are objectives
+#[pyclass(unsendable, name = "CrashFeedback")]
+#[derive(Debug, Clone)]
+pub struct PythonCrashFeedback {}
+
+#[pymethods]
+impl PythonCrashFeedback {
+    #[new]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Inputs timing out are objectives
+#[pyclass(unsendable, name = "TimeoutFeedback")]
+#[derive(Debug, Clone)]
+pub struct PythonTimeoutFeedback {}
+
+#[pymethods]
+impl PythonTimeoutFeedback {
+    #[new]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+#[derive(Debug, Clone, FromPyObject)]
+enum PythonObjective {
+    Crash(PythonCrashFeedback),
+    Timeout(PythonTimeoutFeedback),
+}
+
+/// Fuzzes the corpus entries in order
+#[pyclass(unsendable, name = "QueueScheduler")]
+#[derive(Debug, Clone)]
+pub struct PythonQueueScheduler {}
+
+#[pymethods]
+impl PythonQueueScheduler {
+    #[new]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+/// Picks the corpus entries at random, weighted by one of the AFL++ power schedules:
+/// `explore`, `exploit`, `fast`, `coe`, `lin` or `quad`
+#[pyclass(unsendable, name = "WeightedScheduler")]
+#[derive(Debug, Clone)]
+pub struct PythonWeightedScheduler {
+    schedule: PowerSchedule,
+}
+
+#[pymethods]
+impl PythonWeightedScheduler {
+    #[new]
+    #[pyo3(signature = (schedule="explore"))]
+    fn new(schedule: &str) -> PyResult<Self> {
+        let schedule = match schedule {
+            "explore" => PowerSchedule::explore(),
+            "exploit" => PowerSchedule::exploit(),
+            "fast" => PowerSchedule::fast(),
+            "coe" => PowerSchedule::coe(),
+            "lin" => PowerSchedule::lin(),
+            "quad" => PowerSchedule::quad(),
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown power schedule {schedule}"
+                )))
+            }
+        };
+        Ok(Self { schedule })
+    }
+}
+
+#[derive(Debug, Clone, FromPyObject)]
+enum PythonBaseScheduler {
+    Queue(PythonQueueScheduler),
+    Weighted(PythonWeightedScheduler),
+}
+
+impl PythonBaseScheduler {
+    fn config(&self) -> SchedulerConfig {
+        match self {
+            PythonBaseScheduler::Queue(_) => SchedulerConfig::Queue,
+            PythonBaseScheduler::Weighted(weighted) => SchedulerConfig::Weighted(weighted.schedule),
+        }
+    }
+}
+
+/// Favors the smallest and fastest corpus entries covering all of the coverage map,
+/// picking among them with the `base` scheduler
+#[pyclass(unsendable, name = "MinimizerScheduler")]
+#[derive(Debug, Clone)]
+pub struct PythonMinimizerScheduler {
+    base: SchedulerConfig,
+}
+
+#[pymethods]
+impl PythonMinimizerScheduler {
+    #[new]
+    fn new(base: PythonBaseScheduler) -> Self {
+        Self {
+            base: base.config(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromPyObject)]
+enum PythonScheduler {
+    Base(PythonBaseScheduler),
+    Minimizer(PythonMinimizerScheduler),
+}
+
+/// A whole fuzzer, fuzzing `BytesInput`s.
+/// The corpus goes to `output_dir/queue`, the objectives to `output_dir/crashes`.
+/// Without `input_dirs`, the fuzzer starts from random inputs.
+/// The monitor output goes to the `monitor` callable, or to the log.
+#[pyclass(unsendable, name = "Fuzzer")]
+#[derive(Debug)]
+pub struct PythonFuzzer {
+    config: FuzzerConfig,
+    monitor: Option<Arc<PyObject>>,
+}
+
+#[pymethods]
+impl PythonFuzzer {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (
+        executor,
+        feedback,
+        objectives,
+        scheduler,
+        event_manager,
+        output_dir,
+        input_dirs=Vec::new(),
+        tokens_file=None,
+        seed=None,
+        monitor=None
+    ))]
+    fn new(
+        executor: PythonExecutor,
+        feedback: PythonMaxMapFeedback,
+        objectives: Vec<PythonObjective>,
+        scheduler: PythonScheduler,
+        event_manager: PythonEventManager,
+        output_dir: PathBuf,
+        input_dirs: Vec<PathBuf>,
+        tokens_file: Option<PathBuf>,
+        seed: Option<u64>,
+        monitor: Option<PyObject>,
+    ) -> Self {
+        let (scheduler, minimize) = match scheduler {
+            PythonScheduler::Base(base) => (base.config(), false),
+            PythonScheduler::Minimizer(minimizer) => (minimizer.base, true),
+        };
+        Self {
+            config: FuzzerConfig {
+                event_manager: match event_manager {
+                    PythonEventManager::Simple(_) => EventManagerConfig::Simple,
+                    PythonEventManager::Llmp(llmp) => llmp.inner,
+                },
+                executor: match executor {
+                    PythonExecutor::Forkserver(executor) => executor.inner,
+                    PythonExecutor::Command(executor) => executor.inner,
+                },
+                scheduler,
+                minimize,
+                track_stability: feedback.track_stability,
+                crash_objective: objectives
+                    .iter()
+                    .any(|objective| matches!(objective, PythonObjective::Crash(_))),
+                timeout_objective: objectives
+                    .iter()
+                    .any(|objective| matches!(objective, PythonObjective::Timeout(_))),
+                input_dirs,
+                output_dir,
+                tokens_file,
+                seed,
+            },
+            monitor: monitor.map(Arc::new),
+        }
+    }
+
+    /// Runs the fuzzer, indefinitely, or for `iterations` in each client.
+    /// The GIL is released while fuzzing, and only taken to call the `monitor`.
+    ///
+    /// With a `LlmpEventManager`, the clients exit once their `iterations` are done, but the broker
+    /// in this process keeps running, so this only returns with a `SimpleEventManager`.
+    #[pyo3(signature = (iterations=None))]
+    fn run(&self, py: Python<'_>, iterations: Option<u64>) -> PyResult<()> {
+        let monitor = self.monitor.clone();
+        let print_fn = move |s: &str| match &monitor {
+            Some(monitor) => Python::with_gil(|py| {
+                if let Err(e) = monitor.call1(py, (s,)) {
+                    e.print(py);
+                }
+            }),
+            None => log::info!("{s}"),
+        };
+        let config = &self.config;
+        match py.allow_threads(move || fuzzer::run(config, print_fn, iterations)) {
+            Ok(()) | Err(Error::ShuttingDown) => Ok(()),
+            Err(e) => Err(PyRuntimeError::new_err(format!("Fuzzing failed: {e}"))),
+        }
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "libafl")]
+/// Register the classes to the python module
+pub fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PythonSimpleEventManager>()?;
+    m.add_class::<PythonLlmpEventManager>()?;
+    m.add_class::<PythonForkserverExecutor>()?;
+    m.add_class::<PythonCommandExecutor>()?;
+    m.add_class::<PythonMaxMapFeedback>()?;
+    m.add_class::<PythonCrashFeedback>()?;
+    m.add_class::<PythonTimeoutFeedback>()?;
+    m.add_class::<PythonQueueScheduler>()?;
+    m.add_class::<PythonWeightedScheduler>()?;
+    m.add_class::<PythonMinimizerScheduler>()?;
+    m.add_class::<PythonFuzzer>()?;
+    Ok(())
+}
@@ -26,6 +26,11 @@ pub use accounting::CoverageAccountingScheduler;
 pub mod weighted;
 pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub use replay::{ReplayScheduler, ReplayStep};
+
 pub mod tuneable;
 use libafl_bolts::{
     rands::Rand,
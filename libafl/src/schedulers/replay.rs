@@ -0,0 +1,369 @@
+//! The [`ReplayScheduler`] records the decisions of a fuzzing run, so that a single-client run
+//! can be replayed deterministically, for example to debug a heisenbug in a stage or a mutator.
+//!
+//! The recording holds the state of the RNG when fuzzing starts, and, for each call to
+//! [`Scheduler::next`], the scheduled [`CorpusId`], the testcases imported from other clients
+//! since the last call, and the results so far: a hash of the RNG, the corpus size and
+//! the number of objectives.
+//! While replaying, the same calls check the results against the recording, and fail
+//! with an [`Error::IllegalState`] at the first step that diverges.
+
+use alloc::{collections::VecDeque, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use libafl_bolts::{hash_std, tuples::MatchName};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasImported, HasRand, HasSolutions, Stoppable},
+    Error,
+};
+
+/// A single call to [`Scheduler::next`] in a recording of a [`ReplayScheduler`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayStep<I> {
+    /// The testcases imported from other clients before this step, in order
+    pub imports: Vec<I>,
+    /// The [`CorpusId`] the scheduler returned
+    pub corpus_id: CorpusId,
+    /// The hash of the RNG before this step
+    pub rand_hash: u64,
+    /// The corpus size before this step
+    pub corpus_count: usize,
+    /// The number of objectives before this step
+    pub solutions_count: usize,
+}
+
+/// A record in the file written by a [`ReplayScheduler`], encoded with `postcard`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum ReplayRecord<I> {
+    /// The serialized RNG of the state when fuzzing starts
+    Start(Vec<u8>),
+    /// A call to [`Scheduler::next`]
+    Step(ReplayStep<I>),
+}
+
+#[derive(Debug)]
+enum ReplayMode<I> {
+    Record {
+        file: File,
+        /// The imports counter of the state at the last step, `None` before the first one
+        last_imported: Option<usize>,
+        /// The testcases added to the corpus since the last step
+        added: Vec<CorpusId>,
+    },
+    Replay {
+        start: Option<Vec<u8>>,
+        steps: VecDeque<ReplayStep<I>>,
+    },
+}
+
+/// The [`ReplayScheduler`] wraps another scheduler, and either records its decisions to a file,
+/// or replays a recording, see the [module docs](self).
+///
+/// While replaying, the testcases imported in the recorded run are handed, in order, to a
+/// [`crate::stages::ReplayImportStage`], which must be the last stage of the fuzzer.
+/// A recording covers a single process: with a restarting event manager,
+/// give each run its own path.
+#[derive(Debug)]
+pub struct ReplayScheduler<CS, I> {
+    base: CS,
+    mode: ReplayMode<I>,
+    step: usize,
+    imports: Rc<RefCell<VecDeque<I>>>,
+}
+
+impl<CS, I> ReplayScheduler<CS, I>
+where
+    I: DeserializeOwned,
+{
+    /// Create a new [`ReplayScheduler`], recording the decisions of `base` to the file at `path`
+    pub fn record<P>(base: CS, path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self {
+            base,
+            mode: ReplayMode::Record {
+                file: File::create(path)?,
+                last_imported: None,
+                added: Vec::new(),
+            },
+            step: 0,
+            imports: Rc::new(RefCell::new(VecDeque::new())),
+        })
+    }
+
+    /// Create a new [`ReplayScheduler`], replaying the recording at `path`.
+    /// `base` should be the scheduler of the recorded run.
+    pub fn replay<P>(base: CS, path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        let mut rest = bytes.as_slice();
+        let mut start = None;
+        let mut steps = VecDeque::new();
+        while !rest.is_empty() {
+            match postcard::take_from_bytes::<ReplayRecord<I>>(rest) {
+                Ok((ReplayRecord::Start(rand), next)) => {
+                    start = Some(rand);
+                    rest = next;
+                }
+                Ok((ReplayRecord::Step(step), next)) => {
+                    steps.push_back(step);
+                    rest = next;
+                }
+                Err(e) => {
+                    // The recorded run likely died while writing its last step
+                    log::warn!(
+                        "Ignoring the broken end of the recording {}: {e}",
+                        path.display()
+                    );
+                    break;
+                }
+            }
+        }
+        if start.is_none() {
+            return Err(Error::illegal_argument(format!(
+                "{} is not a recording of a ReplayScheduler",
+                path.display()
+            )));
+        }
+        Ok(Self {
+            base,
+            mode: ReplayMode::Replay { start, steps },
+            step: 0,
+            imports: Rc::new(RefCell::new(VecDeque::new())),
+        })
+    }
+
+    /// The testcases to import before the next step, for the [`crate::stages::ReplayImportStage`]
+    #[must_use]
+    pub fn imports(&self) -> Rc<RefCell<VecDeque<I>>> {
+        self.imports.clone()
+    }
+
+    /// Returns `true` if this scheduler replays a recording
+    #[must_use]
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, ReplayMode::Replay { .. })
+    }
+}
+
+impl<CS, I, S> RemovableScheduler<I, S> for ReplayScheduler<CS, I>
+where
+    CS: RemovableScheduler<I, S>,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, id, testcase)
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, I, S> Scheduler<I, S> for ReplayScheduler<CS, I>
+where
+    CS: Scheduler<I, S>,
+    I: Clone + Serialize,
+    S: HasCorpus + HasSolutions + HasRand + HasImported + Stoppable,
+    S::Corpus: Corpus<Input = I>,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, id)?;
+        if let ReplayMode::Record { added, .. } = &mut self.mode {
+            added.push(id);
+        }
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.base.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let step = self.step;
+        self.step += 1;
+        match &mut self.mode {
+            ReplayMode::Record {
+                file,
+                last_imported,
+                added,
+            } => {
+                let rand = postcard::to_allocvec(state.rand())?;
+                let mut imports = Vec::new();
+                if let Some(last_imported) = *last_imported {
+                    // Imports happen last in an iteration, so they are the newest testcases
+                    let count = state.imported().saturating_sub(last_imported);
+                    for id in &added[added.len().saturating_sub(count)..] {
+                        imports.push(state.corpus().cloned_input_for_id(*id)?);
+                    }
+                } else {
+                    file.write_all(&postcard::to_allocvec(&ReplayRecord::<I>::Start(
+                        rand.clone(),
+                    ))?)?;
+                }
+                *last_imported = Some(*state.imported());
+                added.clear();
+
+                let corpus_count = state.corpus().count();
+                let solutions_count = state.solutions().count();
+                let corpus_id = self.base.next(state)?;
+                file.write_all(&postcard::to_allocvec(&ReplayRecord::Step(ReplayStep {
+                    imports,
+                    corpus_id,
+                    rand_hash: hash_std(&rand),
+                    corpus_count,
+                    solutions_count,
+                }))?)?;
+                Ok(corpus_id)
+            }
+            ReplayMode::Replay { start, steps } => {
+                if let Some(rand) = start.take() {
+                    *state.rand_mut() = postcard::from_bytes(&rand)?;
+                }
+                let Some(recorded) = steps.pop_front() else {
+                    log::info!("Replay finished after {step} steps");
+                    state.request_stop();
+                    return self.base.next(state);
+                };
+
+                let rand_hash = hash_std(&postcard::to_allocvec(state.rand())?);
+                if rand_hash != recorded.rand_hash {
+                    return Err(Error::illegal_state(format!(
+                        "Replay diverged at step {step}: the RNG differs from the recording"
+                    )));
+                }
+                for (what, count, recorded_count) in [
+                    ("corpus", state.corpus().count(), recorded.corpus_count),
+                    (
+                        "objectives",
+                        state.solutions().count(),
+                        recorded.solutions_count,
+                    ),
+                ] {
+                    if count != recorded_count {
+                        return Err(Error::illegal_state(format!(
+                            "Replay diverged at step {step}: {count} entries in the {what}, {recorded_count} in the recording"
+                        )));
+                    }
+                }
+
+                let corpus_id = self.base.next(state)?;
+                if corpus_id != recorded.corpus_id {
+                    log::warn!(
+                        "Replay step {step}: the scheduler picked {corpus_id}, continuing with the recorded {}",
+                        recorded.corpus_id
+                    );
+                    *state.corpus_mut().current_mut() = Some(recorded.corpus_id);
+                }
+                if let Some(next) = steps.front_mut() {
+                    self.imports.borrow_mut().extend(next.imports.drain(..));
+                }
+                Ok(recorded.corpus_id)
+            }
+        }
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.base.set_current_scheduled(state, next_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::{env::temp_dir, fs, process};
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        schedulers::{replay::ReplayScheduler, RandScheduler, Scheduler},
+        state::{HasCorpus, HasRand, StdState},
+        Error,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    fn test_state(seed: u64) -> TestState {
+        let mut state = StdState::new(
+            StdRand::with_seed(seed),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        for i in 0..8 {
+            state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(vec![i])))
+                .unwrap();
+        }
+        state
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_record_replay() {
+        let path = temp_dir().join(format!("libafl_replay_test_{}", process::id()));
+
+        let mut state = test_state(1337);
+        let mut recorder =
+            ReplayScheduler::<_, BytesInput>::record(RandScheduler::new(), &path).unwrap();
+        let recorded = (0..16)
+            .map(|_| Scheduler::<BytesInput, _>::next(&mut recorder, &mut state).unwrap())
+            .collect::<Vec<_>>();
+        drop(recorder);
+
+        // A different seed, which the replay overrides with the recorded RNG
+        let mut state = test_state(42);
+        let mut replayer =
+            ReplayScheduler::<_, BytesInput>::replay(RandScheduler::new(), &path).unwrap();
+        assert!(replayer.is_replaying());
+        for id in recorded {
+            assert_eq!(
+                Scheduler::<BytesInput, _>::next(&mut replayer, &mut state).unwrap(),
+                id
+            );
+        }
+
+        // Using the RNG outside of the recording diverges
+        let mut state = test_state(42);
+        let mut replayer =
+            ReplayScheduler::<_, BytesInput>::replay(RandScheduler::new(), &path).unwrap();
+        Scheduler::<BytesInput, _>::next(&mut replayer, &mut state).unwrap();
+        state.rand_mut().next();
+        assert!(matches!(
+            Scheduler::<BytesInput, _>::next(&mut replayer, &mut state),
+            Err(Error::IllegalState(..))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
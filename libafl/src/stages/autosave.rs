@@ -0,0 +1,235 @@
+//! The [`AutosaveStage`] periodically saves the whole fuzzer state to disk,
+//! so that a campaign can resume after a crash, or a reboot of the host, with [`load_autosave`].
+
+use core::{marker::PhantomData, time::Duration};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{
+    current_time,
+    staterestore::{load_state_snapshot, save_state_snapshot},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{stages::Stage, state::UsesState, Error};
+
+/// The path of the `n`th older autosave, `<path>.<n>`.
+fn autosave_backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(format!(".{n}"));
+    PathBuf::from(backup)
+}
+
+/// The autosave of the client with the id `client_id` (see [`crate::events::ClientDescription::id`])
+/// in the directory `dir`, for campaigns with several clients, such as the ones of a [`crate::events::Launcher`].
+pub fn client_autosave_path<P>(dir: P, client_id: usize) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    dir.as_ref().join(format!("client_{client_id}.state"))
+}
+
+/// Loads the newest intact autosave written by an [`AutosaveStage`] to `path`.
+///
+/// Falls back to the older autosaves, `<path>.1`, `<path>.2`, .., if the newer ones are missing or broken.
+/// Returns `None` if there is no autosave at all, for example on the first run of a campaign.
+pub fn load_autosave<S, P>(path: P) -> Result<Option<S>, Error>
+where
+    S: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut last_error = None;
+    for n in 0.. {
+        let candidate = if n == 0 {
+            path.to_path_buf()
+        } else {
+            autosave_backup_path(path, n)
+        };
+        if !candidate.exists() {
+            // The newest autosave is briefly missing while the autosaves rotate
+            if n == 0 {
+                continue;
+            }
+            break;
+        }
+        match load_state_snapshot(&candidate) {
+            Ok(state) => {
+                log::info!("Resuming from autosave {}", candidate.display());
+                return Ok(Some(state));
+            }
+            Err(e) => {
+                log::warn!("Cannot resume from autosave {}: {e}", candidate.display());
+                last_error = Some(e);
+            }
+        }
+    }
+    last_error.map_or(Ok(None), Err)
+}
+
+/// The [`AutosaveStage`] saves the whole fuzzer state to disk, every `interval`.
+///
+/// The state holds the corpus (or, for on-disk corpora, the references to the testcases on disk),
+/// all metadata, including the one of the schedulers, and the RNG.
+/// Before each save, the older autosaves rotate to `<path>.1` .. `<path>.<backups>`,
+/// and the new autosave is written atomically, so there is always a complete autosave to resume from.
+#[derive(Debug)]
+pub struct AutosaveStage<EM, Z> {
+    path: PathBuf,
+    interval: Duration,
+    backups: usize,
+    last_save: Duration,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> UsesState for AutosaveStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for AutosaveStage<EM, Z>
+where
+    EM: UsesState,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        if current_time().saturating_sub(self.last_save) < self.interval {
+            return Ok(());
+        }
+        self.save(state)
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<EM, Z> AutosaveStage<EM, Z>
+where
+    EM: UsesState,
+{
+    /// Create a new [`AutosaveStage`], saving the state to `path` every `interval`, and keeping
+    /// `backups` older autosaves around.
+    pub fn new<P>(path: P, interval: Duration, backups: usize) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            path,
+            interval,
+            backups,
+            last_save: current_time(),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Saves the state now, regardless of the interval, for example before the fuzzer exits.
+    pub fn save(&mut self, state: &EM::State) -> Result<(), Error> {
+        self.rotate()?;
+        save_state_snapshot(state, &self.path)?;
+        self.last_save = current_time();
+        Ok(())
+    }
+
+    /// Moves `<path>` to `<path>.1`, `<path>.1` to `<path>.2`, .., dropping the oldest autosave.
+    fn rotate(&self) -> Result<(), Error> {
+        if self.backups == 0 {
+            return Ok(());
+        }
+        for n in (1..self.backups).rev() {
+            let older = autosave_backup_path(&self.path, n);
+            if older.exists() {
+                fs::rename(&older, autosave_backup_path(&self.path, n + 1))?;
+            }
+        }
+        if self.path.exists() {
+            fs::rename(&self.path, autosave_backup_path(&self.path, 1))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{env::temp_dir, fs, process};
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        stages::autosave::{autosave_backup_path, load_autosave, AutosaveStage},
+        state::{HasRand, StdState},
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_autosave_rotation() {
+        let dir = temp_dir().join(format!("libafl_autosave_test_{}", process::id()));
+        let path = dir.join("fuzzer.state");
+        assert!(load_autosave::<TestState, _>(&path).unwrap().is_none());
+
+        let mut state: TestState = StdState::nop().unwrap();
+        let mut stage = AutosaveStage::<NopEventManager<TestState>, NopFuzzer<TestState>>::new(
+            &path,
+            Duration::from_secs(3600),
+            2,
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            state.rand_mut().next();
+            stage.save(&state).unwrap();
+        }
+        let mut previous = state.clone();
+        state.rand_mut().next();
+        stage.save(&state).unwrap();
+
+        assert!(path.exists());
+        assert!(autosave_backup_path(&path, 1).exists());
+        assert!(autosave_backup_path(&path, 2).exists());
+        assert!(!autosave_backup_path(&path, 3).exists());
+
+        // The newest autosave continues with the newest RNG state
+        let mut resumed: TestState = load_autosave(&path).unwrap().unwrap();
+        assert_eq!(resumed.rand_mut().next(), state.rand_mut().next());
+
+        // A broken autosave falls back to the previous one
+        fs::write(&path, b"broken").unwrap();
+        let mut resumed: TestState = load_autosave(&path).unwrap().unwrap();
+        assert_eq!(resumed.rand_mut().next(), previous.rand_mut().next());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
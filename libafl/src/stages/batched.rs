@@ -0,0 +1,151 @@
+//! The [`BatchMutationalStage`] runs a batch of mutated inputs in a single target invocation,
+//! using an executor with [`HasBatchExecution`], such as the [`crate::executors::BatchedExecutor`].
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::ToString,
+    vec::Vec,
+};
+use core::{marker::PhantomData, num::NonZeroUsize};
+
+use libafl_bolts::Named;
+use serde::Serialize;
+
+use crate::{
+    corpus::Corpus,
+    events::EventFirer,
+    executors::batched::HasBatchExecution,
+    fuzzer::HasScheduler,
+    mutators::{MutationResult, Mutator},
+    nonzero,
+    observers::ObserversTuple,
+    schedulers::Scheduler,
+    stages::{RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasCurrentTestcase, HasRand, UsesState},
+    Error, ExecutionProcessor, HasNamedMetadata,
+};
+
+/// The default amount of mutated inputs per batch
+pub const DEFAULT_BATCH_SIZE: usize = 32;
+
+/// The unique id for batch mutational stages
+static mut BATCH_MUTATIONAL_STAGE_ID: usize = 0;
+/// The name for batch mutational stages
+pub static BATCH_MUTATIONAL_STAGE_NAME: &str = "batchmutational";
+
+/// A mutational stage executing its mutated inputs in batches.
+///
+/// For the current testcase, it mutates a batch of inputs, runs them in one target invocation,
+/// and then evaluates each input with its own observations, like the
+/// [`crate::stages::StdMutationalStage`] would.
+#[derive(Clone, Debug)]
+pub struct BatchMutationalStage<E, EM, M, Z> {
+    name: Cow<'static, str>,
+    mutator: M,
+    batch_size: NonZeroUsize,
+    #[allow(clippy::type_complexity)]
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, M, Z> BatchMutationalStage<E, EM, M, Z> {
+    /// Creates a new [`BatchMutationalStage`] with batches of [`DEFAULT_BATCH_SIZE`] inputs
+    pub fn new(mutator: M) -> Self {
+        Self::with_batch_size(mutator, nonzero!(DEFAULT_BATCH_SIZE))
+    }
+
+    /// Creates a new [`BatchMutationalStage`] with the given amount of inputs per batch
+    pub fn with_batch_size(mutator: M, batch_size: NonZeroUsize) -> Self {
+        let stage_id = unsafe {
+            let ret = BATCH_MUTATIONAL_STAGE_ID;
+            BATCH_MUTATIONAL_STAGE_ID += 1;
+            ret
+        };
+        Self {
+            name: Cow::Owned(
+                BATCH_MUTATIONAL_STAGE_NAME.to_owned() + ":" + stage_id.to_string().as_str(),
+            ),
+            mutator,
+            batch_size,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The amount of inputs per batch
+    pub fn batch_size(&self) -> NonZeroUsize {
+        self.batch_size
+    }
+}
+
+impl<E, EM, M, Z> UsesState for BatchMutationalStage<E, EM, M, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, M, Z> Named for BatchMutationalStage<E, EM, M, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, M, Z> Stage<E, EM, Z> for BatchMutationalStage<E, EM, M, Z>
+where
+    E: HasBatchExecution<EM, Z, State = Self::State>,
+    E::Observers: ObserversTuple<Self::Input, Self::State> + Serialize,
+    EM: EventFirer<State = Self::State>,
+    M: Mutator<Self::Input, Self::State>,
+    Z: ExecutionProcessor<EM, E::Observers> + HasScheduler,
+    Z::State: HasCorpus + HasRand + HasNamedMetadata + HasCurrentTestcase,
+    <<Self as UsesState>::State as HasCorpus>::Corpus: Corpus<Input = Self::Input>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let input = state.current_input_cloned()?;
+
+        let mut batch = Vec::with_capacity(self.batch_size.get());
+        for _ in 0..self.batch_size.get() {
+            let mut mutated = input.clone();
+            if self.mutator.mutate(state, &mut mutated)? == MutationResult::Mutated {
+                batch.push(mutated);
+            }
+        }
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        executor.observers_mut().pre_exec_all(state, &batch[0])?;
+        let exit_kinds = executor.run_batch(fuzzer, state, manager, &batch)?;
+
+        for (idx, (input, exit_kind)) in batch.into_iter().zip(exit_kinds).enumerate() {
+            executor.load_batch_entry(idx)?;
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &exit_kind)?;
+
+            let observers = &*executor.observers();
+            fuzzer
+                .scheduler_mut()
+                .on_evaluation(state, &input, observers)?;
+            let (_, corpus_id) =
+                fuzzer.evaluate_execution(state, manager, input, observers, &exit_kind, true)?;
+
+            self.mutator.post_exec(state, corpus_id)?;
+        }
+
+        Ok(())
+    }
+
+    fn should_restart(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    fn clear_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
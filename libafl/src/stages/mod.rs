@@ -14,6 +14,9 @@ use core::{fmt, marker::PhantomData};
 
 #[cfg(feature = "std")]
 pub use afl_stats::{AflStatsStage, CalibrationTime, FuzzTime, SyncTime};
+#[cfg(feature = "std")]
+pub use autosave::{client_autosave_path, load_autosave, AutosaveStage};
+pub use batched::BatchMutationalStage;
 pub use calibrate::CalibrationStage;
 pub use colorization::*;
 #[cfg(all(feature = "std", unix))]
@@ -32,8 +35,11 @@ use libafl_bolts::{
 pub use logics::*;
 pub use mutational::{MutationalStage, StdMutationalStage};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
+#[cfg(feature = "std")]
+pub use replay::ReplayImportStage;
 use serde::{Deserialize, Serialize};
 pub use stats::StatsStage;
+pub use stop::{StopPolicy, StopPolicyMetadata, StopReason, StopSummary};
 #[cfg(feature = "std")]
 pub use sync::*;
 #[cfg(feature = "std")]
@@ -69,6 +75,9 @@ pub mod tmin;
 
 #[cfg(feature = "std")]
 pub mod afl_stats;
+#[cfg(feature = "std")]
+pub mod autosave;
+pub mod batched;
 pub mod calibrate;
 pub mod colorization;
 #[cfg(all(feature = "std", unix))]
@@ -79,7 +88,10 @@ pub mod generalization;
 pub mod generation;
 pub mod logics;
 pub mod power;
+#[cfg(feature = "std")]
+pub mod replay;
 pub mod stats;
+pub mod stop;
 #[cfg(feature = "std")]
 pub mod sync;
 #[cfg(feature = "std")]
@@ -554,6 +566,15 @@ impl RetryCountRestartHelper {
             )
         })?;
 
+        if state.has_named_metadata::<PlannedRestartMetadata>(PlannedRestartMetadata::NAME) {
+            if let Ok(metadata) = state.named_metadata::<Self>(name) {
+                if metadata.tries_remaining.is_some() {
+                    // Resuming after a planned restart, not a crash, so this try is for free
+                    return Ok(!metadata.skipped.contains(&corpus_id));
+                }
+            }
+        }
+
         let initial_tries_remaining = max_retries + 1;
         let metadata = state.named_metadata_or_insert_with(name, || Self {
             tries_remaining: Some(initial_tries_remaining),
@@ -592,6 +613,20 @@ impl RetryCountRestartHelper {
     }
 }
 
+/// Marks that the fuzzer restarted on purpose, e.g., in the [`crate::executors::PeriodicRestartExecutor`],
+/// and not because the target crashed.
+///
+/// Stages resuming after such a restart keep their tries in the [`RetryCountRestartHelper`].
+#[derive(Clone, Copy, Deserialize, Serialize, Debug)]
+pub struct PlannedRestartMetadata;
+
+impl_serdeany!(PlannedRestartMetadata);
+
+impl PlannedRestartMetadata {
+    /// The name of this metadata in the named metadata of the state
+    pub const NAME: &'static str = "planned_restart";
+}
+
 /// The index of a stage
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[repr(transparent)]
@@ -721,9 +756,9 @@ mod test {
     use crate::{
         corpus::{Corpus, HasCurrentCorpusId, Testcase},
         inputs::NopInput,
-        stages::{RetryCountRestartHelper, Stage},
+        stages::{PlannedRestartMetadata, RetryCountRestartHelper, Stage},
         state::{HasCorpus, State, StdState, UsesState},
-        HasMetadata,
+        HasMetadata, HasNamedMetadata,
     };
 
     /// A stage that succeeds to resume
@@ -888,4 +923,39 @@ mod test {
 
         Ok(())
     }
+
+    /// Test that planned restarts do not use up tries
+    #[test]
+    fn test_planned_restart_progress() -> Result<(), Error> {
+        // # Safety
+        // No concurrency per testcase
+        #[cfg(any(not(feature = "serdeany_autoreg"), miri))]
+        unsafe {
+            RetryCountRestartHelper::register();
+            PlannedRestartMetadata::register();
+        }
+
+        let mut state = StdState::nop()?;
+        let corpus_id = state.corpus_mut().add(Testcase::new(NopInput {}))?;
+        state.set_corpus_id(corpus_id)?;
+
+        assert!(RetryCountRestartHelper::should_restart(
+            &mut state, "stage", 1
+        )?);
+        // planned restarts, without any try left after the first
+        state.add_named_metadata(PlannedRestartMetadata::NAME, PlannedRestartMetadata);
+        for _ in 0..3 {
+            assert!(RetryCountRestartHelper::should_restart(
+                &mut state, "stage", 1
+            )?);
+        }
+        state.remove_named_metadata::<PlannedRestartMetadata>(PlannedRestartMetadata::NAME);
+
+        // a crash uses up the last try
+        assert!(!RetryCountRestartHelper::should_restart(
+            &mut state, "stage", 1
+        )?);
+
+        Ok(())
+    }
 }
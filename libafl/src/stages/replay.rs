@@ -0,0 +1,73 @@
+//! The [`ReplayImportStage`] feeds the testcases imported in a run recorded by a
+//! [`crate::schedulers::ReplayScheduler`] into a replay of that run.
+
+use alloc::{collections::VecDeque, rc::Rc};
+use core::{cell::RefCell, marker::PhantomData};
+
+use crate::{fuzzer::Evaluator, inputs::UsesInput, stages::Stage, state::UsesState, Error};
+
+/// The [`ReplayImportStage`] adds the testcases that other clients sent to the recorded run,
+/// at the same point of the run, so that a replay does not need the other clients.
+/// It must be the last stage of the fuzzer, as the imports happened after all stages.
+/// While recording, it does nothing.
+#[derive(Debug)]
+pub struct ReplayImportStage<I, EM, Z> {
+    imports: Rc<RefCell<VecDeque<I>>>,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<I, EM, Z> ReplayImportStage<I, EM, Z> {
+    /// Create a new [`ReplayImportStage`], with the imports of [`crate::schedulers::ReplayScheduler::imports`]
+    #[must_use]
+    pub fn new(imports: Rc<RefCell<VecDeque<I>>>) -> Self {
+        Self {
+            imports,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, EM, Z> UsesState for ReplayImportStage<I, EM, Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, I, Z> Stage<E, EM, Z> for ReplayImportStage<I, EM, Z>
+where
+    E: UsesState<State = Self::State>,
+    EM: UsesState<State = Self::State>,
+    Z: Evaluator<E, EM>,
+    Self::State: UsesInput<Input = I>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        loop {
+            // Do not hold the borrow while the target runs
+            let next = self.imports.borrow_mut().pop_front();
+            let Some(input) = next else {
+                return Ok(());
+            };
+            // The recorded run added all of its imports to the corpus
+            fuzzer.add_input(state, executor, manager, input)?;
+        }
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // A crash would end the replay anyway, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // A crash would end the replay anyway, so restart safety is not needed
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! The [`StopPolicy`] ends a fuzzing campaign once a budget is used up,
+//! or once the fuzzer stopped making progress.
+
+use alloc::string::ToString;
+use core::{fmt, marker::PhantomData, time::Duration};
+
+use libafl_bolts::{current_time, format_duration_hms, impl_serdeany};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Corpus,
+    events::{Event, EventFirer, LogSeverity},
+    stages::Stage,
+    state::{HasCorpus, HasExecutions, HasSolutions, HasStartTime, Stoppable, UsesState},
+    Error, HasMetadata,
+};
+
+/// The reason for a [`StopPolicy`] to end the campaign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The wall-clock budget is used up
+    TimeBudget(Duration),
+    /// The fuzzer ran the given number of executions
+    Executions(u64),
+    /// The fuzzer found the given number of objectives
+    Objectives(usize),
+    /// The corpus did not grow for the given time
+    CoverageStall(Duration),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::TimeBudget(budget) => {
+                write!(f, "time budget of {} used up", format_duration_hms(budget))
+            }
+            StopReason::Executions(executions) => {
+                write!(f, "reached {executions} executions")
+            }
+            StopReason::Objectives(objectives) => {
+                write!(f, "found {objectives} objectives")
+            }
+            StopReason::CoverageStall(window) => {
+                write!(f, "no new coverage for {}", format_duration_hms(window))
+            }
+        }
+    }
+}
+
+/// The final report of a campaign ended by a [`StopPolicy`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopSummary {
+    /// Why the campaign ended
+    pub reason: StopReason,
+    /// The run time of the campaign
+    pub run_time: Duration,
+    /// The executions of this client
+    pub executions: u64,
+    /// The entries in the corpus of this client
+    pub corpus_size: usize,
+    /// The objectives found by this client
+    pub objectives: usize,
+}
+
+impl_serdeany!(StopSummary);
+
+impl fmt::Display for StopSummary {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.run_time.as_secs_f64();
+        let execs_per_sec = if secs > 0.0 {
+            self.executions as f64 / secs
+        } else {
+            0.0
+        };
+        write!(
+            f,
+            "Stopping the campaign: {}, run time: {}, executions: {} ({execs_per_sec:.2}/sec), corpus: {}, objectives: {}",
+            self.reason,
+            format_duration_hms(&self.run_time),
+            self.executions,
+            self.corpus_size,
+            self.objectives
+        )
+    }
+}
+
+/// The corpus growth seen by a [`StopPolicy`], kept in the state to survive restarts
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StopPolicyMetadata {
+    /// The corpus size at the last growth
+    pub corpus_size: usize,
+    /// The time of the last growth
+    pub last_growth: Duration,
+}
+
+impl_serdeany!(StopPolicyMetadata);
+
+/// The [`StopPolicy`] ends the campaign after a wall-clock budget, a number of executions,
+/// a number of objectives, or once the corpus did not grow for a while.
+///
+/// Once a condition is met, it logs a [`StopSummary`], keeps it in the state metadata,
+/// and fires an [`Event::Stop`], which stops all the other clients of the cluster, too.
+/// Every condition is checked against the numbers of this client,
+/// so a policy with several clients usually lives in each of them.
+#[derive(Debug)]
+pub struct StopPolicy<EM, Z> {
+    time_budget: Option<Duration>,
+    max_executions: Option<u64>,
+    max_objectives: Option<usize>,
+    coverage_stall: Option<Duration>,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> Default for StopPolicy<EM, Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<EM, Z> StopPolicy<EM, Z> {
+    /// Create a new [`StopPolicy`] without any condition, add them with the `with_` methods.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            time_budget: None,
+            max_executions: None,
+            max_objectives: None,
+            coverage_stall: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Stop once the campaign ran for `budget`, counted from the start time of the state
+    #[must_use]
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Stop after `executions` executions
+    #[must_use]
+    pub fn with_max_executions(mut self, executions: u64) -> Self {
+        self.max_executions = Some(executions);
+        self
+    }
+
+    /// Stop once `objectives` objectives are in the solutions
+    #[must_use]
+    pub fn with_max_objectives(mut self, objectives: usize) -> Self {
+        self.max_objectives = Some(objectives);
+        self
+    }
+
+    /// Stop once the corpus did not grow, by own finds or imports, for `window`
+    #[must_use]
+    pub fn with_coverage_stall(mut self, window: Duration) -> Self {
+        self.coverage_stall = Some(window);
+        self
+    }
+
+    /// Checks all conditions, returning the first one that is met
+    pub fn check<S>(&self, state: &mut S) -> Result<Option<StopReason>, Error>
+    where
+        S: HasExecutions + HasStartTime + HasCorpus + HasSolutions + HasMetadata,
+    {
+        let now = current_time();
+
+        if let Some(budget) = self.time_budget {
+            if now.saturating_sub(*state.start_time()) >= budget {
+                return Ok(Some(StopReason::TimeBudget(budget)));
+            }
+        }
+        if let Some(executions) = self.max_executions {
+            if *state.executions() >= executions {
+                return Ok(Some(StopReason::Executions(executions)));
+            }
+        }
+        if let Some(objectives) = self.max_objectives {
+            if state.solutions().count() >= objectives {
+                return Ok(Some(StopReason::Objectives(objectives)));
+            }
+        }
+        if let Some(window) = self.coverage_stall {
+            let corpus_size = state.corpus().count();
+            let growth = state.metadata_or_insert_with(|| StopPolicyMetadata {
+                corpus_size,
+                last_growth: now,
+            });
+            if corpus_size > growth.corpus_size {
+                growth.corpus_size = corpus_size;
+                growth.last_growth = now;
+            } else if now.saturating_sub(growth.last_growth) >= window {
+                return Ok(Some(StopReason::CoverageStall(window)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<EM, Z> UsesState for StopPolicy<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for StopPolicy<EM, Z>
+where
+    EM: EventFirer,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasExecutions + HasStartTime + HasCorpus + HasSolutions + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(reason) = self.check(state)? else {
+            return Ok(());
+        };
+
+        let summary = StopSummary {
+            reason,
+            run_time: current_time().saturating_sub(*state.start_time()),
+            executions: *state.executions(),
+            corpus_size: state.corpus().count(),
+            objectives: state.solutions().count(),
+        };
+        log::info!("{summary}");
+        manager.log(state, LogSeverity::Info, summary.to_string())?;
+        state.add_metadata(summary);
+
+        // Other clients stop once they receive the event, this one once the stage returns
+        manager.fire(state, Event::Stop)?;
+        state.request_stop();
+        Ok(())
+    }
+
+    #[inline]
+    fn should_restart(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::{current_time, rands::StdRand};
+
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        stages::stop::{StopPolicy, StopPolicyMetadata, StopReason},
+        state::{HasCorpus, HasExecutions, HasSolutions, StdState},
+        HasMetadata,
+    };
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+    type TestPolicy = StopPolicy<NopEventManager<TestState>, NopFuzzer<TestState>>;
+
+    #[test]
+    fn test_stop_policy() {
+        let mut state: TestState = StdState::nop().unwrap();
+
+        let policy = TestPolicy::new()
+            .with_max_executions(10)
+            .with_max_objectives(1);
+        assert_eq!(policy.check(&mut state).unwrap(), None);
+        *state.executions_mut() = 10;
+        assert_eq!(
+            policy.check(&mut state).unwrap(),
+            Some(StopReason::Executions(10))
+        );
+        *state.executions_mut() = 0;
+        state
+            .solutions_mut()
+            .add(Testcase::new(BytesInput::new(vec![0])))
+            .unwrap();
+        assert_eq!(
+            policy.check(&mut state).unwrap(),
+            Some(StopReason::Objectives(1))
+        );
+
+        let policy = TestPolicy::new().with_coverage_stall(Duration::from_secs(60));
+        assert_eq!(policy.check(&mut state).unwrap(), None);
+        // Pretend the last growth was long ago
+        state
+            .metadata_mut::<StopPolicyMetadata>()
+            .unwrap()
+            .last_growth = current_time().saturating_sub(Duration::from_secs(120));
+        assert_eq!(
+            policy.check(&mut state).unwrap(),
+            Some(StopReason::CoverageStall(Duration::from_secs(60)))
+        );
+        // New entries reset the window
+        state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![1])))
+            .unwrap();
+        assert_eq!(policy.check(&mut state).unwrap(), None);
+    }
+}
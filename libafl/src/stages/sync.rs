@@ -16,7 +16,7 @@ use crate::{
     corpus::{Corpus, CorpusId},
     events::{llmp::LlmpEventConverter, Event, EventConfig, EventFirer},
     executors::{Executor, ExitKind, HasObservers},
-    fuzzer::{Evaluator, EvaluatorObservers, ExecutionProcessor},
+    fuzzer::{Evaluator, EvaluatorObservers, ExecuteInputResult, ExecutionProcessor},
     inputs::{Input, InputConverter, UsesInput},
     stages::{RetryCountRestartHelper, Stage},
     state::{HasCorpus, HasExecutions, HasRand, State, UsesState},
@@ -286,6 +286,7 @@ where
                         input,
                         observers_buf: None,
                         exit_kind: ExitKind::Ok,
+                        exec_res: ExecuteInputResult::Corpus,
                         corpus_size: 0, // TODO choose if sending 0 or the actual real value
                         client_config: EventConfig::AlwaysUnique,
                         time: current_time(),
@@ -30,6 +30,8 @@ pub use stack::StageStack;
 use crate::monitors::ClientPerfMonitor;
 #[cfg(feature = "scalability_introspection")]
 use crate::monitors::ScalabilityMonitor;
+#[cfg(feature = "std")]
+use crate::stages::load_autosave;
 use crate::{
     corpus::{Corpus, CorpusId, HasCurrentCorpusId, HasTestcase, InMemoryCorpus, Testcase},
     events::{Event, EventFirer, LogSeverity},
@@ -633,6 +635,23 @@ where
     R: Rand,
     SC: Corpus<Input = <Self as UsesInput>::Input>,
 {
+    /// Resumes a campaign from the autosave at `path`, written by an [`crate::stages::AutosaveStage`].
+    ///
+    /// Falls back to older autosaves, if the newest one is broken, see [`crate::stages::load_autosave`].
+    pub fn resume_from<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        Self: DeserializeOwned,
+    {
+        let path = path.as_ref();
+        load_autosave(path)?.ok_or_else(|| {
+            Error::illegal_argument(format!(
+                "There is no autosave to resume from at {}",
+                path.display()
+            ))
+        })
+    }
+
     /// Decide if the state must load the inputs
     pub fn must_load_initial_inputs(&self) -> bool {
         self.corpus().count() == 0
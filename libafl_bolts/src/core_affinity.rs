@@ -72,6 +72,19 @@ impl CoreId {
     pub fn set_affinity_forced(&self) -> Result<(), Error> {
         set_for_current_helper(*self)
     }
+
+    /// Prefer allocating new memory of the current thread on the NUMA node of this [`CoreId`].
+    /// Threads and processes spawned later inherit the policy.
+    /// If the node runs out of memory, the kernel falls back to the other nodes.
+    ///
+    /// Note: Like [`CoreId::set_affinity`], this will *_not_* fail on platforms without NUMA support.
+    #[cfg(feature = "std")]
+    pub fn set_memory_affinity(&self) -> Result<(), Error> {
+        match set_memory_for_current_helper(*self) {
+            Ok(()) | Err(Error::Unsupported(_, _)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 impl From<usize> for CoreId {
@@ -131,19 +144,8 @@ impl Cores {
                 cores.push(x.into());
             }
         } else {
-            let core_args: Vec<&str> = args.split(',').collect();
-
             // ./fuzzer --cores 1,2-4,6 -> clients run in cores 1,2,3,4,6
-            for csv in core_args {
-                let core_range: Vec<&str> = csv.split('-').collect();
-                if core_range.len() == 1 {
-                    cores.push(core_range[0].parse::<usize>()?.into());
-                } else if core_range.len() == 2 {
-                    for x in core_range[0].parse::<usize>()?..=(core_range[1].parse::<usize>()?) {
-                        cores.push(x.into());
-                    }
-                }
-            }
+            cores = parse_core_list(args)?;
         }
 
         if cores.is_empty() {
@@ -203,6 +205,162 @@ impl TryFrom<&str> for Cores {
     }
 }
 
+/// Parses a list of cores in the format of `1,2-4,6`, as also used by the kernel for cpu lists
+#[cfg(feature = "std")]
+fn parse_core_list(list: &str) -> Result<Vec<CoreId>, Error> {
+    let mut cores = vec![];
+    for csv in list.trim().split(',').filter(|csv| !csv.is_empty()) {
+        let core_range: Vec<&str> = csv.split('-').collect();
+        if core_range.len() == 1 {
+            cores.push(core_range[0].parse::<usize>()?.into());
+        } else if core_range.len() == 2 {
+            for x in core_range[0].parse::<usize>()?..=(core_range[1].parse::<usize>()?) {
+                cores.push(x.into());
+            }
+        }
+    }
+    Ok(cores)
+}
+
+/// Where a logical core sits in the machine, see [`CpuTopology`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Hash)]
+pub struct CoreInfo {
+    /// The logical core
+    pub id: CoreId,
+    /// The NUMA node of the core
+    pub numa_node: usize,
+    /// The physical package, or socket, of the core
+    pub package: usize,
+    /// The physical core in its package. SMT siblings share the same physical core.
+    pub physical_core: usize,
+}
+
+/// The NUMA nodes and SMT siblings of the cores this process may run on
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuTopology {
+    cores: Vec<CoreInfo>,
+}
+
+/// How a [`CpuTopology`] places the clients and the broker of a fuzzing campaign
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorePlacement {
+    /// Use only one logical core of each physical core, leaving its hyperthread siblings idle
+    pub avoid_smt_siblings: bool,
+    /// Prefer allocating the memory of each client on the NUMA node of its core
+    pub bind_memory: bool,
+    /// Take a core away from the clients, to run the broker on
+    pub dedicated_broker_core: bool,
+}
+
+/// The cores picked by [`CpuTopology::plan`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorePlan {
+    /// The cores to run clients on, alternating between the NUMA nodes
+    pub clients: Vec<CoreId>,
+    /// The core to run the broker on, if it gets one of its own
+    pub broker: Option<CoreId>,
+}
+
+#[cfg(feature = "std")]
+impl CpuTopology {
+    /// Reads the topology of the cores this process may run on, see [`get_core_ids`].
+    /// On platforms without topology information, all cores are separate physical cores on a single node.
+    pub fn detect() -> Result<Self, Error> {
+        Ok(Self {
+            cores: get_topology_helper()?,
+        })
+    }
+
+    /// Create a [`CpuTopology`] from a known list of cores
+    #[must_use]
+    pub fn new(mut cores: Vec<CoreInfo>) -> Self {
+        cores.sort_by_key(|info| info.id.0);
+        Self { cores }
+    }
+
+    /// All cores, ordered by [`CoreId`]
+    #[must_use]
+    pub fn cores(&self) -> &[CoreInfo] {
+        &self.cores
+    }
+
+    /// The NUMA node of `core_id`, if the core is known
+    #[must_use]
+    pub fn numa_node(&self, core_id: CoreId) -> Option<usize> {
+        self.info(core_id).map(|info| info.numa_node)
+    }
+
+    /// The other logical cores on the same physical core as `core_id`
+    #[must_use]
+    pub fn smt_siblings(&self, core_id: CoreId) -> Vec<CoreId> {
+        let Some(core) = self.info(core_id) else {
+            return vec![];
+        };
+        self.cores
+            .iter()
+            .filter(|info| {
+                info.id != core_id
+                    && info.package == core.package
+                    && info.physical_core == core.physical_core
+            })
+            .map(|info| info.id)
+            .collect()
+    }
+
+    fn info(&self, core_id: CoreId) -> Option<&CoreInfo> {
+        self.cores.iter().find(|info| info.id == core_id)
+    }
+
+    /// Picks the cores for the clients and the broker, among the given `cores`.
+    ///
+    /// The clients alternate between the NUMA nodes, so that trimming the list later
+    /// keeps the nodes equally loaded, unlike binding to the cores in order,
+    /// which fills the first socket of a multi-socket machine first.
+    /// With a [`CorePlacement::dedicated_broker_core`], the broker gets the first core.
+    pub fn plan(&self, cores: &Cores, placement: &CorePlacement) -> Result<CorePlan, Error> {
+        let mut by_node: Vec<(usize, Vec<CoreId>)> = vec![];
+        let mut used_physical_cores = vec![];
+        for info in self.cores.iter().filter(|info| cores.contains(info.id)) {
+            if placement.avoid_smt_siblings {
+                let physical_core = (info.package, info.physical_core);
+                if used_physical_cores.contains(&physical_core) {
+                    log::debug!("Leaving core {:?} idle, its SMT sibling is used", info.id);
+                    continue;
+                }
+                used_physical_cores.push(physical_core);
+            }
+            match by_node.iter_mut().find(|(node, _)| *node == info.numa_node) {
+                Some((_, ids)) => ids.push(info.id),
+                None => by_node.push((info.numa_node, vec![info.id])),
+            }
+        }
+        by_node.sort_by_key(|(node, _)| *node);
+
+        let mut clients = vec![];
+        let max_per_node = by_node.iter().map(|(_, ids)| ids.len()).max().unwrap_or(0);
+        for i in 0..max_per_node {
+            clients.extend(by_node.iter().filter_map(|(_, ids)| ids.get(i)));
+        }
+
+        let broker = if placement.dedicated_broker_core && !clients.is_empty() {
+            Some(clients.remove(0))
+        } else {
+            None
+        };
+        if clients.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "No cores left for the clients among {}",
+                cores.cmdline
+            )));
+        }
+        Ok(CorePlan { clients, broker })
+    }
+}
+
 // Linux Section
 
 #[cfg(any(
@@ -227,6 +385,42 @@ fn set_for_current_helper(core_id: CoreId) -> Result<(), Error> {
     linux::set_for_current(core_id)
 }
 
+#[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+#[inline]
+fn get_topology_helper() -> Result<Vec<CoreInfo>, Error> {
+    linux::get_topology()
+}
+
+#[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+#[inline]
+fn set_memory_for_current_helper(core_id: CoreId) -> Result<(), Error> {
+    linux::set_memory_for_current(core_id)
+}
+
+// Platforms without topology information
+
+#[cfg(all(feature = "std", not(any(target_os = "android", target_os = "linux"))))]
+#[inline]
+fn get_topology_helper() -> Result<Vec<CoreInfo>, Error> {
+    Ok(get_core_ids()?
+        .into_iter()
+        .map(|id| CoreInfo {
+            id,
+            numa_node: 0,
+            package: 0,
+            physical_core: id.0,
+        })
+        .collect())
+}
+
+#[cfg(all(feature = "std", not(any(target_os = "android", target_os = "linux"))))]
+#[inline]
+fn set_memory_for_current_helper(_core_id: CoreId) -> Result<(), Error> {
+    Err(Error::unsupported(
+        "NUMA memory policies are not supported on this platform",
+    ))
+}
+
 #[cfg(any(
     target_os = "android",
     target_os = "linux",
@@ -249,6 +443,8 @@ mod linux {
     const CPU_SETSIZE: libc::c_int = 256;
 
     use super::CoreId;
+    #[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+    use super::CoreInfo;
     use crate::Error;
 
     #[allow(trivial_numeric_casts)]
@@ -315,6 +511,91 @@ mod linux {
         unsafe { zeroed::<cpu_set_t>() }
     }
 
+    /// Reads a number from a sysfs file
+    #[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+    fn read_sysfs_usize(path: &str) -> Option<usize> {
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    /// The NUMA node of each core, from `/sys/devices/system/node`.
+    /// Empty on kernels without NUMA support.
+    #[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+    fn get_numa_nodes() -> Result<Vec<(CoreId, usize)>, Error> {
+        let mut nodes = vec![];
+        let Ok(entries) = std::fs::read_dir("/sys/devices/system/node") else {
+            return Ok(nodes);
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(node) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|node| node.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            let cpulist = std::fs::read_to_string(entry.path().join("cpulist"))?;
+            for core_id in super::parse_core_list(&cpulist)? {
+                nodes.push((core_id, node));
+            }
+        }
+        Ok(nodes)
+    }
+
+    #[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+    pub fn get_topology() -> Result<Vec<CoreInfo>, Error> {
+        let nodes = get_numa_nodes()?;
+        Ok(get_core_ids()?
+            .into_iter()
+            .map(|id| {
+                let topology = format!("/sys/devices/system/cpu/cpu{}/topology", id.0);
+                CoreInfo {
+                    id,
+                    numa_node: nodes
+                        .iter()
+                        .find(|(core_id, _)| *core_id == id)
+                        .map_or(0, |(_, node)| *node),
+                    package: read_sysfs_usize(&format!("{topology}/physical_package_id"))
+                        .unwrap_or(0),
+                    physical_core: read_sysfs_usize(&format!("{topology}/core_id")).unwrap_or(id.0),
+                }
+            })
+            .collect())
+    }
+
+    #[cfg(all(feature = "std", any(target_os = "android", target_os = "linux")))]
+    pub fn set_memory_for_current(core_id: CoreId) -> Result<(), Error> {
+        const MPOL_PREFERRED: libc::c_int = 1;
+        const MASK_BITS: usize = libc::c_ulong::BITS as usize;
+
+        let Some((_, node)) = get_numa_nodes()?.into_iter().find(|(id, _)| *id == core_id) else {
+            return Err(Error::unsupported(format!(
+                "No NUMA node found for core {core_id:?}"
+            )));
+        };
+
+        let mut node_mask: Vec<libc::c_ulong> = vec![0; node / MASK_BITS + 1];
+        node_mask[node / MASK_BITS] |= 1 << (node % MASK_BITS);
+        // The kernel ignores the last bit of `maxnode`, hence the `+ 1`.
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_set_mempolicy,
+                MPOL_PREFERRED,
+                node_mask.as_ptr(),
+                node_mask.len() * MASK_BITS + 1,
+            )
+        };
+        if result < 0 {
+            Err(Error::unknown(format!(
+                "Failed to set the memory policy to node {node}: {}",
+                std::io::Error::last_os_error()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -834,4 +1115,45 @@ mod tests {
 
         ids[0].set_affinity().unwrap();
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_detect_topology() {
+        let topology = CpuTopology::detect().unwrap();
+        assert_eq!(topology.cores().len(), get_core_ids().unwrap().len());
+    }
+
+    #[test]
+    fn test_core_plan() {
+        // Two sockets with two physical cores each, and two hyperthreads per core
+        let topology = CpuTopology::new(
+            (0..8)
+                .map(|id| CoreInfo {
+                    id: CoreId(id),
+                    numa_node: id % 4 / 2,
+                    package: id % 4 / 2,
+                    physical_core: id % 2,
+                })
+                .collect(),
+        );
+        assert_eq!(topology.smt_siblings(CoreId(1)), vec![CoreId(5)]);
+
+        let cores = Cores::from_cmdline("0-7").unwrap();
+        let plan = topology.plan(&cores, &CorePlacement::default()).unwrap();
+        let ids = [0, 2, 1, 3, 4, 6, 5, 7].map(CoreId);
+        assert_eq!(plan.clients, ids);
+        assert_eq!(plan.broker, None);
+
+        let placement = CorePlacement {
+            avoid_smt_siblings: true,
+            dedicated_broker_core: true,
+            ..CorePlacement::default()
+        };
+        let plan = topology.plan(&cores, &placement).unwrap();
+        assert_eq!(plan.clients, [2, 1, 3].map(CoreId));
+        assert_eq!(plan.broker, Some(CoreId(0)));
+
+        let cores = Cores::from_cmdline("4").unwrap();
+        assert!(topology.plan(&cores, &placement).is_err());
+    }
 }
@@ -79,9 +79,9 @@ use core::{
 use std::{
     boxed::Box,
     env,
-    io::{ErrorKind, Read, Write},
+    io::{self, ErrorKind, Read, Write},
     net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
-    sync::mpsc::channel,
+    sync::{mpsc::channel, Arc, RwLock},
     thread,
 };
 
@@ -145,6 +145,8 @@ pub const LLMP_FLAG_COMPRESSED: Flags = Flags(0x1);
 pub const LLMP_FLAG_FROM_B2B: Flags = Flags(0x2);
 /// From another machine (with the `multi_machine` mode)
 pub const LLMP_FLAG_FROM_MM: Flags = Flags(0x4);
+/// Never forwarded to other brokers, such as status messages of the local broker.
+pub const LLMP_FLAG_NO_B2B: Flags = Flags(0x8);
 
 /// Timt the broker 2 broker connection waits for incoming data,
 /// before checking for own data to forward again.
@@ -211,6 +213,9 @@ impl Debug for Flags {
         if *self & LLMP_FLAG_FROM_B2B == LLMP_FLAG_FROM_B2B {
             f.write_str("FROM_B2B")?;
         }
+        if *self & LLMP_FLAG_NO_B2B == LLMP_FLAG_NO_B2B {
+            f.write_str("NO_B2B")?;
+        }
         f.write_str(" )")
     }
 }
@@ -264,6 +269,9 @@ pub enum TcpRequest {
     RemoteBrokerHello {
         /// The hostname of our broker, trying to connect.
         hostname: String,
+        /// If we want to encrypt the connection with our [`LlmpB2bKey`].
+        /// The other broker refuses the connection, unless it has a key as well.
+        encrypted: bool,
     },
     /// Notify the broker the the othe side is dying so remove this client
     /// `client_id` is the pid of the very initial client
@@ -506,12 +514,255 @@ pub fn recv_tcp_msg(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
     #[cfg(feature = "llmp_debug")]
     log::trace!("LLMP TCP: Receiving payload of size {size}");
 
-    stream
-        .read_exact(&mut bytes)
-        .expect("Failed to read message body");
+    stream.read_exact(&mut bytes).map_err(msg_body_error)?;
     Ok(bytes)
 }
 
+/// If a read failed because the read timeout of the stream expired
+#[cfg(feature = "std")]
+fn is_read_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// The error of a failed read in the middle of a message, after its length was received.
+/// The stream is out of sync afterwards, so an expired read timeout is reported as [`ErrorKind::TimedOut`].
+#[cfg(feature = "std")]
+fn msg_body_error(err: io::Error) -> Error {
+    if is_read_timeout(&err) {
+        Error::os_error(
+            io::Error::new(ErrorKind::TimedOut, err),
+            "LLMP TCP: Timed out in the middle of a message",
+        )
+    } else {
+        Error::os_error(err, "LLMP TCP: Failed to read the message body")
+    }
+}
+
+/// The Noise protocol of encrypted broker-to-broker connections:
+/// new ephemeral keys for each connection, authenticated by the pre-shared [`LlmpB2bKey`].
+#[cfg(feature = "llmp_b2b_encryption")]
+const LLMP_B2B_NOISE_PARAMS: &str = "Noise_NNpsk0_25519_ChaChaPoly_BLAKE2s";
+/// The max size of a single Noise message
+#[cfg(feature = "llmp_b2b_encryption")]
+const LLMP_B2B_NOISE_MAX_MSG_LEN: usize = 65535;
+/// The size of the authentication tag of each encrypted Noise message
+#[cfg(feature = "llmp_b2b_encryption")]
+const LLMP_B2B_NOISE_TAG_LEN: usize = 16;
+
+/// A pre-shared key to encrypt broker-to-broker connections, see [`LlmpBrokerInner::set_b2b_key`].
+///
+/// All brokers of a campaign use the same key. Brokers with a key only connect to brokers with the same key,
+/// and encrypt all messages between them, so testcases never cross untrusted networks in cleartext.
+/// Needs the `llmp_b2b_encryption` feature.
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct LlmpB2bKey([u8; 32]);
+
+#[cfg(feature = "std")]
+impl Debug for LlmpB2bKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // Don't leak the key to the logs
+        f.write_str("LlmpB2bKey(..)")
+    }
+}
+
+#[cfg(feature = "std")]
+impl LlmpB2bKey {
+    /// Creates a key from 32 random bytes
+    #[must_use]
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Parses a key from 64 hex digits, as generated by `openssl rand -hex 32`
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let hex = hex.trim();
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(Error::illegal_argument(
+                "A broker-to-broker key needs to be 64 hex digits",
+            ));
+        }
+        let mut key = [0; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(|_| {
+                Error::illegal_argument("A broker-to-broker key needs to be 64 hex digits")
+            })?;
+        }
+        Ok(Self(key))
+    }
+}
+
+/// Converts a Noise protocol error
+#[cfg(feature = "llmp_b2b_encryption")]
+#[allow(clippy::needless_pass_by_value)]
+fn noise_error(e: snow::Error) -> Error {
+    Error::illegal_state(format!("B2B: Noise protocol error: {e}"))
+}
+
+/// Send one Noise message as `u16` len and `[u8; len]` bytes
+#[cfg(feature = "llmp_b2b_encryption")]
+fn send_noise_frame(stream: &mut TcpStream, frame: &[u8]) -> Result<(), Error> {
+    let size_bytes = (frame.len() as u16).to_be_bytes();
+    stream.write_all(&size_bytes)?;
+    stream.write_all(frame)?;
+    Ok(())
+}
+
+/// Receive one Noise message of `u16` len and `[u8; len]` bytes
+#[cfg(feature = "llmp_b2b_encryption")]
+fn recv_noise_frame(stream: &mut TcpStream) -> Result<Vec<u8>, Error> {
+    let mut size_bytes = [0_u8; 2];
+    stream.read_exact(&mut size_bytes)?;
+    let mut frame = vec![0; u16::from_be_bytes(size_bytes).into()];
+    stream.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+/// A broker-to-broker connection, encrypted if the brokers have a [`LlmpB2bKey`]
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct B2bStream {
+    stream: TcpStream,
+    #[cfg(feature = "llmp_b2b_encryption")]
+    noise: Option<snow::TransportState>,
+}
+
+#[cfg(feature = "std")]
+impl B2bStream {
+    /// Sets up a connection after the hello messages, running the Noise handshake if there is a `key`.
+    /// The `initiator` is the broker that connected to the other one.
+    #[cfg_attr(
+        not(feature = "llmp_b2b_encryption"),
+        allow(unused_variables, clippy::unnecessary_wraps)
+    )]
+    fn new(stream: TcpStream, key: Option<&LlmpB2bKey>, initiator: bool) -> Result<Self, Error> {
+        match key {
+            None => Ok(Self {
+                stream,
+                #[cfg(feature = "llmp_b2b_encryption")]
+                noise: None,
+            }),
+            #[cfg(feature = "llmp_b2b_encryption")]
+            Some(key) => Self::handshake(stream, key, initiator),
+            #[cfg(not(feature = "llmp_b2b_encryption"))]
+            Some(_) => Err(Error::unsupported(
+                "Encrypted broker-to-broker connections need the llmp_b2b_encryption feature",
+            )),
+        }
+    }
+
+    /// Runs the Noise handshake, failing unless both brokers have the same key
+    #[cfg(feature = "llmp_b2b_encryption")]
+    fn handshake(mut stream: TcpStream, key: &LlmpB2bKey, initiator: bool) -> Result<Self, Error> {
+        let builder =
+            snow::Builder::new(LLMP_B2B_NOISE_PARAMS.parse().map_err(noise_error)?).psk(0, &key.0);
+        let mut handshake = if initiator {
+            builder.build_initiator()
+        } else {
+            builder.build_responder()
+        }
+        .map_err(noise_error)?;
+
+        let mut buf = vec![0; LLMP_B2B_NOISE_MAX_MSG_LEN];
+        while !handshake.is_handshake_finished() {
+            if handshake.is_my_turn() {
+                let len = handshake
+                    .write_message(&[], &mut buf)
+                    .map_err(noise_error)?;
+                send_noise_frame(&mut stream, &buf[..len])?;
+            } else {
+                let frame = recv_noise_frame(&mut stream)?;
+                handshake
+                    .read_message(&frame, &mut buf)
+                    .map_err(noise_error)?;
+            }
+        }
+
+        Ok(Self {
+            stream,
+            noise: Some(handshake.into_transport_mode().map_err(noise_error)?),
+        })
+    }
+
+    /// The address of the other broker
+    fn peer_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.stream.peer_addr()?)
+    }
+
+    /// Send one message, like [`send_tcp_msg`].
+    /// Encrypted, the length comes first, followed by the message in as many Noise messages as needed.
+    fn send_msg<T>(&mut self, msg: &T) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        #[cfg(feature = "llmp_b2b_encryption")]
+        if let Some(noise) = &mut self.noise {
+            let msg = postcard::to_allocvec(msg)?;
+            let Ok(len) = u32::try_from(msg.len()) else {
+                return Err(Error::illegal_state(format!(
+                    "Trying to send message a tcp message > u32! (size: {})",
+                    msg.len()
+                )));
+            };
+
+            let mut buf = vec![0; LLMP_B2B_NOISE_MAX_MSG_LEN];
+            let frame_len = noise
+                .write_message(&len.to_be_bytes(), &mut buf)
+                .map_err(noise_error)?;
+            send_noise_frame(&mut self.stream, &buf[..frame_len])?;
+            for chunk in msg.chunks(LLMP_B2B_NOISE_MAX_MSG_LEN - LLMP_B2B_NOISE_TAG_LEN) {
+                let frame_len = noise.write_message(chunk, &mut buf).map_err(noise_error)?;
+                send_noise_frame(&mut self.stream, &buf[..frame_len])?;
+            }
+            return Ok(());
+        }
+
+        send_tcp_msg(&mut self.stream, msg)
+    }
+
+    /// Receive one message, like [`recv_tcp_msg`], or [`None`] if none arrived before the read timeout.
+    ///
+    /// The read timeout applies to the rest of the message, too. If it expires in the middle of a
+    /// message, the stream is out of sync, and an [`ErrorKind::TimedOut`] error is returned.
+    fn recv_msg(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        #[cfg(feature = "llmp_b2b_encryption")]
+        if let Some(noise) = &mut self.noise {
+            let mut buf = vec![0; LLMP_B2B_NOISE_MAX_MSG_LEN];
+            let frame = match recv_noise_frame(&mut self.stream) {
+                Err(Error::OsError(err, ..)) if is_read_timeout(&err) => return Ok(None),
+                frame => frame?,
+            };
+            let frame_len = noise.read_message(&frame, &mut buf).map_err(noise_error)?;
+            let Ok(size_bytes) = <[u8; 4]>::try_from(&buf[..frame_len]) else {
+                return Err(Error::illegal_state(
+                    "B2B: Received an illegal message length",
+                ));
+            };
+            let size = u32::from_be_bytes(size_bytes) as usize;
+
+            let mut bytes = Vec::with_capacity(size);
+            while bytes.len() < size {
+                let frame = recv_noise_frame(&mut self.stream).map_err(|err| match err {
+                    Error::OsError(err, ..) => msg_body_error(err),
+                    err => err,
+                })?;
+                let frame_len = noise.read_message(&frame, &mut buf).map_err(noise_error)?;
+                bytes.extend_from_slice(&buf[..frame_len]);
+            }
+            return Ok(Some(bytes));
+        }
+
+        let mut size_bytes = [0_u8; 4];
+        match self.stream.read_exact(&mut size_bytes) {
+            Err(err) if is_read_timeout(&err) => return Ok(None),
+            read => read?,
+        }
+        let mut bytes = vec![0; u32::from_be_bytes(size_bytes) as usize];
+        self.stream.read_exact(&mut bytes).map_err(msg_body_error)?;
+        Ok(Some(bytes))
+    }
+}
+
 /// In case we don't have enough space, make sure the next page will be large
 /// enough. For now, we want to have at least enough space to store 2 of the
 /// largest messages we encountered (plus message one `new_page` message).
@@ -1869,6 +2120,27 @@ where
         }
     }
 
+    /// The amount of messages the sender posted to the current page, that were not received yet.
+    /// Messages on later pages, after the sender moved on, are not counted until we reach them.
+    #[must_use]
+    pub fn pending_msgs(&self) -> u64 {
+        // # Safety
+        // The current page and the last received message stay mapped as long as we exist.
+        unsafe {
+            let current_msg_id = u64::from(
+                (*self.current_recv_shmem.page())
+                    .current_msg_id
+                    .load(Ordering::Relaxed),
+            );
+            let last_msg_id = if self.last_msg_recvd.is_null() {
+                0
+            } else {
+                u64::from((*self.last_msg_recvd).message_id.0)
+            };
+            current_msg_id.saturating_sub(last_msg_id)
+        }
+    }
+
     /// Describe this client in a way, that it can be restored later with [`Self::on_existing_from_description`]
     pub fn describe(&self) -> Result<LlmpDescription, Error> {
         let map = &self.current_recv_shmem;
@@ -2075,6 +2347,9 @@ where
     clients_to_remove: Vec<ClientId>,
     /// The `ShMemProvider` to use
     shmem_provider: SP,
+    /// The key to encrypt broker-to-broker connections, shared with the listener threads
+    #[cfg(feature = "std")]
+    b2b_key: Arc<RwLock<Option<LlmpB2bKey>>>,
 }
 
 /// The broker (node 0)
@@ -2860,6 +3135,8 @@ where
             exit_cleanly_after: None,
             num_clients_seen: 0,
             shmem_provider,
+            #[cfg(feature = "std")]
+            b2b_key: Arc::default(),
         })
     }
 
@@ -2877,6 +3154,16 @@ where
         )
     }
 
+    /// The amount of messages sent by the clients that the broker did not handle yet,
+    /// see [`LlmpReceiver::pending_msgs`].
+    #[must_use]
+    pub fn pending_msgs(&self) -> u64 {
+        self.llmp_clients
+            .iter()
+            .map(LlmpReceiver::pending_msgs)
+            .sum()
+    }
+
     /// Create a new [`LlmpBrokerInner`] attaching to a TCP port
     #[cfg(feature = "std")]
     pub fn create_attach_to_tcp(shmem_provider: SP, port: u16) -> Result<Self, Error> {
@@ -2901,6 +3188,15 @@ where
         }
     }
 
+    /// Sets the pre-shared key to encrypt broker-to-broker connections, or `None` for cleartext connections.
+    ///
+    /// Brokers with a key only connect to, and accept connections from, brokers with the same key.
+    /// The key applies to all connections established afterwards, including those accepted by running listeners.
+    #[cfg(feature = "std")]
+    pub fn set_b2b_key(&mut self, key: Option<LlmpB2bKey>) {
+        *self.b2b_key.write().unwrap() = key;
+    }
+
     /// Set this broker to exit after at least `n_clients` clients attached and all client exited.
     /// Will ignore the own listener thread, if `create_attach_to_tcp`
     ///
@@ -2975,13 +3271,25 @@ where
             .to_string_lossy()
             .into();
 
-        send_tcp_msg(&mut stream, &TcpRequest::RemoteBrokerHello { hostname })?;
+        let b2b_key = self.b2b_key.read().unwrap().clone();
+        send_tcp_msg(
+            &mut stream,
+            &TcpRequest::RemoteBrokerHello {
+                hostname,
+                encrypted: b2b_key.is_some(),
+            },
+        )?;
 
         let broker_id = match recv_tcp_msg(&mut stream)?.try_into()? {
             TcpResponse::RemoteBrokerAccepted { broker_id } => {
                 log::info!("B2B: Got Connection Ack, broker_id {broker_id:?}");
                 broker_id
             }
+            TcpResponse::Error { description } => {
+                return Err(Error::illegal_state(format!(
+                    "B2B: The remote broker refused the connection: {description}"
+                )));
+            }
             _ => {
                 return Err(Error::illegal_state(
                     "Unexpected response from B2B server received.".to_string(),
@@ -2995,6 +3303,8 @@ where
         // TODO: handle broker_ids properly/at all.
         let map_description = Self::b2b_thread_on(
             stream,
+            b2b_key,
+            true,
             self.peek_next_client_id(),
             &self
                 .llmp_out
@@ -3125,11 +3435,14 @@ where
     /// Launches a proxy thread.
     /// It will read outgoing messages from the given broker map (and handle EOP by mapping a new page).
     /// This function returns the [`ShMemDescription`] the client uses to place incoming messages.
+    /// With a `b2b_key`, the connection is encrypted, the `initiator` being the broker that connected.
     /// The thread exits, when the remote broker disconnects.
     #[cfg(feature = "std")]
     #[allow(clippy::let_and_return, clippy::too_many_lines)]
     fn b2b_thread_on(
-        mut stream: TcpStream,
+        stream: TcpStream,
+        b2b_key: Option<LlmpB2bKey>,
+        initiator: bool,
         b2b_client_id: ClientId,
         broker_shmem_description: &ShMemDescription,
     ) -> Result<ShMemDescription, Error> {
@@ -3151,6 +3464,16 @@ where
                 .set_read_timeout(Some(_LLMP_B2B_BLOCK_TIME))
                 .expect("Failed to set tcp stream timeout");
 
+            // The handshake, if encrypted, before forwarding anything
+            let mut stream = match B2bStream::new(stream, b2b_key.as_ref(), initiator) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::error!("B2B: Could not set up the connection: {e}");
+                    drop(send.send(Err(e)));
+                    return;
+                }
+            };
+
             let mut new_sender =
                 match LlmpSender::new(shmem_provider_bg.clone(), b2b_client_id, false) {
                     Ok(new_sender) => new_sender,
@@ -3159,7 +3482,12 @@ where
                     }
                 };
 
-            send.send(new_sender.out_shmems.first().unwrap().shmem.description())
+            send.send(Ok(new_sender
+                .out_shmems
+                .first()
+                .unwrap()
+                .shmem
+                .description()))
                 .expect("B2B: Error sending map description to channel!");
 
             // the receiver receives from the local broker, and forwards it to the tcp stream.
@@ -3189,6 +3517,9 @@ where
                                 );
                                 continue;
                             }
+                            if flags & LLMP_FLAG_NO_B2B == LLMP_FLAG_NO_B2B {
+                                continue;
+                            }
 
                             #[cfg(feature = "llmp_debug")]
                             log::info!(
@@ -3196,15 +3527,12 @@ where
                                 payload.len()
                             );
                             // We got a new message! Forward...
-                            if let Err(e) = send_tcp_msg(
-                                &mut stream,
-                                &TcpRemoteNewMessage {
-                                    client_id,
-                                    tag,
-                                    flags,
-                                    payload: payload.to_vec(),
-                                },
-                            ) {
+                            if let Err(e) = stream.send_msg(&TcpRemoteNewMessage {
+                                client_id,
+                                tag,
+                                flags,
+                                payload: payload.to_vec(),
+                            }) {
                                 log::info!("Got error {e} while trying to forward a message to broker {peer_address}, exiting thread");
                                 return;
                             }
@@ -3221,10 +3549,12 @@ where
                 // We set a timeout on the receive earlier.
                 // This makes sure we will still forward our own stuff.
                 // Forwarding happens between each recv, too, as simplification.
-                // We ignore errors completely as they may be timeout, or stream closings.
-                // Instead, we catch stream close when/if we next try to send.
-                match recv_tcp_msg(&mut stream) {
-                    Ok(val) => {
+                match stream.recv_msg() {
+                    Ok(None) => {
+                        #[cfg(feature = "llmp_debug")]
+                        log::info!("Received no input, timeout. Looping back up :)");
+                    }
+                    Ok(Some(val)) => {
                         let msg: TcpRemoteNewMessage = val.try_into().expect(
                             "Illegal message received from broker 2 broker connection - shutting down.",
                         );
@@ -3245,26 +3575,27 @@ where
                             )
                             .expect("B2B: Error forwarding message. Exiting.");
                     }
+                    Err(Error::OsError(e, ..)) if e.kind() == ErrorKind::UnexpectedEof => {
+                        log::info!("Broker {peer_address} seems to have disconnected, exiting");
+                        return;
+                    }
                     Err(e) => {
-                        if let Error::OsError(e, ..) = e {
-                            if e.kind() == ErrorKind::UnexpectedEof {
-                                log::info!(
-                                    "Broker {peer_address} seems to have disconnected, exiting"
-                                );
-                                return;
-                            }
-                        }
-
-                        #[cfg(feature = "llmp_debug")]
-                        log::info!("Received no input, timeout or closed. Looping back up :)");
+                        // A partially read message leaves the stream out of sync
+                        log::error!(
+                            "B2B: Failed to receive from broker {peer_address}, exiting: {e}"
+                        );
+                        return;
                     }
                 }
             }
         });
 
-        let ret = recv.recv().map_err(|_| {
-            Error::unknown("Error launching background thread for b2b communcation".to_string())
-        });
+        let ret = recv
+            .recv()
+            .map_err(|_| {
+                Error::unknown("Error launching background thread for b2b communcation".to_string())
+            })
+            .and_then(|description| description);
 
         #[cfg(feature = "llmp_debug")]
         log::info!("B2B: returning from loop. Success: {}", ret.is_ok());
@@ -3280,6 +3611,7 @@ where
         current_client_id: &mut ClientId,
         sender: &mut LlmpSender<SP>,
         broker_shmem_description: &ShMemDescription,
+        b2b_key: Option<LlmpB2bKey>,
     ) {
         match request {
             TcpRequest::ClientQuit { client_id } => {
@@ -3305,9 +3637,30 @@ where
                 };
                 current_client_id.0 += 1;
             }
-            TcpRequest::RemoteBrokerHello { hostname } => {
+            TcpRequest::RemoteBrokerHello {
+                hostname,
+                encrypted,
+            } => {
                 log::info!("B2B new client: {hostname}");
 
+                if *encrypted != b2b_key.is_some() {
+                    let description = if *encrypted {
+                        "This broker has no key for encrypted broker-to-broker connections"
+                    } else {
+                        "This broker only accepts encrypted broker-to-broker connections"
+                    };
+                    log::warn!("B2B: Refusing {hostname}: {description}");
+                    if let Err(e) = send_tcp_msg(
+                        &mut stream,
+                        &TcpResponse::Error {
+                            description: description.to_string(),
+                        },
+                    ) {
+                        log::info!("An error occurred sending via tcp {e}");
+                    }
+                    return;
+                }
+
                 // TODO: Clean up broker ids.
                 if send_tcp_msg(
                     &mut stream,
@@ -3321,9 +3674,13 @@ where
                     return;
                 }
 
-                if let Ok(shmem_description) =
-                    Self::b2b_thread_on(stream, *current_client_id, broker_shmem_description)
-                {
+                if let Ok(shmem_description) = Self::b2b_thread_on(
+                    stream,
+                    b2b_key,
+                    false,
+                    *current_client_id,
+                    broker_shmem_description,
+                ) {
                     if Self::announce_new_client(sender, &shmem_description).is_err() {
                         log::info!("B2B: Error announcing client {shmem_description:?}");
                     };
@@ -3353,6 +3710,7 @@ where
         };
 
         let llmp_tcp_id = self.peek_next_client_id();
+        let b2b_key = self.b2b_key.clone();
 
         // Tcp out map sends messages from background thread tcp server to foreground client
         let tcp_out_shmem = LlmpSharedMap::new(
@@ -3425,6 +3783,7 @@ where
                             &mut current_client_id,
                             &mut tcp_incoming_sender,
                             &broker_shmem_description,
+                            b2b_key.read().unwrap().clone(),
                         );
                     }
                     ListenerStream::Empty() => {
@@ -3787,11 +4146,14 @@ mod tests {
     use serial_test::serial;
 
     use super::{
-        LlmpClient,
+        LlmpB2bKey, LlmpBrokerInner, LlmpClient,
         LlmpConnection::{self, IsBroker, IsClient},
         Tag,
     };
-    use crate::shmem::{ShMemProvider, StdShMemProvider};
+    use crate::{
+        shmem::{ShMemProvider, StdShMemProvider},
+        IP_LOCALHOST,
+    };
 
     #[test]
     #[serial]
@@ -3841,4 +4203,29 @@ mod tests {
         // We want at least the tcp and sender clients.
         assert_eq!(broker.inner.llmp_clients.len(), 2);
     }
+
+    #[test]
+    fn test_llmp_b2b_key_from_hex() {
+        let key = LlmpB2bKey::from_hex(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f\n",
+        )
+        .unwrap();
+        assert_eq!(key, LlmpB2bKey::new(core::array::from_fn(|i| i as u8)));
+        assert!(LlmpB2bKey::from_hex("0001").is_err());
+        assert!(LlmpB2bKey::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_llmp_b2b_refuses_key_mismatch() {
+        let shmem_provider = StdShMemProvider::new().unwrap();
+        let _listening_broker =
+            LlmpBrokerInner::create_attach_to_tcp(shmem_provider.clone(), 1338).unwrap();
+
+        // The listening broker has no key, so it refuses to encrypt, instead of sending in cleartext
+        let mut connecting_broker = LlmpBrokerInner::new(shmem_provider).unwrap();
+        connecting_broker.set_b2b_key(Some(LlmpB2bKey::new([0x42; 32])));
+        assert!(connecting_broker.connect_b2b((IP_LOCALHOST, 1338)).is_err());
+    }
 }
@@ -748,6 +748,24 @@ impl<'a, T: 'a + Sized> OwnedMutSlice<'a, T> {
         }
     }
 
+    /// Resize the inner slice or vec returning the old size on success or `None` on failure.
+    ///
+    /// Owned vecs grow, filling new entries with `value`.
+    /// Borrowed slices can only shrink, as we do not own the memory behind them.
+    pub fn resize(&mut self, new_len: usize, value: T) -> Option<usize>
+    where
+        T: Clone,
+    {
+        match &mut self.inner {
+            OwnedMutSliceInner::Owned(v) => {
+                let tmp = v.len();
+                v.resize(new_len, value);
+                Some(tmp)
+            }
+            _ => self.truncate(new_len),
+        }
+    }
+
     /// Returns an iterator over the slice.
     pub fn iter(&self) -> Iter<'_, T> {
         <&Self as IntoIterator>::into_iter(self)
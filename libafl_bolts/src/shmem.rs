@@ -664,6 +664,7 @@ pub mod unix_shmem {
         };
 
         use crate::{
+            hash_std,
             rands::{Rand, StdRand},
             shmem::{ShMem, ShMemId, ShMemProvider},
             Error,
@@ -692,17 +693,14 @@ pub mod unix_shmem {
         impl MmapShMem {
             /// Create a new [`MmapShMem`]
             ///
-            /// At most [`MAX_MMAP_FILENAME_LEN`] - 2 bytes from filename will be used.
+            /// Filenames of up to [`MAX_MMAP_FILENAME_LEN`] - 2 bytes are used as they are.
+            /// Longer filenames, which would not fit into a [`ShMemId`], nor into the name limits of some
+            /// operating systems, are replaced by their hash, so that they never collide after truncation.
             ///
             /// This will *NOT* automatically delete the shmem files, meaning that it's user's responsibility to delete them after fuzzing
             pub fn new(map_size: usize, filename: impl AsRef<Path>) -> Result<Self, Error> {
-                let filename_bytes = filename.as_ref().as_os_str().as_encoded_bytes();
-
-                let mut filename_path: [u8; 20] = [0_u8; MAX_MMAP_FILENAME_LEN];
-                // Keep room for the leading slash and trailing NULL.
-                let max_copy = usize::min(filename_bytes.len(), MAX_MMAP_FILENAME_LEN - 2);
-                filename_path[0] = b'/';
-                filename_path[1..=max_copy].copy_from_slice(&filename_bytes[..max_copy]);
+                let filename_path =
+                    Self::shm_name(filename.as_ref().as_os_str().as_encoded_bytes());
 
                 log::info!(
                     "{} Creating shmem {} {:?}",
@@ -771,6 +769,40 @@ pub mod unix_shmem {
                 }
             }
 
+            /// The `shm_open` name for `filename`, with a leading slash and a trailing NULL.
+            fn shm_name(filename: &[u8]) -> [u8; MAX_MMAP_FILENAME_LEN] {
+                let mut filename_path = [0_u8; MAX_MMAP_FILENAME_LEN];
+                filename_path[0] = b'/';
+                // Keep room for the leading slash and trailing NULL.
+                if filename.len() <= MAX_MMAP_FILENAME_LEN - 2 {
+                    filename_path[1..=filename.len()].copy_from_slice(filename);
+                } else {
+                    let hash = format!("{:016x}", hash_std(filename));
+                    filename_path[1..=hash.len()].copy_from_slice(hash.as_bytes());
+                }
+                filename_path
+            }
+
+            /// Hints the kernel to back this map with transparent huge pages.
+            ///
+            /// Huge pages need fewer TLB entries for large maps, such as big coverage maps.
+            /// This is best-effort: if the kernel does not support it, the map keeps its normal pages.
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            fn advise_huge_pages(&self) {
+                // # Safety
+                // The map is ours, and madvise does not touch its contents.
+                let ret = unsafe {
+                    libc::madvise(self.map as *mut _, self.map_size, libc::MADV_HUGEPAGE)
+                };
+                if ret != 0 {
+                    log::debug!(
+                        "madvise(MADV_HUGEPAGE) failed for map {:?}: {}",
+                        self.id,
+                        io::Error::last_os_error()
+                    );
+                }
+            }
+
             #[allow(clippy::unnecessary_wraps)]
             fn shmem_from_id_and_size(id: ShMemId, map_size: usize) -> Result<Self, Error> {
                 // # Safety
@@ -938,11 +970,27 @@ pub mod unix_shmem {
         }
 
         /// A [`ShMemProvider`] which uses [`shm_open`] and [`mmap`] to provide shared memory mappings.
+        ///
+        /// On `MacOS` and `iOS`, where maps are shared by their file descriptor, the names of the new maps are
+        /// unlinked right after they got mapped, so the maps never leak, and live as long as a process uses them.
         #[cfg(unix)]
         #[derive(Clone, Debug)]
-        pub struct MmapShMemProvider {}
+        pub struct MmapShMemProvider {
+            /// If the new maps should be backed by (transparent) huge pages, where supported
+            #[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+            huge_pages: bool,
+        }
 
         impl MmapShMemProvider {
+            /// Create a [`MmapShMemProvider`] that backs its maps by transparent huge pages, on Linux and Android.
+            ///
+            /// This is a hint to the kernel: maps stay on normal pages, if huge pages are not available
+            /// (see `/sys/kernel/mm/transparent_hugepage/shmem_enabled`), and on other operating systems.
+            #[must_use]
+            pub fn with_huge_pages() -> Self {
+                Self { huge_pages: true }
+            }
+
             /// Create a [`MmapShMem`] with the specified size and id.
             ///
             /// At most [`MAX_MMAP_FILENAME_LEN`] - 2 bytes from id will be used.
@@ -971,16 +1019,29 @@ pub mod unix_shmem {
             type ShMem = MmapShMem;
 
             fn new() -> Result<Self, Error> {
-                Ok(Self {})
+                Ok(Self { huge_pages: false })
             }
 
             fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
                 let mut rand = StdRand::with_seed(crate::rands::random_seed());
                 let id = rand.next() as u32;
                 let mut full_file_name = format!("libafl_{}_{}", process::id(), id);
-                // leave one byte space for the null byte.
-                full_file_name.truncate(MAX_MMAP_FILENAME_LEN - 1);
-                MmapShMem::new(map_size, full_file_name)
+                // leave space for the leading slash and the null byte.
+                full_file_name.truncate(MAX_MMAP_FILENAME_LEN - 2);
+                let shmem = MmapShMem::new(map_size, full_file_name)?;
+
+                // The map is shared by its fd, so nobody needs the name anymore.
+                #[cfg(target_vendor = "apple")]
+                if let Some(filename_path) = shmem.filename_path() {
+                    unsafe { shm_unlink(filename_path.as_ptr() as *const _) };
+                }
+
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                if self.huge_pages {
+                    shmem.advise_huge_pages();
+                }
+
+                Ok(shmem)
             }
 
             fn shmem_from_id_and_size(
@@ -1012,8 +1073,28 @@ pub mod unix_shmem {
 
         impl CommonUnixShMem {
             /// Create a new shared memory mapping, using shmget/shmat
-            #[allow(unused_qualifications)]
             pub fn new(map_size: usize) -> Result<Self, Error> {
+                Self::with_segment_size(map_size, map_size, 0)
+            }
+
+            /// Create a new shared memory mapping backed by huge pages, using shmget/shmat with `SHM_HUGETLB`.
+            ///
+            /// The segment is rounded up to a multiple of the huge page size, the map keeps its `map_size`.
+            /// This needs enough huge pages reserved in `/proc/sys/vm/nr_hugepages`, and
+            /// the process to be in `/proc/sys/vm/hugetlb_shm_group` (or to have `CAP_IPC_LOCK`).
+            #[cfg(target_os = "linux")]
+            pub fn new_with_huge_pages(map_size: usize) -> Result<Self, Error> {
+                let huge_page_size = huge_page_size();
+                let segment_size = map_size.div_ceil(huge_page_size) * huge_page_size;
+                Self::with_segment_size(map_size, segment_size, libc::SHM_HUGETLB)
+            }
+
+            #[allow(unused_qualifications)]
+            fn with_segment_size(
+                map_size: usize,
+                segment_size: usize,
+                flags: c_int,
+            ) -> Result<Self, Error> {
                 #[cfg(any(target_os = "solaris", target_os = "illumos"))]
                 const SHM_R: libc::c_int = 0o400;
                 #[cfg(not(any(target_os = "solaris", target_os = "illumos")))]
@@ -1026,12 +1107,12 @@ pub mod unix_shmem {
                 unsafe {
                     let os_id = shmget(
                         libc::IPC_PRIVATE,
-                        map_size,
-                        libc::IPC_CREAT | libc::IPC_EXCL | SHM_R | SHM_W,
+                        segment_size,
+                        libc::IPC_CREAT | libc::IPC_EXCL | SHM_R | SHM_W | flags,
                     );
 
                     if os_id < 0_i32 {
-                        return Err(Error::unknown(format!("Failed to allocate a shared mapping of size {map_size} - check OS limits (i.e shmall, shmmax)")));
+                        return Err(Error::unknown(format!("Failed to allocate a shared mapping of size {segment_size} - check OS limits (i.e shmall, shmmax)")));
                     }
 
                     let map = shmat(os_id, ptr::null(), 0) as *mut c_uchar;
@@ -1099,10 +1180,40 @@ pub mod unix_shmem {
             }
         }
 
+        /// The size of the default huge pages, as reported by `/proc/meminfo`, or 2MiB.
+        #[cfg(target_os = "linux")]
+        fn huge_page_size() -> usize {
+            std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| {
+                    meminfo
+                        .lines()
+                        .find_map(|line| line.strip_prefix("Hugepagesize:"))
+                        .and_then(|size| size.trim().trim_end_matches("kB").trim().parse().ok())
+                })
+                .map_or(2 * 1024 * 1024, |size_kb: usize| size_kb * 1024)
+        }
+
         /// A [`ShMemProvider`] which uses `shmget`/`shmat`/`shmctl` to provide shared memory mappings.
         #[cfg(unix)]
         #[derive(Clone, Debug)]
-        pub struct CommonUnixShMemProvider {}
+        pub struct CommonUnixShMemProvider {
+            /// If the new maps should be backed by huge pages, where supported
+            #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+            huge_pages: bool,
+        }
+
+        #[cfg(unix)]
+        impl CommonUnixShMemProvider {
+            /// Create a [`CommonUnixShMemProvider`] that backs its maps by huge pages, on Linux.
+            ///
+            /// If a map cannot get huge pages, see [`CommonUnixShMem::new_with_huge_pages`],
+            /// it falls back to normal pages.
+            #[must_use]
+            pub fn with_huge_pages() -> Self {
+                Self { huge_pages: true }
+            }
+        }
 
         unsafe impl Send for CommonUnixShMemProvider {}
 
@@ -1119,9 +1230,16 @@ pub mod unix_shmem {
             type ShMem = CommonUnixShMem;
 
             fn new() -> Result<Self, Error> {
-                Ok(Self {})
+                Ok(Self { huge_pages: false })
             }
             fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
+                #[cfg(target_os = "linux")]
+                if self.huge_pages {
+                    match CommonUnixShMem::new_with_huge_pages(map_size) {
+                        Ok(shmem) => return Ok(shmem),
+                        Err(e) => log::warn!("No huge pages for map of size {map_size}: {e}"),
+                    }
+                }
                 CommonUnixShMem::new(map_size)
             }
 
@@ -1553,12 +1671,24 @@ pub mod win32_shmem {
 
     use uuid::Uuid;
     use windows::{
-        core::PCSTR,
+        core::{PCSTR, PCWSTR},
         Win32::{
-            Foundation::{CloseHandle, BOOL, HANDLE},
-            System::Memory::{
-                CreateFileMappingA, MapViewOfFile, OpenFileMappingA, UnmapViewOfFile,
-                FILE_MAP_ALL_ACCESS, MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE,
+            Foundation::{
+                CloseHandle, GetLastError, SetHandleInformation, BOOL, ERROR_NOT_ALL_ASSIGNED,
+                HANDLE, HANDLE_FLAG_INHERIT, LUID,
+            },
+            Security::{
+                AdjustTokenPrivileges, LookupPrivilegeValueW, LUID_AND_ATTRIBUTES,
+                SE_LOCK_MEMORY_NAME, SE_PRIVILEGE_ENABLED, TOKEN_ADJUST_PRIVILEGES,
+                TOKEN_PRIVILEGES,
+            },
+            System::{
+                Memory::{
+                    CreateFileMappingA, GetLargePageMinimum, MapViewOfFile, OpenFileMappingA,
+                    UnmapViewOfFile, FILE_MAP_ALL_ACCESS, FILE_MAP_LARGE_PAGES,
+                    MEMORY_MAPPED_VIEW_ADDRESS, PAGE_READWRITE, SEC_COMMIT, SEC_LARGE_PAGES,
+                },
+                Threading::{GetCurrentProcess, OpenProcessToken},
             },
         },
     };
@@ -1570,7 +1700,7 @@ pub mod win32_shmem {
 
     const INVALID_HANDLE_VALUE: *mut c_void = -1isize as *mut c_void;
 
-    /// The default [`ShMem`] impl for Windows using `shmctl` & `shmget`
+    /// The default [`ShMem`] impl for Windows using named file mappings
     #[derive(Clone)]
     pub struct Win32ShMem {
         id: ShMemId,
@@ -1591,7 +1721,22 @@ pub mod win32_shmem {
     }
 
     impl Win32ShMem {
-        fn new_shmem(map_size: usize) -> Result<Self, Error> {
+        fn new_shmem(map_size: usize, large_pages: bool) -> Result<Self, Error> {
+            // Large pages need the mapping, and each view, to be a multiple of the large page size.
+            let (section_size, protection, access) = if large_pages {
+                let large_page_size = unsafe { GetLargePageMinimum() };
+                if large_page_size == 0 {
+                    return Err(Error::unsupported("Large pages are not supported"));
+                }
+                (
+                    map_size.div_ceil(large_page_size) * large_page_size,
+                    PAGE_READWRITE | SEC_COMMIT | SEC_LARGE_PAGES,
+                    FILE_MAP_ALL_ACCESS | FILE_MAP_LARGE_PAGES,
+                )
+            } else {
+                (map_size, PAGE_READWRITE, FILE_MAP_ALL_ACCESS)
+            };
+
             unsafe {
                 let uuid = Uuid::new_v4();
                 let mut map_str = format!("libafl_{}", uuid.simple());
@@ -1600,15 +1745,15 @@ pub mod win32_shmem {
                 let handle = CreateFileMappingA(
                     HANDLE(INVALID_HANDLE_VALUE),
                     None,
-                    PAGE_READWRITE,
-                    0,
-                    map_size as u32,
+                    protection,
+                    (section_size as u64 >> 32) as u32,
+                    section_size as u32,
                     PCSTR(map_str_bytes.as_mut_ptr()),
                 )?;
 
-                let map =
-                    MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, map_size).Value as *mut u8;
+                let map = MapViewOfFile(handle, access, 0, 0, section_size).Value as *mut u8;
                 if map.is_null() {
+                    let _ = CloseHandle(handle);
                     return Err(Error::unknown(format!(
                         "Cannot map shared memory {}",
                         String::from_utf8_lossy(map_str_bytes)
@@ -1634,9 +1779,10 @@ pub mod win32_shmem {
                     PCSTR(map_str_bytes.as_ptr().cast_mut()),
                 )?;
 
-                let map =
-                    MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, map_size).Value as *mut u8;
+                // Size 0 maps the whole mapping, which may be larger than `map_size` for large pages.
+                let map = MapViewOfFile(handle, FILE_MAP_ALL_ACCESS, 0, 0, 0).Value as *mut u8;
                 if map.is_null() {
+                    let _ = CloseHandle(handle);
                     return Err(Error::unknown(format!(
                         "Cannot map shared memory {}",
                         String::from_utf8_lossy(&map_str_bytes)
@@ -1650,6 +1796,63 @@ pub mod win32_shmem {
                 })
             }
         }
+
+        /// Makes the handle of this mapping inheritable, so that child processes hold on to the mapping.
+        ///
+        /// A named mapping only lives as long as a process has a handle to it.
+        /// With an inherited handle, the mapping survives the restarts of the process that created it,
+        /// as long as one of its children lives, and can be reopened from its [`ShMemId`].
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error if the handle flags could not be set.
+        pub fn persist(self) -> Result<Self, Error> {
+            unsafe {
+                SetHandleInformation(self.handle, HANDLE_FLAG_INHERIT.0, HANDLE_FLAG_INHERIT)?
+            };
+            Ok(self)
+        }
+    }
+
+    /// Enables the `SeLockMemoryPrivilege` for this process, which large pages need.
+    ///
+    /// The user needs to have the "Lock pages in memory" right for this to succeed.
+    fn enable_lock_memory_privilege() -> Result<(), Error> {
+        unsafe {
+            let mut token = HANDLE::default();
+            OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES, &mut token)?;
+
+            let mut luid = LUID::default();
+            let res = LookupPrivilegeValueW(PCWSTR::null(), SE_LOCK_MEMORY_NAME, &mut luid)
+                .and_then(|()| {
+                    let privileges = TOKEN_PRIVILEGES {
+                        PrivilegeCount: 1,
+                        Privileges: [LUID_AND_ATTRIBUTES {
+                            Luid: luid,
+                            Attributes: SE_PRIVILEGE_ENABLED,
+                        }],
+                    };
+                    AdjustTokenPrivileges(
+                        token,
+                        BOOL(0),
+                        Some(&raw const privileges),
+                        0,
+                        None,
+                        None,
+                    )
+                });
+            // AdjustTokenPrivileges succeeds, even if the user does not have the privilege.
+            let not_assigned = GetLastError() == ERROR_NOT_ALL_ASSIGNED;
+            let _ = CloseHandle(token);
+
+            res?;
+            if not_assigned {
+                return Err(Error::illegal_state(
+                    "The user lacks the SeLockMemoryPrivilege (\"Lock pages in memory\") for large pages",
+                ));
+            }
+            Ok(())
+        }
     }
 
     impl ShMem for Win32ShMem {
@@ -1692,7 +1895,28 @@ pub mod win32_shmem {
 
     /// A [`ShMemProvider`] which uses `win32` functions to provide shared memory mappings.
     #[derive(Clone, Debug)]
-    pub struct Win32ShMemProvider {}
+    pub struct Win32ShMemProvider {
+        /// If the new maps should be backed by large pages
+        large_pages: bool,
+    }
+
+    impl Win32ShMemProvider {
+        /// Create a [`Win32ShMemProvider`] that backs its maps by large pages.
+        ///
+        /// Large pages need the "Lock pages in memory" right for the user.
+        /// Without it, or if there is not enough contiguous memory left, the maps fall back to normal pages.
+        #[must_use]
+        pub fn with_large_pages() -> Self {
+            let large_pages = match enable_lock_memory_privilege() {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("Cannot use large pages: {e}");
+                    false
+                }
+            };
+            Self { large_pages }
+        }
+    }
 
     impl Default for Win32ShMemProvider {
         fn default() -> Self {
@@ -1705,10 +1929,16 @@ pub mod win32_shmem {
         type ShMem = Win32ShMem;
 
         fn new() -> Result<Self, Error> {
-            Ok(Self {})
+            Ok(Self { large_pages: false })
         }
         fn new_shmem(&mut self, map_size: usize) -> Result<Self::ShMem, Error> {
-            Win32ShMem::new_shmem(map_size)
+            if self.large_pages {
+                match Win32ShMem::new_shmem(map_size, true) {
+                    Ok(shmem) => return Ok(shmem),
+                    Err(e) => log::warn!("No large pages for map of size {map_size}: {e}"),
+                }
+            }
+            Win32ShMem::new_shmem(map_size, false)
         }
 
         fn shmem_from_id_and_size(
@@ -1846,6 +2076,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore)]
+    fn test_huge_pages_shmem() -> Result<(), Error> {
+        use crate::shmem::{MmapShMemProvider, UnixShMemProvider};
+
+        // Falls back to normal pages, if no huge pages are reserved
+        let mut map = UnixShMemProvider::with_huge_pages().new_shmem(1024)?;
+        map.as_slice_mut()[1023] = 1;
+        assert_eq!(map.as_slice().len(), 1024);
+        assert_eq!(1, map.as_slice()[1023]);
+
+        let mut map = MmapShMemProvider::with_huge_pages().new_shmem(1024)?;
+        map.as_slice_mut()[1023] = 1;
+        assert_eq!(map.as_slice().len(), 1024);
+        assert_eq!(1, map.as_slice()[1023]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(unix, not(any(target_os = "android", target_vendor = "apple"))))]
+    #[cfg_attr(miri, ignore)]
+    fn test_mmap_shmem_long_names() -> Result<(), Error> {
+        use std::{format, process, vec::Vec};
+
+        use crate::shmem::{MmapShMemProvider, ShMem as _};
+
+        let mut provider = MmapShMemProvider::new()?;
+        // Both names are longer than a `ShMemId`, and only differ at the end
+        let shmems = ["a", "b"]
+            .iter()
+            .map(|suffix| {
+                provider
+                    .new_shmem_with_id(1024, format!("libafl_long_name_{}_{suffix}", process::id()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for shmem in &shmems {
+            let filename_path = shmem.filename_path().unwrap();
+            unsafe { libc::shm_unlink(filename_path.as_ptr() as *const _) };
+        }
+        assert_ne!(shmems[0].id(), shmems[1].id());
+        Ok(())
+    }
+
     #[test]
     #[cfg(unix)]
     #[cfg_attr(miri, ignore)]
@@ -1,6 +1,12 @@
 //! Stores and restores state when a client needs to relaunch.
 //! Uses a [`ShMem`] up to a threshold, then write to disk.
-use alloc::string::{String, ToString};
+//!
+//! States are framed with a format version, an optional zstd compression, and a checksum,
+//! see [`serialize_state`]. The same format is used for state snapshots on disk, see [`save_state_snapshot`].
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
     hash::{BuildHasher, Hasher},
     marker::PhantomData,
@@ -9,14 +15,16 @@ use core::{
 };
 use std::{
     env::temp_dir,
+    ffi::OsString,
     fs::{self, File},
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     ptr::read_volatile,
 };
 
 use ahash::RandomState;
 use serde::{de::DeserializeOwned, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::{
     shmem::{ShMem, ShMemProvider},
@@ -26,6 +34,151 @@ use crate::{
 /// If the saved page content equals exactly this buf, the restarted child wants to exit cleanly.
 const EXITING_MAGIC: &[u8; 16] = b"LIBAFL_EXIT_NOW\0";
 
+/// Every framed state starts with these bytes.
+const STATE_MAGIC: &[u8; 4] = b"LAFS";
+
+/// The version of the framed state format, and of the way [`StateRestorer`] stores it.
+///
+/// States framed with another version are refused, instead of deserializing garbage.
+/// This only versions the framing: the header, compression and checksum. The serialized state itself
+/// carries no schema version, so states of a fuzzer built with changed state types may still fail
+/// to deserialize, or deserialize to wrong values.
+pub const STATE_FORMAT_VERSION: u16 = 1;
+
+/// The header of a framed state: magic, format version, flags, payload length, and checksum.
+const STATE_HEADER_LEN: usize = 4 + 2 + 2 + 8 + 8;
+
+/// Flag for a zstd compressed payload
+const STATE_FLAG_ZSTD: u16 = 1;
+
+/// Smaller states are not worth compressing.
+#[cfg(feature = "state_compression")]
+const STATE_COMPRESS_THRESHOLD: usize = 4096;
+
+/// The checksum of a framed payload, stable across builds and platforms.
+fn state_checksum(payload: &[u8]) -> u64 {
+    xxh3_64(payload)
+}
+
+/// Serializes `state` to a framed buffer, carrying the [`STATE_FORMAT_VERSION`] and a checksum.
+///
+/// With the `state_compression` feature, larger states are compressed with zstd.
+/// [`deserialize_state`] reads the buffer back, compressed or not.
+pub fn serialize_state<S>(state: &S) -> Result<Vec<u8>, Error>
+where
+    S: Serialize,
+{
+    let serialized = postcard::to_allocvec(state)?;
+
+    #[cfg(feature = "state_compression")]
+    let (flags, payload) = if serialized.len() >= STATE_COMPRESS_THRESHOLD {
+        (STATE_FLAG_ZSTD, zstd::encode_all(serialized.as_slice(), 0)?)
+    } else {
+        (0, serialized)
+    };
+    #[cfg(not(feature = "state_compression"))]
+    let (flags, payload) = (0_u16, serialized);
+
+    let mut framed = Vec::with_capacity(STATE_HEADER_LEN + payload.len());
+    framed.extend_from_slice(STATE_MAGIC);
+    framed.extend_from_slice(&STATE_FORMAT_VERSION.to_le_bytes());
+    framed.extend_from_slice(&flags.to_le_bytes());
+    framed.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    framed.extend_from_slice(&state_checksum(&payload).to_le_bytes());
+    framed.extend_from_slice(&payload);
+    Ok(framed)
+}
+
+/// Deserializes a state framed by [`serialize_state`].
+///
+/// Fails for states of another [`STATE_FORMAT_VERSION`], and for truncated or corrupted states.
+pub fn deserialize_state<S>(framed: &[u8]) -> Result<S, Error>
+where
+    S: DeserializeOwned,
+{
+    if framed.len() < STATE_HEADER_LEN || &framed[..4] != STATE_MAGIC {
+        return Err(Error::illegal_state(
+            "Not a LibAFL state: the magic bytes are missing",
+        ));
+    }
+    let version = u16::from_le_bytes(framed[4..6].try_into().unwrap());
+    if version != STATE_FORMAT_VERSION {
+        return Err(Error::illegal_state(format!(
+            "The state has format version {version}, but this build of LibAFL reads version {STATE_FORMAT_VERSION}"
+        )));
+    }
+    let flags = u16::from_le_bytes(framed[6..8].try_into().unwrap());
+    let payload_len = u64::from_le_bytes(framed[8..16].try_into().unwrap());
+    let checksum = u64::from_le_bytes(framed[16..24].try_into().unwrap());
+
+    let payload = &framed[STATE_HEADER_LEN..];
+    if payload.len() as u64 != payload_len {
+        return Err(Error::illegal_state(format!(
+            "The state is truncated: expected {payload_len} bytes, but got {}",
+            payload.len()
+        )));
+    }
+    if state_checksum(payload) != checksum {
+        return Err(Error::illegal_state(
+            "The checksum of the state does not match. State corrupted?",
+        ));
+    }
+
+    if flags & STATE_FLAG_ZSTD == 0 {
+        return Ok(postcard::from_bytes(payload)?);
+    }
+    #[cfg(feature = "state_compression")]
+    {
+        let decompressed = zstd::decode_all(payload)?;
+        Ok(postcard::from_bytes(&decompressed)?)
+    }
+    #[cfg(not(feature = "state_compression"))]
+    Err(Error::unsupported(
+        "The state is compressed, enable the `state_compression` feature to read it",
+    ))
+}
+
+/// Saves a framed `state` to the file at `path`, to load it again with [`load_state_snapshot`].
+///
+/// Unlike the [`StateRestorer`], which hands the state over to the next restart,
+/// snapshots stay on disk, for example to resume a campaign after a reboot.
+/// The snapshot is written to a temporary file next to `path` first, synced, and then renamed to `path`,
+/// so a crash never leaves a half-written snapshot behind.
+pub fn save_state_snapshot<S, P>(state: &S, path: P) -> Result<(), Error>
+where
+    S: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let framed = serialize_state(state)?;
+
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().ok_or_else(|| {
+        Error::illegal_argument(format!(
+            "The snapshot path {} is not a file",
+            path.display()
+        ))
+    })?);
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&framed)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Loads a state snapshot, saved by [`save_state_snapshot`], from the file at `path`.
+pub fn load_state_snapshot<S, P>(path: P) -> Result<S, Error>
+where
+    S: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    deserialize_state(&fs::read(path)?)
+}
+
 /// The struct stored on the shared map, containing either the data, or the filename to read contents from.
 #[repr(C)]
 struct StateShMemContent {
@@ -116,7 +269,7 @@ where
             ));
         }
 
-        let serialized = postcard::to_allocvec(state)?;
+        let serialized = serialize_state(state)?;
 
         if size_of::<StateShMemContent>() + serialized.len() > self.shmem.len() {
             // generate a filename
@@ -274,7 +427,7 @@ where
             }
             state = &file_content;
         }
-        let deserialized = postcard::from_bytes(state)?;
+        let deserialized = deserialize_state(state)?;
         Ok(Some(deserialized))
     }
 }
@@ -346,4 +499,47 @@ mod tests {
         assert!(!state_restorer.has_content());
         assert!(!tmpfile.exists());
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_state_framing() {
+        use alloc::{string::String, vec::Vec};
+        use std::{env::temp_dir, fs, process};
+
+        use crate::staterestore::{
+            deserialize_state, load_state_snapshot, save_state_snapshot, serialize_state,
+        };
+
+        let state = (String::from("hello world"), vec![7_u8; 8192]);
+        let framed = serialize_state(&state).unwrap();
+        assert_eq!(
+            deserialize_state::<(String, Vec<u8>)>(&framed).unwrap(),
+            state
+        );
+
+        // Corrupted payload
+        let mut corrupted = framed.clone();
+        *corrupted.last_mut().unwrap() ^= 0xff;
+        assert!(deserialize_state::<(String, Vec<u8>)>(&corrupted).is_err());
+
+        // Truncated payload
+        assert!(deserialize_state::<(String, Vec<u8>)>(&framed[..framed.len() - 1]).is_err());
+
+        // Another format version
+        let mut other_version = framed.clone();
+        other_version[4] = other_version[4].wrapping_add(1);
+        assert!(deserialize_state::<(String, Vec<u8>)>(&other_version).is_err());
+
+        // Not framed at all
+        let raw = postcard::to_allocvec(&state).unwrap();
+        assert!(deserialize_state::<(String, Vec<u8>)>(&raw).is_err());
+
+        let path = temp_dir().join(format!("libafl_snapshot_test_{}", process::id()));
+        save_state_snapshot(&state, &path).unwrap();
+        assert_eq!(
+            load_state_snapshot::<(String, Vec<u8>), _>(&path).unwrap(),
+            state
+        );
+        fs::remove_file(&path).unwrap();
+    }
 }
@@ -240,6 +240,7 @@ fn main() {
     println!("cargo:rerun-if-env-changed=LLVM_LDFLAGS");
     println!("cargo:rerun-if-env-changed=LLVM_VERSION");
     println!("cargo:rerun-if-env-changed=LIBAFL_EDGES_MAP_DEFAULT_SIZE");
+    println!("cargo:rerun-if-env-changed=LIBAFL_EDGES_MAP_ALLOCATED_SIZE");
     println!("cargo:rerun-if-env-changed=LIBAFL_ACCOUNTING_MAP_SIZE");
     println!("cargo:rerun-if-env-changed=LIBAFL_DDG_MAP_SIZE");
     println!("cargo:rerun-if-changed=src/common-llvm.h");
@@ -319,6 +320,9 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         .map_or(Ok(2_621_440), str::parse)
         .expect("Could not parse LIBAFL_EDGES_MAP_DEFAULT_SIZE");
     cxxflags.push(format!("-DEDGES_MAP_DEFAULT_SIZE={edge_map_default_size}"));
+    cxxflags.push(format!(
+        "-DEDGES_MAP_ALLOCATED_SIZE={edge_map_allocated_size}"
+    ));
 
     let acc_map_size: usize = option_env!("LIBAFL_ACCOUNTING_MAP_SIZE")
         .map_or(Ok(65_536), str::parse)
@@ -465,6 +469,18 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         true,
     );
 
+    #[cfg(feature = "dict2file")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "dict2file-pass.cc",
+        None,
+        true,
+    );
+
     #[cfg(feature = "coverage-accounting")]
     build_pass(
         bindir_path,
@@ -489,6 +505,55 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         true,
     );
 
+    #[cfg(feature = "split-compares")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "split-compares-pass.cc",
+        None,
+        true,
+    );
+
+    #[cfg(feature = "split-switches")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "split-switches-pass.cc",
+        None,
+        true,
+    );
+
+    #[cfg(feature = "compare-transform")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "compare-transform-pass.cc",
+        None,
+        true,
+    );
+
+    // Needs LLVM 15 or newer with the new pass manager
+    #[cfg(feature = "lto-edges")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "lto-edges-pass.cc",
+        None,
+        false,
+    );
+
     #[cfg(feature = "ctx")]
     build_pass(
         bindir_path,
@@ -1,12 +1,28 @@
 //! LLVM style control flow graph with information of AFL-style index of the each
 //! edges, use together with ``AFLCoverage`` pass having --dump-afl-cfg flag enabled.
+//!
+//! For directed fuzzing, the graph computes `AFLGo`-style distances of its basic blocks and edges to a set of
+//! target basic blocks, see [`ControlFlowGraph::calculate_edge_distances`].
+//!
+//! The dumps of each translation unit merge into a whole-program graph with [`ControlFlowGraph::from_dir`], which
+//! can be exported with [`ControlFlowGraph::to_dot`] and [`ControlFlowGraph::to_json`], and analyzed for
+//! dominators, loops, and reachability.
 use std::{
-    collections::{BinaryHeap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt::Write as _,
+    fs::File,
+    io::{BufReader, BufWriter},
     marker::PhantomData,
+    path::Path,
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::Error;
+
+/// The factor of the function distance of a callee, for the distance of the calling basic block, as in `AFLGo`.
+const CALL_DISTANCE_FACTOR: f64 = 10.0;
+
 /// Compute the weight of a [`CfgEdge`]. Lower means shorter distance in the graph.
 pub trait HasWeight<T> {
     /// Compute the weight of a [`CfgEdge`]. Lower means shorter distance in the graph.
@@ -86,6 +102,12 @@ where
     edges: Vec<Option<CfgEdge<T>>>,
     /// Mapping each function's name to its corresponding entry basic block information.
     func_to_entry_bb: HashMap<String, EntryBasicBlockInfo>,
+    /// Mapping each basic block to the name of its function.
+    bb_to_func: HashMap<usize, String>,
+    /// Mapping each basic block to its successor basic blocks.
+    bb_to_successors: HashMap<usize, Vec<usize>>,
+    /// Mapping each basic block to the functions it calls.
+    bb_to_calls: HashMap<usize, Vec<String>>,
 }
 
 impl<T> ControlFlowGraph<T>
@@ -101,6 +123,9 @@ where
         Self {
             edges: (0..map_size).map(|_| None).collect(),
             func_to_entry_bb: HashMap::default(),
+            bb_to_func: HashMap::default(),
+            bb_to_successors: HashMap::default(),
+            bb_to_calls: HashMap::default(),
         }
     }
 
@@ -125,6 +150,7 @@ where
     current_bb: usize,
     bb_to_func: HashMap<usize, String>,
     bb_to_successors: HashMap<usize, Vec<usize>>,
+    bb_to_calls: HashMap<usize, Vec<String>>,
     func_to_entry_bb: HashMap<String, usize>,
     phantom: PhantomData<T>,
 }
@@ -138,6 +164,7 @@ where
             current_bb: 0,
             bb_to_func: HashMap::default(),
             bb_to_successors: HashMap::default(),
+            bb_to_calls: HashMap::default(),
             func_to_entry_bb: HashMap::default(),
             phantom: PhantomData,
         }
@@ -165,6 +192,13 @@ where
                     }
                 }
             }
+            "=>" => {
+                // "=>{function name}": Current basic block calls {function name}.
+                self.bb_to_calls
+                    .entry(self.current_bb)
+                    .or_default()
+                    .push(line_content.to_string());
+            }
             "%%" => {
                 // "%%{function name}+{index}": Make current basic block to be {index}.
                 let mut splitter = line_content.split('+');
@@ -188,6 +222,36 @@ where
         true
     }
 
+    /// Add the basic blocks, successors, calls, and entries of an existing [`ControlFlowGraph`].
+    pub fn add_cfg(&mut self, cfg: &ControlFlowGraph<T>) {
+        self.bb_to_func.extend(
+            cfg.bb_to_func
+                .iter()
+                .map(|(bb_loc, func_name)| (*bb_loc, func_name.clone())),
+        );
+        for (bb_loc, successors) in &cfg.bb_to_successors {
+            let current = self.bb_to_successors.entry(*bb_loc).or_default();
+            for successor in successors {
+                if !current.contains(successor) {
+                    current.push(*successor);
+                }
+            }
+        }
+        for (bb_loc, callees) in &cfg.bb_to_calls {
+            let current = self.bb_to_calls.entry(*bb_loc).or_default();
+            for callee in callees {
+                if !current.contains(callee) {
+                    current.push(callee.clone());
+                }
+            }
+        }
+        self.func_to_entry_bb.extend(
+            cfg.func_to_entry_bb
+                .iter()
+                .map(|(func_name, entry)| (func_name.clone(), entry.node_loc)),
+        );
+    }
+
     /// Convert current state to a [`ControlFlowGraph`].
     pub fn to_cfg(&self) -> ControlFlowGraph<T> {
         let mut cfg = ControlFlowGraph::new();
@@ -237,6 +301,9 @@ where
                 cfg.insert_edge(xored_loc, edge);
             }
         }
+        cfg.bb_to_func.clone_from(&self.bb_to_func);
+        cfg.bb_to_successors.clone_from(&self.bb_to_successors);
+        cfg.bb_to_calls.clone_from(&self.bb_to_calls);
         cfg
     }
 }
@@ -266,6 +333,72 @@ where
             .collect::<Vec<bool>>();
         reader.to_cfg()
     }
+
+    /// Load a whole-program CFG from the dump files of several translation units.
+    pub fn from_files<I, P>(file_names: I) -> Result<ControlFlowGraph<T>, Error>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut reader = CfgFileReader::new();
+        for file_name in file_names {
+            let content = std::fs::read_to_string(file_name).map_err(Error::Io)?;
+            for line in content.lines() {
+                reader.parse_line(line);
+            }
+        }
+        Ok(reader.to_cfg())
+    }
+
+    /// Load a whole-program CFG from all ``.cfg`` dump files in the directory `dir`.
+    pub fn from_dir<P>(dir: P) -> Result<ControlFlowGraph<T>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file_names = vec![];
+        for entry in std::fs::read_dir(dir).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "cfg") {
+                file_names.push(path);
+            }
+        }
+        file_names.sort();
+        ControlFlowGraph::from_files(file_names)
+    }
+
+    /// Merge the CFG `other`, e.g. of another translation unit, into this one.
+    ///
+    /// The metadata of the edges is kept, preferring the one of `other` for edges in both graphs.
+    pub fn merge(&mut self, mut other: ControlFlowGraph<T>) {
+        let mut reader = CfgFileReader::new();
+        reader.add_cfg(self);
+        reader.add_cfg(&other);
+        let mut merged = reader.to_cfg();
+
+        for (xored_loc, merged_edge) in merged.edges.iter_mut().enumerate() {
+            let Some(merged_edge) = merged_edge else {
+                continue;
+            };
+            let same_edge = |edge: &CfgEdge<T>| {
+                edge.top_node_loc == merged_edge.top_node_loc
+                    && edge.bottom_node_loc == merged_edge.bottom_node_loc
+            };
+            let previous_edge = other
+                .edges
+                .get_mut(xored_loc)
+                .and_then(Option::take)
+                .filter(same_edge)
+                .or_else(|| {
+                    self.edges
+                        .get_mut(xored_loc)
+                        .and_then(Option::take)
+                        .filter(same_edge)
+                });
+            merged_edge.metadata = previous_edge.and_then(|edge| edge.metadata);
+        }
+        *self = merged;
+    }
+
     /// Get the edge at the index of the coverage map AFL inserts to.
     #[must_use]
     pub fn get_edge(&self, xored_loc: usize) -> Option<&CfgEdge<T>> {
@@ -328,6 +461,448 @@ where
         }
         distances
     }
+
+    /// Calculate the distance of each function to the `target_funcs` in the call graph.
+    ///
+    /// As in `AFLGo`, this is the harmonic mean of the number of calls to each reachable target function, and `0`
+    /// for the target functions themselves. Functions reaching no target function are not in the returned map.
+    /// The call graph is made of the ``=>`` lines of the dump files.
+    #[must_use]
+    pub fn calculate_function_distances(&self, target_funcs: &[&str]) -> HashMap<String, f64> {
+        let mut callers: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for (bb_loc, callees) in &self.bb_to_calls {
+            if let Some(caller) = self.bb_to_func.get(bb_loc) {
+                for callee in callees {
+                    callers
+                        .entry(callee.as_str())
+                        .or_default()
+                        .insert(caller.as_str());
+                }
+            }
+        }
+
+        let mut inverse_sums: HashMap<&str, f64> = HashMap::new();
+        for target in target_funcs {
+            // Breadth-first search from the target, backwards along the calls
+            let mut hops: HashMap<&str, u32> = HashMap::from([(*target, 0)]);
+            let mut to_visit = VecDeque::from([*target]);
+            while let Some(func) = to_visit.pop_front() {
+                let func_hops = hops[func];
+                for caller in callers.get(func).into_iter().flatten() {
+                    if !hops.contains_key(caller) {
+                        hops.insert(caller, func_hops + 1);
+                        to_visit.push_back(caller);
+                    }
+                }
+            }
+            for (func, func_hops) in hops {
+                if func_hops > 0 {
+                    *inverse_sums.entry(func).or_default() += 1.0 / f64::from(func_hops);
+                }
+            }
+        }
+
+        let mut distances: HashMap<String, f64> = inverse_sums
+            .into_iter()
+            .map(|(func, inverse_sum)| (func.to_string(), 1.0 / inverse_sum))
+            .collect();
+        for target in target_funcs {
+            distances.insert((*target).to_string(), 0.0);
+        }
+        distances
+    }
+
+    /// Calculate the distance of each basic block to the `target_bbs`.
+    ///
+    /// As in `AFLGo`, the target basic blocks have a distance of `0`, and basic blocks calling functions that reach
+    /// the functions of the targets have ten times the smallest [function distance](Self::calculate_function_distances)
+    /// of their callees. Any other basic block has the harmonic mean of the distances through each of those basic
+    /// blocks it reaches in its function. Basic blocks reaching no target are not in the returned map.
+    #[must_use]
+    pub fn calculate_basic_block_distances(&self, target_bbs: &[usize]) -> HashMap<usize, f64> {
+        let mut target_funcs: Vec<&str> = target_bbs
+            .iter()
+            .filter_map(|bb_loc| self.bb_to_func.get(bb_loc).map(String::as_str))
+            .collect();
+        target_funcs.sort_unstable();
+        target_funcs.dedup();
+        let func_distances = self.calculate_function_distances(&target_funcs);
+
+        // The basic blocks with a distance of their own: the targets, and the calls towards them
+        let mut distances: HashMap<usize, f64> = HashMap::new();
+        for (bb_loc, callees) in &self.bb_to_calls {
+            let call_distance = callees
+                .iter()
+                .filter_map(|callee| func_distances.get(callee))
+                .copied()
+                .reduce(f64::min);
+            if let Some(call_distance) = call_distance {
+                distances.insert(*bb_loc, CALL_DISTANCE_FACTOR * call_distance);
+            }
+        }
+        for bb_loc in target_bbs {
+            distances.insert(*bb_loc, 0.0);
+        }
+
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (bb_loc, successors) in &self.bb_to_successors {
+            for successor in successors {
+                predecessors.entry(*successor).or_default().push(*bb_loc);
+            }
+        }
+
+        // Breadth-first search from each of them, backwards within their function
+        let mut inverse_sums: HashMap<usize, f64> = HashMap::new();
+        for (bb_loc, bb_distance) in &distances {
+            let func = self.bb_to_func.get(bb_loc);
+            let mut hops: HashMap<usize, u32> = HashMap::from([(*bb_loc, 0)]);
+            let mut to_visit = VecDeque::from([*bb_loc]);
+            while let Some(current) = to_visit.pop_front() {
+                let current_hops = hops[&current];
+                for predecessor in predecessors.get(&current).into_iter().flatten() {
+                    if self.bb_to_func.get(predecessor) == func && !hops.contains_key(predecessor) {
+                        hops.insert(*predecessor, current_hops + 1);
+                        to_visit.push_back(*predecessor);
+                    }
+                }
+            }
+            for (predecessor, predecessor_hops) in hops {
+                if predecessor_hops > 0 && !distances.contains_key(&predecessor) {
+                    *inverse_sums.entry(predecessor).or_default() +=
+                        1.0 / (f64::from(predecessor_hops) + bb_distance);
+                }
+            }
+        }
+
+        distances.extend(
+            inverse_sums
+                .into_iter()
+                .map(|(bb_loc, inverse_sum)| (bb_loc, 1.0 / inverse_sum)),
+        );
+        distances
+    }
+
+    /// Calculate the distance of each edge to the `target_bbs`, as the
+    /// [distance](Self::calculate_basic_block_distances) of the basic block it leads to.
+    ///
+    /// The result is indexed like the coverage map, so a directed fuzzer can rate its inputs by the distances of
+    /// the covered edges.
+    #[must_use]
+    pub fn calculate_edge_distances(&self, target_bbs: &[usize]) -> EdgeDistances {
+        let bb_distances = self.calculate_basic_block_distances(target_bbs);
+        let distances = self
+            .edges
+            .iter()
+            .flatten()
+            .filter_map(|edge| {
+                bb_distances
+                    .get(&edge.bottom_node_loc)
+                    .map(|distance| (edge.xored_loc, *distance))
+            })
+            .collect();
+        EdgeDistances { distances }
+    }
+
+    /// The successor basic blocks of `bb_loc`, within its function.
+    fn successors(&self, bb_loc: usize) -> &[usize] {
+        self.bb_to_successors
+            .get(&bb_loc)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Export the graph in the `GraphViz` DOT format.
+    ///
+    /// The basic blocks of each function are clustered, and calls are dashed edges to the entry of the callee.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut func_to_bbs: BTreeMap<&str, BTreeSet<usize>> = BTreeMap::new();
+        for (bb_loc, func_name) in &self.bb_to_func {
+            func_to_bbs
+                .entry(func_name.as_str())
+                .or_default()
+                .insert(*bb_loc);
+        }
+
+        let mut dot = String::from("digraph cfg {\n");
+        for (cluster, (func_name, bb_locs)) in func_to_bbs.iter().enumerate() {
+            writeln!(dot, "  subgraph cluster_{cluster} {{").unwrap();
+            writeln!(dot, "    label={func_name:?};").unwrap();
+            for bb_loc in bb_locs {
+                writeln!(dot, "    {bb_loc} [label=\"{func_name}+{bb_loc}\"];").unwrap();
+            }
+            writeln!(dot, "  }}").unwrap();
+        }
+        for bb_locs in func_to_bbs.values() {
+            for bb_loc in bb_locs {
+                for successor in self.successors(*bb_loc) {
+                    writeln!(dot, "  {bb_loc} -> {successor};").unwrap();
+                }
+                for callee in self.bb_to_calls.get(bb_loc).into_iter().flatten() {
+                    if let Some(entry) = self.func_to_entry_bb.get(callee) {
+                        writeln!(dot, "  {bb_loc} -> {} [style=dashed];", entry.node_loc).unwrap();
+                    }
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Export the graph as JSON.
+    ///
+    /// The result has the entry basic block of each function in ``entries``, and the function, successors, and
+    /// called functions of each basic block in ``basic_blocks``.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        #[derive(Serialize)]
+        struct JsonBasicBlock<'a> {
+            function: &'a str,
+            successors: &'a [usize],
+            calls: &'a [String],
+        }
+
+        #[derive(Serialize)]
+        struct JsonCfg<'a> {
+            entries: BTreeMap<&'a str, usize>,
+            basic_blocks: BTreeMap<usize, JsonBasicBlock<'a>>,
+        }
+
+        let json = JsonCfg {
+            entries: self
+                .func_to_entry_bb
+                .iter()
+                .map(|(func_name, entry)| (func_name.as_str(), entry.node_loc))
+                .collect(),
+            basic_blocks: self
+                .bb_to_func
+                .iter()
+                .map(|(bb_loc, func_name)| {
+                    let basic_block = JsonBasicBlock {
+                        function: func_name,
+                        successors: self.successors(*bb_loc),
+                        calls: self.bb_to_calls.get(bb_loc).map_or(&[], Vec::as_slice),
+                    };
+                    (*bb_loc, basic_block)
+                })
+                .collect(),
+        };
+        serde_json::to_string(&json).expect("The CFG is always serializable")
+    }
+
+    /// Calculate the immediate dominator of each basic block of the function `func_name`.
+    ///
+    /// The entry basic block, and basic blocks unreachable from it, are not in the returned map.
+    /// Returns `None` for unknown functions.
+    #[must_use]
+    pub fn dominators(&self, func_name: &str) -> Option<HashMap<usize, usize>> {
+        let entry = self.get_entry(func_name)?.node_loc;
+
+        // Reverse postorder of a depth-first search from the entry
+        let mut postorder = vec![];
+        let mut visited = HashSet::from([entry]);
+        let mut stack = vec![(entry, 0)];
+        while let Some((bb_loc, next_successor)) = stack.pop() {
+            if let Some(successor) = self.successors(bb_loc).get(next_successor) {
+                stack.push((bb_loc, next_successor + 1));
+                if visited.insert(*successor) {
+                    stack.push((*successor, 0));
+                }
+            } else {
+                postorder.push(bb_loc);
+            }
+        }
+        let order: HashMap<usize, usize> = postorder
+            .iter()
+            .enumerate()
+            .map(|(index, bb_loc)| (*bb_loc, index))
+            .collect();
+
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for bb_loc in &postorder {
+            for successor in self.successors(*bb_loc) {
+                predecessors.entry(*successor).or_default().push(*bb_loc);
+            }
+        }
+
+        // The iterative algorithm of Cooper, Harvey, and Kennedy, in postorder indices the entry is the highest
+        let mut idoms: HashMap<usize, usize> = HashMap::from([(entry, entry)]);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for bb_loc in postorder.iter().rev().skip(1) {
+                let mut new_idom = None;
+                for predecessor in &predecessors[bb_loc] {
+                    if !idoms.contains_key(predecessor) {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *predecessor,
+                        Some(mut other) => {
+                            let mut finger = *predecessor;
+                            while finger != other {
+                                while order[&finger] < order[&other] {
+                                    finger = idoms[&finger];
+                                }
+                                while order[&other] < order[&finger] {
+                                    other = idoms[&other];
+                                }
+                            }
+                            finger
+                        }
+                    });
+                }
+                if let Some(new_idom) = new_idom {
+                    if idoms.insert(*bb_loc, new_idom) != Some(new_idom) {
+                        changed = true;
+                    }
+                }
+            }
+        }
+        idoms.remove(&entry);
+        Some(idoms)
+    }
+
+    /// Find the natural loops of the function `func_name`.
+    ///
+    /// A loop is made of the basic blocks of all back edges to its header, i.e., edges to a basic block dominating
+    /// their source. Returns `None` for unknown functions.
+    #[must_use]
+    pub fn loops(&self, func_name: &str) -> Option<Vec<CfgLoop>> {
+        let entry = self.get_entry(func_name)?.node_loc;
+        let idoms = self.dominators(func_name)?;
+        let dominates = |dominator: usize, mut bb_loc: usize| loop {
+            if bb_loc == dominator {
+                return true;
+            }
+            match idoms.get(&bb_loc) {
+                Some(idom) => bb_loc = *idom,
+                None => return false,
+            }
+        };
+
+        let reachable: Vec<usize> = idoms.keys().copied().chain([entry]).collect();
+        let mut predecessors: HashMap<usize, Vec<usize>> = HashMap::new();
+        for bb_loc in &reachable {
+            for successor in self.successors(*bb_loc) {
+                predecessors.entry(*successor).or_default().push(*bb_loc);
+            }
+        }
+
+        let mut loops: BTreeMap<usize, HashSet<usize>> = BTreeMap::new();
+        for bb_loc in &reachable {
+            for header in self.successors(*bb_loc) {
+                if !dominates(*header, *bb_loc) {
+                    continue;
+                }
+                // Walk backwards from the back edge to the header
+                let blocks = loops
+                    .entry(*header)
+                    .or_insert_with(|| HashSet::from([*header]));
+                let mut to_visit = vec![*bb_loc];
+                while let Some(current) = to_visit.pop() {
+                    if blocks.insert(current) {
+                        to_visit.extend(predecessors.get(&current).into_iter().flatten());
+                    }
+                }
+            }
+        }
+        Some(
+            loops
+                .into_iter()
+                .map(|(header, blocks)| CfgLoop { header, blocks })
+                .collect(),
+        )
+    }
+
+    /// Get all basic blocks reachable from `start`, including itself, following both edges and calls.
+    #[must_use]
+    pub fn reachable_basic_blocks(&self, start: usize) -> HashSet<usize> {
+        let mut reachable = HashSet::from([start]);
+        let mut to_visit = vec![start];
+        while let Some(bb_loc) = to_visit.pop() {
+            let callee_entries = self
+                .bb_to_calls
+                .get(&bb_loc)
+                .into_iter()
+                .flatten()
+                .filter_map(|callee| self.func_to_entry_bb.get(callee))
+                .map(|entry| entry.node_loc);
+            for next in self
+                .successors(bb_loc)
+                .iter()
+                .copied()
+                .chain(callee_entries)
+            {
+                if reachable.insert(next) {
+                    to_visit.push(next);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Check if the basic block `to` is reachable from `from`, following both edges and calls.
+    #[must_use]
+    pub fn is_reachable(&self, from: usize, to: usize) -> bool {
+        self.reachable_basic_blocks(from).contains(&to)
+    }
+
+    /// Get the names of all functions reachable from `func_name`, including itself, in the call graph.
+    #[must_use]
+    pub fn reachable_functions(&self, func_name: &str) -> HashSet<String> {
+        match self.get_entry(func_name) {
+            Some(entry) => self
+                .reachable_basic_blocks(entry.node_loc)
+                .iter()
+                .filter_map(|bb_loc| self.bb_to_func.get(bb_loc).cloned())
+                .collect(),
+            None => HashSet::new(),
+        }
+    }
+}
+
+/// A natural loop in a [`ControlFlowGraph`], see [`ControlFlowGraph::loops`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgLoop {
+    /// The basic block all back edges of the loop lead to, dominating the loop.
+    pub header: usize,
+    /// The basic blocks of the loop, including the header.
+    pub blocks: HashSet<usize>,
+}
+
+/// The distances of the edges of a [`ControlFlowGraph`] to the targets of directed fuzzing.
+///
+/// Written to a file at compile time, the fuzzer loads it to rate inputs by the distances of their covered edges.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EdgeDistances {
+    /// The distance of each edge reaching a target, by the index of the edge in the coverage map.
+    pub distances: HashMap<usize, f64>,
+}
+
+impl EdgeDistances {
+    /// Get the distance of the edge at `xored_loc` in the coverage map, if it reaches a target.
+    #[must_use]
+    pub fn get(&self, xored_loc: usize) -> Option<f64> {
+        self.distances.get(&xored_loc).copied()
+    }
+
+    /// Write the distances as JSON to `path`.
+    pub fn to_file<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::create(path).map_err(Error::Io)?;
+        serde_json::to_writer(BufWriter::new(file), self).map_err(|err| Error::Io(err.into()))
+    }
+
+    /// Load distances written by [`EdgeDistances::to_file`] from `path`.
+    pub fn from_file<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path).map_err(Error::Io)?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|err| Error::Io(err.into()))
+    }
 }
 
 impl<T> Default for ControlFlowGraph<T>
@@ -341,6 +916,8 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+
     use crate::cfg::{ControlFlowGraph, HasWeight};
 
     struct TestMetadata {}
@@ -398,4 +975,110 @@ mod tests {
         assert_eq!(*distances.get(&((26911 >> 1) ^ 41925)).unwrap(), 2);
         assert!(!distances.contains_key(&((41864 >> 1) ^ 52706)));
     }
+
+    // main (1) ──► main (2) ══ calls ══► foo (10) ──► foo (11, target)
+    //     │
+    //     └──────► main (3) ──► main (4)
+    const TEST_CALL_GRAPH_STR: &str = "$$main+1\n$$foo+10\n%%main+1\n->2\n->3\n%%main+2\n=>foo\n%%main+3\n->4\n%%main+4\n%%foo+10\n->11\n%%foo+11\n";
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Testcase takes long in miri.
+    #[allow(clippy::float_cmp)] // The distances are exact
+    fn test_distances() {
+        let cfg: ControlFlowGraph<TestMetadata> =
+            ControlFlowGraph::from_content(TEST_CALL_GRAPH_STR);
+
+        let func_distances = cfg.calculate_function_distances(&["foo"]);
+        assert_eq!(func_distances["foo"], 0.0);
+        assert_eq!(func_distances["main"], 1.0);
+
+        let bb_distances = cfg.calculate_basic_block_distances(&[11]);
+        assert_eq!(bb_distances[&11], 0.0);
+        assert_eq!(bb_distances[&10], 1.0);
+        assert_eq!(bb_distances[&2], 0.0);
+        assert_eq!(bb_distances[&1], 1.0);
+        assert!(!bb_distances.contains_key(&3));
+        assert!(!bb_distances.contains_key(&4));
+
+        let edge_distances = cfg.calculate_edge_distances(&[11]);
+        // The edges (10, 11), (1, 2), (0, 1), and (1, 3)
+        assert_eq!(edge_distances.get(14), Some(0.0));
+        assert_eq!(edge_distances.get(2), Some(0.0));
+        assert_eq!(edge_distances.get(1), Some(1.0));
+        assert_eq!(edge_distances.get(3), None);
+    }
+
+    // Translation unit of main:
+    //
+    // main (1) ──► main (2) ══ calls ══► foo (10) ──► foo (11)
+    //     │         ▲    │
+    //     │         │    ▼
+    //     │         main (3) ──► main (4)
+    //     │                        ▲
+    //     └──────► main (5) ───────┘
+    const TEST_MAIN_UNIT_STR: &str = "$$main+1\n%%main+1\n->2\n->5\n%%main+2\n->3\n=>foo\n%%main+3\n->2\n->4\n%%main+4\n%%main+5\n->4\n";
+    // Translation unit of foo
+    const TEST_FOO_UNIT_STR: &str = "$$foo+10\n%%foo+10\n->11\n%%foo+11\n";
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Testcase takes long in miri.
+    fn test_merge_and_analyses() {
+        let mut cfg: ControlFlowGraph<TestMetadata> =
+            ControlFlowGraph::from_content(TEST_MAIN_UNIT_STR);
+        assert!(!cfg.is_reachable(1, 11));
+        cfg.merge(ControlFlowGraph::from_content(TEST_FOO_UNIT_STR));
+        assert!(cfg.get_entry("foo").is_some());
+        assert!(cfg.get_edge((10 >> 1) ^ 11).is_some());
+        assert!(cfg.get_edge((2 >> 1) ^ 3).is_some());
+
+        let idoms = cfg.dominators("main").unwrap();
+        assert_eq!(idoms[&2], 1);
+        assert_eq!(idoms[&3], 2);
+        assert_eq!(idoms[&4], 1);
+        assert_eq!(idoms[&5], 1);
+        assert!(!idoms.contains_key(&1));
+        assert!(cfg.dominators("bar").is_none());
+
+        let loops = cfg.loops("main").unwrap();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, 2);
+        assert_eq!(loops[0].blocks, HashSet::from([2, 3]));
+        assert!(cfg.loops("foo").unwrap().is_empty());
+
+        assert!(cfg.is_reachable(1, 11));
+        assert!(!cfg.is_reachable(5, 11));
+        assert_eq!(cfg.reachable_basic_blocks(5), HashSet::from([4, 5]));
+        assert_eq!(
+            cfg.reachable_functions("main"),
+            HashSet::from(["main".to_string(), "foo".to_string()])
+        );
+
+        let dot = cfg.to_dot();
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("  3 -> 2;\n"));
+        assert!(dot.contains("  2 -> 10 [style=dashed];\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&cfg.to_json()).unwrap();
+        assert_eq!(json["entries"]["foo"], 10);
+        assert_eq!(json["basic_blocks"]["2"]["function"], "main");
+        assert_eq!(json["basic_blocks"]["2"]["calls"][0], "foo");
+        assert_eq!(json["basic_blocks"]["3"]["successors"][1], 4);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Testcase uses the file system.
+    fn test_from_dir() {
+        let dir = std::env::temp_dir().join(format!("libafl_cc_cfg_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.cfg"), TEST_MAIN_UNIT_STR).unwrap();
+        std::fs::write(dir.join("foo.cfg"), TEST_FOO_UNIT_STR).unwrap();
+        std::fs::write(dir.join("notes.txt"), "$$bar+20\n").unwrap();
+
+        let cfg: ControlFlowGraph<TestMetadata> = ControlFlowGraph::from_dir(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(cfg.get_entry("main").is_some());
+        assert!(cfg.get_entry("foo").is_some());
+        assert!(cfg.get_entry("bar").is_none());
+        assert!(cfg.is_reachable(1, 11));
+    }
 }
@@ -23,21 +23,31 @@ fn dll_extension<'a>() -> &'a str {
 
 include!(concat!(env!("OUT_DIR"), "/clang_constants.rs"));
 
+/// The path of the LTO edges pass, loaded into the linker in LTO mode
+fn lto_edges_pass_path() -> PathBuf {
+    PathBuf::from(env!("OUT_DIR")).join(format!("lto-edges-pass.{}", dll_extension()))
+}
+
 /// The supported LLVM passes
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LLVMPasses {
-    //CmpLogIns,
-    /// The `CmpLog` pass
+    /// The `CmpLog` routines pass, logging the operands of compare-like calls (`memcmp`, `strcmp`, ...) to the
+    /// `CMPLOG_MAP` of the `cmplog` feature of `libafl_targets`
     CmpLogRtn,
     /// The Autotoken pass
     AutoTokens,
+    /// The dict2file pass, appending the comparison constants and string literals of each translation unit as an
+    /// AFL dictionary to the file at the absolute path in the `LIBAFL_DICT2FILE` environment variable, to be loaded
+    /// with `Tokens::from_file`
+    Dict2File,
     /// The Coverage Accouting (BB metric) pass
     CoverageAccounting,
     /// The dump cfg pass
     DumpCfg,
     #[cfg(unix)]
-    /// The `CmpLog` Instruction pass
+    /// The `CmpLog` instructions pass, logging the operands of integer compares to the `CMPLOG_MAP` of the `cmplog`
+    /// feature of `libafl_targets`
     CmpLogInstructions,
     /// Instrument caller for sancov coverage
     Ctx,
@@ -47,6 +57,15 @@ pub enum LLVMPasses {
     Profiling,
     /// Data dependency instrumentation
     DDG,
+    /// The laf-intel split-compares pass, splitting integer comparisons into byte comparisons
+    SplitCompares,
+    /// The laf-intel split-switches pass, splitting switches into byte comparisons
+    SplitSwitches,
+    /// The laf-intel compare-transform pass, inlining `strcmp`, `memcmp`, and the like with a constant string as
+    /// byte comparisons
+    ///
+    /// Add it before [`LLVMPasses::SplitCompares`], the passes run in the order they were added.
+    CompareTransform,
 }
 
 impl LLVMPasses {
@@ -59,6 +78,9 @@ impl LLVMPasses {
             LLVMPasses::AutoTokens => {
                 PathBuf::from(env!("OUT_DIR")).join(format!("autotokens-pass.{}", dll_extension()))
             }
+            LLVMPasses::Dict2File => {
+                PathBuf::from(env!("OUT_DIR")).join(format!("dict2file-pass.{}", dll_extension()))
+            }
             LLVMPasses::CoverageAccounting => PathBuf::from(env!("OUT_DIR"))
                 .join(format!("coverage-accounting-pass.{}", dll_extension())),
             LLVMPasses::DumpCfg => {
@@ -79,6 +101,12 @@ impl LLVMPasses {
             LLVMPasses::DDG => {
                 PathBuf::from(env!("OUT_DIR")).join(format!("ddg-instr.{}", dll_extension()))
             }
+            LLVMPasses::SplitCompares => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("split-compares-pass.{}", dll_extension())),
+            LLVMPasses::SplitSwitches => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("split-switches-pass.{}", dll_extension())),
+            LLVMPasses::CompareTransform => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("compare-transform-pass.{}", dll_extension())),
         }
     }
 }
@@ -102,6 +130,7 @@ pub struct ClangWrapper {
     need_libafl_arg: bool,
     has_libafl_arg: bool,
     use_new_pm: bool,
+    lto: bool,
 
     output: Option<PathBuf>,
     configurations: Vec<crate::Configuration>,
@@ -450,6 +479,21 @@ impl ToolWrapper for ClangWrapper {
                 args.push(pass.path().into_os_string().into_string().unwrap());
             }
         }
+        if self.lto && !self.is_asm {
+            args.push("-flto=full".into());
+            if self.linking {
+                let pass_path = lto_edges_pass_path()
+                    .into_os_string()
+                    .into_string()
+                    .unwrap();
+                args.push("-fuse-ld=lld".into());
+                if self.use_new_pm {
+                    args.push(format!("-Wl,--load-pass-plugin={pass_path}"));
+                } else {
+                    args.push(format!("-Wl,-mllvm=-load={pass_path}"));
+                }
+            }
+        }
         if !self.is_asm && !self.passes.is_empty() {
             for passes_arg in &self.passes_args {
                 args.push("-mllvm".into());
@@ -578,6 +622,7 @@ impl ClangWrapper {
             need_libafl_arg: false,
             has_libafl_arg: false,
             use_new_pm,
+            lto: false,
             output: None,
             configurations: vec![crate::Configuration::Default],
             ignoring_configurations: false,
@@ -657,6 +702,16 @@ impl ClangWrapper {
         self.use_new_pm = value;
         self
     }
+
+    /// Set LTO mode, instrumenting edges with collision-free IDs at link time.
+    ///
+    /// Compiles with full LTO, and links with `lld`, loading the LTO edges pass (LLVM 15 or newer). The pass writes
+    /// each edge into the edges map of `libafl_targets` at a unique index, and stores the number of edges in
+    /// `__afl_final_loc` for the runtime. Do not instrument with `-fsanitize-coverage=trace-pc-guard` in this mode.
+    pub fn lto(&mut self, value: bool) -> &'_ mut Self {
+        self.lto = value;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,283 @@
+/*
+   LibAFL - laf-intel compare-transform LLVM pass
+   --------------------------------------------------
+
+   Based on the laf-intel passes, https://lafintel.wordpress.com/
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+/*
+   Inlines calls to `strcmp`, `strncmp`, `strcasecmp`, `strncasecmp`,
+   `memcmp` and `bcmp` with a constant string, and constant length, as a chain
+   of byte comparisons. Each matching byte takes a new edge, so plain coverage
+   feedback rewards inputs getting closer to the constant string, byte by byte.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include <string>
+#include <vector>
+
+#include "common-llvm.h"
+
+#include "llvm/Analysis/ValueTracking.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/IR/Verifier.h"
+#include "llvm/Pass.h"
+
+using namespace llvm;
+
+/* Longer constants are left to the library call */
+#define MAX_TRANSFORM_LEN 64
+
+namespace {
+
+#if USE_NEW_PM
+class CompareTransformPass : public PassInfoMixin<CompareTransformPass> {
+ public:
+  CompareTransformPass() {
+  }
+#else
+class CompareTransformPass : public ModulePass {
+ public:
+  static char ID;
+  CompareTransformPass() : ModulePass(ID) {
+  }
+#endif
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+
+  #if LLVM_VERSION_MAJOR < 4
+  const char *getPassName() const override {
+  #else
+  StringRef getPassName() const override {
+  #endif
+    return "compare transform";
+  }
+#endif
+
+ private:
+  bool transformCall(CallInst *call);
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "CompareTransformPass", "v0.1",
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+  #if LLVM_VERSION_MAJOR >= 16
+            PB.registerOptimizerEarlyEPCallback(
+  #else
+            PB.registerOptimizerLastEPCallback(
+  #endif
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(CompareTransformPass());
+                });
+          }};
+}
+#else
+char CompareTransformPass::ID = 0;
+#endif
+
+enum CompareKind { CMP_STR, CMP_STRN, CMP_MEM };
+
+static bool compareKind(StringRef name, CompareKind &kind, bool &caseless) {
+  caseless = false;
+  if (name == "strcmp") {
+    kind = CMP_STR;
+  } else if (name == "strncmp") {
+    kind = CMP_STRN;
+  } else if (name == "strcasecmp") {
+    kind = CMP_STR;
+    caseless = true;
+  } else if (name == "strncasecmp") {
+    kind = CMP_STRN;
+    caseless = true;
+  } else if (name == "memcmp" || name == "bcmp") {
+    kind = CMP_MEM;
+  } else {
+    return false;
+  }
+  return true;
+}
+
+/* Lowercases the byte `c`, like `tolower` in the C locale */
+static Value *lowerByte(IRBuilder<> &IRB, Value *c) {
+  IntegerType *Int8Ty = IRB.getInt8Ty();
+  Value       *isUpper = IRB.CreateICmpULT(
+      IRB.CreateSub(c, ConstantInt::get(Int8Ty, 'A')),
+      ConstantInt::get(Int8Ty, 26));
+  return IRB.CreateSelect(isUpper,
+                          IRB.CreateAdd(c, ConstantInt::get(Int8Ty, 32)), c);
+}
+
+/*
+   Replaces `call` with one block per byte of the constant. Each block loads
+   its byte of the other operand, and either returns the difference if the
+   bytes differ, or continues with the next byte. The results meet in a phi in
+   the block following `call`.
+*/
+bool CompareTransformPass::transformCall(CallInst *call) {
+  Function *callee = call->getCalledFunction();
+  if (!callee || call->arg_size() < 2 ||
+      !call->getType()->isIntegerTy(32)) {
+    return false;
+  }
+
+  CompareKind kind;
+  bool        caseless;
+  if (!compareKind(callee->getName(), kind, caseless)) { return false; }
+
+  Value    *arg0 = call->getArgOperand(0);
+  Value    *arg1 = call->getArgOperand(1);
+  StringRef str0, str1;
+  /* memcmp compares past NUL bytes */
+  bool trimAtNul = kind != CMP_MEM;
+#if LLVM_VERSION_MAJOR >= 17
+  bool isConst0 = getConstantStringInfo(arg0, str0, trimAtNul);
+  bool isConst1 = getConstantStringInfo(arg1, str1, trimAtNul);
+#else
+  bool isConst0 = getConstantStringInfo(arg0, str0, 0, trimAtNul);
+  bool isConst1 = getConstantStringInfo(arg1, str1, 0, trimAtNul);
+#endif
+  if (isConst0 == isConst1) { return false; }
+
+  std::string constant = isConst0 ? str0.str() : str1.str();
+  Value      *variable = isConst0 ? arg1 : arg0;
+  uint64_t    len;
+  if (kind == CMP_STR) {
+    /* including the terminating NUL */
+    len = constant.size() + 1;
+  } else {
+    if (call->arg_size() < 3) { return false; }
+    ConstantInt *n = dyn_cast<ConstantInt>(call->getArgOperand(2));
+    if (!n) { return false; }
+    len = n->getZExtValue();
+    if (kind == CMP_STRN) {
+      len = std::min<uint64_t>(len, constant.size() + 1);
+    } else if (len > constant.size()) {
+      return false;
+    }
+  }
+  if (len == 0 || len > MAX_TRANSFORM_LEN) { return false; }
+  /* the terminating NUL, not part of the StringRef */
+  constant.push_back('\0');
+
+  BasicBlock  *bb = call->getParent();
+  Function    *F = bb->getParent();
+  LLVMContext &C = F->getContext();
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  IntegerType *Int32Ty = IntegerType::getInt32Ty(C);
+
+  BasicBlock *end =
+      bb->splitBasicBlock(BasicBlock::iterator(call), "strcmp.end");
+  bb->getTerminator()->eraseFromParent();
+
+  PHINode *result = PHINode::Create(Int32Ty, len, "", &*end->begin());
+
+  BasicBlock *current = BasicBlock::Create(C, "strcmp.byte", F, end);
+  Value      *ptr;
+  {
+    IRBuilder<> IRB(bb);
+    ptr = IRB.CreatePointerCast(variable, PointerType::get(Int8Ty, 0));
+    IRB.CreateBr(current);
+  }
+
+  for (uint64_t i = 0; i < len; i++) {
+    IRBuilder<> IRB(current);
+    Value      *varByte = IRB.CreateLoad(
+        Int8Ty, IRB.CreateConstInBoundsGEP1_64(Int8Ty, ptr, i));
+    uint8_t constByte = constant[i];
+    if (caseless) {
+      varByte = lowerByte(IRB, varByte);
+      if (constByte >= 'A' && constByte <= 'Z') { constByte += 32; }
+    }
+
+    /* the library functions compare unsigned chars */
+    Value *var = IRB.CreateZExt(varByte, Int32Ty);
+    Value *cst = ConstantInt::get(Int32Ty, constByte);
+    Value *diff = isConst0 ? IRB.CreateSub(cst, var) : IRB.CreateSub(var, cst);
+    result->addIncoming(diff, current);
+
+    /* the strings end together at the terminating NUL of the constant */
+    if (i == len - 1 || (kind != CMP_MEM && constByte == 0)) {
+      IRB.CreateBr(end);
+      break;
+    }
+
+    BasicBlock *next = BasicBlock::Create(C, "strcmp.byte", F, end);
+    IRB.CreateCondBr(IRB.CreateICmpNE(diff, ConstantInt::get(Int32Ty, 0)),
+                     end, next);
+    current = next;
+  }
+
+  call->replaceAllUsesWith(result);
+  call->eraseFromParent();
+  return true;
+}
+
+#if USE_NEW_PM
+PreservedAnalyses CompareTransformPass::run(Module                &M,
+                                            ModuleAnalysisManager &MAM) {
+#else
+bool CompareTransformPass::runOnModule(Module &M) {
+#endif
+  std::vector<CallInst *> calls;
+
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+
+    for (auto &BB : F) {
+      for (auto &IN : BB) {
+        if (auto *call = dyn_cast<CallInst>(&IN)) { calls.push_back(call); }
+      }
+    }
+  }
+
+  bool modified = false;
+  for (auto *call : calls) {
+    modified |= transformCall(call);
+  }
+
+  verifyModule(M);
+
+#if USE_NEW_PM
+  return modified ? PreservedAnalyses::none() : PreservedAnalyses::all();
+#else
+  return modified;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerCompareTransformPass(const PassManagerBuilder &,
+                                         legacy::PassManagerBase &PM) {
+  PM.add(new CompareTransformPass());
+}
+
+static RegisterStandardPasses RegisterCompareTransformPass(
+    PassManagerBuilder::EP_OptimizerLast, registerCompareTransformPass);
+
+static RegisterStandardPasses RegisterCompareTransformPass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerCompareTransformPass);
+#endif
@@ -0,0 +1,257 @@
+/*
+   LibAFL - dict2file LLVM pass
+   --------------------------------------------------
+
+   Based on the AFL_LLVM_DICT2FILE mode of AFL++
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+/*
+   Collects tokens at compile time, and appends them as an AFL dictionary to
+   the file at the absolute path in `LIBAFL_DICT2FILE`:
+   - the constant operands of integer comparisons and switches,
+   - the constant strings compared by `strcmp`, `memcmp`, `strstr` and the
+     like,
+   - the string literals of the module.
+
+   Each translation unit appends its tokens, so the file should be removed
+   before a build. The fuzzer loads it with `Tokens::from_file`.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+#ifndef _WIN32
+  #include <unistd.h>
+#else
+  #include <io.h>
+#endif
+#include <ctype.h>
+#include <fcntl.h>
+#include <string.h>
+
+#include <set>
+#include <string>
+
+#include "common-llvm.h"
+
+#include "llvm/Analysis/ValueTracking.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/Constants.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/Pass.h"
+
+using namespace llvm;
+
+/* Tokens are cut to this length */
+#define MAX_TOKEN_LEN 32
+/* Shorter strings are too generic to help */
+#define MIN_STRING_LEN 3
+
+namespace {
+
+#if USE_NEW_PM
+class Dict2FilePass : public PassInfoMixin<Dict2FilePass> {
+ public:
+  Dict2FilePass() {
+  }
+#else
+class Dict2FilePass : public ModulePass {
+ public:
+  static char ID;
+  Dict2FilePass() : ModulePass(ID) {
+  }
+#endif
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+
+  #if LLVM_VERSION_MAJOR < 4
+  const char *getPassName() const override {
+  #else
+  StringRef getPassName() const override {
+  #endif
+    return "dict2file";
+  }
+#endif
+
+ private:
+  std::set<std::string> tokens;
+
+  void addInteger(const APInt &value);
+  void addString(StringRef str);
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "Dict2FilePass", "v0.1",
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+            PB.registerOptimizerLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(Dict2FilePass());
+                });
+          }};
+}
+#else
+char Dict2FilePass::ID = 0;
+#endif
+
+/* Adds the little-endian bytes of an integer constant of 2, 4 or 8 bytes */
+void Dict2FilePass::addInteger(const APInt &value) {
+  unsigned bits = value.getBitWidth();
+  if (bits != 16 && bits != 32 && bits != 64) { return; }
+  uint64_t val = value.getZExtValue();
+
+  /* small values, and all ones, are found by the havoc mutations anyway */
+  if (val < 0x100 || value.isAllOnes()) { return; }
+
+  std::string token;
+  for (unsigned i = 0; i < bits / 8; i++) {
+    token.push_back((char)((val >> (i * 8)) & 0xff));
+  }
+  tokens.insert(token);
+}
+
+/* Adds a string, without its terminating NUL */
+void Dict2FilePass::addString(StringRef str) {
+  size_t nul = str.find('\0');
+  if (nul != StringRef::npos) { str = str.substr(0, nul); }
+  if (str.size() < MIN_STRING_LEN) { return; }
+  tokens.insert(str.substr(0, MAX_TOKEN_LEN).str());
+}
+
+static bool isCompareFunction(StringRef name) {
+  static constexpr const char *compareFunctions[] = {
+      "strcmp",  "strncmp", "strcasecmp", "strncasecmp", "memcmp",
+      "bcmp",    "strstr",  "strcasestr", "memmem",      "strcspn",
+      "strspn",  "strpbrk", "xmlStrcmp",  "xmlStrncmp",  "xmlStrEqual",
+      "g_strcmp0"};
+
+  for (auto const &compareFunction : compareFunctions) {
+    if (name == compareFunction) { return true; }
+  }
+  return false;
+}
+
+/* Escapes a token as the content of an AFL dictionary line */
+static std::string escapeToken(const std::string &token) {
+  std::string escaped;
+  char        hex[8];
+  for (unsigned char c : token) {
+    if (isprint(c) && c != '\\' && c != '"') {
+      escaped.push_back(c);
+    } else {
+      snprintf(hex, sizeof(hex), "\\x%02x", c);
+      escaped.append(hex);
+    }
+  }
+  return escaped;
+}
+
+#if USE_NEW_PM
+PreservedAnalyses Dict2FilePass::run(Module &M, ModuleAnalysisManager &MAM) {
+#else
+bool Dict2FilePass::runOnModule(Module &M) {
+#endif
+  const char *path = getenv("LIBAFL_DICT2FILE");
+  if (!path || *path != '/') {
+    FATAL("LIBAFL_DICT2FILE is not set to an absolute path!\n");
+  }
+
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+
+    for (auto &BB : F) {
+      for (auto &IN : BB) {
+        if (auto *cmp = dyn_cast<ICmpInst>(&IN)) {
+          for (unsigned i = 0; i < 2; i++) {
+            if (auto *c = dyn_cast<ConstantInt>(cmp->getOperand(i))) {
+              addInteger(c->getValue());
+            }
+          }
+        } else if (auto *SI = dyn_cast<SwitchInst>(&IN)) {
+          for (auto &Case : SI->cases()) {
+            addInteger(Case.getCaseValue()->getValue());
+          }
+        } else if (auto *call = dyn_cast<CallInst>(&IN)) {
+          Function *callee = call->getCalledFunction();
+          if (!callee || !isCompareFunction(callee->getName())) { continue; }
+
+          for (unsigned i = 0; i < call->arg_size() && i < 2; i++) {
+            StringRef str;
+#if LLVM_VERSION_MAJOR >= 17
+            if (getConstantStringInfo(call->getArgOperand(i), str, false)) {
+#else
+            if (getConstantStringInfo(call->getArgOperand(i), str, 0, false)) {
+#endif
+              addString(str);
+            }
+          }
+        }
+      }
+    }
+  }
+
+  /* the string literals, which clang emits as private constant arrays */
+  for (auto &G : M.globals()) {
+    if (!G.isConstant() || !G.hasInitializer() || !G.hasPrivateLinkage()) {
+      continue;
+    }
+    if (auto *array = dyn_cast<ConstantDataArray>(G.getInitializer())) {
+      if (array->isCString()) { addString(array->getAsCString()); }
+    }
+  }
+
+  if (!tokens.empty()) {
+    std::string dictionary;
+    for (auto &token : tokens) {
+      dictionary.append("\"" + escapeToken(token) + "\"\n");
+    }
+
+    /* a single write per module, so that parallel builds do not interleave
+     * their lines */
+    int fd = open(path, O_WRONLY | O_APPEND | O_CREAT, 0644);
+    if (fd < 0) { FATAL("Could not open/create %s\n", path); }
+    if (write(fd, dictionary.data(), dictionary.size()) !=
+        (ssize_t)dictionary.size()) {
+      FATAL("Could not write to %s\n", path);
+    }
+    close(fd);
+  }
+
+#if USE_NEW_PM
+  return PreservedAnalyses::all();
+#else
+  return false;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerDict2FilePass(const PassManagerBuilder &,
+                                  legacy::PassManagerBase &PM) {
+  PM.add(new Dict2FilePass());
+}
+
+static RegisterStandardPasses RegisterDict2FilePass(
+    PassManagerBuilder::EP_OptimizerLast, registerDict2FilePass);
+
+static RegisterStandardPasses RegisterDict2FilePass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerDict2FilePass);
+#endif
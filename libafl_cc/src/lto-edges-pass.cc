@@ -0,0 +1,186 @@
+/*
+   LibAFL - LTO edges LLVM pass
+   --------------------------------------------------
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+/*
+   Edge coverage instrumentation at link time. Running on the whole program
+   during full LTO, the pass gives each edge a unique ID, counting up from 1,
+   so the IDs never collide, and the map can be as small as the number of
+   edges. Each edge increments its entry of `__afl_area_ptr`, the edges map of
+   libafl_targets, and the number of entries is stored in `__afl_final_loc`
+   for the runtime, and the forkserver, to size the map.
+
+   The pass is loaded into lld by the LTO mode of the ClangWrapper.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include <vector>
+
+#include "common-llvm.h"
+
+#include "llvm/Analysis/CFG.h"
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/CFG.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/IR/Verifier.h"
+#include "llvm/Pass.h"
+#include "llvm/Transforms/Utils/BasicBlockUtils.h"
+
+#if USE_NEW_PM && LLVM_VERSION_MAJOR < 15
+  #error "The LTO edges pass needs LLVM 15 or newer with the new pass manager"
+#endif
+
+using namespace llvm;
+
+namespace {
+
+#if USE_NEW_PM
+class LtoEdgesPass : public PassInfoMixin<LtoEdgesPass> {
+ public:
+  LtoEdgesPass() {
+  }
+#else
+class LtoEdgesPass : public ModulePass {
+ public:
+  static char ID;
+  LtoEdgesPass() : ModulePass(ID) {
+  }
+#endif
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+
+  StringRef getPassName() const override {
+    return "lto edges";
+  }
+#endif
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "LtoEdgesPass", "v0.1",
+          [](PassBuilder &PB) {
+            PB.registerFullLinkTimeOptimizationLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(LtoEdgesPass());
+                });
+          }};
+}
+#else
+char LtoEdgesPass::ID = 0;
+#endif
+
+#if USE_NEW_PM
+PreservedAnalyses LtoEdgesPass::run(Module &M, ModuleAnalysisManager &MAM) {
+#else
+bool LtoEdgesPass::runOnModule(Module &M) {
+#endif
+  LLVMContext &C = M.getContext();
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  IntegerType *Int32Ty = IntegerType::getInt32Ty(C);
+  PointerType *Int8PtrTy = PointerType::get(Int8Ty, 0);
+
+  GlobalVariable *AFLMapPtr = M.getGlobalVariable("__afl_area_ptr");
+  if (!AFLMapPtr) {
+    AFLMapPtr = new GlobalVariable(M, Int8PtrTy, false,
+                                   GlobalValue::ExternalLinkage, 0,
+                                   "__afl_area_ptr");
+  }
+
+  /* 0 is left to the forkserver, which sets it to signal a live target */
+  uint32_t nextId = 1;
+
+  for (auto &F : M) {
+    if (F.isDeclaration() || isIgnoreFunction(&F)) { continue; }
+
+    /* an edge to a block with several predecessors gets a block of its own,
+     * so that instrumenting each block instruments each edge */
+    std::vector<std::pair<Instruction *, unsigned>> criticalEdges;
+    for (auto &BB : F) {
+      Instruction *terminator = BB.getTerminator();
+      for (unsigned i = 0; i < terminator->getNumSuccessors(); i++) {
+        if (isCriticalEdge(terminator, i)) {
+          criticalEdges.push_back({terminator, i});
+        }
+      }
+    }
+    for (auto &edge : criticalEdges) {
+      SplitCriticalEdge(
+          edge.first, edge.second,
+          CriticalEdgeSplittingOptions().setIgnoreUnreachableDests());
+    }
+
+    for (auto &BB : F) {
+      BasicBlock::iterator IP = BB.getFirstInsertionPt();
+      if (IP == BB.end()) { continue; }
+      IRBuilder<> IRB(&(*IP));
+
+      LoadInst *MapPtr = IRB.CreateLoad(Int8PtrTy, AFLMapPtr);
+      MapPtr->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      Value *MapPtrIdx = IRB.CreateGEP(Int8Ty, MapPtr,
+                                       ConstantInt::get(Int32Ty, nextId++));
+
+      LoadInst *Counter = IRB.CreateLoad(Int8Ty, MapPtrIdx);
+      Counter->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      Value     *Incr = IRB.CreateAdd(Counter, ConstantInt::get(Int8Ty, 1));
+      StoreInst *Store = IRB.CreateStore(Incr, MapPtrIdx);
+      Store->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+    }
+  }
+
+  if (nextId > EDGES_MAP_ALLOCATED_SIZE) {
+    FATAL(
+        "%u edges do not fit into the edges map of %u entries, raise "
+        "LIBAFL_EDGES_MAP_ALLOCATED_SIZE\n",
+        nextId, (uint32_t)EDGES_MAP_ALLOCATED_SIZE);
+  }
+
+  /* the handshake with the runtime, which has a weak definition of 0 */
+  GlobalVariable *FinalLoc = M.getGlobalVariable("__afl_final_loc");
+  if (FinalLoc) {
+    FinalLoc->setLinkage(GlobalValue::ExternalLinkage);
+  } else {
+    FinalLoc = new GlobalVariable(M, Int32Ty, false,
+                                  GlobalValue::ExternalLinkage, 0,
+                                  "__afl_final_loc");
+  }
+  FinalLoc->setInitializer(ConstantInt::get(Int32Ty, nextId));
+
+  verifyModule(M);
+
+#if USE_NEW_PM
+  return PreservedAnalyses::none();
+#else
+  return true;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerLtoEdgesPass(const PassManagerBuilder &,
+                                 legacy::PassManagerBase &PM) {
+  PM.add(new LtoEdgesPass());
+}
+
+static RegisterStandardPasses RegisterLtoEdgesPass(
+    PassManagerBuilder::EP_FullLinkTimeOptimizationLast, registerLtoEdgesPass);
+#endif
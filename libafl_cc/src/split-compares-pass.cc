@@ -0,0 +1,256 @@
+/*
+   LibAFL - laf-intel split-compares LLVM pass
+   --------------------------------------------------
+
+   Based on the laf-intel passes, https://lafintel.wordpress.com/
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+/*
+   Splits integer comparisons wider than 8 bits into a chain of byte
+   comparisons, from the most significant byte down. Each matching byte takes
+   a new edge, so plain coverage feedback rewards inputs getting closer to a
+   multi-byte magic value, byte by byte.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include <vector>
+
+#include "common-llvm.h"
+
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/IR/Verifier.h"
+#include "llvm/Pass.h"
+
+using namespace llvm;
+
+namespace {
+
+#if USE_NEW_PM
+class SplitComparesPass : public PassInfoMixin<SplitComparesPass> {
+ public:
+  SplitComparesPass() {
+  }
+#else
+class SplitComparesPass : public ModulePass {
+ public:
+  static char ID;
+  SplitComparesPass() : ModulePass(ID) {
+  }
+#endif
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+
+  #if LLVM_VERSION_MAJOR < 4
+  const char *getPassName() const override {
+  #else
+  StringRef getPassName() const override {
+  #endif
+    return "split compares";
+  }
+#endif
+
+ private:
+  bool splitCompare(ICmpInst *cmp);
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "SplitComparesPass", "v0.1",
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+  #if LLVM_VERSION_MAJOR >= 16
+            PB.registerOptimizerEarlyEPCallback(
+  #else
+            PB.registerOptimizerLastEPCallback(
+  #endif
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(SplitComparesPass());
+                });
+          }};
+}
+#else
+char SplitComparesPass::ID = 0;
+#endif
+
+/* The predicate deciding a comparison at the first differing byte */
+static CmpInst::Predicate strictPredicate(CmpInst::Predicate pred,
+                                          bool               msb) {
+  switch (pred) {
+    case CmpInst::ICMP_UGT:
+    case CmpInst::ICMP_UGE:
+      return CmpInst::ICMP_UGT;
+    case CmpInst::ICMP_ULT:
+    case CmpInst::ICMP_ULE:
+      return CmpInst::ICMP_ULT;
+    case CmpInst::ICMP_SGT:
+    case CmpInst::ICMP_SGE:
+      /* only the most significant byte carries the sign */
+      return msb ? CmpInst::ICMP_SGT : CmpInst::ICMP_UGT;
+    case CmpInst::ICMP_SLT:
+    case CmpInst::ICMP_SLE:
+      return msb ? CmpInst::ICMP_SLT : CmpInst::ICMP_ULT;
+    default:
+      return pred;
+  }
+}
+
+/* The predicate deciding a comparison at the least significant byte */
+static CmpInst::Predicate lastPredicate(CmpInst::Predicate pred) {
+  switch (pred) {
+    case CmpInst::ICMP_SGT:
+      return CmpInst::ICMP_UGT;
+    case CmpInst::ICMP_SGE:
+      return CmpInst::ICMP_UGE;
+    case CmpInst::ICMP_SLT:
+      return CmpInst::ICMP_ULT;
+    case CmpInst::ICMP_SLE:
+      return CmpInst::ICMP_ULE;
+    default:
+      return pred;
+  }
+}
+
+/*
+   Replaces `cmp` with one block per byte. Each block compares its byte of
+   both operands, and either decides the result if they differ, or continues
+   with the next byte. The results meet in a phi in the block following `cmp`.
+*/
+bool SplitComparesPass::splitCompare(ICmpInst *cmp) {
+  IntegerType *IntTy = dyn_cast<IntegerType>(cmp->getOperand(0)->getType());
+  if (!IntTy) { return false; }
+  unsigned bits = IntTy->getBitWidth();
+  if (bits <= 8 || bits % 8 != 0) { return false; }
+  unsigned bytes = bits / 8;
+
+  CmpInst::Predicate pred = cmp->getPredicate();
+  Value             *op0 = cmp->getOperand(0);
+  Value             *op1 = cmp->getOperand(1);
+
+  BasicBlock  *bb = cmp->getParent();
+  Function    *F = bb->getParent();
+  LLVMContext &C = F->getContext();
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  IntegerType *Int1Ty = IntegerType::getInt1Ty(C);
+
+  BasicBlock *end = bb->splitBasicBlock(BasicBlock::iterator(cmp), "cmp.end");
+  /* the split added an unconditional branch to `end`, we branch to the byte
+   * blocks instead */
+  bb->getTerminator()->eraseFromParent();
+
+  PHINode *result = PHINode::Create(Int1Ty, bytes, "", &*end->begin());
+
+  BasicBlock *current = BasicBlock::Create(C, "cmp.byte", F, end);
+  {
+    IRBuilder<> IRB(bb);
+    IRB.CreateBr(current);
+  }
+
+  for (unsigned i = 0; i < bytes; i++) {
+    IRBuilder<> IRB(current);
+    unsigned    shift = (bytes - 1 - i) * 8;
+    Value      *byte0 = op0;
+    Value      *byte1 = op1;
+    if (shift) {
+      byte0 = IRB.CreateLShr(byte0, ConstantInt::get(IntTy, shift));
+      byte1 = IRB.CreateLShr(byte1, ConstantInt::get(IntTy, shift));
+    }
+    byte0 = IRB.CreateTrunc(byte0, Int8Ty);
+    byte1 = IRB.CreateTrunc(byte1, Int8Ty);
+
+    if (i == bytes - 1) {
+      result->addIncoming(IRB.CreateICmp(lastPredicate(pred), byte0, byte1),
+                          current);
+      IRB.CreateBr(end);
+      break;
+    }
+
+    Value *differs = IRB.CreateICmpNE(byte0, byte1);
+    Value *decided;
+    if (cmp->isEquality()) {
+      decided = ConstantInt::get(Int1Ty, pred == CmpInst::ICMP_NE);
+    } else {
+      decided = IRB.CreateICmp(strictPredicate(pred, i == 0), byte0, byte1);
+    }
+    result->addIncoming(decided, current);
+
+    BasicBlock *next = BasicBlock::Create(C, "cmp.byte", F, end);
+    IRB.CreateCondBr(differs, end, next);
+    current = next;
+  }
+
+  cmp->replaceAllUsesWith(result);
+  cmp->eraseFromParent();
+  return true;
+}
+
+#if USE_NEW_PM
+PreservedAnalyses SplitComparesPass::run(Module                &M,
+                                         ModuleAnalysisManager &MAM) {
+#else
+bool SplitComparesPass::runOnModule(Module &M) {
+#endif
+  std::vector<ICmpInst *> compares;
+
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+
+    for (auto &BB : F) {
+      for (auto &IN : BB) {
+        if (auto *cmp = dyn_cast<ICmpInst>(&IN)) {
+          if (isa<IntegerType>(cmp->getOperand(0)->getType())) {
+            compares.push_back(cmp);
+          }
+        }
+      }
+    }
+  }
+
+  bool modified = false;
+  for (auto *cmp : compares) {
+    modified |= splitCompare(cmp);
+  }
+
+  verifyModule(M);
+
+#if USE_NEW_PM
+  return modified ? PreservedAnalyses::none() : PreservedAnalyses::all();
+#else
+  return modified;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerSplitComparesPass(const PassManagerBuilder &,
+                                      legacy::PassManagerBase &PM) {
+  PM.add(new SplitComparesPass());
+}
+
+static RegisterStandardPasses RegisterSplitComparesPass(
+    PassManagerBuilder::EP_OptimizerLast, registerSplitComparesPass);
+
+static RegisterStandardPasses RegisterSplitComparesPass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerSplitComparesPass);
+#endif
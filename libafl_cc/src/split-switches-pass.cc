@@ -0,0 +1,233 @@
+/*
+   LibAFL - laf-intel split-switches LLVM pass
+   --------------------------------------------------
+
+   Based on the laf-intel passes, https://lafintel.wordpress.com/
+
+   Copyright 2024 AFLplusplus Project. All rights reserved.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+/*
+   Replaces switches over integers wider than 8 bits by a chain of byte
+   comparisons for each case, from the most significant byte down. Each
+   matching byte takes a new edge, so plain coverage feedback rewards inputs
+   getting closer to a case value, byte by byte.
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include <map>
+#include <vector>
+
+#include "common-llvm.h"
+
+#include "llvm/IR/BasicBlock.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+#include "llvm/IR/Verifier.h"
+#include "llvm/Pass.h"
+
+using namespace llvm;
+
+namespace {
+
+#if USE_NEW_PM
+class SplitSwitchesPass : public PassInfoMixin<SplitSwitchesPass> {
+ public:
+  SplitSwitchesPass() {
+  }
+#else
+class SplitSwitchesPass : public ModulePass {
+ public:
+  static char ID;
+  SplitSwitchesPass() : ModulePass(ID) {
+  }
+#endif
+
+#if USE_NEW_PM
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+#else
+  bool runOnModule(Module &M) override;
+
+  #if LLVM_VERSION_MAJOR < 4
+  const char *getPassName() const override {
+  #else
+  StringRef getPassName() const override {
+  #endif
+    return "split switches";
+  }
+#endif
+
+ private:
+  bool splitSwitch(SwitchInst *SI);
+};
+
+}  // namespace
+
+#if USE_NEW_PM
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "SplitSwitchesPass", "v0.1",
+          [](PassBuilder &PB) {
+  #if LLVM_VERSION_MAJOR <= 13
+            using OptimizationLevel = typename PassBuilder::OptimizationLevel;
+  #endif
+  #if LLVM_VERSION_MAJOR >= 16
+            PB.registerOptimizerEarlyEPCallback(
+  #else
+            PB.registerOptimizerLastEPCallback(
+  #endif
+                [](ModulePassManager &MPM, OptimizationLevel OL) {
+                  MPM.addPass(SplitSwitchesPass());
+                });
+          }};
+}
+#else
+char SplitSwitchesPass::ID = 0;
+#endif
+
+/*
+   Replaces `SI` with one block per byte of each case value. A mismatching
+   byte continues with the next case, or the default destination after the
+   last case, and a full match branches to the destination of the case.
+*/
+bool SplitSwitchesPass::splitSwitch(SwitchInst *SI) {
+  IntegerType *IntTy = dyn_cast<IntegerType>(SI->getCondition()->getType());
+  if (!IntTy) { return false; }
+  unsigned bits = IntTy->getBitWidth();
+  if (bits <= 8 || bits % 8 != 0 || SI->getNumCases() == 0) { return false; }
+  unsigned bytes = bits / 8;
+
+  BasicBlock  *bb = SI->getParent();
+  Function    *F = bb->getParent();
+  LLVMContext &C = F->getContext();
+  IntegerType *Int8Ty = IntegerType::getInt8Ty(C);
+  Value       *cond = SI->getCondition();
+
+  /* the incoming values of the phis of the destinations, for the edges from
+   * the switch, which we replace by the edges from the byte blocks */
+  std::map<BasicBlock *, std::vector<std::pair<PHINode *, Value *>>> phis;
+  for (unsigned i = 0; i < SI->getNumSuccessors(); i++) {
+    BasicBlock *dest = SI->getSuccessor(i);
+    if (phis.count(dest)) { continue; }
+    auto &incoming = phis[dest];
+    for (auto &PN : dest->phis()) {
+      incoming.push_back({&PN, PN.getIncomingValueForBlock(bb)});
+      while (PN.getBasicBlockIndex(bb) != -1) {
+        PN.removeIncomingValue(bb, false);
+      }
+    }
+  }
+  auto addEdge = [&](BasicBlock *from, BasicBlock *dest) {
+    for (auto &incoming : phis[dest]) {
+      incoming.first->addIncoming(incoming.second, from);
+    }
+  };
+
+  /* the bytes of the condition, most significant first */
+  std::vector<Value *> condBytes;
+  {
+    IRBuilder<> IRB(SI);
+    for (unsigned i = 0; i < bytes; i++) {
+      unsigned shift = (bytes - 1 - i) * 8;
+      Value   *byte = cond;
+      if (shift) {
+        byte = IRB.CreateLShr(byte, ConstantInt::get(IntTy, shift));
+      }
+      condBytes.push_back(IRB.CreateTrunc(byte, Int8Ty));
+    }
+  }
+
+  BasicBlock *defaultDest = SI->getDefaultDest();
+  BasicBlock *current = BasicBlock::Create(C, "switch.byte", F);
+  BranchInst::Create(current, SI);
+
+  unsigned caseIdx = 0;
+  unsigned numCases = SI->getNumCases();
+  for (auto &Case : SI->cases()) {
+    const APInt &value = Case.getCaseValue()->getValue();
+    BasicBlock  *dest = Case.getCaseSuccessor();
+    BasicBlock  *mismatch = defaultDest;
+    if (++caseIdx < numCases) {
+      mismatch = BasicBlock::Create(C, "switch.byte", F);
+    }
+
+    for (unsigned i = 0; i < bytes; i++) {
+      IRBuilder<> IRB(current);
+      uint64_t    caseByte =
+          value.lshr((bytes - 1 - i) * 8).trunc(8).getZExtValue();
+      Value *matches =
+          IRB.CreateICmpEQ(condBytes[i], ConstantInt::get(Int8Ty, caseByte));
+
+      if (i == bytes - 1) {
+        IRB.CreateCondBr(matches, dest, mismatch);
+        addEdge(current, dest);
+        if (mismatch == defaultDest) { addEdge(current, defaultDest); }
+      } else {
+        BasicBlock *next = BasicBlock::Create(C, "switch.byte", F);
+        IRB.CreateCondBr(matches, next, mismatch);
+        if (mismatch == defaultDest) { addEdge(current, defaultDest); }
+        current = next;
+      }
+    }
+    current = mismatch;
+  }
+
+  SI->eraseFromParent();
+  return true;
+}
+
+#if USE_NEW_PM
+PreservedAnalyses SplitSwitchesPass::run(Module                &M,
+                                         ModuleAnalysisManager &MAM) {
+#else
+bool SplitSwitchesPass::runOnModule(Module &M) {
+#endif
+  std::vector<SwitchInst *> switches;
+
+  for (auto &F : M) {
+    if (isIgnoreFunction(&F)) { continue; }
+
+    for (auto &BB : F) {
+      if (auto *SI = dyn_cast<SwitchInst>(BB.getTerminator())) {
+        switches.push_back(SI);
+      }
+    }
+  }
+
+  bool modified = false;
+  for (auto *SI : switches) {
+    modified |= splitSwitch(SI);
+  }
+
+  verifyModule(M);
+
+#if USE_NEW_PM
+  return modified ? PreservedAnalyses::none() : PreservedAnalyses::all();
+#else
+  return modified;
+#endif
+}
+
+#if USE_NEW_PM
+#else
+static void registerSplitSwitchesPass(const PassManagerBuilder &,
+                                      legacy::PassManagerBase &PM) {
+  PM.add(new SplitSwitchesPass());
+}
+
+static RegisterStandardPasses RegisterSplitSwitchesPass(
+    PassManagerBuilder::EP_OptimizerLast, registerSplitSwitchesPass);
+
+static RegisterStandardPasses RegisterSplitSwitchesPass0(
+    PassManagerBuilder::EP_EnabledOnOptLevel0, registerSplitSwitchesPass);
+#endif
@@ -40,6 +40,8 @@ use yaxpeax_x86::{
     long_mode::DisplayStyle,
 };
 
+#[cfg(target_vendor = "apple")]
+use crate::asan::mach_exceptions;
 #[cfg(target_arch = "x86_64")]
 use crate::utils::frida_to_cs;
 #[cfg(target_arch = "aarch64")]
@@ -192,6 +194,11 @@ impl FridaRuntime for AsanRuntime {
 
     fn pre_exec(&mut self, input_bytes: &[u8]) -> Result<(), libafl::Error> {
         self.unpoison(input_bytes.as_ptr() as usize, input_bytes.len());
+        // Installed late, in front of the crash handlers of the executor
+        #[cfg(target_vendor = "apple")]
+        unsafe {
+            mach_exceptions::install_bad_access_handlers(self);
+        }
         self.enable_hooks();
         Ok(())
     }
@@ -295,6 +302,43 @@ impl AsanRuntime {
             .map_or(stalked, |addr| *addr)
     }
 
+    /// Reports a heap error for a bad access to `fault_address` the instrumentation did not catch, raised as a
+    /// Mach `EXC_BAD_ACCESS` exception, see [`crate::asan::mach_exceptions`].
+    ///
+    /// Returns `false` if the target is not running or the address is not close to one of our allocations.
+    #[cfg(target_vendor = "apple")]
+    pub(crate) fn report_bad_access(
+        &mut self,
+        registers: [usize; ASAN_SAVE_REGISTER_COUNT],
+        pc: usize,
+        fault_address: usize,
+        is_write: bool,
+    ) -> bool {
+        if !self.hooks_enabled || !self.allocator.is_managed(fault_address as *mut c_void) {
+            return false;
+        }
+        let pc = self.real_address_for_stalked(pc);
+        let Some(metadata) = self.allocator.find_metadata(fault_address, fault_address) else {
+            return false;
+        };
+        let asan_readwrite_error = AsanReadWriteError {
+            registers,
+            pc,
+            fault: (None, None, 0, fault_address),
+            metadata: metadata.clone(),
+            backtrace: Backtrace::new(),
+        };
+        let error = match (is_write, metadata.freed) {
+            (false, false) => AsanError::OobRead(asan_readwrite_error),
+            (false, true) => AsanError::ReadAfterFree(asan_readwrite_error),
+            (true, false) => AsanError::OobWrite(asan_readwrite_error),
+            (true, true) => AsanError::WriteAfterFree(asan_readwrite_error),
+        };
+        self.disable_hooks();
+        AsanErrors::get_mut_blocking().report_error(error);
+        true
+    }
+
     /// Unpoison all the memory that is currently mapped with read/write permissions.
     #[allow(clippy::unused_self)]
     pub fn unpoison_all_existing_memory(&mut self) {
@@ -648,6 +692,45 @@ impl AsanRuntime {
         );
         #[cfg(not(any(target_vendor = "apple", windows)))]
         hook_func!(malloc_usable_size, (ptr: *mut c_void), usize);
+        // The zone allocator, used directly by `CoreFoundation` and friends
+        #[cfg(target_vendor = "apple")]
+        hook_func_with_check!(malloc_size, (ptr: *const c_void), usize);
+        #[cfg(target_vendor = "apple")]
+        hook_func!(
+            malloc_zone_malloc,
+            (zone: *mut c_void, size: usize),
+            *mut c_void
+        );
+        #[cfg(target_vendor = "apple")]
+        hook_func!(
+            malloc_zone_calloc,
+            (zone: *mut c_void, nmemb: usize, size: usize),
+            *mut c_void
+        );
+        #[cfg(target_vendor = "apple")]
+        hook_func!(
+            malloc_zone_valloc,
+            (zone: *mut c_void, size: usize),
+            *mut c_void
+        );
+        #[cfg(target_vendor = "apple")]
+        hook_func!(
+            malloc_zone_memalign,
+            (zone: *mut c_void, alignment: usize, size: usize),
+            *mut c_void
+        );
+        #[cfg(target_vendor = "apple")]
+        hook_func_with_check!(
+            malloc_zone_realloc,
+            (zone: *mut c_void, ptr: *mut c_void, size: usize),
+            *mut c_void
+        );
+        #[cfg(target_vendor = "apple")]
+        hook_func_with_check!(
+            malloc_zone_free,
+            (zone: *mut c_void, ptr: *mut c_void),
+            usize
+        );
         // // #[cfg(windows)]
         // hook_priv_func!(
         //     "c:\\windows\\system32\\ntdll.dll",
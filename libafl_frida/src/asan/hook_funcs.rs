@@ -991,6 +991,131 @@ impl AsanRuntime {
         self.allocator_mut().get_usable_size(ptr)
     }
 
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_check_malloc_size(&mut self, ptr: *const c_void) -> bool {
+        self.allocator_mut().is_managed(ptr as *mut c_void)
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_malloc_size(
+        &mut self,
+        _original: extern "C" fn(ptr: *const c_void) -> usize,
+        ptr: *const c_void,
+    ) -> usize {
+        self.allocator_mut().get_usable_size(ptr as *mut c_void)
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_malloc_zone_malloc(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, size: usize) -> *mut c_void,
+        _zone: *mut c_void,
+        size: usize,
+    ) -> *mut c_void {
+        unsafe { self.allocator_mut().alloc(size, 8) }
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_malloc_zone_calloc(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, nmemb: usize, size: usize) -> *mut c_void,
+        _zone: *mut c_void,
+        nmemb: usize,
+        size: usize,
+    ) -> *mut c_void {
+        extern "system" {
+            fn memset(s: *mut c_void, c: i32, n: usize) -> *mut c_void;
+        }
+        let ret = unsafe { self.allocator_mut().alloc(size * nmemb, 8) };
+        unsafe {
+            memset(ret, 0, size * nmemb);
+        }
+        ret
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_malloc_zone_valloc(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, size: usize) -> *mut c_void,
+        _zone: *mut c_void,
+        size: usize,
+    ) -> *mut c_void {
+        // Allocations always start at a page boundary
+        unsafe { self.allocator_mut().alloc(size, 8) }
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_malloc_zone_memalign(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, alignment: usize, size: usize) -> *mut c_void,
+        _zone: *mut c_void,
+        alignment: usize,
+        size: usize,
+    ) -> *mut c_void {
+        unsafe { self.allocator_mut().alloc(size, alignment) }
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_check_malloc_zone_realloc(
+        &mut self,
+        _zone: *mut c_void,
+        ptr: *mut c_void,
+        _size: usize,
+    ) -> bool {
+        // A null pointer is a new allocation, only serve it while the target runs
+        (ptr.is_null() && self.hooks_enabled) || self.allocator_mut().is_managed(ptr)
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    #[allow(clippy::cmp_null)]
+    pub fn hook_malloc_zone_realloc(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, ptr: *mut c_void, size: usize) -> *mut c_void,
+        _zone: *mut c_void,
+        ptr: *mut c_void,
+        size: usize,
+    ) -> *mut c_void {
+        unsafe {
+            let ret = self.allocator_mut().alloc(size, 0x8);
+            if ptr != std::ptr::null_mut() && ret != std::ptr::null_mut() {
+                let old_size = self.allocator_mut().get_usable_size(ptr);
+                let copy_size = if size < old_size { size } else { old_size };
+                (ptr as *mut u8).copy_to(ret as *mut u8, copy_size);
+            }
+            self.allocator_mut().release(ptr);
+            ret
+        }
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    pub fn hook_check_malloc_zone_free(&mut self, _zone: *mut c_void, ptr: *mut c_void) -> bool {
+        self.allocator_mut().is_managed(ptr)
+    }
+
+    #[inline]
+    #[cfg(target_vendor = "apple")]
+    #[allow(clippy::cmp_null)]
+    pub fn hook_malloc_zone_free(
+        &mut self,
+        _original: extern "C" fn(zone: *mut c_void, ptr: *mut c_void) -> usize,
+        _zone: *mut c_void,
+        ptr: *mut c_void,
+    ) -> usize {
+        if ptr != std::ptr::null_mut() {
+            unsafe { self.allocator_mut().release(ptr) }
+        }
+        0
+    }
+
     #[inline]
     #[allow(non_snake_case)]
     #[cfg(windows)]
@@ -0,0 +1,156 @@
+//! Reports of the Mach `EXC_BAD_ACCESS` exceptions of the target on macOS.
+//!
+//! The instrumentation catches most bad accesses to the heap, but not those made by code Frida does not stalk, e.g.,
+//! excluded ranges or the system libraries. These end in a Mach `EXC_BAD_ACCESS` exception once they leave the
+//! mapped allocations, which the kernel delivers to the process as `SIGSEGV` or `SIGBUS`, together with the
+//! exception state of the thread. The handlers installed here look the faulting address up in the allocator and
+//! report a heap error for it, before forwarding the signal to the handler installed before, usually the crash
+//! handler of the executor. A bad access far from our allocations stays a plain crash.
+
+use core::{ffi::c_int, mem, ptr};
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        OnceLock,
+    },
+};
+
+use libc::{
+    sigaction, sigemptyset, siginfo_t, ucontext_t, SA_NODEFER, SA_ONSTACK, SA_SIGINFO, SIGBUS,
+    SIGSEGV, SIG_DFL, SIG_IGN,
+};
+
+use crate::asan::asan_rt::{AsanRuntime, ASAN_SAVE_REGISTER_COUNT};
+
+/// The signals a Mach `EXC_BAD_ACCESS` exception is delivered as
+const BAD_ACCESS_SIGNALS: [c_int; 2] = [SIGSEGV, SIGBUS];
+
+/// The runtime to report to
+static RUNTIME: AtomicPtr<AsanRuntime> = AtomicPtr::new(ptr::null_mut());
+
+/// The handlers installed before ours, in the order of [`BAD_ACCESS_SIGNALS`]
+static PREVIOUS_HANDLERS: OnceLock<[sigaction; 2]> = OnceLock::new();
+
+/// Installs the handlers reporting the bad accesses of the target to `runtime`, in front of the signal handlers
+/// installed so far. The handlers are only installed once, later calls only update the runtime.
+///
+/// # Safety
+/// The runtime must not move or be dropped as long as the handlers are installed.
+pub unsafe fn install_bad_access_handlers(runtime: *mut AsanRuntime) {
+    RUNTIME.store(runtime, Ordering::Release);
+    PREVIOUS_HANDLERS.get_or_init(|| unsafe {
+        let mut previous: [sigaction; 2] = mem::zeroed();
+        let mut sa: sigaction = mem::zeroed();
+        sigemptyset(&raw mut sa.sa_mask);
+        sa.sa_flags = SA_NODEFER | SA_SIGINFO | SA_ONSTACK;
+        sa.sa_sigaction = handle_bad_access as usize;
+        for (signal, previous) in BAD_ACCESS_SIGNALS.iter().zip(&mut previous) {
+            if sigaction(*signal, &raw const sa, previous) < 0 {
+                log::error!("Could not set up the bad access handler for signal {signal}");
+            }
+        }
+        previous
+    });
+}
+
+/// The registers, pc, faulting address, and whether the access was a write, from the exception state
+#[cfg(target_arch = "aarch64")]
+fn exception_state(
+    ucontext: &ucontext_t,
+) -> ([usize; ASAN_SAVE_REGISTER_COUNT], usize, usize, bool) {
+    /// The "write not read" bit of the syndrome of a data abort
+    const ESR_WNR: u32 = 1 << 6;
+
+    let mcontext = unsafe { &*ucontext.uc_mcontext };
+    let mut registers = [0; ASAN_SAVE_REGISTER_COUNT];
+    for (register, value) in registers.iter_mut().zip(mcontext.__ss.__x) {
+        *register = value as usize;
+    }
+    registers[29] = mcontext.__ss.__fp as usize;
+    registers[30] = mcontext.__ss.__lr as usize;
+    registers[31] = mcontext.__ss.__sp as usize;
+    (
+        registers,
+        mcontext.__ss.__pc as usize,
+        mcontext.__es.__far as usize,
+        mcontext.__es.__esr & ESR_WNR != 0,
+    )
+}
+
+/// The registers, pc, faulting address, and whether the access was a write, from the exception state
+#[cfg(target_arch = "x86_64")]
+fn exception_state(
+    ucontext: &ucontext_t,
+) -> ([usize; ASAN_SAVE_REGISTER_COUNT], usize, usize, bool) {
+    /// The write bit of the page fault error code
+    const PF_WRITE: u32 = 1 << 1;
+
+    let mcontext = unsafe { &*ucontext.uc_mcontext };
+    let ss = &mcontext.__ss;
+    let fault_address = mcontext.__es.__faultvaddr as usize;
+    let registers = [
+        ss.__rax,
+        ss.__rbx,
+        ss.__rcx,
+        ss.__rdx,
+        ss.__rbp,
+        ss.__rsp,
+        ss.__rsi,
+        ss.__rdi,
+        ss.__r8,
+        ss.__r9,
+        ss.__r10,
+        ss.__r11,
+        ss.__r12,
+        ss.__r13,
+        ss.__r14,
+        ss.__r15,
+        ss.__rip,
+        fault_address as u64,
+        ss.__rip,
+    ]
+    .map(|value| value as usize);
+    (
+        registers,
+        ss.__rip as usize,
+        fault_address,
+        mcontext.__es.__err & PF_WRITE != 0,
+    )
+}
+
+unsafe extern "C" fn handle_bad_access(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    let runtime = RUNTIME.load(Ordering::Acquire);
+    if let (Some(runtime), Some(ucontext)) =
+        unsafe { (runtime.as_mut(), context.cast::<ucontext_t>().as_ref()) }
+    {
+        let (registers, pc, fault_address, is_write) = exception_state(ucontext);
+        // Without `continue_on_error`, the report aborts the target, which the executor sees as a crash as well
+        if runtime.report_bad_access(registers, pc, fault_address, is_write) {
+            log::info!("Reported the bad access to {fault_address:#x} at {pc:#x}");
+        }
+    }
+
+    let Some(previous) = BAD_ACCESS_SIGNALS
+        .iter()
+        .position(|&bad_access_signal| bad_access_signal == signal)
+        .and_then(|idx| PREVIOUS_HANDLERS.get().map(|previous| &previous[idx]))
+    else {
+        return;
+    };
+    match previous.sa_sigaction {
+        SIG_DFL | SIG_IGN => unsafe {
+            // Returning runs the faulting instruction again, which now takes the default action
+            sigaction(signal, previous, ptr::null_mut());
+        },
+        handler if previous.sa_flags & SA_SIGINFO != 0 => unsafe {
+            let handler =
+                mem::transmute::<usize, extern "C" fn(c_int, *mut siginfo_t, *mut c_void)>(handler);
+            handler(signal, info, context);
+        },
+        handler => unsafe {
+            let handler = mem::transmute::<usize, extern "C" fn(c_int)>(handler);
+            handler(signal);
+        },
+    }
+}
@@ -3,3 +3,5 @@ pub mod asan_rt;
 pub mod errors;
 #[allow(missing_docs)]
 pub mod hook_funcs;
+#[cfg(target_vendor = "apple")]
+pub mod mach_exceptions;
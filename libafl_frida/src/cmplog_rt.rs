@@ -14,17 +14,18 @@ use dynasmrt::dynasm;
 #[cfg(target_arch = "aarch64")]
 use dynasmrt::{DynasmApi, DynasmLabelApi};
 use frida_gum::ModuleMap;
-#[cfg(target_arch = "x86_64")]
-use frida_gum::{instruction_writer::InstructionWriter, stalker::StalkerOutput};
 #[cfg(target_arch = "aarch64")]
 use frida_gum::{
     instruction_writer::{Aarch64Register, IndexMode, InstructionWriter},
     stalker::StalkerOutput,
 };
+#[cfg(target_arch = "x86_64")]
+use frida_gum::{
+    instruction_writer::{InstructionWriter, X86Register},
+    stalker::StalkerOutput,
+};
 use frida_gum_sys::Insn;
 #[cfg(all(feature = "cmplog", target_arch = "x86_64"))]
-use hashbrown::HashMap;
-#[cfg(all(feature = "cmplog", target_arch = "x86_64"))]
 use iced_x86::{
     BlockEncoder, Code, DecoderOptions, Instruction, InstructionBlock, MemoryOperand, MemorySize,
     OpKind, Register,
@@ -71,6 +72,14 @@ fn gum_red_zone_size_i32() -> i32 {
     frida_gum_sys::GUM_RED_ZONE_SIZE as i32
 }
 
+/// The number of bytes pushed by the `save_registers` blob
+#[cfg(all(feature = "cmplog", unix, target_arch = "x86_64"))]
+const SAVED_REGISTERS_SIZE: i64 = 9 * 8;
+
+/// The number of bytes pushed by the `save_registers` blob
+#[cfg(all(feature = "cmplog", windows, target_arch = "x86_64"))]
+const SAVED_REGISTERS_SIZE: i64 = 7 * 8;
+
 /// The type of an operand loggged during `CmpLog`
 #[derive(Debug, Clone, Copy)]
 #[cfg(all(feature = "cmplog", target_arch = "aarch64"))]
@@ -188,8 +197,15 @@ impl CmpLogRuntime {
 
         k &= (CMPLOG_MAP_W as u64) - 1;
 
+        // Registers are pushed whole, drop the bits the compare does not use
+        let mask = if size >= 8 {
+            u64::MAX
+        } else {
+            (1 << (u32::from(size) * 8)) - 1
+        };
+
         unsafe {
-            __libafl_targets_cmplog_instructions(k as usize, size, op1, op2);
+            __libafl_targets_cmplog_instructions(k as usize, size, op1 & mask, op2 & mask);
         }
     }
 
@@ -319,6 +335,7 @@ impl CmpLogRuntime {
                 ; push r9
                 ; push r10
                 ; push r11
+                // rax must be saved last, see `emit_comparison_handling`
                 ; push rax
             );};
         }
@@ -349,13 +366,16 @@ impl CmpLogRuntime {
         macro_rules! save_registers {
             ($ops:ident) => {dynasm!($ops
                 ; .arch x64
-                ; push rax
-                ; push rdi
-                ; push rsi
-                ; push rdx
                 ; push rcx
+                ; push rdx
+                ; push rsi
+                ; push rdi
                 ; push r8
                 ; push r9
+                ; push r10
+                ; push r11
+                // rax must be saved last, see `emit_comparison_handling`
+                ; push rax
             );};
         }
         let mut save_registers = dynasmrt::VecAssembler::<dynasmrt::x64::X64Relocation>::new(0);
@@ -365,13 +385,15 @@ impl CmpLogRuntime {
         macro_rules! restore_registers {
             ($ops:ident) => {dynasm!($ops
                 ; .arch x64
+                ; pop rax
+                ; pop r11
+                ; pop r10
                 ; pop r9
                 ; pop r8
-                ; pop rcx
-                ; pop rdx
-                ; pop rsi
                 ; pop rdi
-                ; pop rax
+                ; pop rsi
+                ; pop rdx
+                ; pop rcx
             );};
         }
         let mut restore_registers = dynasmrt::VecAssembler::<dynasmrt::x64::X64Relocation>::new(0);
@@ -417,8 +439,8 @@ impl CmpLogRuntime {
         _special_case: &Option<SpecialCmpLogCase>,
     ) {
         let writer = output.writer();
-
-        writer.put_bytes(&self.save_registers.clone().unwrap());
+        let redzone_size = isize::try_from(frida_gum_sys::GUM_RED_ZONE_SIZE).unwrap();
+        let redzone_size_i64 = i64::try_from(frida_gum_sys::GUM_RED_ZONE_SIZE).unwrap();
 
         // let int3 = [0xcc];
         // writer.put_bytes(&int3);
@@ -431,10 +453,6 @@ impl CmpLogRuntime {
         let arg_reg_2;
         let arg_reg_3;
         let arg_reg_4;
-        let mut tmp_reg = HashMap::new();
-        tmp_reg.insert(8, Register::RAX);
-        tmp_reg.insert(4, Register::EAX);
-        tmp_reg.insert(2, Register::AX);
 
         #[cfg(windows)]
         {
@@ -445,7 +463,7 @@ impl CmpLogRuntime {
         }
         #[cfg(unix)]
         {
-            arg_reg_1 = Register::DL;
+            arg_reg_1 = Register::DIL;
             arg_reg_2 = Register::RSI;
             arg_reg_3 = Register::RDX;
             arg_reg_4 = Register::RCX;
@@ -458,10 +476,13 @@ impl CmpLogRuntime {
             }
         };
         // we put the operand value into rax and than push it on stack, so the
-        // only clobbered register is rax, and if actual operand value uses it,
+        // only clobbered register is rax, and if an operand uses it,
         // we simply restore it from stack
         for (op_num, op) in [op1, op2].iter().enumerate() {
             let op_num: i64 = op_num.try_into().unwrap();
+            // the saved rax, then the operands pushed so far
+            let rax_offset = op_num * 8;
+            let old_rsp_offset = rax_offset + SAVED_REGISTERS_SIZE + 8 + redzone_size_i64;
             match op {
                 CmplogOperandType::Reg(reg) => {
                     let info = reg.info();
@@ -472,47 +493,57 @@ impl CmpLogRuntime {
                             // we rely on the fact that latest saved register on stack is rax
                             Instruction::with1(
                                 Code::Push_rm64,
-                                MemoryOperand::with_base_displ(Register::RSP, op_num * 8),
+                                MemoryOperand::with_base_displ(Register::RSP, rax_offset),
+                            )
+                            .unwrap(),
+                        );
+                    } else if reg_largest == Register::RSP {
+                        insts.push(
+                            Instruction::with2(
+                                Code::Lea_r64_m,
+                                Register::RAX,
+                                MemoryOperand::with_base_displ(Register::RSP, old_rsp_offset),
                             )
                             .unwrap(),
                         );
+                        insts.push(Instruction::with1(Code::Push_rm64, Register::RAX).unwrap());
                     } else {
                         insts.push(Instruction::with1(Code::Push_rm64, reg_largest).unwrap());
                     }
                 }
                 CmplogOperandType::Mem(reg_base, reg_index, disp, scale, mem_size) => {
-                    let size;
-                    let inst;
-                    match *mem_size {
+                    let (size, inst, tmp_reg) = match *mem_size {
                         MemorySize::UInt64 | MemorySize::Int64 => {
-                            size = 8;
-                            inst = Code::Mov_r64_rm64;
+                            (8, Code::Mov_r64_rm64, Register::RAX)
                         }
                         MemorySize::UInt32 | MemorySize::Int32 => {
-                            size = 4;
-                            inst = Code::Mov_r32_rm32;
+                            (4, Code::Mov_r32_rm32, Register::EAX)
                         }
                         MemorySize::UInt16 | MemorySize::Int16 => {
-                            size = 2;
-                            inst = Code::Mov_r16_rm16;
+                            (2, Code::Movzx_r32_rm16, Register::EAX)
                         }
                         _ => {
-                            println!("Invalid memory size");
-                            size = 4;
-                            inst = Code::Push_rm32;
+                            log::warn!("Invalid memory size {mem_size:?} at {address:#x}");
+                            return;
                         }
-                    }
+                    };
                     set_size(size);
                     let mut disp_adjusted = *disp;
                     let mut reg_base = *reg_base;
                     if reg_base == Register::RSP {
-                        // 0x38 is an amount of bytes used by save_registers()
-                        disp_adjusted = disp_adjusted + 0x38 + 8_i64 * op_num;
+                        disp_adjusted += old_rsp_offset;
                     }
-                    let tmp_reg_adjusted = *tmp_reg.get(&size).unwrap();
+                    // the first operand may have clobbered rax, restore it in case the address uses it
+                    insts.push(
+                        Instruction::with2(
+                            Code::Mov_r64_rm64,
+                            Register::RAX,
+                            MemoryOperand::with_base_displ(Register::RSP, rax_offset),
+                        )
+                        .unwrap(),
+                    );
                     // in case of RIP, disp is an absolute address already calculated
-                    // by iced, we can simply load it to rax (but in this case index register must
-                    // be not rax)
+                    // by iced, we can simply load it to rax (RIP with an index register is not instrumented)
                     if reg_base == Register::RIP {
                         insts.push(
                             Instruction::with2(Code::Mov_r64_imm64, Register::RAX, disp_adjusted)
@@ -524,7 +555,7 @@ impl CmpLogRuntime {
                     insts.push(
                         Instruction::with2(
                             inst,
-                            tmp_reg_adjusted,
+                            tmp_reg,
                             MemoryOperand::with_base_index_scale_displ_size(
                                 reg_base,
                                 *reg_index,
@@ -543,16 +574,51 @@ impl CmpLogRuntime {
             }
         }
 
-        insts.push(Instruction::with2(Code::Mov_r8_imm8, arg_reg_1, size_op as u64).unwrap());
-        insts.push(Instruction::with1(Code::Pop_r64, arg_reg_2).unwrap());
+        // op2 was pushed last
         insts.push(Instruction::with1(Code::Pop_r64, arg_reg_3).unwrap());
+        insts.push(Instruction::with1(Code::Pop_r64, arg_reg_2).unwrap());
+        insts.push(Instruction::with2(Code::Mov_r8_imm8, arg_reg_1, size_op as u64).unwrap());
         insts.push(Instruction::with2(Code::Mov_r64_imm64, arg_reg_4, address).unwrap());
+
+        // the ABI wants a 16-byte aligned stack at the call, keep the old one in the callee-saved rbx
+        insts.push(Instruction::with1(Code::Push_rm64, Register::RBX).unwrap());
+        insts.push(Instruction::with2(Code::Mov_r64_rm64, Register::RBX, Register::RSP).unwrap());
+        insts.push(Instruction::with2(Code::And_rm64_imm8, Register::RSP, -16).unwrap());
+        #[cfg(windows)]
+        // the shadow space of the callee
+        insts.push(Instruction::with2(Code::Sub_rm64_imm8, Register::RSP, 32).unwrap());
+        insts.push(
+            Instruction::with2(
+                Code::Mov_r64_imm64,
+                Register::RAX,
+                CmpLogRuntime::populate_lists as usize as u64,
+            )
+            .unwrap(),
+        );
+        insts.push(Instruction::with1(Code::Call_rm64, Register::RAX).unwrap());
+        insts.push(Instruction::with2(Code::Mov_r64_rm64, Register::RSP, Register::RBX).unwrap());
+        insts.push(Instruction::with1(Code::Pop_r64, Register::RBX).unwrap());
+
         let block = InstructionBlock::new(&insts, 0);
         let block = BlockEncoder::encode(64, block, DecoderOptions::NONE).unwrap();
+
+        // only emit once the operands are known to be supported, so that bailing out above leaves nothing behind
+        /* The stack while the operands are pushed:
+            | op1        | <- only once op1 is pushed
+            | Rax        |
+            | ...        | <- save_registers
+            | flags      |
+            | (red zone) |
+            Old Rsp  ->  |            |
+        */
+        writer.put_lea_reg_reg_offset(X86Register::Rsp, X86Register::Rsp, -redzone_size);
+        writer.put_pushfx();
+        writer.put_bytes(&self.save_registers.clone().unwrap());
         writer.put_bytes(block.code_buffer.as_slice());
-        writer.put_call_address((CmpLogRuntime::populate_lists as usize).try_into().unwrap());
 
         writer.put_bytes(&self.restore_registers.clone().unwrap());
+        writer.put_popfx();
+        writer.put_lea_reg_reg_offset(X86Register::Rsp, X86Register::Rsp, redzone_size);
     }
 
     /// Emit the instrumentation code which is responsible for operands value extraction and cmplog map population
@@ -683,6 +749,24 @@ impl CmpLogRuntime {
             return None;
         }
 
+        // fs and gs relative operands cannot be read back, and the high byte registers are not at the bottom of
+        // their full register
+        let uses_unsupported_register = |op: u32| {
+            instruction.op_kind(op) == OpKind::Register
+                && matches!(
+                    instruction.op_register(op),
+                    Register::AH | Register::BH | Register::CH | Register::DH
+                )
+        };
+        if (matches!(instruction.memory_segment(), Register::FS | Register::GS)
+            && (instruction.op0_kind() == OpKind::Memory
+                || instruction.op1_kind() == OpKind::Memory))
+            || uses_unsupported_register(0)
+            || uses_unsupported_register(1)
+        {
+            return None;
+        }
+
         let op1 = match instruction.op0_kind() {
             OpKind::Register => CmplogOperandType::Reg(instruction.op0_register()),
             OpKind::Immediate16
@@ -1,11 +1,22 @@
 //! Functionality regarding binary-only coverage collection.
-
-use std::{cell::RefCell, marker::PhantomPinned, pin::Pin, rc::Rc};
+//!
+//! Each thread followed by the stalker keeps its own previous location, so that the edges of concurrent threads do
+//! not mix. The edges of all threads go to the shared map, unless the runtime attributes them to their threads
+//! (see [`CoverageRuntime::with_thread_attribution`]): then each thread fills its own map, merged into the shared
+//! map after each run.
+
+use std::{
+    cell::{Ref, RefCell},
+    marker::PhantomPinned,
+    pin::Pin,
+    rc::Rc,
+};
 
 #[cfg(target_arch = "aarch64")]
 use dynasmrt::DynasmLabelApi;
 use dynasmrt::{dynasm, DynasmApi};
 use frida_gum::{instruction_writer::InstructionWriter, stalker::StalkerOutput, ModuleMap};
+use hashbrown::HashMap;
 use libafl_bolts::hash_std;
 use rangemap::RangeMap;
 
@@ -14,13 +25,39 @@ use crate::helper::FridaRuntime;
 /// (Default) map size for frida coverage reporting
 pub const MAP_SIZE: usize = 64 * 1024;
 
+/// The coverage state of a followed thread, referenced by the code generated for it
+#[derive(Debug)]
+struct ThreadCoverage {
+    previous_pc: u64,
+    /// The map of the thread, if the edges are attributed to their threads
+    map: Option<Box<[u8]>>,
+}
+
 #[derive(Debug)]
 struct CoverageRuntimeInner {
     map: [u8; MAP_SIZE],
-    previous_pc: u64,
+    /// The state of each followed thread, by thread id. Boxed, as the generated code points into it.
+    threads: HashMap<u64, Box<ThreadCoverage>>,
+    attribute_threads: bool,
+    /// The maps of the threads which ran in the last run, if the edges are attributed to their threads
+    thread_maps: HashMap<u64, Vec<u8>>,
     _pinned: PhantomPinned,
 }
 
+impl CoverageRuntimeInner {
+    /// The state of the thread translating the current block, i.e., the thread which will run it
+    fn current_thread(&mut self) -> &mut ThreadCoverage {
+        let thread_id = unsafe { frida_gum_sys::gum_process_get_current_thread_id() } as u64;
+        let attribute_threads = self.attribute_threads;
+        self.threads.entry(thread_id).or_insert_with(|| {
+            Box::new(ThreadCoverage {
+                previous_pc: 0,
+                map: attribute_threads.then(|| vec![0; MAP_SIZE].into_boxed_slice()),
+            })
+        })
+    }
+}
+
 /// Frida binary-only coverage
 #[derive(Debug)]
 pub struct CoverageRuntime(Pin<Rc<RefCell<CoverageRuntimeInner>>>);
@@ -45,10 +82,36 @@ impl FridaRuntime for CoverageRuntime {
     fn deinit(&mut self, _gum: &frida_gum::Gum) {}
 
     fn pre_exec(&mut self, _input_bytes: &[u8]) -> Result<(), libafl::Error> {
+        // Drop what background threads covered between the runs
+        let mut inner = self.0.borrow_mut();
+        for thread in inner.threads.values_mut() {
+            if let Some(map) = &mut thread.map {
+                map.fill(0);
+            }
+        }
         Ok(())
     }
 
     fn post_exec(&mut self, _input_bytes: &[u8]) -> Result<(), libafl::Error> {
+        let mut inner = self.0.borrow_mut();
+        if !inner.attribute_threads {
+            return Ok(());
+        }
+        let inner = &mut *inner;
+        inner.thread_maps.clear();
+        for (thread_id, thread) in &mut inner.threads {
+            let Some(map) = &mut thread.map else {
+                continue;
+            };
+            if map.iter().all(|&hits| hits == 0) {
+                continue;
+            }
+            for (shared, hits) in inner.map.iter_mut().zip(map.iter()) {
+                *shared = shared.saturating_add(*hits);
+            }
+            inner.thread_maps.insert(*thread_id, map.to_vec());
+            map.fill(0);
+        }
         Ok(())
     }
 }
@@ -60,11 +123,31 @@ impl CoverageRuntime {
     pub fn new() -> Self {
         Self(Rc::pin(RefCell::new(CoverageRuntimeInner {
             map: [0_u8; MAP_SIZE],
-            previous_pc: 0,
+            threads: HashMap::new(),
+            attribute_threads: false,
+            thread_maps: HashMap::new(),
             _pinned: PhantomPinned,
         })))
     }
 
+    /// Attributes the edges to the threads covering them: each thread fills its own map, merged into the shared
+    /// map after each run and available from [`Self::thread_maps`].
+    ///
+    /// Only useful if the executor follows the threads spawned by the target, see
+    /// [`crate::helper::FridaInstrumentationHelperBuilder::follow_threads`].
+    #[must_use]
+    pub fn with_thread_attribution(self) -> Self {
+        self.0.borrow_mut().attribute_threads = true;
+        self
+    }
+
+    /// The maps of the threads which covered edges in the last run, by thread id, if the runtime attributes the
+    /// edges to their threads
+    #[must_use]
+    pub fn thread_maps(&self) -> Ref<HashMap<u64, Vec<u8>>> {
+        Ref::map(self.0.borrow(), |inner| &inner.thread_maps)
+    }
+
     /// Retrieve the coverage map pointer
     pub fn map_mut_ptr(&mut self) -> *mut u8 {
         self.0.borrow_mut().map.as_mut_ptr()
@@ -77,8 +160,14 @@ impl CoverageRuntime {
     #[allow(clippy::cast_possible_wrap)]
     pub fn generate_inline_code(&mut self, h64: u64) -> Box<[u8]> {
         let mut borrow = self.0.borrow_mut();
-        let prev_loc_ptr = &raw mut borrow.previous_pc;
-        let map_addr_ptr = &raw mut borrow.map;
+        let borrow = &mut *borrow;
+        let shared_map_ptr = borrow.map.as_mut_ptr();
+        let thread = borrow.current_thread();
+        let prev_loc_ptr = &raw mut thread.previous_pc;
+        let map_addr_ptr = thread
+            .map
+            .as_mut()
+            .map_or(shared_map_ptr, |map| map.as_mut_ptr());
         let mut ops = dynasmrt::VecAssembler::<dynasmrt::aarch64::Aarch64Relocation>::new(0);
         dynasm!(ops
             ;   .arch aarch64
@@ -135,8 +224,14 @@ impl CoverageRuntime {
     #[cfg(target_arch = "x86_64")]
     pub fn generate_inline_code(&mut self, h64: u64) -> Box<[u8]> {
         let mut borrow = self.0.borrow_mut();
-        let prev_loc_ptr = &raw mut borrow.previous_pc;
-        let map_addr_ptr = &raw mut borrow.map;
+        let borrow = &mut *borrow;
+        let shared_map_ptr = borrow.map.as_mut_ptr();
+        let thread = borrow.current_thread();
+        let prev_loc_ptr = &raw mut thread.previous_pc;
+        let map_addr_ptr = thread
+            .map
+            .as_mut()
+            .map_or(shared_map_ptr, |map| map.as_mut_ptr());
         let mut ops = dynasmrt::VecAssembler::<dynasmrt::x64::X64Relocation>::new(0);
         dynasm!(ops
             ;   .arch x64
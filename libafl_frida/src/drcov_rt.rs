@@ -1,8 +1,15 @@
 //! Generates `DrCov` traces
+//!
+//! By default, the [`DrCovRuntime`] writes the blocks new to each run reaching new code, which are about the runs
+//! adding a new corpus entry, to their own file. With [`DrCovRuntime::accumulate`], these files hold all the blocks
+//! found up to that run instead, and with [`DrCovRuntime::write_every`], the cumulative coverage is written to
+//! a single file at regular intervals, for coverage review in IDA or Ghidra during the campaign.
 use std::{
+    collections::BTreeMap,
     hash::{BuildHasher, Hasher},
     path::{Path, PathBuf},
     rc::Rc,
+    time::{Duration, Instant},
 };
 
 use ahash::RandomState;
@@ -21,8 +28,16 @@ pub struct DrCovRuntime {
     /// The memory ranges of this target
     ranges: RangeMap<u64, (u16, String)>,
     coverage_directory: PathBuf,
+    /// The end addresses of all blocks found so far, by start address, if accumulating
+    accumulated: Option<BTreeMap<u64, u64>>,
+    /// The interval to write the cumulative coverage at, instead of after each run reaching new code
+    interval: Option<Duration>,
+    last_write: Instant,
 }
 
+/// The file in the coverage directory the cumulative coverage is written to, see [`DrCovRuntime::write_every`]
+pub const DRCOV_CUMULATIVE_FILENAME: &str = "cumulative.drcov";
+
 impl FridaRuntime for DrCovRuntime {
     /// initializes this runtime with the given `ranges`
     fn init(
@@ -45,7 +60,22 @@ impl FridaRuntime for DrCovRuntime {
 
     /// Called after execution, writes the trace to a unique `DrCov` file for this trace
     /// into `./coverage/<input_hash>_<coverage_hash>.drcov`. Empty coverages will be skipped.
+    ///
+    /// With [`DrCovRuntime::write_every`], writes the cumulative coverage once the interval elapsed instead.
     fn post_exec(&mut self, input_bytes: &[u8]) -> Result<(), Error> {
+        if let Some(accumulated) = &mut self.accumulated {
+            accumulated.extend(self.drcov_basic_blocks.iter().map(|bb| (bb.start, bb.end)));
+        }
+
+        if let Some(interval) = self.interval {
+            self.drcov_basic_blocks.clear();
+            if self.last_write.elapsed() >= interval {
+                self.last_write = Instant::now();
+                self.write_to(self.coverage_directory.join(DRCOV_CUMULATIVE_FILENAME))?;
+            }
+            return Ok(());
+        }
+
         // We don't need empty coverage files
         if self.drcov_basic_blocks.is_empty() {
             return Ok(());
@@ -65,7 +95,7 @@ impl FridaRuntime for DrCovRuntime {
         let filename = self
             .coverage_directory
             .join(format!("{input_hash:016x}_{coverage_hash:016x}.drcov"));
-        DrCovWriter::new(&self.ranges).write(filename, &self.drcov_basic_blocks)?;
+        self.write_to(filename)?;
         self.drcov_basic_blocks.clear();
 
         Ok(())
@@ -86,6 +116,39 @@ impl DrCovRuntime {
             ..Self::default()
         }
     }
+
+    /// Keeps the blocks of all runs, so that each file holds all the blocks found so far, not only the new ones
+    #[must_use]
+    pub fn accumulate(mut self) -> Self {
+        self.accumulated.get_or_insert_with(BTreeMap::new);
+        self
+    }
+
+    /// Writes the cumulative coverage to [`DRCOV_CUMULATIVE_FILENAME`] in the coverage directory at most once per
+    /// `interval`, instead of a file per run reaching new code
+    #[must_use]
+    pub fn write_every(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self.accumulate()
+    }
+
+    /// Writes the blocks found so far to a `DrCov` file at `path`, on demand.
+    ///
+    /// Without [`DrCovRuntime::accumulate`], only the blocks new to the current run are known.
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let mut writer = DrCovWriter::new(&self.ranges);
+        match &self.accumulated {
+            Some(accumulated) => {
+                let blocks: Vec<DrCovBasicBlock> = accumulated
+                    .iter()
+                    .map(|(start, end)| DrCovBasicBlock::new(*start, *end))
+                    .collect();
+                writer.write(path, &blocks)?;
+            }
+            None => writer.write(path, &self.drcov_basic_blocks)?,
+        }
+        Ok(())
+    }
 }
 
 impl Default for DrCovRuntime {
@@ -94,6 +157,9 @@ impl Default for DrCovRuntime {
             drcov_basic_blocks: vec![],
             ranges: RangeMap::new(),
             coverage_directory: PathBuf::from("./coverage"),
+            accumulated: None,
+            interval: None,
+            last_write: Instant::now(),
         }
     }
 }
@@ -24,9 +24,12 @@ use libafl_bolts::{tuples::RefIndexable, AsSlice};
 
 #[cfg(not(test))]
 use crate::asan::errors::AsanErrors;
-use crate::helper::{FridaInstrumentationHelper, FridaRuntimeTuple};
 #[cfg(windows)]
 use crate::windows_hooks::initialize;
+use crate::{
+    helper::{FridaInstrumentationHelper, FridaRuntimeTuple},
+    threads,
+};
 
 /// The [`FridaInProcessExecutor`] is an [`Executor`] that executes the target in the same process, usinig [`frida`](https://frida.re/) for binary-only instrumentation.
 pub struct FridaInProcessExecutor<'a, 'b, 'c, H, OT, RT, S, TC>
@@ -40,8 +43,12 @@ where
     base: InProcessExecutor<'a, H, OT, S>,
     /// `thread_id` for the Stalker
     thread_id: Option<u32>,
+    /// To set up a new stalker when the instrumented ranges change
+    gum: &'a Gum,
     /// Frida's dynamic rewriting engine
     stalker: Stalker,
+    /// Stalkers replaced while spawned threads may still run the code they translated
+    retired_stalkers: Vec<Stalker>,
     /// User provided callback for instrumentation
     helper: &'c mut FridaInstrumentationHelper<'b, RT>,
     target_bytes_converter: TC,
@@ -88,6 +95,12 @@ where
         let target_bytes = self.target_bytes_converter.to_target_bytes(input);
         self.helper.pre_exec(target_bytes.as_slice())?;
         if self.helper.stalker_enabled() {
+            if self.helper.instrument_jit() {
+                self.helper.update_jit_ranges();
+            }
+            if self.helper.take_ranges_changed() {
+                self.reset_stalker();
+            }
             if self.followed {
                 self.stalker.activate(NativePointer(core::ptr::null_mut()));
             } else {
@@ -104,7 +117,14 @@ where
                 }
             }
         }
+        let follow_threads = self.helper.stalker_enabled() && self.helper.follow_threads();
+        if follow_threads {
+            unsafe { threads::set_stalker(&raw mut self.stalker, self.helper.transformer()) };
+        }
         let res = self.base.run_target(fuzzer, state, mgr, input);
+        if follow_threads {
+            unsafe { threads::set_stalker(core::ptr::null_mut(), core::ptr::null()) };
+        }
         if self.helper.stalker_enabled() {
             self.stalker.deactivate();
         }
@@ -209,6 +229,30 @@ where
         thread_id: Option<u32>,
         target_bytes_converter: TC,
     ) -> Self {
+        let stalker = Self::new_stalker(gum, helper);
+
+        #[cfg(windows)]
+        initialize(gum);
+
+        if helper.stalker_enabled() && helper.follow_threads() {
+            threads::hook_thread_creation(gum);
+        }
+
+        Self {
+            base,
+            thread_id,
+            gum,
+            stalker,
+            retired_stalkers: Vec::new(),
+            helper,
+            target_bytes_converter,
+            followed: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Creates a [`Stalker`] excluding all code outside of the ranges of the `helper`
+    fn new_stalker(gum: &Gum, helper: &FridaInstrumentationHelper<'b, RT>) -> Stalker {
         let mut stalker = Stalker::new(gum);
         // Include the current module (the fuzzer) in stalked ranges. We clone the ranges so that
         // we don't add it to the INSTRUMENTED ranges.
@@ -238,19 +282,36 @@ where
                 ));
             }
         }
+        stalker
+    }
 
-        #[cfg(windows)]
-        initialize(gum);
-
-        Self {
-            base,
-            thread_id,
-            stalker,
-            helper,
-            target_bytes_converter,
-            followed: false,
-            _phantom: PhantomData,
+    /// Drops the stalker with all the code it translated, to pick up the changed ranges of the helper
+    fn reset_stalker(&mut self) {
+        if self.followed {
+            if let Some(thread_id) = self.thread_id {
+                self.stalker.unfollow(thread_id.try_into().unwrap());
+            } else {
+                self.stalker.unfollow_me();
+            }
+            self.followed = false;
         }
+        let stalker = Self::new_stalker(self.gum, self.helper);
+        let retired = core::mem::replace(&mut self.stalker, stalker);
+        if self.helper.follow_threads() {
+            // Threads spawned in earlier runs may still be following the old stalker
+            self.retired_stalkers.push(retired);
+        }
+    }
+
+    /// The instrumentation helper
+    #[must_use]
+    pub fn helper(&self) -> &FridaInstrumentationHelper<'b, RT> {
+        self.helper
+    }
+
+    /// The instrumentation helper (mutable), e.g., to change the instrumented ranges mid-campaign
+    pub fn helper_mut(&mut self) -> &mut FridaInstrumentationHelper<'b, RT> {
+        self.helper
     }
 }
 
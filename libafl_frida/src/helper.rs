@@ -3,19 +3,22 @@ use std::{
     cell::{Ref, RefCell, RefMut},
     ffi::CStr,
     fs::{self, read_to_string},
+    ops::Range,
     path::{Path, PathBuf},
     rc::Rc,
+    sync::{Mutex, PoisonError},
 };
 
 use frida_gum::{
     instruction_writer::InstructionWriter,
     stalker::{StalkerIterator, StalkerOutput, Transformer},
-    Backend, Gum, ModuleDetails, ModuleMap, Script,
+    Backend, Gum, Module, ModuleDetails, ModuleMap, PageProtection, RangeDetails, Script,
 };
 use frida_gum_sys::gchar;
 use libafl::Error;
 use libafl_bolts::{
     cli::{FridaScriptBackend, FuzzerOptions},
+    hash_std,
     tuples::MatchFirstType,
 };
 use libafl_targets::drcov::DrCovBasicBlock;
@@ -31,7 +34,19 @@ use yaxpeax_x86::amd64::InstDecoder;
 
 #[cfg(feature = "cmplog")]
 use crate::cmplog_rt::CmpLogRuntime;
-use crate::{asan::asan_rt::AsanRuntime, coverage_rt::CoverageRuntime, drcov_rt::DrCovRuntime};
+use crate::{
+    asan::asan_rt::AsanRuntime, coverage_rt::CoverageRuntime, drcov_rt::DrCovRuntime,
+    tokens_rt::ImmediateTokensRuntime,
+};
+
+/// The module id of the code generated at runtime, see [`FridaInstrumentationHelperBuilder::instrument_jit`]
+pub const JIT_MODULE_ID: u16 = 0xfffe;
+
+/// The module name of the code generated at runtime, see [`FridaInstrumentationHelperBuilder::instrument_jit`]
+pub const JIT_MODULE_NAME: &str = "[jit]";
+
+/// Serializes the transformation of blocks by concurrently followed threads
+static TRANSFORM_LOCK: Mutex<()> = Mutex::new(());
 
 /// The Runtime trait
 pub trait FridaRuntime: 'static + Debug {
@@ -141,10 +156,13 @@ pub enum SkipRange {
 pub struct FridaInstrumentationHelperBuilder {
     stalker_enabled: bool,
     disable_excludes: bool,
+    follow_threads: bool,
+    instrument_jit: bool,
     #[allow(clippy::type_complexity)]
     instrument_module_predicate: Option<Box<dyn FnMut(&ModuleDetails) -> bool>>,
     skip_module_predicate: Box<dyn FnMut(&ModuleDetails) -> bool>,
     skip_ranges: Vec<SkipRange>,
+    module_cache_directory: Option<PathBuf>,
 }
 
 impl FridaInstrumentationHelperBuilder {
@@ -204,6 +222,33 @@ impl FridaInstrumentationHelperBuilder {
         }
     }
 
+    /// Follow the threads spawned by the target during a run
+    ///
+    /// By default, only the harness thread is instrumented. With this, each thread spawned by the target follows
+    /// itself with the stalker of the executor, and merges its coverage into the shared map, see
+    /// [`crate::threads`] and [`CoverageRuntime::with_thread_attribution`].
+    #[must_use]
+    pub fn follow_threads(self, follow: bool) -> Self {
+        Self {
+            follow_threads: follow,
+            ..self
+        }
+    }
+
+    /// Instrument code generated at runtime, e.g., by JavaScript, Java, or .NET JITs
+    ///
+    /// Without this, code outside of the loaded modules stays uninstrumented. With this, the anonymous executable
+    /// mappings are looked up before each run: new ones are instrumented, and unmapped ones are dropped together
+    /// with the code the stalker translated for them. The coverage of generated code is hashed into the fixed
+    /// coverage map like the rest.
+    #[must_use]
+    pub fn instrument_jit(self, enabled: bool) -> Self {
+        Self {
+            instrument_jit: enabled,
+            ..self
+        }
+    }
+
     /// Modules for which the given predicate returns `true` will be instrumented.
     ///
     /// Can be specified multiple times; a module will be instrumented if _any_ of the given predicates match.
@@ -272,6 +317,20 @@ impl FridaInstrumentationHelperBuilder {
         self
     }
 
+    /// Cache the modules selected for instrumentation in `directory`
+    ///
+    /// Evaluating the module predicates against all loaded modules is slow for large targets, and the result is
+    /// the same for each restart of a client. The cache is keyed by the paths, sizes, and modification times of the
+    /// loaded modules, so that a rebuilt binary or a changed set of libraries selects the modules again. The
+    /// translated code itself belongs to the stalker of a process and cannot be cached.
+    ///
+    /// The predicates are not part of the key, use a separate directory for each set of instrumented modules.
+    #[must_use]
+    pub fn module_cache_directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.module_cache_directory = Some(directory.into());
+        self
+    }
+
     /// Build a [`FridaInstrumentationHelper`]
     pub fn build<RT: FridaRuntimeTuple>(
         self,
@@ -281,21 +340,49 @@ impl FridaInstrumentationHelperBuilder {
         let Self {
             stalker_enabled,
             disable_excludes,
+            follow_threads,
+            instrument_jit,
             mut instrument_module_predicate,
             mut skip_module_predicate,
             skip_ranges,
+            module_cache_directory,
         } = self;
 
+        let cache_file = module_cache_directory
+            .as_deref()
+            .map(|directory| Self::module_cache_file(gum, directory));
+        let cached_modules = cache_file.as_deref().and_then(Self::load_module_cache);
+        let mut selected_modules = Vec::new();
+        let selected = &mut selected_modules;
         let mut module_filter = Box::new(move |module| {
-            if let Some(instrument_module_predicate) = &mut instrument_module_predicate {
+            let instrument = if let Some(cached_modules) = &cached_modules {
+                cached_modules.contains(&module.path())
+            } else if let Some(instrument_module_predicate) = &mut instrument_module_predicate {
                 let skip = skip_module_predicate(&module);
                 let should_instrument = instrument_module_predicate(&module);
                 should_instrument && !skip
             } else {
                 !skip_module_predicate(&module)
+            };
+            if instrument {
+                selected.push(module.path());
             }
+            instrument
         });
         let module_map = Rc::new(ModuleMap::new_with_filter(gum, &mut module_filter));
+        drop(module_filter);
+        if let Some(cache_file) = &cache_file {
+            if !cache_file.exists() {
+                if let Err(err) = fs::create_dir_all(cache_file.parent().unwrap())
+                    .and_then(|()| fs::write(cache_file, selected_modules.join("\n")))
+                {
+                    log::warn!(
+                        "Could not write the module cache {}: {err}",
+                        cache_file.display()
+                    );
+                }
+            }
+        }
 
         let ranges = RangeMap::new();
         // Wrap ranges and runtimes in reference-counted refcells in order to move
@@ -350,8 +437,34 @@ impl FridaInstrumentationHelperBuilder {
             runtimes,
             stalker_enabled,
             disable_excludes,
+            follow_threads,
+            instrument_jit,
+            jit_ranges: Vec::new(),
+            ranges_changed: false,
         }
     }
+
+    /// The module cache file in `directory` for the modules loaded now
+    fn module_cache_file(gum: &Gum, directory: &Path) -> PathBuf {
+        let mut key = Vec::new();
+        for module in Module::obtain(gum).enumerate_modules() {
+            key.extend_from_slice(module.path.as_bytes());
+            if let Ok(metadata) = fs::metadata(&module.path) {
+                key.extend_from_slice(&metadata.len().to_le_bytes());
+                if let Ok(modified) = metadata.modified() {
+                    key.extend_from_slice(format!("{modified:?}").as_bytes());
+                }
+            }
+        }
+        directory.join(format!("modules-{:016x}.cache", hash_std(&key)))
+    }
+
+    /// The paths of the modules selected for instrumentation, if cached in `cache_file`
+    fn load_module_cache(cache_file: &Path) -> Option<Vec<String>> {
+        let cached = read_to_string(cache_file).ok()?;
+        log::info!("Using the module cache {}", cache_file.display());
+        Some(cached.lines().map(str::to_string).collect())
+    }
 }
 
 impl Debug for FridaInstrumentationHelperBuilder {
@@ -362,7 +475,10 @@ impl Debug for FridaInstrumentationHelperBuilder {
             .field("instrument_module_predicate", &"<closure>")
             .field("skip_module_predicate", &"<closure>")
             .field("skip_ranges", &self.skip_ranges)
-            .field("disable_excludes", &self.disable_excludes);
+            .field("disable_excludes", &self.disable_excludes)
+            .field("follow_threads", &self.follow_threads)
+            .field("instrument_jit", &self.instrument_jit)
+            .field("module_cache_directory", &self.module_cache_directory);
         dbg_me.finish()
     }
 }
@@ -372,6 +488,8 @@ impl Default for FridaInstrumentationHelperBuilder {
         Self {
             stalker_enabled: true,
             disable_excludes: false,
+            follow_threads: false,
+            instrument_jit: false,
             instrument_module_predicate: None,
             skip_module_predicate: Box::new(|module| {
                 // Skip the instrumentation module to avoid recursion.
@@ -381,6 +499,7 @@ impl Default for FridaInstrumentationHelperBuilder {
                 range.contains(&(Self::new as usize))
             }),
             skip_ranges: Vec::new(),
+            module_cache_directory: None,
         }
     }
 }
@@ -392,6 +511,12 @@ pub struct FridaInstrumentationHelper<'a, RT: 'a> {
     runtimes: Rc<RefCell<RT>>,
     stalker_enabled: bool,
     pub(crate) disable_excludes: bool,
+    follow_threads: bool,
+    instrument_jit: bool,
+    /// The anonymous executable mappings instrumented so far, if instrumenting generated code
+    jit_ranges: Vec<Range<u64>>,
+    /// If the instrumented ranges changed since the stalker of the executor was set up
+    ranges_changed: bool,
 }
 
 impl<RT> Debug for FridaInstrumentationHelper<'_, RT> {
@@ -504,6 +629,10 @@ where
         let decoder = <ARMv8 as Arch>::Decoder::default();
 
         Transformer::from_callback(gum, move |basic_block, output| {
+            // Followed threads translate their blocks concurrently, while the runtimes are not thread-safe
+            let _guard = TRANSFORM_LOCK
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
             Self::transform(basic_block, &output, &ranges, &runtimes, decoder);
         })
     }
@@ -614,6 +743,10 @@ where
                     }
                 }
 
+                if let Some(rt) = runtimes.match_first_type_mut::<ImmediateTokensRuntime>() {
+                    rt.harvest(decoder, instr);
+                }
+
                 if let Some(rt) = runtimes.match_first_type_mut::<AsanRuntime>() {
                     rt.add_stalked_address(
                         output.writer().pc() as usize - instr_size,
@@ -721,6 +854,18 @@ where
         self.stalker_enabled
     }
 
+    /// If the threads spawned by the target are followed, see [`FridaInstrumentationHelperBuilder::follow_threads`]
+    #[must_use]
+    pub fn follow_threads(&self) -> bool {
+        self.follow_threads
+    }
+
+    /// If code generated at runtime is instrumented, see [`FridaInstrumentationHelperBuilder::instrument_jit`]
+    #[must_use]
+    pub fn instrument_jit(&self) -> bool {
+        self.instrument_jit
+    }
+
     /// Pointer to coverage map
     pub fn map_mut_ptr(&mut self) -> Option<*mut u8> {
         (*self.runtimes)
@@ -736,7 +881,101 @@ where
     }
 
     /// Mutable ranges
+    ///
+    /// Changes apply from the next run on, see [`Self::exclude_range`].
     pub fn ranges_mut(&mut self) -> RefMut<RangeMap<u64, (u16, String)>> {
+        self.ranges_changed = true;
         (*self.ranges).borrow_mut()
     }
+
+    /// Stops instrumenting `range` from the next run on, e.g., a hot library discovered mid-campaign.
+    ///
+    /// The executor drops the code the stalker translated so far before the next run, so that no stale
+    /// instrumentation stays in its cache.
+    pub fn exclude_range(&mut self, range: &SkipRange) -> Result<(), Error> {
+        let range = match range {
+            SkipRange::Absolute(range) => range.start as u64..range.end as u64,
+            SkipRange::ModuleRelative { name, range } => {
+                let lib_start = Self::module_range(name)?.start;
+                (lib_start + range.start as u64)..(lib_start + range.end as u64)
+            }
+        };
+        self.ranges_mut().remove(range);
+        Ok(())
+    }
+
+    /// Stops instrumenting the module `name` from the next run on, see [`Self::exclude_range`].
+    pub fn exclude_module(&mut self, name: &str) -> Result<(), Error> {
+        let range = Self::module_range(name)?;
+        self.ranges_mut().remove(range);
+        Ok(())
+    }
+
+    /// Instruments the whole module `name` from the next run on, see [`Self::exclude_range`].
+    ///
+    /// The executor sets up the stalker excludes again from the new ranges, so this also works for modules left
+    /// out at startup.
+    pub fn include_module(&mut self, name: &str) -> Result<(), Error> {
+        let module_details = Self::module_details(name)?;
+        let path = module_details.path();
+        let start = module_details.range().base_address().0 as u64;
+        let range = start..start + module_details.range().size() as u64;
+        let mut ranges = self.ranges_mut();
+        // Keep the id of the module if parts of it are still instrumented
+        let id = ranges
+            .iter()
+            .find(|(_, (_, module_path))| *module_path == path)
+            .map(|(_, (id, _))| *id)
+            .or_else(|| ranges.iter().map(|(_, (id, _))| id + 1).max())
+            .unwrap_or(0);
+        ranges.insert(range, (id, path));
+        Ok(())
+    }
+
+    /// The details of the loaded module `name`
+    fn module_details(name: &str) -> Result<ModuleDetails, Error> {
+        ModuleDetails::with_name(name.to_string())
+            .ok_or_else(|| Error::illegal_argument(format!("Module {name} is not loaded")))
+    }
+
+    /// The address range of the loaded module `name`
+    fn module_range(name: &str) -> Result<Range<u64>, Error> {
+        let module_details = Self::module_details(name)?;
+        let start = module_details.range().base_address().0 as u64;
+        Ok(start..start + module_details.range().size() as u64)
+    }
+
+    /// Instruments the anonymous executable mappings created since the last call, and stops instrumenting the
+    /// unmapped ones, see [`FridaInstrumentationHelperBuilder::instrument_jit`]
+    pub(crate) fn update_jit_ranges(&mut self) {
+        let mut jit_ranges = Vec::new();
+        // Frida cloaks its own mappings, e.g., the code of the stalker, so they are not enumerated
+        RangeDetails::enumerate_with_prot(PageProtection::Execute, &mut |range: &RangeDetails| {
+            if range.file_mapping().is_none() {
+                let start = range.memory_range().base_address().0 as u64;
+                jit_ranges.push(start..start + range.memory_range().size() as u64);
+            }
+            true
+        });
+        if jit_ranges == self.jit_ranges {
+            return;
+        }
+
+        let old_jit_ranges = core::mem::replace(&mut self.jit_ranges, jit_ranges);
+        self.ranges_changed = true;
+        let mut ranges = (*self.ranges).borrow_mut();
+        for range in old_jit_ranges {
+            log::info!("JIT range unmapped: {:x}-{:x}", range.start, range.end);
+            ranges.remove(range);
+        }
+        for range in &self.jit_ranges {
+            log::info!("JIT range mapped: {:x}-{:x}", range.start, range.end);
+            ranges.insert(range.clone(), (JIT_MODULE_ID, JIT_MODULE_NAME.to_string()));
+        }
+    }
+
+    /// Whether the ranges changed since the last call, resetting the flag
+    pub(crate) fn take_ranges_changed(&mut self) -> bool {
+        core::mem::take(&mut self.ranges_changed)
+    }
 }
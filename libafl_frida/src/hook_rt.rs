@@ -0,0 +1,475 @@
+//! Rust callbacks on the entry and exit of functions of the target.
+//!
+//! The [`FunctionHookRuntime`] attaches callbacks to functions, given by address or export name, without having
+//! to write raw Gum code, e.g., to harvest the operands of `strcmp` as tokens, or to stub a function checking a
+//! license. Add it to the runtimes tuple of the [`crate::helper::FridaInstrumentationHelper`], the hooks are
+//! installed on init:
+//!
+//! ```ignore
+//! let tokens = Rc::new(RefCell::new(Tokens::new()));
+//! let harvested = Rc::clone(&tokens);
+//! let hooks = FunctionHookRuntime::new()
+//!     .on_enter(HookTarget::export("strcmp"), move |context| {
+//!         if let Some(token) = unsafe { context.arg_cstr(1) } {
+//!             harvested.borrow_mut().add_token(&token.to_bytes().to_vec());
+//!         }
+//!     })
+//!     .replace(HookTarget::export("check_license"), |_context| 1);
+//! ```
+//!
+//! Replacements skip the original function. They support functions with up to six integer or pointer arguments,
+//! returning an integer or pointer, which are not variadic.
+
+use core::fmt::{self, Debug, Formatter};
+use std::{ffi::CStr, rc::Rc};
+
+use frida_gum::{
+    interceptor::{Interceptor, InvocationContext, InvocationListener, Listener},
+    Gum, Module, ModuleMap, NativePointer,
+};
+use libafl::Error;
+use rangemap::RangeMap;
+
+use crate::helper::FridaRuntime;
+
+/// The maximum number of arguments passed to a replacement
+pub const MAX_REPLACEMENT_ARGS: usize = 6;
+
+/// A function replaced by a [`FunctionHookRuntime`], with the maximum number of arguments
+type RawFunction = unsafe extern "C" fn(usize, usize, usize, usize, usize, usize) -> usize;
+
+/// A value passed in a register-sized argument or return value
+pub trait HookArg: Sized {
+    /// The value from the raw register
+    fn from_raw(raw: usize) -> Self;
+    /// The raw register of the value
+    fn into_raw(self) -> usize;
+}
+
+macro_rules! impl_hook_arg {
+    ($($ty:ty),*) => {
+        $(
+            impl HookArg for $ty {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+                fn from_raw(raw: usize) -> Self {
+                    raw as $ty
+                }
+
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+                fn into_raw(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_hook_arg!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl HookArg for bool {
+    fn from_raw(raw: usize) -> Self {
+        raw != 0
+    }
+
+    fn into_raw(self) -> usize {
+        usize::from(self)
+    }
+}
+
+impl<T> HookArg for *const T {
+    fn from_raw(raw: usize) -> Self {
+        raw as *const T
+    }
+
+    fn into_raw(self) -> usize {
+        self as usize
+    }
+}
+
+impl<T> HookArg for *mut T {
+    fn from_raw(raw: usize) -> Self {
+        raw as *mut T
+    }
+
+    fn into_raw(self) -> usize {
+        self as usize
+    }
+}
+
+/// The function to hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookTarget {
+    /// The function at an absolute address
+    Address(usize),
+    /// The function exported under `name`, by the given module or any module
+    Export {
+        /// The module exporting the function, or `None` for any module
+        module: Option<String>,
+        /// The name of the export
+        name: String,
+    },
+}
+
+impl HookTarget {
+    /// The function exported under `name` by any module
+    #[must_use]
+    pub fn export(name: &str) -> Self {
+        Self::Export {
+            module: None,
+            name: name.to_string(),
+        }
+    }
+
+    /// The function exported under `name` by `module`
+    #[must_use]
+    pub fn module_export(module: &str, name: &str) -> Self {
+        Self::Export {
+            module: Some(module.to_string()),
+            name: name.to_string(),
+        }
+    }
+
+    /// Resolves the address of the function
+    fn resolve(&self, gum: &Gum) -> Option<NativePointer> {
+        match self {
+            Self::Address(address) => Some(NativePointer(*address as *mut _)),
+            Self::Export { module, name } => {
+                Module::obtain(gum).find_export_by_name(module.as_deref(), name)
+            }
+        }
+    }
+}
+
+/// The context of a call, passed to the callbacks of a [`FunctionHookRuntime`]
+pub struct HookContext<'a> {
+    invocation: InvocationContext<'a>,
+}
+
+impl Debug for HookContext<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HookContext").finish_non_exhaustive()
+    }
+}
+
+impl HookContext<'_> {
+    /// The argument `n`, starting from 0
+    #[must_use]
+    pub fn arg<T: HookArg>(&self, n: u32) -> T {
+        T::from_raw(self.invocation.arg(n))
+    }
+
+    /// Changes the argument `n` passed to the function, only meaningful on entry
+    pub fn set_arg<T: HookArg>(&mut self, n: u32, value: T) {
+        self.invocation.set_arg(n, value.into_raw());
+    }
+
+    /// The return value, only meaningful on exit
+    #[must_use]
+    pub fn return_value<T: HookArg>(&self) -> T {
+        T::from_raw(self.invocation.return_value())
+    }
+
+    /// Changes the return value, only meaningful on exit
+    pub fn set_return_value<T: HookArg>(&mut self, value: T) {
+        self.invocation.set_return_value(value.into_raw());
+    }
+
+    /// The id of the calling thread
+    #[must_use]
+    pub fn thread_id(&self) -> u32 {
+        self.invocation.thread_id()
+    }
+
+    /// The address the function returns to
+    #[must_use]
+    pub fn return_address(&self) -> usize {
+        self.invocation.return_addr()
+    }
+
+    /// The C string pointed to by the argument `n`, or `None` for a null pointer
+    ///
+    /// # Safety
+    /// The argument must be null or point to a nul-terminated string living through the call.
+    #[must_use]
+    pub unsafe fn arg_cstr(&self, n: u32) -> Option<&CStr> {
+        let ptr = self.arg::<*const core::ffi::c_char>(n);
+        (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) })
+    }
+
+    /// The `len` bytes pointed to by the argument `n`, or `None` for a null pointer
+    ///
+    /// # Safety
+    /// The argument must be null or point to `len` readable bytes living through the call.
+    #[must_use]
+    pub unsafe fn arg_bytes(&self, n: u32, len: usize) -> Option<&[u8]> {
+        let ptr = self.arg::<*const u8>(n);
+        (!ptr.is_null()).then(|| unsafe { core::slice::from_raw_parts(ptr, len) })
+    }
+}
+
+/// The arguments of a call to a replaced function, passed to the replacement of a [`FunctionHookRuntime`]
+#[derive(Debug)]
+pub struct ReplacementContext {
+    args: [usize; MAX_REPLACEMENT_ARGS],
+    original: RawFunction,
+}
+
+impl ReplacementContext {
+    /// The argument `n`, starting from 0, up to [`MAX_REPLACEMENT_ARGS`]
+    #[must_use]
+    pub fn arg<T: HookArg>(&self, n: usize) -> T {
+        T::from_raw(self.args[n])
+    }
+
+    /// Changes the argument `n` passed to the original function by [`Self::call_original`]
+    pub fn set_arg<T: HookArg>(&mut self, n: usize, value: T) {
+        self.args[n] = value.into_raw();
+    }
+
+    /// Calls the original function with the (changed) arguments, returning its raw return value
+    ///
+    /// # Safety
+    /// Calls the original function, with arguments which may have been changed.
+    pub unsafe fn call_original(&self) -> usize {
+        let [a0, a1, a2, a3, a4, a5] = self.args;
+        unsafe { (self.original)(a0, a1, a2, a3, a4, a5) }
+    }
+}
+
+type EntryExitCallback = Box<dyn FnMut(&mut HookContext)>;
+type ReplacementCallback = Box<dyn FnMut(&mut ReplacementContext) -> usize>;
+
+/// A callback on entry or exit, attached as an [`InvocationListener`]
+struct HookListener {
+    on_enter: Option<EntryExitCallback>,
+    on_leave: Option<EntryExitCallback>,
+}
+
+impl InvocationListener for HookListener {
+    fn on_enter(&mut self, invocation: InvocationContext) {
+        if let Some(callback) = &mut self.on_enter {
+            callback(&mut HookContext { invocation });
+        }
+    }
+
+    fn on_leave(&mut self, invocation: InvocationContext) {
+        if let Some(callback) = &mut self.on_leave {
+            callback(&mut HookContext { invocation });
+        }
+    }
+}
+
+/// A replacement, passed to [`replacement_trampoline`] as replacement data
+struct Replacement {
+    callback: ReplacementCallback,
+    original: Option<RawFunction>,
+}
+
+unsafe extern "C" fn replacement_trampoline(
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+) -> usize {
+    let invocation = Interceptor::current_invocation();
+    let replacement =
+        unsafe { &mut *(invocation.replacement_data().unwrap().0 as *mut Replacement) };
+    let original = replacement
+        .original
+        .expect("The replaced function was called before its replacement was installed");
+    (replacement.callback)(&mut ReplacementContext {
+        args: [a0, a1, a2, a3, a4, a5],
+        original,
+    })
+}
+
+/// A hook, with the state of its installation
+enum Hook {
+    Listener {
+        target: HookTarget,
+        listener: Box<HookListener>,
+        attached: Option<Listener>,
+    },
+    Replacement {
+        target: HookTarget,
+        replacement: Box<Replacement>,
+        replaced: Option<NativePointer>,
+    },
+}
+
+/// Calls Rust callbacks on the entry and exit of functions, see the [module-level docs](self).
+#[derive(Default)]
+pub struct FunctionHookRuntime {
+    hooks: Vec<Hook>,
+}
+
+impl Debug for FunctionHookRuntime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let targets: Vec<&HookTarget> = self
+            .hooks
+            .iter()
+            .map(|hook| match hook {
+                Hook::Listener { target, .. } | Hook::Replacement { target, .. } => target,
+            })
+            .collect();
+        f.debug_struct("FunctionHookRuntime")
+            .field("targets", &targets)
+            .finish()
+    }
+}
+
+impl FunctionHookRuntime {
+    /// Creates a new [`FunctionHookRuntime`] without hooks
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `callback` on each entry of the function `target`
+    #[must_use]
+    pub fn on_enter<F>(mut self, target: HookTarget, callback: F) -> Self
+    where
+        F: FnMut(&mut HookContext) + 'static,
+    {
+        self.hooks.push(Hook::Listener {
+            target,
+            listener: Box::new(HookListener {
+                on_enter: Some(Box::new(callback)),
+                on_leave: None,
+            }),
+            attached: None,
+        });
+        self
+    }
+
+    /// Calls `callback` on each exit of the function `target`
+    #[must_use]
+    pub fn on_leave<F>(mut self, target: HookTarget, callback: F) -> Self
+    where
+        F: FnMut(&mut HookContext) + 'static,
+    {
+        self.hooks.push(Hook::Listener {
+            target,
+            listener: Box::new(HookListener {
+                on_enter: None,
+                on_leave: Some(Box::new(callback)),
+            }),
+            attached: None,
+        });
+        self
+    }
+
+    /// Replaces the function `target` with `callback`, returning the raw return value.
+    ///
+    /// The original function only runs if the callback calls [`ReplacementContext::call_original`].
+    #[must_use]
+    pub fn replace<F>(mut self, target: HookTarget, callback: F) -> Self
+    where
+        F: FnMut(&mut ReplacementContext) -> usize + 'static,
+    {
+        self.hooks.push(Hook::Replacement {
+            target,
+            replacement: Box::new(Replacement {
+                callback: Box::new(callback),
+                original: None,
+            }),
+            replaced: None,
+        });
+        self
+    }
+
+    /// Installs all hooks not installed yet
+    fn install(&mut self, gum: &Gum) -> Result<(), Error> {
+        let mut interceptor = Interceptor::obtain(gum);
+        for hook in &mut self.hooks {
+            match hook {
+                Hook::Listener {
+                    target,
+                    listener,
+                    attached: attached @ None,
+                } => {
+                    let function = target.resolve(gum).ok_or_else(|| {
+                        Error::illegal_argument(format!(
+                            "Could not find the hook target {target:?}"
+                        ))
+                    })?;
+                    let listener =
+                        interceptor
+                            .attach(function, listener.as_mut())
+                            .map_err(|err| {
+                                Error::unknown(format!("Could not attach to {target:?}: {err:?}"))
+                            })?;
+                    *attached = Some(listener);
+                }
+                Hook::Replacement {
+                    target,
+                    replacement,
+                    replaced: replaced @ None,
+                } => {
+                    let function = target.resolve(gum).ok_or_else(|| {
+                        Error::illegal_argument(format!(
+                            "Could not find the hook target {target:?}"
+                        ))
+                    })?;
+                    let replacement_data = core::ptr::from_mut(replacement.as_mut());
+                    let original = interceptor
+                        .replace(
+                            function,
+                            NativePointer(replacement_trampoline as *mut _),
+                            NativePointer(replacement_data.cast()),
+                        )
+                        .map_err(|err| {
+                            Error::unknown(format!("Could not replace {target:?}: {err:?}"))
+                        })?;
+                    replacement.original =
+                        Some(unsafe { core::mem::transmute::<*mut _, RawFunction>(original.0) });
+                    *replaced = Some(function);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FridaRuntime for FunctionHookRuntime {
+    /// Installs the hooks
+    fn init(
+        &mut self,
+        gum: &Gum,
+        _ranges: &RangeMap<u64, (u16, String)>,
+        _module_map: &Rc<ModuleMap>,
+    ) {
+        self.install(gum)
+            .unwrap_or_else(|err| panic!("Failed to install the function hooks: {err}"));
+    }
+
+    /// Removes the hooks
+    fn deinit(&mut self, gum: &Gum) {
+        let mut interceptor = Interceptor::obtain(gum);
+        for hook in &mut self.hooks {
+            match hook {
+                Hook::Listener { attached, .. } => {
+                    if let Some(listener) = attached.take() {
+                        interceptor.detach(listener);
+                    }
+                }
+                Hook::Replacement { replaced, .. } => {
+                    if let Some(function) = replaced.take() {
+                        interceptor.revert(function);
+                    }
+                }
+            }
+        }
+    }
+
+    fn pre_exec(&mut self, _input_bytes: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _input_bytes: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
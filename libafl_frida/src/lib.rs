@@ -56,12 +56,19 @@ pub mod asan;
 /// Windows specific hooks to catch __fastfail like exceptions with Frida, see <https://github.com/AFLplusplus/LibAFL/issues/395> for more details
 pub mod windows_hooks;
 
+#[cfg(all(windows, target_arch = "x86_64"))]
+/// Structured capture of the Windows exceptions crashing the target, with their context and stack walk
+pub mod windows_exceptions;
+
 pub mod coverage_rt;
 
 /// Hooking thread lifecycle events. Seems like this is apple-only for now.
 #[cfg(target_vendor = "apple")]
 pub mod pthread_hook;
 
+/// Following the threads spawned by the target with the stalker
+pub mod threads;
+
 #[cfg(feature = "cmplog")]
 pub mod cmplog_rt;
 
@@ -70,6 +77,10 @@ pub mod helper;
 
 pub mod drcov_rt;
 
+pub mod hook_rt;
+
+pub mod tokens_rt;
+
 /// The frida executor
 pub mod executor;
 
@@ -174,10 +185,10 @@ impl FridaOptions {
                     }
                     "cmplog" => {
                         options.enable_cmplog = value.parse().unwrap();
-                        #[cfg(not(target_arch = "aarch64"))]
+                        #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
                         assert!(
                             !options.enable_cmplog,
-                            "cmplog is not currently supported on targets other than aarch64"
+                            "cmplog is not currently supported on targets other than aarch64 and x86_64"
                         );
 
                         if options.enable_cmplog {
@@ -0,0 +1,188 @@
+//! Following the threads spawned by the target.
+//!
+//! The stalker only instruments the threads it follows, by default the harness thread. Multithreaded targets do
+//! much of their work in worker threads, the coverage of which would be lost. With
+//! [`crate::helper::FridaInstrumentationHelperBuilder::follow_threads`], the thread creation function of the
+//! platform (`pthread_create` or `CreateThread`) is replaced, so that each thread spawned during a run starts
+//! by following itself with the stalker and transformer of the executor, before running its start routine.
+
+use std::{
+    ffi::c_void,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        OnceLock,
+    },
+};
+
+use frida_gum::{
+    interceptor::Interceptor,
+    stalker::{NoneEventSink, Stalker, Transformer},
+    Gum, Module, NativePointer,
+};
+
+/// The stalker new threads follow themselves with, only set during a run
+static STALKER: AtomicPtr<Stalker> = AtomicPtr::new(ptr::null_mut());
+
+/// The transformer new threads follow themselves with, only set during a run
+static TRANSFORMER: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// The original thread creation function
+static ORIGINAL_THREAD_CREATE: OnceLock<usize> = OnceLock::new();
+
+#[cfg(unix)]
+type StartRoutine = unsafe extern "C" fn(*mut c_void) -> *mut c_void;
+
+#[cfg(unix)]
+type ThreadCreate = unsafe extern "C" fn(
+    *mut libc::pthread_t,
+    *const libc::pthread_attr_t,
+    StartRoutine,
+    *mut c_void,
+) -> i32;
+
+#[cfg(windows)]
+type StartRoutine = unsafe extern "system" fn(*mut c_void) -> u32;
+
+#[cfg(windows)]
+type ThreadCreate = unsafe extern "system" fn(
+    *const c_void,
+    usize,
+    StartRoutine,
+    *mut c_void,
+    u32,
+    *mut u32,
+) -> *mut c_void;
+
+/// The start routine and argument of a new thread, passed to [`followed_thread_start`]
+struct ThreadStart {
+    routine: StartRoutine,
+    arg: *mut c_void,
+}
+
+/// Follows the new thread with the stalker set for the current run, if any
+unsafe fn follow_current_thread() -> bool {
+    let stalker = STALKER.load(Ordering::Acquire);
+    let transformer = TRANSFORMER.load(Ordering::Acquire);
+    let (Some(stalker), Some(transformer)) = (unsafe { stalker.as_mut() }, unsafe {
+        transformer.cast::<Transformer<'static>>().as_ref()
+    }) else {
+        return false;
+    };
+    stalker.follow_me::<NoneEventSink>(transformer, None);
+    true
+}
+
+/// Stops following the thread with the stalker set for the current run, if any
+unsafe fn unfollow_current_thread() {
+    if let Some(stalker) = unsafe { STALKER.load(Ordering::Acquire).as_mut() } {
+        stalker.unfollow_me();
+    }
+}
+
+#[cfg(unix)]
+unsafe extern "C" fn followed_thread_start(start: *mut c_void) -> *mut c_void {
+    let start = unsafe { Box::from_raw(start.cast::<ThreadStart>()) };
+    let followed = unsafe { follow_current_thread() };
+    let ret = unsafe { (start.routine)(start.arg) };
+    if followed {
+        unsafe { unfollow_current_thread() };
+    }
+    ret
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn followed_thread_start(start: *mut c_void) -> u32 {
+    let start = unsafe { Box::from_raw(start.cast::<ThreadStart>()) };
+    let followed = unsafe { follow_current_thread() };
+    let ret = unsafe { (start.routine)(start.arg) };
+    if followed {
+        unsafe { unfollow_current_thread() };
+    }
+    ret
+}
+
+/// The original thread creation function, as installed by [`hook_thread_creation`]
+fn original_thread_create() -> ThreadCreate {
+    let original = *ORIGINAL_THREAD_CREATE
+        .get()
+        .expect("The thread creation function was not replaced");
+    unsafe { std::mem::transmute::<usize, ThreadCreate>(original) }
+}
+
+#[cfg(unix)]
+unsafe extern "C" fn thread_create_detour(
+    thread: *mut libc::pthread_t,
+    attr: *const libc::pthread_attr_t,
+    routine: StartRoutine,
+    arg: *mut c_void,
+) -> i32 {
+    let start = Box::into_raw(Box::new(ThreadStart { routine, arg }));
+    let ret = unsafe {
+        original_thread_create()(thread, attr, followed_thread_start, start.cast::<c_void>())
+    };
+    if ret != 0 {
+        // The thread did not start, so nobody else owns the start
+        drop(unsafe { Box::from_raw(start) });
+    }
+    ret
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn thread_create_detour(
+    attributes: *const c_void,
+    stack_size: usize,
+    routine: StartRoutine,
+    arg: *mut c_void,
+    flags: u32,
+    thread_id: *mut u32,
+) -> *mut c_void {
+    let start = Box::into_raw(Box::new(ThreadStart { routine, arg }));
+    let handle = unsafe {
+        original_thread_create()(
+            attributes,
+            stack_size,
+            followed_thread_start,
+            start.cast::<c_void>(),
+            flags,
+            thread_id,
+        )
+    };
+    if handle.is_null() {
+        // The thread did not start, so nobody else owns the start
+        drop(unsafe { Box::from_raw(start) });
+    }
+    handle
+}
+
+/// Replaces the thread creation function of the platform, so that new threads follow themselves with the stalker
+/// set by [`set_stalker`]. Only replaces it once.
+pub(crate) fn hook_thread_creation(gum: &Gum) {
+    ORIGINAL_THREAD_CREATE.get_or_init(|| {
+        let module = Module::obtain(gum);
+        #[cfg(unix)]
+        let thread_create = module.find_export_by_name(None, "pthread_create");
+        #[cfg(windows)]
+        let thread_create = module.find_export_by_name(Some("kernel32.dll"), "CreateThread");
+        let thread_create = thread_create.expect("The thread creation function was not found");
+
+        let mut interceptor = Interceptor::obtain(gum);
+        let original = interceptor
+            .replace(
+                thread_create,
+                NativePointer(thread_create_detour as *mut c_void),
+                NativePointer(ptr::null_mut()),
+            )
+            .expect("Could not replace the thread creation function");
+        original.0 as usize
+    });
+}
+
+/// Sets the stalker and transformer new threads follow themselves with, or clears them with null pointers.
+///
+/// # Safety
+/// The stalker and transformer must stay valid until they are cleared again.
+pub(crate) unsafe fn set_stalker(stalker: *mut Stalker, transformer: *const Transformer) {
+    TRANSFORMER.store(transformer.cast_mut().cast::<c_void>(), Ordering::Release);
+    STALKER.store(stalker, Ordering::Release);
+}
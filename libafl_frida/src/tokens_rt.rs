@@ -0,0 +1,167 @@
+//! Harvests the immediate operands of the target into [`Tokens`].
+//!
+//! Binary-only targets lack the compile-time pass collecting the constants a target compares its input against.
+//! While the stalker transforms a block, the [`ImmediateTokensRuntime`] collects the immediate operands of its
+//! compare instructions and wide mov-immediates. The [`ImmediateTokensObserver`] adds the new ones to the
+//! [`Tokens`] of the state after each run, as dictionary material for the token mutators:
+//!
+//! ```ignore
+//! let tokens_runtime = ImmediateTokensRuntime::new();
+//! let tokens_observer = tokens_runtime.observer("immediate_tokens");
+//! let mut frida_helper = FridaInstrumentationHelper::new(&gum, &options, tuple_list!(coverage, tokens_runtime));
+//! ```
+
+use std::{borrow::Cow, cell::RefCell, rc::Rc};
+
+use frida_gum::ModuleMap;
+use frida_gum_sys::Insn;
+use hashbrown::HashSet;
+use libafl::{executors::ExitKind, mutators::Tokens, observers::Observer, Error, HasMetadata};
+use libafl_bolts::Named;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "aarch64")]
+use yaxpeax_arm::armv8::a64::{InstDecoder, Opcode, Operand};
+#[cfg(target_arch = "x86_64")]
+use yaxpeax_x86::amd64::{InstDecoder, Opcode, Operand};
+
+use crate::helper::FridaRuntime;
+#[cfg(target_arch = "x86_64")]
+use crate::utils::frida_to_cs;
+#[cfg(target_arch = "aarch64")]
+use crate::utils::{disas_count, get_reg_size};
+
+/// Immediates below this value are too common to be useful tokens
+const MIN_TOKEN_VALUE: u64 = 0x100;
+
+/// Collects the immediate operands of the target while the stalker transforms it, see the
+/// [module-level docs](self).
+#[derive(Debug, Default)]
+pub struct ImmediateTokensRuntime {
+    /// The immediates seen so far, with their width
+    seen: HashSet<(u64, usize)>,
+    /// The tokens not yet added to the state, shared with the observers
+    pending: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl ImmediateTokensRuntime {
+    /// Creates a new [`ImmediateTokensRuntime`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an [`ImmediateTokensObserver`] adding the tokens found by this runtime to the state
+    #[must_use]
+    pub fn observer(&self, name: &'static str) -> ImmediateTokensObserver {
+        ImmediateTokensObserver {
+            name: Cow::from(name),
+            pending: Rc::clone(&self.pending),
+        }
+    }
+
+    /// Collects the immediate of `instr`, if it is a compare or a wide mov-immediate
+    pub fn harvest(&mut self, decoder: InstDecoder, instr: &Insn) {
+        if let Some((value, width)) = Self::interesting_immediate(decoder, instr) {
+            if value >= MIN_TOKEN_VALUE && self.seen.insert((value, width)) {
+                log::trace!("harvested immediate {value:#x} ({width} bytes)");
+                self.pending
+                    .borrow_mut()
+                    .push(value.to_le_bytes()[..width].to_vec());
+            }
+        }
+    }
+
+    /// The immediate of `instr` with its width in bytes, if it is a compare or a wide mov-immediate
+    #[cfg(target_arch = "x86_64")]
+    #[allow(clippy::cast_sign_loss)]
+    fn interesting_immediate(decoder: InstDecoder, instr: &Insn) -> Option<(u64, usize)> {
+        let instr = frida_to_cs(decoder, instr).ok()?;
+        let is_compare = matches!(instr.opcode(), Opcode::CMP | Opcode::SUB);
+        if !is_compare && instr.opcode() != Opcode::MOV {
+            return None;
+        }
+        (0..instr.operand_count()).find_map(|idx| match instr.operand(idx) {
+            Operand::ImmediateI16 { imm } if is_compare => Some((u64::from(imm as u16), 2)),
+            Operand::ImmediateU16 { imm } if is_compare => Some((u64::from(imm), 2)),
+            Operand::ImmediateI32 { imm } if is_compare => Some((u64::from(imm as u32), 4)),
+            Operand::ImmediateU32 { imm } if is_compare => Some((u64::from(imm), 4)),
+            Operand::ImmediateI64 { imm } => Some((imm as u64, 8)),
+            Operand::ImmediateU64 { imm } => Some((imm, 8)),
+            _ => None,
+        })
+    }
+
+    /// The immediate of `instr` with its width in bytes, if it is a compare or a wide mov-immediate
+    #[cfg(target_arch = "aarch64")]
+    fn interesting_immediate(decoder: InstDecoder, instr: &Insn) -> Option<(u64, usize)> {
+        let instr = *disas_count(&decoder, instr.bytes(), 1).first()?;
+        let width = match instr.operands[0] {
+            Operand::Register(sizecode, _) | Operand::RegisterOrSP(sizecode, _) => {
+                get_reg_size(sizecode) as usize
+            }
+            _ => return None,
+        };
+        // `cmp` and `cmn` are aliases of `subs` and `adds`, with the immediate as the third operand
+        let immediate = match instr.opcode {
+            Opcode::SUBS | Opcode::ADDS => instr.operands[2],
+            Opcode::MOVZ | Opcode::MOVN => instr.operands[1],
+            _ => return None,
+        };
+        match immediate {
+            Operand::Immediate(imm) => Some((u64::from(imm), width)),
+            Operand::ImmShift(imm, shift) => Some((u64::from(imm) << shift, width)),
+            _ => None,
+        }
+    }
+}
+
+impl FridaRuntime for ImmediateTokensRuntime {
+    fn init(
+        &mut self,
+        _gum: &frida_gum::Gum,
+        _ranges: &RangeMap<u64, (u16, String)>,
+        _module_map: &Rc<ModuleMap>,
+    ) {
+    }
+
+    fn deinit(&mut self, _gum: &frida_gum::Gum) {}
+
+    fn pre_exec(&mut self, _input_bytes: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _input_bytes: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Adds the tokens found by an [`ImmediateTokensRuntime`] to the [`Tokens`] of the state after each run, see the
+/// [module-level docs](self).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImmediateTokensObserver {
+    name: Cow<'static, str>,
+    #[serde(skip)]
+    pending: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl<I, S> Observer<I, S> for ImmediateTokensObserver
+where
+    S: HasMetadata,
+{
+    fn post_exec(&mut self, state: &mut S, _input: &I, _exit_kind: &ExitKind) -> Result<(), Error> {
+        let mut pending = self.pending.borrow_mut();
+        if !pending.is_empty() {
+            state
+                .metadata_or_insert_with(Tokens::new)
+                .add_tokens(pending.drain(..));
+        }
+        Ok(())
+    }
+}
+
+impl Named for ImmediateTokensObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
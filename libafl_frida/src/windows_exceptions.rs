@@ -0,0 +1,438 @@
+//! Structured capture of the Windows exceptions crashing the target.
+//!
+//! Instead of only turning an exception into [`ExitKind::Crash`], the handlers installed by
+//! [`crate::windows_hooks::initialize`] record the exception record and the thread context. The
+//! [`WindowsExceptionObserver`] walks the stack of the crash with `dbghelp` and classifies the exception, and the
+//! [`WindowsExceptionFeedback`] attaches the resulting [`WindowsException`] to the objective as metadata:
+//!
+//! ```ignore
+//! let exception_observer = WindowsExceptionObserver::new("windows_exception");
+//! let mut objective = feedback_or_fast!(
+//!     // Exploitable-looking crashes first, in their own corpus
+//!     feedback_and_fast!(
+//!         CrashFeedback::new(),
+//!         WindowsExceptionFeedback::new(&exception_observer).exploitable_only()
+//!     ),
+//!     feedback_and_fast!(CrashFeedback::new(), WindowsExceptionFeedback::new(&exception_observer)),
+//! );
+//! ```
+
+use std::{
+    borrow::Cow,
+    ffi::{c_void, CStr},
+    mem,
+    sync::Mutex,
+};
+
+use libafl::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::Observer,
+    Error, HasMetadata,
+};
+use libafl_bolts::{
+    tuples::{Handle, Handled, MatchNameRef},
+    Named, SerdeAny,
+};
+use serde::{Deserialize, Serialize};
+use windows::{
+    core::PCSTR,
+    Win32::{
+        Foundation::HANDLE,
+        System::{
+            Diagnostics::Debug::{
+                AddrModeFlat, StackWalk64, SymFromAddr, SymFunctionTableAccess64,
+                SymGetModuleBase64, SymInitialize, SymSetOptions, CONTEXT, EXCEPTION_POINTERS,
+                STACKFRAME64, SYMBOL_INFO, SYMOPT_DEFERRED_LOADS, SYMOPT_UNDNAME,
+            },
+            SystemInformation::IMAGE_FILE_MACHINE_AMD64,
+            Threading::{GetCurrentProcess, GetCurrentThread},
+        },
+    },
+};
+
+/// `STATUS_ACCESS_VIOLATION`
+const STATUS_ACCESS_VIOLATION: u32 = 0xC000_0005;
+/// `STATUS_ILLEGAL_INSTRUCTION`
+const STATUS_ILLEGAL_INSTRUCTION: u32 = 0xC000_001D;
+/// `STATUS_INTEGER_DIVIDE_BY_ZERO`
+const STATUS_INTEGER_DIVIDE_BY_ZERO: u32 = 0xC000_0094;
+/// `STATUS_PRIVILEGED_INSTRUCTION`
+const STATUS_PRIVILEGED_INSTRUCTION: u32 = 0xC000_0096;
+/// `STATUS_STACK_OVERFLOW`
+const STATUS_STACK_OVERFLOW: u32 = 0xC000_00FD;
+/// `STATUS_HEAP_CORRUPTION`
+const STATUS_HEAP_CORRUPTION: u32 = 0xC000_0374;
+/// `STATUS_STACK_BUFFER_OVERRUN`, raised by `__fastfail` and the `/GS` checks
+const STATUS_STACK_BUFFER_OVERRUN: u32 = 0xC000_0409;
+
+/// Accesses below this address are considered null pointer dereferences
+const NULL_PAGE_END: u64 = 0x1_0000;
+
+/// The maximum number of frames walked
+const MAX_FRAMES: usize = 64;
+
+/// The maximum length of a symbol name
+const MAX_SYMBOL_LEN: usize = 256;
+
+/// How likely a [`WindowsException`] is to be exploitable, after the rules of `!exploitable`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExceptionClass {
+    /// Writes and executions of bad memory, heap corruptions and stack buffer overruns
+    Exploitable,
+    /// Illegal or privileged instructions, e.g., after a jump to data
+    ProbablyExploitable,
+    /// Null pointer reads, stack overflows, and divisions by zero
+    ProbablyNotExploitable,
+    /// Everything else, e.g., reads of bad memory away from null
+    Unknown,
+}
+
+impl ExceptionClass {
+    /// Classifies the exception `code` raised at `pc`, with the `parameters` of its exception record
+    #[must_use]
+    pub fn classify(code: u32, parameters: &[u64], pc: u64) -> Self {
+        match code {
+            STATUS_ACCESS_VIOLATION => {
+                // The access type is 0 for reads, 1 for writes, and 8 for executions (DEP)
+                let access = parameters.first().copied().unwrap_or_default();
+                let address = parameters.get(1).copied().unwrap_or_default();
+                match access {
+                    1 | 8 => Self::Exploitable,
+                    _ if address == pc => Self::Exploitable,
+                    _ if address < NULL_PAGE_END => Self::ProbablyNotExploitable,
+                    _ => Self::Unknown,
+                }
+            }
+            STATUS_HEAP_CORRUPTION | STATUS_STACK_BUFFER_OVERRUN => Self::Exploitable,
+            STATUS_ILLEGAL_INSTRUCTION | STATUS_PRIVILEGED_INSTRUCTION => Self::ProbablyExploitable,
+            STATUS_STACK_OVERFLOW | STATUS_INTEGER_DIVIDE_BY_ZERO => Self::ProbablyNotExploitable,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// If the class is [`ExceptionClass::Exploitable`] or [`ExceptionClass::ProbablyExploitable`]
+    #[must_use]
+    pub fn is_exploitable(self) -> bool {
+        matches!(self, Self::Exploitable | Self::ProbablyExploitable)
+    }
+}
+
+/// A frame of the stack walk of a [`WindowsException`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackFrame {
+    /// The program counter of the frame
+    pub address: u64,
+    /// The symbol containing the address, with the offset into it, if `dbghelp` found one
+    pub symbol: Option<String>,
+}
+
+/// A Windows exception crashing the target, stored as testcase metadata by the [`WindowsExceptionFeedback`]
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeAny)]
+pub struct WindowsException {
+    /// The exception code, e.g., `0xC0000005` for an access violation
+    pub code: u32,
+    /// The exception flags
+    pub flags: u32,
+    /// The address the exception was raised at
+    pub address: u64,
+    /// The parameters of the exception record, e.g., the access type and address of an access violation
+    pub parameters: Vec<u64>,
+    /// The registers of the faulting thread, by name
+    pub registers: Vec<(String, u64)>,
+    /// The stack walk, starting with the faulting frame
+    pub stack: Vec<StackFrame>,
+    /// How likely the exception is to be exploitable
+    pub class: ExceptionClass,
+}
+
+/// The last crash-like exception, with the context of the faulting thread
+static LAST_EXCEPTION: Mutex<Option<(WindowsException, Box<CONTEXT>)>> = Mutex::new(None);
+
+/// Records the exception, to be picked up by the [`WindowsExceptionObserver`]. Returns `EXCEPTION_CONTINUE_SEARCH`,
+/// so that it can be used as a vectored exception handler in front of the crash handlers.
+///
+/// Only error exceptions are recorded, not the ones raised by software, e.g., C++ exceptions.
+///
+/// # Safety
+/// The pointers must be valid, as given by the system.
+pub unsafe extern "system" fn record_exception(exception_pointers: *mut EXCEPTION_POINTERS) -> i32 {
+    let Some(pointers) = (unsafe { exception_pointers.as_ref() }) else {
+        return 0;
+    };
+    let (Some(record), Some(context)) = (unsafe { pointers.ExceptionRecord.as_ref() }, unsafe {
+        pointers.ContextRecord.as_ref()
+    }) else {
+        return 0;
+    };
+    #[allow(clippy::cast_sign_loss)]
+    let code = record.ExceptionCode.0 as u32;
+    // Severity error, not a customer code
+    if code & 0xE000_0000 != 0xC000_0000 {
+        return 0;
+    }
+
+    let parameters: Vec<u64> = record.ExceptionInformation
+        [..(record.NumberParameters as usize).min(record.ExceptionInformation.len())]
+        .iter()
+        .map(|parameter| *parameter as u64)
+        .collect();
+    let address = record.ExceptionAddress as u64;
+    let exception = WindowsException {
+        code,
+        flags: record.ExceptionFlags,
+        address,
+        class: ExceptionClass::classify(code, &parameters, address),
+        parameters,
+        registers: registers(context),
+        stack: Vec::new(),
+    };
+    if let Ok(mut last) = LAST_EXCEPTION.lock() {
+        *last = Some((exception, Box::new(*context)));
+    }
+    0
+}
+
+/// The general purpose registers of `context`
+fn registers(context: &CONTEXT) -> Vec<(String, u64)> {
+    [
+        ("rax", context.Rax),
+        ("rbx", context.Rbx),
+        ("rcx", context.Rcx),
+        ("rdx", context.Rdx),
+        ("rsi", context.Rsi),
+        ("rdi", context.Rdi),
+        ("rbp", context.Rbp),
+        ("rsp", context.Rsp),
+        ("r8", context.R8),
+        ("r9", context.R9),
+        ("r10", context.R10),
+        ("r11", context.R11),
+        ("r12", context.R12),
+        ("r13", context.R13),
+        ("r14", context.R14),
+        ("r15", context.R15),
+        ("rip", context.Rip),
+        ("eflags", u64::from(context.EFlags)),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value))
+    .collect()
+}
+
+unsafe extern "system" fn function_table_access(process: HANDLE, addr_base: u64) -> *mut c_void {
+    unsafe { SymFunctionTableAccess64(process, addr_base) }
+}
+
+unsafe extern "system" fn module_base(process: HANDLE, address: u64) -> u64 {
+    unsafe { SymGetModuleBase64(process, address) }
+}
+
+/// A [`SYMBOL_INFO`] with room for its name
+#[repr(C)]
+struct SymbolBuffer {
+    info: SYMBOL_INFO,
+    name: [u8; MAX_SYMBOL_LEN],
+}
+
+/// The symbol containing `address`, as `name+offset`
+#[allow(clippy::cast_possible_truncation)]
+fn symbolize(process: HANDLE, address: u64) -> Option<String> {
+    let mut buffer: SymbolBuffer = unsafe { mem::zeroed() };
+    buffer.info.SizeOfStruct = mem::size_of::<SYMBOL_INFO>() as u32;
+    buffer.info.MaxNameLen = MAX_SYMBOL_LEN as u32;
+    let mut displacement = 0;
+    unsafe {
+        SymFromAddr(
+            process,
+            address,
+            Some(&raw mut displacement),
+            &raw mut buffer.info,
+        )
+    }
+    .ok()?;
+    let name = unsafe { CStr::from_ptr(buffer.info.Name.as_ptr()) };
+    Some(format!("{}+{displacement:#x}", name.to_string_lossy()))
+}
+
+/// Walks the stack of the faulting thread from its `context`, still intact while the crash is handled
+fn walk_stack(mut context: CONTEXT) -> Vec<StackFrame> {
+    let process = unsafe { GetCurrentProcess() };
+    let thread = unsafe { GetCurrentThread() };
+    // Initializing twice fails, which is fine
+    unsafe {
+        SymSetOptions(SYMOPT_UNDNAME | SYMOPT_DEFERRED_LOADS);
+        let _ = SymInitialize(process, PCSTR::null(), true);
+    }
+
+    let mut frame = STACKFRAME64::default();
+    frame.AddrPC.Offset = context.Rip;
+    frame.AddrPC.Mode = AddrModeFlat;
+    frame.AddrFrame.Offset = context.Rbp;
+    frame.AddrFrame.Mode = AddrModeFlat;
+    frame.AddrStack.Offset = context.Rsp;
+    frame.AddrStack.Mode = AddrModeFlat;
+
+    let mut stack = Vec::new();
+    while stack.len() < MAX_FRAMES {
+        let walked = unsafe {
+            StackWalk64(
+                u32::from(IMAGE_FILE_MACHINE_AMD64.0),
+                process,
+                thread,
+                &raw mut frame,
+                (&raw mut context).cast(),
+                None,
+                Some(function_table_access),
+                Some(module_base),
+                None,
+            )
+        };
+        if !walked.as_bool() || frame.AddrPC.Offset == 0 {
+            break;
+        }
+        stack.push(StackFrame {
+            address: frame.AddrPC.Offset,
+            symbol: symbolize(process, frame.AddrPC.Offset),
+        });
+    }
+    stack
+}
+
+/// An observer picking up the [`WindowsException`] crashing the target, see the [module-level docs](self)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowsExceptionObserver {
+    name: Cow<'static, str>,
+    exception: Option<WindowsException>,
+}
+
+impl WindowsExceptionObserver {
+    /// Creates a new [`WindowsExceptionObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            exception: None,
+        }
+    }
+
+    /// The exception crashing the last run, if any
+    #[must_use]
+    pub fn exception(&self) -> Option<&WindowsException> {
+        self.exception.as_ref()
+    }
+}
+
+impl<I, S> Observer<I, S> for WindowsExceptionObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.exception = None;
+        if let Ok(mut last) = LAST_EXCEPTION.lock() {
+            *last = None;
+        }
+        Ok(())
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I, exit_kind: &ExitKind) -> Result<(), Error> {
+        let last = LAST_EXCEPTION.lock().ok().and_then(|mut last| last.take());
+        if *exit_kind == ExitKind::Crash {
+            self.exception = last.map(|(mut exception, context)| {
+                exception.stack = walk_stack(*context);
+                exception
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Named for WindowsExceptionObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+/// A feedback attaching the [`WindowsException`] of a crash to the testcase, see the [module-level docs](self).
+///
+/// Interesting if the run crashed with a recorded exception, use it with an AND after a crash feedback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowsExceptionFeedback {
+    observer_handle: Handle<WindowsExceptionObserver>,
+    exploitable_only: bool,
+    exception: Option<WindowsException>,
+}
+
+impl WindowsExceptionFeedback {
+    /// Creates a new [`WindowsExceptionFeedback`]
+    #[must_use]
+    pub fn new(observer: &WindowsExceptionObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            exploitable_only: false,
+            exception: None,
+        }
+    }
+
+    /// Only considers exceptions classified as (probably) exploitable interesting, see
+    /// [`ExceptionClass::is_exploitable`]
+    #[must_use]
+    pub fn exploitable_only(mut self) -> Self {
+        self.exploitable_only = true;
+        self
+    }
+}
+
+impl Named for WindowsExceptionFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl<S> StateInitializer<S> for WindowsExceptionFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for WindowsExceptionFeedback
+where
+    OT: MatchNameRef,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.observer_handle)
+            .expect("A WindowsExceptionFeedback needs a WindowsExceptionObserver");
+        self.exception = observer
+            .exception()
+            .filter(|exception| !self.exploitable_only || exception.class.is_exploitable())
+            .cloned();
+        Ok(self.exception.is_some())
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if let Some(exception) = self.exception.take() {
+            testcase.add_metadata(exception);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.exception = None;
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(self.exception.is_some())
+    }
+}
@@ -6,6 +6,11 @@ use libafl_bolts::os::windows_exceptions::{
     handle_exception, IsProcessorFeaturePresent, UnhandledExceptionFilter, EXCEPTION_POINTERS,
     PROCESSOR_FEATURE_ID,
 };
+#[cfg(target_arch = "x86_64")]
+use windows::Win32::System::Diagnostics::Debug::AddVectoredExceptionHandler;
+
+#[cfg(target_arch = "x86_64")]
+use crate::windows_exceptions::record_exception;
 
 unsafe extern "C" fn is_processor_feature_present_detour(feature: u32) -> bool {
     match feature {
@@ -16,11 +21,20 @@ unsafe extern "C" fn is_processor_feature_present_detour(feature: u32) -> bool {
 unsafe extern "C" fn unhandled_exception_filter_detour(
     exception_pointers: *mut EXCEPTION_POINTERS,
 ) -> i32 {
+    // Exceptions like `__fastfail` bypass the vectored handlers, record them here
+    #[cfg(target_arch = "x86_64")]
+    record_exception(exception_pointers.cast());
     handle_exception(exception_pointers);
     UnhandledExceptionFilter(exception_pointers)
 }
 /// Initialize the hooks
 pub fn initialize(gum: &Gum) {
+    // Record the exception and context of crashes first, before any handler unwinds the stack
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        AddVectoredExceptionHandler(1, Some(record_exception));
+    }
+
     let module = Module::obtain(gum);
     let is_processor_feature_present =
         module.find_export_by_name(Some("kernel32.dll"), "IsProcessorFeaturePresent");
@@ -7,7 +7,7 @@ use std::{
     net::TcpListener,
     os::fd::AsRawFd,
     str::FromStr,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use libafl::{
@@ -97,8 +97,19 @@ where
             return Err(Error::shutting_down());
         }
     }
-    fuzzer.fuzz_loop(stages, executor, state, mgr)?;
-    Ok(())
+    let Some(runs) = options.runs() else {
+        fuzzer.fuzz_loop(stages, executor, state, mgr)?;
+        return Ok(());
+    };
+    // like libFuzzer, stop after `-runs` executions of the target,
+    // so `-runs=0` stops right after loading the corpus
+    while *state.executions() < runs {
+        mgr.maybe_report_progress(state, Duration::from_secs(15))?;
+        fuzzer.fuzz_one(stages, executor, state, mgr)?;
+    }
+    log::info!("Done {runs} runs; shutting down.");
+    mgr.on_shutdown()?;
+    Err(Error::shutting_down())
 }
 
 fn fuzz_single_forking<M>(
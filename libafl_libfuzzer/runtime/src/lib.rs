@@ -166,7 +166,7 @@ macro_rules! fuzz_with {
                 CalibrationStage, GeneralizationStage, IfStage, StdMutationalStage,
                 StdPowerMutationalStage, UnicodeIdentificationStage, TracingStage,
             },
-            state::{HasCorpus, StdState},
+            state::{HasCorpus, HasMaxSize, StdState},
             StdFuzzer,
         };
         use libafl_targets::{CmpLogObserver, LLVMCustomMutator, OomFeedback, OomObserver, CMP_MAP};
@@ -298,6 +298,9 @@ macro_rules! fuzz_with {
                 .expect("Failed to create state")
             });
             state.metadata_map_mut().insert_boxed(grimoire_metadata);
+            if let Some(max_len) = $options.max_len() {
+                state.set_max_size(max_len);
+            }
 
             // Set up a string category analysis stage for unicode mutations
             let unicode_used = $options.unicode();
@@ -467,8 +470,11 @@ macro_rules! fuzz_with {
                     println!("We imported {} inputs from disk.", state.corpus().count());
                 }
                 if state.corpus().count() < 1 {
-                    // Generator of bytearrays of max size 64
-                    let mut generator = RandBytesGenerator::from(RandBytesGenerator::new(nonzero!(64)));
+                    // Generator of bytearrays of max size 64, or max_len if smaller
+                    let max_generated = $options.max_len().map_or(nonzero!(64), |max_len| {
+                        NonZeroUsize::new(max_len.min(64)).unwrap()
+                    });
+                    let mut generator = RandBytesGenerator::from(RandBytesGenerator::new(max_generated));
 
                     // Generate 1024 initial inputs
                     state
@@ -111,6 +111,7 @@ pub struct LibfuzzerOptions {
     unicode: bool,
     forks: Option<usize>,
     dict: Option<Tokens>,
+    max_len: Option<usize>,
     dirs: Vec<PathBuf>,
     ignore_crashes: bool,
     ignore_timeouts: bool,
@@ -121,7 +122,7 @@ pub struct LibfuzzerOptions {
     shrink: bool,
     skip_tracing: bool,
     tui: bool,
-    runs: usize,
+    runs: Option<u64>,
     close_fd_mask: u8,
     unknown: Vec<String>,
 }
@@ -180,6 +181,10 @@ impl LibfuzzerOptions {
         self.dict.as_ref()
     }
 
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
     pub fn dirs(&self) -> &[PathBuf] {
         &self.dirs
     }
@@ -220,7 +225,8 @@ impl LibfuzzerOptions {
         self.tui
     }
 
-    pub fn runs(&self) -> usize {
+    /// The number of runs to stop after, `None` to run forever
+    pub fn runs(&self) -> Option<u64> {
         self.runs
     }
 
@@ -244,6 +250,7 @@ struct LibfuzzerOptionsBuilder<'a> {
     unicode: Option<bool>,
     forks: Option<usize>,
     dict: Option<&'a str>,
+    max_len: Option<usize>,
     dirs: Vec<&'a str>,
     ignore_crashes: Option<bool>,
     ignore_timeouts: Option<bool>,
@@ -255,7 +262,7 @@ struct LibfuzzerOptionsBuilder<'a> {
     shrink: bool,
     skip_tracing: bool,
     tui: bool,
-    runs: usize,
+    runs: Option<u64>,
     close_fd_mask: u8,
     unknown: Vec<&'a str>,
 }
@@ -318,6 +325,11 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 })?);
                         }
                         "dict" => self.dict = Some(value),
+                        "max_len" => {
+                            // 0 lets the fuzzer choose, like libFuzzer
+                            self.max_len = Some(parse_or_bail!(name, value, usize))
+                                .filter(|max_len| *max_len > 0);
+                        }
                         "fork" | "jobs" => {
                             self.forks = Some(parse_or_bail!(name, value, usize));
                         }
@@ -356,7 +368,10 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 }
                             }
                         }
-                        "runs" => self.runs = parse_or_bail!(name, value, usize),
+                        "runs" => {
+                            // -1, or any negative value, runs forever, like libFuzzer
+                            self.runs = u64::try_from(parse_or_bail!(name, value, i64)).ok();
+                        }
                         "close_fd_mask" => self.close_fd_mask = parse_or_bail!(name, value, u8),
                         _ => {
                             self.unknown.push(arg);
@@ -386,6 +401,7 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
             dict: self.dict.map(|path| {
                 Tokens::from_file(path).expect("Couldn't load tokens from specified tokens file")
             }),
+            max_len: self.max_len,
             dirs: self.dirs.into_iter().map(PathBuf::from).collect(),
             ignore_crashes: self.ignore_crashes.unwrap_or_default(),
             ignore_timeouts: self.ignore_timeouts.unwrap_or_default(),
@@ -408,3 +424,23 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LibfuzzerOptions;
+
+    fn runs(args: &[&str]) -> Option<u64> {
+        LibfuzzerOptions::new(["fuzzer"].iter().chain(args).copied())
+            .unwrap()
+            .runs()
+    }
+
+    #[test]
+    fn test_runs() {
+        assert_eq!(runs(&[]), None);
+        assert_eq!(runs(&["-runs=-1"]), None);
+        assert_eq!(runs(&["-runs=0"]), Some(0));
+        assert_eq!(runs(&["-runs=1000"]), Some(1000));
+        assert!(LibfuzzerOptions::new(["fuzzer", "-runs=many"].into_iter()).is_err());
+    }
+}
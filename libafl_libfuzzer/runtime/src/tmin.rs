@@ -28,6 +28,14 @@ use crate::{options::LibfuzzerOptions, CustomMutationStatus};
 type TMinState =
     StdState<BytesInput, InMemoryCorpus<BytesInput>, RomuDuoJrRand, InMemoryCorpus<BytesInput>>;
 
+/// The number of minimization runs, `-runs` if positive, 128 otherwise
+fn tmin_runs(options: &LibfuzzerOptions) -> usize {
+    options
+        .runs()
+        .filter(|runs| *runs > 0)
+        .map_or(128, |runs| usize::try_from(runs).unwrap_or(usize::MAX))
+}
+
 fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
     options: &LibfuzzerOptions,
     harness: extern "C" fn(*const u8, usize) -> c_int,
@@ -80,29 +88,13 @@ fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
     match exit_kind {
         ExitKind::Crash => {
             let factory = CrashFeedback::new();
-            let tmin = StdTMinMutationalStage::new(
-                mutator,
-                factory,
-                if options.runs() == 0 {
-                    128
-                } else {
-                    options.runs()
-                },
-            );
+            let tmin = StdTMinMutationalStage::new(mutator, factory, tmin_runs(options));
             let mut stages = tuple_list!(tmin);
             fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
         }
         ExitKind::Timeout => {
             let factory = TimeoutFeedback::new();
-            let tmin = StdTMinMutationalStage::new(
-                mutator,
-                factory,
-                if options.runs() == 0 {
-                    128
-                } else {
-                    options.runs()
-                },
-            );
+            let tmin = StdTMinMutationalStage::new(mutator, factory, tmin_runs(options));
             let mut stages = tuple_list!(tmin);
             fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
         }
@@ -1,12 +1,16 @@
 use std::{
+    borrow::Cow,
     io::{Read, Seek},
     marker::PhantomData,
     os::fd::AsRawFd,
+    time::Duration,
 };
 
 use libafl::{
+    events::{Event, EventFirer},
     executors::{Executor, ExitKind, HasObservers, HasTimeout},
     inputs::HasTargetBytes,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
     observers::{ObserversTuple, StdOutObserver},
     state::{HasExecutions, State, UsesState},
     Error,
@@ -16,6 +20,14 @@ use libnyx::NyxReturnValue;
 
 use crate::helper::NyxHelper;
 
+/// The default interval, in executions, in which the snapshot stats are sent to the monitor
+const DEFAULT_SNAPSHOT_STATS_INTERVAL: u64 = 1000;
+
+/// The microseconds of a [`Duration`], for the user stats
+fn duration_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
+
 /// executor for nyx standalone mode
 pub struct NyxExecutor<S, OT> {
     /// implement nyx function
@@ -26,6 +38,8 @@ pub struct NyxExecutor<S, OT> {
     // stderr: Option<StdErrObserver>,
     /// observers
     observers: OT,
+    /// the interval in which the snapshot stats are sent to the monitor
+    snapshot_stats_interval: u64,
     /// phantom data to keep generic type <I,S>
     phantom: PhantomData<S>,
 }
@@ -47,7 +61,7 @@ where
 
 impl<EM, S, Z, OT> Executor<EM, Z> for NyxExecutor<S, OT>
 where
-    EM: UsesState<State = S>,
+    EM: EventFirer<State = S>,
     S: State + HasExecutions,
     S::Input: HasTargetBytes,
     Z: UsesState<State = S>,
@@ -57,10 +71,11 @@ where
         &mut self,
         _fuzzer: &mut Z,
         state: &mut Self::State,
-        _mgr: &mut EM,
+        mgr: &mut EM,
         input: &Self::Input,
     ) -> Result<ExitKind, Error> {
         *state.executions_mut() += 1;
+        self.report_snapshot_stats(state, mgr)?;
 
         let bytes = input.target_bytes();
         let buffer = bytes.as_slice();
@@ -90,7 +105,7 @@ where
         self.helper.nyx_process.set_hprintf_fd(hprintf_fd);
 
         // exec will take care of trace_bits, so no need to reset
-        let exit_kind = match self.helper.nyx_process.exec() {
+        let exit_kind = match self.helper.exec() {
             NyxReturnValue::Normal => ExitKind::Ok,
             NyxReturnValue::Crash | NyxReturnValue::Asan => ExitKind::Crash,
             NyxReturnValue::Timeout => ExitKind::Timeout,
@@ -152,6 +167,60 @@ impl<S, OT> HasTimeout for NyxExecutor<S, OT> {
 }
 
 impl<S, OT> NyxExecutor<S, OT> {
+    /// Sends the snapshot stats of the [`NyxHelper`] to the monitor, if due
+    fn report_snapshot_stats<EM>(&self, state: &mut S, mgr: &mut EM) -> Result<(), Error>
+    where
+        EM: EventFirer<State = S>,
+        S: State + HasExecutions,
+    {
+        if !self.helper.incremental_snapshots
+            || self.snapshot_stats_interval == 0
+            || *state.executions() % self.snapshot_stats_interval != 0
+        {
+            return Ok(());
+        }
+
+        let stats = self.helper.snapshot_stats();
+        let values = [
+            (
+                "nyx_snapshots",
+                UserStatsValue::Number(stats.snapshots_created),
+                AggregatorOps::Sum,
+            ),
+            (
+                "nyx_root_refreshes",
+                UserStatsValue::Number(stats.root_refreshes),
+                AggregatorOps::Sum,
+            ),
+            (
+                "nyx_snapshot_us",
+                UserStatsValue::Number(duration_micros(stats.avg_snapshot_time())),
+                AggregatorOps::Avg,
+            ),
+            (
+                "nyx_incremental_exec_us",
+                UserStatsValue::Number(duration_micros(stats.avg_incremental_exec_time())),
+                AggregatorOps::Avg,
+            ),
+            (
+                "nyx_root_exec_us",
+                UserStatsValue::Number(duration_micros(stats.avg_root_exec_time())),
+                AggregatorOps::Avg,
+            ),
+        ];
+        for (name, value, aggregator) in values {
+            mgr.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::Borrowed(name),
+                    value: UserStats::new(value, aggregator),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
     /// Convert `trace_bits` ptr into real trace map
     ///
     /// # Safety
@@ -165,6 +234,7 @@ impl<S, OT> NyxExecutor<S, OT> {
 
 pub struct NyxExecutorBuilder {
     stdout: Option<StdOutObserver>,
+    snapshot_stats_interval: u64,
     // stderr: Option<StdErrObserver>,
 }
 
@@ -179,6 +249,7 @@ impl NyxExecutorBuilder {
     pub fn new() -> Self {
         Self {
             stdout: None,
+            snapshot_stats_interval: DEFAULT_SNAPSHOT_STATS_INTERVAL,
             // stderr: None,
         }
     }
@@ -188,6 +259,13 @@ impl NyxExecutorBuilder {
         self
     }
 
+    /// Set after how many executions the snapshot stats are sent to the monitor, if incremental
+    /// snapshots are enabled. An interval of `0` disables reporting.
+    pub fn snapshot_stats_interval(&mut self, snapshot_stats_interval: u64) -> &mut Self {
+        self.snapshot_stats_interval = snapshot_stats_interval;
+        self
+    }
+
     /*
     pub fn stderr(&mut self, stderr: StdErrObserver) -> &mut Self {
         self.stderr = Some(stderr);
@@ -201,6 +279,7 @@ impl NyxExecutorBuilder {
             stdout: self.stdout.clone(),
             // stderr: self.stderr.clone(),
             observers,
+            snapshot_stats_interval: self.snapshot_stats_interval,
             phantom: PhantomData,
         }
     }
@@ -1,8 +1,13 @@
 /// [`NyxHelper`] is used to wrap `NyxProcess`
-use std::{fmt::Debug, fs::File, path::Path, time::Duration};
+use std::{
+    fmt::Debug,
+    fs::File,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use libafl::Error;
-use libnyx::{NyxConfig, NyxProcess, NyxProcessRole};
+use libnyx::{NyxConfig, NyxProcess, NyxProcessRole, NyxReturnValue};
 
 use crate::settings::NyxSettings;
 
@@ -14,6 +19,69 @@ pub struct NyxHelper {
 
     pub bitmap_size: usize,
     pub bitmap_buffer: *mut u8,
+
+    /// Keep the incremental snapshots of the harness, see [`NyxSettings::incremental_snapshots`]
+    pub incremental_snapshots: bool,
+    /// See [`NyxSettings::root_snapshot_refresh_interval`]
+    pub root_snapshot_refresh_interval: u64,
+
+    snapshot_stats: NyxSnapshotStats,
+    /// If there is an incremental snapshot to restore
+    has_incremental_snapshot: bool,
+    /// If the incremental snapshot is discarded with the next execution
+    discard_pending: bool,
+    /// The executions since the incremental snapshot was created
+    execs_since_snapshot: u64,
+}
+
+/// Statistics on the snapshots of a [`NyxHelper`], to tell if incremental snapshots pay off
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NyxSnapshotStats {
+    /// The number of incremental snapshots created by the harness
+    pub snapshots_created: u64,
+    /// The total time of the executions that created an incremental snapshot
+    pub snapshot_time: Duration,
+    /// The number of executions starting from an incremental snapshot
+    pub incremental_execs: u64,
+    /// The total time of the executions starting from an incremental snapshot
+    pub incremental_exec_time: Duration,
+    /// The number of executions starting from the root snapshot
+    pub root_execs: u64,
+    /// The total time of the executions starting from the root snapshot
+    pub root_exec_time: Duration,
+    /// The number of times the incremental snapshot was discarded for the root snapshot
+    pub root_refreshes: u64,
+}
+
+impl NyxSnapshotStats {
+    /// The average time of an execution creating an incremental snapshot
+    #[must_use]
+    pub fn avg_snapshot_time(&self) -> Duration {
+        avg(self.snapshot_time, self.snapshots_created)
+    }
+
+    /// The average time of an execution restoring the incremental snapshot
+    #[must_use]
+    pub fn avg_incremental_exec_time(&self) -> Duration {
+        avg(self.incremental_exec_time, self.incremental_execs)
+    }
+
+    /// The average time of an execution restoring the root snapshot
+    #[must_use]
+    pub fn avg_root_exec_time(&self) -> Duration {
+        avg(self.root_exec_time, self.root_execs)
+    }
+}
+
+/// The average of `count` samples summing up to `total`
+fn avg(total: Duration, count: u64) -> Duration {
+    if count == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_nanos(
+            u64::try_from(total.as_nanos() / u128::from(count)).unwrap_or(u64::MAX),
+        )
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -53,6 +121,7 @@ impl NyxHelper {
             .map_err(|e| Error::illegal_state(format!("Failed to create Nyx process: {e}")))?;
         nyx_process.option_set_reload_mode(settings.snap_mode);
         nyx_process.option_set_timeout(settings.timeout_secs, settings.timeout_micro_secs);
+        nyx_process.option_set_delete_incremental_snapshot(!settings.incremental_snapshots);
         nyx_process.option_apply();
 
         let path = Path::new(nyx_config.workdir_path())
@@ -77,6 +146,12 @@ impl NyxHelper {
             timeout,
             bitmap_size,
             bitmap_buffer,
+            incremental_snapshots: settings.incremental_snapshots,
+            root_snapshot_refresh_interval: settings.root_snapshot_refresh_interval,
+            snapshot_stats: NyxSnapshotStats::default(),
+            has_incremental_snapshot: false,
+            discard_pending: false,
+            execs_since_snapshot: 0,
         })
     }
 
@@ -85,4 +160,60 @@ impl NyxHelper {
         self.nyx_process.option_set_timeout(secs, micro_secs);
         self.nyx_process.option_apply();
     }
+
+    /// Discard the incremental snapshot with the next execution, so the harness
+    /// starts from the root snapshot again.
+    pub fn discard_incremental_snapshot(&mut self) {
+        if self.has_incremental_snapshot && !self.discard_pending {
+            self.nyx_process
+                .option_set_delete_incremental_snapshot(true);
+            self.nyx_process.option_apply();
+            self.discard_pending = true;
+        }
+    }
+
+    /// The statistics on the snapshots so far
+    #[must_use]
+    pub fn snapshot_stats(&self) -> &NyxSnapshotStats {
+        &self.snapshot_stats
+    }
+
+    /// Run one execution, keeping track of the incremental snapshot.
+    pub fn exec(&mut self) -> NyxReturnValue {
+        if self.root_snapshot_refresh_interval != 0
+            && self.execs_since_snapshot >= self.root_snapshot_refresh_interval
+        {
+            self.discard_incremental_snapshot();
+        }
+        let from_incremental = self.has_incremental_snapshot && !self.discard_pending;
+
+        let start = Instant::now();
+        let ret = self.nyx_process.exec();
+        let elapsed = start.elapsed();
+
+        if self.discard_pending {
+            self.nyx_process
+                .option_set_delete_incremental_snapshot(false);
+            self.nyx_process.option_apply();
+            self.discard_pending = false;
+            self.has_incremental_snapshot = false;
+            self.snapshot_stats.root_refreshes += 1;
+        }
+
+        let stats = &mut self.snapshot_stats;
+        if self.incremental_snapshots && self.nyx_process.aux_tmp_snapshot_created() {
+            self.has_incremental_snapshot = true;
+            self.execs_since_snapshot = 0;
+            stats.snapshots_created += 1;
+            stats.snapshot_time += elapsed;
+        } else if from_incremental {
+            self.execs_since_snapshot += 1;
+            stats.incremental_execs += 1;
+            stats.incremental_exec_time += elapsed;
+        } else {
+            stats.root_execs += 1;
+            stats.root_exec_time += elapsed;
+        }
+        ret
+    }
 }
@@ -4,6 +4,8 @@ const DEFAULT_INPUT_BUFFER_SIZE: usize = 1024 * 1024;
 const DEFAULT_TIMEOUT_SECS: u8 = 2;
 const DEFAULT_TIMEOUT_MICRO_SECS: u32 = 0;
 const DEFAULT_SNAP_MODE: bool = true;
+const DEFAULT_INCREMENTAL_SNAPSHOTS: bool = false;
+const DEFAULT_ROOT_SNAPSHOT_REFRESH_INTERVAL: u64 = 0;
 
 #[derive(Debug, Clone, Copy, TypedBuilder)]
 pub struct NyxSettings {
@@ -44,4 +46,19 @@ pub struct NyxSettings {
     /// `timeout_secs`.
     #[builder(default = DEFAULT_TIMEOUT_MICRO_SECS)]
     pub timeout_micro_secs: u32,
+
+    /// Keep the incremental snapshot created by the harness, and restore it instead of the
+    /// root snapshot for the following executions.
+    ///
+    /// The harness sets the snapshot point with the `HYPERCALL_KAFL_CREATE_TMP_SNAPSHOT`
+    /// hypercall of `nyx_api.h`, e.g., after it consumed a common prefix of the input.
+    #[builder(default = DEFAULT_INCREMENTAL_SNAPSHOTS)]
+    pub incremental_snapshots: bool,
+
+    /// Discard the incremental snapshot every `root_snapshot_refresh_interval` executions,
+    /// so the harness starts from the root snapshot again and creates a fresh incremental snapshot.
+    ///
+    /// Default is `0`, which keeps the incremental snapshot for good.
+    #[builder(default = DEFAULT_ROOT_SNAPSHOT_REFRESH_INTERVAL)]
+    pub root_snapshot_refresh_interval: u64,
 }
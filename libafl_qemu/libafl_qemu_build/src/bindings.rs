@@ -60,6 +60,7 @@ const WRAPPER_HEADER: &str = r#"
 #include "sysemu/tcg.h"
 #include "sysemu/runstate.h"
 #include "sysemu/replay.h"
+#include "qapi/qapi-commands-misc.h"
 
 #include "libafl/system.h"
 #include "libafl/qemu_snapshot.h"
@@ -164,6 +165,12 @@ pub fn generate(
         .allowlist_function("cpu_memory_rw_debug")
         .allowlist_function("cpu_physical_memory_rw")
         .allowlist_function("cpu_reset")
+        .allowlist_function("cpu_interrupt")
+        .allowlist_function("cpu_reset_interrupt")
+        .allowlist_function("qmp_human_monitor_command")
+        .allowlist_function("error_get_pretty")
+        .allowlist_function("error_free")
+        .allowlist_function("g_free")
         .allowlist_function("cpu_synchronize_state")
         .allowlist_function("cpu_get_phys_page_attrs_debug")
         .allowlist_function("tlb_plugin_lookup")
@@ -3424,6 +3424,29 @@ extern "C" {
     #[doc = " cpu_reset:\n @cpu: The CPU whose state is to be reset."]
     pub fn cpu_reset(cpu: *mut CPUState);
 }
+extern "C" {
+    pub fn cpu_interrupt(cpu: *mut CPUState, mask: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn cpu_reset_interrupt(cpu: *mut CPUState, mask: ::std::os::raw::c_int);
+}
+extern "C" {
+    pub fn qmp_human_monitor_command(
+        command_line: *const ::std::os::raw::c_char,
+        has_cpu_index: bool,
+        cpu_index: i64,
+        errp: *mut *mut Error,
+    ) -> *mut ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn error_get_pretty(err: *const Error) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn error_free(err: *mut Error);
+}
+extern "C" {
+    pub fn g_free(mem: gpointer);
+}
 pub type target_long = i64;
 pub type target_ulong = u64;
 #[doc = " Property:\n @set_default: true if the default value should be set from @defval,\n    in which case @info->set_default_value must not be NULL\n    (if false then no default value is set by the property system\n     and the field retains whatever value it was given by instance_init).\n @defval: default value for the property. This is used only if @set_default\n     is true."]
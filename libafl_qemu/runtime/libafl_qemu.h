@@ -20,6 +20,7 @@ enum LibaflQemuEndStatus {
   LIBAFL_QEMU_END_UNKNOWN = 0,
   LIBAFL_QEMU_END_OK = 1,
   LIBAFL_QEMU_END_CRASH = 2,
+  LIBAFL_QEMU_END_TIMEOUT = 3,
 };
 
 libafl_word libafl_qemu_start_virt(void *buf_vaddr, libafl_word max_len);
@@ -50,6 +51,15 @@ void libafl_qemu_trace_vaddr_range(libafl_word start, libafl_word end);
 
 void libafl_qemu_trace_vaddr_size(libafl_word start, libafl_word size);
 
+void libafl_qemu_asan_poison(void *addr, libafl_word size);
+
+void libafl_qemu_asan_unpoison(void *addr, libafl_word size);
+
+// Sends at most LIBAFL_QEMU_MODULE_DATA_MAX_SIZE bytes to the modules of the
+// fuzzer, which recognize their data by its tag.
+void libafl_qemu_send_module_data(libafl_word tag, const void *buf,
+                                  libafl_word len);
+
 #include "libafl_qemu_impl.h"
 
 #endif
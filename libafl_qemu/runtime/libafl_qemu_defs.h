@@ -19,6 +19,8 @@
 
 #define LIBAFL_QEMU_HDR_VERSION_NUMBER 0111  // TODO: find a nice way to set it.
 
+#define LIBAFL_QEMU_MODULE_DATA_MAX_SIZE 0x10000
+
 typedef enum LibaflQemuCommand {
   LIBAFL_QEMU_COMMAND_START_VIRT = 0,
   LIBAFL_QEMU_COMMAND_START_PHYS = 1,
@@ -32,6 +34,9 @@ typedef enum LibaflQemuCommand {
   LIBAFL_QEMU_COMMAND_INTERNAL_ERROR = 9,
   LIBAFL_QEMU_COMMAND_LQPRINTF = 10,
   LIBAFL_QEMU_COMMAND_TEST = 11,
+  LIBAFL_QEMU_COMMAND_ASAN_POISON = 12,
+  LIBAFL_QEMU_COMMAND_ASAN_UNPOISON = 13,
+  LIBAFL_QEMU_COMMAND_MODULE_DATA = 14,
 } LibaflExit;
 
 #endif
@@ -81,4 +81,23 @@ noinline void libafl_qemu_trace_vaddr_size(libafl_word start,
   libafl_qemu_trace_vaddr_range(start, start + size);
 }
 
+noinline void libafl_qemu_asan_poison(void *addr, libafl_word size) {
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_ASAN_POISON, (libafl_word)addr,
+                          size);
+}
+
+noinline void libafl_qemu_asan_unpoison(void *addr, libafl_word size) {
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_ASAN_UNPOISON, (libafl_word)addr,
+                          size);
+}
+
+noinline void libafl_qemu_send_module_data(libafl_word tag, const void *buf,
+                                           libafl_word len) {
+  // Only two arguments fit in registers on all architectures, the buffer is
+  // described in memory.
+  volatile libafl_word desc[2] = {(libafl_word)buf, len};
+  _libafl_sync_exit_call2(LIBAFL_QEMU_COMMAND_MODULE_DATA, tag,
+                          (libafl_word)desc);
+}
+
 #endif
\ No newline at end of file
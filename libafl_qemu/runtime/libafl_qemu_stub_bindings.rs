@@ -6,6 +6,7 @@ pub const LIBAFL_SYNC_EXIT_OPCODE: u32 = 1727150607;
 pub const LIBAFL_BACKDOOR_OPCODE: u32 = 1156725263;
 pub const LIBAFL_QEMU_TEST_VALUE: u32 = 3405691582;
 pub const LIBAFL_QEMU_HDR_VERSION_NUMBER: u32 = 73;
+pub const LIBAFL_QEMU_MODULE_DATA_MAX_SIZE: u32 = 65536;
 pub const _STDIO_H: u32 = 1;
 pub const _FEATURES_H: u32 = 1;
 pub const _DEFAULT_SOURCE: u32 = 1;
@@ -165,6 +166,12 @@ pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_INTERNAL_ERROR: LibaflQemuComman
     LibaflQemuCommand(9);
 pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_LQPRINTF: LibaflQemuCommand = LibaflQemuCommand(10);
 pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_TEST: LibaflQemuCommand = LibaflQemuCommand(11);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASAN_POISON: LibaflQemuCommand =
+    LibaflQemuCommand(12);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASAN_UNPOISON: LibaflQemuCommand =
+    LibaflQemuCommand(13);
+pub const LibaflQemuCommand_LIBAFL_QEMU_COMMAND_MODULE_DATA: LibaflQemuCommand =
+    LibaflQemuCommand(14);
 impl ::std::ops::BitOr<LibaflQemuCommand> for LibaflQemuCommand {
     type Output = Self;
     #[inline]
@@ -1083,6 +1090,8 @@ extern "C" {
 pub const LibaflQemuEndStatus_LIBAFL_QEMU_END_UNKNOWN: LibaflQemuEndStatus = LibaflQemuEndStatus(0);
 pub const LibaflQemuEndStatus_LIBAFL_QEMU_END_OK: LibaflQemuEndStatus = LibaflQemuEndStatus(1);
 pub const LibaflQemuEndStatus_LIBAFL_QEMU_END_CRASH: LibaflQemuEndStatus = LibaflQemuEndStatus(2);
+pub const LibaflQemuEndStatus_LIBAFL_QEMU_END_TIMEOUT: LibaflQemuEndStatus =
+    LibaflQemuEndStatus(3);
 impl ::std::ops::BitOr<LibaflQemuEndStatus> for LibaflQemuEndStatus {
     type Output = Self;
     #[inline]
@@ -8,10 +8,15 @@ use std::{
     },
 };
 
-use libafl::inputs::UsesInput;
+use hashbrown::HashMap;
+use libafl::{executors::ExitKind, inputs::UsesInput, Error};
 use libafl_qemu_sys::GuestAddr;
 
-use crate::{command::CommandManager, Qemu};
+use crate::{
+    command::{CommandManager, EndCommand, InputCommand, StartCommand},
+    elf::EasyElf,
+    Emulator, Qemu, QemuMemoryChunk,
+};
 
 #[repr(transparent)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -189,3 +194,269 @@ where
         self.cmd.clone()
     }
 }
+
+/// Where a breakpoint of a [`BreakpointHarness`] is set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointLocation {
+    /// A guest address
+    Addr(GuestAddr),
+    /// A symbol of the target, resolved when the harness is installed
+    Symbol(String),
+}
+
+impl From<GuestAddr> for BreakpointLocation {
+    fn from(addr: GuestAddr) -> Self {
+        Self::Addr(addr)
+    }
+}
+
+impl From<&str> for BreakpointLocation {
+    fn from(symbol: &str) -> Self {
+        Self::Symbol(symbol.to_string())
+    }
+}
+
+impl From<String> for BreakpointLocation {
+    fn from(symbol: String) -> Self {
+        Self::Symbol(symbol)
+    }
+}
+
+/// What happens when the target reaches a breakpoint of a [`BreakpointHarness`]
+#[derive(Debug, Clone)]
+enum BreakpointAction<C> {
+    /// Snapshot the target and write the input, once
+    Start(QemuMemoryChunk),
+    /// Write the input
+    Input(QemuMemoryChunk),
+    /// Restore the snapshot and end the run
+    End(ExitKind),
+    /// Run a custom command
+    Command(C, bool),
+    /// Return to the harness, with the breakpoint as exit reason
+    Stop,
+}
+
+/// The named breakpoints of a harness, declared with their actions and set on the [`Emulator`] at once.
+///
+/// This replaces the breakpoint bookkeeping of QEMU harnesses: locations may be symbols of the target,
+/// and the installed breakpoints are retrieved by name, see [`HarnessBreakpoints`].
+///
+/// ```ignore
+/// let breakpoints = BreakpointHarness::new()
+///     .start("start", "main", QemuMemoryChunk::phys(input_addr, max_size, None))
+///     .end("done", "BREAKPOINT", ExitKind::Ok)
+///     .end("panic", "panic_handler", ExitKind::Crash)
+///     .install_with_elf(&emu, &elf, 0)?;
+/// ```
+pub struct BreakpointHarness<CM, ED, ET, S, SM>
+where
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput,
+{
+    breakpoints: Vec<(String, BreakpointLocation, BreakpointAction<CM::Commands>)>,
+}
+
+impl<CM, ED, ET, S, SM> Debug for BreakpointHarness<CM, ED, ET, S, SM>
+where
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(
+                self.breakpoints
+                    .iter()
+                    .map(|(name, location, _)| (name, location)),
+            )
+            .finish()
+    }
+}
+
+impl<CM, ED, ET, S, SM> Default for BreakpointHarness<CM, ED, ET, S, SM>
+where
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput,
+{
+    fn default() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+        }
+    }
+}
+
+impl<CM, ED, ET, S, SM> BreakpointHarness<CM, ED, ET, S, SM>
+where
+    CM: CommandManager<ED, ET, S, SM>,
+    CM::Commands: From<StartCommand> + From<InputCommand> + From<EndCommand>,
+    S: UsesInput,
+{
+    /// Creates a new [`BreakpointHarness`], without breakpoints
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with<L>(mut self, name: &str, location: L, action: BreakpointAction<CM::Commands>) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.breakpoints
+            .push((name.to_string(), location.into(), action));
+        self
+    }
+
+    /// At `location`, snapshots the target and writes the input to `input_location`, the first time only
+    #[must_use]
+    pub fn start<L>(self, name: &str, location: L, input_location: QemuMemoryChunk) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.with(name, location, BreakpointAction::Start(input_location))
+    }
+
+    /// At `location`, writes the input to `input_location`
+    #[must_use]
+    pub fn input<L>(self, name: &str, location: L, input_location: QemuMemoryChunk) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.with(name, location, BreakpointAction::Input(input_location))
+    }
+
+    /// At `location`, restores the snapshot and ends the run with `exit_kind`
+    #[must_use]
+    pub fn end<L>(self, name: &str, location: L, exit_kind: ExitKind) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.with(name, location, BreakpointAction::End(exit_kind))
+    }
+
+    /// At `location`, runs `cmd`, and disables the breakpoint if `disable_on_trigger` is set
+    #[must_use]
+    pub fn command<L>(
+        self,
+        name: &str,
+        location: L,
+        cmd: CM::Commands,
+        disable_on_trigger: bool,
+    ) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.with(
+            name,
+            location,
+            BreakpointAction::Command(cmd, disable_on_trigger),
+        )
+    }
+
+    /// At `location`, returns to the harness, with the breakpoint as exit reason
+    #[must_use]
+    pub fn stop<L>(self, name: &str, location: L) -> Self
+    where
+        L: Into<BreakpointLocation>,
+    {
+        self.with(name, location, BreakpointAction::Stop)
+    }
+
+    /// Sets the breakpoints on the `emu`. Fails if a location is a symbol, see [`Self::install_with_elf`].
+    pub fn install(self, emu: &Emulator<CM, ED, ET, S, SM>) -> Result<HarnessBreakpoints, Error> {
+        self.install_resolved(emu, |symbol| {
+            Err(Error::illegal_argument(format!(
+                "Can not resolve the symbol {symbol} without an ELF"
+            )))
+        })
+    }
+
+    /// Sets the breakpoints on the `emu`, resolving symbols in the `elf` loaded at `load_addr`
+    pub fn install_with_elf(
+        self,
+        emu: &Emulator<CM, ED, ET, S, SM>,
+        elf: &EasyElf,
+        load_addr: GuestAddr,
+    ) -> Result<HarnessBreakpoints, Error> {
+        self.install_resolved(emu, |symbol| {
+            elf.resolve_symbol(symbol, load_addr)
+                .ok_or_else(|| Error::not_found(format!("Symbol {symbol} not found")))
+        })
+    }
+
+    fn install_resolved<F>(
+        self,
+        emu: &Emulator<CM, ED, ET, S, SM>,
+        resolve: F,
+    ) -> Result<HarnessBreakpoints, Error>
+    where
+        F: Fn(&str) -> Result<GuestAddr, Error>,
+    {
+        // Resolve all the locations first, to not leave a half-installed harness behind
+        let resolved = self
+            .breakpoints
+            .into_iter()
+            .map(|(name, location, action)| {
+                let addr = match &location {
+                    BreakpointLocation::Addr(addr) => *addr,
+                    BreakpointLocation::Symbol(symbol) => resolve(symbol)?,
+                };
+                Ok((name, addr, action))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let qemu = emu.qemu();
+        let mut installed = HarnessBreakpoints::default();
+        for (name, addr, action) in resolved {
+            let bp = match action {
+                BreakpointAction::Start(input_location) => {
+                    Breakpoint::with_command(addr, StartCommand::new(input_location).into(), true)
+                }
+                BreakpointAction::Input(input_location) => Breakpoint::with_command(
+                    addr,
+                    InputCommand::new(input_location, qemu.cpu_from_index(0)).into(),
+                    false,
+                ),
+                BreakpointAction::End(exit_kind) => {
+                    Breakpoint::with_command(addr, EndCommand::new(Some(exit_kind)).into(), false)
+                }
+                BreakpointAction::Command(cmd, disable_on_trigger) => {
+                    Breakpoint::with_command(addr, cmd, disable_on_trigger)
+                }
+                BreakpointAction::Stop => Breakpoint::without_command(addr, false),
+            };
+            log::info!("Breakpoint {name} @ {addr:#x}");
+            let id = emu.add_breakpoint(bp, true);
+            installed.breakpoints.insert(name, (id, addr));
+        }
+        Ok(installed)
+    }
+}
+
+/// The breakpoints set by a [`BreakpointHarness`], by name
+#[derive(Debug, Clone, Default)]
+pub struct HarnessBreakpoints {
+    breakpoints: HashMap<String, (BreakpointId, GuestAddr)>,
+}
+
+impl HarnessBreakpoints {
+    /// The id of the breakpoint `name`, e.g., to [remove](Emulator::remove_breakpoint) it
+    #[must_use]
+    pub fn id(&self, name: &str) -> Option<BreakpointId> {
+        self.breakpoints.get(name).map(|(id, _)| *id)
+    }
+
+    /// The address of the breakpoint `name`
+    #[must_use]
+    pub fn addr(&self, name: &str) -> Option<GuestAddr> {
+        self.breakpoints.get(name).map(|(_, addr)| *addr)
+    }
+
+    /// The name of the breakpoint with the given id, e.g., of the breakpoint that stopped the target
+    #[must_use]
+    pub fn name(&self, id: BreakpointId) -> Option<&str> {
+        self.breakpoints
+            .iter()
+            .find(|(_, (bp_id, _))| *bp_id == id)
+            .map(|(name, _)| name.as_str())
+    }
+}
@@ -10,6 +10,8 @@ use libafl::{
     executors::ExitKind,
     inputs::{HasTargetBytes, UsesInput},
 };
+#[cfg(feature = "systemmode")]
+use libafl_bolts::tuples::MatchFirstType;
 use libafl_bolts::AsSlice;
 use libafl_qemu_sys::GuestAddr;
 #[cfg(feature = "systemmode")]
@@ -20,8 +22,9 @@ use paste::paste;
 
 use crate::{
     command::parser::{
-        EndCommandParser, InputPhysCommandParser, InputVirtCommandParser, LoadCommandParser,
-        LqprintfCommandParser, NativeCommandParser, SaveCommandParser, StartPhysCommandParser,
+        AsanPoisonCommandParser, AsanUnpoisonCommandParser, EndCommandParser,
+        InputPhysCommandParser, InputVirtCommandParser, LoadCommandParser, LqprintfCommandParser,
+        ModuleDataCommandParser, NativeCommandParser, SaveCommandParser, StartPhysCommandParser,
         StartVirtCommandParser, TestCommandParser, VaddrFilterAllowRangeCommandParser,
         VersionCommandParser,
     },
@@ -192,7 +195,9 @@ define_std_command_manager!(
         VersionCommand,
         AddressAllowCommand,
         LqprintfCommand,
-        TestCommand
+        TestCommand,
+        AsanPoisonCommand,
+        ModuleDataCommand
     ],
     [
         StartPhysCommandParser,
@@ -205,7 +210,10 @@ define_std_command_manager!(
         VersionCommandParser,
         VaddrFilterAllowRangeCommandParser,
         LqprintfCommandParser,
-        TestCommandParser
+        TestCommandParser,
+        AsanPoisonCommandParser,
+        AsanUnpoisonCommandParser,
+        ModuleDataCommandParser
     ]
 );
 
@@ -215,8 +223,12 @@ pub enum NativeExitKind {
     Unknown = bindings::LibaflQemuEndStatus_LIBAFL_QEMU_END_UNKNOWN.0 as u64, // Should not be used
     Ok = bindings::LibaflQemuEndStatus_LIBAFL_QEMU_END_OK.0 as u64,           // Normal exit
     Crash = bindings::LibaflQemuEndStatus_LIBAFL_QEMU_END_CRASH.0 as u64, // Crash reported in the VM
+    Timeout = bindings::LibaflQemuEndStatus_LIBAFL_QEMU_END_TIMEOUT.0 as u64, // Timeout reported in the VM
 }
 
+/// The maximum size of the data sent by the guest to the modules
+pub const MODULE_DATA_MAX_SIZE: usize = bindings::LIBAFL_QEMU_MODULE_DATA_MAX_SIZE as usize;
+
 pub trait IsCommand<CM, ED, ET, S, SM>: Clone + Debug
 where
     CM: CommandManager<ED, ET, S, SM>,
@@ -625,6 +637,89 @@ where
     }
 }
 
+/// (Un)poisons guest memory in the [`crate::modules::AsanSystemModule`], if there is one
+#[derive(Debug, Clone)]
+pub struct AsanPoisonCommand {
+    addr: GuestAddr,
+    size: usize,
+    poison: bool,
+}
+impl<CM, ED, ET, S, SM> IsCommand<CM, ED, ET, S, SM> for AsanPoisonCommand
+where
+    ET: EmulatorModuleTuple<S>,
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput + Unpin,
+{
+    fn usable_at_runtime(&self) -> bool {
+        true
+    }
+
+    #[cfg_attr(feature = "usermode", allow(unused_variables))]
+    fn run(
+        &self,
+        emu: &mut Emulator<CM, ED, ET, S, SM>,
+        _state: &mut S,
+        _input: &S::Input,
+        _ret_reg: Option<Regs>,
+    ) -> Result<Option<EmulatorDriverResult<CM, ED, ET, S, SM>>, EmulatorDriverError> {
+        #[cfg(feature = "systemmode")]
+        if let Some(asan) = emu
+            .modules_mut()
+            .modules_mut()
+            .match_first_type_mut::<crate::modules::AsanSystemModule>()
+        {
+            if self.poison {
+                asan.poison(self.addr, self.size);
+            } else {
+                asan.unpoison(self.addr, self.size);
+            }
+            return Ok(None);
+        }
+
+        log::warn!("ASAN poisoning requested, but there is no ASAN module to handle it");
+        Ok(None)
+    }
+}
+
+/// Sends data from the guest to the modules, see [`crate::modules::EmulatorModule::on_guest_data`]
+#[derive(Debug, Clone)]
+pub struct ModuleDataCommand {
+    tag: GuestReg,
+    data: Vec<u8>,
+}
+impl<CM, ED, ET, S, SM> IsCommand<CM, ED, ET, S, SM> for ModuleDataCommand
+where
+    ET: EmulatorModuleTuple<S>,
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput + Unpin,
+{
+    fn usable_at_runtime(&self) -> bool {
+        true
+    }
+
+    fn run(
+        &self,
+        emu: &mut Emulator<CM, ED, ET, S, SM>,
+        _state: &mut S,
+        _input: &S::Input,
+        _ret_reg: Option<Regs>,
+    ) -> Result<Option<EmulatorDriverResult<CM, ED, ET, S, SM>>, EmulatorDriverError> {
+        let qemu = emu.qemu();
+        if !emu
+            .modules_mut()
+            .modules_mut()
+            .on_guest_data_all(qemu, self.tag, &self.data)
+        {
+            log::warn!(
+                "No module handled the guest data with tag {:#x} ({} bytes)",
+                self.tag,
+                self.data.len()
+            );
+        }
+        Ok(None)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LqprintfCommand {
     content: String,
@@ -714,6 +809,20 @@ impl AddressAllowCommand {
     }
 }
 
+impl AsanPoisonCommand {
+    #[must_use]
+    pub fn new(addr: GuestAddr, size: usize, poison: bool) -> Self {
+        Self { addr, size, poison }
+    }
+}
+
+impl ModuleDataCommand {
+    #[must_use]
+    pub fn new(tag: GuestReg, data: Vec<u8>) -> Self {
+        Self { tag, data }
+    }
+}
+
 impl Display for SaveCommand {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Save VM")
@@ -1,4 +1,4 @@
-use std::{ffi::CStr, sync::OnceLock};
+use std::{ffi::CStr, mem::size_of, sync::OnceLock};
 
 use enum_map::{enum_map, EnumMap};
 use libafl::{
@@ -11,9 +11,10 @@ use libc::c_uint;
 
 use crate::{
     command::{
-        bindings, AddressAllowCommand, CommandError, CommandManager, EndCommand, InputCommand,
-        IsCommand, LoadCommand, LqprintfCommand, NativeExitKind, SaveCommand, StartCommand,
-        StdCommandManager, TestCommand, VersionCommand,
+        bindings, AddressAllowCommand, AsanPoisonCommand, CommandError, CommandManager, EndCommand,
+        InputCommand, IsCommand, LoadCommand, LqprintfCommand, ModuleDataCommand, NativeExitKind,
+        SaveCommand, StartCommand, StdCommandManager, TestCommand, VersionCommand,
+        MODULE_DATA_MAX_SIZE,
     },
     modules::EmulatorModuleTuple,
     sync_exit::ExitArgs,
@@ -214,7 +215,8 @@ where
                 enum_map! {
                     NativeExitKind::Unknown => None,
                     NativeExitKind::Ok      => Some(ExitKind::Ok),
-                    NativeExitKind::Crash   => Some(ExitKind::Crash)
+                    NativeExitKind::Crash   => Some(ExitKind::Crash),
+                    NativeExitKind::Timeout => Some(ExitKind::Timeout)
                 }
             })[k]
         });
@@ -329,3 +331,99 @@ where
         ))
     }
 }
+
+pub struct AsanPoisonCommandParser;
+impl<CM, ED, ET, S, SM> NativeCommandParser<CM, ED, ET, S, SM> for AsanPoisonCommandParser
+where
+    ET: EmulatorModuleTuple<S>,
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput + Unpin,
+{
+    type OutputCommand = AsanPoisonCommand;
+    const COMMAND_ID: c_uint = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASAN_POISON.0;
+
+    fn parse(
+        qemu: Qemu,
+        arch_regs_map: &'static EnumMap<ExitArgs, Regs>,
+    ) -> Result<Self::OutputCommand, CommandError> {
+        let addr: GuestAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+        let size: GuestReg = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+
+        Ok(AsanPoisonCommand::new(addr, size.try_into().unwrap(), true))
+    }
+}
+
+pub struct AsanUnpoisonCommandParser;
+impl<CM, ED, ET, S, SM> NativeCommandParser<CM, ED, ET, S, SM> for AsanUnpoisonCommandParser
+where
+    ET: EmulatorModuleTuple<S>,
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput + Unpin,
+{
+    type OutputCommand = AsanPoisonCommand;
+    const COMMAND_ID: c_uint = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_ASAN_UNPOISON.0;
+
+    fn parse(
+        qemu: Qemu,
+        arch_regs_map: &'static EnumMap<ExitArgs, Regs>,
+    ) -> Result<Self::OutputCommand, CommandError> {
+        let addr: GuestAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+        let size: GuestReg = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+
+        Ok(AsanPoisonCommand::new(
+            addr,
+            size.try_into().unwrap(),
+            false,
+        ))
+    }
+}
+
+pub struct ModuleDataCommandParser;
+impl<CM, ED, ET, S, SM> NativeCommandParser<CM, ED, ET, S, SM> for ModuleDataCommandParser
+where
+    ET: EmulatorModuleTuple<S>,
+    CM: CommandManager<ED, ET, S, SM>,
+    S: UsesInput + Unpin,
+{
+    type OutputCommand = ModuleDataCommand;
+    const COMMAND_ID: c_uint = bindings::LibaflQemuCommand_LIBAFL_QEMU_COMMAND_MODULE_DATA.0;
+
+    fn parse(
+        qemu: Qemu,
+        arch_regs_map: &'static EnumMap<ExitArgs, Regs>,
+    ) -> Result<Self::OutputCommand, CommandError> {
+        let tag: GuestReg = qemu.read_reg(arch_regs_map[ExitArgs::Arg1])?;
+        let desc_addr: GuestAddr = qemu.read_reg(arch_regs_map[ExitArgs::Arg2])?;
+        let cpu = qemu.current_cpu().unwrap();
+
+        // The descriptor of the data: its address and its size, in guest words
+        let mut desc = [0u8; 2 * size_of::<GuestReg>()];
+        let desc_chunk =
+            QemuMemoryChunk::virt(desc_addr as GuestVirtAddr, desc.len() as GuestReg, cpu);
+        desc_chunk.read(qemu, &mut desc)?;
+        let (buf_addr, size) = desc.split_at(size_of::<GuestReg>());
+        #[cfg(feature = "be")]
+        let (buf_addr, size) = (
+            GuestReg::from_be_bytes(buf_addr.try_into().unwrap()),
+            GuestReg::from_be_bytes(size.try_into().unwrap()),
+        );
+        #[cfg(not(feature = "be"))]
+        let (buf_addr, size) = (
+            GuestReg::from_le_bytes(buf_addr.try_into().unwrap()),
+            GuestReg::from_le_bytes(size.try_into().unwrap()),
+        );
+
+        let size = usize::try_from(size).unwrap_or(usize::MAX);
+        if size > MODULE_DATA_MAX_SIZE {
+            log::warn!(
+                "Truncating the guest data with tag {tag:#x} from {size} to {MODULE_DATA_MAX_SIZE} bytes"
+            );
+        }
+        let mut data = vec![0; size.min(MODULE_DATA_MAX_SIZE)];
+        let data_chunk =
+            QemuMemoryChunk::virt(buf_addr as GuestVirtAddr, data.len() as GuestReg, cpu);
+        data_chunk.read(qemu, &mut data)?;
+
+        Ok(ModuleDataCommand::new(tag, data))
+    }
+}
@@ -2,7 +2,10 @@
 
 use std::{fs::File, io::Read, ops::Range, path::Path, str};
 
-use goblin::elf::{header::ET_DYN, Elf};
+use goblin::{
+    elf::{header::ET_DYN, section_header::SHN_UNDEF, Elf, Symtab},
+    strtab::Strtab,
+};
 use libafl::Error;
 use libafl_qemu_sys::GuestAddr;
 
@@ -74,6 +77,22 @@ impl<'a> EasyElf<'a> {
         None
     }
 
+    /// The raw value of the symbol `name` defined by this ELF, looked up in the symbol table first, then in the
+    /// dynamic symbol table, to also find the exports of stripped libraries.
+    ///
+    /// Unlike [`Self::resolve_symbol`], the value is not relocated, and keeps the thumb bit on arm.
+    #[must_use]
+    pub fn symbol_value(&self, name: &str) -> Option<u64> {
+        let find = |syms: &Symtab, strtab: &Strtab| {
+            syms.iter()
+                .filter(|sym| sym.st_shndx != SHN_UNDEF as usize && sym.st_value != 0)
+                .find(|sym| strtab.get_at(sym.st_name) == Some(name))
+                .map(|sym| sym.st_value)
+        };
+        find(&self.elf.syms, &self.elf.strtab)
+            .or_else(|| find(&self.elf.dynsyms, &self.elf.dynstrtab))
+    }
+
     #[must_use]
     pub fn get_section(&self, name: &str, load_addr: GuestAddr) -> Option<Range<GuestAddr>> {
         for section in &self.elf.section_headers {
@@ -11,6 +11,8 @@ use libafl::{
 use libafl_bolts::os::{unix_signals::Signal, CTRL_C_EXIT};
 use typed_builder::TypedBuilder;
 
+#[cfg(feature = "systemmode")]
+use crate::HarnessCpuPolicy;
 use crate::{
     command::{CommandError, CommandManager, InputCommand, IsCommand},
     modules::EmulatorModuleTuple,
@@ -120,6 +122,9 @@ pub struct StdEmulatorDriver {
     #[cfg(feature = "systemmode")]
     #[builder(default = false)]
     allow_page_on_start: bool,
+    #[cfg(feature = "systemmode")]
+    #[builder(default)]
+    harness_cpu: HarnessCpuPolicy,
     #[cfg(feature = "x86_64")]
     #[builder(default = false)]
     process_only: bool,
@@ -152,6 +157,12 @@ impl StdEmulatorDriver {
         self.allow_page_on_start
     }
 
+    /// Which vCPUs run the harness hypercalls
+    #[cfg(feature = "systemmode")]
+    pub fn harness_cpu(&self) -> HarnessCpuPolicy {
+        self.harness_cpu
+    }
+
     #[cfg(feature = "x86_64")]
     pub fn is_process_only(&self) -> bool {
         self.process_only
@@ -254,6 +265,14 @@ where
             }
             EmulatorExitResult::Breakpoint(bp) => (bp.trigger(qemu), None),
             EmulatorExitResult::SyncExit(sync_backdoor) => {
+                #[cfg(feature = "systemmode")]
+                if let Some(cpu) = qemu.current_cpu() {
+                    if !emulator.driver.harness_cpu.allows(&cpu) {
+                        log::trace!("Ignoring the hypercall of vCPU {}", cpu.index());
+                        return Ok(None);
+                    }
+                }
+
                 let command = sync_backdoor.command().clone();
                 (Some(command), Some(sync_backdoor.ret_reg()))
             }
@@ -1,15 +1,68 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
 
 use hashbrown::HashMap;
 use libafl::inputs::UsesInput;
-use libafl_qemu_sys::GuestPhysAddr;
+use libafl_qemu_sys::{CPUArchState, GuestPhysAddr};
 
 use crate::{
     command::CommandManager,
     emu::{IsSnapshotManager, QemuSnapshotCheckResult},
-    DeviceSnapshotFilter, Emulator, Qemu, SnapshotId, SnapshotManagerError,
+    DeviceSnapshotFilter, Emulator, Qemu, SnapshotId, SnapshotManagerError, CPU,
 };
 
+/// Which vCPUs may run the harness hypercalls of a multi-core target, see [`crate::StdEmulatorDriverBuilder`].
+///
+/// Firmware booting several cores runs the same code on all of them, so several vCPUs may reach the harness
+/// hypercalls. The hypercalls of the other vCPUs are ignored, the guest continues as if they returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HarnessCpuPolicy {
+    /// Any vCPU runs the hypercalls
+    #[default]
+    Any,
+    /// Only the vCPU with the given index runs the hypercalls, e.g., `0` for the boot CPU
+    Index(usize),
+}
+
+impl HarnessCpuPolicy {
+    /// Whether the hypercalls of `cpu` are run
+    #[must_use]
+    pub fn allows(&self, cpu: &CPU) -> bool {
+        match self {
+            HarnessCpuPolicy::Any => true,
+            HarnessCpuPolicy::Index(index) => cpu.index() == *index,
+        }
+    }
+}
+
+/// The registers of all the vCPUs, saved with a fast snapshot
+#[derive(Clone)]
+struct VcpuStates(Vec<CPUArchState>);
+
+impl VcpuStates {
+    fn save(qemu: Qemu) -> Self {
+        Self(
+            (0..qemu.num_cpus())
+                .map(|index| qemu.cpu_from_index(index).save_state())
+                .collect(),
+        )
+    }
+
+    fn restore(&self, qemu: Qemu) {
+        for (index, state) in self.0.iter().enumerate() {
+            qemu.cpu_from_index(index).restore_state(state);
+        }
+    }
+}
+
+impl Debug for VcpuStates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "VcpuStates({} vCPUs)", self.0.len())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum SnapshotManager {
     Qemu(QemuSnapshotManager),
@@ -51,9 +104,52 @@ impl IsSnapshotManager for SnapshotManager {
 
 pub type FastSnapshotPtr = *mut libafl_qemu_sys::SyxSnapshot;
 
+/// The cost of the snapshot restores of a [`FastSnapshotManager`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotRestoreStats {
+    /// The number of restores
+    pub restores: u64,
+    /// The time spent restoring, in total
+    pub total_time: Duration,
+    /// The time spent in the last restore
+    pub last_time: Duration,
+    /// The time spent in the slowest restore
+    pub max_time: Duration,
+}
+
+impl SnapshotRestoreStats {
+    /// The mean time spent per restore
+    #[must_use]
+    pub fn mean_time(&self) -> Duration {
+        if self.restores == 0 {
+            Duration::ZERO
+        } else {
+            self.total_time / u32::try_from(self.restores).unwrap_or(u32::MAX)
+        }
+    }
+
+    fn record(&mut self, time: Duration) {
+        self.restores += 1;
+        self.total_time += time;
+        self.last_time = time;
+        self.max_time = self.max_time.max(time);
+    }
+}
+
+/// A snapshot manager restoring the memory pages dirtied since the snapshot, and the device states.
+///
+/// The writes of the guest are tracked during the run, so each restore only copies back the dirty pages, instead
+/// of the whole RAM. Restrict the devices saved and restored with [`FastSnapshotManager::with_device_filter`],
+/// to skip the devices the target does not touch. The cost of the restores is kept in the
+/// [`SnapshotRestoreStats`].
+///
+/// The registers of all the vCPUs are saved with each snapshot, so multi-core targets restart with all their
+/// cores in the snapshotted state.
 #[derive(Debug, Clone)]
 pub struct FastSnapshotManager {
-    snapshots: HashMap<SnapshotId, FastSnapshotPtr>,
+    snapshots: HashMap<SnapshotId, (FastSnapshotPtr, VcpuStates)>,
+    device_filter: DeviceSnapshotFilter,
+    stats: SnapshotRestoreStats,
 }
 
 impl Default for FastSnapshotManager {
@@ -66,11 +162,26 @@ impl FastSnapshotManager {
     pub fn new() -> Self {
         Self {
             snapshots: HashMap::new(),
+            device_filter: DeviceSnapshotFilter::All,
+            stats: SnapshotRestoreStats::default(),
         }
     }
 
+    /// Only saves and restores the state of the devices allowed by the `device_filter`
+    #[must_use]
+    pub fn with_device_filter(mut self, device_filter: DeviceSnapshotFilter) -> Self {
+        self.device_filter = device_filter;
+        self
+    }
+
     pub unsafe fn get(&self, id: &SnapshotId) -> FastSnapshotPtr {
-        *self.snapshots.get(id).unwrap()
+        self.snapshots.get(id).unwrap().0
+    }
+
+    /// The cost of the restores so far
+    #[must_use]
+    pub fn restore_stats(&self) -> &SnapshotRestoreStats {
+        &self.stats
     }
 }
 
@@ -127,8 +238,12 @@ impl IsSnapshotManager for QemuSnapshotManager {
 impl IsSnapshotManager for FastSnapshotManager {
     fn save(&mut self, qemu: Qemu) -> SnapshotId {
         let snapshot_id = SnapshotId::gen_unique_id();
+        let snapshot = match self.device_filter {
+            DeviceSnapshotFilter::All => qemu.create_fast_snapshot(true),
+            _ => qemu.create_fast_snapshot_filter(true, &self.device_filter),
+        };
         self.snapshots
-            .insert(snapshot_id, qemu.create_fast_snapshot(true));
+            .insert(snapshot_id, (snapshot, VcpuStates::save(qemu)));
         snapshot_id
     }
 
@@ -137,14 +252,17 @@ impl IsSnapshotManager for FastSnapshotManager {
         qemu: Qemu,
         snapshot_id: &SnapshotId,
     ) -> Result<(), SnapshotManagerError> {
-        let fast_snapshot_ptr = *self
+        let (fast_snapshot_ptr, vcpu_states) = self
             .snapshots
             .get(snapshot_id)
             .ok_or(SnapshotManagerError::SnapshotIdNotFound(*snapshot_id))?;
 
+        let start = Instant::now();
         unsafe {
-            qemu.restore_fast_snapshot(fast_snapshot_ptr);
+            qemu.restore_fast_snapshot(*fast_snapshot_ptr);
         }
+        vcpu_states.restore(qemu);
+        self.stats.record(start.elapsed());
 
         Ok(())
     }
@@ -154,11 +272,11 @@ impl IsSnapshotManager for FastSnapshotManager {
         qemu: Qemu,
         reference_snapshot_id: &SnapshotId,
     ) -> Result<QemuSnapshotCheckResult, SnapshotManagerError> {
-        let fast_snapshot_ptr = *self.snapshots.get(reference_snapshot_id).ok_or(
+        let (fast_snapshot_ptr, _) = self.snapshots.get(reference_snapshot_id).ok_or(
             SnapshotManagerError::SnapshotIdNotFound(*reference_snapshot_id),
         )?;
 
-        unsafe { Ok(qemu.check_fast_snapshot(fast_snapshot_ptr)) }
+        unsafe { Ok(qemu.check_fast_snapshot(*fast_snapshot_ptr)) }
     }
 }
 
@@ -1,13 +1,18 @@
-use core::{cell::UnsafeCell, fmt::Debug};
+use core::{cell::UnsafeCell, fmt::Debug, ops::Range};
+use std::{fs, path::PathBuf};
 
 use capstone::prelude::*;
 use libafl::{
     executors::ExitKind,
     inputs::{Input, UsesInput},
-    observers::{stacktrace::BacktraceObserver, ObserversTuple},
+    observers::{
+        stacktrace::BacktraceObserver, CrashContext, CrashContextObserver, CrashFrame, MapObserver,
+        ObserversTuple,
+    },
 };
 use libafl_bolts::tuples::{Handle, Handled, MatchFirstType, MatchNameRef};
 use libafl_qemu_sys::GuestAddr;
+use object::{Object, ObjectSegment, ObjectSymbol};
 use thread_local::ThreadLocal;
 
 #[cfg(feature = "systemmode")]
@@ -18,7 +23,7 @@ use crate::{
         AddressFilter, EmulatorModule, EmulatorModuleTuple, EmulatorModules, StdAddressFilter,
     },
     qemu::{ArchExtras, Hook},
-    Qemu,
+    Qemu, Regs,
 };
 
 pub trait CallTraceCollector: 'static {
@@ -640,3 +645,410 @@ impl CallTraceCollector for FullBacktraceCollector {
         self.reset();
     }
 }
+
+/// The function symbols of a guest image, loaded at `range`
+#[derive(Debug)]
+struct GuestImageSymbols {
+    path: PathBuf,
+    range: Range<GuestAddr>,
+    /// The difference between the guest addresses and the addresses of the image
+    bias: GuestAddr,
+    /// The function symbols, sorted by address
+    symbols: Vec<(u64, String)>,
+}
+
+impl GuestImageSymbols {
+    fn load(path: PathBuf, range: Range<GuestAddr>) -> Option<Self> {
+        let data = fs::read(&path).ok()?;
+        let obj = object::read::File::parse(&*data).ok()?;
+        // Position-independent images are linked at 0, and relocated to their mapping
+        let linked_at = obj.segments().map(|s| s.address()).min().unwrap_or(0);
+        let bias = if linked_at == 0 { range.start } else { 0 };
+        let mut symbols: Vec<(u64, String)> = obj
+            .symbols()
+            .chain(obj.dynamic_symbols())
+            .filter(|sym| sym.kind() == object::SymbolKind::Text && sym.address() != 0)
+            .filter_map(|sym| Some((sym.address(), sym.name().ok()?.to_string())))
+            .collect();
+        symbols.sort_unstable();
+        Some(Self {
+            path,
+            range,
+            bias,
+            symbols,
+        })
+    }
+
+    /// The symbolized frame of `addr`, in this image
+    #[allow(clippy::unnecessary_cast)]
+    fn frame(&self, addr: GuestAddr) -> CrashFrame {
+        let raddr = (addr - self.bias) as u64;
+        let symbol = match self.symbols.partition_point(|(start, _)| *start <= raddr) {
+            0 => format!("{}+{raddr:#x}", self.path.display()),
+            i => {
+                let (start, name) = &self.symbols[i - 1];
+                format!(
+                    "{}+{:#x}",
+                    addr2line::demangle_auto(name.into(), None),
+                    raddr - start
+                )
+            }
+        };
+        CrashFrame {
+            ip: addr as usize,
+            symbol: Some(symbol),
+            file: Some(self.path.display().to_string()),
+            line: None,
+            module: None,
+        }
+    }
+}
+
+/// A collector keeping a shadow call stack of the target, and storing the symbolized guest backtrace in a
+/// [`CrashContextObserver`] when the run crashes.
+///
+/// With a [`libafl::feedbacks::CrashContextFeedback`] in the objective, the backtrace is stored with the crashing
+/// testcase; with a [`libafl::feedbacks::NewHashFeedback`] on the observer, crashes are deduplicated on their
+/// innermost frames. Frames are symbolized with the function symbols of the guest images: in usermode, of all the
+/// files mapped by the target, in systemmode, of the images added with [`Self::with_image`].
+// TODO support multiple threads with thread local callstack
+#[derive(Debug)]
+pub struct CrashBacktraceCollector {
+    observer_handle: Handle<CrashContextObserver>,
+    /// The pc of each call on the stack, and its return address
+    callstack: Vec<(GuestAddr, GuestAddr)>,
+    images: Vec<(PathBuf, Range<GuestAddr>)>,
+    /// The symbols of the images, loaded at the first crash
+    symbols: Option<Vec<GuestImageSymbols>>,
+}
+
+impl CrashBacktraceCollector {
+    /// Creates a new [`CrashBacktraceCollector`], filling the given [`CrashContextObserver`]
+    #[must_use]
+    pub fn new(observer: &CrashContextObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            callstack: Vec::new(),
+            images: Vec::new(),
+            symbols: None,
+        }
+    }
+
+    /// Symbolizes the frames in `range` with the symbols of the ELF at `path`
+    #[must_use]
+    pub fn with_image<P>(mut self, path: P, range: Range<GuestAddr>) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.images.push((path.into(), range));
+        self
+    }
+
+    /// The return addresses on the shadow call stack, innermost last
+    pub fn callstack(&self) -> impl Iterator<Item = GuestAddr> + '_ {
+        self.callstack.iter().map(|(_, ret_addr)| *ret_addr)
+    }
+
+    /// The symbolized backtrace at `pc`, innermost frame first
+    #[allow(unused_variables)]
+    fn backtrace(&mut self, qemu: Qemu, pc: GuestAddr) -> Vec<CrashFrame> {
+        let symbols = self.symbols.get_or_insert_with(|| {
+            #[allow(unused_mut)]
+            let mut images = self.images.clone();
+            #[cfg(feature = "usermode")]
+            if images.is_empty() {
+                for region in qemu.mappings() {
+                    let Some(path) = region.path() else {
+                        continue;
+                    };
+                    let path = PathBuf::from(path);
+                    match images.iter_mut().find(|(p, _)| *p == path) {
+                        Some((_, range)) => {
+                            range.start = range.start.min(region.start());
+                            range.end = range.end.max(region.end());
+                        }
+                        None => images.push((path, region.start()..region.end())),
+                    }
+                }
+            }
+            images
+                .into_iter()
+                .filter_map(|(path, range)| GuestImageSymbols::load(path, range))
+                .collect()
+        });
+
+        core::iter::once(pc)
+            .chain(self.callstack.iter().rev().map(|(call_pc, _)| *call_pc))
+            .map(|addr| {
+                symbols
+                    .iter()
+                    .find(|image| image.range.contains(&addr))
+                    .map_or(
+                        CrashFrame {
+                            ip: addr as usize,
+                            symbol: None,
+                            file: None,
+                            line: None,
+                            module: None,
+                        },
+                        |image| image.frame(addr),
+                    )
+            })
+            .collect()
+    }
+}
+
+impl CallTraceCollector for CrashBacktraceCollector {
+    #[allow(clippy::unnecessary_cast)]
+    fn on_call<ET, S>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: Option<&mut S>,
+        pc: GuestAddr,
+        call_len: usize,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+        S: Unpin + UsesInput,
+    {
+        self.callstack.push((pc, pc + call_len as GuestAddr));
+    }
+
+    fn on_ret<ET, S>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: Option<&mut S>,
+        _pc: GuestAddr,
+        ret_addr: GuestAddr,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+        S: Unpin + UsesInput,
+    {
+        // Frames left with longjmp or exceptions are dropped with the matching one
+        if self.callstack.iter().any(|(_, addr)| *addr == ret_addr) {
+            while let Some((_, addr)) = self.callstack.pop() {
+                if addr == ret_addr {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn pre_exec<I>(&mut self, _qemu: Qemu, _input: &I)
+    where
+        I: Input,
+    {
+        self.callstack.clear();
+    }
+
+    fn post_exec<OT, S>(
+        &mut self,
+        qemu: Qemu,
+        _input: &S::Input,
+        observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        S: Unpin + UsesInput,
+    {
+        if *exit_kind != ExitKind::Crash {
+            return;
+        }
+        let pc: GuestAddr = qemu
+            .current_cpu()
+            .unwrap_or_else(|| qemu.cpu_from_index(0))
+            .read_reg(Regs::Pc)
+            .unwrap_or(0);
+        let context = CrashContext {
+            signal: "guest crash".to_string(),
+            signal_number: 0,
+            si_code: 0,
+            fault_addr: 0,
+            pc: Some(pc as usize),
+            registers: String::new(),
+            backtrace: self.backtrace(qemu, pc),
+            locals: Vec::new(),
+        };
+        observers
+            .get_mut(&self.observer_handle)
+            .expect("A CrashBacktraceCollector needs a CrashContextObserver")
+            .set_context(Some(context));
+    }
+}
+
+/// A collector tracking the maximum call depth and stack usage of each run.
+///
+/// Both are recorded in map observers, at the index of the reached depth and of the used stack, in steps of
+/// [`Self::with_stack_granularity`] bytes: with a [`libafl::feedbacks::MaxMapFeedback`] on them, deeper recursion
+/// and larger stack frames are new coverage. With [`Self::with_depth_limit`] and [`Self::with_stack_limit`], runs
+/// exceeding the limits are reported as crashes, e.g., to look for stack exhaustion.
+///
+/// The stack usage is the distance between the stack pointer at the start of the run and its lowest value at a
+/// call, for stacks growing down.
+// TODO support multiple threads with thread local callstack
+#[derive(Debug)]
+pub struct StackDepthCollector<O> {
+    depth_handle: Handle<O>,
+    usage_handle: Handle<O>,
+    /// The return addresses on the stack
+    callstack: Vec<GuestAddr>,
+    max_depth: usize,
+    stack_base: Option<GuestAddr>,
+    max_usage: GuestAddr,
+    granularity: GuestAddr,
+    depth_limit: Option<usize>,
+    stack_limit: Option<GuestAddr>,
+}
+
+impl<O> StackDepthCollector<O>
+where
+    O: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`StackDepthCollector`], recording the call depth in `depth_observer`, and the stack usage in
+    /// `usage_observer`
+    #[must_use]
+    pub fn new(depth_observer: &O, usage_observer: &O) -> Self {
+        Self {
+            depth_handle: depth_observer.handle(),
+            usage_handle: usage_observer.handle(),
+            callstack: Vec::new(),
+            max_depth: 0,
+            stack_base: None,
+            max_usage: 0,
+            granularity: 64,
+            depth_limit: None,
+            stack_limit: None,
+        }
+    }
+}
+
+impl<O> StackDepthCollector<O> {
+    /// Records the stack usage in steps of `bytes`, 64 by default
+    #[must_use]
+    pub fn with_stack_granularity(mut self, bytes: GuestAddr) -> Self {
+        self.granularity = bytes.max(1);
+        self
+    }
+
+    /// Reports the runs exceeding a call depth of `depth` as crashes
+    #[must_use]
+    pub fn with_depth_limit(mut self, depth: usize) -> Self {
+        self.depth_limit = Some(depth);
+        self
+    }
+
+    /// Reports the runs using more than `bytes` of stack as crashes
+    #[must_use]
+    pub fn with_stack_limit(mut self, bytes: GuestAddr) -> Self {
+        self.stack_limit = Some(bytes);
+        self
+    }
+
+    /// The maximum call depth of the current or last run
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The maximum stack usage of the current or last run, in bytes
+    #[must_use]
+    pub fn max_stack_usage(&self) -> GuestAddr {
+        self.max_usage
+    }
+}
+
+impl<O> CallTraceCollector for StackDepthCollector<O>
+where
+    O: MapObserver<Entry = u8> + 'static,
+{
+    #[allow(clippy::unnecessary_cast)]
+    fn on_call<ET, S>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: Option<&mut S>,
+        pc: GuestAddr,
+        call_len: usize,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+        S: Unpin + UsesInput,
+    {
+        self.callstack.push(pc + call_len as GuestAddr);
+        self.max_depth = self.max_depth.max(self.callstack.len());
+
+        if let Ok(sp) = emulator_modules.qemu().read_reg(Regs::Sp) {
+            let sp = sp as GuestAddr;
+            let base = *self.stack_base.get_or_insert(sp);
+            self.max_usage = self.max_usage.max(base.saturating_sub(sp));
+        }
+    }
+
+    fn on_ret<ET, S>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: Option<&mut S>,
+        _pc: GuestAddr,
+        ret_addr: GuestAddr,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+        S: Unpin + UsesInput,
+    {
+        // Frames left with longjmp or exceptions are dropped with the matching one
+        if self.callstack.contains(&ret_addr) {
+            while let Some(addr) = self.callstack.pop() {
+                if addr == ret_addr {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn pre_exec<I>(&mut self, qemu: Qemu, _input: &I)
+    where
+        I: Input,
+    {
+        self.callstack.clear();
+        self.max_depth = 0;
+        self.max_usage = 0;
+        self.stack_base = qemu.read_reg(Regs::Sp).ok().map(|sp| sp as GuestAddr);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn post_exec<OT, S>(
+        &mut self,
+        _qemu: Qemu,
+        _input: &S::Input,
+        observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        S: Unpin + UsesInput,
+    {
+        let depth_observer = observers
+            .get_mut(&self.depth_handle)
+            .expect("A StackDepthCollector needs its depth observer");
+        let len = depth_observer.usable_count();
+        if len > 0 {
+            depth_observer.set(self.max_depth.min(len - 1), 1);
+        }
+
+        let usage_observer = observers
+            .get_mut(&self.usage_handle)
+            .expect("A StackDepthCollector needs its stack usage observer");
+        let len = usage_observer.usable_count();
+        if len > 0 {
+            let idx = (self.max_usage / self.granularity) as usize;
+            usage_observer.set(idx.min(len - 1), 1);
+        }
+
+        let exceeded = self.depth_limit.is_some_and(|limit| self.max_depth > limit)
+            || self.stack_limit.is_some_and(|limit| self.max_usage > limit);
+        if exceeded && *exit_kind == ExitKind::Ok {
+            log::info!(
+                "Stack limit exceeded: depth {}, {} bytes",
+                self.max_depth,
+                self.max_usage
+            );
+            *exit_kind = ExitKind::Crash;
+        }
+    }
+}
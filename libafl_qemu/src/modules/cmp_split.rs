@@ -0,0 +1,189 @@
+//! The [`CmpSplitModule`] splits wide comparisons of the target into byte-wise coverage, like `laf-intel`.
+//!
+//! Coverage-guided fuzzers rarely solve a comparison against a 4 or 8 byte magic value by chance: all the inputs
+//! failing it look the same to the coverage map. `laf-intel` splits such comparisons into chains of single-byte
+//! comparisons at compile time, so each correct byte reaches new coverage. Closed-source binaries can not be
+//! recompiled, so this module emits the same information from the comparison hooks of QEMU instead: for each
+//! comparison of 2 to 8 bytes, it marks one entry of a [`MapObserver`] per matching leading byte of the operands.
+//!
+//! Use the edges observer of the fuzzer, or a dedicated map with its own [`libafl::feedbacks::MaxMapFeedback`].
+//! Unlike [`crate::modules::CmpLogModule`], it needs no mutator support.
+
+use core::{fmt::Debug, mem::size_of};
+
+use hashbrown::HashMap;
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+};
+use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::{
+    emu::EmulatorModules,
+    modules::{hash_me, AddressFilter, EmulatorModule, EmulatorModuleTuple, StdAddressFilter},
+    qemu::Hook,
+};
+
+/// The number of equal leading bytes of the `size` bytes wide operands
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn matching_leading_bytes(v0: u64, v1: u64, size: usize) -> usize {
+    let unused_bits = 64 - 8 * size as u32;
+    ((v0 ^ v1).leading_zeros() - unused_bits) as usize / 8
+}
+
+/// A module turning the matching bytes of wide comparisons into coverage, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct CmpSplitModule<O> {
+    observer_handle: Handle<O>,
+    address_filter: StdAddressFilter,
+    /// The most leading bytes matched by each comparison of the current run, by pc
+    matched: HashMap<u64, usize>,
+}
+
+impl<O> CmpSplitModule<O>
+where
+    O: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`CmpSplitModule`], splitting the comparisons of the code allowed by `address_filter`
+    /// into the given map `observer`
+    #[must_use]
+    pub fn new(observer: &O, address_filter: StdAddressFilter) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            address_filter,
+            matched: HashMap::new(),
+        }
+    }
+}
+
+impl<O> CmpSplitModule<O> {
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.address_filter.allowed(&addr)
+    }
+}
+
+impl<O, S> EmulatorModule<S> for CmpSplitModule<O>
+where
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.cmps(
+            Hook::Function(gen_cmp_split::<ET, O, S>),
+            Hook::Empty,
+            Hook::Function(trace_cmp_split::<ET, O, S, u16>),
+            Hook::Function(trace_cmp_split::<ET, O, S, u32>),
+            Hook::Function(trace_cmp_split::<ET, O, S, u64>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.matched.clear();
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let observer = observers
+            .get_mut(&self.observer_handle)
+            .expect("A CmpSplitModule needs its map observer");
+        let len = observer.usable_count() as u64;
+        if len == 0 {
+            return;
+        }
+        // One entry per matched byte, like the branches of a split comparison
+        for (&pc, &matched) in &self.matched {
+            for level in 1..=matched as u64 {
+                let idx = (hash_me(pc ^ level.rotate_right(8)) % len) as usize;
+                observer.set(idx, observer.get(idx).saturating_add(1));
+            }
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn gen_cmp_split<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    size: usize,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get::<CmpSplitModule<O>>().unwrap();
+    if size > 1 && h.must_instrument(pc) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn trace_cmp_split<ET, O, S, T>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    v0: T,
+    v1: T,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+    T: Into<u64>,
+{
+    let matched = matching_leading_bytes(v0.into(), v1.into(), size_of::<T>());
+    if matched == 0 {
+        return;
+    }
+    let h = emulator_modules.get_mut::<CmpSplitModule<O>>().unwrap();
+    let best = h.matched.entry(id).or_insert(0);
+    *best = (*best).max(matched);
+}
@@ -0,0 +1,176 @@
+//! Capture the registers and selected memory of the guest when it crashes.
+//!
+//! The [`CrashStateModule`] reads the whole register file of the crashing vCPU, the top of its stack, and the
+//! memory regions given by the user, into a [`CrashState`]. The state is picked up by a [`CrashStateObserver`],
+//! and stored next to the objective by a [`libafl::feedbacks::CrashStateFeedback`]:
+//!
+//! ```ignore
+//! let crash_state_observer = CrashStateObserver::new("crash_state");
+//! let module = CrashStateModule::new()
+//!     .with_stack(0x200)
+//!     .with_region(config_addr..config_addr + 0x100);
+//! let mut objective = feedback_and_fast!(
+//!     CrashFeedback::new(),
+//!     CrashStateFeedback::new(&crash_state_observer, "./crashes"),
+//! );
+//! ```
+//!
+//! In usermode, the state is captured by the crash handler. In systemmode, it is captured after each run ending
+//! with [`ExitKind::Crash`].
+//!
+//! [`CrashStateObserver`]: libafl::observers::CrashStateObserver
+
+use core::ops::Range;
+
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{record_crash_state, CrashMemoryRegion, CrashState, ObserversTuple},
+};
+use libafl_qemu_sys::GuestAddr;
+use strum::IntoEnumIterator;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::{
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple, NopAddressFilter, NOP_ADDRESS_FILTER},
+    Qemu, Regs,
+};
+
+/// The name of the guest architecture, stored in the [`CrashState`]
+const ARCH: &str = if cfg!(cpu_target = "x86_64") {
+    "x86_64"
+} else if cfg!(cpu_target = "i386") {
+    "i386"
+} else if cfg!(cpu_target = "aarch64") {
+    "aarch64"
+} else if cfg!(cpu_target = "arm") {
+    "arm"
+} else if cfg!(cpu_target = "mips") {
+    "mips"
+} else if cfg!(cpu_target = "ppc") {
+    "ppc"
+} else if cfg!(cpu_target = "riscv32") {
+    "riscv32"
+} else if cfg!(cpu_target = "riscv64") {
+    "riscv64"
+} else {
+    "unknown"
+};
+
+/// A module capturing the [`CrashState`] of the guest when it crashes, see the [module-level docs](self).
+#[derive(Debug, Default)]
+pub struct CrashStateModule {
+    /// The memory regions to capture, as start address and length
+    regions: Vec<(GuestAddr, usize)>,
+    /// The number of bytes to capture from the stack pointer on
+    stack_size: usize,
+}
+
+impl CrashStateModule {
+    /// Creates a new [`CrashStateModule`], capturing only the registers
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also captures the memory in `range`
+    #[must_use]
+    pub fn with_region(mut self, range: Range<GuestAddr>) -> Self {
+        let len = range.end.saturating_sub(range.start) as usize;
+        self.regions.push((range.start, len));
+        self
+    }
+
+    /// Also captures `size` bytes of the stack, from the stack pointer on
+    #[must_use]
+    pub fn with_stack(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    /// Reads the registers and the memory regions of the current vCPU.
+    ///
+    /// Registers that cannot be read are left out, and regions that cannot be read are stored empty.
+    #[must_use]
+    pub fn capture(&self, qemu: Qemu) -> CrashState {
+        let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+        let mut state = CrashState::new(ARCH);
+        for reg in Regs::iter() {
+            if let Ok(value) = cpu.read_reg(reg) {
+                state.registers.push((format!("{reg:?}"), value.into()));
+            }
+        }
+
+        let stack = cpu
+            .read_reg(Regs::Sp)
+            .ok()
+            .filter(|_| self.stack_size > 0)
+            .map(|sp| (sp, self.stack_size));
+        for &(addr, len) in stack.iter().chain(&self.regions) {
+            let mut bytes = vec![0; len];
+            if cpu.read_mem(addr, &mut bytes).is_err() {
+                bytes.clear();
+            }
+            state.regions.push(CrashMemoryRegion {
+                addr: addr.into(),
+                bytes,
+            });
+        }
+        state
+    }
+}
+
+impl<S> EmulatorModule<S> for CrashStateModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        if *exit_kind == ExitKind::Crash {
+            let state = self.capture(emulator_modules.qemu());
+            // # Safety
+            // The observers run after the modules, on the same thread.
+            unsafe { record_crash_state(state) };
+        }
+    }
+
+    unsafe fn on_crash(&mut self) {
+        if let Some(qemu) = Qemu::get() {
+            // The crash handler runs the observers after the modules
+            unsafe { record_crash_state(self.capture(qemu)) };
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
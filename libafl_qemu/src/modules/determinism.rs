@@ -0,0 +1,507 @@
+//! Make the time and entropy sources of the guest deterministic.
+//!
+//! Targets reading the clock or random numbers behave differently in each run of the same input, which makes
+//! coverage unstable and crashes hard to reproduce. The [`DeterminismModule`] replaces these sources with
+//! sequences derived from a fixed seed or from the current input:
+//! - on `x86_64`, the results of `rdtsc`, `rdtscp`, `rdrand` and `rdseed` are overwritten right after the
+//!   instruction, with a virtual clock and seeded random numbers,
+//! - in usermode, `clock_gettime`, `gettimeofday` and `getrandom` are answered by the module, and so are the reads
+//!   of `/dev/urandom` and `/dev/random`.
+//!
+//! The virtual clock starts at the same time in every run, and advances by a fixed step on each read.
+//! In systemmode, the timers and the RTC of the machine are driven by QEMU: run it with the arguments of
+//! [`DeterminismModule::qemu_args`] to derive them from the number of executed instructions instead of the host.
+
+#[cfg(feature = "usermode")]
+use std::ffi::CStr;
+
+#[cfg(any(cpu_target = "x86_64", feature = "usermode"))]
+use hashbrown::HashSet;
+use libafl::inputs::{HasTargetBytes, UsesInput};
+use libafl_bolts::{
+    hash_std,
+    rands::{Rand, StdRand},
+    AsSlice,
+};
+use libafl_qemu_sys::GuestAddr;
+#[cfg(cpu_target = "x86_64")]
+use {
+    crate::{capstone, Regs},
+    capstone::{arch::x86::X86Insn, prelude::*},
+    hashbrown::HashMap,
+};
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+#[cfg(any(cpu_target = "x86_64", feature = "usermode"))]
+use crate::Qemu;
+use crate::{
+    emu::EmulatorModules,
+    modules::{AddressFilter, EmulatorModule, EmulatorModuleTuple, StdAddressFilter},
+    qemu::Hook,
+};
+#[cfg(feature = "usermode")]
+use crate::{qemu::SyscallHookResult, SYS_close, SYS_getrandom, SYS_openat, SYS_read};
+#[cfg(all(feature = "usermode", not(cpu_target = "riscv32")))]
+use crate::{SYS_clock_gettime, SYS_gettimeofday};
+
+/// The nanoseconds per second, for the virtual clock
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Where the random numbers of a [`DeterminismModule`] come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySeed {
+    /// The same numbers in every run
+    Fixed(u64),
+    /// Numbers seeded by a hash of the input, so the fuzzer can explore them
+    Input,
+}
+
+/// The instructions whose results are overwritten, by the address following them
+#[cfg(cpu_target = "x86_64")]
+#[derive(Debug, Clone, Copy)]
+enum Fixup {
+    /// `rdtsc` and `rdtscp`, writing `edx:eax`
+    Timestamp,
+    /// `rdrand` and `rdseed`, writing the register
+    Random(Regs, usize),
+}
+
+/// A module making the time and entropy of the guest deterministic, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct DeterminismModule {
+    filter: StdAddressFilter,
+    seed: EntropySeed,
+    /// The virtual time at the start of each run, in nanoseconds
+    time_base: u64,
+    /// The advance of the virtual time on each read, in nanoseconds
+    time_step: u64,
+    /// The virtual time of the current run, in nanoseconds
+    time: u64,
+    rand: StdRand,
+    #[cfg(cpu_target = "x86_64")]
+    cs: Capstone,
+    #[cfg(cpu_target = "x86_64")]
+    fixups: HashMap<GuestAddr, Fixup>,
+    /// The blocks already searched for time and entropy instructions
+    #[cfg(cpu_target = "x86_64")]
+    translated: HashSet<GuestAddr>,
+    /// The file descriptors of the random devices opened by the target
+    #[cfg(feature = "usermode")]
+    random_fds: HashSet<GuestAddr>,
+}
+
+impl DeterminismModule {
+    /// Creates a new [`DeterminismModule`], for the code allowed by `filter`, with random numbers from `seed`
+    #[must_use]
+    pub fn new(filter: StdAddressFilter, seed: EntropySeed) -> Self {
+        // 2020-09-13, a fixed date in the past
+        let time_base = 1_600_000_000 * NANOS_PER_SEC;
+        Self {
+            filter,
+            seed,
+            time_base,
+            time_step: 1000,
+            time: time_base,
+            rand: StdRand::with_seed(0),
+            #[cfg(cpu_target = "x86_64")]
+            cs: capstone().detail(true).build().unwrap(),
+            #[cfg(cpu_target = "x86_64")]
+            fixups: HashMap::new(),
+            #[cfg(cpu_target = "x86_64")]
+            translated: HashSet::new(),
+            #[cfg(feature = "usermode")]
+            random_fds: HashSet::new(),
+        }
+    }
+
+    /// Starts the virtual clock at `secs` seconds since the epoch in each run
+    #[must_use]
+    pub fn with_time_base(mut self, secs: u64) -> Self {
+        self.time_base = secs * NANOS_PER_SEC;
+        self.time = self.time_base;
+        self
+    }
+
+    /// Advances the virtual clock by `nanos` nanoseconds on each read, at least 1
+    #[must_use]
+    pub fn with_time_step(mut self, nanos: u64) -> Self {
+        self.time_step = nanos.max(1);
+        self
+    }
+
+    /// The QEMU arguments deriving the timers and the RTC of a full-system guest from the executed instructions
+    #[must_use]
+    pub fn qemu_args() -> Vec<String> {
+        [
+            "-icount",
+            "shift=auto,align=off,sleep=off",
+            "-rtc",
+            "clock=vm",
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    /// Reads the virtual clock, in nanoseconds, and advances it
+    pub fn read_time(&mut self) -> u64 {
+        let time = self.time;
+        self.time += self.time_step;
+        time
+    }
+
+    /// The next random bytes of the current run
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn random_bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.rand.next() as u8).collect()
+    }
+
+    /// Searches the block at `pc` for time and entropy instructions, and returns the addresses following them
+    #[cfg(cpu_target = "x86_64")]
+    fn find_fixups(&mut self, qemu: Qemu, pc: GuestAddr) -> Vec<GuestAddr> {
+        #[allow(unused_mut)]
+        let mut code = {
+            #[cfg(feature = "usermode")]
+            unsafe {
+                std::slice::from_raw_parts(qemu.g2h(pc), 512)
+            }
+            #[cfg(feature = "systemmode")]
+            &mut [0; 512]
+        };
+        #[cfg(feature = "systemmode")]
+        if let Err(err) = qemu.read_mem(pc, code) {
+            log::warn!("Failed to read the block at {pc:#x}: {err:?}");
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        let Ok(insns) = self.cs.disasm_all(code, pc.into()) else {
+            return found;
+        };
+        for insn in insns.iter() {
+            let next = (insn.address() + insn.bytes().len() as u64) as GuestAddr;
+            let id = insn.id().0;
+            let fixup = if id == X86Insn::X86_INS_RDTSC as u32
+                || id == X86Insn::X86_INS_RDTSCP as u32
+            {
+                Some(Fixup::Timestamp)
+            } else if id == X86Insn::X86_INS_RDRAND as u32 || id == X86Insn::X86_INS_RDSEED as u32 {
+                insn.op_str()
+                    .and_then(random_dest)
+                    .map(|(reg, size)| Fixup::Random(reg, size))
+            } else {
+                None
+            };
+            if let Some(fixup) = fixup {
+                self.fixups.insert(next, fixup);
+                found.push(next);
+            }
+            // The block ends at the first branch
+            let detail = self.cs.insn_detail(insn).unwrap();
+            if detail.groups().iter().any(|group| {
+                matches!(
+                    u32::from(group.0),
+                    capstone::InsnGroupType::CS_GRP_JUMP
+                        | capstone::InsnGroupType::CS_GRP_CALL
+                        | capstone::InsnGroupType::CS_GRP_RET
+                        | capstone::InsnGroupType::CS_GRP_INT
+                        | capstone::InsnGroupType::CS_GRP_IRET
+                )
+            }) {
+                break;
+            }
+        }
+        found
+    }
+
+    /// Overwrites the result of the instruction before `pc`
+    #[cfg(cpu_target = "x86_64")]
+    fn apply_fixup(&mut self, qemu: Qemu, pc: GuestAddr) {
+        let Some(fixup) = self.fixups.get(&pc).copied() else {
+            return;
+        };
+        let Some(cpu) = qemu.current_cpu() else {
+            return;
+        };
+        let result = match fixup {
+            Fixup::Timestamp => {
+                let time = self.read_time();
+                cpu.write_reg(Regs::Rax, time & 0xffff_ffff)
+                    .and_then(|()| cpu.write_reg(Regs::Rdx, time >> 32))
+            }
+            Fixup::Random(reg, size) => {
+                let value = self.rand.next();
+                if size == 2 {
+                    // 16 bit writes keep the upper bits of the register
+                    cpu.read_reg(reg)
+                        .and_then(|old| cpu.write_reg(reg, (old & !0xffff) | (value & 0xffff)))
+                } else if size == 4 {
+                    cpu.write_reg(reg, value & 0xffff_ffff)
+                } else {
+                    cpu.write_reg(reg, value)
+                }
+            }
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to overwrite the result of the instruction before {pc:#x}: {err:?}");
+        }
+    }
+}
+
+/// The register written by `rdrand` or `rdseed`, and its size, from the operand string
+#[cfg(cpu_target = "x86_64")]
+fn random_dest(op: &str) -> Option<(Regs, usize)> {
+    const REGS: [(Regs, [&str; 4]); 16] = [
+        (Regs::Rax, ["rax", "eax", "ax", ""]),
+        (Regs::Rbx, ["rbx", "ebx", "bx", ""]),
+        (Regs::Rcx, ["rcx", "ecx", "cx", ""]),
+        (Regs::Rdx, ["rdx", "edx", "dx", ""]),
+        (Regs::Rsi, ["rsi", "esi", "si", ""]),
+        (Regs::Rdi, ["rdi", "edi", "di", ""]),
+        (Regs::Rbp, ["rbp", "ebp", "bp", ""]),
+        (Regs::Rsp, ["rsp", "esp", "sp", ""]),
+        (Regs::R8, ["r8", "r8d", "r8w", ""]),
+        (Regs::R9, ["r9", "r9d", "r9w", ""]),
+        (Regs::R10, ["r10", "r10d", "r10w", ""]),
+        (Regs::R11, ["r11", "r11d", "r11w", ""]),
+        (Regs::R12, ["r12", "r12d", "r12w", ""]),
+        (Regs::R13, ["r13", "r13d", "r13w", ""]),
+        (Regs::R14, ["r14", "r14d", "r14w", ""]),
+        (Regs::R15, ["r15", "r15d", "r15w", ""]),
+    ];
+    let op = op.trim();
+    REGS.iter().find_map(|(reg, names)| {
+        names
+            .iter()
+            .position(|name| *name == op)
+            .map(|i| (*reg, 8 >> i))
+    })
+}
+
+impl<S> EmulatorModule<S> for DeterminismModule
+where
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    #[allow(unused_variables)]
+    fn post_qemu_init<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        #[cfg(cpu_target = "x86_64")]
+        emulator_modules.blocks(
+            Hook::Function(gen_determinism_block::<ET, S>),
+            Hook::Empty,
+            Hook::Empty,
+        );
+        #[cfg(feature = "usermode")]
+        {
+            emulator_modules.syscalls(Hook::Function(determinism_syscall::<ET, S>));
+            emulator_modules.after_syscalls(Hook::Function(determinism_after_syscall::<ET, S>));
+        }
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let seed = match self.seed {
+            EntropySeed::Fixed(seed) => seed,
+            EntropySeed::Input => hash_std(input.target_bytes().as_slice()),
+        };
+        self.rand.set_seed(seed);
+        self.time = self.time_base;
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+#[cfg(cpu_target = "x86_64")]
+pub fn gen_determinism_block<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<DeterminismModule>().unwrap();
+    if !h.must_instrument(pc) || !h.translated.insert(pc) {
+        return None;
+    }
+    for next in h.find_fixups(qemu, pc) {
+        emulator_modules.instruction_function(next, on_determinism_fixup::<ET, S>, false);
+    }
+    None
+}
+
+#[cfg(cpu_target = "x86_64")]
+pub fn on_determinism_fixup<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<DeterminismModule>().unwrap();
+    h.apply_fixup(qemu, pc);
+}
+
+/// Writes `words` of the guest word size at `addr`, e.g., a `timespec`
+#[cfg(feature = "usermode")]
+fn write_words(qemu: Qemu, addr: GuestAddr, words: &[GuestAddr]) -> bool {
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_ne_bytes())
+        .collect::<Vec<_>>();
+    qemu.write_mem(addr, &bytes).is_ok()
+}
+
+#[cfg(feature = "usermode")]
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::cast_possible_truncation)]
+fn determinism_syscall<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let Some(h) = emulator_modules.get_mut::<DeterminismModule>() else {
+        return SyscallHookResult::new(None);
+    };
+    let efault = (-(libc::EFAULT as libafl_qemu_sys::GuestIsize)) as GuestAddr;
+
+    match i64::from(sys_num) {
+        #[cfg(not(cpu_target = "riscv32"))]
+        SYS_clock_gettime => {
+            let time = h.read_time();
+            let timespec = [
+                (time / NANOS_PER_SEC) as GuestAddr,
+                (time % NANOS_PER_SEC) as GuestAddr,
+            ];
+            if write_words(qemu, a1, &timespec) {
+                SyscallHookResult::new(Some(0))
+            } else {
+                SyscallHookResult::new(Some(efault))
+            }
+        }
+        #[cfg(not(cpu_target = "riscv32"))]
+        SYS_gettimeofday => {
+            let time = h.read_time();
+            let timeval = [
+                (time / NANOS_PER_SEC) as GuestAddr,
+                (time % NANOS_PER_SEC / 1000) as GuestAddr,
+            ];
+            if a0 == 0 || write_words(qemu, a0, &timeval) {
+                SyscallHookResult::new(Some(0))
+            } else {
+                SyscallHookResult::new(Some(efault))
+            }
+        }
+        SYS_getrandom => {
+            let bytes = h.random_bytes(a1 as usize);
+            if qemu.write_mem(a0, &bytes).is_ok() {
+                SyscallHookResult::new(Some(a1))
+            } else {
+                SyscallHookResult::new(Some(efault))
+            }
+        }
+        SYS_read if h.random_fds.contains(&a0) => {
+            let bytes = h.random_bytes(a2 as usize);
+            if qemu.write_mem(a1, &bytes).is_ok() {
+                SyscallHookResult::new(Some(a2))
+            } else {
+                SyscallHookResult::new(Some(efault))
+            }
+        }
+        SYS_close => {
+            h.random_fds.remove(&a0);
+            SyscallHookResult::new(None)
+        }
+        _ => SyscallHookResult::new(None),
+    }
+}
+
+#[cfg(feature = "usermode")]
+#[allow(clippy::too_many_arguments)]
+fn determinism_after_syscall<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    _a0: GuestAddr,
+    a1: GuestAddr,
+    _a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    // Negative results are errors
+    if i64::from(sys_num) != SYS_openat || (result as libafl_qemu_sys::GuestIsize) < 0 || a1 == 0 {
+        return result;
+    }
+    let qemu = emulator_modules.qemu();
+    let path = unsafe { CStr::from_ptr(qemu.g2h::<libc::c_char>(a1)) };
+    if matches!(path.to_bytes(), b"/dev/urandom" | b"/dev/random") {
+        if let Some(h) = emulator_modules.get_mut::<DeterminismModule>() {
+            h.random_fds.insert(result);
+        }
+    }
+    result
+}
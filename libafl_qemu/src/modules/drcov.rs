@@ -1,14 +1,39 @@
-use std::{path::PathBuf, sync::Mutex};
+//! The [`DrCovModule`] records the basic blocks executed by the target, and writes them to [`DrCov`](https://dynamorio.org/page_drcov.html)
+//! files, to visualize the coverage in tools such as [Lighthouse](https://github.com/gaasedelen/lighthouse) or
+//! [Cartographer](https://github.com/nccgroup/Cartographer).
+//!
+//! Only the blocks allowed by the address filter (and, in systemmode, the page filter) are recorded.
+//! The cumulative coverage is written to the configured file after each run; with
+//! [`DrCovModuleBuilder::testcase_dir`], the blocks of each run are additionally written to their own file, named
+//! after the input. [`DrCovModule::write_to`] writes the cumulative coverage on demand.
+//!
+//! Every file starts with the module table, so the tools can map the blocks back to the guest binaries.
+//! In usermode it is filled from the guest mappings if not given; in systemmode, set it with
+//! [`DrCovModuleBuilder::module`] or [`DrCovModuleBuilder::module_mapping`], e.g., from the sections of the firmware.
+
+use std::{
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
 
-use hashbrown::{hash_map::Entry, HashMap};
-use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple, HasMetadata};
+use hashbrown::{hash_map::Entry, HashMap, HashSet};
+use libafl::{
+    executors::ExitKind,
+    inputs::{Input, UsesInput},
+    observers::ObserversTuple,
+    HasMetadata,
+};
+#[cfg(feature = "systemmode")]
+use libafl_qemu_sys::GuestPhysAddr;
 use libafl_qemu_sys::{GuestAddr, GuestUsize};
 use libafl_targets::drcov::{DrCovBasicBlock, DrCovWriter};
 use rangemap::RangeMap;
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "systemmode")]
-use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::modules::{PageFilter, StdPageFilter};
 use crate::{
     emu::EmulatorModules,
     modules::{AddressFilter, EmulatorModule, EmulatorModuleTuple, NopAddressFilter},
@@ -40,8 +65,11 @@ libafl_bolts::impl_serdeany!(DrCovMetadata);
 #[derive(Debug)]
 pub struct DrCovModuleBuilder<F> {
     filter: Option<F>,
+    #[cfg(feature = "systemmode")]
+    page_filter: StdPageFilter,
     module_mapping: Option<RangeMap<u64, (u16, String)>>,
     filename: Option<PathBuf>,
+    testcase_dir: Option<PathBuf>,
     full_trace: Option<bool>,
 }
 
@@ -50,59 +78,87 @@ where
     F: AddressFilter,
 {
     pub fn build(self) -> DrCovModule<F> {
-        DrCovModule::new(
+        let mut module = DrCovModule::new(
             self.filter.unwrap(),
             self.filename.unwrap(),
             self.module_mapping,
             self.full_trace.unwrap(),
-        )
+        );
+        #[cfg(feature = "systemmode")]
+        {
+            module.page_filter = self.page_filter;
+        }
+        module.testcase_dir = self.testcase_dir;
+        module
     }
 
     pub fn filter<F2>(self, filter: F2) -> DrCovModuleBuilder<F2> {
         DrCovModuleBuilder {
             filter: Some(filter),
+            #[cfg(feature = "systemmode")]
+            page_filter: self.page_filter,
             module_mapping: self.module_mapping,
             filename: self.filename,
+            testcase_dir: self.testcase_dir,
             full_trace: self.full_trace,
         }
     }
 
+    /// Only records the blocks executed in the paging ids allowed by the `page_filter`
+    #[cfg(feature = "systemmode")]
     #[must_use]
-    pub fn module_mapping(self, module_mapping: RangeMap<u64, (u16, String)>) -> Self {
-        Self {
-            filter: self.filter,
-            module_mapping: Some(module_mapping),
-            filename: self.filename,
-            full_trace: self.full_trace,
-        }
+    pub fn page_filter(mut self, page_filter: StdPageFilter) -> Self {
+        self.page_filter = page_filter;
+        self
     }
 
     #[must_use]
-    pub fn filename(self, filename: PathBuf) -> Self {
-        Self {
-            filter: self.filter,
-            module_mapping: self.module_mapping,
-            filename: Some(filename),
-            full_trace: self.full_trace,
-        }
+    pub fn module_mapping(mut self, module_mapping: RangeMap<u64, (u16, String)>) -> Self {
+        self.module_mapping = Some(module_mapping);
+        self
     }
 
+    /// Adds a guest module to the module table, with the next free id
     #[must_use]
-    pub fn full_trace(self, full_trace: bool) -> Self {
-        Self {
-            filter: self.filter,
-            module_mapping: self.module_mapping,
-            filename: self.filename,
-            full_trace: Some(full_trace),
-        }
+    pub fn module<P>(mut self, path: P, range: Range<u64>) -> Self
+    where
+        P: Into<String>,
+    {
+        let module_mapping = self.module_mapping.get_or_insert_with(RangeMap::new);
+        let id = u16::try_from(module_mapping.iter().count()).unwrap();
+        module_mapping.insert(range, (id, path.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn filename(mut self, filename: PathBuf) -> Self {
+        self.filename = Some(filename);
+        self
+    }
+
+    /// Additionally writes the blocks of each run to a file in `testcase_dir`, named after the input
+    #[must_use]
+    pub fn testcase_dir(mut self, testcase_dir: PathBuf) -> Self {
+        self.testcase_dir = Some(testcase_dir);
+        self
+    }
+
+    #[must_use]
+    pub fn full_trace(mut self, full_trace: bool) -> Self {
+        self.full_trace = Some(full_trace);
+        self
     }
 }
 
 #[derive(Debug)]
 pub struct DrCovModule<F> {
     filter: F,
+    #[cfg(feature = "systemmode")]
+    page_filter: StdPageFilter,
     module_mapping: Option<RangeMap<u64, (u16, String)>>,
     filename: PathBuf,
+    testcase_dir: Option<PathBuf>,
+    run_ids: HashSet<u64>,
     full_trace: bool,
     drcov_len: usize,
 }
@@ -112,9 +168,12 @@ impl DrCovModule<NopAddressFilter> {
     pub fn builder() -> DrCovModuleBuilder<NopAddressFilter> {
         DrCovModuleBuilder {
             filter: Some(NopAddressFilter),
+            #[cfg(feature = "systemmode")]
+            page_filter: StdPageFilter::default(),
             module_mapping: None,
             full_trace: None,
             filename: None,
+            testcase_dir: None,
         }
     }
 }
@@ -134,13 +193,86 @@ impl<F> DrCovModule<F> {
         let _ = DRCOV_LENGTHS.lock().unwrap().insert(HashMap::new());
         Self {
             filter,
+            #[cfg(feature = "systemmode")]
+            page_filter: StdPageFilter::default(),
             module_mapping,
             filename,
+            testcase_dir: None,
+            run_ids: HashSet::new(),
             full_trace,
             drcov_len: 0,
         }
     }
 
+    /// Whether the blocks need to be traced at runtime, not only when they are translated
+    fn traces_exec(&self) -> bool {
+        self.full_trace || self.testcase_dir.is_some()
+    }
+
+    /// The [`DrCovBasicBlock`]s of the given pcs that lie in a known module
+    #[allow(clippy::unnecessary_cast)] // for GuestAddr -> u64
+    fn drcov_blocks<'a, I>(&self, pcs: I) -> Vec<DrCovBasicBlock>
+    where
+        I: IntoIterator<Item = &'a GuestAddr>,
+    {
+        let Some(module_mapping) = self.module_mapping.as_ref() else {
+            return Vec::new();
+        };
+        let lengths_opt = DRCOV_LENGTHS.lock().unwrap();
+        let lengths = lengths_opt.as_ref().unwrap();
+
+        pcs.into_iter()
+            .filter(|pc| module_mapping.contains_key(&(**pc as u64)))
+            .filter_map(|pc| match lengths.get(pc) {
+                Some(block_length) => Some(DrCovBasicBlock::new(
+                    *pc as u64,
+                    *pc as u64 + *block_length as u64,
+                )),
+                None => {
+                    log::info!("Failed to find block length for: {pc:}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the blocks to a `DrCov` file at `path`, with the module table
+    fn write_blocks(&self, path: &Path, blocks: &[DrCovBasicBlock]) {
+        if let Some(module_mapping) = self.module_mapping.as_ref() {
+            DrCovWriter::new(module_mapping)
+                .write(path, blocks)
+                .expect("Failed to write coverage file");
+        }
+    }
+
+    /// Writes all the blocks executed so far to a `DrCov` file at `path`, on demand
+    pub fn write_to<P>(&self, path: P)
+    where
+        P: AsRef<Path>,
+    {
+        let blocks = {
+            let map = DRCOV_MAP.lock().unwrap();
+            self.drcov_blocks(map.as_ref().unwrap().keys())
+        };
+        self.write_blocks(path.as_ref(), &blocks);
+    }
+
+    /// Writes the blocks executed in the last run to a `DrCov` file at `path`
+    fn write_run(&mut self, path: &Path) {
+        let pcs: Vec<GuestAddr> = DRCOV_MAP
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|(_, id)| self.run_ids.contains(*id))
+            .map(|(pc, _)| *pc)
+            .collect();
+        let blocks = self.drcov_blocks(&pcs);
+        self.write_blocks(path, &blocks);
+        self.run_ids.clear();
+    }
+
     pub fn write(&mut self) {
         let lengths_opt = DRCOV_LENGTHS.lock().unwrap();
         let lengths = lengths_opt.as_ref().unwrap();
@@ -249,10 +381,21 @@ impl<F> DrCovModule<F>
 where
     F: AddressFilter,
 {
+    #[cfg(feature = "usermode")]
     #[must_use]
     pub fn must_instrument(&self, addr: GuestAddr) -> bool {
         self.filter.allowed(&addr)
     }
+
+    #[cfg(feature = "systemmode")]
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, page_id: Option<GuestPhysAddr>) -> bool {
+        if let Some(page_id) = page_id {
+            self.filter.allowed(&addr) && self.page_filter.allowed(&page_id)
+        } else {
+            self.filter.allowed(&addr)
+        }
+    }
 }
 
 impl<F, S> EmulatorModule<S> for DrCovModule<F>
@@ -262,7 +405,7 @@ where
 {
     type ModuleAddressFilter = F;
     #[cfg(feature = "systemmode")]
-    type ModulePageFilter = NopPageFilter;
+    type ModulePageFilter = StdPageFilter;
 
     fn post_qemu_init<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
     where
@@ -321,7 +464,7 @@ where
         &mut self,
         _emulator_modules: &mut EmulatorModules<ET, S>,
         _state: &mut S,
-        _input: &S::Input,
+        input: &S::Input,
         _observers: &mut OT,
         _exit_kind: &mut ExitKind,
     ) where
@@ -329,6 +472,15 @@ where
         ET: EmulatorModuleTuple<S>,
     {
         self.write();
+
+        if let Some(testcase_dir) = self.testcase_dir.clone() {
+            if let Err(err) = fs::create_dir_all(&testcase_dir) {
+                log::error!("Failed to create the DrCov directory {testcase_dir:?}: {err}");
+            } else {
+                let path = testcase_dir.join(format!("{}.drcov", input.generate_name(None)));
+                self.write_run(&path);
+            }
+        }
     }
 
     unsafe fn on_crash(&mut self) {
@@ -349,12 +501,12 @@ where
 
     #[cfg(feature = "systemmode")]
     fn page_filter(&self) -> &Self::ModulePageFilter {
-        &NopPageFilter
+        &self.page_filter
     }
 
     #[cfg(feature = "systemmode")]
     fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
-        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+        &mut self.page_filter
     }
 }
 
@@ -368,10 +520,20 @@ where
     S: Unpin + UsesInput + HasMetadata,
     ET: EmulatorModuleTuple<S>,
 {
+    #[cfg(feature = "systemmode")]
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
     let drcov_module = emulator_modules.get::<DrCovModule<F>>().unwrap();
+    #[cfg(feature = "usermode")]
     if !drcov_module.must_instrument(pc) {
         return None;
     }
+    #[cfg(feature = "systemmode")]
+    if !drcov_module.must_instrument(pc, paging_id) {
+        return None;
+    }
 
     let state = state.expect("The gen_unique_block_ids hook works only for in-process fuzzing");
     if state
@@ -386,7 +548,7 @@ where
     match DRCOV_MAP.lock().unwrap().as_mut().unwrap().entry(pc) {
         Entry::Occupied(e) => {
             let id = *e.get();
-            if drcov_module.full_trace {
+            if drcov_module.traces_exec() {
                 Some(id)
             } else {
                 None
@@ -396,7 +558,7 @@ where
             let id = meta.current_id;
             e.insert(id);
             meta.current_id = id + 1;
-            if drcov_module.full_trace {
+            if drcov_module.traces_exec() {
                 // GuestAddress is u32 for 32 bit guests
                 #[allow(clippy::unnecessary_cast)]
                 Some(id as u64)
@@ -417,10 +579,20 @@ pub fn gen_block_lengths<ET, F, S>(
     S: Unpin + UsesInput + HasMetadata,
     ET: EmulatorModuleTuple<S>,
 {
+    #[cfg(feature = "systemmode")]
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
     let drcov_module = emulator_modules.get::<DrCovModule<F>>().unwrap();
+    #[cfg(feature = "usermode")]
     if !drcov_module.must_instrument(pc) {
         return;
     }
+    #[cfg(feature = "systemmode")]
+    if !drcov_module.must_instrument(pc, paging_id) {
+        return;
+    }
     DRCOV_LENGTHS
         .lock()
         .unwrap()
@@ -438,7 +610,11 @@ pub fn exec_trace_block<ET, F, S>(
     ET: EmulatorModuleTuple<S>,
     S: Unpin + UsesInput + HasMetadata,
 {
-    if emulator_modules.get::<DrCovModule<F>>().unwrap().full_trace {
+    let drcov_module = emulator_modules.get_mut::<DrCovModule<F>>().unwrap();
+    if drcov_module.full_trace {
         DRCOV_IDS.lock().unwrap().as_mut().unwrap().push(id);
     }
+    if drcov_module.testcase_dir.is_some() {
+        drcov_module.run_ids.insert(id);
+    }
 }
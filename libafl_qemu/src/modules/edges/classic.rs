@@ -112,6 +112,9 @@ impl Default for StdEdgeCoverageClassicModuleBuilder {
             page_filter: StdPageFilter::default(),
             use_hitcounts: true,
             use_jit: true,
+            map_size: None,
+            use_unique_block_ids: false,
+            thread_maps: 1,
         }
     }
 }
@@ -102,6 +102,9 @@ impl Default for StdEdgeCoverageFullModuleBuilder {
             page_filter: StdPageFilter::default(),
             use_hitcounts: true,
             use_jit: true,
+            map_size: None,
+            use_unique_block_ids: false,
+            thread_maps: 1,
         }
     }
 }
@@ -1,14 +1,17 @@
 use std::ptr;
 
 /// Generators, responsible for generating block/edge ids
-pub use generators::{gen_hashed_block_ids, gen_hashed_edge_ids, gen_unique_edge_ids};
+pub use generators::{
+    gen_hashed_block_ids, gen_hashed_edge_ids, gen_unique_block_ids, gen_unique_edge_ids,
+};
 use hashbrown::HashMap;
 use libafl_qemu_sys::GuestAddr;
 use serde::{Deserialize, Serialize};
 /// Tracers, responsible for propagating an ID in a map.
 pub use tracers::{
-    trace_block_transition_hitcount, trace_block_transition_single, trace_edge_hitcount,
-    trace_edge_hitcount_ptr, trace_edge_single, trace_edge_single_ptr,
+    reset_thread_slots, trace_block_transition_hitcount, trace_block_transition_single,
+    trace_edge_hitcount, trace_edge_hitcount_ptr, trace_edge_single, trace_edge_single_ptr,
+    trace_thread_edge_hitcount, trace_thread_edge_single,
 };
 
 // Constants used for variable-length maps
@@ -25,6 +28,12 @@ pub(super) static mut LIBAFL_QEMU_EDGES_MAP_ALLOCATED_SIZE: usize = 0;
 #[no_mangle]
 pub(super) static mut LIBAFL_QEMU_EDGES_MAP_MASK_MAX: usize = 0;
 
+/// The number of per-thread views the map is split into, see [`super::EdgeCoverageModuleBuilder::thread_maps`]
+pub(super) static mut LIBAFL_QEMU_EDGES_THREAD_MAPS: usize = 1;
+
+/// The size of each per-thread view of the map, a power of two
+pub(super) static mut LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE: usize = 0;
+
 #[cfg_attr(
     any(not(feature = "serdeany_autoreg"), miri),
     allow(clippy::unsafe_derive_deserialize)
@@ -47,6 +56,29 @@ impl QemuEdgesMapMetadata {
     }
 }
 
+/// The ids of the translated blocks, for [`super::EdgeCoverageModuleBuilder::unique_block_ids`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QemuBlocksMapMetadata {
+    pub map: HashMap<GuestAddr, u64>,
+    pub current_id: u64,
+}
+
+libafl_bolts::impl_serdeany!(QemuBlocksMapMetadata);
+
+impl QemuBlocksMapMetadata {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+            current_id: 0,
+        }
+    }
+}
+
 mod generators {
     use std::{cmp::max, ptr};
 
@@ -55,8 +87,8 @@ mod generators {
     use libafl_qemu_sys::GuestAddr;
 
     use super::{
-        super::EdgeCoverageVariant, QemuEdgesMapMetadata, LIBAFL_QEMU_EDGES_MAP_MASK_MAX,
-        LIBAFL_QEMU_EDGES_MAP_SIZE_PTR,
+        super::EdgeCoverageVariant, QemuBlocksMapMetadata, QemuEdgesMapMetadata,
+        LIBAFL_QEMU_EDGES_MAP_MASK_MAX, LIBAFL_QEMU_EDGES_MAP_SIZE_PTR,
     };
     use crate::{
         modules::{hash_me, AddressFilter, EdgeCoverageModule, EmulatorModuleTuple, PageFilter},
@@ -155,6 +187,66 @@ mod generators {
         }
     }
 
+    pub fn gen_unique_block_ids<AF, ET, PF, S, V, const IS_CONST_MAP: bool, const MAP_SIZE: usize>(
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        state: Option<&mut S>,
+        pc: GuestAddr,
+    ) -> Option<u64>
+    where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<S>,
+        PF: PageFilter,
+        S: Unpin + UsesInput + HasMetadata,
+        V: EdgeCoverageVariant<AF, PF, IS_CONST_MAP, MAP_SIZE>,
+    {
+        if let Some(module) =
+            emulator_modules.get::<EdgeCoverageModule<AF, PF, V, IS_CONST_MAP, MAP_SIZE>>()
+        {
+            #[cfg(feature = "usermode")]
+            {
+                if !module.must_instrument(pc) {
+                    return None;
+                }
+            }
+            #[cfg(feature = "systemmode")]
+            {
+                let page_id = emulator_modules
+                    .qemu()
+                    .current_cpu()
+                    .and_then(|cpu| cpu.current_paging_id());
+
+                if !module.must_instrument(pc, page_id) {
+                    return None;
+                }
+            }
+        }
+
+        let mask: usize = get_mask::<IS_CONST_MAP, MAP_SIZE>();
+
+        let state = state.expect("The gen_unique_block_ids hook works only for in-process fuzzing");
+        let meta = state.metadata_or_insert_with(QemuBlocksMapMetadata::new);
+
+        let id = match meta.map.entry(pc) {
+            Entry::Occupied(e) => *e.get(),
+            Entry::Vacant(e) => {
+                let id = meta.current_id;
+                e.insert(id);
+                // Once the map is full, the ids wrap around and collide again
+                meta.current_id = (id + 1) & (mask as u64);
+                id
+            }
+        };
+
+        if !IS_CONST_MAP {
+            unsafe {
+                *LIBAFL_QEMU_EDGES_MAP_SIZE_PTR =
+                    max(*LIBAFL_QEMU_EDGES_MAP_SIZE_PTR, id as usize + 1);
+            }
+        }
+
+        Some(id)
+    }
+
     #[allow(clippy::unnecessary_cast)]
     pub fn gen_hashed_edge_ids<AF, ET, PF, S, V, const IS_CONST_MAP: bool, const MAP_SIZE: usize>(
         emulator_modules: &mut EmulatorModules<ET, S>,
@@ -262,14 +354,82 @@ mod generators {
 }
 
 mod tracers {
-    use std::cell::UnsafeCell;
+    use std::{
+        cell::{Cell, UnsafeCell},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
     use libafl_targets::EDGES_MAP;
 
-    use super::{LIBAFL_QEMU_EDGES_MAP_MASK_MAX, LIBAFL_QEMU_EDGES_MAP_PTR};
+    use super::{
+        LIBAFL_QEMU_EDGES_MAP_MASK_MAX, LIBAFL_QEMU_EDGES_MAP_PTR, LIBAFL_QEMU_EDGES_THREAD_MAPS,
+        LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE,
+    };
 
     thread_local!(static PREV_LOC : UnsafeCell<u64> = const { UnsafeCell::new(0) });
 
+    /// The slot of the current host thread, each guest thread running on its own host thread in usermode
+    thread_local!(static THREAD_SLOT : Cell<Option<usize>> = const { Cell::new(None) });
+
+    /// The slot of the next thread to execute guest code
+    static NEXT_THREAD_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+    /// Gives the calling thread, running the harness, the first slot, and the threads started later the next ones.
+    ///
+    /// Called before each run, so the threads of the target get the same slots in each run.
+    pub fn reset_thread_slots() {
+        THREAD_SLOT.with(|slot| slot.set(Some(0)));
+        NEXT_THREAD_SLOT.store(1, Ordering::Relaxed);
+    }
+
+    /// The map entry of `id` in the view of the current thread
+    fn thread_entry<const IS_CONST_MAP: bool>(id: u64) -> *mut u8 {
+        let slot = THREAD_SLOT.with(|slot| {
+            slot.get().unwrap_or_else(|| {
+                let next = NEXT_THREAD_SLOT.fetch_add(1, Ordering::Relaxed);
+                slot.set(Some(next));
+                next
+            })
+        });
+        unsafe {
+            // Threads beyond the views share them, in turn
+            let view = slot % LIBAFL_QEMU_EDGES_THREAD_MAPS;
+            let idx = view * LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE
+                + (id as usize & (LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE - 1));
+            if IS_CONST_MAP {
+                (&raw mut EDGES_MAP).cast::<u8>().add(idx)
+            } else {
+                LIBAFL_QEMU_EDGES_MAP_PTR.add(idx)
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Increases id in the view of the current thread - potentially racey if called concurrently.
+    pub unsafe extern "C" fn trace_thread_edge_hitcount<const IS_CONST_MAP: bool>(
+        _: *const (),
+        id: u64,
+    ) {
+        unsafe {
+            let entry = thread_entry::<IS_CONST_MAP>(id);
+            *entry = (*entry).wrapping_add(1);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Fine.
+    /// Worst case we set the byte to 1 multiple times.
+    pub unsafe extern "C" fn trace_thread_edge_single<const IS_CONST_MAP: bool>(
+        _: *const (),
+        id: u64,
+    ) {
+        unsafe {
+            *thread_entry::<IS_CONST_MAP>(id) = 1;
+        }
+    }
+
     /// # Safety
     ///
     /// - @id should be the one generated by a gen_* function from this module.
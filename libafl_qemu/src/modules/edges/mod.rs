@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, slice::Chunks};
 
 use libafl::{inputs::UsesInput, observers::VarLenMapObserver, HasMetadata};
 use libafl_bolts::Error;
@@ -9,12 +9,16 @@ use libafl_qemu_sys::GuestPhysAddr;
 use crate::{
     emu::EmulatorModules,
     modules::{AddressFilter, EmulatorModule, EmulatorModuleTuple, PageFilter},
+    Hook,
 };
 
 mod helpers;
 use helpers::{
-    LIBAFL_QEMU_EDGES_MAP_ALLOCATED_SIZE, LIBAFL_QEMU_EDGES_MAP_MASK_MAX,
-    LIBAFL_QEMU_EDGES_MAP_PTR, LIBAFL_QEMU_EDGES_MAP_SIZE_PTR,
+    gen_hashed_edge_ids, gen_unique_block_ids, reset_thread_slots, trace_edge_hitcount,
+    trace_edge_hitcount_ptr, trace_edge_single, trace_edge_single_ptr, trace_thread_edge_hitcount,
+    trace_thread_edge_single, LIBAFL_QEMU_EDGES_MAP_ALLOCATED_SIZE, LIBAFL_QEMU_EDGES_MAP_MASK_MAX,
+    LIBAFL_QEMU_EDGES_MAP_PTR, LIBAFL_QEMU_EDGES_MAP_SIZE_PTR, LIBAFL_QEMU_EDGES_THREAD_MAPS,
+    LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE,
 };
 
 pub mod full;
@@ -42,6 +46,16 @@ pub type StdEdgeCoverageModuleBuilder = StdEdgeCoverageFullModuleBuilder;
 pub type CollidingEdgeCoverageModule<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usize> =
     EdgeCoverageModule<AF, PF, EdgeCoverageChildVariant, IS_CONST_MAP, MAP_SIZE>;
 
+/// What the edge coverage module records in each map entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EdgeCoverageMode {
+    /// Whether the edge was taken
+    Binary,
+    /// How often the edge was taken, like AFL
+    #[default]
+    Hitcounts,
+}
+
 /// An edge coverage module variant.
 trait EdgeCoverageVariant<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usize>:
     'static + Debug
@@ -87,6 +101,66 @@ trait EdgeCoverageVariant<AF, PF, const IS_CONST_MAP: bool, const MAP_SIZE: usiz
     {
         panic!("Func no hitcount is not supported.")
     }
+
+    /// Records block coverage with a distinct id per translated block, the same for all variants
+    fn unique_blocks<ET, S>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        use_hitcounts: bool,
+        use_jit: bool,
+    ) where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<S>,
+        PF: PageFilter,
+        S: Unpin + UsesInput + HasMetadata,
+        Self: Sized,
+    {
+        let gen =
+            Hook::Function(gen_unique_block_ids::<AF, ET, PF, S, Self, IS_CONST_MAP, MAP_SIZE>);
+        if use_jit {
+            let hook_id = emulator_modules.blocks(gen, Hook::Empty, Hook::Empty);
+            let jit: unsafe extern "C" fn(u64, u64) -> usize = if use_hitcounts {
+                libafl_qemu_sys::libafl_jit_trace_edge_hitcount
+            } else {
+                libafl_qemu_sys::libafl_jit_trace_edge_single
+            };
+            unsafe {
+                libafl_qemu_sys::libafl_qemu_block_hook_set_jit(hook_id.0, Some(jit));
+            }
+        } else {
+            // Const maps are not registered with a pointer, they are the static edges map
+            let exec: unsafe extern "C" fn(*const (), u64) = match (use_hitcounts, IS_CONST_MAP) {
+                (true, true) => trace_edge_hitcount,
+                (true, false) => trace_edge_hitcount_ptr,
+                (false, true) => trace_edge_single,
+                (false, false) => trace_edge_single_ptr,
+            };
+            emulator_modules.blocks(gen, Hook::Empty, Hook::Raw(exec));
+        }
+    }
+
+    /// Records hashed edges in the view of the executing thread, the same for all variants
+    fn thread_edges<ET, S>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        use_hitcounts: bool,
+    ) where
+        AF: AddressFilter,
+        ET: EmulatorModuleTuple<S>,
+        PF: PageFilter,
+        S: Unpin + UsesInput + HasMetadata,
+        Self: Sized,
+    {
+        let exec: unsafe extern "C" fn(*const (), u64) = if use_hitcounts {
+            trace_thread_edge_hitcount::<IS_CONST_MAP>
+        } else {
+            trace_thread_edge_single::<IS_CONST_MAP>
+        };
+        emulator_modules.edges(
+            Hook::Function(gen_hashed_edge_ids::<AF, ET, PF, S, Self, IS_CONST_MAP, MAP_SIZE>),
+            Hook::Raw(exec),
+        );
+    }
 }
 
 #[derive(Debug)]
@@ -103,6 +177,9 @@ pub struct EdgeCoverageModuleBuilder<
     page_filter: PF,
     use_hitcounts: bool,
     use_jit: bool,
+    map_size: Option<usize>,
+    use_unique_block_ids: bool,
+    thread_maps: usize,
 }
 
 #[derive(Debug)]
@@ -114,6 +191,8 @@ pub struct EdgeCoverageModule<AF, PF, V, const IS_CONST_MAP: bool, const MAP_SIZ
     page_filter: PF,
     use_hitcounts: bool,
     use_jit: bool,
+    use_unique_block_ids: bool,
+    thread_maps: usize,
 }
 
 impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_SIZE: usize>
@@ -127,13 +206,70 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             );
         };
 
-        Ok(EdgeCoverageModule::new(
+        if let Some(map_size) = self.map_size {
+            if !map_size.is_power_of_two() {
+                return Err(Error::illegal_argument(format!(
+                    "The edges map size must be a power of two, got {map_size}"
+                )));
+            }
+            if IS_CONST_MAP {
+                if map_size != MAP_SIZE {
+                    return Err(Error::illegal_argument(format!(
+                        "The edges map size of a const map is its length {MAP_SIZE}, got {map_size}"
+                    )));
+                }
+            } else {
+                let allocated_size = unsafe { LIBAFL_QEMU_EDGES_MAP_ALLOCATED_SIZE };
+                if map_size > allocated_size {
+                    return Err(Error::illegal_argument(format!(
+                        "The edges map size {map_size} exceeds the map of the observer, of size {allocated_size}"
+                    )));
+                }
+                unsafe {
+                    LIBAFL_QEMU_EDGES_MAP_MASK_MAX = map_size - 1;
+                }
+            }
+        }
+
+        if self.thread_maps > 1 {
+            if !self.thread_maps.is_power_of_two() {
+                return Err(Error::illegal_argument(format!(
+                    "The number of per-thread maps must be a power of two, got {}",
+                    self.thread_maps
+                )));
+            }
+            if self.use_jit || self.use_unique_block_ids {
+                return Err(Error::illegal_argument(
+                    "Per-thread maps are not supported with the JIT or unique block ids",
+                ));
+            }
+            let map_size = if IS_CONST_MAP {
+                MAP_SIZE
+            } else {
+                unsafe { LIBAFL_QEMU_EDGES_MAP_MASK_MAX + 1 }
+            };
+            if self.thread_maps > map_size {
+                return Err(Error::illegal_argument(format!(
+                    "The edges map of size {map_size} cannot be split into {} per-thread maps",
+                    self.thread_maps
+                )));
+            }
+            unsafe {
+                LIBAFL_QEMU_EDGES_THREAD_MAPS = self.thread_maps;
+                LIBAFL_QEMU_EDGES_THREAD_MAP_SIZE = map_size / self.thread_maps;
+            }
+        }
+
+        let mut module = EdgeCoverageModule::new(
             self.address_filter,
             self.page_filter,
             self.variant,
             self.use_hitcounts,
             self.use_jit,
-        ))
+        );
+        module.use_unique_block_ids = self.use_unique_block_ids;
+        module.thread_maps = self.thread_maps;
+        Ok(module)
     }
 }
 
@@ -146,6 +282,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
         page_filter: PF,
         use_hitcounts: bool,
         use_jit: bool,
+        map_size: Option<usize>,
+        use_unique_block_ids: bool,
+        thread_maps: usize,
     ) -> Self {
         Self {
             variant,
@@ -153,6 +292,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             page_filter,
             use_hitcounts,
             use_jit,
+            map_size,
+            use_unique_block_ids,
+            thread_maps,
         }
     }
 
@@ -181,6 +323,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             self.use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 
@@ -198,6 +343,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             self.use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 
@@ -211,6 +359,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             self.use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 
@@ -224,6 +375,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             self.use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 
@@ -237,6 +391,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             page_filter,
             self.use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 
@@ -251,6 +408,81 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             use_hitcounts,
             self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
+        )
+    }
+
+    /// Selects between [binary](EdgeCoverageMode::Binary) and [hitcount](EdgeCoverageMode::Hitcounts) coverage
+    #[must_use]
+    pub fn mode(
+        self,
+        mode: EdgeCoverageMode,
+    ) -> EdgeCoverageModuleBuilder<AF, PF, V, IS_INITIALIZED, IS_CONST_MAP, MAP_SIZE> {
+        self.hitcounts(mode == EdgeCoverageMode::Hitcounts)
+    }
+
+    /// Only uses the first `map_size` entries of the map, a power of two.
+    ///
+    /// By default, the whole map of the observer is used.
+    #[must_use]
+    pub fn map_size(
+        self,
+        map_size: usize,
+    ) -> EdgeCoverageModuleBuilder<AF, PF, V, IS_INITIALIZED, IS_CONST_MAP, MAP_SIZE> {
+        EdgeCoverageModuleBuilder::new(
+            self.variant,
+            self.address_filter,
+            self.page_filter,
+            self.use_hitcounts,
+            self.use_jit,
+            Some(map_size),
+            self.use_unique_block_ids,
+            self.thread_maps,
+        )
+    }
+
+    /// Allocates a distinct map entry to each translated block, instead of hashing the edges of the variant.
+    ///
+    /// This records block coverage without collisions, until the map is full.
+    #[must_use]
+    pub fn unique_block_ids(
+        self,
+        use_unique_block_ids: bool,
+    ) -> EdgeCoverageModuleBuilder<AF, PF, V, IS_INITIALIZED, IS_CONST_MAP, MAP_SIZE> {
+        EdgeCoverageModuleBuilder::new(
+            self.variant,
+            self.address_filter,
+            self.page_filter,
+            self.use_hitcounts,
+            self.use_jit,
+            self.map_size,
+            use_unique_block_ids,
+            self.thread_maps,
+        )
+    }
+
+    /// Splits the map into `thread_maps` views, a power of two, and records the edges of each guest thread in its
+    /// own view, so the threads of multi-threaded targets don't collapse into one noisy map.
+    ///
+    /// The thread running the harness records in the first view, and the threads of the target in the next ones,
+    /// in the order they first execute code. Not supported with the JIT. By default, all threads share the map.
+    #[cfg(feature = "usermode")]
+    #[must_use]
+    pub fn thread_maps(
+        self,
+        thread_maps: usize,
+    ) -> EdgeCoverageModuleBuilder<AF, PF, V, IS_INITIALIZED, IS_CONST_MAP, MAP_SIZE> {
+        EdgeCoverageModuleBuilder::new(
+            self.variant,
+            self.address_filter,
+            self.page_filter,
+            self.use_hitcounts,
+            self.use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            thread_maps.max(1),
         )
     }
 
@@ -265,6 +497,9 @@ impl<AF, PF, V, const IS_INITIALIZED: bool, const IS_CONST_MAP: bool, const MAP_
             self.page_filter,
             self.use_hitcounts,
             use_jit,
+            self.map_size,
+            self.use_unique_block_ids,
+            self.thread_maps,
         )
     }
 }
@@ -286,8 +521,49 @@ impl<AF, PF, V, const IS_CONST_MAP: bool, const MAP_SIZE: usize>
             page_filter,
             use_hitcounts,
             use_jit,
+            use_unique_block_ids: false,
+            thread_maps: 1,
+        }
+    }
+
+    /// The recorded coverage
+    #[must_use]
+    pub fn mode(&self) -> EdgeCoverageMode {
+        if self.use_hitcounts {
+            EdgeCoverageMode::Hitcounts
+        } else {
+            EdgeCoverageMode::Binary
         }
     }
+
+    /// Changes the recorded coverage. Only effective before the first run, when the hooks are installed.
+    pub fn set_mode(&mut self, mode: EdgeCoverageMode) {
+        self.use_hitcounts = mode == EdgeCoverageMode::Hitcounts;
+    }
+
+    /// Whether each translated block gets a distinct map entry
+    #[must_use]
+    pub fn unique_block_ids(&self) -> bool {
+        self.use_unique_block_ids
+    }
+
+    /// The number of per-thread views of the map, 1 if all threads share it
+    #[must_use]
+    pub fn thread_maps(&self) -> usize {
+        self.thread_maps
+    }
+
+    /// The per-thread views of `map`, the map of the observer, in thread order
+    #[must_use]
+    pub fn thread_views<'a>(&self, map: &'a [u8]) -> Chunks<'a, u8> {
+        let map_size = if IS_CONST_MAP {
+            MAP_SIZE
+        } else {
+            unsafe { LIBAFL_QEMU_EDGES_MAP_MASK_MAX + 1 }
+        };
+        let map = &map[..map_size.min(map.len())];
+        map.chunks((map.len() / self.thread_maps).max(1))
+    }
 }
 
 impl<AF, PF, V, const IS_CONST_MAP: bool, const MAP_SIZE: usize>
@@ -331,7 +607,19 @@ where
     where
         ET: EmulatorModuleTuple<S>,
     {
-        if self.use_hitcounts {
+        if self.thread_maps > 1 {
+            // The views cover the whole map
+            if !IS_CONST_MAP {
+                unsafe {
+                    *LIBAFL_QEMU_EDGES_MAP_SIZE_PTR = LIBAFL_QEMU_EDGES_MAP_MASK_MAX + 1;
+                }
+            }
+            self.variant
+                .thread_edges(emulator_modules, self.use_hitcounts);
+        } else if self.use_unique_block_ids {
+            self.variant
+                .unique_blocks(emulator_modules, self.use_hitcounts, self.use_jit);
+        } else if self.use_hitcounts {
             if self.use_jit {
                 self.variant.jit_hitcount(emulator_modules);
             } else {
@@ -344,6 +632,19 @@ where
         }
     }
 
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        if self.thread_maps > 1 {
+            reset_thread_slots();
+        }
+    }
+
     fn address_filter(&self) -> &Self::ModuleAddressFilter {
         &self.address_filter
     }
@@ -0,0 +1,272 @@
+//! Coverage of the targets of indirect branches.
+//!
+//! Edge coverage sees that an indirect call or jump ran, but rarely where it went: a function pointer or a vtable
+//! entry overwritten by the input leads to the same edges as long as the target is already covered. The
+//! [`IndirectBranchCoverageModule`] records each (source, target) pair of indirect calls and jumps in a dedicated
+//! [`MapObserver`], a CFI-like feedback that rewards inputs reaching new targets.
+//!
+//! Finding indirect branches needs to disassemble the translated blocks, and the target of a branch is only
+//! known when the next block runs. Use the address filter to limit the module to the hot ranges of the target,
+//! e.g., the main binary without its libraries.
+
+use core::fmt::Debug;
+
+use capstone::prelude::*;
+use hashbrown::{HashMap, HashSet};
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+};
+use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+use libafl_qemu_sys::GuestAddr;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::{
+    capstone,
+    emu::EmulatorModules,
+    modules::{hash_me, AddressFilter, EmulatorModule, EmulatorModuleTuple, StdAddressFilter},
+    qemu::Hook,
+};
+
+/// A module recording the targets of indirect branches, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct IndirectBranchCoverageModule<O> {
+    observer_handle: Handle<O>,
+    filter: StdAddressFilter,
+    cs: Capstone,
+    /// The indirect branches an instruction hook was set on
+    hooked: HashSet<GuestAddr>,
+    /// The indirect branch that just ran, waiting for its target block
+    pending: Option<GuestAddr>,
+    /// The hit counts of the current run, by (source, target) pair
+    hits: HashMap<(GuestAddr, GuestAddr), u64>,
+}
+
+impl<O> IndirectBranchCoverageModule<O>
+where
+    O: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`IndirectBranchCoverageModule`], recording the indirect branches allowed by `filter` in
+    /// the map `observer`
+    #[must_use]
+    pub fn new(observer: &O, filter: StdAddressFilter) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            filter,
+            cs: capstone().detail(true).build().unwrap(),
+            hooked: HashSet::new(),
+            pending: None,
+            hits: HashMap::new(),
+        }
+    }
+}
+
+impl<O> IndirectBranchCoverageModule<O> {
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    /// The (source, target) pairs of the indirect branches of the current or last run, with their hit counts
+    #[must_use]
+    pub fn targets(&self) -> &HashMap<(GuestAddr, GuestAddr), u64> {
+        &self.hits
+    }
+
+    /// Finds the indirect branch ending the block at `pc`, if any
+    fn find_indirect_branch(&mut self, code: &[u8], pc: GuestAddr) -> Option<GuestAddr> {
+        #[cfg(cpu_target = "arm")]
+        self.cs
+            .set_mode(if pc & 1 == 1 {
+                arch::arm::ArchMode::Thumb.into()
+            } else {
+                arch::arm::ArchMode::Arm.into()
+            })
+            .unwrap();
+
+        let mut offset = 0;
+        while let Ok(insns) =
+            self.cs
+                .disasm_count(&code[offset..], (pc + offset as GuestAddr).into(), 1)
+        {
+            let insn = insns.first()?;
+            let detail = self.cs.insn_detail(insn).ok()?;
+            let groups: Vec<u32> = detail
+                .groups()
+                .iter()
+                .map(|group| u32::from(group.0))
+                .collect();
+            let is_branch = groups.iter().any(|&group| {
+                group == capstone::InsnGroupType::CS_GRP_JUMP
+                    || group == capstone::InsnGroupType::CS_GRP_CALL
+            });
+            if is_branch {
+                // Direct branches encode their target relative to the pc
+                let relative = groups.contains(&capstone::InsnGroupType::CS_GRP_BRANCH_RELATIVE);
+                return (!relative).then_some(insn.address() as GuestAddr);
+            }
+            if groups.iter().any(|&group| {
+                group == capstone::InsnGroupType::CS_GRP_RET
+                    || group == capstone::InsnGroupType::CS_GRP_IRET
+                    || group == capstone::InsnGroupType::CS_GRP_INVALID
+                    || group == capstone::InsnGroupType::CS_GRP_PRIVILEGE
+            }) {
+                return None;
+            }
+
+            offset += insn.bytes().len();
+            if offset >= code.len() {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+impl<O, S> EmulatorModule<S> for IndirectBranchCoverageModule<O>
+where
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_indirect_branch_block::<ET, O, S>),
+            Hook::Empty,
+            Hook::Function(trace_indirect_branch_target::<ET, O, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.pending = None;
+        self.hits.clear();
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let observer = observers
+            .get_mut(&self.observer_handle)
+            .expect("An IndirectBranchCoverageModule needs its map observer");
+        let len = observer.usable_count() as u64;
+        if len == 0 {
+            return;
+        }
+        for (&(src, dst), &hits) in &self.hits {
+            let idx = ((hash_me(src.into()) ^ hash_me(dst.into()).rotate_left(1)) % len) as usize;
+            let hits = hits.min(u64::from(u8::MAX)) as u8;
+            observer.set(idx, observer.get(idx).saturating_add(hits));
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn gen_indirect_branch_block<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<IndirectBranchCoverageModule<O>>()?;
+
+    // Every block may be the target of an indirect branch, only the sources are filtered
+    if h.must_instrument(pc) {
+        #[cfg(feature = "usermode")]
+        let code = unsafe { std::slice::from_raw_parts(qemu.g2h(pc), 512) };
+        #[cfg(feature = "systemmode")]
+        let code = &mut [0; 512];
+        #[cfg(feature = "systemmode")]
+        if let Err(err) = qemu.read_mem(pc, code) {
+            log::error!("gen_indirect_branch_block: Failed to read mem at pc {pc:#x}: {err:?}");
+            return Some(pc.into());
+        }
+
+        if let Some(branch) = h.find_indirect_branch(code, pc) {
+            if h.hooked.insert(branch) {
+                emulator_modules.instruction_function(
+                    branch,
+                    on_indirect_branch::<ET, O, S>,
+                    false,
+                );
+            }
+        }
+    }
+    Some(pc.into())
+}
+
+pub fn on_indirect_branch<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    if let Some(h) = emulator_modules.get_mut::<IndirectBranchCoverageModule<O>>() {
+        h.pending = Some(pc);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn trace_indirect_branch_target<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    if let Some(h) = emulator_modules.get_mut::<IndirectBranchCoverageModule<O>>() {
+        if let Some(src) = h.pending.take() {
+            *h.hits.entry((src, id as GuestAddr)).or_insert(0) += 1;
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! The [`InstructionTimeoutModule`] ends runs that exceed a budget of executed instructions or blocks.
+//!
+//! Wall-clock timeouts depend on the load of the host: the same input may time out on a busy machine and not on
+//! an idle one, and a replay of a timeout may not reproduce it. The budget of this module only depends on the
+//! executed code, so the verdict is the same on every run.
+//!
+//! Blocks are counted when they are executed; for an [`ExecutionBudget::Instructions`] budget, the instructions of
+//! each block are counted once, when it is translated. A block left early, e.g., by a fault, still counts all its
+//! instructions. Once the budget is exhausted, QEMU is stopped and the run ends with [`ExitKind::Timeout`].
+//! In usermode, the harness sees this stop as a breakpoint at the current pc.
+//!
+//! Keep a (larger) wall-clock timeout on the executor as well, for targets hanging outside of translated code.
+
+use capstone::prelude::*;
+use hashbrown::HashMap;
+use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple};
+#[cfg(feature = "systemmode")]
+use libafl_qemu_sys::libafl_exit_request_timeout;
+use libafl_qemu_sys::{GuestAddr, GuestUsize};
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::{
+    capstone,
+    modules::{
+        AddressFilter, EmulatorModule, EmulatorModuleTuple, EmulatorModules, StdAddressFilter,
+    },
+    qemu::Hook,
+};
+
+/// The budget of a run of the [`InstructionTimeoutModule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionBudget {
+    /// The maximum number of executed translation blocks
+    Blocks(u64),
+    /// The maximum number of executed guest instructions
+    Instructions(u64),
+}
+
+impl ExecutionBudget {
+    fn limit(&self) -> u64 {
+        match self {
+            ExecutionBudget::Blocks(limit) | ExecutionBudget::Instructions(limit) => *limit,
+        }
+    }
+}
+
+/// A module ending runs with a [`ExitKind::Timeout`] after a budget of executed code, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct InstructionTimeoutModule {
+    filter: StdAddressFilter,
+    cs: Capstone,
+    budget: ExecutionBudget,
+    /// The number of instructions of each translated block, by pc
+    block_sizes: HashMap<GuestAddr, u64>,
+    executed: u64,
+    exhausted: bool,
+}
+
+impl InstructionTimeoutModule {
+    /// Creates a new [`InstructionTimeoutModule`], counting the code allowed by the `filter` against the `budget`
+    #[must_use]
+    pub fn new(filter: StdAddressFilter, budget: ExecutionBudget) -> Self {
+        Self {
+            filter,
+            cs: capstone().detail(false).build().unwrap(),
+            budget,
+            block_sizes: HashMap::new(),
+            executed: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Creates a new [`InstructionTimeoutModule`], with a budget of `max_instructions` for the whole target
+    #[must_use]
+    pub fn with_instructions(max_instructions: u64) -> Self {
+        Self::new(
+            StdAddressFilter::default(),
+            ExecutionBudget::Instructions(max_instructions),
+        )
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    /// The budget of a run
+    #[must_use]
+    pub fn budget(&self) -> ExecutionBudget {
+        self.budget
+    }
+
+    /// Sets the budget of the next runs
+    pub fn set_budget(&mut self, budget: ExecutionBudget) {
+        self.budget = budget;
+    }
+
+    /// The instructions, or blocks, executed in the current or last run
+    #[must_use]
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Whether the current or last run exhausted its budget
+    #[must_use]
+    pub fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// Counts the instructions in the `code` of the block at `pc`
+    fn count_instructions(&mut self, code: &[u8], pc: GuestAddr) -> u64 {
+        #[cfg(cpu_target = "arm")]
+        self.cs
+            .set_mode(if pc & 1 == 1 {
+                arch::arm::ArchMode::Thumb.into()
+            } else {
+                arch::arm::ArchMode::Arm.into()
+            })
+            .unwrap();
+
+        match self.cs.disasm_all(code, pc.into()) {
+            Ok(insns) if !insns.is_empty() => insns.len() as u64,
+            _ => 1,
+        }
+    }
+}
+
+impl<S> EmulatorModule<S> for InstructionTimeoutModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_block_budget::<ET, S>),
+            Hook::Function(gen_block_instructions::<ET, S>),
+            Hook::Function(exec_block_budget::<ET, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.executed = 0;
+        self.exhausted = false;
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        if self.exhausted && *exit_kind == ExitKind::Ok {
+            *exit_kind = ExitKind::Timeout;
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn gen_block_budget<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get::<InstructionTimeoutModule>().unwrap();
+    if h.must_instrument(pc) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn gen_block_instructions<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    block_length: GuestUsize,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules
+        .get_mut::<InstructionTimeoutModule>()
+        .unwrap();
+    if !h.must_instrument(pc) || !matches!(h.budget, ExecutionBudget::Instructions(_)) {
+        return;
+    }
+
+    #[cfg(feature = "usermode")]
+    let code: &[u8] = unsafe { std::slice::from_raw_parts(qemu.g2h(pc), block_length as usize) };
+    #[cfg(feature = "systemmode")]
+    let code = {
+        let mut code = vec![0; block_length as usize];
+        if let Err(err) = qemu.read_mem(pc, &mut code) {
+            log::error!("gen_block_instructions: Failed to read mem at pc {pc:#x}: {err:?}");
+            return;
+        }
+        code
+    };
+
+    #[allow(clippy::needless_borrow)]
+    let instructions = h.count_instructions(&code, pc);
+    h.block_sizes.insert(pc, instructions);
+}
+
+pub fn exec_block_budget<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    #[cfg(feature = "usermode")]
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules
+        .get_mut::<InstructionTimeoutModule>()
+        .unwrap();
+    if h.exhausted {
+        return;
+    }
+
+    h.executed += match h.budget {
+        ExecutionBudget::Blocks(_) => 1,
+        #[allow(clippy::cast_possible_truncation)]
+        ExecutionBudget::Instructions(_) => *h.block_sizes.get(&(id as GuestAddr)).unwrap_or(&1),
+    };
+    if h.executed > h.budget.limit() {
+        h.exhausted = true;
+        log::debug!("Execution budget of {:?} exhausted", h.budget);
+
+        #[cfg(feature = "systemmode")]
+        unsafe {
+            libafl_exit_request_timeout();
+        }
+        #[cfg(feature = "usermode")]
+        if let Some(cpu) = qemu.current_cpu() {
+            cpu.trigger_breakpoint();
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! The [`MemoryAccessModule`] traces the loads and stores of the target into a map observer.
+//!
+//! Each run, the module collects the regions of guest memory read and written by the target, with the sizes of the
+//! accesses, and marks one entry of a [`MapObserver`] for each distinct region, size and direction. With a
+//! [`libafl::feedbacks::MaxMapFeedback`] on that observer, inputs touching new memory, e.g., new fields of a parsed
+//! structure or new parts of a heap, are interesting. Only the accesses to the data addresses allowed by the data
+//! filter, from the code allowed by the address filter of the module, are traced.
+//!
+//! Targets without ASAN corrupt freed memory silently. For them, the harness can mark freed chunks with
+//! [`MemoryAccessModule::mark_freed`], e.g., from a hook on the `free` function of the target. Accesses to these
+//! chunks are collected as likely use-after-free, and turn the run into a crash if
+//! [`MemoryAccessModule::with_crash_on_freed_access`] is set.
+
+use core::{
+    fmt::{self, Debug},
+    ops::Range,
+};
+use std::collections::BTreeMap;
+
+use hashbrown::HashSet;
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+};
+use libafl_bolts::{
+    hash_std,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use libafl_qemu_sys::GuestAddr;
+#[cfg(feature = "systemmode")]
+use libafl_qemu_sys::GuestPhysAddr;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{PageFilter, StdPageFilter};
+use crate::{
+    emu::EmulatorModules,
+    modules::{AddressFilter, EmulatorModule, EmulatorModuleTuple, StdAddressFilter},
+    qemu::{Hook, MemAccessInfo},
+    sys::TCGTemp,
+};
+
+/// The default log2 of the size of the memory regions told apart by the [`MemoryAccessModule`]
+pub const DEFAULT_REGION_BITS: u32 = 6;
+
+/// The default maximum number of accesses kept per run by the [`MemoryAccessModule`]
+pub const DEFAULT_MAX_ACCESSES: usize = 4096;
+
+/// A load or store of the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemoryAccess {
+    /// The pc of the accessing block
+    pub pc: GuestAddr,
+    /// The accessed address
+    pub addr: GuestAddr,
+    /// The size of the access, in bytes
+    pub size: usize,
+    /// Whether the access is a store
+    pub write: bool,
+}
+
+impl fmt::Display for MemoryAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of size {} at {:#x} from pc {:#x}",
+            if self.write { "write" } else { "read" },
+            self.size,
+            self.addr,
+            self.pc
+        )
+    }
+}
+
+/// A module tracing the memory accesses of the target into a [`MapObserver`], see the [module-level docs](self).
+#[derive(Debug)]
+pub struct MemoryAccessModule<O> {
+    observer_handle: Handle<O>,
+    address_filter: StdAddressFilter,
+    data_filter: StdAddressFilter,
+    #[cfg(feature = "systemmode")]
+    page_filter: StdPageFilter,
+    region_bits: u32,
+    max_accesses: usize,
+    /// The distinct regions, log2 of the sizes and directions of the accesses of the current run
+    regions: HashSet<(GuestAddr, u32, bool)>,
+    accesses: Vec<MemoryAccess>,
+    /// The freed chunks, by start address
+    freed: BTreeMap<GuestAddr, GuestAddr>,
+    freed_accesses: Vec<MemoryAccess>,
+    crash_on_freed_access: bool,
+}
+
+impl<O> MemoryAccessModule<O>
+where
+    O: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`MemoryAccessModule`], tracing the accesses to the addresses allowed by `data_filter`
+    /// into the given map `observer`
+    #[must_use]
+    pub fn new(observer: &O, data_filter: StdAddressFilter) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            address_filter: StdAddressFilter::default(),
+            data_filter,
+            #[cfg(feature = "systemmode")]
+            page_filter: StdPageFilter::default(),
+            region_bits: DEFAULT_REGION_BITS,
+            max_accesses: DEFAULT_MAX_ACCESSES,
+            regions: HashSet::new(),
+            accesses: Vec::new(),
+            freed: BTreeMap::new(),
+            freed_accesses: Vec::new(),
+            crash_on_freed_access: false,
+        }
+    }
+}
+
+impl<O> MemoryAccessModule<O> {
+    /// Only traces the accesses from the code allowed by `filter`
+    #[must_use]
+    pub fn with_address_filter(mut self, filter: StdAddressFilter) -> Self {
+        self.address_filter = filter;
+        self
+    }
+
+    /// Only traces the accesses from the address spaces allowed by `filter`
+    #[cfg(feature = "systemmode")]
+    #[must_use]
+    pub fn with_page_filter(mut self, filter: StdPageFilter) -> Self {
+        self.page_filter = filter;
+        self
+    }
+
+    /// Tells apart memory regions of `1 << region_bits` bytes, [`DEFAULT_REGION_BITS`] by default
+    #[must_use]
+    pub fn with_region_bits(mut self, region_bits: u32) -> Self {
+        self.region_bits = region_bits.min(GuestAddr::BITS - 1);
+        self
+    }
+
+    /// Keeps at most `max_accesses` accesses per run, see [`Self::accesses`]
+    #[must_use]
+    pub fn with_max_accesses(mut self, max_accesses: usize) -> Self {
+        self.max_accesses = max_accesses;
+        self
+    }
+
+    /// Ends the runs accessing freed memory with [`ExitKind::Crash`]
+    #[must_use]
+    pub fn with_crash_on_freed_access(mut self, crash: bool) -> Self {
+        self.crash_on_freed_access = crash;
+        self
+    }
+
+    #[cfg(feature = "usermode")]
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.address_filter.allowed(&addr)
+    }
+
+    #[cfg(feature = "systemmode")]
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, page_id: Option<GuestPhysAddr>) -> bool {
+        if let Some(page_id) = page_id {
+            self.address_filter.allowed(&addr) && self.page_filter.allowed(&page_id)
+        } else {
+            self.address_filter.allowed(&addr)
+        }
+    }
+
+    /// Marks the `size` bytes at `addr` as freed, until they are [allocated](Self::mark_allocated) again
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn mark_freed(&mut self, addr: GuestAddr, size: usize) {
+        self.mark_allocated(addr, size);
+        if size > 0 {
+            self.freed
+                .insert(addr, addr.wrapping_add(size as GuestAddr));
+        }
+    }
+
+    /// Removes the freed chunks overlapping the `size` bytes at `addr`
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn mark_allocated(&mut self, addr: GuestAddr, size: usize) {
+        let end = addr.wrapping_add(size as GuestAddr);
+        self.freed
+            .retain(|&start, &mut chunk_end| chunk_end <= addr || start >= end);
+    }
+
+    /// The freed chunk containing `addr`, if any
+    #[must_use]
+    pub fn freed_chunk(&self, addr: GuestAddr) -> Option<Range<GuestAddr>> {
+        self.freed
+            .range(..=addr)
+            .next_back()
+            .filter(|(_, &end)| addr < end)
+            .map(|(&start, &end)| start..end)
+    }
+
+    /// The accesses of the current run, up to the [maximum](Self::with_max_accesses)
+    #[must_use]
+    pub fn accesses(&self) -> &[MemoryAccess] {
+        &self.accesses
+    }
+
+    /// The accesses to freed memory of the current run
+    #[must_use]
+    pub fn freed_accesses(&self) -> &[MemoryAccess] {
+        &self.freed_accesses
+    }
+
+    /// Records an access of the target
+    fn access(&mut self, pc: GuestAddr, addr: GuestAddr, size: usize, write: bool) {
+        if !self.data_filter.allowed(&addr) {
+            return;
+        }
+        let access = MemoryAccess {
+            pc,
+            addr,
+            size,
+            write,
+        };
+        self.regions.insert((
+            addr >> self.region_bits,
+            size.next_power_of_two().trailing_zeros(),
+            write,
+        ));
+        if self.accesses.len() < self.max_accesses {
+            self.accesses.push(access);
+        }
+        if !self.freed.is_empty() && self.freed_chunk(addr).is_some() {
+            log::warn!("Access to freed memory: {access}");
+            self.freed_accesses.push(access);
+        }
+    }
+}
+
+impl<O, S> EmulatorModule<S> for MemoryAccessModule<O>
+where
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = StdPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.reads(
+            Hook::Function(gen_memory_access::<ET, O, S>),
+            Hook::Function(trace_memory_access::<ET, O, S, 1, false>),
+            Hook::Function(trace_memory_access::<ET, O, S, 2, false>),
+            Hook::Function(trace_memory_access::<ET, O, S, 4, false>),
+            Hook::Function(trace_memory_access::<ET, O, S, 8, false>),
+            Hook::Function(trace_memory_access_n::<ET, O, S, false>),
+        );
+        emulator_modules.writes(
+            Hook::Function(gen_memory_access::<ET, O, S>),
+            Hook::Function(trace_memory_access::<ET, O, S, 1, true>),
+            Hook::Function(trace_memory_access::<ET, O, S, 2, true>),
+            Hook::Function(trace_memory_access::<ET, O, S, 4, true>),
+            Hook::Function(trace_memory_access::<ET, O, S, 8, true>),
+            Hook::Function(trace_memory_access_n::<ET, O, S, true>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.regions.clear();
+        self.accesses.clear();
+        self.freed_accesses.clear();
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let observer = observers
+            .get_mut(&self.observer_handle)
+            .expect("A MemoryAccessModule needs its map observer");
+        let len = observer.usable_count();
+        if len > 0 {
+            for &(region, size_bits, write) in &self.regions {
+                let mut key = u64::from(region).to_le_bytes().to_vec();
+                key.push(size_bits as u8);
+                key.push(u8::from(write));
+                let idx = (hash_std(&key) % len as u64) as usize;
+                observer.set(idx, observer.get(idx).saturating_add(1));
+            }
+        }
+
+        if self.crash_on_freed_access && !self.freed_accesses.is_empty() {
+            *exit_kind = ExitKind::Crash;
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &self.page_filter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        &mut self.page_filter
+    }
+}
+
+pub fn gen_memory_access<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    #[cfg(feature = "systemmode")]
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
+    let h = emulator_modules.get_mut::<MemoryAccessModule<O>>().unwrap();
+
+    #[cfg(feature = "usermode")]
+    let must_instrument = h.must_instrument(pc);
+    #[cfg(feature = "systemmode")]
+    let must_instrument = h.must_instrument(pc, paging_id);
+
+    if must_instrument {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn trace_memory_access<ET, O, S, const N: usize, const WRITE: bool>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<MemoryAccessModule<O>>().unwrap();
+    h.access(id as GuestAddr, addr, N, WRITE);
+}
+
+pub fn trace_memory_access_n<ET, O, S, const WRITE: bool>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<MemoryAccessModule<O>>().unwrap();
+    h.access(id as GuestAddr, addr, size, WRITE);
+}
@@ -4,7 +4,7 @@ use std::cell::UnsafeCell;
 use hashbrown::HashSet;
 use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple};
 use libafl_bolts::tuples::{MatchFirstType, SplitBorrowExtractFirstType};
-use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr, GuestReg};
 
 #[cfg(feature = "usermode")]
 pub mod usermode;
@@ -19,7 +19,7 @@ pub use systemmode::*;
 
 pub mod edges;
 pub use edges::{
-    EdgeCoverageModule, EdgeCoverageModuleBuilder, StdEdgeCoverageChildModule,
+    EdgeCoverageMode, EdgeCoverageModule, EdgeCoverageModuleBuilder, StdEdgeCoverageChildModule,
     StdEdgeCoverageChildModuleBuilder, StdEdgeCoverageClassicModule,
     StdEdgeCoverageClassicModuleBuilder, StdEdgeCoverageFullModule,
     StdEdgeCoverageFullModuleBuilder, StdEdgeCoverageModule, StdEdgeCoverageModuleBuilder,
@@ -28,18 +28,53 @@ pub use edges::{
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod calls;
 #[cfg(not(cpu_target = "hexagon"))]
-pub use calls::CallTracerModule;
+pub use calls::{CallTracerModule, CrashBacktraceCollector, StackDepthCollector};
 
 #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub mod cmplog;
 #[cfg(not(any(cpu_target = "mips", cpu_target = "hexagon")))]
 pub use cmplog::CmpLogModule;
 
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod cmp_split;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use cmp_split::CmpSplitModule;
+
 #[cfg(not(cpu_target = "hexagon"))]
 pub mod drcov;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use drcov::{DrCovMetadata, DrCovModule, DrCovModuleBuilder};
 
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod instruction_timeout;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use instruction_timeout::{ExecutionBudget, InstructionTimeoutModule};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod crash_state;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use crash_state::CrashStateModule;
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod determinism;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use determinism::{DeterminismModule, EntropySeed};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod indirect_branch;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use indirect_branch::IndirectBranchCoverageModule;
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod memory_access;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use memory_access::{MemoryAccess, MemoryAccessModule};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod register_input;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use register_input::{RegisterInput, RegisterInputModule, RegisterInputSpec};
+
 use crate::{emu::EmulatorModules, EmulatorHooks, Qemu};
 
 /// A module for `libafl_qemu`.
@@ -109,6 +144,12 @@ where
     {
     }
 
+    /// Run when the guest sends data to the modules, with `libafl_qemu_send_module_data`.
+    /// Returns whether the module handled the data, recognized by its `tag`.
+    fn on_guest_data(&mut self, _qemu: Qemu, _tag: GuestReg, _data: &[u8]) -> bool {
+        false
+    }
+
     /// # Safety
     ///
     /// This is getting executed in a signal handler.
@@ -122,9 +163,18 @@ where
     fn address_filter(&self) -> &Self::ModuleAddressFilter;
     fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter;
     fn update_address_filter(&mut self, qemu: Qemu, filter: Self::ModuleAddressFilter) {
+        let changed_ranges = self.address_filter().changed_ranges(&filter);
         *self.address_filter_mut() = filter;
         // Necessary because some hooks filter during TB generation.
-        qemu.flush_jit();
+        // Only the blocks of the changed ranges are retranslated, if the filter can tell them.
+        match changed_ranges {
+            Some(ranges) => {
+                for range in ranges {
+                    qemu.invalidate_jit_range(range);
+                }
+            }
+            None => qemu.flush_jit(),
+        }
     }
 
     #[cfg(feature = "systemmode")]
@@ -177,6 +227,9 @@ where
         OT: ObserversTuple<S::Input, S>,
         ET: EmulatorModuleTuple<S>;
 
+    /// Sends the data of the guest to all modules, returns whether any of them handled it
+    fn on_guest_data_all(&mut self, qemu: Qemu, tag: GuestReg, data: &[u8]) -> bool;
+
     /// # Safety
     ///
     /// This is getting executed in a signal handler.
@@ -240,6 +293,10 @@ where
     {
     }
 
+    fn on_guest_data_all(&mut self, _qemu: Qemu, _tag: GuestReg, _data: &[u8]) -> bool {
+        false
+    }
+
     unsafe fn on_crash_all(&mut self) {}
 
     unsafe fn on_timeout_all(&mut self) {}
@@ -311,6 +368,11 @@ where
             .post_exec_all(emulator_modules, state, input, observers, exit_kind);
     }
 
+    fn on_guest_data_all(&mut self, qemu: Qemu, tag: GuestReg, data: &[u8]) -> bool {
+        let handled = self.0.on_guest_data(qemu, tag, data);
+        self.1.on_guest_data_all(qemu, tag, data) || handled
+    }
+
     unsafe fn on_crash_all(&mut self) {
         self.0.on_crash();
         self.1.on_crash_all();
@@ -359,6 +421,17 @@ where
             FilterList::None => true,
         }
     }
+
+    fn changed_ranges(&self, other: &Self) -> Option<Vec<Range<GuestAddr>>> {
+        match (self, other) {
+            (FilterList::AllowList(list), FilterList::AllowList(other_list))
+            | (FilterList::DenyList(list), FilterList::DenyList(other_list)) => {
+                list.changed_ranges(other_list)
+            }
+            (FilterList::None, FilterList::None) => Some(Vec::new()),
+            _ => None,
+        }
+    }
 }
 
 impl<T> PageFilter for FilterList<T>
@@ -423,8 +496,14 @@ impl AddressFilterVec {
 
 impl AddressFilter for AddressFilterVec {
     fn register(&mut self, address_range: Range<GuestAddr>) {
+        let qemu = Qemu::get().unwrap();
+        // The first range restricts an empty list, which allows everything
+        if self.registered_addresses.is_empty() {
+            qemu.flush_jit();
+        } else {
+            qemu.invalidate_jit_range(address_range.clone());
+        }
         self.registered_addresses.push(address_range);
-        Qemu::get().unwrap().flush_jit();
     }
 
     fn allowed(&self, addr: &GuestAddr) -> bool {
@@ -440,6 +519,28 @@ impl AddressFilter for AddressFilterVec {
 
         false
     }
+
+    fn changed_ranges(&self, other: &Self) -> Option<Vec<Range<GuestAddr>>> {
+        match (
+            self.registered_addresses.is_empty(),
+            other.registered_addresses.is_empty(),
+        ) {
+            (true, true) => Some(Vec::new()),
+            (false, false) => {
+                let removed = self
+                    .registered_addresses
+                    .iter()
+                    .filter(|range| !other.registered_addresses.contains(range));
+                let added = other
+                    .registered_addresses
+                    .iter()
+                    .filter(|range| !self.registered_addresses.contains(range));
+                Some(removed.chain(added).cloned().collect())
+            }
+            // An empty list allows everything
+            _ => None,
+        }
+    }
 }
 
 impl AddressFilter for StdAddressFilter {
@@ -450,6 +551,10 @@ impl AddressFilter for StdAddressFilter {
     fn allowed(&self, address: &GuestAddr) -> bool {
         self.0.allowed(address)
     }
+
+    fn changed_ranges(&self, other: &Self) -> Option<Vec<Range<GuestAddr>>> {
+        self.0.changed_ranges(&other.0)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -521,6 +626,17 @@ pub trait AddressFilter: 'static + Debug {
     fn register(&mut self, address_range: Range<GuestAddr>);
 
     fn allowed(&self, address: &GuestAddr) -> bool;
+
+    /// The address ranges that `other` may filter differently, or `None` if they are unknown.
+    ///
+    /// Used when a filter is replaced, to only invalidate the translated blocks of these ranges instead of
+    /// flushing the whole JIT.
+    fn changed_ranges(&self, _other: &Self) -> Option<Vec<Range<GuestAddr>>>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -531,6 +647,10 @@ impl AddressFilter for NopAddressFilter {
     fn allowed(&self, _address: &GuestAddr) -> bool {
         true
     }
+
+    fn changed_ranges(&self, _other: &Self) -> Option<Vec<Range<GuestAddr>>> {
+        Some(Vec::new())
+    }
 }
 
 pub trait PageFilter: 'static + Debug {
@@ -0,0 +1,324 @@
+//! Inject the fuzzer input into guest registers at the entry point of the target.
+//!
+//! Functions of firmware often get their arguments in registers, not in memory. To fuzz them directly, the
+//! [`RegisterInputModule`] writes the leading bytes of the current input to chosen registers each time the
+//! target reaches the entry point, before its first instruction runs. Status registers can be fuzzed too: with a
+//! mask, only the given bits, e.g., the condition flags, are taken from the input.
+//!
+//! The registers are described by a [`RegisterInputSpec`], built in code or parsed from a short string:
+//!
+//! ```ignore
+//! let spec: RegisterInputSpec = "rdi:8, rsi:4, rflags:4&0x8d5".parse()?;
+//! let module = RegisterInputModule::new(entry_addr, spec);
+//! ```
+//!
+//! The remaining bytes of the input, see [`RegisterInputModule::consumed`], can still be written to memory by
+//! the harness.
+
+use core::{fmt, mem::size_of, str::FromStr};
+
+use libafl::{
+    inputs::{HasTargetBytes, UsesInput},
+    Error,
+};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::{GuestAddr, GuestReg};
+use strum::IntoEnumIterator;
+
+#[cfg(feature = "systemmode")]
+use crate::modules::{NopPageFilter, NOP_PAGE_FILTER};
+use crate::{
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple, NopAddressFilter, NOP_ADDRESS_FILTER},
+    Qemu, Regs,
+};
+
+/// A register written from the input, see [`RegisterInputSpec`]
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterInput {
+    reg: Regs,
+    size: usize,
+    mask: GuestReg,
+}
+
+impl RegisterInput {
+    /// Creates a new [`RegisterInput`], writing `size` bytes of the input to `reg`, at most the size of a register
+    #[must_use]
+    pub fn new(reg: Regs, size: usize) -> Self {
+        let size = size.clamp(1, size_of::<GuestReg>());
+        let mask = if size == size_of::<GuestReg>() {
+            GuestReg::MAX
+        } else {
+            (1 << (8 * size)) - 1
+        };
+        Self { reg, size, mask }
+    }
+
+    /// Only takes the bits in `mask` from the input, and keeps the others, e.g., for a status register
+    #[must_use]
+    pub fn mask(mut self, mask: GuestReg) -> Self {
+        self.mask &= mask;
+        self
+    }
+
+    /// The written register
+    #[must_use]
+    pub fn reg(&self) -> Regs {
+        self.reg
+    }
+
+    /// The number of input bytes consumed by this register
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The new value of the register, given its `old` value and its bytes of the input
+    fn value(&self, old: GuestReg, bytes: &[u8]) -> GuestReg {
+        let mut buf = [0; size_of::<GuestReg>()];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        let input = GuestReg::from_le_bytes(buf);
+        (old & !self.mask) | (input & self.mask)
+    }
+}
+
+/// The registers written from the input by a [`RegisterInputModule`], in input order.
+///
+/// Parsed from a comma-separated list of `reg:size`, with an optional `&mask` to take only some bits from the
+/// input, e.g., `"r0:4, r1:4, cpsr:4&0xf0000000"`. Register names are the names of [`Regs`], in any case.
+#[derive(Debug, Clone, Default)]
+pub struct RegisterInputSpec {
+    registers: Vec<RegisterInput>,
+}
+
+impl RegisterInputSpec {
+    /// Creates a new, empty [`RegisterInputSpec`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the next `size` bytes of the input to `reg`
+    #[must_use]
+    pub fn register(mut self, reg: Regs, size: usize) -> Self {
+        self.registers.push(RegisterInput::new(reg, size));
+        self
+    }
+
+    /// Writes the bits in `mask` of the next `size` bytes of the input to the flags register `reg`
+    #[must_use]
+    pub fn flags(mut self, reg: Regs, size: usize, mask: GuestReg) -> Self {
+        self.registers
+            .push(RegisterInput::new(reg, size).mask(mask));
+        self
+    }
+
+    /// The written registers
+    #[must_use]
+    pub fn registers(&self) -> &[RegisterInput] {
+        &self.registers
+    }
+
+    /// The number of input bytes written to registers
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.registers.iter().map(RegisterInput::size).sum()
+    }
+}
+
+impl FromStr for RegisterInputSpec {
+    type Err = Error;
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = Self::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (entry, mask) = match entry.split_once('&') {
+                Some((entry, mask)) => (entry.trim(), Some(parse_int(mask.trim())?)),
+                None => (entry, None),
+            };
+            let (name, size) = entry.split_once(':').ok_or_else(|| {
+                Error::illegal_argument(format!("Expected reg:size, got {entry}"))
+            })?;
+            let reg = Regs::iter()
+                .find(|reg| format!("{reg:?}").eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| Error::illegal_argument(format!("Unknown register {name}")))?;
+            let size = parse_int(size.trim())? as usize;
+            if size == 0 || size > size_of::<GuestReg>() {
+                return Err(Error::illegal_argument(format!(
+                    "Invalid size {size} for register {name}"
+                )));
+            }
+            let input = RegisterInput::new(reg, size);
+            spec.registers
+                .push(mask.map_or(input, |mask| input.mask(mask)));
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for RegisterInputSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, input) in self.registers.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}:{}&{:#x}", input.reg, input.size, input.mask)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer
+fn parse_int(s: &str) -> Result<GuestReg, Error> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => GuestReg::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| Error::illegal_argument(format!("Invalid number {s}")))
+}
+
+/// A module writing the input to guest registers at the entry point, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct RegisterInputModule {
+    entry: GuestAddr,
+    spec: RegisterInputSpec,
+    input_offset: usize,
+    /// The register bytes of the current input
+    input: Vec<u8>,
+}
+
+impl RegisterInputModule {
+    /// Creates a new [`RegisterInputModule`], writing the registers of `spec` at `entry`
+    #[must_use]
+    pub fn new(entry: GuestAddr, spec: RegisterInputSpec) -> Self {
+        Self {
+            entry,
+            spec,
+            input_offset: 0,
+            input: Vec::new(),
+        }
+    }
+
+    /// Skips the first `offset` bytes of the input, e.g., if the harness uses them itself
+    #[must_use]
+    pub fn input_offset(mut self, offset: usize) -> Self {
+        self.input_offset = offset;
+        self
+    }
+
+    /// The entry point, at which the registers are written
+    #[must_use]
+    pub fn entry(&self) -> GuestAddr {
+        self.entry
+    }
+
+    /// The registers written from the input
+    #[must_use]
+    pub fn spec(&self) -> &RegisterInputSpec {
+        &self.spec
+    }
+
+    /// The number of input bytes consumed by the registers, including the skipped ones
+    #[must_use]
+    pub fn consumed(&self) -> usize {
+        self.input_offset + self.spec.size()
+    }
+
+    /// Writes the registers of the spec on the current CPU
+    fn inject(&self, qemu: Qemu) {
+        let Some(cpu) = qemu.current_cpu() else {
+            log::warn!("No CPU to write the register input to");
+            return;
+        };
+        let mut cursor = 0;
+        for input in &self.spec.registers {
+            let end = (cursor + input.size).min(self.input.len());
+            let bytes = &self.input[cursor.min(end)..end];
+            cursor += input.size;
+            let old = if input.mask == GuestReg::MAX {
+                0
+            } else {
+                match cpu.read_reg(input.reg) {
+                    Ok(old) => old,
+                    Err(err) => {
+                        log::warn!("Failed to read {:?}: {err:?}", input.reg);
+                        continue;
+                    }
+                }
+            };
+            if let Err(err) = cpu.write_reg(input.reg, input.value(old, bytes)) {
+                log::warn!("Failed to write {:?}: {err:?}", input.reg);
+            }
+        }
+    }
+}
+
+impl<S> EmulatorModule<S> for RegisterInputModule
+where
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+    #[cfg(feature = "systemmode")]
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.instruction_function(self.entry, on_register_input_entry::<ET, S>, true);
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let bytes = input.target_bytes();
+        let bytes = bytes.as_slice();
+        let start = self.input_offset.min(bytes.len());
+        let end = self.consumed().min(bytes.len());
+        self.input.clear();
+        self.input.extend_from_slice(&bytes[start..end]);
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    #[cfg(feature = "systemmode")]
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn on_register_input_entry<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get::<RegisterInputModule>().unwrap();
+    h.inject(qemu);
+}
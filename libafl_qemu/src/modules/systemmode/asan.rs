@@ -0,0 +1,381 @@
+//! Shadow-memory based ASAN for full-system targets.
+//!
+//! Firmware and kernels come with their own allocators, so heap bugs in them usually corrupt memory silently
+//! and only surface as crashes much later, if ever. The [`AsanSystemModule`] keeps a shadow of the guest memory
+//! on the host, and checks every load and store of the target against it, from memory hooks inserted at TCG level.
+//!
+//! The guest memory is accessible unless the harness poisons it, with the `libafl_qemu_asan_poison` and
+//! `libafl_qemu_asan_unpoison` hypercalls of `libafl_qemu.h`, e.g., for the redzones of its allocations
+//! and for freed chunks. Addresses are guest virtual addresses; restrict the module to a single address space with
+//! its page filter if the target runs several.
+//!
+//! Invalid accesses do not stop the target, they are collected and turn the execution into a crash once it ends.
+//! The shadow is rolled back at the end of each run, to match the guest memory restored from the snapshot.
+
+use core::fmt;
+
+use hashbrown::HashMap;
+use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple};
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        AddressFilter, EmulatorModule, EmulatorModuleTuple, PageFilter, StdAddressFilter,
+        StdPageFilter,
+    },
+    qemu::{Hook, MemAccessInfo},
+    sys::TCGTemp,
+};
+
+/// The size of the guest memory covered by one page of the shadow
+pub const ASAN_SHADOW_PAGE_SIZE: usize = 4096;
+
+/// The maximum number of invalid accesses kept per run
+const MAX_VIOLATIONS: usize = 64;
+
+type ShadowPage = Box<[u8; ASAN_SHADOW_PAGE_SIZE / 8]>;
+
+/// The shadow of the guest memory, one bit per byte, set for poisoned bytes.
+///
+/// Only pages with poisoned bytes are kept.
+#[derive(Debug, Default, Clone)]
+pub struct AsanShadow {
+    pages: HashMap<GuestAddr, ShadowPage>,
+}
+
+impl AsanShadow {
+    /// Creates an empty shadow, with the whole memory accessible
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `f` with the page, and the offset and length within the page, of each page overlapping the range
+    #[allow(clippy::cast_possible_truncation)]
+    fn for_each_page<F>(addr: GuestAddr, size: usize, mut f: F)
+    where
+        F: FnMut(GuestAddr, usize, usize),
+    {
+        let mut addr = addr;
+        let mut remaining = size;
+        while remaining > 0 {
+            let offset = (addr as usize) % ASAN_SHADOW_PAGE_SIZE;
+            let len = remaining.min(ASAN_SHADOW_PAGE_SIZE - offset);
+            f(addr - offset as GuestAddr, offset, len);
+            remaining -= len;
+            addr = addr.wrapping_add(len as GuestAddr);
+        }
+    }
+
+    /// Poisons `size` bytes starting at `addr`
+    pub fn poison(&mut self, addr: GuestAddr, size: usize) {
+        Self::for_each_page(addr, size, |page, offset, len| {
+            let bits = self
+                .pages
+                .entry(page)
+                .or_insert_with(|| Box::new([0; ASAN_SHADOW_PAGE_SIZE / 8]));
+            for i in offset..offset + len {
+                bits[i / 8] |= 1 << (i % 8);
+            }
+        });
+    }
+
+    /// Unpoisons `size` bytes starting at `addr`
+    pub fn unpoison(&mut self, addr: GuestAddr, size: usize) {
+        Self::for_each_page(addr, size, |page, offset, len| {
+            if let Some(bits) = self.pages.get_mut(&page) {
+                for i in offset..offset + len {
+                    bits[i / 8] &= !(1 << (i % 8));
+                }
+                if bits.iter().all(|b| *b == 0) {
+                    self.pages.remove(&page);
+                }
+            }
+        });
+    }
+
+    /// The first poisoned byte of the `size` bytes starting at `addr`, if any
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn first_poisoned(&self, addr: GuestAddr, size: usize) -> Option<GuestAddr> {
+        if self.pages.is_empty() {
+            return None;
+        }
+        let mut poisoned = None;
+        Self::for_each_page(addr, size, |page, offset, len| {
+            if poisoned.is_some() {
+                return;
+            }
+            if let Some(bits) = self.pages.get(&page) {
+                poisoned = (offset..offset + len)
+                    .find(|i| bits[i / 8] & (1 << (i % 8)) != 0)
+                    .map(|i| page + i as GuestAddr);
+            }
+        });
+        poisoned
+    }
+
+    /// Whether any of the `size` bytes starting at `addr` is poisoned
+    #[must_use]
+    pub fn is_poisoned(&self, addr: GuestAddr, size: usize) -> bool {
+        self.first_poisoned(addr, size).is_some()
+    }
+}
+
+/// An invalid access of the target, found by the [`AsanSystemModule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsanViolation {
+    /// The program counter of the access
+    pub pc: GuestAddr,
+    /// The first poisoned byte accessed
+    pub addr: GuestAddr,
+    /// The size of the access
+    pub size: usize,
+    /// Whether the access is a store
+    pub write: bool,
+}
+
+impl fmt::Display for AsanViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.write { "write" } else { "read" };
+        write!(
+            f,
+            "Invalid {} bytes {kind} at {:#x} (pc {:#x})",
+            self.size, self.addr, self.pc
+        )
+    }
+}
+
+/// An ASAN module for QEMU systemmode, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct AsanSystemModule {
+    shadow: AsanShadow,
+    snapshot: Option<AsanShadow>,
+    violations: Vec<AsanViolation>,
+    address_filter: StdAddressFilter,
+    page_filter: StdPageFilter,
+}
+
+impl Default for AsanSystemModule {
+    fn default() -> Self {
+        Self::new(StdAddressFilter::default(), StdPageFilter::default())
+    }
+}
+
+impl AsanSystemModule {
+    /// Creates a new [`AsanSystemModule`], checking the accesses of the code allowed by the filters
+    #[must_use]
+    pub fn new(address_filter: StdAddressFilter, page_filter: StdPageFilter) -> Self {
+        Self {
+            shadow: AsanShadow::new(),
+            snapshot: None,
+            violations: Vec::new(),
+            address_filter,
+            page_filter,
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, page_id: Option<GuestPhysAddr>) -> bool {
+        if let Some(page_id) = page_id {
+            self.address_filter.allowed(&addr) && self.page_filter.allowed(&page_id)
+        } else {
+            self.address_filter.allowed(&addr)
+        }
+    }
+
+    /// The shadow of the guest memory
+    #[must_use]
+    pub fn shadow(&self) -> &AsanShadow {
+        &self.shadow
+    }
+
+    /// Poisons `size` bytes of guest memory starting at `addr`
+    pub fn poison(&mut self, addr: GuestAddr, size: usize) {
+        self.shadow.poison(addr, size);
+    }
+
+    /// Unpoisons `size` bytes of guest memory starting at `addr`
+    pub fn unpoison(&mut self, addr: GuestAddr, size: usize) {
+        self.shadow.unpoison(addr, size);
+    }
+
+    /// The invalid accesses of the current run
+    #[must_use]
+    pub fn violations(&self) -> &[AsanViolation] {
+        &self.violations
+    }
+
+    /// Checks an access of the target against the shadow
+    pub fn check(&mut self, pc: GuestAddr, addr: GuestAddr, size: usize, write: bool) {
+        if let Some(addr) = self.shadow.first_poisoned(addr, size) {
+            let violation = AsanViolation {
+                pc,
+                addr,
+                size,
+                write,
+            };
+            if self.violations.len() < MAX_VIOLATIONS {
+                log::error!("ASAN: {violation}");
+                self.violations.push(violation);
+            }
+        }
+    }
+}
+
+impl<S> EmulatorModule<S> for AsanSystemModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = StdPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.reads(
+            Hook::Function(gen_readwrite_asan_system::<ET, S>),
+            Hook::Function(trace_read_asan_system::<ET, S, 1>),
+            Hook::Function(trace_read_asan_system::<ET, S, 2>),
+            Hook::Function(trace_read_asan_system::<ET, S, 4>),
+            Hook::Function(trace_read_asan_system::<ET, S, 8>),
+            Hook::Function(trace_read_n_asan_system::<ET, S>),
+        );
+        emulator_modules.writes(
+            Hook::Function(gen_readwrite_asan_system::<ET, S>),
+            Hook::Function(trace_write_asan_system::<ET, S, 1>),
+            Hook::Function(trace_write_asan_system::<ET, S, 2>),
+            Hook::Function(trace_write_asan_system::<ET, S, 4>),
+            Hook::Function(trace_write_asan_system::<ET, S, 8>),
+            Hook::Function(trace_write_n_asan_system::<ET, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        if self.snapshot.is_none() {
+            self.snapshot = Some(self.shadow.clone());
+        }
+        self.violations.clear();
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        _observers: &mut OT,
+        exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        if !self.violations.is_empty() {
+            *exit_kind = ExitKind::Crash;
+        }
+        if let Some(snapshot) = &self.snapshot {
+            self.shadow.clone_from(snapshot);
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &self.page_filter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        &mut self.page_filter
+    }
+}
+
+pub fn gen_readwrite_asan_system<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
+    let h = emulator_modules.get_mut::<AsanSystemModule>().unwrap();
+    if h.must_instrument(pc, paging_id) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn trace_read_asan_system<ET, S, const N: usize>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<AsanSystemModule>().unwrap();
+    h.check(id as GuestAddr, addr, N, false);
+}
+
+pub fn trace_read_n_asan_system<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<AsanSystemModule>().unwrap();
+    h.check(id as GuestAddr, addr, size, false);
+}
+
+pub fn trace_write_asan_system<ET, S, const N: usize>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<AsanSystemModule>().unwrap();
+    h.check(id as GuestAddr, addr, N, true);
+}
+
+pub fn trace_write_n_asan_system<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get_mut::<AsanSystemModule>().unwrap();
+    h.check(id as GuestAddr, addr, size, true);
+}
@@ -0,0 +1,288 @@
+//! Serve the reads of emulated device registers from the fuzzer input.
+//!
+//! Drivers and hypervisors trust the values read from their devices. To fuzz this attack surface, the
+//! [`DeviceInputModule`] turns every load of the target from a configured device window, e.g., the MMIO registers
+//! of a virtio device, into the next bytes of the current input. Before the load executes, the module writes the
+//! bytes to the accessed address, so the load returns them. DMA buffers, such as virtio descriptor rings, are
+//! filled from the input at the start of each run.
+//!
+//! The device window must be backed by RAM for the written bytes to be read back, e.g., with a RAM region mapped
+//! over the window of a device removed from the machine. Port I/O is not a memory access and is not intercepted.
+//! Once the input is exhausted, reads return zeroes.
+
+use core::ops::Range;
+
+use libafl::{
+    executors::ExitKind,
+    inputs::{HasTargetBytes, UsesInput},
+    observers::ObserversTuple,
+};
+use libafl_bolts::AsSlice;
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        AddressFilter, EmulatorModule, EmulatorModuleTuple, PageFilter, StdAddressFilter,
+        StdPageFilter,
+    },
+    qemu::{Hook, MemAccessInfo},
+    sys::TCGTemp,
+    Qemu,
+};
+
+/// A module serving the device reads of the target from the input, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct DeviceInputModule {
+    address_filter: StdAddressFilter,
+    page_filter: StdPageFilter,
+    /// The device windows, as guest virtual addresses
+    windows: Vec<Range<GuestAddr>>,
+    /// The DMA buffers filled at the start of each run
+    dma_buffers: Vec<Range<GuestAddr>>,
+    input_offset: usize,
+    input: Vec<u8>,
+    cursor: usize,
+    reads: u64,
+}
+
+impl DeviceInputModule {
+    /// Creates a new [`DeviceInputModule`], serving the reads from the device `window`
+    #[must_use]
+    pub fn new(window: Range<GuestAddr>) -> Self {
+        Self {
+            address_filter: StdAddressFilter::default(),
+            page_filter: StdPageFilter::default(),
+            windows: vec![window],
+            dma_buffers: Vec::new(),
+            input_offset: 0,
+            input: Vec::new(),
+            cursor: 0,
+            reads: 0,
+        }
+    }
+
+    /// Also serves the reads from the device `window`
+    #[must_use]
+    pub fn window(mut self, window: Range<GuestAddr>) -> Self {
+        self.windows.push(window);
+        self
+    }
+
+    /// Fills the DMA `buffer` from the input at the start of each run, before the device reads
+    #[must_use]
+    pub fn dma_buffer(mut self, buffer: Range<GuestAddr>) -> Self {
+        self.dma_buffers.push(buffer);
+        self
+    }
+
+    /// Skips the first `offset` bytes of the input, e.g., if the harness uses them itself
+    #[must_use]
+    pub fn input_offset(mut self, offset: usize) -> Self {
+        self.input_offset = offset;
+        self
+    }
+
+    /// Only serves the reads of the code allowed by `filter`
+    #[must_use]
+    pub fn with_address_filter(mut self, filter: StdAddressFilter) -> Self {
+        self.address_filter = filter;
+        self
+    }
+
+    /// Only serves the reads of the address spaces allowed by `filter`
+    #[must_use]
+    pub fn with_page_filter(mut self, filter: StdPageFilter) -> Self {
+        self.page_filter = filter;
+        self
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, page_id: Option<GuestPhysAddr>) -> bool {
+        if let Some(page_id) = page_id {
+            self.address_filter.allowed(&addr) && self.page_filter.allowed(&page_id)
+        } else {
+            self.address_filter.allowed(&addr)
+        }
+    }
+
+    /// Whether `addr` is in a device window
+    #[must_use]
+    pub fn in_window(&self, addr: GuestAddr) -> bool {
+        self.windows.iter().any(|window| window.contains(&addr))
+    }
+
+    /// The number of device reads served in the current or last run
+    #[must_use]
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// The number of input bytes consumed in the current or last run
+    #[must_use]
+    pub fn consumed(&self) -> usize {
+        self.cursor
+    }
+
+    /// The next `size` bytes of the input, padded with zeroes
+    fn next_bytes(&mut self, size: usize) -> Vec<u8> {
+        let mut bytes = vec![0; size];
+        let available = self.input.len().saturating_sub(self.cursor).min(size);
+        bytes[..available].copy_from_slice(&self.input[self.cursor..self.cursor + available]);
+        self.cursor += available;
+        bytes
+    }
+
+    /// Serves a read of `size` bytes at `addr` from the input
+    fn serve(&mut self, qemu: Qemu, addr: GuestAddr, size: usize) {
+        if !self.in_window(addr) {
+            return;
+        }
+        let bytes = self.next_bytes(size);
+        if let Err(err) = qemu.write_mem(addr, &bytes) {
+            log::warn!("Failed to serve the device read at {addr:#x}: {err:?}");
+        }
+        self.reads += 1;
+    }
+}
+
+impl<S> EmulatorModule<S> for DeviceInputModule
+where
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = StdPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.reads(
+            Hook::Function(gen_device_read::<ET, S>),
+            Hook::Function(trace_device_read::<ET, S, 1>),
+            Hook::Function(trace_device_read::<ET, S, 2>),
+            Hook::Function(trace_device_read::<ET, S, 4>),
+            Hook::Function(trace_device_read::<ET, S, 8>),
+            Hook::Function(trace_device_read_n::<ET, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let bytes = input.target_bytes();
+        let bytes = bytes.as_slice();
+        self.input.clear();
+        self.input
+            .extend_from_slice(&bytes[self.input_offset.min(bytes.len())..]);
+        self.cursor = 0;
+        self.reads = 0;
+
+        let qemu = emulator_modules.qemu();
+        for i in 0..self.dma_buffers.len() {
+            let buffer = self.dma_buffers[i].clone();
+            let bytes = self.next_bytes((buffer.end - buffer.start) as usize);
+            if let Err(err) = qemu.write_mem(buffer.start, &bytes) {
+                log::warn!(
+                    "Failed to fill the DMA buffer at {:#x}: {err:?}",
+                    buffer.start
+                );
+            }
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        _observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        log::trace!(
+            "Served {} device reads with {} input bytes",
+            self.reads,
+            self.cursor
+        );
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &self.page_filter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        &mut self.page_filter
+    }
+}
+
+pub fn gen_device_read<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+    _addr: *mut TCGTemp,
+    _info: MemAccessInfo,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
+    let h = emulator_modules.get_mut::<DeviceInputModule>().unwrap();
+    if h.must_instrument(pc, paging_id) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn trace_device_read<ET, S, const N: usize>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<DeviceInputModule>().unwrap();
+    h.serve(qemu, addr, N);
+}
+
+pub fn trace_device_read_n<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+    addr: GuestAddr,
+    size: usize,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+    S::Input: HasTargetBytes,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<DeviceInputModule>().unwrap();
+    h.serve(qemu, addr, size);
+}
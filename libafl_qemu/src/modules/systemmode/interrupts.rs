@@ -0,0 +1,295 @@
+//! Deterministic interrupt injection for full-system targets.
+//!
+//! Firmware spends much of its time in interrupt handlers, and its races are between these handlers and the main
+//! loop. Under fuzzing, devices rarely raise interrupts at all, and if they do, at points depending on the load of
+//! the host. The [`InterruptModule`] raises interrupt lines of the CPU at chosen points of the execution instead:
+//! every few executed blocks, once after some blocks, or when the target reaches an address. The same input
+//! always gets the same interrupts at the same points.
+//!
+//! Interrupts are raised with the target-specific `CPU_INTERRUPT_*` masks of QEMU, see [`CPU_INTERRUPT_HARD`].
+//! A raised line is lowered again after the configured number of blocks, so the target takes one interrupt
+//! per injection, as with a pulse of the interrupt line of a timer.
+
+use libafl::{executors::ExitKind, inputs::UsesInput, observers::ObserversTuple};
+use libafl_qemu_sys::GuestAddr;
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        AddressFilter, EmulatorModule, EmulatorModuleTuple, NopPageFilter, StdAddressFilter,
+        NOP_PAGE_FILTER,
+    },
+    qemu::Hook,
+    Qemu,
+};
+
+/// The external interrupt line of the CPU, e.g., IRQ on ARM, or the line of the interrupt controller on x86
+pub const CPU_INTERRUPT_HARD: i32 = 0x0002;
+/// The first target-specific external interrupt line, e.g., SMI on x86
+pub const CPU_INTERRUPT_TGT_EXT_0: i32 = 0x0008;
+/// The second target-specific external interrupt line, e.g., FIQ on ARM
+pub const CPU_INTERRUPT_TGT_EXT_1: i32 = 0x0010;
+/// The third target-specific external interrupt line, e.g., VIRQ on ARM
+pub const CPU_INTERRUPT_TGT_EXT_2: i32 = 0x0040;
+/// The fourth target-specific external interrupt line, e.g., NMI on x86
+pub const CPU_INTERRUPT_TGT_EXT_3: i32 = 0x0200;
+
+/// When an [`InterruptInjection`] fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptTrigger {
+    /// Every given number of executed blocks
+    EveryBlocks(u64),
+    /// Once, after the given number of executed blocks
+    AfterBlocks(u64),
+    /// Each time the target reaches the address
+    AtAddr(GuestAddr),
+}
+
+/// An interrupt raised by the [`InterruptModule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptInjection {
+    trigger: InterruptTrigger,
+    mask: i32,
+    duration: u64,
+}
+
+impl InterruptInjection {
+    /// Creates a new [`InterruptInjection`], raising the lines in `mask` on `trigger`, for one block
+    #[must_use]
+    pub fn new(trigger: InterruptTrigger, mask: i32) -> Self {
+        Self {
+            trigger,
+            mask,
+            duration: 1,
+        }
+    }
+
+    /// A timer tick on the external interrupt line, every `blocks` executed blocks
+    #[must_use]
+    pub fn timer(blocks: u64) -> Self {
+        Self::new(InterruptTrigger::EveryBlocks(blocks), CPU_INTERRUPT_HARD)
+    }
+
+    /// Keeps the lines raised for `blocks` executed blocks, e.g., until the guest unmasks the interrupt
+    #[must_use]
+    pub fn duration(mut self, blocks: u64) -> Self {
+        self.duration = blocks.max(1);
+        self
+    }
+
+    /// When the interrupt is raised
+    #[must_use]
+    pub fn trigger(&self) -> InterruptTrigger {
+        self.trigger
+    }
+
+    /// The raised interrupt lines
+    #[must_use]
+    pub fn mask(&self) -> i32 {
+        self.mask
+    }
+}
+
+/// A module raising interrupts at chosen points of the execution, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct InterruptModule {
+    filter: StdAddressFilter,
+    injections: Vec<InterruptInjection>,
+    /// The blocks executed in the current run
+    blocks: u64,
+    /// The raised lines, and the block after which they are lowered
+    raised: Vec<(i32, u64)>,
+    injected: u64,
+}
+
+impl InterruptModule {
+    /// Creates a new [`InterruptModule`], counting the blocks allowed by the `filter` for its `injections`
+    #[must_use]
+    pub fn new(filter: StdAddressFilter, injections: Vec<InterruptInjection>) -> Self {
+        Self {
+            filter,
+            injections,
+            blocks: 0,
+            raised: Vec::new(),
+            injected: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr) -> bool {
+        self.filter.allowed(&addr)
+    }
+
+    /// The injected interrupts
+    #[must_use]
+    pub fn injections(&self) -> &[InterruptInjection] {
+        &self.injections
+    }
+
+    /// Replaces the interrupts injected in the next runs, e.g., with interrupts derived from the input.
+    ///
+    /// Interrupts at new addresses only fire if they were also configured before the first run.
+    pub fn set_injections(&mut self, injections: Vec<InterruptInjection>) {
+        self.injections = injections;
+    }
+
+    /// The number of interrupts injected in the current or last run
+    #[must_use]
+    pub fn injected(&self) -> u64 {
+        self.injected
+    }
+
+    /// Raises the lines in `mask` on the current CPU, for `duration` blocks
+    fn raise(&mut self, qemu: Qemu, mask: i32, duration: u64) {
+        if let Some(cpu) = qemu.current_cpu() {
+            log::trace!("Raising interrupt {mask:#x} after {} blocks", self.blocks);
+            cpu.interrupt(mask);
+            self.raised.push((mask, self.blocks + duration));
+            self.injected += 1;
+        }
+    }
+
+    /// Lowers the lines raised until block `until`, or all of them
+    fn lower(&mut self, qemu: Qemu, until: Option<u64>) {
+        let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+        self.raised.retain(|&(mask, end)| {
+            if until.is_some_and(|until| end > until) {
+                true
+            } else {
+                cpu.reset_interrupt(mask);
+                false
+            }
+        });
+    }
+}
+
+impl<S> EmulatorModule<S> for InterruptModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = NopPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_interrupt_block::<ET, S>),
+            Hook::Empty,
+            Hook::Function(exec_interrupt_block::<ET, S>),
+        );
+        for injection in &self.injections {
+            if let InterruptTrigger::AtAddr(addr) = injection.trigger {
+                emulator_modules.instruction_function(addr, on_interrupt_addr::<ET, S>, true);
+            }
+        }
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.lower(emulator_modules.qemu(), None);
+        self.blocks = 0;
+        self.injected = 0;
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        _observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.lower(emulator_modules.qemu(), None);
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &NopPageFilter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        unsafe { (&raw mut NOP_PAGE_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+pub fn gen_interrupt_block<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let h = emulator_modules.get::<InterruptModule>().unwrap();
+    if h.must_instrument(pc) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+pub fn exec_interrupt_block<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    _id: u64,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<InterruptModule>().unwrap();
+    h.blocks += 1;
+    if !h.raised.is_empty() {
+        h.lower(qemu, Some(h.blocks));
+    }
+
+    let blocks = h.blocks;
+    for i in 0..h.injections.len() {
+        let injection = h.injections[i];
+        let fire = match injection.trigger {
+            InterruptTrigger::EveryBlocks(every) => every > 0 && blocks % every == 0,
+            InterruptTrigger::AfterBlocks(after) => blocks == after,
+            InterruptTrigger::AtAddr(_) => false,
+        };
+        if fire {
+            h.raise(qemu, injection.mask, injection.duration);
+        }
+    }
+}
+
+pub fn on_interrupt_addr<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) where
+    ET: EmulatorModuleTuple<S>,
+    S: Unpin + UsesInput,
+{
+    let qemu = emulator_modules.qemu();
+    let h = emulator_modules.get_mut::<InterruptModule>().unwrap();
+    for i in 0..h.injections.len() {
+        let injection = h.injections[i];
+        if injection.trigger == InterruptTrigger::AtAddr(pc) {
+            h.raise(qemu, injection.mask, injection.duration);
+        }
+    }
+}
@@ -1 +1,11 @@
+pub mod asan;
+pub use asan::{AsanShadow, AsanSystemModule, AsanViolation};
 
+pub mod interrupts;
+pub use interrupts::{InterruptInjection, InterruptModule, InterruptTrigger};
+
+pub mod device_input;
+pub use device_input::DeviceInputModule;
+
+pub mod vcpu_coverage;
+pub use vcpu_coverage::VcpuCoverageModule;
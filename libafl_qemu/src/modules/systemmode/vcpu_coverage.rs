@@ -0,0 +1,218 @@
+//! Attribute the coverage of multi-core targets to the vCPUs executing it.
+//!
+//! On a target with several vCPUs, the same block means different things depending on the core running it: a
+//! driver may only be initialized by the boot CPU, or a race only happen when a secondary core enters a function.
+//! The [`VcpuCoverageModule`] splits a [`MapObserver`] into one slice per vCPU, and marks the executed blocks in the
+//! slice of the vCPU executing them. A block first executed by another core is new coverage.
+//!
+//! The number of blocks executed per vCPU is kept too, see [`VcpuCoverageModule::blocks_per_cpu`].
+
+use core::fmt::Debug;
+
+use hashbrown::HashMap;
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+};
+use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+use libafl_qemu_sys::{GuestAddr, GuestPhysAddr};
+
+use crate::{
+    emu::EmulatorModules,
+    modules::{
+        hash_me, AddressFilter, EmulatorModule, EmulatorModuleTuple, PageFilter, StdAddressFilter,
+        StdPageFilter,
+    },
+    qemu::Hook,
+};
+
+/// A module attributing the executed blocks to vCPUs, see the [module-level docs](self).
+#[derive(Debug)]
+pub struct VcpuCoverageModule<O> {
+    observer_handle: Handle<O>,
+    address_filter: StdAddressFilter,
+    page_filter: StdPageFilter,
+    /// The number of vCPUs the map is split for
+    num_cpus: usize,
+    /// The hit counts of the current run, by vCPU index and block
+    hits: HashMap<(usize, GuestAddr), u64>,
+    /// The blocks executed in the current run, by vCPU index
+    blocks_per_cpu: Vec<u64>,
+}
+
+impl<O> VcpuCoverageModule<O>
+where
+    O: MapObserver<Entry = u8>,
+{
+    /// Creates a new [`VcpuCoverageModule`], splitting the given map `observer` between `num_cpus` vCPUs
+    #[must_use]
+    pub fn new(observer: &O, num_cpus: usize) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            address_filter: StdAddressFilter::default(),
+            page_filter: StdPageFilter::default(),
+            num_cpus: num_cpus.max(1),
+            hits: HashMap::new(),
+            blocks_per_cpu: vec![0; num_cpus.max(1)],
+        }
+    }
+}
+
+impl<O> VcpuCoverageModule<O> {
+    /// Only records the blocks allowed by `filter`
+    #[must_use]
+    pub fn with_address_filter(mut self, filter: StdAddressFilter) -> Self {
+        self.address_filter = filter;
+        self
+    }
+
+    /// Only records the blocks of the address spaces allowed by `filter`
+    #[must_use]
+    pub fn with_page_filter(mut self, filter: StdPageFilter) -> Self {
+        self.page_filter = filter;
+        self
+    }
+
+    #[must_use]
+    pub fn must_instrument(&self, addr: GuestAddr, page_id: Option<GuestPhysAddr>) -> bool {
+        if let Some(page_id) = page_id {
+            self.address_filter.allowed(&addr) && self.page_filter.allowed(&page_id)
+        } else {
+            self.address_filter.allowed(&addr)
+        }
+    }
+
+    /// The number of vCPUs the map is split for
+    #[must_use]
+    pub fn num_cpus(&self) -> usize {
+        self.num_cpus
+    }
+
+    /// The number of blocks executed by each vCPU in the current or last run
+    #[must_use]
+    pub fn blocks_per_cpu(&self) -> &[u64] {
+        &self.blocks_per_cpu
+    }
+
+    /// Records an execution of `pc` by the vCPU `index`
+    fn record(&mut self, index: usize, pc: GuestAddr) {
+        // vCPUs beyond the configured ones share the slice of the last one
+        let index = index.min(self.num_cpus - 1);
+        *self.hits.entry((index, pc)).or_insert(0) += 1;
+        self.blocks_per_cpu[index] += 1;
+    }
+}
+
+impl<O, S> EmulatorModule<S> for VcpuCoverageModule<O>
+where
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = StdAddressFilter;
+    type ModulePageFilter = StdPageFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        emulator_modules.blocks(
+            Hook::Function(gen_vcpu_block::<ET, O, S>),
+            Hook::Empty,
+            Hook::Function(trace_vcpu_block::<ET, O, S>),
+        );
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.hits.clear();
+        self.blocks_per_cpu.fill(0);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let observer = observers
+            .get_mut(&self.observer_handle)
+            .expect("A VcpuCoverageModule needs its map observer");
+        let slice_len = (observer.usable_count() / self.num_cpus) as u64;
+        if slice_len == 0 {
+            return;
+        }
+        for (&(index, pc), &hits) in &self.hits {
+            let idx = index * slice_len as usize + (hash_me(pc.into()) % slice_len) as usize;
+            let hits = hits.min(u64::from(u8::MAX)) as u8;
+            observer.set(idx, observer.get(idx).saturating_add(hits));
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &self.address_filter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        &mut self.address_filter
+    }
+
+    fn page_filter(&self) -> &Self::ModulePageFilter {
+        &self.page_filter
+    }
+
+    fn page_filter_mut(&mut self) -> &mut Self::ModulePageFilter {
+        &mut self.page_filter
+    }
+}
+
+pub fn gen_vcpu_block<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    pc: GuestAddr,
+) -> Option<u64>
+where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let paging_id = emulator_modules
+        .qemu()
+        .current_cpu()
+        .and_then(|cpu| cpu.current_paging_id());
+    let h = emulator_modules.get::<VcpuCoverageModule<O>>().unwrap();
+    if h.must_instrument(pc, paging_id) {
+        Some(pc.into())
+    } else {
+        None
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+pub fn trace_vcpu_block<ET, O, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    id: u64,
+) where
+    ET: EmulatorModuleTuple<S>,
+    O: MapObserver<Entry = u8> + Debug + 'static,
+    S: Unpin + UsesInput,
+{
+    let Some(cpu) = emulator_modules.qemu().current_cpu() else {
+        return;
+    };
+    let h = emulator_modules.get_mut::<VcpuCoverageModule<O>>().unwrap();
+    h.record(cpu.index(), id as GuestAddr);
+}
@@ -33,6 +33,24 @@ use crate::{
 /// <https://github.com/qemu/qemu/blob/11be70677c70fdccd452a3233653949b79e97908/linux-user/hexagon/syscall_nr.h#L230>
 const SYS_execve: u8 = 221;
 
+/// Parses an injections definition file, in the yaml or toml format depending on its extension
+fn parse_file<P: AsRef<Path> + Display>(
+    path: P,
+) -> Result<HashMap<String, InjectionDefinition>, Error> {
+    let extension = path
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    match extension.as_deref() {
+        Some("yaml" | "yml") => yaml_entries_to_definition(&parse_yaml(&path)?),
+        Some("toml") => parse_toml(&path),
+        _ => Err(Error::illegal_argument(format!(
+            "Unknown injections definition format for {path}, the file must end in .toml or .yaml/.yml"
+        ))),
+    }
+}
+
 /// Parses `injections.yaml`
 fn parse_yaml<P: AsRef<Path> + Display>(path: P) -> Result<Vec<YamlInjectionEntry>, Error> {
     serde_yaml::from_str(&fs::read_to_string(&path)?)
@@ -129,6 +147,8 @@ struct FunctionDescription {
     param: u8,
 }
 
+/// An injection type: the functions (sinks) to check, the tokens to plant in the input, and the substrings that
+/// must never reach the sinks
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InjectionDefinition {
     tokens: Vec<String>,
@@ -136,6 +156,56 @@ pub struct InjectionDefinition {
     functions: HashMap<String, FunctionDescription>,
 }
 
+impl InjectionDefinition {
+    /// Creates a new [`InjectionDefinition`] planting `tokens`, and crashing if one of `matches` (case
+    /// insensitive) reaches a hooked function
+    #[must_use]
+    pub fn new(tokens: Vec<String>, matches: Vec<String>) -> Self {
+        Self {
+            tokens,
+            matches,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Hooks the function `name`, checking its parameter `param`, 0 being the first one.
+    ///
+    /// Names starting with `0x` are guest addresses, for functions without symbol.
+    #[must_use]
+    pub fn with_function(mut self, name: &str, param: u8) -> Self {
+        self.functions
+            .insert(name.to_string(), FunctionDescription { param });
+        self
+    }
+
+    /// The tokens to plant in the input
+    #[must_use]
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// The substrings that must not reach the hooked functions
+    #[must_use]
+    pub fn matches(&self) -> &[String] {
+        &self.matches
+    }
+
+    /// Checks that the definition detects something
+    fn validate(&self, name: &str) -> Result<(), Error> {
+        if self.functions.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "Injection definition {name} hooks no function"
+            )));
+        }
+        if self.matches.iter().all(String::is_empty) {
+            return Err(Error::illegal_argument(format!(
+                "Injection definition {name} has no match"
+            )));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Matches {
     id: usize,
@@ -172,7 +242,31 @@ impl InjectionModule {
         Self::new(definition)
     }
 
+    /// Loads the injection definitions of all `files`, in the yaml or toml format depending on their extension.
+    ///
+    /// This way, custom definitions, e.g., for the sinks of the target, can be used next to the default ones.
+    /// A name defined in several files is an error.
+    pub fn from_files<P: AsRef<Path> + Display>(files: &[P]) -> Result<Self, Error> {
+        let mut definitions = HashMap::new();
+        for file in files {
+            for (name, definition) in parse_file(file)? {
+                if definitions.contains_key(&name) {
+                    return Err(Error::illegal_argument(format!(
+                        "Entry {name} of {file} was multiply defined!"
+                    )));
+                }
+                definitions.insert(name, definition);
+            }
+        }
+        Self::new(definitions)
+    }
+
+    /// Creates a new [`InjectionModule`] from the given definitions, by name
     pub fn new(definitions: HashMap<String, InjectionDefinition>) -> Result<Self, Error> {
+        for (name, definition) in &definitions {
+            definition.validate(name)?;
+        }
+
         let tokens = definitions
             .iter()
             .flat_map(|(_lib_name, definition)| &definition.tokens)
@@ -240,7 +334,7 @@ impl InjectionModule {
             log::trace!("Checking {}", matches.lib_name);
 
             for match_value in &matches.matches {
-                if match_value.bytes_lower.len() > matches.matches.len() {
+                if match_value.bytes_lower.len() > query.len() {
                     continue;
                 }
 
@@ -434,7 +528,9 @@ fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 mod tests {
     use hashbrown::HashMap;
 
-    use super::{yaml_entries_to_definition, InjectionDefinition, YamlInjectionEntry};
+    use super::{
+        yaml_entries_to_definition, InjectionDefinition, InjectionModule, YamlInjectionEntry,
+    };
 
     #[test]
     fn test_yaml_parsing() {
@@ -498,4 +594,66 @@ mod tests {
         .unwrap();
         assert_eq!(injections.len(), 2);
     }
+
+    #[test]
+    fn test_from_files() {
+        let dir = std::env::temp_dir().join(format!("libafl_injections_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let toml_file = dir.join("custom.toml");
+        std::fs::write(
+            &toml_file,
+            r#"
+            [template]
+            tokens = ["{{7*7}}"]
+            matches = ["{{7*7}}"]
+            [template.functions]
+            render_template = {param = 1}
+            "#,
+        )
+        .unwrap();
+        let yaml_file = dir.join("custom.yml");
+        std::fs::write(
+            &yaml_file,
+            r#"
+            - name: "path"
+              functions:
+                - function: "open_config"
+                  parameter: 0
+              tests:
+                - input_value: "../../FUZZ"
+                  match_value: "../../FUZZ"
+            "#,
+        )
+        .unwrap();
+
+        let files = [
+            toml_file.display().to_string(),
+            yaml_file.display().to_string(),
+        ];
+        let module = InjectionModule::from_files(&files).unwrap();
+        assert_eq!(module.tokens.len(), 2);
+
+        // The same definition twice
+        assert!(InjectionModule::from_files(&[&files[0], &files[0]]).is_err());
+        assert!(InjectionModule::from_files(&[dir.display().to_string()]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_definition_validation() {
+        let mut definitions = HashMap::new();
+        definitions.insert(
+            "template".to_string(),
+            InjectionDefinition::new(vec!["{{7*7}}".into()], vec!["{{7*7}}".into()]),
+        );
+        assert!(InjectionModule::new(definitions.clone()).is_err());
+
+        let definition = definitions.remove("template").unwrap();
+        definitions.insert(
+            "template".to_string(),
+            definition.with_function("render_template", 1),
+        );
+        assert!(InjectionModule::new(definitions).is_ok());
+    }
 }
@@ -17,3 +17,11 @@ pub use asan::{init_qemu_with_asan, AsanModule};
 pub mod asan_guest;
 #[cfg(not(cpu_target = "hexagon"))]
 pub use asan_guest::{init_qemu_with_asan_guest, AsanGuestModule};
+
+pub mod syscalls;
+pub use syscalls::{SyscallPolicy, SyscallTracerModule};
+
+#[cfg(not(cpu_target = "hexagon"))]
+pub mod symbol_hooks;
+#[cfg(not(cpu_target = "hexagon"))]
+pub use symbol_hooks::{FunctionCall, SymbolHookModule};
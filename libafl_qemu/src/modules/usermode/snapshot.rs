@@ -2,7 +2,7 @@ use std::{cell::UnsafeCell, mem::MaybeUninit, sync::Mutex};
 
 use hashbrown::{HashMap, HashSet};
 use libafl::inputs::UsesInput;
-use libafl_qemu_sys::{GuestAddr, MmapPerms};
+use libafl_qemu_sys::{CPUArchState, GuestAddr, MmapPerms};
 use meminterval::{Interval, IntervalTree};
 use thread_local::ThreadLocal;
 
@@ -96,6 +96,14 @@ pub struct SnapshotModule {
     pub empty: bool,
     pub accurate_unmap: bool,
     pub interval_filter: Vec<IntervalSnapshotFilter>,
+    /// The entry point of persistent mode, at which the snapshot is taken
+    pub persistent_entry: Option<GuestAddr>,
+    /// The registers at the entry point, restored before each run in persistent mode
+    pub cpu_state: Option<CPUArchState>,
+    /// Memory is restored every `restore_interval` runs
+    pub restore_interval: u64,
+    /// The runs since the last restore of the memory
+    pub runs_since_restore: u64,
 }
 
 impl core::fmt::Debug for SnapshotModule {
@@ -108,6 +116,8 @@ impl core::fmt::Debug for SnapshotModule {
             .field("mmap_start", &self.mmap_start)
             .field("mmap_limit", &self.mmap_limit)
             .field("empty", &self.empty)
+            .field("persistent_entry", &self.persistent_entry)
+            .field("restore_interval", &self.restore_interval)
             .finish_non_exhaustive()
     }
 }
@@ -127,6 +137,10 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            persistent_entry: None,
+            cpu_state: None,
+            restore_interval: 1,
+            runs_since_restore: 0,
         }
     }
 
@@ -144,6 +158,10 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter,
+            persistent_entry: None,
+            cpu_state: None,
+            restore_interval: 1,
+            runs_since_restore: 0,
         }
     }
 
@@ -161,6 +179,10 @@ impl SnapshotModule {
             empty: true,
             accurate_unmap: false,
             interval_filter: Vec::<IntervalSnapshotFilter>::new(),
+            persistent_entry: None,
+            cpu_state: None,
+            restore_interval: 1,
+            runs_since_restore: 0,
         }
     }
 
@@ -168,6 +190,25 @@ impl SnapshotModule {
         self.accurate_unmap = true;
     }
 
+    /// Enables persistent mode: the target runs once to `entry`, where its memory and registers are snapshotted.
+    ///
+    /// Each run then starts from the state at `entry`, skipping the initialization of the target.
+    /// The harness should run the target from the current pc, and stop it when the fuzzed code returns.
+    #[must_use]
+    pub fn with_persistent_entry(mut self, entry: GuestAddr) -> Self {
+        self.persistent_entry = Some(entry);
+        self
+    }
+
+    /// Restores the memory every `interval` runs only, the registers are restored before each run.
+    ///
+    /// Faster for targets that keep little state between runs, at the cost of determinism.
+    #[must_use]
+    pub fn with_restore_interval(mut self, interval: u64) -> Self {
+        self.restore_interval = interval.max(1);
+        self
+    }
+
     pub fn to_skip(&self, addr: GuestAddr) -> bool {
         for filter in &self.interval_filter {
             match filter {
@@ -705,10 +746,25 @@ where
     ) where
         ET: EmulatorModuleTuple<S>,
     {
+        let qemu = emulator_modules.qemu();
         if self.empty {
-            self.snapshot(emulator_modules.qemu());
+            if let Some(entry) = self.persistent_entry {
+                log::info!("Running the target to the persistent entry point {entry:#x}");
+                qemu.entry_break(entry);
+                let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+                self.cpu_state = Some(cpu.save_state());
+            }
+            self.snapshot(qemu);
         } else {
-            self.reset(emulator_modules.qemu());
+            self.runs_since_restore += 1;
+            if self.runs_since_restore >= self.restore_interval {
+                self.reset(qemu);
+                self.runs_since_restore = 0;
+            }
+            if let Some(cpu_state) = &self.cpu_state {
+                let cpu = qemu.current_cpu().unwrap_or_else(|| qemu.cpu_from_index(0));
+                cpu.restore_state(cpu_state);
+            }
         }
     }
 
@@ -0,0 +1,368 @@
+//! Hook guest functions by symbol name.
+//!
+//! Harnesses often need to observe or stub out functions of the target, such as `getenv`, `rand`, or checksum
+//! routines. Their addresses change with every build of the target and its libraries, so the
+//! [`SymbolHookModule`] looks them up by name, in the guest binary and all the libraries loaded at the first
+//! execution, both in their symbol tables and in their exports.
+//!
+//! Two kinds of hooks exist:
+//! - [`SymbolHookModule::on_call`] runs a closure each time the function is entered, e.g., to log or change its
+//!   arguments. The function then runs as usual.
+//! - [`SymbolHookModule::replace`] replaces the whole function: its entry is patched to return immediately, with
+//!   the value returned by the closure.
+//!
+//! ```ignore
+//! let module = SymbolHookModule::new()
+//!     .replace("rand", |_call| 4)
+//!     .on_call("getenv", |call| {
+//!         let name = call.read_c_string(call.arg(0).unwrap(), 256);
+//!         log::info!("getenv({})", String::from_utf8_lossy(&name));
+//!     });
+//! ```
+//!
+//! The arguments and return values follow the C calling convention of the guest architecture, see
+//! [`FunctionCall`]. Libraries loaded later, e.g., with `dlopen`, are not hooked.
+
+use core::fmt;
+
+use libafl::inputs::UsesInput;
+use libafl_qemu_sys::{GuestAddr, MmapPerms};
+
+use crate::{
+    elf::EasyElf,
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple, NopAddressFilter, NOP_ADDRESS_FILTER},
+    qemu::{ArchExtras, Hook, QemuRWError},
+    CallingConvention, GuestReg, Qemu, Regs,
+};
+
+/// The register holding the return value of a function, in the C calling convention
+#[cfg(cpu_target = "x86_64")]
+const RETURN_VALUE_REG: Regs = Regs::Rax;
+#[cfg(cpu_target = "i386")]
+const RETURN_VALUE_REG: Regs = Regs::Eax;
+#[cfg(cpu_target = "arm")]
+const RETURN_VALUE_REG: Regs = Regs::R0;
+#[cfg(cpu_target = "aarch64")]
+const RETURN_VALUE_REG: Regs = Regs::X0;
+#[cfg(cpu_target = "mips")]
+const RETURN_VALUE_REG: Regs = Regs::V0;
+#[cfg(cpu_target = "ppc")]
+const RETURN_VALUE_REG: Regs = Regs::R3;
+#[cfg(any(cpu_target = "riscv32", cpu_target = "riscv64"))]
+const RETURN_VALUE_REG: Regs = Regs::A0;
+
+/// Encodes a 32-bit instruction in the byte order of the guest
+#[allow(dead_code)]
+fn encode_insn(insn: u32) -> [u8; 4] {
+    if cfg!(feature = "be") {
+        insn.to_be_bytes()
+    } else {
+        insn.to_le_bytes()
+    }
+}
+
+/// The code returning from a function, written at the entry of replaced functions
+#[allow(unused_variables)]
+fn return_stub(thumb: bool) -> Vec<u8> {
+    #[cfg(any(cpu_target = "x86_64", cpu_target = "i386"))]
+    let stub = vec![0xc3]; // ret
+    #[cfg(cpu_target = "arm")]
+    let stub = if thumb {
+        // bx lr
+        if cfg!(feature = "be") {
+            vec![0x47, 0x70]
+        } else {
+            vec![0x70, 0x47]
+        }
+    } else {
+        encode_insn(0xe12f_ff1e).to_vec() // bx lr
+    };
+    #[cfg(cpu_target = "aarch64")]
+    let stub = encode_insn(0xd65f_03c0).to_vec(); // ret
+    #[cfg(cpu_target = "mips")]
+    let stub = [encode_insn(0x03e0_0008), encode_insn(0)].concat(); // jr $ra; nop
+    #[cfg(cpu_target = "ppc")]
+    let stub = encode_insn(0x4e80_0020).to_vec(); // blr
+    #[cfg(any(cpu_target = "riscv32", cpu_target = "riscv64"))]
+    let stub = encode_insn(0x0000_8067).to_vec(); // ret
+    stub
+}
+
+/// A call of a hooked function, giving access to its arguments and return value
+#[derive(Debug, Clone, Copy)]
+pub struct FunctionCall {
+    qemu: Qemu,
+    pc: GuestAddr,
+}
+
+impl FunctionCall {
+    /// The [`Qemu`] instance running the call
+    #[must_use]
+    pub fn qemu(&self) -> Qemu {
+        self.qemu
+    }
+
+    /// The address of the called function
+    #[must_use]
+    pub fn pc(&self) -> GuestAddr {
+        self.pc
+    }
+
+    /// Reads the argument `idx` of the call
+    pub fn arg(&self, idx: u8) -> Result<GuestReg, QemuRWError> {
+        self.cpu()
+            .read_function_argument(CallingConvention::Cdecl, idx)
+    }
+
+    /// Overwrites the argument `idx` of the call, before the function reads it
+    pub fn set_arg<T>(&self, idx: u8, val: T) -> Result<(), QemuRWError>
+    where
+        T: Into<GuestReg>,
+    {
+        self.cpu()
+            .write_function_argument(CallingConvention::Cdecl, i32::from(idx), val)
+    }
+
+    /// The address the function returns to
+    pub fn return_address(&self) -> Result<GuestReg, QemuRWError> {
+        self.cpu().read_return_address()
+    }
+
+    /// Reads the NUL-terminated string at `addr`, without the NUL, of at most `max_len` bytes.
+    ///
+    /// Stops early at unreadable memory.
+    #[must_use]
+    pub fn read_c_string(&self, addr: GuestReg, max_len: usize) -> Vec<u8> {
+        let cpu = self.cpu();
+        let mut string = Vec::new();
+        let mut byte = [0];
+        for addr in (addr as GuestAddr..).take(max_len) {
+            if cpu.read_mem(addr, &mut byte).is_err() || byte[0] == 0 {
+                break;
+            }
+            string.push(byte[0]);
+        }
+        string
+    }
+
+    fn cpu(&self) -> crate::CPU {
+        self.qemu
+            .current_cpu()
+            .unwrap_or_else(|| self.qemu.cpu_from_index(0))
+    }
+}
+
+/// What a [`SymbolHook`] does
+enum SymbolHookKind {
+    /// Runs the closure on entry, then the function
+    Call(Box<dyn FnMut(&FunctionCall)>),
+    /// Returns the value of the closure instead of running the function
+    Replace(Box<dyn FnMut(&FunctionCall) -> GuestReg>),
+}
+
+/// A hook on a function, by symbol name
+struct SymbolHook {
+    symbol: String,
+    kind: SymbolHookKind,
+    addrs: Vec<GuestAddr>,
+}
+
+impl fmt::Debug for SymbolHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            SymbolHookKind::Call(_) => "call",
+            SymbolHookKind::Replace(_) => "replace",
+        };
+        f.debug_struct("SymbolHook")
+            .field("symbol", &self.symbol)
+            .field("kind", &kind)
+            .field("addrs", &self.addrs)
+            .finish()
+    }
+}
+
+/// A module hooking guest functions by symbol name, see the [module-level docs](self).
+#[derive(Debug, Default)]
+pub struct SymbolHookModule {
+    hooks: Vec<SymbolHook>,
+}
+
+impl SymbolHookModule {
+    /// Creates a new [`SymbolHookModule`], without hooks
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `hook` each time a function named `symbol` is entered, before the function itself
+    #[must_use]
+    pub fn on_call<F>(mut self, symbol: &str, hook: F) -> Self
+    where
+        F: FnMut(&FunctionCall) + 'static,
+    {
+        self.hooks.push(SymbolHook {
+            symbol: symbol.to_string(),
+            kind: SymbolHookKind::Call(Box::new(hook)),
+            addrs: Vec::new(),
+        });
+        self
+    }
+
+    /// Replaces the functions named `symbol`: they return the value of `hook` without running
+    #[must_use]
+    pub fn replace<F>(mut self, symbol: &str, hook: F) -> Self
+    where
+        F: FnMut(&FunctionCall) -> GuestReg + 'static,
+    {
+        self.hooks.push(SymbolHook {
+            symbol: symbol.to_string(),
+            kind: SymbolHookKind::Replace(Box::new(hook)),
+            addrs: Vec::new(),
+        });
+        self
+    }
+
+    /// The addresses `symbol` was resolved to and hooked at, empty before the first execution
+    #[must_use]
+    pub fn resolved(&self, symbol: &str) -> Vec<GuestAddr> {
+        self.hooks
+            .iter()
+            .filter(|hook| hook.symbol == symbol)
+            .flat_map(|hook| hook.addrs.iter().copied())
+            .collect()
+    }
+
+    /// Runs the hook `id`, for a call of the function at `pc`
+    fn run(&mut self, qemu: Qemu, id: usize, pc: GuestAddr) {
+        let call = FunctionCall { qemu, pc };
+        match &mut self.hooks[id].kind {
+            SymbolHookKind::Call(hook) => hook(&call),
+            SymbolHookKind::Replace(hook) => {
+                let ret = hook(&call);
+                // The patched entry returns right after the hook
+                if let Err(err) = call.cpu().write_reg(RETURN_VALUE_REG, ret) {
+                    log::warn!(
+                        "SymbolHookModule: could not set the return value of {}: {err:?}",
+                        self.hooks[id].symbol
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Patches the entry of the function at `addr` to return immediately
+fn patch_return(qemu: Qemu, addr: GuestAddr, thumb: bool) -> Result<(), String> {
+    let stub = return_stub(thumb);
+    let region = qemu
+        .mappings()
+        .find(|region| region.start() <= addr && addr < region.end())
+        .ok_or_else(|| format!("{addr:#x} is not mapped"))?;
+    let (start, size, perms) = (
+        region.start(),
+        (region.end() - region.start()) as usize,
+        region.flags(),
+    );
+
+    qemu.mprotect(start, size, MmapPerms::ReadWriteExecute)?;
+    let written = qemu.write_mem(addr, &stub);
+    qemu.mprotect(start, size, perms)?;
+    written.map_err(|err| format!("Failed to write to {addr:#x}: {err:?}"))?;
+
+    qemu.invalidate_jit_range(addr..addr + stub.len() as GuestAddr);
+    Ok(())
+}
+
+impl<S> EmulatorModule<S> for SymbolHookModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+
+    fn first_exec<ET>(&mut self, emulator_modules: &mut EmulatorModules<ET, S>, _state: &mut S)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        let qemu = emulator_modules.qemu();
+
+        // The lowest mapping of each file is where it is loaded
+        let mut objects: Vec<(String, GuestAddr)> = Vec::new();
+        for region in qemu.mappings() {
+            if let Some(path) = region.path() {
+                // skip [heap], [vdso] and friends
+                if !path.is_empty()
+                    && !path.starts_with('[')
+                    && !objects.iter().any(|(name, _)| name == path)
+                {
+                    objects.push((path.clone(), region.start()));
+                }
+            }
+        }
+
+        for (path, load_addr) in objects {
+            let mut elf_buffer = Vec::new();
+            let Ok(elf) = EasyElf::from_file(&path, &mut elf_buffer) else {
+                continue;
+            };
+            for hook in &mut self.hooks {
+                let Some(value) = elf.symbol_value(&hook.symbol) else {
+                    continue;
+                };
+                let value = value as GuestAddr;
+                let value = if elf.is_pic() {
+                    value + load_addr
+                } else {
+                    value
+                };
+                // Arm interworking addresses have bit 0 set for thumb functions
+                let thumb = cfg!(cpu_target = "arm") && value & 1 == 1;
+                let addr = if cfg!(cpu_target = "arm") {
+                    value & !1
+                } else {
+                    value
+                };
+
+                if matches!(hook.kind, SymbolHookKind::Replace(_)) {
+                    if let Err(err) = patch_return(qemu, addr, thumb) {
+                        log::warn!(
+                            "SymbolHookModule: could not replace {} at {addr:#x}: {err}",
+                            hook.symbol
+                        );
+                        continue;
+                    }
+                }
+                log::info!(
+                    "SymbolHookModule: hooking {} at {addr:#x} in {path}",
+                    hook.symbol
+                );
+                hook.addrs.push(addr);
+            }
+        }
+
+        for (id, hook) in self.hooks.iter().enumerate() {
+            if hook.addrs.is_empty() {
+                log::warn!("SymbolHookModule: symbol {} not found", hook.symbol);
+            }
+            for &addr in &hook.addrs {
+                emulator_modules.instructions(
+                    addr,
+                    Hook::Closure(Box::new(move |emulator_modules, _state, pc| {
+                        let qemu = emulator_modules.qemu();
+                        let h = emulator_modules.get_mut::<Self>().unwrap();
+                        h.run(qemu, id, pc);
+                    })),
+                    true,
+                );
+            }
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
@@ -0,0 +1,343 @@
+//! Trace the system calls of the target into a [`SyscallObserver`], and enforce a [`SyscallPolicy`] on them.
+//!
+//! The [`SyscallTracerModule`] records the number, first six arguments and return value of every system call.
+//! With a [`SyscallPolicy`], it also keeps the target away from the host: it can deny system calls such as
+//! network access, make time and randomness deterministic, and redirect opened paths to a sandbox directory.
+//! Denied system calls are reported as violations of the observer.
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+};
+
+use libafl::{
+    executors::ExitKind,
+    inputs::UsesInput,
+    observers::{ObserversTuple, SyscallEvent, SyscallObserver, SyscallRecord},
+};
+use libafl_bolts::{
+    rands::{Rand, StdRand},
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use libafl_qemu_sys::{GuestAddr, GuestIsize};
+
+#[cfg(not(cpu_target = "riscv32"))]
+use crate::SYS_clock_gettime;
+#[cfg(cpu_target = "i386")]
+use crate::SYS_socketcall;
+use crate::{
+    emu::EmulatorModules,
+    modules::{EmulatorModule, EmulatorModuleTuple, NopAddressFilter, NOP_ADDRESS_FILTER},
+    qemu::{Hook, SyscallHookResult},
+    SYS_getrandom, SYS_openat, SYS_socket,
+};
+
+/// What a [`SyscallTracerModule`] does with the system calls of the target, instead of running them.
+///
+/// Empty by default: all system calls run unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct SyscallPolicy {
+    /// System calls failing with the given error number
+    denied: Vec<(i64, i32)>,
+    /// The seconds returned by `clock_gettime`
+    fake_time: Option<u64>,
+    /// The seed of the bytes returned by `getrandom`
+    fake_random: Option<u64>,
+    /// Path prefixes replaced when opening files
+    redirects: Vec<(String, String)>,
+}
+
+impl SyscallPolicy {
+    /// Creates a new, empty [`SyscallPolicy`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails the system call `nr` with the error number `errno`, reporting a violation
+    #[must_use]
+    pub fn deny(mut self, nr: i64, errno: i32) -> Self {
+        self.denied.push((nr, errno));
+        self
+    }
+
+    /// Fails the creation of sockets with `EACCES`, reporting a violation
+    #[must_use]
+    pub fn deny_network(self) -> Self {
+        let policy = self.deny(SYS_socket, libc::EACCES);
+        #[cfg(cpu_target = "i386")]
+        let policy = policy.deny(SYS_socketcall, libc::EACCES);
+        policy
+    }
+
+    /// Answers `clock_gettime` with `secs` seconds, on all clocks
+    #[must_use]
+    pub fn fake_time(mut self, secs: u64) -> Self {
+        self.fake_time = Some(secs);
+        self
+    }
+
+    /// Answers `getrandom` with bytes seeded by `seed`, the same in every run
+    #[must_use]
+    pub fn fake_random(mut self, seed: u64) -> Self {
+        self.fake_random = Some(seed);
+        self
+    }
+
+    /// Opens the paths starting with `from` at `to` instead.
+    ///
+    /// Only `openat` is redirected. The file is opened on the host with the access mode of the target,
+    /// other flags, such as `O_CREAT`, are dropped.
+    #[must_use]
+    pub fn redirect(mut self, from: &str, to: &str) -> Self {
+        self.redirects.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Whether this policy leaves all system calls unchanged
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.denied.is_empty()
+            && self.fake_time.is_none()
+            && self.fake_random.is_none()
+            && self.redirects.is_empty()
+    }
+
+    /// The redirected path of `path`, if any
+    fn redirected(&self, path: &str) -> Option<String> {
+        self.redirects.iter().find_map(|(from, to)| {
+            path.strip_prefix(from.as_str())
+                .map(|rest| format!("{to}{rest}"))
+        })
+    }
+}
+
+/// A module recording the system calls issued by the target, to be used with a
+/// [`libafl::feedbacks::SyscallSetFeedback`], and enforcing a [`SyscallPolicy`], see the [module-level docs](self).
+#[derive(Debug)]
+pub struct SyscallTracerModule {
+    observer_handle: Handle<SyscallObserver>,
+    trace: Vec<SyscallRecord>,
+    events: Vec<SyscallEvent>,
+    policy: SyscallPolicy,
+    violations: Vec<SyscallRecord>,
+    rand: StdRand,
+}
+
+impl SyscallTracerModule {
+    /// Creates a new [`SyscallTracerModule`], filling the given [`SyscallObserver`]
+    #[must_use]
+    pub fn new(observer: &SyscallObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            trace: Vec::new(),
+            events: Vec::new(),
+            policy: SyscallPolicy::default(),
+            violations: Vec::new(),
+            rand: StdRand::with_seed(0),
+        }
+    }
+
+    /// Enforces the `policy` on the system calls of the target
+    #[must_use]
+    pub fn with_policy(mut self, policy: SyscallPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The enforced policy
+    #[must_use]
+    pub fn policy(&self) -> &SyscallPolicy {
+        &self.policy
+    }
+
+    /// The system calls traced during the current run
+    #[must_use]
+    pub fn trace(&self) -> &[SyscallRecord] {
+        &self.trace
+    }
+
+    /// The system calls traced during the current run, with their arguments and return values
+    #[must_use]
+    pub fn events(&self) -> &[SyscallEvent] {
+        &self.events
+    }
+
+    /// The system calls denied by the policy during the current run
+    #[must_use]
+    pub fn violations(&self) -> &[SyscallRecord] {
+        &self.violations
+    }
+}
+
+impl<S> EmulatorModule<S> for SyscallTracerModule
+where
+    S: Unpin + UsesInput,
+{
+    type ModuleAddressFilter = NopAddressFilter;
+
+    fn post_qemu_init<ET>(&self, emulator_modules: &mut EmulatorModules<ET, S>)
+    where
+        ET: EmulatorModuleTuple<S>,
+    {
+        if !self.policy.is_empty() {
+            emulator_modules.syscalls(Hook::Function(apply_syscall_policy::<ET, S>));
+        }
+        emulator_modules.after_syscalls(Hook::Function(trace_syscall::<ET, S>));
+    }
+
+    fn pre_exec<ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+    ) where
+        ET: EmulatorModuleTuple<S>,
+    {
+        self.trace.clear();
+        self.events.clear();
+        self.violations.clear();
+        if let Some(seed) = self.policy.fake_random {
+            self.rand.set_seed(seed);
+        }
+    }
+
+    fn post_exec<OT, ET>(
+        &mut self,
+        _emulator_modules: &mut EmulatorModules<ET, S>,
+        _state: &mut S,
+        _input: &S::Input,
+        observers: &mut OT,
+        _exit_kind: &mut ExitKind,
+    ) where
+        OT: ObserversTuple<S::Input, S>,
+        ET: EmulatorModuleTuple<S>,
+    {
+        let observer = observers
+            .get_mut(&self.observer_handle)
+            .expect("A SyscallTracerModule needs a SyscallObserver");
+        observer.fill_external_events(&self.events);
+        for violation in &self.violations {
+            observer.record_violation(*violation);
+        }
+    }
+
+    fn address_filter(&self) -> &Self::ModuleAddressFilter {
+        &NopAddressFilter
+    }
+
+    fn address_filter_mut(&mut self) -> &mut Self::ModuleAddressFilter {
+        unsafe { (&raw mut NOP_ADDRESS_FILTER).as_mut().unwrap().get_mut() }
+    }
+}
+
+/// The negated `errno` as return value of a system call
+#[allow(clippy::cast_sign_loss)]
+fn syscall_error(errno: i32) -> GuestAddr {
+    (-(errno as GuestIsize)) as GuestAddr
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::cast_possible_truncation)]
+fn apply_syscall_policy<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    _a3: GuestAddr,
+    _a4: GuestAddr,
+    _a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> SyscallHookResult
+where
+    S: Unpin + UsesInput,
+    ET: EmulatorModuleTuple<S>,
+{
+    let qemu = emulator_modules.qemu();
+    let Some(h) = emulator_modules.get_mut::<SyscallTracerModule>() else {
+        return SyscallHookResult::new(None);
+    };
+    let nr = i64::from(sys_num);
+
+    if let Some(&(_, errno)) = h.policy.denied.iter().find(|(denied, _)| *denied == nr) {
+        log::debug!("Denied syscall {nr}");
+        h.violations.push(SyscallRecord::new(nr as u64, errno));
+        return SyscallHookResult::new(Some(syscall_error(errno)));
+    }
+
+    match nr {
+        #[cfg(not(cpu_target = "riscv32"))]
+        SYS_clock_gettime if h.policy.fake_time.is_some() => {
+            // A `timespec` of two words, as seen by the target
+            let secs = h.policy.fake_time.unwrap() as GuestAddr;
+            let mut timespec = secs.to_ne_bytes().to_vec();
+            timespec.extend_from_slice(&GuestAddr::to_ne_bytes(0));
+            if qemu.write_mem(a1, &timespec).is_err() {
+                return SyscallHookResult::new(Some(syscall_error(libc::EFAULT)));
+            }
+            SyscallHookResult::new(Some(0))
+        }
+        SYS_getrandom if h.policy.fake_random.is_some() => {
+            let buf = (0..a1).map(|_| h.rand.next() as u8).collect::<Vec<_>>();
+            if qemu.write_mem(a0, &buf).is_err() {
+                return SyscallHookResult::new(Some(syscall_error(libc::EFAULT)));
+            }
+            SyscallHookResult::new(Some(a1))
+        }
+        SYS_openat if !h.policy.redirects.is_empty() && a1 != 0 => {
+            let path = unsafe { CStr::from_ptr(qemu.g2h::<c_char>(a1)) }.to_string_lossy();
+            let Some(redirected) = h.policy.redirected(&path) else {
+                return SyscallHookResult::new(None);
+            };
+            log::debug!("Redirecting {path} to {redirected}");
+            let Ok(redirected) = CString::new(redirected) else {
+                return SyscallHookResult::new(Some(syscall_error(libc::ENOENT)));
+            };
+            let flags = (a2 as i32 & libc::O_ACCMODE) | libc::O_CLOEXEC;
+            let fd = unsafe { libc::openat(libc::AT_FDCWD, redirected.as_ptr(), flags) };
+            if fd < 0 {
+                let errno = std::io::Error::last_os_error()
+                    .raw_os_error()
+                    .unwrap_or(libc::ENOENT);
+                SyscallHookResult::new(Some(syscall_error(errno)))
+            } else {
+                SyscallHookResult::new(Some(fd as GuestAddr))
+            }
+        }
+        _ => SyscallHookResult::new(None),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn trace_syscall<ET, S>(
+    emulator_modules: &mut EmulatorModules<ET, S>,
+    _state: Option<&mut S>,
+    result: GuestAddr,
+    sys_num: i32,
+    a0: GuestAddr,
+    a1: GuestAddr,
+    a2: GuestAddr,
+    a3: GuestAddr,
+    a4: GuestAddr,
+    a5: GuestAddr,
+    _a6: GuestAddr,
+    _a7: GuestAddr,
+) -> GuestAddr
+where
+    S: Unpin + UsesInput,
+    ET: EmulatorModuleTuple<S>,
+{
+    if let Some(h) = emulator_modules.get_mut::<SyscallTracerModule>() {
+        let event = SyscallEvent {
+            nr: sys_num as u64,
+            args: [a0, a1, a2, a3, a4, a5].map(u64::from),
+            ret: i64::from(result as GuestIsize),
+        };
+        h.trace.push(event.record());
+        h.events.push(event);
+    }
+    result
+}
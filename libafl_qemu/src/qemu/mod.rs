@@ -18,18 +18,21 @@ use std::{
 
 use libafl_bolts::os::unix_signals::Signal;
 use libafl_qemu_sys::{
-    libafl_flush_jit, libafl_get_exit_reason, libafl_page_from_addr, libafl_qemu_add_gdb_cmd,
-    libafl_qemu_cpu_index, libafl_qemu_current_cpu, libafl_qemu_gdb_reply, libafl_qemu_get_cpu,
-    libafl_qemu_init, libafl_qemu_num_cpus, libafl_qemu_num_regs, libafl_qemu_read_reg,
-    libafl_qemu_remove_breakpoint, libafl_qemu_set_breakpoint, libafl_qemu_trigger_breakpoint,
-    libafl_qemu_write_reg, CPUArchState, CPUStatePtr, FatPtr, GuestAddr, GuestPhysAddr, GuestUsize,
-    GuestVirtAddr,
+    libafl_breakpoint_invalidate, libafl_flush_jit, libafl_get_exit_reason, libafl_page_from_addr,
+    libafl_qemu_add_gdb_cmd, libafl_qemu_cpu_index, libafl_qemu_current_cpu, libafl_qemu_gdb_reply,
+    libafl_qemu_get_cpu, libafl_qemu_init, libafl_qemu_num_cpus, libafl_qemu_num_regs,
+    libafl_qemu_read_reg, libafl_qemu_remove_breakpoint, libafl_qemu_set_breakpoint,
+    libafl_qemu_trigger_breakpoint, libafl_qemu_write_reg, CPUArchState, CPUStatePtr, FatPtr,
+    GuestAddr, GuestPhysAddr, GuestUsize, GuestVirtAddr,
 };
 use num_traits::Num;
 use strum::IntoEnumIterator;
 
 use crate::{GuestAddrKind, GuestReg, Regs};
 
+/// The largest range [`Qemu::invalidate_jit_range`] invalidates block by block, before flushing the whole JIT
+pub const JIT_INVALIDATE_MAX_SIZE: GuestUsize = 0x10000;
+
 pub mod config;
 use config::{QemuConfig, QemuConfigBuilder, QEMU_CONFIG};
 
@@ -875,6 +878,28 @@ impl Qemu {
         }
     }
 
+    /// Invalidates the translated blocks intersecting `range`, to retranslate them on their next execution.
+    ///
+    /// Unlike [`Self::flush_jit`], the rest of the translation cache is kept. Ranges larger than
+    /// [`JIT_INVALIDATE_MAX_SIZE`] flush the whole JIT, which is faster than invalidating them.
+    pub fn invalidate_jit_range(&self, range: Range<GuestAddr>) {
+        if range.is_empty() {
+            return;
+        }
+        if range.end - range.start > JIT_INVALIDATE_MAX_SIZE {
+            self.flush_jit();
+            return;
+        }
+
+        let cpu = self.current_cpu().unwrap_or_else(|| self.cpu_from_index(0));
+        // Blocks start at any instruction, so every address of the range has to be checked
+        for addr in range {
+            unsafe {
+                libafl_breakpoint_invalidate(cpu.ptr, addr);
+            }
+        }
+    }
+
     #[must_use]
     pub fn remove_hook(&self, id: &impl HookId, invalidate_block: bool) -> bool {
         id.remove(invalidate_block)
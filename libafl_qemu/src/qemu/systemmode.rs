@@ -2,6 +2,7 @@ use std::{
     ffi::{c_void, CStr, CString},
     marker::PhantomData,
     mem::MaybeUninit,
+    path::Path,
     ptr::null_mut,
     slice,
 };
@@ -26,6 +27,16 @@ pub(super) extern "C" fn qemu_cleanup_atexit() {
     }
 }
 
+/// The failure of a monitor command, see [`Qemu::monitor_command`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QemuMonitorError {
+    /// The command contains a nul byte
+    InvalidCommand(String),
+    /// QEMU failed to run the command, with its error message
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
 pub enum DeviceSnapshotFilter {
     All,
     AllowList(Vec<String>),
@@ -70,13 +81,18 @@ impl DeviceSnapshotFilter {
         }
     }
 
-    fn devices(&self, v: &mut Vec<*mut i8>) -> *mut *mut i8 {
+    /// The null-terminated list of device names, pointing into `names`
+    fn devices(&self, names: &mut Vec<CString>, v: &mut Vec<*mut i8>) -> *mut *mut i8 {
         v.clear();
         match self {
             DeviceSnapshotFilter::All => null_mut(),
             DeviceSnapshotFilter::AllowList(l) | DeviceSnapshotFilter::DenyList(l) => {
-                for name in l {
-                    v.push(name.as_bytes().as_ptr() as *mut i8);
+                *names = l
+                    .iter()
+                    .map(|name| CString::new(name.as_str()).expect("Invalid device name"))
+                    .collect();
+                for name in names.iter() {
+                    v.push(name.as_ptr() as *mut i8);
                 }
                 v.push(core::ptr::null_mut());
                 v.as_mut_ptr()
@@ -140,6 +156,18 @@ impl CPU {
         }
     }
 
+    /// Raises the interrupt lines of this CPU in `mask`, one of the target-specific `CPU_INTERRUPT_*` masks of QEMU.
+    ///
+    /// The interrupt is taken once the guest unmasks it, until the lines are [reset](Self::reset_interrupt).
+    pub fn interrupt(&self, mask: i32) {
+        unsafe { libafl_qemu_sys::cpu_interrupt(self.ptr, mask) };
+    }
+
+    /// Lowers the interrupt lines of this CPU in `mask`
+    pub fn reset_interrupt(&self, mask: i32) {
+        unsafe { libafl_qemu_sys::cpu_reset_interrupt(self.ptr, mask) };
+    }
+
     /// Read a value from a guest address, taking into account the potential MMU / MPU.
     ///
     /// # Safety
@@ -248,13 +276,14 @@ impl Qemu {
         track: bool,
         device_filter: &DeviceSnapshotFilter,
     ) -> FastSnapshotPtr {
+        let mut names = vec![];
         let mut v = vec![];
         unsafe {
             libafl_qemu_sys::syx_snapshot_new(
                 track,
                 true,
                 device_filter.enum_id(),
-                device_filter.devices(&mut v),
+                device_filter.devices(&mut names, &mut v),
             )
         }
     }
@@ -298,6 +327,78 @@ impl Qemu {
     pub fn target_page_size(&self) -> usize {
         unsafe { libafl_qemu_sys::qemu_target_page_size() }
     }
+
+    /// Runs a human monitor (HMP) command, as typed in the QEMU monitor, and returns its output.
+    ///
+    /// This gives harnesses access to the monitor without patching QEMU, e.g., to hotplug devices during the
+    /// setup, or to save guest memory between runs. The command runs synchronously, and must only be issued
+    /// while QEMU is stopped, i.e., from the harness and not from a hook.
+    pub fn monitor_command(&self, command: &str) -> Result<String, QemuMonitorError> {
+        let c_command = CString::new(command)
+            .map_err(|_| QemuMonitorError::InvalidCommand(command.to_string()))?;
+        let mut err: *mut libafl_qemu_sys::Error = null_mut();
+        unsafe {
+            let output = libafl_qemu_sys::qmp_human_monitor_command(
+                c_command.as_ptr(),
+                false,
+                0,
+                &raw mut err,
+            );
+            if !err.is_null() {
+                let message = CStr::from_ptr(libafl_qemu_sys::error_get_pretty(err))
+                    .to_string_lossy()
+                    .into_owned();
+                libafl_qemu_sys::error_free(err);
+                return Err(QemuMonitorError::Failed(message));
+            }
+            if output.is_null() {
+                return Ok(String::new());
+            }
+            let result = CStr::from_ptr(output).to_string_lossy().into_owned();
+            libafl_qemu_sys::g_free(output.cast());
+            Ok(result)
+        }
+    }
+
+    /// Runs a monitor command printing nothing on success, and fails with its output otherwise
+    fn monitor_action(&self, command: &str) -> Result<(), QemuMonitorError> {
+        let output = self.monitor_command(command)?;
+        if output.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(QemuMonitorError::Failed(output.trim().to_string()))
+        }
+    }
+
+    /// Hotplugs a device, given as with `-device`, e.g., `virtio-net-pci,id=net1`
+    pub fn device_add(&self, device: &str) -> Result<(), QemuMonitorError> {
+        self.monitor_action(&format!("device_add {device}"))
+    }
+
+    /// Unplugs the device with the given `id`
+    pub fn device_del(&self, id: &str) -> Result<(), QemuMonitorError> {
+        self.monitor_action(&format!("device_del {id}"))
+    }
+
+    /// Injects a non-maskable interrupt on all the vCPUs
+    pub fn inject_nmi(&self) -> Result<(), QemuMonitorError> {
+        self.monitor_action("nmi")
+    }
+
+    /// Saves the screen of the guest to a PPM image at `path`
+    pub fn screendump(&self, path: &Path) -> Result<(), QemuMonitorError> {
+        self.monitor_action(&format!("screendump \"{}\"", path.display()))
+    }
+
+    /// Saves `size` bytes of the guest physical memory at `addr` to the file at `path`
+    pub fn save_phys_memory(
+        &self,
+        addr: GuestPhysAddr,
+        size: usize,
+        path: &Path,
+    ) -> Result<(), QemuMonitorError> {
+        self.monitor_action(&format!("pmemsave {addr:#x} {size} \"{}\"", path.display()))
+    }
 }
 
 impl QemuMemoryChunk {
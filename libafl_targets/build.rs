@@ -56,8 +56,22 @@ fn main() {
         .map_or(Ok(SIXTY_FIVE_KB), str::parse)
         .expect("Could not parse LIBAFL_DDG_MAP_SIZE");
 
+    // The n-gram features pick a default size, the env overrides it
+    let default_ngram_size = if cfg!(feature = "sancov_ngram8") {
+        8
+    } else {
+        4
+    };
+    let ngram_size: usize = option_env!("LIBAFL_NGRAM_SIZE")
+        .map_or(Ok(default_ngram_size), str::parse)
+        .expect("Could not parse LIBAFL_NGRAM_SIZE");
+
     assert!(edges_map_default_size <= edges_map_allocated_size);
     assert!(edges_map_default_size.is_power_of_two());
+    assert!(
+        (2..=8).contains(&ngram_size),
+        "LIBAFL_NGRAM_SIZE must be between 2 and 8"
+    );
 
     write!(
         constants_file,
@@ -76,7 +90,9 @@ fn main() {
         /// The size of the accounting maps
         pub const ACCOUNTING_MAP_SIZE: usize = {acc_map_size};
         /// The size of the accounting maps
-        pub const DDG_MAP_SIZE: usize = {ddg_map_size};        
+        pub const DDG_MAP_SIZE: usize = {ddg_map_size};
+        /// The number of edges hashed together by the n-gram coverage
+        pub const NGRAM_SIZE: usize = {ngram_size};
 "
     )
     .expect("Could not write file");
@@ -90,6 +106,7 @@ fn main() {
     println!("cargo:rerun-if-env-changed=LIBAFL_CMPLOG_MAP_H");
     println!("cargo:rerun-if-env-changed=LIBAFL_ACCOUNTING_MAP_SIZE");
     println!("cargo:rerun-if-env-changed=LIBAFL_DDG_MAP_SIZE");
+    println!("cargo:rerun-if-env-changed=LIBAFL_NGRAM_SIZE");
 
     #[cfg(feature = "common")]
     {
@@ -152,6 +169,15 @@ fn main() {
         println!("cargo:rustc-link-arg=--undefined=__sanitizer_cov_trace_switch");
     }
 
+    #[cfg(feature = "sancov_stack_depth")]
+    {
+        println!("cargo:rerun-if-changed=src/sancov_stack_depth.c");
+
+        cc::Build::new()
+            .file(src_dir.join("sancov_stack_depth.c"))
+            .compile("sancov_stack_depth");
+    }
+
     #[cfg(feature = "libfuzzer")]
     {
         println!("cargo:rerun-if-changed=src/libfuzzer.c");
@@ -163,6 +189,8 @@ fn main() {
         libfuzzer.define("FUZZER_NO_LINK_MAIN", "1");
         #[cfg(feature = "libfuzzer_define_run_driver")]
         libfuzzer.define("FUZZER_DEFINE_RUN_DRIVER", "1");
+        #[cfg(feature = "sancov_stack_depth")]
+        libfuzzer.define("SANCOV_STACK_DEPTH", "1");
 
         libfuzzer.compile("libfuzzer");
     }
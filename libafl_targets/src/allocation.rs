@@ -0,0 +1,270 @@
+//! Allocator interception hooks for allocation observers.
+//!
+//! With the `allocation_hooks` feature, `malloc`, `calloc`, `realloc` and `free` are replaced by hooks forwarding
+//! to the glibc allocator. While an [`AllocationObserver`] observes a run, the hooks count the allocations, and keep
+//! track of the peak of live bytes and the largest single allocation. With an OOM limit, an allocation larger than
+//! the limit, or pushing the live bytes over it, aborts the run, which the crash handler reports as
+//! [`libafl::executors::ExitKind::Oom`]:
+//!
+//! ```rust,ignore
+//! let allocation_observer = AllocationObserver::new("allocations").with_oom_limit(2 << 30);
+//! let mut objective = feedback_or_fast!(CrashFeedback::new(), OomFeedback::new());
+//! ```
+//!
+//! As the hooks replace the allocator of the target, they can not be used with sanitizers bringing their own
+//! allocator, such as ASan; use the `libfuzzer_oom` hooks there. Aligned allocations are not counted.
+
+use alloc::borrow::Cow;
+use core::{
+    ffi::c_void,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use libafl::{
+    executors::{hooks::inprocess::inprocess_report_oom, ExitKind},
+    observers::Observer,
+    Error,
+};
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+extern "C" {
+    fn __libc_malloc(size: usize) -> *mut c_void;
+    fn __libc_calloc(count: usize, size: usize) -> *mut c_void;
+    fn __libc_realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    fn __libc_free(ptr: *mut c_void);
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static OOMED: AtomicBool = AtomicBool::new(false);
+/// The OOM limit in bytes, `0` for none
+static OOM_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static FREES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+static MAX_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The allocations of a run, as counted by the allocation hooks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocationStats {
+    /// The number of allocations, including reallocations
+    pub allocations: usize,
+    /// The number of frees
+    pub frees: usize,
+    /// The peak of live bytes
+    pub peak_bytes: usize,
+    /// The size of the largest single allocation
+    pub max_allocation: usize,
+}
+
+/// The allocations counted since the last run started
+#[must_use]
+pub fn allocation_stats() -> AllocationStats {
+    AllocationStats {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        frees: FREES.load(Ordering::Relaxed),
+        peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        max_allocation: MAX_ALLOCATION.load(Ordering::Relaxed),
+    }
+}
+
+/// Aborts the run as OOM, once
+fn oom() {
+    if !OOMED.swap(true, Ordering::Relaxed) {
+        inprocess_report_oom();
+        unsafe {
+            // we need to kill the process in a way that immediately triggers the crash handler
+            libc::raise(libc::SIGABRT);
+        }
+    }
+}
+
+/// Checks a requested allocation against the OOM limit, before it is made
+fn check_request(size: usize) {
+    let limit = OOM_LIMIT.load(Ordering::Relaxed);
+    if RUNNING.load(Ordering::Relaxed) && limit != 0 && size > limit {
+        oom();
+    }
+}
+
+/// Counts the allocation at `ptr`
+fn record_allocation(ptr: *mut c_void) {
+    if ptr.is_null() || !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    let size = unsafe { libc::malloc_usable_size(ptr) };
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    MAX_ALLOCATION.fetch_max(size, Ordering::Relaxed);
+    let live = LIVE_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_BYTES.fetch_max(live, Ordering::Relaxed);
+
+    let limit = OOM_LIMIT.load(Ordering::Relaxed);
+    if limit != 0 && live > limit {
+        oom();
+    }
+}
+
+/// Counts the free of `size` bytes
+fn record_free(size: usize) {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    FREES.fetch_add(1, Ordering::Relaxed);
+    // the allocation may predate the run
+    LIVE_BYTES
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+            Some(live.saturating_sub(size))
+        })
+        .expect("must complete successfully");
+}
+
+/// `malloc` hook, see the [module-level docs](self)
+///
+/// # Safety
+/// Same as `malloc`.
+#[no_mangle]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    check_request(size);
+    let ptr = unsafe { __libc_malloc(size) };
+    record_allocation(ptr);
+    ptr
+}
+
+/// `calloc` hook, see the [module-level docs](self)
+///
+/// # Safety
+/// Same as `calloc`.
+#[no_mangle]
+pub unsafe extern "C" fn calloc(count: usize, size: usize) -> *mut c_void {
+    check_request(count.saturating_mul(size));
+    let ptr = unsafe { __libc_calloc(count, size) };
+    record_allocation(ptr);
+    ptr
+}
+
+/// `realloc` hook, see the [module-level docs](self)
+///
+/// # Safety
+/// Same as `realloc`.
+#[no_mangle]
+pub unsafe extern "C" fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    check_request(size);
+    let old_size = if ptr.is_null() {
+        0
+    } else {
+        unsafe { libc::malloc_usable_size(ptr) }
+    };
+    let new_ptr = unsafe { __libc_realloc(ptr, size) };
+    // on failure, the old allocation is still live
+    if !new_ptr.is_null() || size == 0 {
+        if !ptr.is_null() {
+            record_free(old_size);
+        }
+        record_allocation(new_ptr);
+    }
+    new_ptr
+}
+
+/// `free` hook, see the [module-level docs](self)
+///
+/// # Safety
+/// Same as `free`.
+#[no_mangle]
+pub unsafe extern "C" fn free(ptr: *mut c_void) {
+    if !ptr.is_null() {
+        record_free(unsafe { libc::malloc_usable_size(ptr) });
+    }
+    unsafe { __libc_free(ptr) }
+}
+
+/// An observer of the allocations of each run, see the [module-level docs](self)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationObserver {
+    name: Cow<'static, str>,
+    oom_limit: Option<usize>,
+    stats: AllocationStats,
+    oomed: bool,
+}
+
+impl AllocationObserver {
+    /// Creates a new [`AllocationObserver`] with the given name, without an OOM limit
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            oom_limit: None,
+            stats: AllocationStats::default(),
+            oomed: false,
+        }
+    }
+
+    /// Aborts runs allocating more than `oom_limit` bytes at once, or in total, as OOM
+    #[must_use]
+    pub fn with_oom_limit(mut self, oom_limit: usize) -> Self {
+        self.oom_limit = Some(oom_limit);
+        self
+    }
+
+    /// The allocations of the last run
+    #[must_use]
+    pub fn stats(&self) -> &AllocationStats {
+        &self.stats
+    }
+
+    /// If the last run hit the OOM limit
+    #[must_use]
+    pub fn oomed(&self) -> bool {
+        self.oomed
+    }
+}
+
+impl Named for AllocationObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for AllocationObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        for counter in [
+            &ALLOCATIONS,
+            &FREES,
+            &LIVE_BYTES,
+            &PEAK_BYTES,
+            &MAX_ALLOCATION,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        OOM_LIMIT.store(self.oom_limit.unwrap_or_default(), Ordering::Relaxed);
+        OOMED.store(false, Ordering::Relaxed);
+        RUNNING.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        RUNNING.store(false, Ordering::Relaxed);
+        self.stats = allocation_stats();
+        self.oomed = OOMED.load(Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.pre_exec(state, input)
+    }
+
+    fn post_exec_child(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.post_exec(state, input, exit_kind)
+    }
+}
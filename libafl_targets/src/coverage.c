@@ -11,6 +11,14 @@ typedef uint32_t prev_loc_t;
 extern uint8_t __afl_area_ptr_local[EDGES_MAP_ALLOCATED_SIZE];
 uint8_t       *__afl_area_ptr = __afl_area_ptr_local;
 
+// The number of edges instrumented by the LTO mode of libafl_cc, which
+// overrides it
+#if defined(__linux__) || defined(__APPLE__)
+EXT_VAR(__afl_final_loc, uint32_t) = 0;
+#else
+uint32_t __afl_final_loc = 0;
+#endif
+
 extern uint8_t __ddg_area_ptr_local[DDG_MAP_SIZE];
 uint8_t       *__ddg_area_ptr = __ddg_area_ptr_local;
 
@@ -3,8 +3,7 @@
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 use alloc::borrow::Cow;
@@ -48,6 +47,9 @@ extern "C" {
     /// The area pointer points to the accounting mem operations map.
     pub static mut __afl_acc_memop_ptr: *mut u32;
 
+    /// The number of edges instrumented by the LTO mode of `libafl_cc`, `0` for other targets
+    pub static __afl_final_loc: u32;
+
     /// Start of libafl token section
     #[cfg(any(target_os = "linux", target_vendor = "apple"))]
     pub static __token_start: *const u8;
@@ -76,6 +78,18 @@ pub fn autotokens() -> Result<Tokens, Error> {
     }
 }
 
+/// The number of edges of a target instrumented by the LTO mode of `libafl_cc`, with collision-free edge IDs.
+///
+/// The LTO mode stores it in the target at link time, so the edges map can be sized exactly, e.g., with
+/// `MAX_EDGES_FOUND`.
+#[must_use]
+pub fn lto_edges_num() -> Option<usize> {
+    match unsafe { __afl_final_loc } {
+        0 => None,
+        num => Some(num as usize),
+    }
+}
+
 /// The actual size we use for the map of edges.
 /// This is used for forkserver backend
 #[allow(non_upper_case_globals)]
@@ -85,16 +99,14 @@ pub static mut __afl_map_size: usize = EDGES_MAP_DEFAULT_SIZE;
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 use libafl::observers::StdMapObserver;
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 use libafl_bolts::ownedref::OwnedMutSlice;
@@ -110,8 +122,7 @@ use libafl_bolts::ownedref::OwnedMutSlice;
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 pub unsafe fn edges_map_mut_slice<'a>() -> OwnedMutSlice<'a, u8> {
@@ -148,8 +159,7 @@ pub unsafe fn edges_map_mut_slice<'a>() -> OwnedMutSlice<'a, u8> {
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 pub unsafe fn std_edges_map_observer<'a, S>(name: S) -> StdMapObserver<'a, u8, false>
@@ -178,8 +188,7 @@ pub fn edges_map_mut_ptr() -> *mut u8 {
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 #[must_use]
@@ -187,6 +196,8 @@ pub fn edges_max_num() -> usize {
     unsafe {
         if MAX_EDGES_FOUND > 0 {
             MAX_EDGES_FOUND
+        } else if let Some(lto_edges_num) = lto_edges_num() {
+            lto_edges_num
         } else {
             #[cfg(feature = "pointer_maps")]
             {
@@ -0,0 +1,267 @@
+//! Symbolization of the covered map entries, and coverage reports.
+//!
+//! The entries of the edges map, or of the flattened `8-bit-counters` maps, are mapped to the address of their
+//! block through the [PC tables](crate::sancov_pc_table), and symbolized in-process with the debug info of the
+//! target. This needs a target built with `-fsanitize-coverage=pc-table`, and plain edge coverage, as the map
+//! indices of n-gram or context-sensitive coverage are hashes.
+//!
+//! A [`CoverageReport`] lists the covered locations of the history map of a `MapFeedback`, and the coverage of
+//! each function, as JSON or HTML:
+//!
+//! ```rust,ignore
+//! let report = CoverageReport::from_state(fuzzer.state(), "edges")?;
+//! report.write_json("coverage.json")?;
+//! report.write_html("coverage.html")?;
+//! ```
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+use std::{fs, path::Path};
+
+use libafl::{feedbacks::MapFeedbackMetadata, Error, HasNamedMetadata};
+use serde::{Deserialize, Serialize};
+
+use crate::sancov_pc_table::{sanitizer_cov_pc_table, sanitizer_cov_pc_table_entry};
+
+/// The source location of an entry of the coverage map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// The index of the entry in the coverage map
+    pub index: usize,
+    /// The address of the block of the entry
+    pub pc: usize,
+    /// The function containing the block, if it could be symbolized
+    pub function: Option<String>,
+    /// The source file of the block, if the target has debug info
+    pub file: Option<String>,
+    /// The source line of the block, if the target has debug info
+    pub line: Option<u32>,
+}
+
+/// Symbolizes `pc` with the debug info of the current process, as function, file and line
+fn symbolize_pc(pc: usize) -> (Option<String>, Option<String>, Option<u32>) {
+    let mut location = (None, None, None);
+    backtrace::resolve(pc as *mut _, |symbol| {
+        if location.0.is_none() {
+            location = (
+                symbol.name().map(|name| name.to_string()),
+                symbol
+                    .filename()
+                    .map(|file| file.to_string_lossy().into_owned()),
+                symbol.lineno(),
+            );
+        }
+    });
+    location
+}
+
+/// Returns the [`SourceLocation`] of the entry at `index` in the coverage map, or `None` if there is no PC table
+/// entry for it.
+#[must_use]
+pub fn symbolize_map_index(index: usize) -> Option<SourceLocation> {
+    let pc = sanitizer_cov_pc_table_entry(index)?.addr();
+    let (function, file, line) = symbolize_pc(pc);
+    Some(SourceLocation {
+        index,
+        pc,
+        function,
+        file,
+        line,
+    })
+}
+
+/// The coverage of a single function in a [`CoverageReport`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionCoverage {
+    /// The name of the function, or its address if it could not be symbolized
+    pub name: String,
+    /// The source file of the function, if the target has debug info
+    pub file: Option<String>,
+    /// The number of covered entries of the function
+    pub covered: usize,
+    /// The number of entries of the function
+    pub total: usize,
+}
+
+/// A report of the covered entries of a coverage map, see the [module-level docs](self)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// The number of covered entries with a PC table entry
+    pub covered: usize,
+    /// The number of entries with a PC table entry
+    pub total: usize,
+    /// The coverage of each function, in the order of the PC tables
+    pub functions: Vec<FunctionCoverage>,
+    /// The locations of the covered entries
+    pub locations: Vec<SourceLocation>,
+}
+
+impl CoverageReport {
+    /// Creates a report of the entries of `history_map` that are not `initial`
+    ///
+    /// Only the function entries and covered entries are symbolized, the blocks of a function follow its entry in
+    /// the PC tables.
+    #[must_use]
+    pub fn from_history_map<T>(history_map: &[T], initial: T) -> Self
+    where
+        T: PartialEq + Copy,
+    {
+        let mut report = Self::default();
+        for (index, entry) in sanitizer_cov_pc_table().flatten().enumerate() {
+            if entry.is_function_entry() || report.functions.is_empty() {
+                let (name, file, _) = symbolize_pc(entry.addr());
+                report.functions.push(FunctionCoverage {
+                    name: name.unwrap_or_else(|| format!("{:#x}", entry.addr())),
+                    file,
+                    covered: 0,
+                    total: 0,
+                });
+            }
+            let function = report.functions.last_mut().unwrap();
+            function.total += 1;
+            report.total += 1;
+
+            if history_map
+                .get(index)
+                .is_some_and(|value| *value != initial)
+            {
+                function.covered += 1;
+                report.covered += 1;
+                let (_, file, line) = symbolize_pc(entry.addr());
+                report.locations.push(SourceLocation {
+                    index,
+                    pc: entry.addr(),
+                    function: Some(function.name.clone()),
+                    file,
+                    line,
+                });
+            }
+        }
+        report
+    }
+
+    /// Creates a report of the history map of the `MapFeedback` called `name`
+    pub fn from_state<S>(state: &S, name: &str) -> Result<Self, Error>
+    where
+        S: HasNamedMetadata,
+    {
+        let metadata = state.named_metadata::<MapFeedbackMetadata<u8>>(name)?;
+        Ok(Self::from_history_map(&metadata.history_map, 0))
+    }
+
+    /// The report as JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| {
+            Error::serialize(format!("Could not serialize the coverage report: {err}"))
+        })
+    }
+
+    /// The report as a standalone HTML page, with a table of the functions and a table of the covered locations
+    #[must_use]
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage report</title>\n\
+             <style>body{font-family:sans-serif}table{border-collapse:collapse}\
+             td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n</head>\n<body>\n",
+        );
+        let _ = writeln!(
+            html,
+            "<h1>Coverage report</h1>\n<p>{} of {} entries covered ({:.2}%)</p>",
+            self.covered,
+            self.total,
+            percentage(self.covered, self.total)
+        );
+
+        html.push_str("<h2>Functions</h2>\n<table>\n<tr><th>Function</th><th>File</th><th>Covered</th></tr>\n");
+        for function in &self.functions {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}/{} ({:.2}%)</td></tr>",
+                escape_html(&function.name),
+                escape_html(function.file.as_deref().unwrap_or_default()),
+                function.covered,
+                function.total,
+                percentage(function.covered, function.total)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Covered locations</h2>\n<table>\n<tr><th>Index</th><th>Address</th><th>Function</th><th>Location</th></tr>\n");
+        for location in &self.locations {
+            let source = match (&location.file, location.line) {
+                (Some(file), Some(line)) => format!("{file}:{line}"),
+                (Some(file), None) => file.clone(),
+                _ => String::new(),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{:#x}</td><td>{}</td><td>{}</td></tr>",
+                location.index,
+                location.pc,
+                escape_html(location.function.as_deref().unwrap_or_default()),
+                escape_html(&source)
+            );
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+
+    /// Writes the report as JSON to `path`
+    pub fn write_json<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Writes the report as HTML to `path`
+    pub fn write_html<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_html())?;
+        Ok(())
+    }
+}
+
+/// `part` of `total` in percent
+#[allow(clippy::cast_precision_loss)]
+fn percentage(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Escapes the HTML special characters of `text`
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_html;
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("std::vector<int>::at & \"x\""),
+            "std::vector&lt;int&gt;::at &amp; &quot;x&quot;"
+        );
+    }
+}
@@ -66,6 +66,7 @@ int __afl_sharedmem_fuzzing __attribute__((weak));
 
 extern uint8_t *__afl_area_ptr;
 extern size_t   __afl_map_size;
+extern uint32_t __afl_final_loc;
 extern uint8_t *__token_start;
 extern uint8_t *__token_stop;
 
@@ -113,6 +114,9 @@ void __afl_map_shm(void) {
   if (already_initialized_shm) return;
   already_initialized_shm = 1;
 
+  // The exact number of edges of a target built in LTO mode
+  if (__afl_final_loc) { __afl_map_size = __afl_final_loc; }
+
   char *id_str = getenv(SHM_ENV_VAR);
 
   if (id_str) {
@@ -54,20 +54,47 @@ include!(concat!(env!("OUT_DIR"), "/constants.rs"));
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 pub mod sancov_pcguard;
 #[cfg(any(
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
     feature = "sancov_ctx"
 ))]
 pub use sancov_pcguard::*;
 
+#[cfg(any(
+    feature = "sancov_pcguard_edges",
+    feature = "sancov_pcguard_hitcounts",
+    feature = "sancov_ngram",
+    feature = "sancov_ctx",
+    feature = "sancov_8bit"
+))]
+pub mod sancov_pc_table;
+#[cfg(any(
+    feature = "sancov_pcguard_edges",
+    feature = "sancov_pcguard_hitcounts",
+    feature = "sancov_ngram",
+    feature = "sancov_ctx",
+    feature = "sancov_8bit"
+))]
+pub use sancov_pc_table::*;
+
+#[cfg(all(
+    feature = "coverage_report",
+    any(
+        feature = "sancov_pcguard_edges",
+        feature = "sancov_pcguard_hitcounts",
+        feature = "sancov_ngram",
+        feature = "sancov_ctx",
+        feature = "sancov_8bit"
+    )
+))]
+pub mod coverage_report;
+
 #[cfg(any(feature = "sancov_cmplog", feature = "sancov_value_profile"))]
 pub mod sancov_cmp;
 #[cfg(any(feature = "sancov_cmplog", feature = "sancov_value_profile"))]
@@ -101,6 +128,16 @@ pub mod sancov_8bit;
 #[cfg(feature = "sancov_8bit")]
 pub use sancov_8bit::*;
 
+#[cfg(feature = "sancov_stack_depth")]
+pub mod sancov_stack_depth;
+#[cfg(feature = "sancov_stack_depth")]
+pub use sancov_stack_depth::*;
+
+#[cfg(all(feature = "allocation_hooks", target_os = "linux", target_env = "gnu"))]
+pub mod allocation;
+#[cfg(all(feature = "allocation_hooks", target_os = "linux", target_env = "gnu"))]
+pub use allocation::*;
+
 #[cfg(feature = "coverage")]
 pub mod coverage;
 #[cfg(feature = "coverage")]
@@ -66,10 +66,11 @@ int main(int argc, char **argv) {
 
 #pragma GCC diagnostic pop
 
-// take a page out of libfuzzer's book: static define __sancov_lowest_stack
-// since we don't support it yet
-// TODO support it
+#ifndef SANCOV_STACK_DEPTH
+// take a page out of libfuzzer's book: static define __sancov_lowest_stack,
+// unless the `sancov_stack_depth` feature defines and uses it
 MAYBE_THREAD_LOCAL uintptr_t __sancov_lowest_stack;
+#endif
 
 EXPORT_FN int libafl_targets_has_libfuzzer_init() {
   return CHECK_WEAK_FN(LLVMFuzzerInitialize);
@@ -1,6 +1,12 @@
 //! [`LLVM` `8-bit-counters`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-pcs-with-guards) runtime for `LibAFL`.
+//!
+//! Targets built with `-fsanitize-coverage=inline-8bit-counters,pc-table`, the default of libFuzzer, register their
+//! counters in the [`COUNTERS_MAPS`], which take the place of the edges map, e.g., with the
+//! [`counters_edges_observer`]. The entries of the PC tables match the flattened counters, see
+//! [`crate::sancov_pc_table::sanitizer_cov_pc_table_entry`].
 use alloc::vec::Vec;
 
+use libafl::observers::MultiMapObserver;
 use libafl_bolts::{ownedref::OwnedMutSlice, AsSlice, AsSliceMut};
 
 /// A [`Vec`] of `8-bit-counters` maps for multiple modules.
@@ -35,6 +41,16 @@ pub unsafe fn extra_counters() -> Vec<OwnedMutSlice<'static, u8>> {
         .collect()
 }
 
+/// Gets a new [`MultiMapObserver`] over all the [`COUNTERS_MAPS`], as the edges map of targets instrumented with
+/// `inline-8bit-counters` instead of `trace-pc-guard`.
+///
+/// # Safety
+/// The observer aliases the [`COUNTERS_MAPS`], written by the target while it runs.
+#[must_use]
+pub unsafe fn counters_edges_observer(name: &'static str) -> MultiMapObserver<'static, u8, false> {
+    MultiMapObserver::new(name, extra_counters())
+}
+
 /// Initialize the sancov `8-bit-counters` - usually called by `llvm`.
 ///
 /// # Safety
@@ -14,7 +14,7 @@
 #ifdef SANCOV_VALUE_PROFILE
   #define SANCOV_VALUE_PROFILE_CALL(k, arg_size, arg1, arg2, arg1_is_const) \
     k &= CMP_MAP_SIZE - 1; \
-    __libafl_targets_value_profile1(k, arg1, arg2);
+    __libafl_targets_value_profile##arg_size(k, arg1, arg2);
 #else
   #define SANCOV_VALUE_PROFILE_CALL(k, arg_size, arg1, arg2, arg1_is_const)
 #endif
@@ -0,0 +1,86 @@
+//! [`LLVM` `pc-table`](https://clang.llvm.org/docs/SanitizerCoverage.html#pc-table) runtime for `LibAFL`.
+//!
+//! With `-fsanitize-coverage=pc-table`, each module registers a table with the address of each instrumented block
+//! through `__sanitizer_cov_pcs_init`. The tables have one entry per guard or `8-bit-counter`, in the same order,
+//! so they can be used to symbolize the entries of the edges map.
+
+use alloc::vec::Vec;
+use core::{mem::align_of, slice};
+
+static mut PC_TABLES: Vec<&'static [PcTableEntry]> = Vec::new();
+
+#[no_mangle]
+unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *const usize) {
+    // "The Unsafe Code Guidelines also notably defines that usize and isize are respectively compatible with uintptr_t and intptr_t defined in C."
+    let len = pcs_end.offset_from(pcs_beg);
+    let Ok(len) = usize::try_from(len) else {
+        panic!("Invalid PC Table bounds - start: {pcs_beg:x?} end: {pcs_end:x?}")
+    };
+    assert_eq!(
+        len % 2,
+        0,
+        "PC Table size is not evens - start: {pcs_beg:x?} end: {pcs_end:x?}"
+    );
+    assert_eq!(
+        (pcs_beg as usize) % align_of::<PcTableEntry>(),
+        0,
+        "Unaligned PC Table - start: {pcs_beg:x?} end: {pcs_end:x?}"
+    );
+
+    let pc_tables_ptr = &raw mut PC_TABLES;
+    let pc_tables = &mut *pc_tables_ptr;
+    // Each entry is a pair of `usize`, the address and the flags
+    pc_tables.push(slice::from_raw_parts(
+        pcs_beg as *const PcTableEntry,
+        len / 2,
+    ));
+}
+
+/// An entry to the `sanitizer_cov` `pc_table`
+#[repr(C, packed)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PcTableEntry {
+    addr: usize,
+    flags: usize,
+}
+
+impl PcTableEntry {
+    /// Returns whether the PC corresponds to a function entry point.
+    #[must_use]
+    pub fn is_function_entry(&self) -> bool {
+        self.flags == 0x1
+    }
+
+    /// Returns the address associated with this PC.
+    #[must_use]
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+}
+
+/// Returns an iterator over the PC tables. If no tables were registered, this will be empty.
+pub fn sanitizer_cov_pc_table<'a>() -> impl Iterator<Item = &'a [PcTableEntry]> {
+    // SAFETY: Once PCS_BEG and PCS_END have been initialized, will not be written to again. So
+    // there's no TOCTOU issue.
+    unsafe {
+        let pc_tables_ptr = &raw const PC_TABLES;
+        let pc_tables = &*pc_tables_ptr;
+        pc_tables.iter().copied()
+    }
+}
+
+/// Returns the [`PcTableEntry`] of the `index`-th entry over all PC tables, in the order the modules were
+/// registered.
+///
+/// This is the same order as the guards of the edges map, or the flattened `8-bit-counters` maps, so `index` may
+/// be the index of an entry in one of those maps.
+#[must_use]
+pub fn sanitizer_cov_pc_table_entry<'a>(mut index: usize) -> Option<&'a PcTableEntry> {
+    for table in sanitizer_cov_pc_table() {
+        if index < table.len() {
+            return Some(&table[index]);
+        }
+        index -= table.len();
+    }
+    None
+}
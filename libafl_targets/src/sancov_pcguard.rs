@@ -1,32 +1,28 @@
 //! [`LLVM` `PcGuard`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-pcs-with-guards) runtime for `LibAFL`.
 
-#[rustversion::nightly]
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-use core::simd::num::SimdUint;
-use core::{mem::align_of, slice};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+#[cfg(unix)]
+use core::ffi::CStr;
+use core::{ffi::c_void, ops::Range};
 
-#[cfg(any(
-    feature = "sancov_ngram4",
-    feature = "sancov_ctx",
-    feature = "sancov_ngram8"
-))]
+#[cfg(any(feature = "sancov_ngram", feature = "sancov_ctx"))]
 use libafl::executors::{hooks::ExecutorHook, HasObservers};
+use libafl::observers::{MultiMapObserver, StdMapObserver};
+use libafl_bolts::ownedref::OwnedMutSlice;
 
 #[cfg(any(
     feature = "pointer_maps",
     feature = "sancov_pcguard_edges",
     feature = "sancov_pcguard_hitcounts",
     feature = "sancov_ctx",
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
+    feature = "sancov_ngram",
 ))]
 use crate::coverage::EDGES_MAP;
-use crate::coverage::MAX_EDGES_FOUND;
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[allow(unused)]
-use crate::EDGES_MAP_DEFAULT_SIZE;
+use crate::coverage::{edges_map_mut_ptr, MAX_EDGES_FOUND};
 #[cfg(feature = "pointer_maps")]
 use crate::{coverage::EDGES_MAP_PTR, EDGES_MAP_ALLOCATED_SIZE};
+#[cfg(feature = "sancov_ngram")]
+use crate::{EDGES_MAP_DEFAULT_SIZE, NGRAM_SIZE};
 
 #[cfg(all(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts"))]
 #[cfg(not(any(doc, feature = "clippy")))]
@@ -34,51 +30,18 @@ compile_error!(
     "the libafl_targets `sancov_pcguard_edges` and `sancov_pcguard_hitcounts` features are mutually exclusive."
 );
 
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[allow(unused)]
-use core::ops::ShlAssign;
-
-#[cfg(feature = "sancov_ngram4")]
-#[rustversion::nightly]
-type Ngram4 = core::simd::u32x4;
-
-#[cfg(feature = "sancov_ngram8")]
-#[rustversion::nightly]
-type Ngram8 = core::simd::u32x8;
+/// The previous locs, most recent first. This is required for n-gram instrumentation, see [`NGRAM_SIZE`].
+#[cfg(feature = "sancov_ngram")]
+pub static mut PREV_ARRAY: [u32; NGRAM_SIZE] = [0; NGRAM_SIZE];
 
-/// The array holding the previous locs. This is required for NGRAM-4 instrumentation
-#[cfg(feature = "sancov_ngram4")]
-#[rustversion::nightly]
-pub static mut PREV_ARRAY_4: Ngram4 = Ngram4::from_array([0, 0, 0, 0]);
+/// The modules that registered their guards, see [`guard_modules`]
+static mut GUARD_MODULES: Vec<GuardModule> = Vec::new();
 
-/// The array holding the previous locs. This is required for NGRAM-4 instrumentation
-#[cfg(feature = "sancov_ngram8")]
-#[rustversion::nightly]
-pub static mut PREV_ARRAY_8: Ngram8 = Ngram8::from_array([0, 0, 0, 0, 0, 0, 0, 0]);
-
-/// We shift each of the values in ngram4 everytime we see new edges
-#[cfg(feature = "sancov_ngram4")]
-#[rustversion::nightly]
-pub static SHR_4: Ngram4 = Ngram4::from_array([1, 1, 1, 1]);
-
-/// We shift each of the values in ngram8 everytime we see new edges
-#[cfg(feature = "sancov_ngram8")]
-#[rustversion::nightly]
-pub static SHR_8: Ngram8 = Ngram8::from_array([1, 1, 1, 1, 1, 1, 1, 1]);
-
-static mut PC_TABLES: Vec<&'static [PcTableEntry]> = Vec::new();
-
-use alloc::vec::Vec;
-#[cfg(any(
-    feature = "sancov_ngram4",
-    feature = "sancov_ngram8",
-    feature = "sancov_ctx"
-))]
+#[cfg(any(feature = "sancov_ngram", feature = "sancov_ctx"))]
 use core::marker::PhantomData;
 
 /// The hook to initialize ngram everytime we run the harness
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[rustversion::nightly]
+#[cfg(feature = "sancov_ngram")]
 #[derive(Debug, Clone, Copy)]
 pub struct NgramHook<S>
 where
@@ -118,29 +81,21 @@ where
     }
 }
 
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[rustversion::nightly]
+#[cfg(feature = "sancov_ngram")]
 impl<S> ExecutorHook<S> for NgramHook<S>
 where
     S: libafl::inputs::UsesInput,
 {
     fn init<E: HasObservers>(&mut self, _state: &mut S) {}
     fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) {
-        #[cfg(feature = "sancov_ngram4")]
         unsafe {
-            PREV_ARRAY_4 = Ngram4::from_array([0, 0, 0, 0]);
-        }
-
-        #[cfg(feature = "sancov_ngram8")]
-        unsafe {
-            PREV_ARRAY_8 = Ngram8::from_array([0, 0, 0, 0, 0, 0, 0, 0]);
+            PREV_ARRAY = [0; NGRAM_SIZE];
         }
     }
     fn post_exec(&mut self, _state: &mut S, _input: &S::Input) {}
 }
 
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[rustversion::nightly]
+#[cfg(feature = "sancov_ngram")]
 impl<S> NgramHook<S>
 where
     S: libafl::inputs::UsesInput,
@@ -154,8 +109,7 @@ where
     }
 }
 
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-#[rustversion::nightly]
+#[cfg(feature = "sancov_ngram")]
 impl<S> Default for NgramHook<S>
 where
     S: libafl::inputs::UsesInput,
@@ -179,38 +133,22 @@ where
     fn post_exec(&mut self, _state: &mut S, _input: &S::Input) {}
 }
 
-#[rustversion::nightly]
-#[allow(unused)]
+/// Shifts `pos` into the previous locs, and hashes the last [`NGRAM_SIZE`] locs into an index into the map.
+///
+/// Older locs are shifted further left, so that the same locs in a different order hash differently.
 #[inline]
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+#[cfg(feature = "sancov_ngram")]
+#[allow(clippy::cast_possible_truncation)]
 unsafe fn update_ngram(pos: usize) -> usize {
-    let mut reduced = pos;
-    #[cfg(feature = "sancov_ngram4")]
-    {
-        let prev_array_4_ptr = &raw mut PREV_ARRAY_4;
-        let prev_array_4 = &mut *prev_array_4_ptr;
-        *prev_array_4 = prev_array_4.rotate_elements_right::<1>();
-        prev_array_4.shl_assign(SHR_4);
-        prev_array_4.as_mut_array()[0] = pos as u32;
-        reduced = prev_array_4.reduce_xor() as usize;
-    }
-    #[cfg(feature = "sancov_ngram8")]
-    {
-        let prev_array_8_ptr = &raw mut PREV_ARRAY_8;
-        let prev_array_8 = &mut *prev_array_8_ptr;
-        *prev_array_8 = prev_array_8.rotate_elements_right::<1>();
-        prev_array_8.shl_assign(SHR_8);
-        prev_array_8.as_mut_array()[0] = pos as u32;
-        reduced = prev_array_8.reduce_xor() as usize;
+    let prev_array_ptr = &raw mut PREV_ARRAY;
+    let prev_array = &mut *prev_array_ptr;
+    prev_array.copy_within(..NGRAM_SIZE - 1, 1);
+    for prev in prev_array.iter_mut() {
+        *prev <<= 1;
     }
-    reduced %= EDGES_MAP_DEFAULT_SIZE;
-    reduced
-}
-
-#[rustversion::not(nightly)]
-#[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
-unsafe fn update_ngram(pos: usize) -> usize {
-    pos
+    prev_array[0] = pos as u32;
+    let reduced = prev_array.iter().fold(0, |reduced, prev| reduced ^ prev) as usize;
+    reduced % EDGES_MAP_DEFAULT_SIZE
 }
 
 extern "C" {
@@ -229,7 +167,7 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
     #[allow(unused_mut)]
     let mut pos = *guard as usize;
 
-    #[cfg(any(feature = "sancov_ngram4", feature = "sancov_ngram8"))]
+    #[cfg(feature = "sancov_ngram")]
     {
         pos = update_ngram(pos);
         // println!("Wrinting to {} {}", pos, EDGES_MAP_DEFAULT_SIZE);
@@ -286,6 +224,8 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
         return;
     }
 
+    let first_guard = start;
+    let first_edge = MAX_EDGES_FOUND;
     while start < stop {
         *start = MAX_EDGES_FOUND as u32;
         start = start.offset(1);
@@ -302,60 +242,106 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard_init(mut start: *mut u32
             assert!((MAX_EDGES_FOUND <= edges_map_len), "The number of edges reported by SanitizerCoverage exceed the size of the edges map ({edges_map_len}). Use the LIBAFL_EDGES_MAP_DEFAULT_SIZE env to increase it at compile time.");
         }
     }
-}
 
-#[no_mangle]
-unsafe extern "C" fn __sanitizer_cov_pcs_init(pcs_beg: *const usize, pcs_end: *const usize) {
-    // "The Unsafe Code Guidelines also notably defines that usize and isize are respectively compatible with uintptr_t and intptr_t defined in C."
-    let len = pcs_end.offset_from(pcs_beg);
-    let Ok(len) = usize::try_from(len) else {
-        panic!("Invalid PC Table bounds - start: {pcs_beg:x?} end: {pcs_end:x?}")
-    };
-    assert_eq!(
-        len % 2,
-        0,
-        "PC Table size is not evens - start: {pcs_beg:x?} end: {pcs_end:x?}"
-    );
-    assert_eq!(
-        (pcs_beg as usize) % align_of::<PcTableEntry>(),
-        0,
-        "Unaligned PC Table - start: {pcs_beg:x?} end: {pcs_end:x?}"
-    );
-
-    let pc_tables_ptr = &raw mut PC_TABLES;
-    let pc_tables = &mut *pc_tables_ptr;
-    pc_tables.push(slice::from_raw_parts(pcs_beg as *const PcTableEntry, len));
+    // with `pointer_maps`, the edges may have wrapped around
+    if first_edge < MAX_EDGES_FOUND {
+        let guard_modules_ptr = &raw mut GUARD_MODULES;
+        let guard_modules = &mut *guard_modules_ptr;
+        guard_modules.push(GuardModule {
+            path: module_path(first_guard.cast()),
+            edges: first_edge..MAX_EDGES_FOUND,
+        });
+    }
 }
 
-/// An entry to the `sanitizer_cov` `pc_table`
-#[repr(C, packed)]
-#[derive(Debug, PartialEq, Eq)]
-pub struct PcTableEntry {
-    addr: usize,
-    flags: usize,
+/// A module that registered its guards, with the entries of its edges in the edges map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuardModule {
+    /// The path of the module, or its index if it could not be determined
+    pub path: String,
+    /// The entries of the edges of the module in the edges map
+    pub edges: Range<usize>,
 }
 
-impl PcTableEntry {
-    /// Returns whether the PC corresponds to a function entry point.
+impl GuardModule {
+    /// If the module is called `module`, or its path ends with `module`
     #[must_use]
-    pub fn is_function_entry(&self) -> bool {
-        self.flags == 0x1
+    pub fn matches(&self, module: &str) -> bool {
+        self.path == module
+            || self
+                .path
+                .strip_suffix(module)
+                .is_some_and(|prefix| prefix.ends_with('/') || prefix.ends_with('\\'))
     }
+}
 
-    /// Returns the address associated with this PC.
-    #[must_use]
-    pub fn addr(&self) -> usize {
-        self.addr
+/// The path of the module containing `addr`
+#[cfg(unix)]
+fn module_path(addr: *const c_void) -> String {
+    let mut info: libc::Dl_info = unsafe { core::mem::zeroed() };
+    if unsafe { libc::dladdr(addr, &raw mut info) } != 0 && !info.dli_fname.is_null() {
+        unsafe { CStr::from_ptr(info.dli_fname) }
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        format!("module{}", guard_modules().len())
     }
 }
 
-/// Returns an iterator over the PC tables. If no tables were registered, this will be empty.
-pub fn sanitizer_cov_pc_table<'a>() -> impl Iterator<Item = &'a [PcTableEntry]> {
-    // SAFETY: Once PCS_BEG and PCS_END have been initialized, will not be written to again. So
-    // there's no TOCTOU issue.
+/// The path of the module containing `addr`
+#[cfg(not(unix))]
+fn module_path(_addr: *const c_void) -> String {
+    format!("module{}", guard_modules().len())
+}
+
+/// Returns the modules that registered their guards so far, in the order of their edges in the edges map.
+///
+/// The modules only partition the edges map for plain edge coverage, not for n-gram or context-sensitive coverage.
+#[must_use]
+pub fn guard_modules<'a>() -> &'a [GuardModule] {
+    // SAFETY: The modules are only registered by their constructors, before the fuzzer runs.
     unsafe {
-        let pc_tables_ptr = &raw const PC_TABLES;
-        let pc_tables = &*pc_tables_ptr;
-        pc_tables.iter().copied()
+        let guard_modules_ptr = &raw const GUARD_MODULES;
+        (*guard_modules_ptr).as_slice()
     }
 }
+
+/// Gets a new [`StdMapObserver`] over the edges of `module` only, see [`GuardModule::matches`], or `None` if it did
+/// not register its guards.
+///
+/// # Safety
+/// The observer aliases the edges map, like the [`crate::std_edges_map_observer`].
+pub unsafe fn module_edges_observer<S>(
+    name: S,
+    module: &str,
+) -> Option<StdMapObserver<'static, u8, false>>
+where
+    S: Into<Cow<'static, str>>,
+{
+    let module = guard_modules().iter().find(|m| m.matches(module))?;
+    Some(StdMapObserver::from_mut_ptr(
+        name,
+        edges_map_mut_ptr().add(module.edges.start),
+        module.edges.len(),
+    ))
+}
+
+/// Gets a new [`MultiMapObserver`] over the edges of all modules, except the `excluded` ones, see
+/// [`GuardModule::matches`], e.g., to ignore the coverage of instrumented system libraries.
+///
+/// # Safety
+/// The observer aliases the edges map, like the [`crate::std_edges_map_observer`].
+#[must_use]
+pub unsafe fn edges_observer_excluding(
+    name: &'static str,
+    excluded: &[&str],
+) -> MultiMapObserver<'static, u8, false> {
+    let maps = guard_modules()
+        .iter()
+        .filter(|m| !excluded.iter().any(|module| m.matches(module)))
+        .map(|m| {
+            OwnedMutSlice::from_raw_parts_mut(edges_map_mut_ptr().add(m.edges.start), m.edges.len())
+        })
+        .collect();
+    MultiMapObserver::new(name, maps)
+}
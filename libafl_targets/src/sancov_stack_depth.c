@@ -0,0 +1,20 @@
+#include "common.h"
+
+// Updated by the `-fsanitize-coverage=stack-depth` instrumentation with the
+// lowest stack pointer seen in the thread
+MAYBE_THREAD_LOCAL uintptr_t __sancov_lowest_stack;
+
+// The stack pointer at the last reset, the depth is measured from there
+static MAYBE_THREAD_LOCAL uintptr_t libafl_initial_stack;
+
+EXPORT_FN void libafl_targets_stack_depth_reset(void) {
+  int stack;
+  libafl_initial_stack = (uintptr_t)&stack;
+  __sancov_lowest_stack = libafl_initial_stack;
+}
+
+EXPORT_FN uintptr_t libafl_targets_stack_depth(void) {
+  // The stack grows down
+  if (__sancov_lowest_stack >= libafl_initial_stack) { return 0; }
+  return libafl_initial_stack - __sancov_lowest_stack;
+}
@@ -0,0 +1,264 @@
+//! [`LLVM` `stack-depth`](https://clang.llvm.org/docs/SanitizerCoverage.html#tracing-stack-depth) runtime for `LibAFL`.
+//!
+//! Targets built with `-fsanitize-coverage=stack-depth` keep the lowest stack pointer of each thread in
+//! `__sancov_lowest_stack`. The [`StackDepthObserver`] measures the stack depth of each run from there, and the
+//! [`StackDepthFeedback`] turns deep recursion into novelty, like libFuzzer, or into an objective past a limit:
+//!
+//! ```rust,ignore
+//! let stack_depth_observer = StackDepthObserver::new("stack_depth");
+//! let mut feedback = feedback_or!(
+//!     MaxMapFeedback::new(&edges_observer),
+//!     StackDepthFeedback::new(&stack_depth_observer),
+//! );
+//! let mut objective = feedback_or_fast!(
+//!     CrashFeedback::new(),
+//!     StackDepthFeedback::new(&stack_depth_observer).with_limit(512 * 1024),
+//! );
+//! ```
+
+use alloc::borrow::Cow;
+
+use libafl::{
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::Observer,
+    Error, HasNamedMetadata,
+};
+use libafl_bolts::{
+    impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+extern "C" {
+    fn libafl_targets_stack_depth_reset();
+    fn libafl_targets_stack_depth() -> usize;
+}
+
+/// Resets the lowest stack pointer of the current thread to the current one, starting a new measurement.
+pub fn stack_depth_reset() {
+    unsafe { libafl_targets_stack_depth_reset() }
+}
+
+/// The maximum stack depth of the current thread since the last [`stack_depth_reset`], in bytes.
+#[must_use]
+pub fn stack_depth() -> usize {
+    unsafe { libafl_targets_stack_depth() }
+}
+
+/// Maps a stack depth to a coarse step, so that only significantly deeper stacks count as new.
+///
+/// The same step function as libFuzzer: exact below 8, then 8 steps for each power of two.
+#[must_use]
+pub fn stack_depth_step(depth: usize) -> usize {
+    if depth < 8 {
+        return depth;
+    }
+    let log2 = (usize::BITS - 1 - depth.leading_zeros()) as usize - 3;
+    (log2 + 1) * 8 + ((depth >> log2) & 7)
+}
+
+/// An observer of the maximum stack depth of each run, see the [module-level docs](self)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackDepthObserver {
+    name: Cow<'static, str>,
+    depth: usize,
+}
+
+impl StackDepthObserver {
+    /// Creates a new [`StackDepthObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            depth: 0,
+        }
+    }
+
+    /// The maximum stack depth of the last run, in bytes
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Named for StackDepthObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for StackDepthObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.depth = 0;
+        stack_depth_reset();
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.depth = stack_depth();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.pre_exec(state, input)
+    }
+
+    fn post_exec_child(
+        &mut self,
+        state: &mut S,
+        input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.post_exec(state, input, exit_kind)
+    }
+}
+
+/// The deepest [`stack_depth_step`] seen by a [`StackDepthFeedback`] so far
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StackDepthMetadata {
+    /// The deepest step of an interesting input
+    pub max_step: usize,
+}
+
+impl_serdeany!(StackDepthMetadata);
+
+/// A feedback on the stack depth measured by a [`StackDepthObserver`], see the [module-level docs](self).
+///
+/// Without a limit, a run is interesting if its stack got deeper than that of all interesting runs before, by at
+/// least one [`stack_depth_step`]. With a limit, a run is interesting if its stack got deeper than the limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackDepthFeedback {
+    observer_handle: Handle<StackDepthObserver>,
+    limit: Option<usize>,
+    step: Option<usize>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+}
+
+impl StackDepthFeedback {
+    /// Creates a new [`StackDepthFeedback`], interesting for new maximum stack depths
+    #[must_use]
+    pub fn new(observer: &StackDepthObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            limit: None,
+            step: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Only considers runs with a stack deeper than `limit` bytes interesting, e.g., as an objective
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl Named for StackDepthFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl<S> StateInitializer<S> for StackDepthFeedback
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        if self.limit.is_none() {
+            state.add_named_metadata(self.name(), StackDepthMetadata::default());
+        }
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for StackDepthFeedback
+where
+    OT: MatchNameRef,
+    S: HasNamedMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let depth = observers
+            .get(&self.observer_handle)
+            .expect("A StackDepthFeedback needs a StackDepthObserver")
+            .depth();
+        let interesting = if let Some(limit) = self.limit {
+            depth > limit
+        } else {
+            let step = stack_depth_step(depth);
+            self.step = Some(step);
+            step > state
+                .named_metadata::<StackDepthMetadata>(self.name())?
+                .max_step
+        };
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(interesting);
+        }
+        Ok(interesting)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if let Some(step) = self.step.take() {
+            let metadata = state.named_metadata_mut::<StackDepthMetadata>(self.name())?;
+            metadata.max_step = metadata.max_step.max(step);
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.step = None;
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result
+            .ok_or_else(|| Error::illegal_state("last_result called before Feedback was run"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::stack_depth_step;
+
+    #[test]
+    fn test_stack_depth_step() {
+        assert_eq!(stack_depth_step(0), 0);
+        assert_eq!(stack_depth_step(7), 7);
+        assert_eq!(stack_depth_step(8), 8);
+        assert_eq!(stack_depth_step(15), 15);
+        assert_eq!(stack_depth_step(16), 16);
+        assert_eq!(stack_depth_step(31), 23);
+        // Monotonic
+        let mut last = 0;
+        for depth in 0..0x10000 {
+            let step = stack_depth_step(depth);
+            assert!(step >= last);
+            last = step;
+        }
+    }
+}
@@ -9,6 +9,9 @@
 
 extern uint8_t libafl_cmp_map[CMP_MAP_SIZE];
 
+// The value profile of a comparison is the number of matching bits of its operands, the highest one seen for each
+// comparison site is kept. The operands are promoted to int, so the negation is cast back to their width.
+
 #ifdef _MSC_VER
   #include <intrin.h>
   #define __builtin_popcount __popcnt
@@ -18,25 +21,25 @@ extern uint8_t libafl_cmp_map[CMP_MAP_SIZE];
 static void __libafl_targets_value_profile1(uintptr_t k, uint8_t arg1,
                                             uint8_t arg2) {
   libafl_cmp_map[k] =
-      MAX(libafl_cmp_map[k], (__builtin_popcount(~(arg1 ^ arg2))));
+      MAX(libafl_cmp_map[k], (__builtin_popcount((uint8_t)~(arg1 ^ arg2))));
 }
 
 static void __libafl_targets_value_profile2(uintptr_t k, uint16_t arg1,
                                             uint16_t arg2) {
   libafl_cmp_map[k] =
-      MAX(libafl_cmp_map[k], (__builtin_popcount(~(arg1 ^ arg2))));
+      MAX(libafl_cmp_map[k], (__builtin_popcount((uint16_t)~(arg1 ^ arg2))));
 }
 
 static void __libafl_targets_value_profile4(uintptr_t k, uint32_t arg1,
                                             uint32_t arg2) {
   libafl_cmp_map[k] =
-      MAX(libafl_cmp_map[k], (__builtin_popcount(~(arg1 ^ arg2))));
+      MAX(libafl_cmp_map[k], (__builtin_popcount((uint32_t)~(arg1 ^ arg2))));
 }
 
 static void __libafl_targets_value_profile8(uintptr_t k, uint64_t arg1,
                                             uint64_t arg2) {
   libafl_cmp_map[k] =
-      MAX(libafl_cmp_map[k], (__builtin_popcountll(~(arg1 ^ arg2))));
+      MAX(libafl_cmp_map[k], (__builtin_popcountll((uint64_t)~(arg1 ^ arg2))));
 }
 
 #endif
@@ -1,4 +1,16 @@
 //! Value profile support for `LibAFL`
+//!
+//! With the `sancov_value_profile` feature, the `__sanitizer_cov_trace_cmp*`, `__sanitizer_cov_trace_const_cmp*`,
+//! and `__sanitizer_cov_trace_switch` callbacks keep the highest number of matching operand bits of each
+//! comparison site in the [`CMP_MAP`], alongside the cmplog map if `sancov_cmplog` is enabled as well.
+//! A `MaxMapFeedback` over the [`std_value_profile_observer`] rewards inputs getting closer to pass a comparison,
+//! like the value profile of libFuzzer.
+
+#[cfg(feature = "sancov_value_profile")]
+use alloc::borrow::Cow;
+
+#[cfg(feature = "sancov_value_profile")]
+use libafl::observers::StdMapObserver;
 
 use crate::CMP_MAP_SIZE;
 
@@ -8,19 +20,14 @@ pub static mut libafl_cmp_map: [u8; CMP_MAP_SIZE] = [0; CMP_MAP_SIZE];
 
 pub use libafl_cmp_map as CMP_MAP;
 
-/*
-extern {
-    #[link_name = "llvm.returnaddress"]
-    fn return_address() -> usize;
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn __sanitizer_cov_trace_cmp1(arg1: u8, arg2: u8) {
-    let mut pos = return_address();
-    pos = (pos >> 4) ^ (pos << 8);
-    pos &= CMP_MAP_SIZE - 1;
-    *CMP_MAP.get_unchecked_mut(pos) = core::cmp::max(*CMP_MAP.get_unchecked(pos), (!(arg1 ^ arg2)).count_ones() as u8);
+/// Gets a new [`StdMapObserver`] over the value profile map [`CMP_MAP`].
+///
+/// # Safety
+/// The observer takes a mutable reference to the global [`CMP_MAP`], filled by the target while it runs.
+#[cfg(feature = "sancov_value_profile")]
+pub unsafe fn std_value_profile_observer<'a, S>(name: S) -> StdMapObserver<'a, u8, false>
+where
+    S: Into<Cow<'static, str>>,
+{
+    StdMapObserver::from_mut_ptr(name, &raw mut CMP_MAP as *mut u8, CMP_MAP_SIZE)
 }
-*/
-
-// TODO complete when linking to LLVM intrinsic will land to stable Rust
@@ -15,12 +15,125 @@ use libafl_bolts::{
 };
 use tinyinst::tinyinst::{litecov::RunResult, TinyInst};
 
+/// The coverage `TinyInst` collects, see [`TinyInstExecutorBuilder::coverage_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverageType {
+    /// Basic block coverage, the `TinyInst` default
+    #[default]
+    BasicBlock,
+    /// Edge coverage
+    Edge,
+}
+
+impl CoverageType {
+    /// The value of the `-covtype` argument
+    #[must_use]
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            Self::BasicBlock => "bb",
+            Self::Edge => "edge",
+        }
+    }
+}
+
+/// The function `TinyInst` runs in a loop in persistent mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetFunction {
+    /// An exported function, by name
+    Method(String),
+    /// A function at the given offset from the start of the module
+    Offset(usize),
+}
+
+/// The persistent mode target of `TinyInst`, see [`TinyInstExecutorBuilder::persistent_target`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistentTarget {
+    module: String,
+    function: TargetFunction,
+    nargs: usize,
+    iterations: usize,
+    loop_function: bool,
+    calling_convention: Option<String>,
+}
+
+impl PersistentTarget {
+    /// Creates a new [`PersistentTarget`], the `function` with `nargs` arguments in `module`.
+    ///
+    /// By default, the target process restarts every 10000 iterations, and `TinyInst` loops the function itself.
+    #[must_use]
+    pub fn new(module: String, function: TargetFunction, nargs: usize) -> Self {
+        Self {
+            module,
+            function,
+            nargs,
+            iterations: 10000,
+            loop_function: true,
+            calling_convention: None,
+        }
+    }
+
+    /// Sets after how many iterations the target process restarts
+    #[must_use]
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets if `TinyInst` loops the function (`-loop`), or the target calls it repeatedly by itself
+    #[must_use]
+    pub fn loop_function(mut self, loop_function: bool) -> Self {
+        self.loop_function = loop_function;
+        self
+    }
+
+    /// Sets the calling convention of the function on 32-bit Windows, e.g., `stdcall`, `fastcall`, or `thiscall`
+    #[must_use]
+    pub fn calling_convention(mut self, calling_convention: String) -> Self {
+        self.calling_convention = Some(calling_convention);
+        self
+    }
+
+    /// Appends the `TinyInst` arguments for this target
+    fn push_args(&self, args: &mut Vec<String>) {
+        args.push("-target_module".to_string());
+        args.push(self.module.clone());
+
+        match &self.function {
+            TargetFunction::Method(method) => {
+                args.push("-target_method".to_string());
+                args.push(method.clone());
+            }
+            TargetFunction::Offset(offset) => {
+                args.push("-target_offset".to_string());
+                args.push(format!("{offset:#x}"));
+            }
+        }
+
+        args.push("-nargs".to_string());
+        args.push(self.nargs.to_string());
+
+        args.push("-iterations".to_string());
+        args.push(self.iterations.to_string());
+
+        args.push("-persist".to_string());
+        if self.loop_function {
+            args.push("-loop".to_string());
+        }
+        if let Some(calling_convention) = &self.calling_convention {
+            args.push("-callconv".to_string());
+            args.push(calling_convention.clone());
+        }
+    }
+}
+
 /// [`TinyInst`](https://github.com/googleprojectzero/TinyInst) executor
 pub struct TinyInstExecutor<S, SP, OT>
 where
     SP: ShMemProvider,
 {
     tinyinst: TinyInst,
+    tinyinst_args: Vec<String>,
+    program_args: Vec<String>,
     coverage_ptr: *mut Vec<u64>,
     timeout: Duration,
     observers: OT,
@@ -43,6 +156,8 @@ where
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("TinyInstExecutor")
+            .field("tinyinst_args", &self.tinyinst_args)
+            .field("program_args", &self.program_args)
             .field("timeout", &self.timeout)
             .finish_non_exhaustive()
     }
@@ -180,6 +295,37 @@ where
         self
     }
 
+    /// The kind of coverage to collect; `TinyInst` defaults to [`CoverageType::BasicBlock`].
+    #[must_use]
+    pub fn coverage_type(mut self, coverage_type: CoverageType) -> Self {
+        self.tinyinst_args.push("-covtype".to_string());
+        self.tinyinst_args.push(coverage_type.as_arg().to_string());
+        self
+    }
+
+    /// Also collect the coverage of the bytes matched by comparisons, to solve magic values
+    #[must_use]
+    pub fn cmp_coverage(mut self) -> Self {
+        self.tinyinst_args.push("-cmp_coverage".to_string());
+        self
+    }
+
+    /// Also instrument calls between the instrumented modules, for faster execution
+    #[must_use]
+    pub fn instrument_cross_module_calls(mut self) -> Self {
+        self.tinyinst_args
+            .push("-instrument_cross_module_calls".to_string());
+        self
+    }
+
+    /// Patch return addresses, for targets that inspect them, e.g., with C++ exceptions
+    #[must_use]
+    pub fn patch_return_addresses(mut self) -> Self {
+        self.tinyinst_args
+            .push("-patch_return_addresses".to_string());
+        self
+    }
+
     /// Use shmem
     #[must_use]
     pub fn use_shmem(mut self) -> Self {
@@ -191,26 +337,22 @@ where
     /// Persistent mode
     #[must_use]
     pub fn persistent(
-        mut self,
+        self,
         target_module: String,
         target_method: String,
         nargs: usize,
         iterations: usize,
     ) -> Self {
-        self.tinyinst_args.push("-target_module".to_string());
-        self.tinyinst_args.push(target_module);
-
-        self.tinyinst_args.push("-target_method".to_string());
-        self.tinyinst_args.push(target_method);
-
-        self.tinyinst_args.push("-nargs".to_string());
-        self.tinyinst_args.push(nargs.to_string());
-
-        self.tinyinst_args.push("-iterations".to_string());
-        self.tinyinst_args.push(iterations.to_string());
+        self.persistent_target(
+            PersistentTarget::new(target_module, TargetFunction::Method(target_method), nargs)
+                .iterations(iterations),
+        )
+    }
 
-        self.tinyinst_args.push("-persist".to_string());
-        self.tinyinst_args.push("-loop".to_string());
+    /// Persistent mode, with full control over the target function
+    #[must_use]
+    pub fn persistent_target(mut self, target: PersistentTarget) -> Self {
+        target.push_args(&mut self.tinyinst_args);
         self
     }
 
@@ -306,6 +448,8 @@ where
 
         Ok(TinyInstExecutor {
             tinyinst,
+            tinyinst_args: self.tinyinst_args.clone(),
+            program_args,
             coverage_ptr: self.coverage_ptr,
             timeout: self.timeout,
             observers,
@@ -316,6 +460,54 @@ where
     }
 }
 
+impl<S, SP, OT> TinyInstExecutor<S, SP, OT>
+where
+    SP: ShMemProvider,
+{
+    /// The modules instrumented by `TinyInst`
+    #[must_use]
+    pub fn instrumented_modules(&self) -> Vec<&str> {
+        self.tinyinst_args
+            .windows(2)
+            .filter(|pair| pair[0] == "-instrument_module")
+            .map(|pair| pair[1].as_str())
+            .collect()
+    }
+
+    /// Replaces the instrumented modules, e.g., to focus on a library once the fuzzer found it is reached.
+    ///
+    /// This restarts `TinyInst` and the target, and clears the coverage vec.
+    pub fn set_instrumented_modules(&mut self, modules: Vec<String>) {
+        let mut args = Vec::with_capacity(self.tinyinst_args.len());
+        let mut old_args = self.tinyinst_args.drain(..);
+        while let Some(arg) = old_args.next() {
+            if arg == "-instrument_module" {
+                old_args.next();
+            } else {
+                args.push(arg);
+            }
+        }
+        drop(old_args);
+        for module in modules {
+            args.push("-instrument_module".to_string());
+            args.push(module);
+        }
+        self.tinyinst_args = args;
+        log::info!("tinyinst args: {:#?}", &self.tinyinst_args);
+
+        self.tinyinst = unsafe {
+            TinyInst::new(
+                &self.tinyinst_args,
+                &self.program_args,
+                self.timeout.as_millis() as u32,
+            )
+        };
+        unsafe {
+            self.coverage_ptr.as_mut().unwrap().clear();
+        }
+    }
+}
+
 impl<S, SP, OT> HasObservers for TinyInstExecutor<S, SP, OT>
 where
     S: State,
@@ -0,0 +1,32 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use libafl::observers::CrashState;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[command(
+    name = "crash_state_printer",
+    about,
+    long_about = "Prints the registers and memory of `.crashstate` files, written by the CrashStateFeedback"
+)]
+pub struct Opt {
+    #[arg(help = "Crash state files to print", required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let opts = Opt::parse();
+
+    let mut code = ExitCode::SUCCESS;
+    for input in opts.inputs {
+        match CrashState::from_file(&input) {
+            Ok(state) => println!("{}:\n{state}", input.display()),
+            Err(err) => {
+                eprintln!("Could not read crash state {input:?}: {err:?}");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    code
+}